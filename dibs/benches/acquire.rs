@@ -0,0 +1,68 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use dibs::predicate::{ComparisonOperator, Predicate, Value};
+use dibs::{Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use std::time::Duration;
+
+fn dibs() -> Dibs {
+    let templates = vec![RequestTemplate::new(
+        0,
+        [1, 2].iter().cloned().collect(),
+        [3].iter().cloned().collect(),
+        Predicate::conjunction(vec![
+            Predicate::comparison(ComparisonOperator::Eq, 0, 0),
+            Predicate::comparison(ComparisonOperator::Eq, 1, 1),
+        ]),
+    )];
+
+    Dibs::new(
+        &[Some(0)],
+        &templates,
+        OptimizationLevel::Filtered,
+        None,
+        None,
+        Duration::from_secs(60),
+    )
+}
+
+fn arguments(i: usize) -> Vec<Value> {
+    vec![Value::Integer(i), Value::String(format!("{:0>15}", i))]
+}
+
+fn bench_acquire(c: &mut Criterion) {
+    let dibs = dibs();
+    let mut i = 0;
+
+    c.bench_function("acquire", |b| {
+        b.iter_batched(
+            || {
+                i += 1;
+                (i, arguments(i))
+            },
+            |(i, arguments)| {
+                let mut transaction = Transaction::new(i, i);
+                dibs.acquire(&mut transaction, 0, arguments).unwrap();
+                transaction.commit();
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    c.bench_function("acquire_borrowed", |b| {
+        b.iter_batched(
+            || {
+                i += 1;
+                (i, arguments(i))
+            },
+            |(i, arguments)| {
+                let mut transaction = Transaction::new(i, i);
+                dibs.acquire_borrowed(&mut transaction, 0, &arguments)
+                    .unwrap();
+                transaction.commit();
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, bench_acquire);
+criterion_main!(benches);
@@ -0,0 +1,65 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use dibs::predicate::{ComparisonOperator, Predicate, Value};
+use dibs::{Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use std::time::Duration;
+
+const REQUESTS_PER_TRANSACTION: usize = 8;
+
+fn dibs() -> Dibs {
+    // Reads never conflict, so every request lands in the table's only bucket without waiting.
+    let templates = vec![RequestTemplate::new(
+        0,
+        [1].iter().cloned().collect(),
+        Default::default(),
+        Predicate::comparison(ComparisonOperator::Eq, 0, 0),
+    )];
+
+    Dibs::new(
+        &[None],
+        &templates,
+        OptimizationLevel::Prepared,
+        None,
+        None,
+        Duration::from_secs(60),
+    )
+}
+
+fn acquire(dibs: &Dibs, transaction_id: usize, num_requests: usize) -> Transaction {
+    let mut transaction = Transaction::new(transaction_id, transaction_id);
+
+    for i in 0..num_requests {
+        dibs.acquire(&mut transaction, 0, vec![Value::Integer(i)])
+            .unwrap();
+    }
+
+    transaction
+}
+
+fn bench_commit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("commit");
+
+    // Long-running transactions hold requests in the bucket while short ones come and go.
+    for &num_held in &[0, 1000, 10000] {
+        let dibs = dibs();
+        let held = acquire(&dibs, 0, num_held);
+        let mut i = 0;
+
+        group.bench_with_input(BenchmarkId::from_parameter(num_held), &num_held, |b, _| {
+            b.iter_batched(
+                || {
+                    i += 1;
+                    acquire(&dibs, i, REQUESTS_PER_TRANSACTION)
+                },
+                Transaction::commit,
+                BatchSize::SmallInput,
+            )
+        });
+
+        held.commit();
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_commit);
+criterion_main!(benches);
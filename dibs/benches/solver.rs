@@ -0,0 +1,198 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use dibs::internals::{self, Program};
+use dibs::predicate::{ComparisonOperator, Predicate, Value};
+
+// Each shape compares a predicate with itself, on arguments that don't conflict but that the
+// solver can only rule out by checking every comparison.
+struct Shape {
+    name: &'static str,
+    predicate: Predicate,
+    p_args: Vec<Value>,
+    q_args: Vec<Value>,
+}
+
+fn integers(values: &[usize]) -> Vec<Value> {
+    values.iter().map(|&v| Value::Integer(v)).collect()
+}
+
+fn shapes() -> Vec<Shape> {
+    vec![
+        Shape {
+            name: "point",
+            predicate: Predicate::comparison(ComparisonOperator::Eq, 0, 0),
+            p_args: integers(&[0]),
+            q_args: integers(&[1]),
+        },
+        Shape {
+            name: "range",
+            predicate: Predicate::conjunction(vec![
+                Predicate::comparison(ComparisonOperator::Ge, 0, 0),
+                Predicate::comparison(ComparisonOperator::Le, 0, 1),
+                Predicate::comparison(ComparisonOperator::Ge, 1, 2),
+                Predicate::comparison(ComparisonOperator::Le, 1, 3),
+            ]),
+            p_args: integers(&[0, 10, 0, 10]),
+            q_args: integers(&[5, 15, 20, 30]),
+        },
+        Shape {
+            name: "disjunction",
+            predicate: Predicate::disjunction(
+                (0..8)
+                    .map(|i| Predicate::comparison(ComparisonOperator::Eq, 0, i))
+                    .collect(),
+            ),
+            p_args: integers(&[0, 1, 2, 3, 4, 5, 6, 7]),
+            q_args: integers(&[8, 9, 10, 11, 12, 13, 14, 15]),
+        },
+    ]
+}
+
+// A conjunction of `n` disjunctions of two equalities each, which normalizes to 2^n conjunctions.
+// The arguments overlap on every column but the last.
+fn blowup_shape(n: usize) -> Shape {
+    let predicate = Predicate::conjunction(
+        (0..n)
+            .map(|i| {
+                Predicate::disjunction(vec![
+                    Predicate::comparison(ComparisonOperator::Eq, i, 2 * i),
+                    Predicate::comparison(ComparisonOperator::Eq, i, 2 * i + 1),
+                ])
+            })
+            .collect(),
+    );
+
+    let p_args = (0..2 * n).collect::<Vec<_>>();
+    let mut q_args = p_args.clone();
+    q_args[2 * n - 2] += 1000;
+    q_args[2 * n - 1] += 1000;
+
+    Shape {
+        name: "blowup",
+        predicate,
+        p_args: integers(&p_args),
+        q_args: integers(&q_args),
+    }
+}
+
+fn normalized(predicate: &Predicate) -> Predicate {
+    let mut predicate = predicate.clone();
+    predicate.normalize();
+    predicate
+}
+
+fn bench_prepare(c: &mut Criterion) {
+    let mut group = c.benchmark_group("prepare");
+
+    for shape in shapes() {
+        group.bench_function(shape.name, |b| {
+            b.iter(|| internals::prepare(black_box(&shape.predicate), black_box(&shape.predicate)))
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_evaluate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("evaluate");
+
+    for shape in shapes() {
+        let program = Program::compile(&internals::prepare(&shape.predicate, &shape.predicate));
+
+        group.bench_function(shape.name, |b| {
+            b.iter(|| program.evaluate(black_box(&shape.p_args), black_box(&shape.q_args)))
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_solve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solve");
+
+    for shape in shapes() {
+        let predicate = normalized(&shape.predicate);
+
+        group.bench_function(BenchmarkId::new("dnf", shape.name), |b| {
+            b.iter(|| {
+                internals::solve_dnf(
+                    &predicate,
+                    black_box(&shape.p_args),
+                    &predicate,
+                    black_box(&shape.q_args),
+                )
+            })
+        });
+
+        group.bench_function(BenchmarkId::new("clustered", shape.name), |b| {
+            b.iter(|| {
+                internals::solve_clustered(
+                    &shape.predicate,
+                    black_box(&shape.p_args),
+                    &shape.predicate,
+                    black_box(&shape.q_args),
+                )
+            })
+        });
+
+        // What grouped tables do, with the clusters prepared along with the templates.
+        let clusters = internals::prepare_clusters(&shape.predicate, &shape.predicate);
+
+        group.bench_function(BenchmarkId::new("cached", shape.name), |b| {
+            b.iter(|| {
+                internals::solve_clusters(
+                    &clusters,
+                    black_box(&shape.p_args),
+                    black_box(&shape.q_args),
+                )
+            })
+        });
+    }
+
+    group.finish();
+}
+
+// Unprepared requests on an ungrouped table are solved in DNF if their predicates normalize to
+// fewer conjunctions than the blowup limit, and clustered otherwise. Comparing the two as the
+// blowup grows shows where the limit should sit.
+fn bench_blowup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("blowup");
+
+    for n in 1..=7 {
+        let shape = blowup_shape(n);
+        let blowup = internals::dnf_blowup(&shape.predicate);
+        let predicate = normalized(&shape.predicate);
+
+        group.bench_with_input(BenchmarkId::new("dnf", blowup), &shape, |b, shape| {
+            b.iter(|| {
+                internals::solve_dnf(
+                    &predicate,
+                    black_box(&shape.p_args),
+                    &predicate,
+                    black_box(&shape.q_args),
+                )
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("clustered", blowup), &shape, |b, shape| {
+            b.iter(|| {
+                internals::solve_clustered(
+                    &shape.predicate,
+                    black_box(&shape.p_args),
+                    &shape.predicate,
+                    black_box(&shape.q_args),
+                )
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_prepare,
+    bench_evaluate,
+    bench_solve,
+    bench_blowup
+);
+criterion_main!(benches);
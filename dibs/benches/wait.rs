@@ -0,0 +1,99 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use dibs::predicate::{ComparisonOperator, Predicate, Value};
+use dibs::{Dibs, OptimizationLevel, RequestTemplate, Transaction, WaitStrategy};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const NUM_THREADS: usize = 4;
+
+fn dibs(wait_strategy: WaitStrategy) -> Dibs {
+    let templates = vec![RequestTemplate::new(
+        0,
+        Default::default(),
+        [1].iter().cloned().collect(),
+        Predicate::comparison(ComparisonOperator::Eq, 0, 0),
+    )];
+
+    let mut dibs = Dibs::new(
+        &[Some(0)],
+        &templates,
+        OptimizationLevel::Filtered,
+        None,
+        None,
+        Duration::from_secs(60),
+    );
+
+    dibs.set_wait_strategy(wait_strategy);
+    dibs
+}
+
+fn run(dibs: &Arc<Dibs>, num_keys: usize, iters: u64) -> Duration {
+    let next_transaction_id = Arc::new(AtomicUsize::new(0));
+    let start = Instant::now();
+
+    let threads = (0..NUM_THREADS)
+        .map(|_| {
+            let dibs = Arc::clone(dibs);
+            let next_transaction_id = Arc::clone(&next_transaction_id);
+
+            thread::spawn(move || {
+                for i in 0..iters as usize / NUM_THREADS {
+                    let transaction_id = next_transaction_id.fetch_add(1, Ordering::Relaxed);
+                    let mut transaction = Transaction::new(transaction_id, transaction_id);
+
+                    dibs.acquire(&mut transaction, 0, vec![Value::Integer(i % num_keys)])
+                        .unwrap();
+
+                    transaction.commit();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    start.elapsed()
+}
+
+fn bench_wait(c: &mut Criterion) {
+    let strategies = [
+        ("park", WaitStrategy::Park),
+        (
+            "spin_then_park",
+            WaitStrategy::SpinThenPark {
+                spins: 100,
+                yields: 10,
+            },
+        ),
+        (
+            "backoff",
+            WaitStrategy::Backoff {
+                initial: Duration::from_micros(1),
+                max: Duration::from_micros(100),
+            },
+        ),
+    ];
+
+    let mut group = c.benchmark_group("wait");
+
+    for &num_keys in &[1, 16, 1024] {
+        for &(name, wait_strategy) in &strategies {
+            let dibs = Arc::new(dibs(wait_strategy));
+
+            group.bench_with_input(
+                BenchmarkId::new(name, num_keys),
+                &num_keys,
+                |b, &num_keys| b.iter_custom(|iters| run(&dibs, num_keys, iters)),
+            );
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_wait);
+criterion_main!(benches);
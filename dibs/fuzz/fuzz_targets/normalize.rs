@@ -0,0 +1,160 @@
+//! Fuzzes predicate normalization and conflict preparation. Run from `dibs` with:
+//!
+//! ```text
+//! cargo +nightly fuzz run normalize
+//! ```
+//!
+//! The input is read as two predicates in postfix order, like the nodes passed through the C
+//! interface, followed by the arguments of each. Condensing and normalizing a predicate must not
+//! change which rows satisfy it, and a prepared conflict must not be false while some row
+//! satisfies both predicates. Rows and arguments are small integers, so that the rows can be
+//! enumerated.
+
+#![no_main]
+
+use dibs::internals::{self, testing, Program};
+use dibs::predicate::{ComparisonOperator, Predicate, Value, MAX_DEPTH};
+use libfuzzer_sys::fuzz_target;
+
+const NUM_COLUMNS: usize = 3;
+const NUM_PARAMETERS: usize = 4;
+const DOMAIN: usize = 4;
+
+// The lock manager only normalizes predicates below the blowup limit, since normalization can
+// grow a predicate exponentially.
+const BLOWUP_LIMIT: usize = 64;
+
+const OPERATORS: [ComparisonOperator; 6] = [
+    ComparisonOperator::Eq,
+    ComparisonOperator::Ne,
+    ComparisonOperator::Lt,
+    ComparisonOperator::Le,
+    ComparisonOperator::Gt,
+    ComparisonOperator::Ge,
+];
+
+struct Input<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        let (&byte, rest) = self.data.split_first()?;
+        self.data = rest;
+        Some(byte)
+    }
+
+    /// Reads a predicate of up to 65536 nodes. The low bit of a node's first byte picks a
+    /// comparison or a connective. A comparison reads its operator, column and parameter from
+    /// the next byte, and a connective takes as many of the predicates before it as the rest of
+    /// the first byte asks for. Predicates left over at the end are joined in a conjunction.
+    /// Predicates nested deeper than the lock manager accepts are rejected before they are built,
+    /// since dropping them could overflow the stack.
+    fn predicate(&mut self) -> Option<Predicate> {
+        let num_nodes = usize::from(u16::from_le_bytes([self.byte()?, self.byte()?])) + 1;
+        let mut stack: Vec<(Predicate, usize)> = vec![];
+
+        for _ in 0..num_nodes {
+            let byte = self.byte()?;
+
+            if byte & 1 == 0 {
+                let operands = self.byte()?;
+
+                stack.push((
+                    Predicate::comparison(
+                        OPERATORS[usize::from(byte >> 1) % OPERATORS.len()],
+                        usize::from(operands & 0xf) % NUM_COLUMNS,
+                        usize::from(operands >> 4) % NUM_PARAMETERS,
+                    ),
+                    1,
+                ));
+            } else {
+                let num_operands = usize::from(byte >> 2).min(stack.len());
+                let (operands, depths): (Vec<_>, Vec<_>) =
+                    stack.drain(stack.len() - num_operands..).unzip();
+                let depth = depths.into_iter().max().unwrap_or(0) + 1;
+
+                if depth > MAX_DEPTH {
+                    return None;
+                }
+
+                if byte & 2 == 0 {
+                    stack.push((Predicate::conjunction(operands), depth));
+                } else {
+                    stack.push((Predicate::disjunction(operands), depth));
+                }
+            }
+        }
+
+        if stack.len() == 1 {
+            stack.pop().map(|(predicate, _)| predicate)
+        } else if stack.iter().all(|&(_, depth)| depth < MAX_DEPTH) {
+            Some(Predicate::conjunction(
+                stack.into_iter().map(|(predicate, _)| predicate).collect(),
+            ))
+        } else {
+            None
+        }
+    }
+
+    fn arguments(&mut self) -> Vec<Value> {
+        (0..NUM_PARAMETERS)
+            .map(|_| Value::Integer(usize::from(self.byte().unwrap_or(0)) % DOMAIN))
+            .collect()
+    }
+}
+
+fn assert_equivalent(p: &Predicate, q: &Predicate, arguments: &[Value], what: &str) {
+    for row in testing::rows(NUM_COLUMNS, DOMAIN) {
+        assert_eq!(
+            testing::satisfies(p, &row, arguments),
+            testing::satisfies(q, &row, arguments),
+            "{} changed whether {:?} satisfies\n{}\nwith {:?}, giving\n{}",
+            what,
+            row,
+            p,
+            arguments,
+            q,
+        );
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut input = Input { data };
+
+    let (p, q) = match (input.predicate(), input.predicate()) {
+        (Some(p), Some(q)) => (p, q),
+        _ => return,
+    };
+
+    let p_args = input.arguments();
+    let q_args = input.arguments();
+
+    let mut condensed = p.clone();
+    condensed.condense();
+    assert_equivalent(&p, &condensed, &p_args, "condensing");
+
+    if internals::dnf_blowup(&p) >= BLOWUP_LIMIT || internals::dnf_blowup(&q) >= BLOWUP_LIMIT {
+        return;
+    }
+
+    let mut normalized = p.clone();
+    normalized.normalize();
+    assert!(
+        normalized.is_normalized(),
+        "not normalized:\n{}",
+        normalized
+    );
+    assert_equivalent(&p, &normalized, &p_args, "normalizing");
+
+    let conflict = Program::compile(&internals::prepare(&p, &q)).evaluate(&p_args, &q_args);
+
+    if !conflict {
+        if let Some(row) = testing::shared_row(&p, &p_args, &q, &q_args, NUM_COLUMNS, DOMAIN) {
+            panic!(
+                "missed a conflict on {:?} between\n{}\nwith {:?} and\n{}\nwith {:?}",
+                row, p, p_args, q, q_args,
+            );
+        }
+    }
+});
@@ -0,0 +1,197 @@
+/*
+ * C interface to the dibs predicate lock manager, generated from src/ffi.rs by cbindgen. Don't edit
+ * it by hand; build dibs with the ffi feature to regenerate it.
+ */
+
+#ifndef DIBS_H
+#define DIBS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#define DIBS_OK 0
+
+#define DIBS_TIMEOUT 1
+
+#define DIBS_GROUP_CONFLICT 2
+
+#define DIBS_INVALID_ARGUMENTS 3
+
+#define DIBS_PREEMPTED 4
+
+#define DIBS_WAIT_BUDGET_EXHAUSTED 5
+
+#define DIBS_VALIDATION_FAILED 6
+
+#define DIBS_INVALID_TEMPLATE 7
+
+#define DIBS_OVER_CAPACITY 8
+
+#define DIBS_WOULD_BLOCK 9
+
+#define DIBS_DEADLINE_EXCEEDED 10
+
+#define DIBS_PANICKED 11
+
+#define DIBS_NODE_COMPARISON 0
+
+#define DIBS_NODE_CONJUNCTION 1
+
+#define DIBS_NODE_DISJUNCTION 2
+
+// The deepest a predicate may nest, counting a comparison as depth 1.
+#define DIBS_MAX_PREDICATE_DEPTH 128
+
+#define DIBS_EQ 0
+
+#define DIBS_NE 1
+
+#define DIBS_LT 2
+
+#define DIBS_LE 3
+
+#define DIBS_GT 4
+
+#define DIBS_GE 5
+
+#define DIBS_VALUE_BOOLEAN 0
+
+#define DIBS_VALUE_INTEGER 1
+
+#define DIBS_VALUE_STRING 2
+
+#define DIBS_VALUE_TIMESTAMP 3
+
+#define DIBS_VALUE_BYTES 4
+
+#define DIBS_UNGROUPED 0
+
+#define DIBS_GROUPED 1
+
+#define DIBS_PREPARED 2
+
+#define DIBS_FILTERED 3
+
+typedef struct Dibs Dibs;
+
+// Collects the tables and request templates of a lock manager.
+typedef struct DibsBuilder DibsBuilder;
+
+typedef struct DibsTransaction DibsTransaction;
+
+// One node of a predicate, which C code passes as an array of nodes in postfix order. A
+// comparison compares column `left` of the table to argument `right` using `op`, one of
+// `DIBS_EQ` to `DIBS_GE`. A conjunction or disjunction pops the last
+// `num_operands` predicates as its operands. The array must leave exactly one predicate, nested
+// at most `DIBS_MAX_PREDICATE_DEPTH` deep.
+typedef struct {
+  uint32_t kind;
+  uint32_t op;
+  size_t left;
+  size_t right;
+  size_t num_operands;
+} DibsPredicateNode;
+
+// An argument to a request. Booleans, integers and timestamps are held in `integer`, with
+// timestamps as signed microseconds since the Unix epoch. Strings, which must be UTF-8, and
+// bytes are borrowed from `data` for the duration of the call.
+typedef struct {
+  uint32_t kind;
+  uint64_t integer;
+  const uint8_t *data;
+  size_t len;
+} DibsValue;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Creates a builder for a lock manager over `num_tables` tables, none of them filtered.
+DibsBuilder *dibs_builder_new(size_t num_tables);
+
+// Partitions the requests on `table` by `column` when the lock manager is filtered. Returns
+// false if the table doesn't exist.
+//
+// # Safety
+//
+// `builder` must be a builder that hasn't been consumed.
+bool dibs_builder_set_filter(DibsBuilder *builder, size_t table, size_t column);
+
+// Adds a request template and returns its ID, which templates are numbered by in the order they
+// are added. Returns `SIZE_MAX` if the table doesn't exist or the predicate is malformed.
+//
+// # Safety
+//
+// `builder` must be a builder that hasn't been consumed, and each array must point to the given
+// number of elements.
+size_t dibs_register_template(DibsBuilder *builder,
+                              size_t table,
+                              const size_t *read_columns,
+                              size_t num_read_columns,
+                              const size_t *write_columns,
+                              size_t num_write_columns,
+                              const DibsPredicateNode *nodes,
+                              size_t num_nodes);
+
+// Consumes `builder` and creates a lock manager from it at `optimization`, one of
+// `DIBS_UNGROUPED` to `DIBS_FILTERED`. Acquires give up after waiting
+// `timeout_micros` for a conflicting request. Returns null, still consuming the builder, if the
+// optimization level is unknown.
+//
+// # Safety
+//
+// `builder` must be a builder that hasn't been consumed.
+Dibs *dibs_new(DibsBuilder *builder, uint32_t optimization, uint64_t timeout_micros);
+
+// Frees a lock manager.
+//
+// # Safety
+//
+// `dibs` must come from `dibs_new` and no thread may be using it. Transactions that acquired
+// requests on it may still be committed afterwards.
+void dibs_free(Dibs *dibs);
+
+// Starts a transaction. Transaction IDs must be unique among the transactions in flight, and
+// transactions that share a group ID never conflict with each other.
+DibsTransaction *dibs_transaction_new(size_t group_id, size_t transaction_id);
+
+// Acquires a request on the rows selected by template `template_id` with the given arguments,
+// waiting for conflicting requests to complete. Returns `DIBS_OK` or the reason it failed, after
+// which the transaction should be committed and retried.
+//
+// # Safety
+//
+// `dibs` must be a live lock manager, `transaction` a transaction that hasn't been committed and
+// isn't in use by another thread, and `arguments` must point to `num_arguments` values.
+int32_t dibs_acquire(const Dibs *dibs,
+                     DibsTransaction *transaction,
+                     size_t template_id,
+                     const DibsValue *arguments,
+                     size_t num_arguments);
+
+// Like `dibs_acquire`, but returns `DIBS_WOULD_BLOCK` rather than waiting if conflicting
+// requests are in flight. The request is then withdrawn, and the transaction may try again
+// later rather than being committed.
+//
+// # Safety
+//
+// As for `dibs_acquire`.
+int32_t dibs_try_acquire(const Dibs *dibs,
+                         DibsTransaction *transaction,
+                         size_t template_id,
+                         const DibsValue *arguments,
+                         size_t num_arguments);
+
+// Ends a transaction, releasing its requests and freeing it.
+//
+// # Safety
+//
+// `transaction` must come from `dibs_transaction_new` and not have been committed.
+void dibs_commit(DibsTransaction *transaction);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* DIBS_H */
@@ -0,0 +1,187 @@
+//! Switches tables between the prepared and filtered levels while requests are in flight.
+//!
+//! An adaptive table keeps its buckets in a layout that a switch replaces. The requests in the old
+//! layout stay there until their transactions commit, and requests acquired since the switch
+//! check the old layout's buckets as well as their own, since the requests in it never check the
+//! new one. A switch waits only for the acquires solving in the old layout, not for transactions.
+
+use crate::sync::{Arc, AtomicUsize, Ordering, RwLock};
+use crate::{Dibs, OptimizationLevel, TableBuckets};
+use std::time::Duration;
+
+/// The buckets of an adaptive table at one level.
+pub(crate) struct Layout {
+    pub(crate) optimization: OptimizationLevel,
+    pub(crate) buckets: Arc<TableBuckets>,
+    /// The buckets of the layout this one replaced, until the requests in them are gone.
+    pub(crate) retired: Option<Arc<TableBuckets>>,
+}
+
+pub(crate) struct AdaptiveTable {
+    pub(crate) layout: RwLock<Layout>,
+    num_acquires: AtomicUsize,
+    check_nanos: AtomicUsize,
+    wait_nanos: AtomicUsize,
+}
+
+impl AdaptiveTable {
+    pub(crate) fn new(optimization: OptimizationLevel, buckets: TableBuckets) -> AdaptiveTable {
+        AdaptiveTable {
+            layout: RwLock::new(Layout {
+                optimization,
+                buckets: Arc::new(buckets),
+                retired: None,
+            }),
+            num_acquires: AtomicUsize::new(0),
+            check_nanos: AtomicUsize::new(0),
+            wait_nanos: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn record_check(&self, checked: Duration) {
+        self.num_acquires.fetch_add(1, Ordering::Relaxed);
+        self.check_nanos
+            .fetch_add(checked.as_nanos() as usize, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_wait(&self, waited: Duration) {
+        self.wait_nanos
+            .fetch_add(waited.as_nanos() as usize, Ordering::Relaxed);
+    }
+
+    pub(crate) fn costs(&self) -> TableCosts {
+        TableCosts {
+            num_acquires: self.num_acquires.load(Ordering::Relaxed),
+            check_nanos: self.check_nanos.load(Ordering::Relaxed),
+            wait_nanos: self.wait_nanos.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The running costs of the acquires on an adaptive table: the time spent finding the conflicting
+/// requests, and the time spent waiting for them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TableCosts {
+    pub num_acquires: usize,
+    pub check_nanos: usize,
+    pub wait_nanos: usize,
+}
+
+impl TableCosts {
+    fn since(&self, earlier: &TableCosts) -> TableCosts {
+        TableCosts {
+            num_acquires: self.num_acquires - earlier.num_acquires,
+            check_nanos: self.check_nanos - earlier.check_nanos,
+            wait_nanos: self.wait_nanos - earlier.wait_nanos,
+        }
+    }
+
+    fn check_nanos_per_acquire(&self) -> f64 {
+        self.check_nanos as f64 / self.num_acquires as f64
+    }
+
+    fn check_share(&self) -> f64 {
+        self.check_nanos as f64 / (self.check_nanos + self.wait_nanos).max(1) as f64
+    }
+}
+
+struct TableState {
+    last: TableCosts,
+    /// The check time per acquire last measured at the prepared and filtered levels.
+    prepared_cost: Option<f64>,
+    filtered_cost: Option<f64>,
+}
+
+/// Decides, from the costs of each adaptive table since it last ran, whether to switch the table
+/// to the other level. A table whose acquires spend most of their time waiting is left alone,
+/// since finding conflicts faster wouldn't shorten the waits. Otherwise a table moves to whichever
+/// level it last found its conflicts faster at, trying the other level once if it hasn't yet.
+pub struct AdaptiveController {
+    min_check_share: f64,
+    min_acquires: usize,
+    tables: Vec<Option<TableState>>,
+    num_switches: usize,
+}
+
+impl AdaptiveController {
+    /// Creates a controller for the adaptive tables of `dibs`, which only considers a table whose
+    /// acquires spent at least `min_check_share` of their time checking for conflicts, over at
+    /// least `min_acquires` acquires since the controller last ran.
+    pub fn new(dibs: &Dibs, min_check_share: f64, min_acquires: usize) -> AdaptiveController {
+        AdaptiveController {
+            min_check_share,
+            min_acquires,
+            tables: (0..dibs.num_tables())
+                .map(|table| {
+                    dibs.table_costs(table).map(|last| TableState {
+                        last,
+                        prepared_cost: None,
+                        filtered_cost: None,
+                    })
+                })
+                .collect(),
+            num_switches: 0,
+        }
+    }
+
+    /// Measures each adaptive table's costs since the last call and switches the tables that
+    /// check for conflicts faster at the other level, returning the tables it switched.
+    pub fn tick(&mut self, dibs: &Dibs) -> Vec<usize> {
+        let mut switched = vec![];
+
+        for (table, state) in self.tables.iter_mut().enumerate() {
+            let state = match state {
+                Some(state) => state,
+                None => continue,
+            };
+
+            let costs = dibs.table_costs(table).unwrap();
+            let interval = costs.since(&state.last);
+
+            if interval.num_acquires < self.min_acquires {
+                continue;
+            }
+
+            state.last = costs;
+
+            let current = dibs.optimization(table);
+            let cost = interval.check_nanos_per_acquire();
+
+            let (own_cost, other_cost, other) = match current {
+                OptimizationLevel::Filtered => (
+                    &mut state.filtered_cost,
+                    state.prepared_cost,
+                    OptimizationLevel::Prepared,
+                ),
+                _ => (
+                    &mut state.prepared_cost,
+                    state.filtered_cost,
+                    OptimizationLevel::Filtered,
+                ),
+            };
+
+            *own_cost = Some(cost);
+
+            let switch = interval.check_share() >= self.min_check_share
+                && match other_cost {
+                    Some(other_cost) => other_cost < cost,
+                    None => true,
+                };
+
+            if !switch {
+                // Lets go of the buckets from before the last switch once they empty.
+                dibs.set_optimization(table, current);
+            } else if dibs.set_optimization(table, other) {
+                self.num_switches += 1;
+                switched.push(table);
+            }
+        }
+
+        switched
+    }
+
+    /// Returns how many times the controller has switched a table.
+    pub fn num_switches(&self) -> usize {
+        self.num_switches
+    }
+}
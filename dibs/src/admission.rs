@@ -0,0 +1,90 @@
+//! A cap on the number of transactions that hold requests at once.
+//!
+//! Past some number of concurrent transactions, adding more only lengthens the queues in the
+//! buckets, and transactions spend their time timing out on each other rather than committing. A
+//! transaction is admitted on its first acquire and holds its permit until it commits, so a
+//! transaction waiting for admission holds no requests and nothing waits on it.
+
+use crate::clock::Clock;
+use crate::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+pub(crate) struct Admission {
+    max_active: usize,
+    active: Mutex<usize>,
+    released: Condvar,
+}
+
+impl Admission {
+    pub(crate) fn new(max_active: usize) -> Admission {
+        assert!(max_active > 0, "at least one transaction must be admitted");
+
+        Admission {
+            max_active,
+            active: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Blocks until fewer than the maximum number of transactions are active, returning a permit
+    /// for the new one and how long it waited for it on `clock`, or `None` if it didn't have to
+    /// wait. If admission takes longer than `limit`, gives up and returns no permit.
+    pub(crate) fn admit(
+        admission: &Arc<Admission>,
+        clock: &dyn Clock,
+        limit: Option<Duration>,
+    ) -> (Option<Permit>, Option<Duration>) {
+        let mut active = admission.active.lock().unwrap();
+        let mut wait_start = None;
+        let deadline = limit.map(|limit| clock.now() + limit);
+
+        while *active >= admission.max_active {
+            let start = *wait_start.get_or_insert_with(|| clock.now());
+
+            active = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(clock.now());
+
+                    if remaining == Duration::default() {
+                        return (None, Some(clock.now().saturating_duration_since(start)));
+                    }
+
+                    let (active, _) = admission
+                        .released
+                        .wait_timeout(active, clock.poll_interval(remaining))
+                        .unwrap();
+
+                    active
+                }
+                None => admission.released.wait(active).unwrap(),
+            };
+        }
+
+        *active += 1;
+
+        let permit = Permit {
+            admission: Arc::clone(admission),
+        };
+
+        (
+            Some(permit),
+            wait_start.map(|start| clock.now().saturating_duration_since(start)),
+        )
+    }
+
+    pub(crate) fn num_active(&self) -> usize {
+        *self.active.lock().unwrap()
+    }
+}
+
+/// Counts its transaction as active until dropped.
+pub(crate) struct Permit {
+    admission: Arc<Admission>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        *self.admission.active.lock().unwrap() -= 1;
+        self.admission.released.notify_one();
+    }
+}
@@ -0,0 +1,165 @@
+//! Counting Bloom filters over the equality keys of the requests in a bucket.
+//!
+//! A template whose predicate fixes a column to a parameter, such as `id = ?0`, gives each of its
+//! requests a key: the column and the argument's hash. Every bucket counts its requests' keys in
+//! a filter, so that a point request arriving at a crowded bucket can tell that no request there
+//! touches its key without solving against each of them. Counters are decremented when requests
+//! are released, so the filter tracks the bucket's contents rather than its history.
+
+use crate::predicate::Value;
+use fnv::FnvHasher;
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+const NUM_COUNTERS: usize = 1024;
+const NUM_PROBES: u64 = 3;
+
+/// Feeds a value to a hasher such that equal values hash equally.
+pub fn hash_value<H: Hasher>(value: &Value, hasher: &mut H) {
+    mem::discriminant(value).hash(hasher);
+
+    match value {
+        Value::Boolean(b) => b.hash(hasher),
+        Value::Integer(i) => i.hash(hasher),
+        Value::String(s) => s.hash(hasher),
+        Value::Timestamp(t) => t.hash(hasher),
+        Value::Bytes(b) => b.hash(hasher),
+        Value::Decimal(d) => d.hash(hasher),
+    }
+}
+
+/// A column and the hash of the value that a request fixes it to, along with the parameter the
+/// value was bound to.
+#[derive(Clone, Copy, Debug)]
+pub struct Key {
+    column: usize,
+    parameter: usize,
+    hash: u64,
+}
+
+impl Key {
+    pub fn new(column: usize, parameter: usize, arguments: &[Value]) -> Key {
+        let mut hasher = FnvHasher::default();
+        column.hash(&mut hasher);
+        hash_value(&arguments[parameter], &mut hasher);
+
+        Key {
+            column,
+            parameter,
+            hash: hasher.finish(),
+        }
+    }
+
+    pub fn column(self) -> usize {
+        self.column
+    }
+
+    /// Returns the column and the hash of the value, which are equal for equal keys.
+    pub fn fingerprint(self) -> (usize, u64) {
+        (self.column, self.hash)
+    }
+
+    /// Returns the value the key fixes its column to, given the arguments it was created from.
+    pub fn value(self, arguments: &[Value]) -> &Value {
+        &arguments[self.parameter]
+    }
+
+    /// Returns a slot for this key in a table of `len` entries.
+    pub fn slot(self, len: usize) -> usize {
+        (self.hash % len as u64) as usize
+    }
+
+    fn probes(self) -> impl Iterator<Item = usize> {
+        // Double hashing derives every probe from the two halves of a single hash.
+        let (h1, h2) = (self.hash, (self.hash >> 32) | 1);
+        (0..NUM_PROBES)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % NUM_COUNTERS as u64) as usize)
+    }
+}
+
+/// The keys of the requests in a bucket. Requests without a key are only counted, since they may
+/// conflict with any request.
+#[derive(Debug, Default)]
+pub struct KeyFilter {
+    /// Allocated when the first key is inserted, so that buckets that never see one stay small.
+    counters: Vec<u8>,
+    /// The number of keyed requests on each column.
+    columns: Vec<(usize, usize)>,
+    num_unkeyed: usize,
+}
+
+impl KeyFilter {
+    pub fn insert(&mut self, key: Option<Key>) {
+        let key = match key {
+            Some(key) => key,
+            None => {
+                self.num_unkeyed += 1;
+                return;
+            }
+        };
+
+        if self.counters.is_empty() {
+            self.counters = vec![0; NUM_COUNTERS];
+        }
+
+        for probe in key.probes() {
+            // A saturated counter is never decremented, which can only cause false positives.
+            self.counters[probe] = self.counters[probe].saturating_add(1);
+        }
+
+        match self
+            .columns
+            .iter_mut()
+            .find(|(column, _)| *column == key.column)
+        {
+            Some((_, count)) => *count += 1,
+            None => self.columns.push((key.column, 1)),
+        }
+    }
+
+    pub fn remove(&mut self, key: Option<Key>) {
+        let key = match key {
+            Some(key) => key,
+            None => {
+                self.num_unkeyed -= 1;
+                return;
+            }
+        };
+
+        for probe in key.probes() {
+            if self.counters[probe] < u8::MAX {
+                self.counters[probe] -= 1;
+            }
+        }
+
+        if let Some(position) = self
+            .columns
+            .iter()
+            .position(|&(column, _)| column == key.column)
+        {
+            self.columns[position].1 -= 1;
+
+            if self.columns[position].1 == 0 {
+                self.columns.swap_remove(position);
+            }
+        }
+    }
+
+    /// Returns whether every request in the bucket is keyed on `column`, so that only requests
+    /// with the same key can conflict with a request keyed on it.
+    pub fn keyed_on(&self, column: usize) -> bool {
+        self.num_unkeyed == 0
+            && self
+                .columns
+                .iter()
+                .all(|&(keyed_column, _)| keyed_column == column)
+    }
+
+    /// Returns whether some request in the bucket may share a row with a request on `key`. This is
+    /// only false if every request in the bucket is keyed on the same column and none of their
+    /// keys hash like `key`.
+    pub fn may_conflict(&self, key: Key) -> bool {
+        !self.keyed_on(key.column)
+            || (!self.counters.is_empty() && key.probes().all(|probe| self.counters[probe] > 0))
+    }
+}
@@ -0,0 +1,88 @@
+//! The time source that `Dibs` measures timeouts and waits against.
+//!
+//! `SystemClock` reads the monotonic system clock and is the default. `ManualClock` only moves
+//! when told to, so tests can time out a waiting acquire, or measure a wait, at an exact point
+//! instead of sleeping and hoping the scheduler cooperates. A set clock is also available to
+//! callers through `Dibs::clock`, so that latencies they measure agree with dibs' own.
+
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Blocks until `duration` has passed on this clock.
+    fn sleep(&self, duration: Duration);
+
+    /// Returns how long a thread waiting on a condition variable, with `remaining` left before
+    /// its deadline on this clock, may block in real time before checking the clock again.
+    fn poll_interval(&self, remaining: Duration) -> Duration {
+        remaining
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Real time a thread waiting on a `ManualClock` blocks for before checking whether the clock has
+/// been advanced.
+const MANUAL_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A clock that starts at the time it was created and only moves forward by `advance`.
+pub struct ManualClock {
+    origin: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock {
+            origin: Instant::now(),
+            elapsed: Mutex::new(Duration::default()),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+
+    /// Returns how far the clock has been advanced since it was created.
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.origin + self.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let deadline = self.now() + duration;
+
+        while self.now() < deadline {
+            thread::sleep(MANUAL_POLL_INTERVAL);
+        }
+    }
+
+    fn poll_interval(&self, remaining: Duration) -> Duration {
+        remaining.min(MANUAL_POLL_INTERVAL)
+    }
+}
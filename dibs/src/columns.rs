@@ -0,0 +1,63 @@
+//! Sets of columns as bitmasks, so that checking whether two templates touch a common column takes
+//! a few bitwise ANDs rather than a hash lookup per column.
+
+const WORD_BITS: usize = 128;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ColumnSet {
+    /// The columns below 128, which covers most tables without allocating.
+    low: u128,
+    /// The columns from 128 up, 128 to a word.
+    high: Vec<u128>,
+}
+
+impl ColumnSet {
+    pub fn new<'a, I>(columns: I) -> ColumnSet
+    where
+        I: IntoIterator<Item = &'a usize>,
+    {
+        let mut set = ColumnSet::default();
+
+        for &column in columns {
+            let bit = 1 << (column % WORD_BITS);
+
+            match column / WORD_BITS {
+                0 => set.low |= bit,
+                word => {
+                    if set.high.len() < word {
+                        set.high.resize(word, 0);
+                    }
+
+                    set.high[word - 1] |= bit;
+                }
+            }
+        }
+
+        set
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.low == 0 && self.high.iter().all(|&word| word == 0)
+    }
+
+    /// Returns the columns in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        std::iter::once(&self.low)
+            .chain(&self.high)
+            .enumerate()
+            .flat_map(|(word_index, &word)| {
+                (0..WORD_BITS)
+                    .filter(move |bit| word & (1 << bit) != 0)
+                    .map(move |bit| word_index * WORD_BITS + bit)
+            })
+    }
+
+    pub fn intersects(&self, other: &ColumnSet) -> bool {
+        self.low & other.low != 0
+            || self
+                .high
+                .iter()
+                .zip(&other.high)
+                .any(|(word, other_word)| word & other_word != 0)
+    }
+}
@@ -0,0 +1,437 @@
+//! A C interface to the lock manager, behind the `ffi` feature, so that storage engines written in
+//! C or C++ can use dibs as their predicate lock manager. Building with the feature generates
+//! `dibs.h` from this module with cbindgen into the build's `OUT_DIR`, where a test checks that
+//! `include/dibs.h` matches it, and
+//! `cargo rustc -p dibs --features ffi --crate-type staticlib` (or `cdylib`) builds a library to
+//! link against.
+//!
+//! The interface hands out three kinds of opaque handles. A `DibsBuilder` collects the tables and
+//! request templates and is consumed by `dibs_new`, which returns a `Dibs`. A `Dibs` may be shared
+//! by any number of threads, which acquire requests on it concurrently. A `DibsTransaction`
+//! belongs to one thread at a time and is consumed by `dibs_commit`, which releases its requests.
+//! Every handle is freed by exactly one of the functions that consume it.
+//!
+//! Arguments may be booleans, integers, strings, timestamps or bytes. Decimals have no C
+//! representation, so templates over decimal columns can't be acquired through this interface.
+//!
+//! A panic never unwinds into C. Each function catches it and fails the way it fails on invalid
+//! input, returning null, `SIZE_MAX`, false or `DIBS_PANICKED`.
+
+use crate::predicate::{ComparisonOperator, Predicate, Value, MAX_DEPTH};
+use crate::{AcquireError, Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use fnv::FnvHashSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+use std::{ptr, slice, str};
+
+// Results of `dibs_acquire`.
+pub const DIBS_OK: i32 = 0;
+pub const DIBS_TIMEOUT: i32 = 1;
+pub const DIBS_GROUP_CONFLICT: i32 = 2;
+pub const DIBS_INVALID_ARGUMENTS: i32 = 3;
+pub const DIBS_PREEMPTED: i32 = 4;
+pub const DIBS_WAIT_BUDGET_EXHAUSTED: i32 = 5;
+pub const DIBS_VALIDATION_FAILED: i32 = 6;
+pub const DIBS_INVALID_TEMPLATE: i32 = 7;
+pub const DIBS_OVER_CAPACITY: i32 = 8;
+pub const DIBS_WOULD_BLOCK: i32 = 9;
+pub const DIBS_DEADLINE_EXCEEDED: i32 = 10;
+pub const DIBS_PANICKED: i32 = 11;
+
+// Kinds of predicate nodes.
+pub const DIBS_NODE_COMPARISON: u32 = 0;
+pub const DIBS_NODE_CONJUNCTION: u32 = 1;
+pub const DIBS_NODE_DISJUNCTION: u32 = 2;
+
+/// The deepest a predicate may nest, counting a comparison as depth 1.
+// Spelled out rather than taken from `predicate::MAX_DEPTH` so that cbindgen can evaluate it.
+pub const DIBS_MAX_PREDICATE_DEPTH: usize = 128;
+
+// Comparison operators.
+pub const DIBS_EQ: u32 = 0;
+pub const DIBS_NE: u32 = 1;
+pub const DIBS_LT: u32 = 2;
+pub const DIBS_LE: u32 = 3;
+pub const DIBS_GT: u32 = 4;
+pub const DIBS_GE: u32 = 5;
+
+// Kinds of values.
+pub const DIBS_VALUE_BOOLEAN: u32 = 0;
+pub const DIBS_VALUE_INTEGER: u32 = 1;
+pub const DIBS_VALUE_STRING: u32 = 2;
+pub const DIBS_VALUE_TIMESTAMP: u32 = 3;
+pub const DIBS_VALUE_BYTES: u32 = 4;
+
+// Optimization levels.
+pub const DIBS_UNGROUPED: u32 = 0;
+pub const DIBS_GROUPED: u32 = 1;
+pub const DIBS_PREPARED: u32 = 2;
+pub const DIBS_FILTERED: u32 = 3;
+
+const _: () = assert!(DIBS_MAX_PREDICATE_DEPTH == MAX_DEPTH);
+
+/// One node of a predicate, which C code passes as an array of nodes in postfix order. A
+/// comparison compares column `left` of the table to argument `right` using `op`, one of
+/// `DIBS_EQ` to `DIBS_GE`. A conjunction or disjunction pops the last
+/// `num_operands` predicates as its operands. The array must leave exactly one predicate, nested
+/// at most `DIBS_MAX_PREDICATE_DEPTH` deep.
+#[repr(C)]
+pub struct DibsPredicateNode {
+    pub kind: u32,
+    pub op: u32,
+    pub left: usize,
+    pub right: usize,
+    pub num_operands: usize,
+}
+
+/// An argument to a request. Booleans, integers and timestamps are held in `integer`, with
+/// timestamps as signed microseconds since the Unix epoch. Strings, which must be UTF-8, and
+/// bytes are borrowed from `data` for the duration of the call.
+#[repr(C)]
+pub struct DibsValue {
+    pub kind: u32,
+    pub integer: u64,
+    pub data: *const u8,
+    pub len: usize,
+}
+
+/// Collects the tables and request templates of a lock manager.
+pub struct DibsBuilder {
+    filters: Vec<Option<usize>>,
+    templates: Vec<RequestTemplate>,
+}
+
+fn operator(operator: u32) -> Option<ComparisonOperator> {
+    match operator {
+        DIBS_EQ => Some(ComparisonOperator::Eq),
+        DIBS_NE => Some(ComparisonOperator::Ne),
+        DIBS_LT => Some(ComparisonOperator::Lt),
+        DIBS_LE => Some(ComparisonOperator::Le),
+        DIBS_GT => Some(ComparisonOperator::Gt),
+        DIBS_GE => Some(ComparisonOperator::Ge),
+        _ => None,
+    }
+}
+
+fn predicate(nodes: &[DibsPredicateNode]) -> Option<Predicate> {
+    // Each predicate on the stack is paired with its depth, so that deep nesting is rejected before
+    // anything recurses into it.
+    let mut stack: Vec<(Predicate, usize)> = vec![];
+
+    for node in nodes {
+        let (predicate, depth) = match node.kind {
+            DIBS_NODE_COMPARISON => (
+                Predicate::comparison(operator(node.op)?, node.left, node.right),
+                1,
+            ),
+            DIBS_NODE_CONJUNCTION | DIBS_NODE_DISJUNCTION => {
+                let start = stack.len().checked_sub(node.num_operands)?;
+                let (operands, depths): (Vec<_>, Vec<_>) = stack.drain(start..).unzip();
+                let depth = depths.into_iter().max().unwrap_or(0) + 1;
+
+                if depth > DIBS_MAX_PREDICATE_DEPTH {
+                    return None;
+                }
+
+                if node.kind == DIBS_NODE_CONJUNCTION {
+                    (Predicate::conjunction(operands), depth)
+                } else {
+                    (Predicate::disjunction(operands), depth)
+                }
+            }
+            _ => return None,
+        };
+
+        stack.push((predicate, depth));
+    }
+
+    match stack.len() {
+        1 => stack.pop().map(|(predicate, _)| predicate),
+        _ => None,
+    }
+}
+
+/// Runs `f`, returning `on_panic` if it panics rather than unwinding into the caller.
+fn catch<T>(on_panic: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(on_panic)
+}
+
+/// # Safety
+///
+/// `data` must point to `len` readable elements, or `len` must be zero.
+unsafe fn borrow<'a, T>(data: *const T, len: usize) -> &'a [T] {
+    if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, len)
+    }
+}
+
+/// # Safety
+///
+/// The data of a string or bytes value must point to `len` readable bytes.
+unsafe fn value(value: &DibsValue) -> Option<Value> {
+    match value.kind {
+        DIBS_VALUE_BOOLEAN => Some(Value::Boolean(value.integer != 0)),
+        DIBS_VALUE_INTEGER => Some(Value::Integer(value.integer as usize)),
+        DIBS_VALUE_STRING => str::from_utf8(borrow(value.data, value.len))
+            .ok()
+            .map(|s| Value::String(s.to_string())),
+        DIBS_VALUE_TIMESTAMP => Some(Value::Timestamp(value.integer as i64)),
+        DIBS_VALUE_BYTES => Some(Value::Bytes(borrow(value.data, value.len).to_vec())),
+        _ => None,
+    }
+}
+
+fn status(result: Result<(), AcquireError>) -> i32 {
+    match result {
+        Ok(_) => DIBS_OK,
+        Err(AcquireError::Timeout(_)) => DIBS_TIMEOUT,
+        Err(AcquireError::GroupConflict) => DIBS_GROUP_CONFLICT,
+        Err(AcquireError::InvalidArguments(_)) => DIBS_INVALID_ARGUMENTS,
+        Err(AcquireError::Preempted) => DIBS_PREEMPTED,
+        Err(AcquireError::WaitBudgetExhausted) => DIBS_WAIT_BUDGET_EXHAUSTED,
+        Err(AcquireError::ValidationFailed(_)) => DIBS_VALIDATION_FAILED,
+        Err(AcquireError::OverCapacity) => DIBS_OVER_CAPACITY,
+        Err(AcquireError::WouldBlock(_)) => DIBS_WOULD_BLOCK,
+        Err(AcquireError::DeadlineExceeded) => DIBS_DEADLINE_EXCEEDED,
+    }
+}
+
+/// Creates a builder for a lock manager over `num_tables` tables, none of them filtered.
+#[no_mangle]
+pub extern "C" fn dibs_builder_new(num_tables: usize) -> *mut DibsBuilder {
+    catch(ptr::null_mut(), || {
+        Box::into_raw(Box::new(DibsBuilder {
+            filters: vec![None; num_tables],
+            templates: vec![],
+        }))
+    })
+}
+
+/// Partitions the requests on `table` by `column` when the lock manager is filtered. Returns
+/// false if the table doesn't exist.
+///
+/// # Safety
+///
+/// `builder` must be a builder that hasn't been consumed.
+#[no_mangle]
+pub unsafe extern "C" fn dibs_builder_set_filter(
+    builder: *mut DibsBuilder,
+    table: usize,
+    column: usize,
+) -> bool {
+    catch(false, || {
+        let builder = &mut *builder;
+
+        match builder.filters.get_mut(table) {
+            Some(filter) => {
+                *filter = Some(column);
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// Adds a request template and returns its ID, which templates are numbered by in the order they
+/// are added. Returns `SIZE_MAX` if the table doesn't exist or the predicate is malformed.
+///
+/// # Safety
+///
+/// `builder` must be a builder that hasn't been consumed, and each array must point to the given
+/// number of elements.
+#[no_mangle]
+pub unsafe extern "C" fn dibs_register_template(
+    builder: *mut DibsBuilder,
+    table: usize,
+    read_columns: *const usize,
+    num_read_columns: usize,
+    write_columns: *const usize,
+    num_write_columns: usize,
+    nodes: *const DibsPredicateNode,
+    num_nodes: usize,
+) -> usize {
+    catch(usize::MAX, || {
+        let builder = &mut *builder;
+
+        if table >= builder.filters.len() {
+            return usize::MAX;
+        }
+
+        let predicate = match predicate(borrow(nodes, num_nodes)) {
+            Some(predicate) => predicate,
+            None => return usize::MAX,
+        };
+
+        builder.templates.push(RequestTemplate::new(
+            table,
+            borrow(read_columns, num_read_columns)
+                .iter()
+                .copied()
+                .collect::<FnvHashSet<_>>(),
+            borrow(write_columns, num_write_columns)
+                .iter()
+                .copied()
+                .collect::<FnvHashSet<_>>(),
+            predicate,
+        ));
+
+        builder.templates.len() - 1
+    })
+}
+
+/// Consumes `builder` and creates a lock manager from it at `optimization`, one of
+/// `DIBS_UNGROUPED` to `DIBS_FILTERED`. Acquires give up after waiting
+/// `timeout_micros` for a conflicting request. Returns null, still consuming the builder, if the
+/// optimization level is unknown.
+///
+/// # Safety
+///
+/// `builder` must be a builder that hasn't been consumed.
+#[no_mangle]
+pub unsafe extern "C" fn dibs_new(
+    builder: *mut DibsBuilder,
+    optimization: u32,
+    timeout_micros: u64,
+) -> *mut Dibs {
+    catch(ptr::null_mut(), || {
+        let builder = Box::from_raw(builder);
+
+        let optimization = match optimization {
+            DIBS_UNGROUPED => OptimizationLevel::Ungrouped,
+            DIBS_GROUPED => OptimizationLevel::Grouped,
+            DIBS_PREPARED => OptimizationLevel::Prepared,
+            DIBS_FILTERED => OptimizationLevel::Filtered,
+            _ => return ptr::null_mut(),
+        };
+
+        Box::into_raw(Box::new(Dibs::new(
+            &builder.filters,
+            &builder.templates,
+            optimization,
+            None,
+            None,
+            Duration::from_micros(timeout_micros),
+        )))
+    })
+}
+
+/// Frees a lock manager.
+///
+/// # Safety
+///
+/// `dibs` must come from `dibs_new` and no thread may be using it. Transactions that acquired
+/// requests on it may still be committed afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn dibs_free(dibs: *mut Dibs) {
+    catch((), || {
+        if !dibs.is_null() {
+            drop(Box::from_raw(dibs));
+        }
+    })
+}
+
+/// Starts a transaction. Transaction IDs must be unique among the transactions in flight, and
+/// transactions that share a group ID never conflict with each other.
+#[no_mangle]
+pub extern "C" fn dibs_transaction_new(group_id: usize, transaction_id: usize) -> *mut Transaction {
+    catch(ptr::null_mut(), || {
+        Box::into_raw(Box::new(Transaction::new(group_id, transaction_id)))
+    })
+}
+
+/// Acquires a request on the rows selected by template `template_id` with the given arguments,
+/// waiting for conflicting requests to complete. Returns `DIBS_OK` or the reason it failed, after
+/// which the transaction should be committed and retried.
+///
+/// # Safety
+///
+/// `dibs` must be a live lock manager, `transaction` a transaction that hasn't been committed and
+/// isn't in use by another thread, and `arguments` must point to `num_arguments` values.
+#[no_mangle]
+pub unsafe extern "C" fn dibs_acquire(
+    dibs: *const Dibs,
+    transaction: *mut Transaction,
+    template_id: usize,
+    arguments: *const DibsValue,
+    num_arguments: usize,
+) -> i32 {
+    catch(DIBS_PANICKED, || {
+        acquire(
+            dibs,
+            transaction,
+            template_id,
+            arguments,
+            num_arguments,
+            Dibs::acquire,
+        )
+    })
+}
+
+/// Like `dibs_acquire`, but returns `DIBS_WOULD_BLOCK` rather than waiting if conflicting
+/// requests are in flight. The request is then withdrawn, and the transaction may try again
+/// later rather than being committed.
+///
+/// # Safety
+///
+/// As for `dibs_acquire`.
+#[no_mangle]
+pub unsafe extern "C" fn dibs_try_acquire(
+    dibs: *const Dibs,
+    transaction: *mut Transaction,
+    template_id: usize,
+    arguments: *const DibsValue,
+    num_arguments: usize,
+) -> i32 {
+    catch(DIBS_PANICKED, || {
+        acquire(
+            dibs,
+            transaction,
+            template_id,
+            arguments,
+            num_arguments,
+            Dibs::try_acquire,
+        )
+    })
+}
+
+unsafe fn acquire(
+    dibs: *const Dibs,
+    transaction: *mut Transaction,
+    template_id: usize,
+    arguments: *const DibsValue,
+    num_arguments: usize,
+    acquire: fn(&Dibs, &mut Transaction, usize, Vec<Value>) -> Result<(), AcquireError>,
+) -> i32 {
+    let dibs = &*dibs;
+    let transaction = &mut *transaction;
+
+    match template_id.checked_add(transaction.template_offset()) {
+        Some(template_id) if template_id < dibs.num_templates() => {}
+        _ => return DIBS_INVALID_TEMPLATE,
+    }
+
+    let arguments = match borrow(arguments, num_arguments)
+        .iter()
+        .map(|argument| value(argument))
+        .collect::<Option<Vec<_>>>()
+    {
+        Some(arguments) => arguments,
+        None => return DIBS_INVALID_ARGUMENTS,
+    };
+
+    status(acquire(dibs, transaction, template_id, arguments))
+}
+
+/// Ends a transaction, releasing its requests and freeing it.
+///
+/// # Safety
+///
+/// `transaction` must come from `dibs_transaction_new` and not have been committed.
+#[no_mangle]
+pub unsafe extern "C" fn dibs_commit(transaction: *mut Transaction) {
+    catch((), || {
+        Box::from_raw(transaction).commit();
+    })
+}
@@ -0,0 +1,166 @@
+//! How the requests of each template were routed among their table's buckets, to show whether a
+//! table's filter column suits the workload.
+//!
+//! On a partitioned table, a request whose template fixes the filter column goes to the one
+//! partition its filter value picks, and checks the table's residual bucket as well. A request
+//! whose template doesn't goes to the residual bucket instead, and is checked against every
+//! partition. A filter that few requests fix partitions little, and the residual requests it
+//! leaves are checked by the partitioned requests too.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The bucket that a request was routed to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Route {
+    /// A partition, picked by the request's filter value or its template's group.
+    Partition,
+    /// The residual bucket, which is checked against every partition.
+    Residual,
+    /// The only bucket of a table that isn't partitioned.
+    Unpartitioned,
+}
+
+#[derive(Default)]
+struct Counts {
+    num_partitioned: AtomicUsize,
+    num_residual: AtomicUsize,
+    num_unpartitioned: AtomicUsize,
+    num_residual_checked: AtomicUsize,
+}
+
+/// Counts the routes of each template's requests. Counters are per template, so unlike the wait
+/// graph this takes no lock.
+pub(crate) struct FilterRecorder {
+    templates: Vec<Counts>,
+}
+
+impl FilterRecorder {
+    pub(crate) fn new(num_templates: usize) -> FilterRecorder {
+        FilterRecorder {
+            templates: (0..num_templates).map(|_| Counts::default()).collect(),
+        }
+    }
+
+    pub(crate) fn record_route(&self, template_id: usize, route: Route) {
+        let counts = &self.templates[template_id];
+
+        let counter = match route {
+            Route::Partition => &counts.num_partitioned,
+            Route::Residual => &counts.num_residual,
+            Route::Unpartitioned => &counts.num_unpartitioned,
+        };
+
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that a partitioned request of `template_id` found `num_requests` in the residual
+    /// bucket.
+    pub(crate) fn record_residual_checked(&self, template_id: usize, num_requests: usize) {
+        self.templates[template_id]
+            .num_residual_checked
+            .fetch_add(num_requests, Ordering::Relaxed);
+    }
+
+    /// Returns the counts of each template that has routed a request, given the table and filter
+    /// parameter of each template.
+    pub(crate) fn report<F>(&self, template: F) -> FilterReport
+    where
+        F: Fn(usize) -> (usize, Option<usize>),
+    {
+        let templates = self
+            .templates
+            .iter()
+            .enumerate()
+            .map(|(template_id, counts)| {
+                let (table, filter) = template(template_id);
+
+                TemplateRoutes {
+                    template_id,
+                    table,
+                    filter,
+                    num_partitioned: counts.num_partitioned.load(Ordering::Relaxed),
+                    num_residual: counts.num_residual.load(Ordering::Relaxed),
+                    num_unpartitioned: counts.num_unpartitioned.load(Ordering::Relaxed),
+                    num_residual_checked: counts.num_residual_checked.load(Ordering::Relaxed),
+                }
+            })
+            .filter(|routes| routes.num_requests() > 0)
+            .collect();
+
+        FilterReport { templates }
+    }
+}
+
+/// The routes taken by the requests of one template since `Dibs::enable_filter_stats`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateRoutes {
+    pub template_id: usize,
+    pub table: usize,
+    /// The template's parameter that fixes its table's filter column, if any.
+    pub filter: Option<usize>,
+    /// Requests routed to one partition.
+    pub num_partitioned: usize,
+    /// Requests routed to the residual bucket.
+    pub num_residual: usize,
+    /// Requests on tables with a single bucket.
+    pub num_unpartitioned: usize,
+    /// The residual requests that the partitioned requests found in flight, and so checked.
+    pub num_residual_checked: usize,
+}
+
+impl TemplateRoutes {
+    pub fn num_requests(&self) -> usize {
+        self.num_partitioned + self.num_residual + self.num_unpartitioned
+    }
+
+    /// Returns the fraction of the template's requests on partitioned tables that went to one
+    /// partition, or `None` if none of its requests were on a partitioned table.
+    pub fn hit_rate(&self) -> Option<f64> {
+        match self.num_partitioned + self.num_residual {
+            0 => None,
+            num_requests => Some(self.num_partitioned as f64 / num_requests as f64),
+        }
+    }
+}
+
+/// The routes of every template that has routed a request, in template order. Point templates
+/// follow the templates the `Dibs` was created with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterReport {
+    pub templates: Vec<TemplateRoutes>,
+}
+
+impl fmt::Display for FilterReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "filter hit rates:")?;
+
+        for routes in &self.templates {
+            let filter = match routes.filter {
+                Some(parameter) => format!("parameter {}", parameter),
+                None => "none".to_string(),
+            };
+
+            let hit_rate = match routes.hit_rate() {
+                Some(hit_rate) => format!("{:.1}%", hit_rate * 100.0),
+                None => "unpartitioned".to_string(),
+            };
+
+            writeln!(
+                f,
+                "  template {} (table {}, filter {}): {} of {} requests, {} partitioned, {} \
+                 residual, {} residual requests checked",
+                routes.template_id,
+                routes.table,
+                filter,
+                hit_rate,
+                routes.num_requests(),
+                routes.num_partitioned,
+                routes.num_residual,
+                routes.num_residual_checked
+            )?;
+        }
+
+        Ok(())
+    }
+}
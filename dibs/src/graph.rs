@@ -0,0 +1,200 @@
+//! The conflict graph of a `Dibs`: which templates may conflict, as prepared, and which waited for
+//! which during a run, exported to GraphViz DOT or JSON.
+
+use fnv::FnvHashMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Default)]
+struct WaitCounts {
+    num_waits: usize,
+    num_timeouts: usize,
+    waited: Duration,
+}
+
+/// Counts every wait of a request of one template for an in-flight request of another. Like the
+/// conflict sampler, it takes a global lock on every wait.
+#[derive(Default)]
+pub(crate) struct WaitRecorder {
+    waits: Mutex<FnvHashMap<(usize, usize), WaitCounts>>,
+}
+
+impl WaitRecorder {
+    pub(crate) fn record(
+        &self,
+        template_id: usize,
+        other_template_id: usize,
+        waited: Duration,
+        timed_out: bool,
+    ) {
+        let mut waits = self.waits.lock().unwrap();
+        let counts = waits.entry((template_id, other_template_id)).or_default();
+        counts.num_waits += 1;
+        counts.num_timeouts += timed_out as usize;
+        counts.waited += waited;
+    }
+
+    pub(crate) fn edges(&self) -> Vec<WaitEdge> {
+        let mut edges = self
+            .waits
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&(template_id, other_template_id), counts)| WaitEdge {
+                template_id,
+                other_template_id,
+                num_waits: counts.num_waits,
+                num_timeouts: counts.num_timeouts,
+                waited: counts.waited,
+            })
+            .collect::<Vec<_>>();
+
+        edges.sort_by_key(|edge| (edge.template_id, edge.other_template_id));
+        edges
+    }
+}
+
+/// A template of the graph. Point templates follow the templates the `Dibs` was created with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateNode {
+    pub template_id: usize,
+    pub table: usize,
+    /// Whether the template writes any column.
+    pub writes: bool,
+    /// Whether the template is one of the point templates of its table.
+    pub point: bool,
+}
+
+/// The waits of requests of `template_id` for in-flight requests of `other_template_id`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WaitEdge {
+    pub template_id: usize,
+    pub other_template_id: usize,
+    pub num_waits: usize,
+    pub num_timeouts: usize,
+    pub waited: Duration,
+}
+
+/// The static conflicts between templates and the waits observed between them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConflictGraph {
+    pub templates: Vec<TemplateNode>,
+    /// The pairs of templates whose requests may conflict, each listed once with the lower ID
+    /// first. A template that may conflict with itself is paired with itself.
+    pub conflicts: Vec<(usize, usize)>,
+    /// The waits observed since `Dibs::enable_wait_graph`, empty if it wasn't called.
+    pub waits: Vec<WaitEdge>,
+}
+
+fn node_label(template: &TemplateNode) -> String {
+    let kind = match (template.point, template.writes) {
+        (true, true) => "point write",
+        (true, false) => "point read",
+        (false, true) => "write",
+        (false, false) => "read",
+    };
+
+    format!(
+        "{} ({}, table {})",
+        template.template_id, kind, template.table
+    )
+}
+
+impl ConflictGraph {
+    /// Renders the graph in GraphViz DOT. Static conflicts are dashed and undirected, and each
+    /// wait points from the waiting template to the one it waited for, thicker for more waits.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph conflicts {\n");
+        let max_waits = self.waits.iter().map(|edge| edge.num_waits).max();
+
+        for template in &self.templates {
+            writeln!(
+                dot,
+                "  t{} [label=\"{}\", shape={}];",
+                template.template_id,
+                node_label(template),
+                if template.writes { "box" } else { "ellipse" }
+            )
+            .unwrap();
+        }
+
+        for &(template_id, other_template_id) in &self.conflicts {
+            writeln!(
+                dot,
+                "  t{} -> t{} [dir=none, style=dashed, color=gray];",
+                template_id, other_template_id
+            )
+            .unwrap();
+        }
+
+        for edge in &self.waits {
+            let penwidth = 1.0 + 4.0 * edge.num_waits as f64 / max_waits.unwrap() as f64;
+
+            writeln!(
+                dot,
+                "  t{} -> t{} [label=\"{} waits, {} timeouts\", penwidth={:.2}, color=red];",
+                edge.template_id,
+                edge.other_template_id,
+                edge.num_waits,
+                edge.num_timeouts,
+                penwidth
+            )
+            .unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the graph as a JSON object with `templates`, `conflicts` and `waits` arrays.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\n  \"templates\": [");
+
+        for (i, template) in self.templates.iter().enumerate() {
+            write!(
+                json,
+                "{}\n    {{\"template_id\": {}, \"table\": {}, \"writes\": {}, \"point\": {}}}",
+                if i == 0 { "" } else { "," },
+                template.template_id,
+                template.table,
+                template.writes,
+                template.point
+            )
+            .unwrap();
+        }
+
+        json.push_str("\n  ],\n  \"conflicts\": [");
+
+        for (i, &(template_id, other_template_id)) in self.conflicts.iter().enumerate() {
+            write!(
+                json,
+                "{}\n    [{}, {}]",
+                if i == 0 { "" } else { "," },
+                template_id,
+                other_template_id
+            )
+            .unwrap();
+        }
+
+        json.push_str("\n  ],\n  \"waits\": [");
+
+        for (i, edge) in self.waits.iter().enumerate() {
+            write!(
+                json,
+                "{}\n    {{\"template_id\": {}, \"other_template_id\": {}, \"num_waits\": {}, \
+                 \"num_timeouts\": {}, \"waited_ns\": {}}}",
+                if i == 0 { "" } else { "," },
+                edge.template_id,
+                edge.other_template_id,
+                edge.num_waits,
+                edge.num_timeouts,
+                edge.waited.as_nanos()
+            )
+            .unwrap();
+        }
+
+        json.push_str("\n  ]\n}\n");
+        json
+    }
+}
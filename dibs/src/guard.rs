@@ -0,0 +1,51 @@
+//! Acquiring single requests without managing a `Transaction`.
+//!
+//! With the `guard` feature enabled, `Dibs::acquire_guarded` acquires a request in a transaction
+//! of its own and returns a guard that commits the transaction when dropped, releasing the request
+//! and waking any requests waiting on it. Guards and transactions can be mixed on the same `Dibs`.
+
+use crate::predicate::Value;
+use crate::{AcquireError, Dibs, Transaction};
+use std::sync::atomic::Ordering;
+
+/// Holds a request until dropped.
+pub struct RequestGuard {
+    transaction: Option<Transaction>,
+}
+
+impl RequestGuard {
+    /// The ID of the transaction that holds the request, as it appears in
+    /// `AcquireError::Timeout` and `ValidationFailed`.
+    pub fn transaction_id(&self) -> usize {
+        self.transaction.as_ref().unwrap().transaction_id
+    }
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        if let Some(transaction) = self.transaction.take() {
+            transaction.commit();
+        }
+    }
+}
+
+impl Dibs {
+    /// Acquires a request in a transaction of its own, which is released when the returned guard
+    /// is dropped. The transaction, which is also its own group, gets an ID counting down from
+    /// `usize::MAX`, so transactions acquired directly should use IDs below that range. If the
+    /// acquire fails, the request is released before the error is returned.
+    pub fn acquire_guarded(
+        &self,
+        template_id: usize,
+        arguments: Vec<Value>,
+    ) -> Result<RequestGuard, AcquireError> {
+        let transaction_id = usize::MAX - self.num_guards.fetch_add(1, Ordering::Relaxed);
+
+        let mut guard = RequestGuard {
+            transaction: Some(Transaction::new(transaction_id, transaction_id)),
+        };
+
+        self.acquire(guard.transaction.as_mut().unwrap(), template_id, arguments)?;
+        Ok(guard)
+    }
+}
@@ -0,0 +1,151 @@
+//! Per-key queues for the keys that conflict most often in a bucket.
+//!
+//! Under skew, most conflicts in a bucket fall on a few keys, and every request on one of them
+//! solves against the whole bucket only to find the same handful of requests. Each bucket tracks
+//! how often requests on each key conflict and promotes the keys that conflict often to a queue
+//! holding every in-flight request on the key, in the order they arrived. While every request in
+//! the bucket is keyed on the same column, a request on a hot key can only conflict with the
+//! requests in its key's queue, so it checks those alone.
+
+use crate::bloom::Key;
+use crate::predicate::Value;
+use crate::sync::Arc;
+use crate::Request;
+use std::collections::VecDeque;
+use std::ptr;
+
+const NUM_HEAT_COUNTERS: usize = 1024;
+
+/// Heat is halved every this many conflicts, so that keys which stop conflicting cool down.
+const HEAT_WINDOW: usize = 4096;
+
+/// Buckets keep queues for at most this many keys, since requests look their key up by a linear
+/// search.
+const MAX_HOT_KEYS: usize = 64;
+
+struct KeyQueue {
+    slot: usize,
+    fingerprint: (usize, u64),
+    value: Value,
+    requests: VecDeque<Arc<Request>>,
+}
+
+impl KeyQueue {
+    fn holds(&self, key: Key, arguments: &[Value]) -> bool {
+        self.fingerprint == key.fingerprint() && self.value == *key.value(arguments)
+    }
+}
+
+#[derive(Default)]
+pub struct HotKeys {
+    /// Conflicts per key hash, allocated when the first conflict is recorded.
+    heat: Vec<u16>,
+    /// Conflicts recorded since the heat was last halved.
+    num_conflicts: usize,
+    queues: Vec<KeyQueue>,
+}
+
+impl HotKeys {
+    /// Returns the in-flight requests on `key`, bound to `arguments`, if the key is hot.
+    pub fn queue(&self, key: Key, arguments: &[Value]) -> Option<&VecDeque<Arc<Request>>> {
+        self.queues
+            .iter()
+            .find(|queue| queue.holds(key, arguments))
+            .map(|queue| &queue.requests)
+    }
+
+    /// Adds a request that was just added to the bucket to its key's queue, if the key is hot.
+    pub fn push(&mut self, request: &Arc<Request>) {
+        if let Some(key) = request.key {
+            if let Some(queue) = self
+                .queues
+                .iter_mut()
+                .find(|queue| queue.holds(key, &request.arguments))
+            {
+                queue.requests.push_back(Arc::clone(request));
+            }
+        }
+    }
+
+    /// Removes a request that was just removed from the bucket from its key's queue.
+    pub fn remove(&mut self, request: &Request) {
+        if let Some(key) = request.key {
+            if let Some(queue) = self
+                .queues
+                .iter_mut()
+                .find(|queue| queue.holds(key, &request.arguments))
+            {
+                if let Some(position) = queue
+                    .requests
+                    .iter()
+                    .position(|queued| ptr::eq(&**queued, request))
+                {
+                    queue.requests.remove(position);
+                }
+            }
+        }
+    }
+
+    /// Records that a request on `key` conflicted, and promotes the key once it has conflicted
+    /// `threshold` times. The queue of a promoted key starts with the requests on it among
+    /// `requests`, which are those in the bucket. Returns whether the key was promoted.
+    pub fn record_conflict(
+        &mut self,
+        key: Key,
+        arguments: &[Value],
+        threshold: usize,
+        requests: &[Arc<Request>],
+    ) -> bool {
+        if self.heat.is_empty() {
+            self.heat = vec![0; NUM_HEAT_COUNTERS];
+        }
+
+        let slot = key.slot(NUM_HEAT_COUNTERS);
+        self.heat[slot] = self.heat[slot].saturating_add(1);
+        self.num_conflicts += 1;
+
+        if self.num_conflicts == HEAT_WINDOW {
+            self.num_conflicts = 0;
+            self.cool(threshold);
+        }
+
+        if usize::from(self.heat[slot]) < threshold
+            || self.queues.len() >= MAX_HOT_KEYS
+            || self.queue(key, arguments).is_some()
+        {
+            return false;
+        }
+
+        let mut queue = KeyQueue {
+            slot,
+            fingerprint: key.fingerprint(),
+            value: key.value(arguments).clone(),
+            requests: VecDeque::new(),
+        };
+
+        queue.requests = requests
+            .iter()
+            .filter(
+                |request| matches!(request.key, Some(key) if queue.holds(key, &request.arguments)),
+            )
+            .cloned()
+            .collect();
+
+        self.queues.push(queue);
+        true
+    }
+
+    /// Halves the heat of every key, and demotes the keys that have cooled below `threshold` and
+    /// have no requests in flight.
+    fn cool(&mut self, threshold: usize) {
+        for heat in &mut self.heat {
+            *heat /= 2;
+        }
+
+        let heat = &self.heat;
+
+        self.queues.retain(|queue| {
+            !queue.requests.is_empty() || usize::from(heat[queue.slot]) >= threshold
+        });
+    }
+}
@@ -0,0 +1,12 @@
+//! The solver behind the lock manager, exposed with the `internals` feature so that it can be
+//! benchmarked and tested on its own. None of this is a stable interface.
+
+pub use crate::bloom::{Key, KeyFilter};
+pub use crate::columns::ColumnSet;
+pub use crate::interval::{disjoint, IntervalTemplate};
+pub use crate::program::Program;
+pub use crate::solver::{
+    dnf_blowup, prepare, prepare_clusters, solve_clustered, solve_clusters, solve_dnf,
+};
+
+pub mod testing;
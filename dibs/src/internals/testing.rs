@@ -0,0 +1,160 @@
+//! Random predicates and a brute-force oracle to check the solver against.
+//!
+//! The solver may report a conflict between two requests that don't conflict, but it must never
+//! miss one. The oracle finds conflicts by enumerating every row of integers over a small domain,
+//! so it is exact for rows in that domain but only fast enough for a few columns.
+
+use crate::predicate::{ComparisonOperator, Connective, Predicate, Value};
+use rand::Rng;
+
+const OPERATORS: [ComparisonOperator; 6] = [
+    ComparisonOperator::Eq,
+    ComparisonOperator::Ne,
+    ComparisonOperator::Lt,
+    ComparisonOperator::Le,
+    ComparisonOperator::Gt,
+    ComparisonOperator::Ge,
+];
+
+/// Generates random predicates over a fixed number of columns and parameters, and random
+/// arguments for them.
+#[derive(Clone, Debug)]
+pub struct PredicateGenerator {
+    num_columns: usize,
+    num_parameters: usize,
+    domain: usize,
+    max_depth: usize,
+    max_operands: usize,
+}
+
+impl PredicateGenerator {
+    /// Creates a generator of predicates comparing `num_columns` columns with `num_parameters`
+    /// parameters, whose arguments are integers less than `domain`.
+    pub fn new(num_columns: usize, num_parameters: usize, domain: usize) -> PredicateGenerator {
+        assert!(num_columns > 0 && num_parameters > 0 && domain > 0);
+
+        PredicateGenerator {
+            num_columns,
+            num_parameters,
+            domain,
+            max_depth: 3,
+            max_operands: 3,
+        }
+    }
+
+    /// Sets the number of connectives that may be nested above a comparison.
+    pub fn with_max_depth(mut self, max_depth: usize) -> PredicateGenerator {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the number of operands a connective may have. Connectives may also have none, making
+    /// them the constants true and false.
+    pub fn with_max_operands(mut self, max_operands: usize) -> PredicateGenerator {
+        self.max_operands = max_operands;
+        self
+    }
+
+    pub fn num_columns(&self) -> usize {
+        self.num_columns
+    }
+
+    pub fn domain(&self) -> usize {
+        self.domain
+    }
+
+    pub fn predicate<R: Rng>(&self, rng: &mut R) -> Predicate {
+        self.predicate_at_depth(rng, 0)
+    }
+
+    fn predicate_at_depth<R: Rng>(&self, rng: &mut R, depth: usize) -> Predicate {
+        if depth >= self.max_depth || rng.gen_bool(0.4) {
+            Predicate::comparison(
+                OPERATORS[rng.gen_range(0, OPERATORS.len())],
+                rng.gen_range(0, self.num_columns),
+                rng.gen_range(0, self.num_parameters),
+            )
+        } else {
+            let operands = (0..rng.gen_range(0, self.max_operands + 1))
+                .map(|_| self.predicate_at_depth(rng, depth + 1))
+                .collect();
+
+            if rng.gen() {
+                Predicate::conjunction(operands)
+            } else {
+                Predicate::disjunction(operands)
+            }
+        }
+    }
+
+    pub fn arguments<R: Rng>(&self, rng: &mut R) -> Vec<Value> {
+        (0..self.num_parameters)
+            .map(|_| Value::Integer(rng.gen_range(0, self.domain)))
+            .collect()
+    }
+}
+
+/// Returns whether `row` satisfies `predicate` bound to `arguments`, where each comparison
+/// compares a column of the row with an argument.
+pub fn satisfies(predicate: &Predicate, row: &[Value], arguments: &[Value]) -> bool {
+    match predicate {
+        Predicate::Comparison(comparison) => {
+            let column = &row[comparison.left];
+            let argument = &arguments[comparison.right];
+
+            match comparison.operator {
+                ComparisonOperator::Eq => column == argument,
+                ComparisonOperator::Ne => column != argument,
+                ComparisonOperator::Lt => column < argument,
+                ComparisonOperator::Le => column <= argument,
+                ComparisonOperator::Gt => column > argument,
+                ComparisonOperator::Ge => column >= argument,
+            }
+        }
+        Predicate::Connective(Connective::Conjunction, operands) => operands
+            .iter()
+            .all(|operand| satisfies(operand, row, arguments)),
+        Predicate::Connective(Connective::Disjunction, operands) => operands
+            .iter()
+            .any(|operand| satisfies(operand, row, arguments)),
+    }
+}
+
+/// Returns every row of `num_columns` integers, each at most `domain`. The domain includes one
+/// value past the arguments generated for it, so that a row can be greater than all of them.
+pub fn rows(num_columns: usize, domain: usize) -> impl Iterator<Item = Vec<Value>> {
+    let mut digits = Some(vec![0; num_columns]);
+
+    std::iter::from_fn(move || {
+        let current = digits.take()?;
+        let row = current.iter().map(|&digit| Value::Integer(digit)).collect();
+
+        // Advance to the next row, counting in base `domain + 1`.
+        let mut next = current;
+
+        if let Some(i) = next.iter().position(|&digit| digit < domain) {
+            next[i] += 1;
+
+            for digit in &mut next[..i] {
+                *digit = 0;
+            }
+
+            digits = Some(next);
+        }
+
+        Some(row)
+    })
+}
+
+/// Returns a row from `rows` that satisfies both `p` bound to `p_args` and `q` bound to `q_args`,
+/// or `None` if there is no such row.
+pub fn shared_row(
+    p: &Predicate,
+    p_args: &[Value],
+    q: &Predicate,
+    q_args: &[Value],
+    num_columns: usize,
+    domain: usize,
+) -> Option<Vec<Value>> {
+    rows(num_columns, domain).find(|row| satisfies(p, row, p_args) && satisfies(q, row, q_args))
+}
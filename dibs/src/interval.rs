@@ -0,0 +1,596 @@
+//! Interval summaries, which rule out conflicts between requests without solving their predicates.
+//!
+//! A template whose predicate is a conjunction restricts each row it covers to the intersection of
+//! the bounds its comparisons place on each column, such as `start_time <= ?0 AND ?1 < end_time`.
+//! When a request is acquired, the bounds are narrowed to the tightest given its arguments. Two
+//! requests whose intervals on some column don't overlap can't cover a common row, whatever the
+//! rest of their predicates say, so the solver doesn't need to run for them. A union of ranges,
+//! such as `(?0 <= x AND x <= ?1) OR (?2 <= x AND x <= ?3)`, is summarized by the interval
+//! spanning all of them.
+//!
+//! A template may also declare the domain of a column, the values it holds in every row. Two
+//! intervals that overlap only between the values of a domain, such as `x <= 7` and `x >= 1` when
+//! `x` is a multiple of 8, can't cover a common row either.
+
+use crate::predicate::{ComparisonOperator, Connective, Domain, Predicate, Value};
+use std::cmp::Ordering;
+use std::sync::Arc;
+use std::{mem, slice};
+
+#[derive(Clone, Copy, Debug)]
+struct Bound {
+    parameter: usize,
+    inclusive: bool,
+}
+
+/// The bounds that a conjunction of comparisons places on one column. The column lies within the
+/// tightest bound on each side.
+#[derive(Clone, Debug)]
+struct Bounds {
+    column: usize,
+    lower: Vec<Bound>,
+    upper: Vec<Bound>,
+}
+
+/// Alternative sets of bounds on one side of a column, of which every covered row satisfies at
+/// least one. The column lies within the loosest of the tightest bounds in each set.
+type Hull = Vec<Vec<Bound>>;
+
+#[derive(Clone, Debug)]
+struct ColumnHulls {
+    column: usize,
+    lower: Vec<Hull>,
+    upper: Vec<Hull>,
+    domain: Option<Arc<Domain>>,
+}
+
+/// The bounds that a template's predicate places on each column, sorted by column.
+#[derive(Clone, Debug)]
+pub struct IntervalTemplate {
+    columns: Vec<ColumnHulls>,
+}
+
+/// A request's interval on one column.
+#[derive(Clone, Debug)]
+pub struct Interval {
+    column: usize,
+    lower: Option<Bound>,
+    upper: Option<Bound>,
+    domain: Option<Arc<Domain>>,
+}
+
+impl IntervalTemplate {
+    /// Returns `None` if the predicate bounds no column and no domain is declared. Bounds are
+    /// taken from comparisons in the top-level conjunction, and from disjunctions there whose every
+    /// operand bounds the same column, which covers ranges and unions of ranges. Leaving out
+    /// predicates nested deeper only loosens the bounds, since they narrow the rows covered.
+    pub fn new(predicate: &Predicate, domains: &[(usize, Domain)]) -> Option<IntervalTemplate> {
+        let mut predicate = predicate.clone();
+        predicate.condense();
+
+        let conjuncts = match &predicate {
+            Predicate::Connective(Connective::Conjunction, operands) => operands.as_slice(),
+            _ => slice::from_ref(&predicate),
+        };
+
+        let mut columns: Vec<ColumnHulls> = vec![];
+
+        // Each comparison in the conjunction is a hull with a single alternative.
+        for bounds in conjunction_bounds(conjuncts) {
+            let hulls = column_hulls(&mut columns, bounds.column);
+            hulls
+                .lower
+                .extend(bounds.lower.into_iter().map(|bound| vec![vec![bound]]));
+            hulls
+                .upper
+                .extend(bounds.upper.into_iter().map(|bound| vec![vec![bound]]));
+        }
+
+        for conjunct in conjuncts {
+            let alternatives = match conjunct {
+                Predicate::Connective(Connective::Disjunction, operands) => operands
+                    .iter()
+                    .map(|operand| match operand {
+                        Predicate::Connective(Connective::Conjunction, operands) => {
+                            conjunction_bounds(operands)
+                        }
+                        _ => conjunction_bounds(slice::from_ref(operand)),
+                    })
+                    .collect::<Vec<_>>(),
+                _ => continue,
+            };
+
+            let (first, rest) = match alternatives.split_first() {
+                Some(split) => split,
+                None => continue,
+            };
+
+            for bounds in first {
+                let lower = hull(bounds, rest, |bounds| &bounds.lower);
+                let upper = hull(bounds, rest, |bounds| &bounds.upper);
+
+                if lower.is_some() || upper.is_some() {
+                    let hulls = column_hulls(&mut columns, bounds.column);
+                    hulls.lower.extend(lower);
+                    hulls.upper.extend(upper);
+                }
+            }
+        }
+
+        for (column, domain) in domains {
+            column_hulls(&mut columns, *column).domain = Some(Arc::new(domain.clone()));
+        }
+
+        if columns.is_empty() {
+            None
+        } else {
+            columns.sort_unstable_by_key(|hulls| hulls.column);
+            Some(IntervalTemplate { columns })
+        }
+    }
+
+    /// Returns the request's interval on each bounded column. The arguments must have been
+    /// validated, so that every parameter compared to a column has the same type.
+    pub fn summarize(&self, arguments: &[Value]) -> Vec<Interval> {
+        // A side's bound is the tightest of its hulls, each of which is the loosest of its
+        // alternatives' tightest bounds.
+        let side = |hulls: &[Hull], inwards: Ordering| {
+            let tightest =
+                |bounds: &[Bound]| extreme(bounds.iter().copied(), arguments, inwards, true);
+
+            let loosest = |hull: &Hull| {
+                extreme(
+                    hull.iter().filter_map(|bounds| tightest(bounds)),
+                    arguments,
+                    inwards.reverse(),
+                    false,
+                )
+            };
+
+            extreme(hulls.iter().filter_map(loosest), arguments, inwards, true)
+        };
+
+        self.columns
+            .iter()
+            .map(|hulls| Interval {
+                column: hulls.column,
+                lower: side(&hulls.lower, Ordering::Greater),
+                upper: side(&hulls.upper, Ordering::Less),
+                domain: hulls.domain.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Returns the bounds that the comparisons among `conjuncts` place on each column.
+fn conjunction_bounds(conjuncts: &[Predicate]) -> Vec<Bounds> {
+    let mut columns: Vec<Bounds> = vec![];
+
+    for conjunct in conjuncts {
+        let comparison = match conjunct {
+            Predicate::Comparison(comparison) => comparison,
+            _ => continue,
+        };
+
+        let (lower, upper) = match comparison.operator {
+            ComparisonOperator::Eq => (Some(true), Some(true)),
+            ComparisonOperator::Ne => continue,
+            ComparisonOperator::Lt => (None, Some(false)),
+            ComparisonOperator::Le => (None, Some(true)),
+            ComparisonOperator::Gt => (Some(false), None),
+            ComparisonOperator::Ge => (Some(true), None),
+        };
+
+        let bounds = match columns
+            .iter()
+            .position(|bounds| bounds.column == comparison.left)
+        {
+            Some(position) => &mut columns[position],
+            None => {
+                columns.push(Bounds {
+                    column: comparison.left,
+                    lower: vec![],
+                    upper: vec![],
+                });
+
+                columns.last_mut().unwrap()
+            }
+        };
+
+        let bound = |inclusive| Bound {
+            parameter: comparison.right,
+            inclusive,
+        };
+
+        bounds.lower.extend(lower.map(bound));
+        bounds.upper.extend(upper.map(bound));
+    }
+
+    columns
+}
+
+/// Returns the hull of one side of a column over the alternatives of a disjunction, given the
+/// first alternative's bounds on the column, or `None` if some alternative leaves it unbounded.
+fn hull<F>(first: &Bounds, rest: &[Vec<Bounds>], side: F) -> Option<Hull>
+where
+    F: Fn(&Bounds) -> &Vec<Bound>,
+{
+    let mut hull = vec![side(first).clone()];
+
+    for alternative in rest {
+        let bounds = alternative
+            .iter()
+            .find(|bounds| bounds.column == first.column)
+            .map(&side)?;
+
+        hull.push(bounds.clone());
+    }
+
+    if hull.iter().any(Vec::is_empty) {
+        None
+    } else {
+        Some(hull)
+    }
+}
+
+fn column_hulls(columns: &mut Vec<ColumnHulls>, column: usize) -> &mut ColumnHulls {
+    match columns.iter().position(|hulls| hulls.column == column) {
+        Some(position) => &mut columns[position],
+        None => {
+            columns.push(ColumnHulls {
+                column,
+                lower: vec![],
+                upper: vec![],
+                domain: None,
+            });
+
+            columns.last_mut().unwrap()
+        }
+    }
+}
+
+/// Returns the bound whose value is furthest in the direction of `towards`, preferring exclusive
+/// bounds between equal values if `exclusive` and inclusive ones otherwise.
+fn extreme<I>(bounds: I, arguments: &[Value], towards: Ordering, exclusive: bool) -> Option<Bound>
+where
+    I: Iterator<Item = Bound>,
+{
+    bounds.reduce(|extreme, bound| {
+        match arguments[bound.parameter].cmp(&arguments[extreme.parameter]) {
+            Ordering::Equal if bound.inclusive != exclusive => bound,
+            ordering if ordering == towards => bound,
+            _ => extreme,
+        }
+    })
+}
+
+/// Returns whether every value within `upper` lies below every value within `lower`. Bounds on
+/// values of different types are never apart.
+fn apart(upper: Bound, upper_args: &[Value], lower: Bound, lower_args: &[Value]) -> bool {
+    let upper_value = &upper_args[upper.parameter];
+    let lower_value = &lower_args[lower.parameter];
+
+    if mem::discriminant(upper_value) != mem::discriminant(lower_value) {
+        return false;
+    }
+
+    match upper_value.cmp(lower_value) {
+        Ordering::Less => true,
+        Ordering::Equal => !(upper.inclusive && lower.inclusive),
+        Ordering::Greater => false,
+    }
+}
+
+/// A bound's value and whether it is inclusive.
+type Endpoint<'a> = (&'a Value, bool);
+
+fn endpoint(bound: Option<Bound>, args: &[Value]) -> Option<Endpoint<'_>> {
+    bound.map(|bound| (&args[bound.parameter], bound.inclusive))
+}
+
+/// Returns the tighter of two endpoints, which is the one furthest in the direction of `inwards`.
+/// Endpoints of different types aren't compared, and the first is kept.
+fn tighter<'a>(
+    a: Option<Endpoint<'a>>,
+    b: Option<Endpoint<'a>>,
+    inwards: Ordering,
+) -> Option<Endpoint<'a>> {
+    match (a, b) {
+        (Some(a), Some(b)) if mem::discriminant(a.0) == mem::discriminant(b.0) => {
+            match b.0.cmp(a.0) {
+                Ordering::Equal if !b.1 => Some(b),
+                ordering if ordering == inwards => Some(b),
+                _ => Some(a),
+            }
+        }
+        (None, b) => b,
+        (a, _) => a,
+    }
+}
+
+/// Returns whether some value of the domain lies between two endpoints. Endpoints of a different
+/// type than the domain's values don't narrow it.
+fn meets(domain: &Domain, lower: Option<Endpoint>, upper: Option<Endpoint>) -> bool {
+    match domain {
+        Domain::Values(values) => values.iter().any(|value| {
+            let within = |endpoint: Option<Endpoint>, outwards: Ordering| match endpoint {
+                Some((bound, inclusive))
+                    if mem::discriminant(bound) == mem::discriminant(value) =>
+                {
+                    match value.cmp(bound) {
+                        Ordering::Equal => inclusive,
+                        ordering => ordering != outwards,
+                    }
+                }
+                _ => true,
+            };
+
+            within(lower, Ordering::Less) && within(upper, Ordering::Greater)
+        }),
+        Domain::Range(min, max) => {
+            if mem::discriminant(min) != mem::discriminant(max) {
+                return true;
+            }
+
+            let (lower, lower_inclusive) =
+                tighter(Some((min, true)), lower, Ordering::Greater).unwrap();
+            let (upper, upper_inclusive) =
+                tighter(Some((max, true)), upper, Ordering::Less).unwrap();
+
+            match lower.cmp(upper) {
+                // Integers and timestamps have no values strictly between consecutive ones.
+                Ordering::Less => match (lower, upper) {
+                    (Value::Integer(lower), Value::Integer(upper))
+                        if !lower_inclusive && !upper_inclusive =>
+                    {
+                        upper - lower > 1
+                    }
+                    (Value::Timestamp(lower), Value::Timestamp(upper))
+                        if !lower_inclusive && !upper_inclusive =>
+                    {
+                        upper.saturating_sub(*lower) > 1
+                    }
+                    _ => true,
+                },
+                Ordering::Equal => lower_inclusive && upper_inclusive,
+                Ordering::Greater => false,
+            }
+        }
+    }
+}
+
+/// Returns whether no value of a column's domains lies within all of its intervals, so that the
+/// requests can't cover a common row.
+fn outside_domains(intervals: &[(&Interval, &[Value])]) -> bool {
+    if intervals
+        .iter()
+        .all(|(interval, _)| interval.domain.is_none())
+    {
+        return false;
+    }
+
+    let (lower, upper) = intervals
+        .iter()
+        .fold((None, None), |(lower, upper), (interval, args)| {
+            (
+                tighter(lower, endpoint(interval.lower, args), Ordering::Greater),
+                tighter(upper, endpoint(interval.upper, args), Ordering::Less),
+            )
+        });
+
+    intervals
+        .iter()
+        .filter_map(|(interval, _)| interval.domain.as_deref())
+        .any(|domain| !meets(domain, lower, upper))
+}
+
+/// Returns whether the intervals of two requests are disjoint on some column they both bound, or
+/// lie outside the domain declared for a column.
+pub fn disjoint(p: &[Interval], p_args: &[Value], q: &[Interval], q_args: &[Value]) -> bool {
+    let (mut i, mut j) = (0, 0);
+
+    while i < p.len() || j < q.len() {
+        let ordering = match (p.get(i), q.get(j)) {
+            (Some(p_interval), Some(q_interval)) => p_interval.column.cmp(&q_interval.column),
+            (Some(_), None) => Ordering::Less,
+            _ => Ordering::Greater,
+        };
+
+        match ordering {
+            Ordering::Less => {
+                if outside_domains(&[(&p[i], p_args)]) {
+                    return true;
+                }
+
+                i += 1;
+            }
+            Ordering::Greater => {
+                if outside_domains(&[(&q[j], q_args)]) {
+                    return true;
+                }
+
+                j += 1;
+            }
+            Ordering::Equal => {
+                let (p_interval, q_interval) = (&p[i], &q[j]);
+
+                if let (Some(p_upper), Some(q_lower)) = (p_interval.upper, q_interval.lower) {
+                    if apart(p_upper, p_args, q_lower, q_args) {
+                        return true;
+                    }
+                }
+
+                if let (Some(q_upper), Some(p_lower)) = (q_interval.upper, p_interval.lower) {
+                    if apart(q_upper, q_args, p_lower, p_args) {
+                        return true;
+                    }
+                }
+
+                if outside_domains(&[(p_interval, p_args), (q_interval, q_args)]) {
+                    return true;
+                }
+
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ComparisonOperator::*;
+
+    /// A conjunction of comparisons of a column with the parameter of the same position.
+    fn bounds(comparisons: &[(ComparisonOperator, usize)]) -> Predicate {
+        Predicate::conjunction(
+            comparisons
+                .iter()
+                .enumerate()
+                .map(|(parameter, &(operator, column))| {
+                    Predicate::comparison(operator, column, parameter)
+                })
+                .collect(),
+        )
+    }
+
+    fn integers(values: &[usize]) -> Vec<Value> {
+        values.iter().map(|&value| Value::Integer(value)).collect()
+    }
+
+    fn disjoint_requests(p: &Predicate, p_args: &[usize], q: &Predicate, q_args: &[usize]) -> bool {
+        let (p_args, q_args) = (integers(p_args), integers(q_args));
+        let p_intervals = IntervalTemplate::new(p, &[]).unwrap().summarize(&p_args);
+        let q_intervals = IntervalTemplate::new(q, &[]).unwrap().summarize(&q_args);
+
+        let p_first = disjoint(&p_intervals, &p_args, &q_intervals, &q_args);
+        let q_first = disjoint(&q_intervals, &q_args, &p_intervals, &p_args);
+        assert_eq!(p_first, q_first);
+        p_first
+    }
+
+    #[test]
+    fn closed_bounds_meeting_at_a_value_overlap() {
+        let below = bounds(&[(Le, 0)]);
+        let above = bounds(&[(Ge, 0)]);
+
+        assert!(!disjoint_requests(&below, &[5], &above, &[5]));
+        assert!(disjoint_requests(&below, &[4], &above, &[5]));
+    }
+
+    #[test]
+    fn open_bounds_meeting_at_a_value_are_disjoint() {
+        assert!(disjoint_requests(
+            &bounds(&[(Lt, 0)]),
+            &[5],
+            &bounds(&[(Ge, 0)]),
+            &[5]
+        ));
+        assert!(disjoint_requests(
+            &bounds(&[(Le, 0)]),
+            &[5],
+            &bounds(&[(Gt, 0)]),
+            &[5]
+        ));
+        assert!(disjoint_requests(
+            &bounds(&[(Lt, 0)]),
+            &[5],
+            &bounds(&[(Gt, 0)]),
+            &[5]
+        ));
+        assert!(!disjoint_requests(
+            &bounds(&[(Lt, 0)]),
+            &[6],
+            &bounds(&[(Gt, 0)]),
+            &[5]
+        ));
+    }
+
+    #[test]
+    fn the_tightest_bound_on_each_side_is_kept() {
+        // 2 <= x < 6 AND x <= 9 lies below 6, whichever order the upper bounds come in.
+        let range = bounds(&[(Ge, 0), (Lt, 0), (Le, 0)]);
+        let point = bounds(&[(Eq, 0)]);
+
+        assert!(disjoint_requests(&range, &[2, 6, 9], &point, &[6]));
+        assert!(disjoint_requests(&range, &[2, 9, 6], &point, &[7]));
+        assert!(!disjoint_requests(&range, &[2, 9, 6], &point, &[6]));
+        assert!(!disjoint_requests(&range, &[2, 6, 9], &point, &[2]));
+
+        // Between equal values, the exclusive bound is the tighter one.
+        assert!(disjoint_requests(&range, &[2, 6, 6], &point, &[6]));
+    }
+
+    #[test]
+    fn inequalities_bound_nothing() {
+        assert!(IntervalTemplate::new(&bounds(&[(Ne, 0)]), &[]).is_none());
+
+        // The upper bound still applies alongside the inequality.
+        let bounded = bounds(&[(Ne, 0), (Le, 0)]);
+        assert!(disjoint_requests(
+            &bounded,
+            &[5, 3],
+            &bounds(&[(Eq, 0)]),
+            &[5]
+        ));
+        assert!(!disjoint_requests(
+            &bounded,
+            &[5, 9],
+            &bounds(&[(Eq, 0)]),
+            &[5]
+        ));
+    }
+
+    #[test]
+    fn empty_intersections_are_disjoint() {
+        let range = bounds(&[(Ge, 0), (Le, 0)]);
+
+        assert!(disjoint_requests(&range, &[1, 4], &range, &[5, 9]));
+        assert!(!disjoint_requests(&range, &[1, 5], &range, &[5, 9]));
+        assert!(disjoint_requests(
+            &bounds(&[(Eq, 0)]),
+            &[3],
+            &bounds(&[(Eq, 0)]),
+            &[4]
+        ));
+
+        // The ranges overlap on column 0 but not on column 1.
+        let two_columns = bounds(&[(Ge, 0), (Le, 0), (Eq, 1)]);
+        assert!(disjoint_requests(
+            &two_columns,
+            &[1, 9, 3],
+            &two_columns,
+            &[5, 7, 4]
+        ));
+        assert!(!disjoint_requests(
+            &two_columns,
+            &[1, 9, 3],
+            &two_columns,
+            &[5, 7, 3]
+        ));
+    }
+
+    #[test]
+    fn bounds_on_different_columns_or_types_overlap() {
+        assert!(!disjoint_requests(
+            &bounds(&[(Lt, 0)]),
+            &[1],
+            &bounds(&[(Gt, 1)]),
+            &[9]
+        ));
+
+        let below = IntervalTemplate::new(&bounds(&[(Lt, 0)]), &[]).unwrap();
+        let p_args = vec![Value::Integer(1)];
+        let q_args = vec![Value::Timestamp(9)];
+
+        assert!(!disjoint(
+            &below.summarize(&p_args),
+            &p_args,
+            &below.summarize(&q_args),
+            &q_args
+        ));
+    }
+}
@@ -1,40 +1,166 @@
-#![feature(drain_filter)]
-
-use crate::predicate::{ComparisonOperator, Connective, Predicate, Value};
-use fnv::FnvHashSet;
-use rand::Rng;
+use crate::adaptive::{AdaptiveTable, Layout, TableCosts};
+use crate::admission::{Admission, Permit};
+use crate::bloom::{Key, KeyFilter};
+use crate::clock::{Clock, SystemClock};
+use crate::columns::ColumnSet;
+use crate::filters::{FilterRecorder, FilterReport, Route};
+use crate::graph::{ConflictGraph, TemplateNode, WaitRecorder};
+use crate::hot_keys::HotKeys;
+use crate::interval::{Interval, IntervalTemplate};
+use crate::memory::MemoryAccount;
+use crate::observer::TransactionObserver;
+use crate::predicate::{ComparisonOperator, Connective, Domain, Expression, Predicate, Value};
+use crate::program::Program;
+use crate::sampling::{ConflictReport, ConflictSampler};
+use crate::shared_reads::SharedReads;
+use crate::sync::{Arc, AtomicBool, Condvar, Mutex, MutexGuard, Ordering, RwLockReadGuard};
+use crate::union_find::UnionFind;
+use fnv::{FnvHashMap, FnvHashSet};
+use std::cell::RefCell;
+use std::cmp;
 use std::str::FromStr;
-use std::sync::{Arc, Condvar, Mutex, WaitTimeoutResult};
-use std::time::Duration;
+use std::sync::atomic::AtomicUsize;
+use std::time::{Duration, Instant};
+use std::{hint, mem, slice, thread};
+
+#[cfg(feature = "tracing")]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        tracing::trace!($($arg)*)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace {
+    ($($arg:tt)*) => {};
+}
 
+pub mod adaptive;
+mod admission;
+mod bloom;
+pub mod clock;
+mod columns;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filters;
+pub mod graph;
+#[cfg(feature = "guard")]
+pub mod guard;
+mod hot_keys;
+#[cfg(feature = "internals")]
+#[doc(hidden)]
+pub mod internals;
+mod interval;
+mod memory;
+pub mod observer;
 pub mod predicate;
+mod program;
+pub mod sampling;
+mod shared_reads;
+pub mod simulation;
 mod solver;
+pub mod sql;
+mod sync;
 mod union_find;
 
 const FILTER_MAGNITUDE: usize = 1024;
+const DEFAULT_BLOWUP_LIMIT: usize = 64;
+const ARGUMENT_POOL_CAPACITY: usize = 64;
+
+thread_local! {
+    static ARGUMENT_POOL: RefCell<Vec<Vec<Value>>> = const { RefCell::new(vec![]) };
+}
+
+fn take_arguments(arguments: &[Value]) -> Vec<Value> {
+    let mut buffer = ARGUMENT_POOL
+        .with(|pool| pool.borrow_mut().pop())
+        .unwrap_or_default();
+
+    buffer.extend_from_slice(arguments);
+    buffer
+}
+
+fn recycle_arguments(request: Arc<Request>) {
+    if let Ok(request) = Arc::try_unwrap(request) {
+        let mut buffer = request.arguments;
+        buffer.clear();
+
+        ARGUMENT_POOL.with(|pool| {
+            let mut pool = pool.borrow_mut();
+            if pool.len() < ARGUMENT_POOL_CAPACITY {
+                pool.push(buffer);
+            }
+        });
+    }
+}
 
 #[derive(Clone)]
 pub struct RequestTemplate {
     table: usize,
-    read_columns: FnvHashSet<usize>,
-    write_columns: FnvHashSet<usize>,
+    read_columns: ColumnSet,
+    write_columns: ColumnSet,
     predicate: Predicate,
+    derived: Vec<Expression>,
+    domains: Vec<(usize, Domain)>,
 }
 
 impl RequestTemplate {
+    /// # Panics
+    ///
+    /// Panics if the predicate nests deeper than `predicate::MAX_DEPTH`.
     pub fn new(
         table: usize,
         read_columns: FnvHashSet<usize>,
         write_columns: FnvHashSet<usize>,
         predicate: Predicate,
     ) -> RequestTemplate {
+        assert!(
+            predicate.depth() <= predicate::MAX_DEPTH,
+            "predicate nests deeper than {}",
+            predicate::MAX_DEPTH
+        );
+
         RequestTemplate {
             table,
-            read_columns,
-            write_columns,
+            read_columns: ColumnSet::new(&read_columns),
+            write_columns: ColumnSet::new(&write_columns),
             predicate,
+            derived: vec![],
+            domains: vec![],
         }
     }
+
+    /// Adds parameters computed from the supplied arguments, such as `?2 + ?3`. They are numbered
+    /// after the supplied arguments in order, so with four arguments the first derived parameter
+    /// is `?4`, and each expression may refer to the ones before it. The predicate compares columns
+    /// to derived parameters like any other, so conflicts between expressions are solved exactly
+    /// rather than falling back to whole-column conflicts.
+    pub fn with_derived(mut self, derived: Vec<Expression>) -> RequestTemplate {
+        self.derived = derived;
+        self
+    }
+
+    /// Declares the values that `column` holds in every row the template's requests cover,
+    /// replacing any domain declared for it before. With interval pruning, two requests whose
+    /// intervals on the column only overlap outside the domain of either are known not to
+    /// conflict, such as `start_time <= 7` and `start_time >= 1` when start times are multiples
+    /// of 8. Rows aren't checked against the domain, so conflicts over rows outside it are missed.
+    pub fn with_domain(mut self, column: usize, domain: Domain) -> RequestTemplate {
+        self.domains.retain(|&(declared, _)| declared != column);
+        self.domains.push((column, domain));
+        self
+    }
+
+    pub fn table(&self) -> usize {
+        self.table
+    }
+
+    /// Moves the template to another table, such as when combining the tables of several
+    /// workloads into one `Dibs`.
+    pub fn with_table(mut self, table: usize) -> RequestTemplate {
+        self.table = table;
+        self
+    }
 }
 
 pub enum RequestVariant {
@@ -45,89 +171,650 @@ pub enum RequestVariant {
 pub struct Request {
     group_id: usize,
     transaction_id: usize,
+    priority: usize,
+    savepoint: usize,
     variant: RequestVariant,
+    /// The prepared template the request was built from, which ad hoc requests keep so that
+    /// their potential conflicts can be looked up rather than recomputed.
+    prepared_id: Option<usize>,
     arguments: Vec<Value>,
-    completed: (Mutex<bool>, Condvar),
+    intervals: Vec<Interval>,
+    key: Option<Key>,
+    upgrade: bool,
+    preempted: Arc<AtomicBool>,
+    validated: AtomicBool,
+    /// The hash under which identical reads of read-only transactions may share the request.
+    share_hash: Option<u64>,
+    /// Whether the acquire that made the request has finished waiting for conflicting requests.
+    granted: AtomicBool,
+    /// The number of requests that had been added to its table's residual bucket when the request
+    /// was added to its own bucket, counting itself if that is the residual bucket.
+    residual_epoch: sync::AtomicUsize,
+    is_completed: AtomicBool,
+    completed: Mutex<Completion>,
+}
+
+struct Completion {
+    completed: bool,
+    /// The batches of requests being awaited that include this one.
+    batches: Vec<Arc<Batch>>,
+}
+
+/// Wakes a thread awaiting several requests once the last of them completes.
+struct Batch {
+    remaining: Mutex<usize>,
+    cvar: Condvar,
+}
+
+impl Batch {
+    fn complete_one(&self) {
+        let mut remaining = lock(&self.remaining);
+        *remaining -= 1;
+
+        if *remaining == 0 {
+            self.cvar.notify_all();
+        }
+    }
+}
+
+/// Locks one of the mutexes that requests are awaited with, recovering it if a thread panicked
+/// while holding it. Each guards flags and counts that are updated in a single step, so a panic
+/// (in a `Clock`, say) can't leave them inconsistent, and waiters that went on to fail because of
+/// it would only stall the transactions that wait on theirs.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        trace!("recovered poisoned lock");
+        poisoned.into_inner()
+    })
 }
 
 impl Request {
     pub fn new(
-        group_id: usize,
-        transaction_id: usize,
+        transaction: &Transaction,
         variant: RequestVariant,
         arguments: Vec<Value>,
+        upgrade: bool,
     ) -> Request {
+        let prepared_id = match variant {
+            RequestVariant::AdHoc(_) => None,
+            RequestVariant::Prepared(template_id) => Some(template_id),
+        };
+
         Request {
-            group_id,
-            transaction_id,
+            group_id: transaction.group_id,
+            transaction_id: transaction.transaction_id,
+            priority: transaction.priority,
+            savepoint: transaction.savepoint,
             variant,
+            prepared_id,
             arguments,
-            completed: (Mutex::new(false), Condvar::new()),
+            intervals: vec![],
+            key: None,
+            upgrade,
+            preempted: Arc::clone(&transaction.preempted),
+            validated: AtomicBool::new(true),
+            share_hash: None,
+            granted: AtomicBool::new(false),
+            residual_epoch: sync::AtomicUsize::new(0),
+            is_completed: AtomicBool::new(false),
+            completed: Mutex::new(Completion {
+                completed: false,
+                batches: vec![],
+            }),
         }
     }
 
     pub fn complete(&self) {
-        let (lock, cvar) = &self.completed;
-        *lock.lock().unwrap() = true;
-        cvar.notify_all();
+        self.is_completed.store(true, Ordering::Release);
+
+        let batches = {
+            let mut completion = lock(&self.completed);
+            completion.completed = true;
+            mem::take(&mut completion.batches)
+        };
+
+        for batch in batches {
+            batch.complete_one();
+        }
+    }
+
+    fn template_id(&self) -> Option<usize> {
+        match self.variant {
+            RequestVariant::AdHoc(_) => None,
+            RequestVariant::Prepared(template_id) => Some(template_id),
+        }
+    }
+
+    /// Blocks until the request completes or `timeout` elapses on `clock`, returning whether it
+    /// completed.
+    pub fn await_completion(
+        &self,
+        timeout: Duration,
+        strategy: WaitStrategy,
+        clock: &dyn Clock,
+    ) -> bool {
+        Request::await_all(&[self], timeout, strategy, clock)
     }
 
-    pub fn await_completion(&self, timeout: Duration) -> WaitTimeoutResult {
-        let (lock, cvar) = &self.completed;
-        cvar.wait_timeout_while(lock.lock().unwrap(), timeout, |completed| !*completed)
-            .unwrap()
-            .1
+    /// Blocks until all of `requests` complete or `timeout` elapses on `clock`, returning whether
+    /// they all completed. A parked waiter is woken once, by the last of them to complete, rather
+    /// than once for each.
+    pub fn await_all(
+        requests: &[&Request],
+        timeout: Duration,
+        strategy: WaitStrategy,
+        clock: &dyn Clock,
+    ) -> bool {
+        let deadline = clock.now() + timeout;
+        let all_completed = || {
+            requests
+                .iter()
+                .all(|request| request.is_completed.load(Ordering::Acquire))
+        };
+
+        match strategy {
+            WaitStrategy::Park => {}
+
+            WaitStrategy::SpinThenPark { spins, yields } => {
+                for _ in 0..spins {
+                    if all_completed() {
+                        return true;
+                    }
+
+                    hint::spin_loop();
+                }
+
+                for _ in 0..yields {
+                    if all_completed() {
+                        return true;
+                    }
+
+                    thread::yield_now();
+                }
+            }
+
+            WaitStrategy::Backoff { initial, max } => {
+                let mut sleep = initial;
+
+                while !all_completed() {
+                    let remaining = deadline.saturating_duration_since(clock.now());
+                    if remaining == Duration::default() {
+                        return false;
+                    }
+
+                    clock.sleep(sleep.min(remaining));
+                    sleep = (sleep * 2).min(max);
+                }
+
+                return true;
+            }
+        }
+
+        let batch = Arc::new(Batch {
+            remaining: Mutex::new(0),
+            cvar: Condvar::new(),
+        });
+
+        for request in requests {
+            let mut completion = lock(&request.completed);
+
+            if !completion.completed {
+                *lock(&batch.remaining) += 1;
+                completion.batches.push(Arc::clone(&batch));
+            }
+        }
+
+        let mut remaining = lock(&batch.remaining);
+
+        while *remaining > 0 {
+            let timeout = deadline.saturating_duration_since(clock.now());
+            if timeout == Duration::default() {
+                break;
+            }
+
+            remaining = match batch
+                .cvar
+                .wait_timeout(remaining, clock.poll_interval(timeout))
+            {
+                Ok((remaining, _)) => remaining,
+                Err(poisoned) => poisoned.into_inner().0,
+            };
+        }
+
+        *remaining == 0
     }
 }
 
 struct PreparedRequest {
     template: RequestTemplate,
     filter: Option<usize>,
-    conflicts: Vec<Option<Predicate>>,
+    normalized: Option<Predicate>,
+    intervals: Option<IntervalTemplate>,
+    key: Option<(usize, usize)>,
+    conflicts: Vec<Option<Arc<Program>>>,
+    upgrade_conflicts: Vec<Option<Arc<Program>>>,
+    /// The clusters of this template's predicate with each template's, for solving the ad hoc
+    /// requests of a grouped table.
+    clusters: Vec<Option<Vec<(Predicate, Predicate)>>>,
+    /// The bucket of the template's table that its requests go to on a grouped table, which holds
+    /// only the requests of templates that may conflict with it, directly or through another.
+    /// `None` for the point templates of a grouped table, whose requests go to its residual bucket.
+    group: Option<usize>,
+    parameters: Vec<(usize, usize)>,
+    /// Whether the template is one of the point templates of its table.
+    point: bool,
+}
+
+/// The in-flight requests that may conflict with a request routed to a bucket, along with a
+/// filter over their keys and queues for their hot keys.
+///
+/// Requests are kept densely so that solving scans only live requests. Each is also given a slot
+/// that stays valid until it is removed, so that its transaction can remove it without searching.
+#[derive(Default)]
+struct Bucket {
+    requests: Vec<Arc<Request>>,
+    /// The slot of each request in `requests`.
+    slots: Vec<usize>,
+    /// The index in `requests` of the request in each slot, or `usize::MAX` for a free slot.
+    positions: Vec<usize>,
+    free_slots: Vec<usize>,
+    keys: KeyFilter,
+    hot_keys: HotKeys,
+    shared_reads: SharedReads,
+}
+
+impl Bucket {
+    /// Adds a request and returns its slot.
+    fn push(&mut self, request: Arc<Request>) -> usize {
+        self.keys.insert(request.key);
+        self.hot_keys.push(&request);
+
+        let slot = self.free_slots.pop().unwrap_or_else(|| {
+            self.positions.push(usize::MAX);
+            self.positions.len() - 1
+        });
+
+        self.positions[slot] = self.requests.len();
+        self.shared_reads.register(&request, slot);
+        self.slots.push(slot);
+        self.requests.push(request);
+        slot
+    }
+
+    /// Adds a holder to the entry of a granted request identical to `request`, returning its
+    /// slot, or returns `None` if there is none.
+    fn join(&mut self, request: &Request) -> Option<usize> {
+        self.shared_reads
+            .join(request, &self.requests, &self.positions)
+    }
+
+    /// Releases one holder of the request in `slot`, removing the request once its last holder
+    /// releases it.
+    fn release(&mut self, slot: usize) -> Option<Arc<Request>> {
+        if self.shared_reads.release(slot) {
+            None
+        } else {
+            Some(self.remove(slot))
+        }
+    }
+
+    fn record_conflict(&mut self, key: Key, arguments: &[Value], threshold: usize) -> bool {
+        self.hot_keys
+            .record_conflict(key, arguments, threshold, &self.requests)
+    }
+
+    /// Removes the request in `slot`, moving the last request into its place.
+    fn remove(&mut self, slot: usize) -> Arc<Request> {
+        let position = mem::replace(&mut self.positions[slot], usize::MAX);
+        let request = self.requests.swap_remove(position);
+        self.slots.swap_remove(position);
+
+        if let Some(&moved_slot) = self.slots.get(position) {
+            self.positions[moved_slot] = position;
+        }
+
+        self.free_slots.push(slot);
+        self.shared_reads.remove(&request, slot);
+        self.keys.remove(request.key);
+        self.hot_keys.remove(&request);
+        request
+    }
+}
+
+/// A bucket, along with counts of its requests that can be read without locking it.
+struct LockedBucket {
+    bucket: Mutex<Bucket>,
+    /// The number of requests in the bucket, so that a request scanning many buckets can skip the
+    /// empty ones.
+    occupancy: sync::AtomicUsize,
+    /// The number of requests ever added to the bucket, which only a residual bucket counts.
+    epoch: sync::AtomicUsize,
+}
+
+impl LockedBucket {
+    fn new() -> LockedBucket {
+        LockedBucket {
+            bucket: Mutex::new(Bucket::default()),
+            occupancy: sync::AtomicUsize::new(0),
+            epoch: sync::AtomicUsize::new(0),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Bucket> {
+        self.bucket.lock().unwrap()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.occupancy.load(Ordering::SeqCst) == 0
+    }
+
+    /// Adds a request to the bucket, which `bucket_guard` locks, and returns its slot.
+    fn push(&self, bucket_guard: &mut Bucket, request: Arc<Request>) -> usize {
+        let slot = bucket_guard.push(request);
+        self.occupancy.fetch_add(1, Ordering::SeqCst);
+        slot
+    }
+
+    /// Releases one holder of the request in `slot`, returning the request if it was removed.
+    fn release(&self, slot: usize) -> Option<Arc<Request>> {
+        let request = self.lock().release(slot);
+
+        if request.is_some() {
+            self.occupancy.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        request
+    }
+}
+
+type RequestBucket = Arc<LockedBucket>;
+
+/// The buckets of a table's in-flight requests.
+///
+/// A filtered table spreads the requests whose templates fix its filter column over its
+/// partitions, and keeps the rest in a residual bucket rather than adding them to every partition.
+/// A partitioned request checks the residual bucket after adding itself to its partition, unless
+/// the bucket is empty, and a residual request checks every partition that isn't empty after
+/// adding itself to the residual bucket. Two such requests can then each find the other, and must
+/// not both wait. A partitioned request records how many requests had been added to the residual
+/// bucket when it checked it, and a residual request skips the partitioned requests that checked
+/// after it was added, since those wait on it instead.
+///
+/// A grouped table keeps the requests of each group of its templates in a partition of their own,
+/// and its point requests, which may conflict with any group, in its residual bucket.
+struct TableBuckets {
+    partitions: Vec<RequestBucket>,
+    residual: Option<RequestBucket>,
+}
+
+impl TableBuckets {
+    fn new(num_partitions: usize, residual: bool) -> TableBuckets {
+        TableBuckets {
+            partitions: (0..num_partitions)
+                .map(|_| Arc::new(LockedBucket::new()))
+                .collect(),
+            residual: if residual {
+                Some(Arc::new(LockedBucket::new()))
+            } else {
+                None
+            },
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.partitions
+            .iter()
+            .chain(&self.residual)
+            .all(|bucket| bucket.is_empty())
+    }
+}
+
+/// How solving a request on a filtered table involves the table's residual bucket.
+#[derive(Clone, Copy)]
+enum Residual<'a> {
+    /// The table has no residual bucket.
+    None,
+    /// The request is added to a partition, and checks the residual bucket.
+    Check(&'a LockedBucket),
+    /// The request is added to the residual bucket.
+    Add,
 }
 
-type RequestBucket = Arc<Mutex<Vec<Arc<Request>>>>;
+/// A request that a transaction added to a bucket, and the slot that removes it.
+struct BucketSlot {
+    bucket: RequestBucket,
+    slot: usize,
+    savepoint: usize,
+    /// The bytes charged to the memory account for the request in this slot.
+    bytes: usize,
+}
 
-fn potential_conflict(p: &RequestTemplate, q: &RequestTemplate) -> bool {
+fn potential_conflict(p: &RequestTemplate, q: &RequestTemplate, upgrade: bool) -> bool {
     p.table == q.table
-        && (!p.read_columns.is_disjoint(&q.write_columns)
-            || !p.write_columns.is_disjoint(&q.read_columns)
-            || !p.write_columns.is_disjoint(&q.write_columns))
+        && (p.read_columns.intersects(&q.write_columns)
+            || p.write_columns.intersects(&q.read_columns)
+            || p.write_columns.intersects(&q.write_columns)
+            || (upgrade && p.read_columns.intersects(&q.read_columns)))
+}
+
+/// Returns the columns and parameters of the equalities that every row covered by the template
+/// must satisfy.
+fn equalities(template: &RequestTemplate) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let conjuncts = match &template.predicate {
+        Predicate::Connective(Connective::Conjunction, operands) => operands.as_slice(),
+        Predicate::Connective(Connective::Disjunction, _) => &[],
+        predicate => slice::from_ref(predicate),
+    };
+
+    conjuncts.iter().filter_map(|conjunct| match conjunct {
+        Predicate::Comparison(comparison) if comparison.operator == ComparisonOperator::Eq => {
+            Some((comparison.left, comparison.right))
+        }
+        _ => None,
+    })
+}
+
+/// Returns whether the template's predicate is a single equality, so that two requests fixing the
+/// same column to the same value cover the same rows.
+fn is_point(template: &RequestTemplate) -> bool {
+    matches!(&template.predicate, Predicate::Comparison(comparison) if comparison.operator == ComparisonOperator::Eq)
+}
+
+/// Returns the column that point requests on `table` are keyed on: its filter column if it has
+/// one, and otherwise the column that the most of its templates fix with an equality.
+fn prepare_key_column(
+    table: usize,
+    filter: Option<usize>,
+    templates: &[RequestTemplate],
+) -> Option<usize> {
+    filter.or_else(|| {
+        let mut counts = FnvHashMap::default();
+
+        for template in templates.iter().filter(|template| template.table == table) {
+            let columns = equalities(template)
+                .map(|(column, _)| column)
+                .collect::<FnvHashSet<_>>();
+
+            for column in columns {
+                *counts.entry(column).or_insert(0) += 1;
+            }
+        }
+
+        counts
+            .into_iter()
+            .max_by_key(|&(column, count)| (count, cmp::Reverse(column)))
+            .map(|(column, _)| column)
+    })
+}
+
+/// Returns the templates of point reads and point writes on `table`, which fix `key_column` to
+/// their only parameter and read or write every column that the table's templates touch.
+fn prepare_point_templates(
+    table: usize,
+    key_column: usize,
+    templates: &[RequestTemplate],
+) -> [RequestTemplate; 2] {
+    let mut columns = templates
+        .iter()
+        .filter(|template| template.table == table)
+        .flat_map(|template| {
+            template
+                .read_columns
+                .iter()
+                .chain(template.write_columns.iter())
+        })
+        .collect::<FnvHashSet<_>>();
+
+    columns.insert(key_column);
+
+    let predicate = Predicate::comparison(ComparisonOperator::Eq, key_column, 0);
+
+    [
+        RequestTemplate::new(
+            table,
+            columns.clone(),
+            Default::default(),
+            predicate.clone(),
+        ),
+        RequestTemplate::new(table, Default::default(), columns, predicate),
+    ]
 }
 
 fn prepare_filter(template: &RequestTemplate, column: usize) -> Option<usize> {
-    match &template.predicate {
-        Predicate::Comparison(comparison)
-            if comparison.operator == ComparisonOperator::Eq && comparison.left == column =>
-        {
-            Some(comparison.right)
+    equalities(template).find_map(|(left, right)| if left == column { Some(right) } else { None })
+}
+
+fn prepare_key(template: &RequestTemplate) -> Option<(usize, usize)> {
+    equalities(template).next()
+}
+
+fn prepare_normalized(template: &RequestTemplate, blowup_limit: usize) -> Option<Predicate> {
+    if solver::dnf_blowup(&template.predicate) < blowup_limit {
+        let mut normalized = template.predicate.clone();
+        normalized.normalize();
+        Some(normalized)
+    } else {
+        None
+    }
+}
+
+fn prepare_parameters(template: &RequestTemplate) -> Vec<(usize, usize)> {
+    let mut parameters = template
+        .predicate
+        .preorder()
+        .filter_map(|node| match node {
+            Predicate::Comparison(comparison) => Some((comparison.left, comparison.right)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    parameters.sort_unstable();
+    parameters.dedup();
+    parameters
+}
+
+fn derive_arguments(
+    derived: &[Expression],
+    mut arguments: Vec<Value>,
+) -> Result<Vec<Value>, AcquireError> {
+    for expression in derived {
+        match expression.evaluate(&arguments) {
+            Some(value) => arguments.push(value),
+            None => {
+                return Err(AcquireError::InvalidArguments(format!(
+                    "derived parameter {} ({}) could not be evaluated",
+                    arguments.len(),
+                    expression
+                )))
+            }
         }
-        Predicate::Connective(_connective @ Connective::Conjunction, operands) => {
-            operands.iter().find_map(|operand| match operand {
-                Predicate::Comparison(comparison)
-                    if comparison.operator == ComparisonOperator::Eq
-                        && comparison.left == column =>
-                {
-                    Some(comparison.right)
-                }
-                _ => None,
-            })
+    }
+
+    Ok(arguments)
+}
+
+fn validate_arguments(
+    parameters: &[(usize, usize)],
+    arguments: &[Value],
+) -> Result<(), AcquireError> {
+    for (i, &(column, parameter)) in parameters.iter().enumerate() {
+        if parameter >= arguments.len() {
+            return Err(AcquireError::InvalidArguments(format!(
+                "parameter {} is referenced but only {} arguments were supplied",
+                parameter,
+                arguments.len()
+            )));
+        }
+
+        if i > 0 {
+            let (prev_column, prev_parameter) = parameters[i - 1];
+
+            if prev_column == column
+                && mem::discriminant(&arguments[prev_parameter])
+                    != mem::discriminant(&arguments[parameter])
+            {
+                return Err(AcquireError::InvalidArguments(format!(
+                    "parameters {} and {} are compared to column {} but have different types",
+                    prev_parameter, parameter, column
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Identifies the type of a value, counting from 1 so that 0 can stand for no type yet.
+fn type_tag(value: &Value) -> usize {
+    match value {
+        Value::Boolean(_) => 1,
+        Value::Integer(_) => 2,
+        Value::String(_) => 3,
+        Value::Timestamp(_) => 4,
+        Value::Bytes(_) => 5,
+        Value::Decimal(_) => 6,
+    }
+}
+
+/// Returns, for each table, a slot per column for the type of the values compared to it.
+fn prepare_column_types(
+    num_tables: usize,
+    prepared_requests: &[PreparedRequest],
+) -> Vec<Vec<AtomicUsize>> {
+    let mut num_columns = vec![0; num_tables];
+
+    for prepared_request in prepared_requests {
+        for &(column, _) in &prepared_request.parameters {
+            let table = prepared_request.template.table;
+            num_columns[table] = cmp::max(num_columns[table], column + 1);
         }
-        _ => None,
     }
+
+    num_columns
+        .into_iter()
+        .map(|num_columns| (0..num_columns).map(|_| AtomicUsize::new(0)).collect())
+        .collect()
 }
 
 fn prepare_conflicts(
     template: &RequestTemplate,
     other_templates: &[RequestTemplate],
-) -> Vec<Option<Predicate>> {
+    upgrade: bool,
+    programs: &mut FnvHashMap<Program, Arc<Program>>,
+) -> Vec<Option<Arc<Program>>> {
     other_templates
         .iter()
         .map(|other_template| {
-            if potential_conflict(template, other_template) {
-                Some(solver::prepare(
+            if potential_conflict(template, other_template, upgrade) {
+                let program = Program::compile(&solver::prepare(
                     &template.predicate,
                     &other_template.predicate,
+                ));
+
+                Some(Arc::clone(
+                    programs
+                        .entry(program)
+                        .or_insert_with_key(|program| Arc::new(program.clone())),
                 ))
             } else {
                 None
@@ -136,10 +823,134 @@ fn prepare_conflicts(
         .collect()
 }
 
+fn prepare_clusters(
+    template: &RequestTemplate,
+    other_templates: &[RequestTemplate],
+    optimization: OptimizationLevel,
+) -> Vec<Option<Vec<(Predicate, Predicate)>>> {
+    match optimization {
+        OptimizationLevel::Grouped => other_templates
+            .iter()
+            .map(|other_template| {
+                if potential_conflict(template, other_template, true) {
+                    Some(solver::prepare_clusters(
+                        &template.predicate,
+                        &other_template.predicate,
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Splits the templates of each grouped table into groups that can never conflict with each
+/// other, and returns the group of each template along with the number of groups of each table.
+/// Two templates share a group if they may conflict, even only when both are acquired for
+/// upgrade, or if both share a group with a third. Templates of other tables are all in group 0.
+///
+/// The point templates of a grouped table, from `num_templates` on, may conflict with every
+/// group, so they are left out of the groups and their requests go to the residual bucket.
+fn prepare_groups(
+    templates: &[RequestTemplate],
+    num_templates: usize,
+    optimizations: &[OptimizationLevel],
+) -> (Vec<Option<usize>>, Vec<usize>) {
+    let mut groups = templates
+        .iter()
+        .map(|template| match optimizations[template.table] {
+            OptimizationLevel::Grouped => None,
+            _ => Some(0),
+        })
+        .collect::<Vec<_>>();
+
+    let mut num_groups = vec![1; optimizations.len()];
+    let mut union_find = UnionFind::default();
+
+    for (table, &optimization) in optimizations.iter().enumerate() {
+        if optimization != OptimizationLevel::Grouped {
+            continue;
+        }
+
+        let table_templates = (0..num_templates)
+            .filter(|&template_id| templates[template_id].table == table)
+            .collect::<Vec<_>>();
+
+        union_find.reset(table_templates.len());
+
+        for (i, &template_id) in table_templates.iter().enumerate() {
+            for (j, &other_template_id) in table_templates.iter().enumerate().skip(i + 1) {
+                if potential_conflict(&templates[template_id], &templates[other_template_id], true)
+                {
+                    union_find.union(i, j);
+                }
+            }
+        }
+
+        let (num_sets, labels) = union_find.labels();
+
+        for (&template_id, &label) in table_templates.iter().zip(labels) {
+            groups[template_id] = Some(label);
+        }
+
+        // A table without templates still gets a bucket.
+        num_groups[table] = num_sets.max(1);
+    }
+
+    (groups, num_groups)
+}
+
 #[derive(Debug)]
 pub enum AcquireError {
     Timeout(usize),
     GroupConflict,
+    InvalidArguments(String),
+    Preempted,
+    WaitBudgetExhausted,
+    ValidationFailed(usize),
+    /// The request would take the memory held by in-flight requests past the limit set with
+    /// `Dibs::enable_memory_accounting`.
+    OverCapacity,
+    /// The transaction's deadline passed before the acquire finished.
+    DeadlineExceeded,
+    /// `Dibs::try_acquire` found conflicting requests of the listed transactions in flight.
+    WouldBlock(Vec<usize>),
+}
+
+/// What an acquire does about the conflicting requests it finds.
+#[derive(Clone, Copy, PartialEq)]
+enum ConflictHandling {
+    /// Wait for them to complete.
+    Wait,
+    /// Ignore them until `Transaction::validate`.
+    Validate,
+    /// Withdraw the request and report their transactions.
+    Report,
+}
+
+/// How an acquire waits for the conflicting requests it found to complete. They are awaited
+/// together, within a single timeout.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WaitStrategy {
+    /// Block until the last of the requests wakes the waiter.
+    Park,
+
+    /// Poll the requests `spins` times, then yield the thread up to `yields` times, and only then
+    /// park. Suited to short conflicts, where parking costs more than the wait itself.
+    SpinThenPark { spins: usize, yields: usize },
+
+    /// Sleep between polls, starting at `initial` and doubling up to `max`. Never parks, so the
+    /// waiter may oversleep the completion by up to `max`.
+    Backoff { initial: Duration, max: Duration },
+}
+
+/// Whether a point request reads or writes its row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessMode {
+    Read,
+    Write,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -164,260 +975,1894 @@ impl FromStr for OptimizationLevel {
     }
 }
 
+/// The observer and clock of the `Dibs` that a transaction acquired from, and when it began.
+struct Observation {
+    observer: std::sync::Arc<dyn TransactionObserver>,
+    clock: std::sync::Arc<dyn Clock>,
+    begin: Instant,
+}
+
 pub struct Transaction {
     group_id: usize,
     transaction_id: usize,
-    buckets: Vec<RequestBucket>,
+    priority: usize,
+    preempted: Arc<AtomicBool>,
+    savepoint: usize,
+    num_savepoints: usize,
+    wait_budget: Option<Duration>,
+    deadline: Option<Instant>,
+    template_offset: usize,
+    slots: Vec<BucketSlot>,
+    optimistic_requests: Vec<(Arc<Request>, Vec<RequestBucket>)>,
+    /// Counts the transaction against the admission limit, from its first acquire until it
+    /// commits or is dropped.
+    permit: Option<Permit>,
+    memory: Option<Arc<MemoryAccount>>,
+    observation: Option<Observation>,
+    /// Whether an acquire failed since the transaction began or last rolled back.
+    failed: bool,
+    read_only: bool,
 }
 
 impl Transaction {
     pub fn new(group_id: usize, transaction_id: usize) -> Transaction {
+        Transaction::with_priority(group_id, transaction_id, 0)
+    }
+
+    /// Creates a transaction that preempts the read-only requests of transactions with a lower
+    /// priority that it conflicts with. Their next `acquire` fails with `AcquireError::Preempted`
+    /// so that they commit and restart sooner, but this transaction still waits for them to commit
+    /// before its own request is granted.
+    pub fn with_priority(group_id: usize, transaction_id: usize, priority: usize) -> Transaction {
         Transaction {
             group_id,
             transaction_id,
-            buckets: vec![],
+            priority,
+            preempted: Arc::new(AtomicBool::new(false)),
+            savepoint: 0,
+            num_savepoints: 0,
+            wait_budget: None,
+            deadline: None,
+            template_offset: 0,
+            slots: vec![],
+            optimistic_requests: vec![],
+            permit: None,
+            memory: None,
+            observation: None,
+            failed: false,
+            read_only: false,
         }
     }
 
-    pub fn commit(self) {
-        let transaction_id = self.transaction_id;
-        for bucket in self.buckets {
-            for request in bucket
-                .lock()
-                .unwrap()
-                .drain_filter(|request| request.transaction_id == transaction_id)
-            {
-                request.complete();
-            }
-        }
+    /// Bounds the total time this transaction may spend blocked across all of its acquires. Once
+    /// the budget is used up, `acquire` fails with `AcquireError::WaitBudgetExhausted` rather than
+    /// waiting for the full timeout again.
+    pub fn set_wait_budget(&mut self, budget: Duration) {
+        self.wait_budget = Some(budget);
     }
-}
 
-pub struct Dibs {
-    prepared_requests: Vec<PreparedRequest>,
-    inflight_requests: Vec<Vec<RequestBucket>>,
-    optimization: OptimizationLevel,
-    blowup_limit: usize,
-    timeout: Duration,
-}
+    /// Declares that the transaction only reads. With `Dibs::enable_shared_reads`, its reads can
+    /// share in-flight entries with identical reads of other read-only transactions. A read-only
+    /// transaction must not acquire write requests.
+    pub fn set_read_only(&mut self) {
+        self.read_only = true;
+    }
 
-impl Dibs {
-    pub fn new(
+    pub fn remaining_wait_budget(&self) -> Option<Duration> {
+        self.wait_budget
+    }
+
+    /// Sets a point in time, on the clock of the `Dibs` the transaction acquires from, by which
+    /// every acquire must finish. Waits are cut short at the deadline, and once it has passed,
+    /// `acquire` fails with `AcquireError::DeadlineExceeded` without waiting.
+    pub fn set_deadline(&mut self, deadline: Instant) {
+        self.deadline = Some(deadline);
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Returns the time left before the deadline as of `now`, or `None` if there is no deadline.
+    pub fn remaining_time(&self, now: Instant) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(now))
+    }
+
+    pub fn template_offset(&self) -> usize {
+        self.template_offset
+    }
+
+    /// Adds `offset` to the template IDs of subsequent acquires. This lets a workload written
+    /// against its own templates run against a `Dibs` whose templates start at `offset`.
+    pub fn set_template_offset(&mut self, offset: usize) {
+        self.template_offset = offset;
+    }
+
+    /// Marks a savepoint. Requests acquired after it can be released with `rollback_to` while
+    /// those acquired before it are kept.
+    pub fn savepoint(&mut self) -> usize {
+        self.num_savepoints += 1;
+        self.savepoint = self.num_savepoints;
+        self.savepoint
+    }
+
+    /// Releases the requests acquired since `savepoint` was marked, including those acquired after
+    /// any savepoints nested inside it. The savepoint itself remains valid.
+    pub fn rollback_to(&mut self, savepoint: usize) {
+        assert!(
+            savepoint > 0 && savepoint <= self.num_savepoints,
+            "unknown savepoint"
+        );
+
+        trace!(
+            transaction_id = self.transaction_id,
+            savepoint,
+            "rollback to savepoint"
+        );
+
+        let (rolled_back, kept): (Vec<_>, _) = mem::take(&mut self.slots)
+            .into_iter()
+            .partition(|slot| slot.savepoint >= savepoint);
+
+        for slot in rolled_back {
+            if let Some(request) = slot.bucket.release(slot.slot) {
+                request.complete();
+                recycle_arguments(request);
+            }
+
+            if let Some(memory) = &self.memory {
+                memory.release(slot.bytes);
+            }
+        }
+
+        self.slots = kept;
+
+        self.savepoint = savepoint;
+        self.failed = false;
+    }
+
+    pub fn is_preempted(&self) -> bool {
+        self.preempted.load(Ordering::Acquire)
+    }
+
+    /// Checks the requests made with `Dibs::acquire_optimistic` against every conflicting request
+    /// that is still in flight and was either acquired pessimistically or already validated. On
+    /// success, this transaction's requests count as validated until it commits. On failure, the
+    /// transaction should be committed to release its requests and then retried.
+    pub fn validate(&self, dibs: &Dibs) -> Result<(), AcquireError> {
+        let _validation_guard = dibs.validation.lock().unwrap();
+
+        for (request, buckets) in &self.optimistic_requests {
+            for bucket in buckets.iter().filter(|bucket| !bucket.is_empty()) {
+                for other_request in bucket.lock().requests.iter() {
+                    if other_request.validated.load(Ordering::Acquire)
+                        && dibs.in_conflict(request, other_request)
+                    {
+                        trace!(
+                            transaction_id = self.transaction_id,
+                            other_transaction_id = other_request.transaction_id,
+                            "validation failed"
+                        );
+
+                        return Err(AcquireError::ValidationFailed(other_request.transaction_id));
+                    }
+                }
+            }
+        }
+
+        for (request, _) in &self.optimistic_requests {
+            request.validated.store(true, Ordering::Release);
+        }
+
+        Ok(())
+    }
+
+    pub fn commit(self) {
+        trace!(transaction_id = self.transaction_id, "commit");
+
+        // Report the outcome before any waiters are woken, so it precedes their unblocks.
+        if let Some(observation) = &self.observation {
+            let duration = observation
+                .clock
+                .now()
+                .saturating_duration_since(observation.begin);
+
+            if self.failed {
+                observation.observer.on_abort(self.transaction_id, duration);
+            } else {
+                observation
+                    .observer
+                    .on_commit(self.transaction_id, duration);
+            }
+        }
+
+        for slot in self.slots {
+            if let Some(request) = slot.bucket.release(slot.slot) {
+                request.complete();
+                recycle_arguments(request);
+            }
+
+            if let Some(memory) = &self.memory {
+                memory.release(slot.bytes);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BucketSummary {
+    pub num_requests: usize,
+    pub template_ids: Vec<Option<usize>>,
+}
+
+/// Counts of the conflicts that `acquire` has encountered since the `Dibs` was created.
+#[derive(Clone, Debug, Default)]
+pub struct ConflictStats {
+    /// Conflicts that the acquiring transaction waited on, including those that timed out.
+    pub num_waits: usize,
+    pub num_timeouts: usize,
+    pub num_group_conflicts: usize,
+    pub num_preemptions: usize,
+    /// Pairs of requests that interval pruning found couldn't conflict without solving them.
+    pub num_pruned: usize,
+    /// Buckets whose key filter was consulted, and those it let a request skip without solving.
+    pub num_key_filter_checks: usize,
+    pub num_key_filter_skips: usize,
+    /// Keys promoted to their own queues, and buckets that requests on them solved against
+    /// through the queue.
+    pub num_hot_key_promotions: usize,
+    pub num_hot_key_solves: usize,
+    /// Transactions that waited for admission, and the nanoseconds they spent waiting in total.
+    pub num_admission_waits: usize,
+    pub admission_wait_nanos: usize,
+    /// Reads that joined the entry of an identical granted read rather than adding their own.
+    pub num_shared_reads: usize,
+}
+
+#[derive(Default)]
+struct ConflictCounters {
+    num_waits: AtomicUsize,
+    num_timeouts: AtomicUsize,
+    num_group_conflicts: AtomicUsize,
+    num_preemptions: AtomicUsize,
+    num_pruned: AtomicUsize,
+    num_key_filter_checks: AtomicUsize,
+    num_key_filter_skips: AtomicUsize,
+    num_hot_key_promotions: AtomicUsize,
+    num_hot_key_solves: AtomicUsize,
+    num_admission_waits: AtomicUsize,
+    admission_wait_nanos: AtomicUsize,
+    num_shared_reads: AtomicUsize,
+}
+
+pub struct Dibs {
+    prepared_requests: Vec<PreparedRequest>,
+    /// The number of templates the `Dibs` was created with, which the point templates follow.
+    num_templates: usize,
+    key_columns: Vec<Option<usize>>,
+    /// The ID of the point read template of each table with a key column, which its point write
+    /// template follows.
+    point_templates: Vec<Option<usize>>,
+    /// The type of the values compared to each column of each table, fixed by the first request
+    /// that compares one, so that the solver never compares values of different types.
+    column_types: Vec<Vec<AtomicUsize>>,
+    inflight_requests: Vec<TableBuckets>,
+    optimizations: Vec<OptimizationLevel>,
+    /// The tables that can switch levels at runtime, whose buckets are kept here rather than in
+    /// `inflight_requests`.
+    adaptive: Vec<Option<AdaptiveTable>>,
+    timeout: Duration,
+    jitter_seed: Option<u64>,
+    wait_strategy: WaitStrategy,
+    sampler: Option<ConflictSampler>,
+    wait_graph: Option<WaitRecorder>,
+    filter_stats: Option<FilterRecorder>,
+    interval_pruning: bool,
+    key_filter_threshold: Option<usize>,
+    hot_key_threshold: Option<usize>,
+    shared_reads: bool,
+    admission: Option<Arc<Admission>>,
+    memory: Option<Arc<MemoryAccount>>,
+    observer: Option<std::sync::Arc<dyn TransactionObserver>>,
+    clock: std::sync::Arc<dyn Clock>,
+    counters: ConflictCounters,
+    validation: Mutex<()>,
+    #[cfg(feature = "guard")]
+    num_guards: AtomicUsize,
+    simulation: Option<simulation::Environment>,
+}
+
+impl Dibs {
+    pub fn new(
         filters: &[Option<usize>],
         templates: &[RequestTemplate],
         optimization: OptimizationLevel,
-        blowup_limit: usize,
+        table_optimizations: Option<&[OptimizationLevel]>,
+        blowup_limit: Option<usize>,
         timeout: Duration,
     ) -> Dibs {
-        let prepared_requests = templates
+        let blowup_limit = blowup_limit.unwrap_or(DEFAULT_BLOWUP_LIMIT);
+
+        let optimizations = match table_optimizations {
+            Some(table_optimizations) => {
+                assert_eq!(
+                    table_optimizations.len(),
+                    filters.len(),
+                    "expected one optimization level per table"
+                );
+
+                table_optimizations.to_vec()
+            }
+            None => vec![optimization; filters.len()],
+        };
+
+        let num_templates = templates.len();
+
+        let key_columns = filters
+            .iter()
+            .enumerate()
+            .map(|(table, &filter)| prepare_key_column(table, filter, templates))
+            .collect::<Vec<_>>();
+
+        let mut point_templates = vec![None; filters.len()];
+        let mut all_templates = templates.to_vec();
+
+        for (table, key_column) in key_columns.iter().enumerate() {
+            if let &Some(key_column) = key_column {
+                point_templates[table] = Some(all_templates.len());
+                all_templates.extend(prepare_point_templates(table, key_column, templates));
+            }
+        }
+
+        let templates = &all_templates[..];
+        let mut programs = FnvHashMap::default();
+        let (groups, num_groups) = prepare_groups(templates, num_templates, &optimizations);
+
+        let prepared_requests: Vec<PreparedRequest> = templates
             .iter()
-            .map(|template| PreparedRequest {
+            .enumerate()
+            .map(|(template_id, template)| PreparedRequest {
                 template: template.clone(),
-                filter: filters[template.table].and_then(|column| prepare_filter(template, column)),
-                conflicts: prepare_conflicts(template, templates),
+                // Prepared tables get filters too, in case they are made adaptive.
+                filter: match optimizations[template.table] {
+                    OptimizationLevel::Prepared | OptimizationLevel::Filtered => {
+                        filters[template.table].and_then(|column| prepare_filter(template, column))
+                    }
+                    _ => None,
+                },
+                normalized: prepare_normalized(template, blowup_limit),
+                intervals: IntervalTemplate::new(&template.predicate, &template.domains),
+                key: prepare_key(template),
+                conflicts: prepare_conflicts(template, templates, false, &mut programs),
+                upgrade_conflicts: prepare_conflicts(template, templates, true, &mut programs),
+                clusters: prepare_clusters(template, templates, optimizations[template.table]),
+                group: groups[template_id],
+                parameters: prepare_parameters(template),
+                point: template_id >= num_templates,
             })
             .collect();
 
+        let column_types = prepare_column_types(filters.len(), &prepared_requests);
+
         let inflight_requests = filters
             .iter()
-            .map(|filter| {
-                let num_partitions = match filter {
-                    Some(_) => FILTER_MAGNITUDE,
-                    None => 1,
-                };
-
-                (0..num_partitions)
-                    .map(|_| Arc::new(Mutex::new(vec![])))
-                    .collect()
+            .zip(&optimizations)
+            .zip(&point_templates)
+            .enumerate()
+            .map(|(table, ((filter, optimization), point_templates))| {
+                match (filter, optimization) {
+                    (Some(_), OptimizationLevel::Filtered) => {
+                        TableBuckets::new(FILTER_MAGNITUDE, true)
+                    }
+                    (_, OptimizationLevel::Grouped) => {
+                        TableBuckets::new(num_groups[table], point_templates.is_some())
+                    }
+                    _ => TableBuckets::new(1, false),
+                }
             })
             .collect();
 
         Dibs {
             prepared_requests,
+            num_templates,
+            key_columns,
+            point_templates,
+            column_types,
             inflight_requests,
-            optimization,
-            blowup_limit,
+            adaptive: optimizations.iter().map(|_| None).collect(),
+            optimizations,
             timeout,
+            jitter_seed: None,
+            wait_strategy: WaitStrategy::Park,
+            sampler: None,
+            wait_graph: None,
+            filter_stats: None,
+            interval_pruning: false,
+            key_filter_threshold: None,
+            hot_key_threshold: None,
+            shared_reads: false,
+            admission: None,
+            memory: None,
+            observer: None,
+            clock: std::sync::Arc::new(SystemClock),
+            counters: ConflictCounters::default(),
+            validation: Mutex::new(()),
+            #[cfg(feature = "guard")]
+            num_guards: AtomicUsize::new(0),
+            simulation: None,
+        }
+    }
+
+    /// Returns the number of templates the `Dibs` was created with.
+    pub fn num_templates(&self) -> usize {
+        self.num_templates
+    }
+
+    /// Returns the column that point requests on `table` are keyed on, or `None` if the table has
+    /// neither a filter column nor a template that fixes a column with an equality.
+    pub fn key_column(&self, table: usize) -> Option<usize> {
+        self.key_columns[table]
+    }
+
+    pub fn set_wait_strategy(&mut self, wait_strategy: WaitStrategy) {
+        self.wait_strategy = wait_strategy;
+    }
+
+    /// Draws the jitter of each acquire's timeout from `seed`, the acquiring transaction's ID and
+    /// the number of requests it holds, instead of from the thread's RNG. A transaction then times
+    /// out after the same waits however the threads are scheduled.
+    pub fn enable_seeded_jitter(&mut self, seed: u64) {
+        self.jitter_seed = Some(seed);
+    }
+
+    /// Records a sample of up to `capacity` conflicts for `conflict_report`. Sampling takes a
+    /// global lock on every conflict, so it is meant for profiling runs.
+    pub fn enable_conflict_sampling(&mut self, capacity: usize) {
+        self.sampler = Some(ConflictSampler::new(capacity));
+    }
+
+    /// Counts the waits between each pair of templates for `conflict_graph`. Like sampling, this
+    /// takes a global lock on every wait.
+    pub fn enable_wait_graph(&mut self) {
+        self.wait_graph = Some(WaitRecorder::default());
+    }
+
+    /// Counts, for `filter_report`, how many requests of each template went to one partition of
+    /// their table and how many to its residual bucket.
+    pub fn enable_filter_stats(&mut self) {
+        self.filter_stats = Some(FilterRecorder::new(self.prepared_requests.len()));
+    }
+
+    /// Lets `table` switch between the prepared and filtered levels with `set_optimization` while
+    /// requests are in flight, starting from its current level, and counts the costs of its
+    /// acquires for `table_costs`. Requests on the table can't be acquired optimistically. Returns
+    /// `false`, leaving the table as it is, if it is at neither level or none of its templates fix
+    /// its filter column.
+    pub fn enable_adaptive(&mut self, table: usize) -> bool {
+        let optimization = self.optimizations[table];
+
+        let adaptable = (optimization == OptimizationLevel::Prepared
+            || optimization == OptimizationLevel::Filtered)
+            && self.prepared_requests.iter().any(|prepared_request| {
+                prepared_request.template.table == table && prepared_request.filter.is_some()
+            });
+
+        if adaptable {
+            let buckets = self.layout_buckets(optimization);
+            self.adaptive[table] = Some(AdaptiveTable::new(optimization, buckets));
+        }
+
+        adaptable
+    }
+
+    fn layout_buckets(&self, optimization: OptimizationLevel) -> TableBuckets {
+        match optimization {
+            OptimizationLevel::Filtered => TableBuckets::new(FILTER_MAGNITUDE, true),
+            _ => TableBuckets::new(1, false),
+        }
+    }
+
+    /// Switches an adaptive table to `optimization`, which must be prepared or filtered, without
+    /// waiting for the requests in flight on it. Returns `false` without switching if the requests
+    /// from before the table's last switch are still in flight, or if the table isn't adaptive.
+    ///
+    /// Until those requests are gone, every acquire on the table also checks the buckets they are
+    /// in. Calling this with the table's current level lets go of those buckets once they empty.
+    pub fn set_optimization(&self, table: usize, optimization: OptimizationLevel) -> bool {
+        assert!(
+            optimization == OptimizationLevel::Prepared
+                || optimization == OptimizationLevel::Filtered,
+            "adaptive tables switch between the prepared and filtered levels"
+        );
+
+        let adaptive = match &self.adaptive[table] {
+            Some(adaptive) => adaptive,
+            None => return false,
+        };
+
+        let mut layout = adaptive.layout.write().unwrap();
+
+        if matches!(&layout.retired, Some(retired) if retired.is_empty()) {
+            layout.retired = None;
+        }
+
+        if layout.optimization == optimization {
+            return true;
+        }
+
+        if layout.retired.is_some() {
+            return false;
+        }
+
+        let buckets = Arc::new(self.layout_buckets(optimization));
+        layout.retired = Some(mem::replace(&mut layout.buckets, buckets));
+        layout.optimization = optimization;
+        true
+    }
+
+    /// Returns the level that `table` is at, which an adaptive table may change.
+    pub fn optimization(&self, table: usize) -> OptimizationLevel {
+        match &self.adaptive[table] {
+            Some(adaptive) => adaptive.layout.read().unwrap().optimization,
+            None => self.optimizations[table],
+        }
+    }
+
+    /// Returns the costs of the acquires on `table` so far, or `None` if it isn't adaptive.
+    pub fn table_costs(&self, table: usize) -> Option<TableCosts> {
+        self.adaptive[table].as_ref().map(AdaptiveTable::costs)
+    }
+
+    /// Returns the number of tables the `Dibs` was created with.
+    pub fn num_tables(&self) -> usize {
+        self.optimizations.len()
+    }
+
+    /// Summarizes the interval that each request's predicate bounds a column to when it is
+    /// acquired, and skips solving for pairs of requests whose intervals on some column are
+    /// disjoint. This only pays off for templates that bound columns to ranges, since the summaries
+    /// cost an allocation per request. Intervals that only overlap outside a domain declared with
+    /// `RequestTemplate::with_domain` are disjoint too.
+    pub fn enable_interval_pruning(&mut self) {
+        self.interval_pruning = true;
+    }
+
+    /// Counts the keys of the requests in each bucket, where a request's key is the value its
+    /// template's predicate fixes a column to. A request with a key arriving at a bucket of at
+    /// least `min_requests` requests skips solving against them if they are all keyed on the same
+    /// column and the filter rules out its key. Smaller buckets are cheaper to solve than to
+    /// filter, so they are always solved.
+    pub fn enable_key_filters(&mut self, min_requests: usize) {
+        self.key_filter_threshold = Some(min_requests);
+    }
+
+    /// Promotes keys whose requests have conflicted `threshold` times in a bucket to their own
+    /// queues. While every request in a bucket is keyed on the same column, a request on a hot key
+    /// only checks the requests in its key's queue, and two requests whose predicates are just the
+    /// equality on the key are checked by their columns alone, without the solver.
+    pub fn enable_hot_keys(&mut self, threshold: usize) {
+        self.hot_key_threshold = Some(threshold);
+    }
+
+    /// Lets the reads of transactions marked with `Transaction::set_read_only` join the entry of an
+    /// identical read of another read-only transaction, with the same prepared template and
+    /// arguments, once that read has been granted. Joining skips solving, and the bucket holds one
+    /// entry for all of them. Preemption only reaches the transaction that made a shared entry,
+    /// so shared reads should not be combined with transaction priorities.
+    pub fn enable_shared_reads(&mut self) {
+        self.shared_reads = true;
+    }
+
+    /// Limits the number of transactions holding requests at once to `max_active`. A transaction
+    /// is admitted on its first acquire, which blocks while the limit is reached, and counts
+    /// against the limit until it commits or is dropped. Waiting for admission is cut short by the
+    /// transaction's deadline and counts against its wait budget.
+    pub fn enable_admission_control(&mut self, max_active: usize) {
+        self.admission = Some(Arc::new(Admission::new(max_active)));
+    }
+
+    /// Returns the number of admitted transactions, or `None` if admission control is not enabled.
+    pub fn num_active_transactions(&self) -> Option<usize> {
+        self.admission
+            .as_ref()
+            .map(|admission| admission.num_active())
+    }
+
+    /// Estimates the memory held by in-flight requests, from their arguments and the templates of
+    /// ad hoc requests. If `limit` is given, a request that would take the total past it fails with
+    /// `AcquireError::OverCapacity` rather than being added. Every acquire and commit updates a
+    /// shared counter, so accounting is off by default.
+    pub fn enable_memory_accounting(&mut self, limit: Option<usize>) {
+        self.memory = Some(Arc::new(MemoryAccount::new(limit)));
+    }
+
+    /// Returns the estimated bytes held by in-flight requests, or `None` if memory accounting is
+    /// not enabled.
+    pub fn inflight_bytes(&self) -> Option<usize> {
+        self.memory.as_ref().map(|memory| memory.bytes())
+    }
+
+    /// Measures timeouts, waits and the durations reported to the observer against `clock`
+    /// rather than the system clock.
+    pub fn set_clock(&mut self, clock: std::sync::Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn clock(&self) -> &std::sync::Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Registers an observer of the lifecycle of transactions that acquire from this `Dibs`,
+    /// replacing any earlier one. Transactions that already began keep the observer they began
+    /// with.
+    pub fn set_observer(&mut self, observer: std::sync::Arc<dyn TransactionObserver>) {
+        self.observer = Some(observer);
+    }
+
+    /// Summarizes the `top` most frequently sampled template pairs and argument values, or returns
+    /// `None` if sampling is not enabled.
+    pub fn conflict_report(&self, top: usize) -> Option<ConflictReport> {
+        self.sampler.as_ref().map(|sampler| sampler.report(top))
+    }
+
+    /// Returns the pairs of templates that may conflict, and the waits between them so far if
+    /// `enable_wait_graph` was called. Point templates get IDs from `num_templates` on, as they do
+    /// for the observer.
+    pub fn conflict_graph(&self) -> ConflictGraph {
+        let templates = self
+            .prepared_requests
+            .iter()
+            .enumerate()
+            .map(|(template_id, prepared_request)| TemplateNode {
+                template_id,
+                table: prepared_request.template.table,
+                writes: !prepared_request.template.write_columns.is_empty(),
+                point: prepared_request.point,
+            })
+            .collect();
+
+        let conflicts = self
+            .prepared_requests
+            .iter()
+            .enumerate()
+            .flat_map(|(template_id, prepared_request)| {
+                prepared_request
+                    .conflicts
+                    .iter()
+                    .enumerate()
+                    .skip(template_id)
+                    .filter(|(_, conflict)| conflict.is_some())
+                    .map(move |(other_template_id, _)| (template_id, other_template_id))
+            })
+            .collect();
+
+        ConflictGraph {
+            templates,
+            conflicts,
+            waits: self
+                .wait_graph
+                .as_ref()
+                .map_or_else(Vec::new, WaitRecorder::edges),
+        }
+    }
+
+    /// Returns the routes that each template's requests took so far, or `None` if
+    /// `enable_filter_stats` wasn't called.
+    pub fn filter_report(&self) -> Option<FilterReport> {
+        self.filter_stats.as_ref().map(|filter_stats| {
+            filter_stats.report(|template_id| {
+                let prepared_request = &self.prepared_requests[template_id];
+                (prepared_request.template.table, prepared_request.filter)
+            })
+        })
+    }
+
+    /// Returns the conflict counts so far. The counters are read one at a time, so the snapshot is
+    /// not atomic.
+    pub fn conflict_stats(&self) -> ConflictStats {
+        ConflictStats {
+            num_waits: self.counters.num_waits.load(Ordering::Relaxed),
+            num_timeouts: self.counters.num_timeouts.load(Ordering::Relaxed),
+            num_group_conflicts: self.counters.num_group_conflicts.load(Ordering::Relaxed),
+            num_preemptions: self.counters.num_preemptions.load(Ordering::Relaxed),
+            num_pruned: self.counters.num_pruned.load(Ordering::Relaxed),
+            num_key_filter_checks: self.counters.num_key_filter_checks.load(Ordering::Relaxed),
+            num_key_filter_skips: self.counters.num_key_filter_skips.load(Ordering::Relaxed),
+            num_hot_key_promotions: self.counters.num_hot_key_promotions.load(Ordering::Relaxed),
+            num_hot_key_solves: self.counters.num_hot_key_solves.load(Ordering::Relaxed),
+            num_admission_waits: self.counters.num_admission_waits.load(Ordering::Relaxed),
+            admission_wait_nanos: self.counters.admission_wait_nanos.load(Ordering::Relaxed),
+            num_shared_reads: self.counters.num_shared_reads.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns a snapshot of the in-flight requests, indexed by table and then by bucket. The
+    /// partitions of a filtered table are followed by its residual bucket, which holds the requests
+    /// whose templates don't fix the filter column. A grouped table has a bucket per group,
+    /// followed by a residual bucket for its point requests if it has point templates. Ad hoc
+    /// requests have no template ID. Buckets are locked one at a time, so the snapshot is not
+    /// atomic across buckets.
+    pub fn inflight_summary(&self) -> Vec<Vec<BucketSummary>> {
+        (0..self.num_tables())
+            .map(|table| {
+                let layout = self.layout(table);
+                let (_, table_buckets) = self.table_buckets(table, &layout);
+
+                table_buckets
+                    .partitions
+                    .iter()
+                    .chain(&table_buckets.residual)
+                    .map(|bucket| {
+                        let bucket_guard = bucket.lock();
+
+                        BucketSummary {
+                            num_requests: bucket_guard.requests.len(),
+                            template_ids: bucket_guard
+                                .requests
+                                .iter()
+                                .map(|request| request.template_id())
+                                .collect(),
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn acquire(
+        &self,
+        transaction: &mut Transaction,
+        template_id: usize,
+        arguments: Vec<Value>,
+    ) -> Result<(), AcquireError> {
+        self.acquire_internal(
+            transaction,
+            template_id,
+            arguments,
+            false,
+            ConflictHandling::Wait,
+        )
+    }
+
+    /// Like `acquire`, but copies the arguments into a buffer taken from a thread-local pool.
+    /// Buffers are returned to the pool when the transaction commits, so a worker that commits
+    /// its own transactions avoids allocating a fresh argument vector for each request.
+    pub fn acquire_borrowed(
+        &self,
+        transaction: &mut Transaction,
+        template_id: usize,
+        arguments: &[Value],
+    ) -> Result<(), AcquireError> {
+        self.acquire_internal(
+            transaction,
+            template_id,
+            take_arguments(arguments),
+            false,
+            ConflictHandling::Wait,
+        )
+    }
+
+    /// Acquires a read request that the transaction intends to follow with a write to the same
+    /// rows. Two such requests are treated as exclusive, so a pair of readers cannot both proceed
+    /// and then deadlock waiting for each other's upgrade.
+    pub fn acquire_for_upgrade(
+        &self,
+        transaction: &mut Transaction,
+        template_id: usize,
+        arguments: Vec<Value>,
+    ) -> Result<(), AcquireError> {
+        self.acquire_internal(
+            transaction,
+            template_id,
+            arguments,
+            true,
+            ConflictHandling::Wait,
+        )
+    }
+
+    /// Records a request without waiting for conflicting requests. The transaction must call
+    /// `Transaction::validate` before committing its changes.
+    pub fn acquire_optimistic(
+        &self,
+        transaction: &mut Transaction,
+        template_id: usize,
+        arguments: Vec<Value>,
+    ) -> Result<(), AcquireError> {
+        self.acquire_internal(
+            transaction,
+            template_id,
+            arguments,
+            false,
+            ConflictHandling::Validate,
+        )
+    }
+
+    /// Like `acquire`, but returns immediately rather than waiting for conflicting requests. If
+    /// any are still in flight, the request is withdrawn and the acquire fails with
+    /// `AcquireError::WouldBlock`, listing the transactions that hold them, so that the caller
+    /// can run something else and try again later. The transaction keeps its other requests.
+    pub fn try_acquire(
+        &self,
+        transaction: &mut Transaction,
+        template_id: usize,
+        arguments: Vec<Value>,
+    ) -> Result<(), AcquireError> {
+        self.acquire_internal(
+            transaction,
+            template_id,
+            arguments,
+            false,
+            ConflictHandling::Report,
+        )
+    }
+
+    /// Acquires the row of `table` whose key column equals `key`, for reading or writing every
+    /// column of the table. Point requests skip deriving and checking arguments, and two of them
+    /// conflict only if their keys are equal and one of them writes. They are routed and solved
+    /// alongside the table's other requests, so they conflict with those as a template that fixes
+    /// the key column would. The table is not shifted by the transaction's template offset, and
+    /// its point templates are reported to the observer and the conflict sampler with IDs from
+    /// `num_templates` on.
+    pub fn acquire_point(
+        &self,
+        transaction: &mut Transaction,
+        table: usize,
+        key: Value,
+        mode: AccessMode,
+    ) -> Result<(), AcquireError> {
+        let read_template_id = self.point_templates[table].ok_or_else(|| {
+            AcquireError::InvalidArguments(format!("table {} has no key column", table))
+        })?;
+
+        let template_id = match mode {
+            AccessMode::Read => read_template_id,
+            AccessMode::Write => read_template_id + 1,
+        };
+
+        self.acquire_template(
+            transaction,
+            template_id,
+            vec![key],
+            false,
+            ConflictHandling::Wait,
+        )
+    }
+
+    /// Acquires every request of a transaction whose requests are all known up front, as
+    /// `acquire_borrowed` would, but ordered by the table and then the bucket that each request
+    /// goes to rather than as listed. Transactions that acquire their requests this way wait for
+    /// each other's in one global order, so they cannot deadlock among themselves. Requests bound
+    /// for the same bucket keep their listed order.
+    pub fn acquire_all(
+        &self,
+        transaction: &mut Transaction,
+        requests: &[(usize, Vec<Value>)],
+    ) -> Result<(), AcquireError> {
+        let mut ordered = requests
+            .iter()
+            .map(|(template_id, arguments)| {
+                let order =
+                    self.acquisition_order(template_id + transaction.template_offset, arguments)?;
+
+                Ok((order, *template_id, arguments))
+            })
+            .collect::<Result<Vec<_>, AcquireError>>()?;
+
+        ordered.sort_by_key(|&(order, _, _)| order);
+
+        for (_, template_id, arguments) in ordered {
+            self.acquire_borrowed(transaction, template_id, arguments)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the table and the index of the bucket that a request goes to, counting a table's
+    /// residual bucket after its partitions.
+    fn acquisition_order(
+        &self,
+        template_id: usize,
+        arguments: &[Value],
+    ) -> Result<(usize, usize), AcquireError> {
+        let prepared_request = &self.prepared_requests[template_id];
+        let table = prepared_request.template.table;
+        let layout = self.layout(table);
+        let (optimization, table_buckets) = self.table_buckets(table, &layout);
+
+        let filter = match optimization {
+            OptimizationLevel::Ungrouped | OptimizationLevel::Grouped => {
+                let num_groups = table_buckets.partitions.len();
+                return Ok((table, prepared_request.group.unwrap_or(num_groups)));
+            }
+            OptimizationLevel::Prepared | OptimizationLevel::Filtered => prepared_request.filter,
+        };
+
+        let num_partitions = table_buckets.partitions.len();
+
+        match (filter, &table_buckets.residual) {
+            (Some(filter), Some(_)) => {
+                let value = if filter < arguments.len() {
+                    arguments.get(filter).cloned()
+                } else {
+                    derive_arguments(&prepared_request.template.derived, arguments.to_vec())?
+                        .get(filter)
+                        .cloned()
+                };
+
+                match value {
+                    Some(Value::Integer(v)) => Ok((table, v % num_partitions)),
+                    _ => Err(AcquireError::InvalidArguments(format!(
+                        "filter parameter {} must be an integer",
+                        filter
+                    ))),
+                }
+            }
+            (None, Some(_)) => Ok((table, num_partitions)),
+            (_, None) => Ok((table, 0)),
+        }
+    }
+
+    /// Locks the layout of `table` if it is adaptive, so that it can't switch while its buckets
+    /// are in use.
+    fn layout(&self, table: usize) -> Option<RwLockReadGuard<'_, Layout>> {
+        self.adaptive[table]
+            .as_ref()
+            .map(|adaptive| adaptive.layout.read().unwrap())
+    }
+
+    /// Returns the level and buckets of `table`, from its `layout` if it is adaptive.
+    fn table_buckets<'a>(
+        &'a self,
+        table: usize,
+        layout: &'a Option<RwLockReadGuard<'_, Layout>>,
+    ) -> (OptimizationLevel, &'a TableBuckets) {
+        match layout {
+            Some(layout) => (layout.optimization, &layout.buckets),
+            None => (self.optimizations[table], &self.inflight_requests[table]),
+        }
+    }
+
+    fn acquire_internal(
+        &self,
+        transaction: &mut Transaction,
+        template_id: usize,
+        arguments: Vec<Value>,
+        upgrade: bool,
+        conflict_handling: ConflictHandling,
+    ) -> Result<(), AcquireError> {
+        let template_id = template_id + transaction.template_offset;
+        self.acquire_template(
+            transaction,
+            template_id,
+            arguments,
+            upgrade,
+            conflict_handling,
+        )
+    }
+
+    fn acquire_template(
+        &self,
+        transaction: &mut Transaction,
+        template_id: usize,
+        arguments: Vec<Value>,
+        upgrade: bool,
+        conflict_handling: ConflictHandling,
+    ) -> Result<(), AcquireError> {
+        let observer = match &self.observer {
+            Some(observer) => observer,
+            None => {
+                return self.acquire_unobserved(
+                    transaction,
+                    template_id,
+                    arguments,
+                    upgrade,
+                    conflict_handling,
+                )
+            }
+        };
+
+        let start = self.clock.now();
+
+        if transaction.observation.is_none() {
+            observer.on_begin(transaction.transaction_id);
+            transaction.observation = Some(Observation {
+                observer: std::sync::Arc::clone(observer),
+                clock: std::sync::Arc::clone(&self.clock),
+                begin: start,
+            });
         }
+
+        let result = self.acquire_unobserved(
+            transaction,
+            template_id,
+            arguments,
+            upgrade,
+            conflict_handling,
+        );
+
+        // A transaction told that an acquire would block can still go on to commit.
+        transaction.failed |= match &result {
+            Ok(()) | Err(AcquireError::WouldBlock(_)) => false,
+            Err(_) => true,
+        };
+
+        observer.on_acquire(
+            transaction.transaction_id,
+            template_id,
+            self.clock.now().saturating_duration_since(start),
+            result.as_ref().err(),
+        );
+
+        result
     }
 
-    pub fn acquire(
+    fn acquire_unobserved(
         &self,
         transaction: &mut Transaction,
         template_id: usize,
         arguments: Vec<Value>,
+        upgrade: bool,
+        conflict_handling: ConflictHandling,
     ) -> Result<(), AcquireError> {
-        let mut conflicting_requests: Vec<Arc<Request>>;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!(
+            "acquire",
+            template_id,
+            transaction_id = transaction.transaction_id,
+            upgrade
+        )
+        .entered();
 
-        match self.optimization {
-            OptimizationLevel::Ungrouped | OptimizationLevel::Grouped => {
-                let mut template = self.prepared_requests[template_id].template.clone();
+        trace!("acquire start");
+
+        if transaction.is_preempted() {
+            return Err(AcquireError::Preempted);
+        }
+
+        if transaction.remaining_time(self.clock.now()) == Some(Duration::default()) {
+            trace!("deadline exceeded");
+            return Err(AcquireError::DeadlineExceeded);
+        }
+
+        if let (Some(admission), None) = (&self.admission, &transaction.permit) {
+            // Admission honors the deadline and the wait budget like any other wait.
+            let limit = [
+                transaction.wait_budget,
+                transaction.remaining_time(self.clock.now()),
+            ]
+            .iter()
+            .flatten()
+            .min()
+            .copied();
+
+            let (permit, waited) = Admission::admit(admission, &*self.clock, limit);
+
+            if let Some(waited) = waited {
+                trace!(
+                    transaction_id = transaction.transaction_id,
+                    "admission wait"
+                );
+                self.counters
+                    .num_admission_waits
+                    .fetch_add(1, Ordering::Relaxed);
+                self.counters
+                    .admission_wait_nanos
+                    .fetch_add(waited.as_nanos() as usize, Ordering::Relaxed);
 
-                if self.optimization == OptimizationLevel::Ungrouped
-                    && solver::dnf_blowup(&template.predicate) < self.blowup_limit
+                if let Some(budget) = &mut transaction.wait_budget {
+                    *budget = budget.saturating_sub(waited);
+                }
+            }
+
+            match permit {
+                Some(permit) => transaction.permit = Some(permit),
+                None if transaction.remaining_time(self.clock.now())
+                    == Some(Duration::default()) =>
                 {
-                    template.predicate.normalize();
+                    trace!("deadline exceeded");
+                    return Err(AcquireError::DeadlineExceeded);
                 }
+                None => return Err(AcquireError::WaitBudgetExhausted),
+            }
+        }
 
-                let request = Arc::new(Request::new(
-                    transaction.group_id,
-                    transaction.transaction_id,
-                    RequestVariant::AdHoc(template.clone()),
-                    arguments,
-                ));
+        let prepared_request = &self.prepared_requests[template_id];
+
+        debug_assert!(
+            !transaction.read_only || prepared_request.template.write_columns.is_empty(),
+            "read-only transaction acquired a write request"
+        );
+
+        let arguments = if prepared_request.point {
+            arguments
+        } else {
+            let arguments = derive_arguments(&prepared_request.template.derived, arguments)?;
+            validate_arguments(&prepared_request.parameters, &arguments)?;
+            arguments
+        };
+
+        let table = prepared_request.template.table;
+        let adaptive = self.adaptive[table].as_ref();
+
+        if adaptive.is_some() && conflict_handling == ConflictHandling::Validate {
+            return Err(AcquireError::InvalidArguments(format!(
+                "table {} is adaptive, so its requests can't be acquired optimistically",
+                table
+            )));
+        }
+
+        let layout = self.layout(table);
+        let (optimization, table_buckets) = self.table_buckets(table, &layout);
+
+        let intervals = match &prepared_request.intervals {
+            Some(intervals) if self.interval_pruning => intervals.summarize(&arguments),
+            _ => vec![],
+        };
 
-                let buckets = &self.inflight_requests[template.table];
+        let key = match prepared_request.key {
+            Some((column, parameter))
+                if self.key_filter_threshold.is_some() || self.hot_key_threshold.is_some() =>
+            {
+                Some(Key::new(column, parameter, &arguments))
+            }
+            _ => None,
+        };
 
-                conflicting_requests = vec![];
+        let (request, bucket, residual) = match optimization {
+            OptimizationLevel::Ungrouped | OptimizationLevel::Grouped => {
+                let mut template = prepared_request.template.clone();
 
-                for bucket in buckets {
-                    conflicting_requests.extend(self.solve_ad_hoc(&request, &template, bucket));
+                if optimization == OptimizationLevel::Ungrouped {
+                    if let Some(normalized) = &prepared_request.normalized {
+                        template.predicate = normalized.clone();
+                    }
                 }
 
-                transaction.buckets.extend(buckets.iter().cloned());
+                let request = Arc::new(Request {
+                    prepared_id: Some(template_id),
+                    intervals,
+                    key,
+                    ..Request::new(
+                        transaction,
+                        RequestVariant::AdHoc(template),
+                        arguments,
+                        upgrade,
+                    )
+                });
+
+                match (prepared_request.group, &table_buckets.residual) {
+                    (Some(group), Some(residual)) => (
+                        request,
+                        &table_buckets.partitions[group],
+                        Residual::Check(residual),
+                    ),
+                    (Some(group), None) => {
+                        (request, &table_buckets.partitions[group], Residual::None)
+                    }
+                    (None, _) => (
+                        request,
+                        table_buckets.residual.as_ref().unwrap(),
+                        Residual::Add,
+                    ),
+                }
             }
 
             OptimizationLevel::Prepared | OptimizationLevel::Filtered => {
-                let prepared_request = &self.prepared_requests[template_id];
+                let share_hash = if self.shared_reads
+                    && transaction.read_only
+                    && !upgrade
+                    && conflict_handling != ConflictHandling::Validate
+                    && prepared_request.template.write_columns.is_empty()
+                {
+                    Some(shared_reads::share_hash(template_id, &arguments))
+                } else {
+                    None
+                };
 
-                let request = Arc::new(Request::new(
-                    transaction.group_id,
-                    transaction.transaction_id,
-                    RequestVariant::Prepared(template_id),
-                    arguments,
-                ));
+                let request = Arc::new(Request {
+                    intervals,
+                    key,
+                    share_hash,
+                    ..Request::new(
+                        transaction,
+                        RequestVariant::Prepared(template_id),
+                        arguments,
+                        upgrade,
+                    )
+                });
 
-                let buckets = &self.inflight_requests[prepared_request.template.table];
+                match (prepared_request.filter, &table_buckets.residual) {
+                    (Some(filter), Some(residual)) => {
+                        let partitions = &table_buckets.partitions;
 
-                match prepared_request.filter {
-                    Some(filter) => {
                         let bucket_index = match &request.arguments[filter] {
-                            &Value::Integer(v) => v % buckets.len(),
-                            _ => panic!("filtering on non-integer columns is not yet supported"),
+                            &Value::Integer(v) => v % partitions.len(),
+                            _ => {
+                                return Err(AcquireError::InvalidArguments(format!(
+                                    "filter parameter {} must be an integer",
+                                    filter
+                                )))
+                            }
                         };
 
-                        let bucket = &buckets[bucket_index];
+                        (
+                            request,
+                            &partitions[bucket_index],
+                            Residual::Check(residual),
+                        )
+                    }
+
+                    (None, Some(residual)) => (request, residual, Residual::Add),
+                    (_, None) => (request, &table_buckets.partitions[0], Residual::None),
+                }
+            }
+        };
 
-                        conflicting_requests = self.solve_prepared(&request, template_id, bucket);
+        // Only requests that are otherwise valid fix the types of columns.
+        self.check_column_types(prepared_request, &request.arguments)?;
 
-                        transaction.buckets.push(Arc::clone(&bucket));
-                    }
+        if let Some(filter_stats) = &self.filter_stats {
+            let route = match residual {
+                Residual::None => Route::Unpartitioned,
+                Residual::Check(_) => Route::Partition,
+                Residual::Add => Route::Residual,
+            };
 
-                    None => {
-                        conflicting_requests = vec![];
+            filter_stats.record_route(template_id, route);
+        }
 
-                        for bucket in buckets {
-                            conflicting_requests.extend(self.solve_prepared(
-                                &request,
-                                template_id,
-                                bucket,
-                            ));
-                        }
+        if request.share_hash.is_some() {
+            if let Some(slot) = bucket.lock().join(&request) {
+                trace!("shared read");
+                self.counters
+                    .num_shared_reads
+                    .fetch_add(1, Ordering::Relaxed);
 
-                        transaction.buckets.extend(buckets.iter().cloned())
-                    }
+                transaction.slots.push(BucketSlot {
+                    bucket: Arc::clone(bucket),
+                    slot,
+                    savepoint: transaction.savepoint,
+                    bytes: 0,
+                });
+
+                return Ok(());
+            }
+        }
+
+        let bytes = match &self.memory {
+            Some(memory) => {
+                let bytes = memory::request_bytes(&request) + memory::BUCKET_ENTRY_BYTES;
+                memory.reserve(bytes)?;
+
+                if transaction.memory.is_none() {
+                    transaction.memory = Some(Arc::clone(memory));
                 }
+
+                bytes
             }
+            None => 0,
         };
 
-        let timeout = self.timeout.mul_f32(rand::thread_rng().gen_range(0.8, 1.2));
+        if conflict_handling == ConflictHandling::Validate {
+            request.validated.store(false, Ordering::Relaxed);
+
+            let slot = bucket.push(&mut bucket.lock(), Arc::clone(&request));
+
+            transaction.slots.push(BucketSlot {
+                bucket: Arc::clone(bucket),
+                slot,
+                savepoint: request.savepoint,
+                bytes,
+            });
+
+            // Validation checks the buckets that solving would have.
+            let mut buckets = vec![Arc::clone(bucket)];
+
+            match residual {
+                Residual::None => {}
+                Residual::Check(_) => buckets.extend(table_buckets.residual.iter().cloned()),
+                Residual::Add => buckets.extend(table_buckets.partitions.iter().cloned()),
+            }
+
+            transaction.optimistic_requests.push((request, buckets));
+
+            return Ok(());
+        }
+
+        let check_start = adaptive.map(|_| self.clock.now());
+        let (slot, mut conflicting_requests) = self.solve(&request, bucket, residual);
+
+        transaction.slots.push(BucketSlot {
+            bucket: Arc::clone(bucket),
+            slot,
+            savepoint: request.savepoint,
+            bytes,
+        });
+
+        if let Residual::Add = residual {
+            for partition in &table_buckets.partitions {
+                conflicting_requests.extend(self.scan_partition(&request, partition));
+            }
+        }
+
+        if let Some(retired) = layout.as_ref().and_then(|layout| layout.retired.as_ref()) {
+            conflicting_requests.extend(self.scan_retired(&request, retired));
+        }
+
+        // The table may switch levels once the request is in its buckets and has found the
+        // requests it conflicts with.
+        drop(layout);
+
+        if let (Some(adaptive), Some(check_start)) = (adaptive, check_start) {
+            adaptive.record_check(self.clock.now().saturating_duration_since(check_start));
+        }
+
+        if let Some(environment) = &self.simulation {
+            environment.shuffle(&mut conflicting_requests);
+        }
+
+        let timeout = self.timeout.mul_f32(self.jitter(transaction));
+        let mut blocking_transaction_ids = vec![];
+
+        let mut awaited = vec![];
 
         for conflicting_request in &conflicting_requests {
+            if let Some(sampler) = &self.sampler {
+                sampler.record(
+                    Some(template_id),
+                    conflicting_request.template_id(),
+                    &request.arguments,
+                );
+            }
+
             if conflicting_request.group_id == transaction.group_id {
+                trace!(
+                    other_transaction_id = conflicting_request.transaction_id,
+                    "group conflict"
+                );
+                self.counters
+                    .num_group_conflicts
+                    .fetch_add(1, Ordering::Relaxed);
                 return Err(AcquireError::GroupConflict);
             }
 
-            if conflicting_request.await_completion(timeout).timed_out() {
-                return Err(AcquireError::Timeout(conflicting_request.transaction_id));
+            if transaction.priority > conflicting_request.priority
+                && self.template(conflicting_request).write_columns.is_empty()
+            {
+                trace!(
+                    other_transaction_id = conflicting_request.transaction_id,
+                    "preempt"
+                );
+                conflicting_request.preempted.store(true, Ordering::Release);
+                self.counters
+                    .num_preemptions
+                    .fetch_add(1, Ordering::Relaxed);
+
+                // The preempted request keeps its rows until its transaction commits, so this one
+                // still waits for it like any other conflicting request.
+            }
+
+            let is_completed = conflicting_request.is_completed.load(Ordering::Acquire);
+
+            if conflict_handling == ConflictHandling::Report {
+                if !is_completed {
+                    blocking_transaction_ids.push(conflicting_request.transaction_id);
+                }
+
+                continue;
+            }
+
+            awaited.push((conflicting_request, is_completed));
+        }
+
+        if !awaited.is_empty() {
+            let remaining = transaction.remaining_time(self.clock.now());
+
+            let wait = [transaction.wait_budget, remaining]
+                .iter()
+                .flatten()
+                .fold(timeout, |wait, &limit| wait.min(limit));
+
+            for &(conflicting_request, _) in &awaited {
+                trace!(
+                    other_template_id = ?conflicting_request.template_id(),
+                    other_transaction_id = conflicting_request.transaction_id,
+                    "conflict wait begin"
+                );
+
+                self.counters.num_waits.fetch_add(1, Ordering::Relaxed);
+
+                if let Some(observer) = &self.observer {
+                    observer.on_block(
+                        transaction.transaction_id,
+                        template_id,
+                        conflicting_request.transaction_id,
+                    );
+                }
+            }
+
+            // The whole conflict set is awaited at once, within a single timeout.
+            let pending = awaited
+                .iter()
+                .filter(|&&(_, is_completed)| !is_completed)
+                .map(|&(conflicting_request, _)| &**conflicting_request)
+                .collect::<Vec<_>>();
+
+            let waited = self.await_conflicts(&pending, wait);
+
+            if let Some(adaptive) = adaptive {
+                adaptive.record_wait(waited);
+            }
+
+            if let Some(budget) = &mut transaction.wait_budget {
+                *budget = budget.saturating_sub(waited);
+            }
+
+            let mut timed_out_on = None;
+
+            for &(conflicting_request, was_completed) in &awaited {
+                let timed_out = !conflicting_request.is_completed.load(Ordering::Acquire);
+                let waited = if was_completed {
+                    Duration::default()
+                } else {
+                    waited
+                };
+
+                if let (Some(wait_graph), Some(other_template_id)) =
+                    (&self.wait_graph, conflicting_request.prepared_id)
+                {
+                    wait_graph.record(template_id, other_template_id, waited, timed_out);
+                }
+
+                if let Some(observer) = &self.observer {
+                    observer.on_unblock(
+                        transaction.transaction_id,
+                        template_id,
+                        conflicting_request.transaction_id,
+                        waited,
+                        timed_out,
+                    );
+                }
+
+                if timed_out {
+                    trace!(
+                        other_template_id = ?conflicting_request.template_id(),
+                        other_transaction_id = conflicting_request.transaction_id,
+                        "conflict wait timeout"
+                    );
+                    timed_out_on.get_or_insert(conflicting_request.transaction_id);
+                } else {
+                    trace!(
+                        other_template_id = ?conflicting_request.template_id(),
+                        other_transaction_id = conflicting_request.transaction_id,
+                        "conflict wait end"
+                    );
+                }
+            }
+
+            if let Some(other_transaction_id) = timed_out_on {
+                self.counters.num_timeouts.fetch_add(1, Ordering::Relaxed);
+
+                return Err(
+                    if matches!(remaining, Some(remaining) if remaining <= wait) {
+                        AcquireError::DeadlineExceeded
+                    } else if wait < timeout {
+                        AcquireError::WaitBudgetExhausted
+                    } else {
+                        AcquireError::Timeout(other_transaction_id)
+                    },
+                );
+            }
+        }
+
+        if !blocking_transaction_ids.is_empty() {
+            trace!("would block");
+
+            // The request is withdrawn, since requests that find it would otherwise wait on a
+            // transaction that isn't waiting for its own.
+            let slot = transaction.slots.pop().unwrap();
+            drop(request);
+
+            if let Some(request) = slot.bucket.release(slot.slot) {
+                request.complete();
+                recycle_arguments(request);
+            }
+
+            if let Some(memory) = &transaction.memory {
+                memory.release(slot.bytes);
             }
+
+            blocking_transaction_ids.sort_unstable();
+            blocking_transaction_ids.dedup();
+            return Err(AcquireError::WouldBlock(blocking_transaction_ids));
+        }
+
+        if request.share_hash.is_some() {
+            request.granted.store(true, Ordering::Release);
         }
 
         Ok(())
     }
 
-    fn solve_ad_hoc(
+    fn jitter(&self, transaction: &Transaction) -> f32 {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        if let Some(environment) = &self.simulation {
+            return environment.jitter();
+        }
+
+        match self.jitter_seed {
+            Some(seed) => StdRng::seed_from_u64(
+                seed ^ (transaction.transaction_id as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+                    ^ transaction.slots.len() as u64,
+            )
+            .gen_range(0.8, 1.2),
+            None => rand::thread_rng().gen_range(0.8, 1.2),
+        }
+    }
+
+    /// Waits for all of `requests` to complete or `timeout` to elapse, returning how long it
+    /// waited.
+    fn await_conflicts(&self, requests: &[&Request], timeout: Duration) -> Duration {
+        if let Some(environment) = &self.simulation {
+            return environment.await_completion(requests, timeout);
+        }
+
+        let wait_start = self.clock.now();
+        Request::await_all(requests, timeout, self.wait_strategy, &*self.clock);
+        self.clock.now().saturating_duration_since(wait_start)
+    }
+
+    /// Fails unless each argument has the type of the arguments that earlier requests compared to
+    /// the same column, fixing the type of columns that no request has compared an argument to.
+    fn check_column_types(
         &self,
-        request: &Arc<Request>,
-        template: &RequestTemplate,
-        bucket: &RequestBucket,
-    ) -> Vec<Arc<Request>> {
-        let mut other_requests = vec![];
+        prepared_request: &PreparedRequest,
+        arguments: &[Value],
+    ) -> Result<(), AcquireError> {
+        let table = prepared_request.template.table;
 
-        {
-            let mut bucket_guard = bucket.lock().unwrap();
-            other_requests.extend(bucket_guard.iter().cloned());
-            bucket_guard.push(Arc::clone(request));
+        for &(column, parameter) in &prepared_request.parameters {
+            let tag = type_tag(&arguments[parameter]);
+
+            match self.column_types[table][column].compare_exchange(
+                0,
+                tag,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {}
+                Err(column_tag) if column_tag == tag => {}
+                Err(_) => {
+                    return Err(AcquireError::InvalidArguments(format!(
+                        "parameter {} ({}) differs in type from earlier arguments for column {}",
+                        parameter, arguments[parameter], column
+                    )))
+                }
+            }
         }
 
-        other_requests.retain(|other_request| {
-            other_request.transaction_id != request.transaction_id && {
-                let other_template = match &other_request.variant {
-                    RequestVariant::AdHoc(t) => t,
-                    &RequestVariant::Prepared(id) => &self.prepared_requests[id].template,
+        Ok(())
+    }
+
+    fn template<'a>(&'a self, request: &'a Request) -> &'a RequestTemplate {
+        match &request.variant {
+            RequestVariant::AdHoc(template) => template,
+            &RequestVariant::Prepared(template_id) => &self.prepared_requests[template_id].template,
+        }
+    }
+
+    /// Returns whether two requests share a column that one of them writes, or that both read if
+    /// `upgrade` is set. Requests built from prepared templates look this up among the templates'
+    /// prepared conflicts.
+    fn potential_conflict(
+        &self,
+        request: &Request,
+        other_request: &Request,
+        upgrade: bool,
+    ) -> bool {
+        match (request.prepared_id, other_request.prepared_id) {
+            (Some(template_id), Some(other_template_id)) => {
+                let prepared_request = &self.prepared_requests[template_id];
+
+                let conflicts = if upgrade {
+                    &prepared_request.upgrade_conflicts
+                } else {
+                    &prepared_request.conflicts
                 };
 
-                potential_conflict(template, other_template)
-                    && match self.optimization {
-                        OptimizationLevel::Ungrouped => solver::solve_dnf(
-                            &template.predicate,
+                conflicts[other_template_id].is_some()
+            }
+            _ => potential_conflict(
+                self.template(request),
+                self.template(other_request),
+                upgrade,
+            ),
+        }
+    }
+
+    fn in_conflict(&self, request: &Request, other_request: &Request) -> bool {
+        if other_request.transaction_id == request.transaction_id {
+            return false;
+        }
+
+        let upgrade = request.upgrade && other_request.upgrade;
+
+        match (&request.variant, &other_request.variant) {
+            (
+                &RequestVariant::Prepared(prepared_id),
+                &RequestVariant::Prepared(other_prepared_id),
+            ) => {
+                let prepared_request = &self.prepared_requests[prepared_id];
+
+                let conflicts = if upgrade {
+                    &prepared_request.upgrade_conflicts
+                } else {
+                    &prepared_request.conflicts
+                };
+
+                // Two point requests on the same table share a row exactly when their keys are
+                // equal.
+                if prepared_request.point && self.prepared_requests[other_prepared_id].point {
+                    return conflicts[other_prepared_id].is_some()
+                        && request.arguments[0] == other_request.arguments[0];
+                }
+
+                match &conflicts[other_prepared_id] {
+                    Some(conflict) => {
+                        conflict.evaluate(&request.arguments, &other_request.arguments)
+                    }
+                    None => false,
+                }
+            }
+            _ => {
+                if !self.potential_conflict(request, other_request, upgrade) {
+                    return false;
+                }
+
+                // Requests on a grouped table were clustered with each other when their templates
+                // were prepared, so only the clusters are left to solve.
+                if let (Some(prepared_id), Some(other_prepared_id)) =
+                    (request.prepared_id, other_request.prepared_id)
+                {
+                    if let Some(Some(clusters)) = self.prepared_requests[prepared_id]
+                        .clusters
+                        .get(other_prepared_id)
+                    {
+                        return solver::solve_clusters(
+                            clusters,
                             &request.arguments,
-                            &other_template.predicate,
                             &other_request.arguments,
-                        ),
-                        _ => solver::solve_clustered(
+                        );
+                    }
+                }
+
+                let template = self.template(request);
+                let other_template = self.template(other_request);
+
+                match self.optimizations[template.table] {
+                    OptimizationLevel::Ungrouped
+                        if template.predicate.is_normalized()
+                            && other_template.predicate.is_normalized() =>
+                    {
+                        solver::solve_dnf(
                             &template.predicate,
                             &request.arguments,
                             &other_template.predicate,
                             &other_request.arguments,
-                        ),
+                        )
                     }
+                    _ => solver::solve_clustered(
+                        &template.predicate,
+                        &request.arguments,
+                        &other_template.predicate,
+                        &other_request.arguments,
+                    ),
+                }
             }
-        });
+        }
+    }
 
-        other_requests
+    /// Returns whether two requests that fix the same column to the same value conflict. If both
+    /// predicates are just that equality, the requests cover the same rows, so only their columns
+    /// need to be checked.
+    fn in_conflict_on_key(&self, request: &Request, other_request: &Request) -> bool {
+        let template = self.template(request);
+        let other_template = self.template(other_request);
+
+        if is_point(template) && is_point(other_template) {
+            other_request.transaction_id != request.transaction_id
+                && self.potential_conflict(
+                    request,
+                    other_request,
+                    request.upgrade && other_request.upgrade,
+                )
+        } else {
+            self.in_conflict(request, other_request)
+        }
     }
 
-    fn solve_prepared(
+    /// Adds a request to a bucket and returns its slot there, along with the requests in the
+    /// bucket, and in the residual bucket if it checks it, that it conflicts with.
+    fn solve(
         &self,
         request: &Arc<Request>,
-        prepared_id: usize,
-        bucket: &RequestBucket,
-    ) -> Vec<Arc<Request>> {
+        bucket: &LockedBucket,
+        residual: Residual,
+    ) -> (usize, Vec<Arc<Request>>) {
         let mut other_requests = vec![];
+        let mut residual_requests = vec![];
+        let mut queued = false;
+        let slot;
 
         {
-            let mut bucket_guard = bucket.lock().unwrap();
-            other_requests.extend(bucket_guard.iter().cloned());
-            bucket_guard.push(Arc::clone(request));
-        };
+            let mut bucket_guard = bucket.lock();
 
-        other_requests.retain(|other_request| {
-            other_request.transaction_id != request.transaction_id
-                && match &other_request.variant {
-                    RequestVariant::AdHoc(other_template) => {
-                        potential_conflict(
-                            &self.prepared_requests[prepared_id].template,
-                            other_template,
-                        ) && solver::solve_clustered(
-                            &self.prepared_requests[prepared_id].template.predicate,
-                            &request.arguments,
-                            &other_template.predicate,
-                            &other_request.arguments,
-                        )
+            // A request on a hot key only needs its key's queue if every request in the bucket is
+            // keyed on the same column, since requests on other values can't share its rows.
+            if let (Some(threshold), Some(key)) = (self.hot_key_threshold, request.key) {
+                if bucket_guard.keys.keyed_on(key.column()) {
+                    if let Some(queue) = bucket_guard.hot_keys.queue(key, &request.arguments) {
+                        other_requests.extend(queue.iter().cloned());
+                        queued = true;
                     }
-                    &RequestVariant::Prepared(other_prepared_id) => {
-                        match &self.prepared_requests[prepared_id].conflicts[other_prepared_id] {
-                            Some(conflict) => solver::evaluate(
-                                conflict,
-                                &request.arguments,
-                                &other_request.arguments,
-                            ),
-                            None => false,
-                        }
+                }
+
+                // Requests that keep running into others on the key keep it hot.
+                if !other_requests.is_empty() {
+                    bucket_guard.record_conflict(key, &request.arguments, threshold);
+                }
+            }
+
+            if !queued {
+                // A point request can skip a crowded bucket if the bucket's key filter rules out
+                // its key. It is still added to the bucket, so that later requests see it.
+                let skip = match (self.key_filter_threshold, request.key) {
+                    (Some(min_requests), Some(key))
+                        if bucket_guard.requests.len() >= min_requests =>
+                    {
+                        self.counters
+                            .num_key_filter_checks
+                            .fetch_add(1, Ordering::Relaxed);
+
+                        !bucket_guard.keys.may_conflict(key)
+                    }
+                    _ => false,
+                };
+
+                if skip {
+                    self.counters
+                        .num_key_filter_skips
+                        .fetch_add(1, Ordering::Relaxed);
+                } else {
+                    other_requests.extend(bucket_guard.requests.iter().cloned());
+                }
+            }
+
+            slot = bucket.push(&mut bucket_guard, Arc::clone(request));
+
+            // The residual bucket is checked while the partition is still locked, so that a
+            // residual request that finds this one also finds how far it checked.
+            match residual {
+                Residual::None => {}
+                Residual::Check(residual_bucket) => {
+                    let mut epoch = residual_bucket.epoch.load(Ordering::SeqCst);
+
+                    if !residual_bucket.is_empty() {
+                        let residual_guard = residual_bucket.lock();
+                        residual_requests.extend(residual_guard.requests.iter().cloned());
+                        epoch = residual_bucket.epoch.load(Ordering::SeqCst);
+                    }
+
+                    if let (Some(filter_stats), Some(template_id)) =
+                        (&self.filter_stats, request.prepared_id)
+                    {
+                        filter_stats.record_residual_checked(template_id, residual_requests.len());
                     }
+
+                    request.residual_epoch.store(epoch, Ordering::Relaxed);
                 }
-        });
+                Residual::Add => {
+                    let epoch = bucket.epoch.fetch_add(1, Ordering::SeqCst) + 1;
+                    request.residual_epoch.store(epoch, Ordering::Relaxed);
+                }
+            }
+        }
+
+        if queued {
+            self.counters
+                .num_hot_key_solves
+                .fetch_add(1, Ordering::Relaxed);
+
+            other_requests.retain(|other_request| self.in_conflict_on_key(request, other_request));
+        } else {
+            self.retain_conflicts(request, &mut other_requests);
 
+            // A key that keeps conflicting is promoted to its own queue.
+            if let (Some(threshold), Some(key)) = (self.hot_key_threshold, request.key) {
+                if !other_requests.is_empty()
+                    && bucket
+                        .lock()
+                        .record_conflict(key, &request.arguments, threshold)
+                {
+                    self.counters
+                        .num_hot_key_promotions
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+
+        if !residual_requests.is_empty() {
+            self.retain_conflicts(request, &mut residual_requests);
+            other_requests.extend(residual_requests);
+        }
+
+        (slot, other_requests)
+    }
+
+    /// Returns the requests in a partition that a residual request conflicts with, without adding
+    /// it there. Partitioned requests that checked the residual bucket after the residual request
+    /// was added wait on it, so it doesn't wait on them. An empty partition isn't locked.
+    fn scan_partition(&self, request: &Request, partition: &LockedBucket) -> Vec<Arc<Request>> {
+        if partition.is_empty() {
+            return vec![];
+        }
+
+        let epoch = request.residual_epoch.load(Ordering::Relaxed);
+
+        let mut other_requests = partition
+            .lock()
+            .requests
+            .iter()
+            .filter(|other_request| other_request.residual_epoch.load(Ordering::Relaxed) < epoch)
+            .cloned()
+            .collect();
+
+        self.retain_conflicts(request, &mut other_requests);
+        other_requests
+    }
+
+    /// Returns the requests in the buckets of an adaptive table's old layout that `request`
+    /// conflicts with. None of them checked the current layout, so none of them wait on it.
+    fn scan_retired(&self, request: &Request, retired: &TableBuckets) -> Vec<Arc<Request>> {
+        let mut other_requests = vec![];
+
+        for bucket in retired.partitions.iter().chain(&retired.residual) {
+            if !bucket.is_empty() {
+                other_requests.extend(bucket.lock().requests.iter().cloned());
+            }
+        }
+
+        self.retain_conflicts(request, &mut other_requests);
         other_requests
     }
+
+    /// Keeps the requests that `request` conflicts with.
+    fn retain_conflicts(&self, request: &Request, other_requests: &mut Vec<Arc<Request>>) {
+        // Requests with disjoint intervals are pruned before the solver runs. Requests without
+        // summaries have no intervals, so none of theirs are pruned.
+        let mut num_pruned = 0;
+
+        other_requests.retain(|other_request| {
+            if interval::disjoint(
+                &request.intervals,
+                &request.arguments,
+                &other_request.intervals,
+                &other_request.arguments,
+            ) {
+                num_pruned += 1;
+                false
+            } else {
+                self.in_conflict(request, other_request)
+            }
+        });
+
+        if num_pruned > 0 {
+            self.counters
+                .num_pruned
+                .fetch_add(num_pruned, Ordering::Relaxed);
+        }
+    }
 }
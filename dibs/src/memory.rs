@@ -0,0 +1,100 @@
+//! Accounting for the memory held by in-flight requests.
+//!
+//! A stalled transaction keeps its requests in their buckets until it commits, and so do the
+//! transactions queued behind it. The account estimates the bytes each request holds, from its
+//! arguments and, for ad hoc requests, its template, and can refuse requests that would take the
+//! total past a limit rather than let the buckets grow without bound.
+
+use crate::interval::Interval;
+use crate::predicate::{Expression, Predicate, Value};
+use crate::{AcquireError, Request, RequestTemplate, RequestVariant};
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// What a bucket spends on each request it holds besides the request itself: the request's
+/// pointer, its slot and its position.
+pub(crate) const BUCKET_ENTRY_BYTES: usize = 3 * mem::size_of::<usize>();
+
+pub(crate) struct MemoryAccount {
+    limit: Option<usize>,
+    bytes: AtomicUsize,
+}
+
+impl MemoryAccount {
+    pub(crate) fn new(limit: Option<usize>) -> MemoryAccount {
+        MemoryAccount {
+            limit,
+            bytes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Charges `bytes` to the account, or fails with `AcquireError::OverCapacity` if that would
+    /// exceed the limit.
+    pub(crate) fn reserve(&self, bytes: usize) -> Result<(), AcquireError> {
+        let held = self.bytes.fetch_add(bytes, Ordering::Relaxed);
+
+        match self.limit {
+            Some(limit) if held + bytes > limit => {
+                self.release(bytes);
+                Err(AcquireError::OverCapacity)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn release(&self, bytes: usize) {
+        self.bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn bytes(&self) -> usize {
+        self.bytes.load(Ordering::Relaxed)
+    }
+}
+
+fn value_bytes(value: &Value) -> usize {
+    match value {
+        Value::String(s) => s.capacity(),
+        Value::Bytes(bytes) => bytes.capacity(),
+        _ => 0,
+    }
+}
+
+fn expression_bytes(expression: &Expression) -> usize {
+    match expression {
+        Expression::Parameter(_) => 0,
+        Expression::Constant(value) => value_bytes(value),
+        Expression::Sum(left, right) | Expression::Difference(left, right) => {
+            2 * mem::size_of::<Expression>() + expression_bytes(left) + expression_bytes(right)
+        }
+    }
+}
+
+fn template_bytes(template: &RequestTemplate) -> usize {
+    let predicate_bytes = template
+        .predicate
+        .preorder()
+        .map(|predicate| match predicate {
+            Predicate::Comparison(_) => 0,
+            Predicate::Connective(_, operands) => operands.capacity() * mem::size_of::<Predicate>(),
+        })
+        .sum::<usize>();
+
+    let derived_bytes = template.derived.capacity() * mem::size_of::<Expression>()
+        + template.derived.iter().map(expression_bytes).sum::<usize>();
+
+    mem::size_of::<RequestTemplate>() + predicate_bytes + derived_bytes
+}
+
+/// Estimates the heap memory that a request holds, not counting its entries in buckets.
+pub(crate) fn request_bytes(request: &Request) -> usize {
+    let variant_bytes = match &request.variant {
+        RequestVariant::AdHoc(template) => template_bytes(template),
+        RequestVariant::Prepared(_) => 0,
+    };
+
+    mem::size_of::<Request>()
+        + request.arguments.capacity() * mem::size_of::<Value>()
+        + request.arguments.iter().map(value_bytes).sum::<usize>()
+        + request.intervals.capacity() * mem::size_of::<Interval>()
+        + variant_bytes
+}
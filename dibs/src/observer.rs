@@ -0,0 +1,50 @@
+//! Callbacks on the lifecycle of transactions, for embedders that want to log or measure what
+//! their transactions do inside `Dibs`.
+//!
+//! An observer registered with `Dibs::set_observer` sees a transaction begin with its first
+//! acquire, each acquire it makes and each conflicting request it waits on, and then its end. A
+//! transaction ends when it commits, and counts as aborted if an acquire failed since it began or
+//! since it last rolled back to a savepoint. Callbacks run on the transaction's thread, inside
+//! `acquire` and `Transaction::commit`, so they should be quick.
+
+use crate::AcquireError;
+use std::time::Duration;
+
+/// Every callback does nothing by default, so an observer implements only the ones it needs.
+#[allow(unused_variables)]
+pub trait TransactionObserver: Send + Sync {
+    fn on_begin(&self, transaction_id: usize) {}
+
+    /// Called once an acquire of `template_id` succeeds or fails, with the time it took including
+    /// any waits. `template_id` includes the transaction's template offset.
+    fn on_acquire(
+        &self,
+        transaction_id: usize,
+        template_id: usize,
+        duration: Duration,
+        error: Option<&AcquireError>,
+    ) {
+    }
+
+    /// Called before the acquire of `template_id` waits for a conflicting request of another
+    /// transaction.
+    fn on_block(&self, transaction_id: usize, template_id: usize, other_transaction_id: usize) {}
+
+    /// Called when the wait that `on_block` announced ends, whether or not it timed out.
+    fn on_unblock(
+        &self,
+        transaction_id: usize,
+        template_id: usize,
+        other_transaction_id: usize,
+        waited: Duration,
+        timed_out: bool,
+    ) {
+    }
+
+    /// Called when the transaction commits, with the time since it began.
+    fn on_commit(&self, transaction_id: usize, duration: Duration) {}
+
+    /// Called instead of `on_commit` when a transaction whose acquire failed is committed to
+    /// release its requests.
+    fn on_abort(&self, transaction_id: usize, duration: Duration) {}
+}
@@ -1,7 +1,8 @@
+use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::Write;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ComparisonOperator {
     Eq,
     Ne,
@@ -24,11 +25,225 @@ impl fmt::Display for ComparisonOperator {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, PartialOrd)]
+/// A fixed-point decimal number equal to `mantissa * 10^-scale`. Decimals are stored with trailing
+/// zeros removed from the mantissa, so equal numbers have equal representations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    pub fn new(mut mantissa: i128, mut scale: u32) -> Decimal {
+        while scale > 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+
+        Decimal { mantissa, scale }
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn checked_add(self, other: Decimal) -> Option<Decimal> {
+        let scale = self.scale.max(other.scale);
+        let mantissa = self.rescale(scale)?.checked_add(other.rescale(scale)?)?;
+        Some(Decimal::new(mantissa, scale))
+    }
+
+    pub fn checked_sub(self, other: Decimal) -> Option<Decimal> {
+        let scale = self.scale.max(other.scale);
+        let mantissa = self.rescale(scale)?.checked_sub(other.rescale(scale)?)?;
+        Some(Decimal::new(mantissa, scale))
+    }
+
+    fn rescale(self, scale: u32) -> Option<i128> {
+        10i128
+            .checked_pow(scale - self.scale)
+            .and_then(|factor| self.mantissa.checked_mul(factor))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        let (a, b, reversed) = if self.scale < other.scale {
+            (self, other, false)
+        } else {
+            (other, self, true)
+        };
+
+        // Rescale the operand with the smaller scale. If that overflows, its magnitude exceeds
+        // anything representable by the other mantissa, so its sign decides the comparison.
+        let ordering = match 10i128
+            .checked_pow(b.scale - a.scale)
+            .and_then(|factor| a.mantissa.checked_mul(factor))
+        {
+            Some(a_mantissa) => a_mantissa.cmp(&b.mantissa),
+            None => a.mantissa.cmp(&0),
+        };
+
+        if reversed {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+
+        if self.mantissa < 0 {
+            f.write_char('-')?;
+        }
+
+        if digits.len() > scale {
+            let (integer, fraction) = digits.split_at(digits.len() - scale);
+            f.write_str(integer)?;
+
+            if !fraction.is_empty() {
+                write!(f, ".{}", fraction)?;
+            }
+        } else {
+            write!(f, "0.{:0>width$}", digits, width = scale)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A value of a column or an argument. Values of different types never compare equal and are
+/// ordered by type, in the order declared here, so every integer orders before every decimal.
+/// Acquires reject arguments whose type differs from that of the arguments compared to the same
+/// column, whether by the same request or by an earlier one.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Value {
     Boolean(bool),
     Integer(usize),
     String(String),
+    /// Microseconds since the Unix epoch.
+    Timestamp(i64),
+    Bytes(Vec<u8>),
+    Decimal(Decimal),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::String(s) => write!(f, "'{}'", s),
+            Value::Timestamp(t) => write!(f, "{}µs", t),
+            Value::Bytes(bytes) => {
+                f.write_str("0x")?;
+
+                for byte in bytes {
+                    write!(f, "{:02x}", byte)?;
+                }
+
+                Ok(())
+            }
+            Value::Decimal(d) => write!(f, "{}", d),
+        }
+    }
+}
+
+/// Arithmetic over a request's arguments. Integers and timestamps saturate at the bounds of their
+/// type; a timestamp may be offset by an integer number of microseconds. Two timestamps can't be
+/// subtracted, since no value type holds a signed duration.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Parameter(usize),
+    Constant(Value),
+    Sum(Box<Expression>, Box<Expression>),
+    Difference(Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    pub fn sum(left: Expression, right: Expression) -> Expression {
+        Expression::Sum(Box::new(left), Box::new(right))
+    }
+
+    pub fn difference(left: Expression, right: Expression) -> Expression {
+        Expression::Difference(Box::new(left), Box::new(right))
+    }
+
+    /// Returns `None` if a parameter is out of range, the operand types are unsupported, or a
+    /// decimal result overflows.
+    pub fn evaluate(&self, arguments: &[Value]) -> Option<Value> {
+        match self {
+            Expression::Parameter(parameter) => arguments.get(*parameter).cloned(),
+            Expression::Constant(value) => Some(value.clone()),
+            Expression::Sum(left, right) => {
+                match (left.evaluate(arguments)?, right.evaluate(arguments)?) {
+                    (Value::Integer(a), Value::Integer(b)) => {
+                        Some(Value::Integer(a.saturating_add(b)))
+                    }
+                    (Value::Timestamp(a), Value::Integer(b))
+                    | (Value::Integer(b), Value::Timestamp(a)) => {
+                        Some(Value::Timestamp(a.saturating_add(saturating_micros(b))))
+                    }
+                    (Value::Decimal(a), Value::Decimal(b)) => a.checked_add(b).map(Value::Decimal),
+                    _ => None,
+                }
+            }
+            Expression::Difference(left, right) => {
+                match (left.evaluate(arguments)?, right.evaluate(arguments)?) {
+                    (Value::Integer(a), Value::Integer(b)) => {
+                        Some(Value::Integer(a.saturating_sub(b)))
+                    }
+                    (Value::Timestamp(a), Value::Integer(b)) => {
+                        Some(Value::Timestamp(a.saturating_sub(saturating_micros(b))))
+                    }
+                    (Value::Decimal(a), Value::Decimal(b)) => a.checked_sub(b).map(Value::Decimal),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+fn saturating_micros(micros: usize) -> i64 {
+    if micros > i64::MAX as usize {
+        i64::MAX
+    } else {
+        micros as i64
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expression::Parameter(parameter) => write!(f, "param_{}", parameter),
+            Expression::Constant(value) => write!(f, "{}", value),
+            Expression::Sum(left, right) => write!(f, "({} + {})", left, right),
+            Expression::Difference(left, right) => write!(f, "({} - {})", left, right),
+        }
+    }
+}
+
+/// The values that a column can hold, which a template may declare with
+/// `RequestTemplate::with_domain`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Domain {
+    /// The values between two, inclusive.
+    Range(Value, Value),
+    /// The values listed.
+    Values(Vec<Value>),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -58,13 +273,18 @@ impl fmt::Display for Comparison {
     }
 }
 
+/// The deepest that a request template's predicate may nest, counting a comparison as depth 1.
+/// Cloning, dropping and solving predicates recurse into their operands, so deeper predicates
+/// could overflow the stack.
+pub const MAX_DEPTH: usize = 128;
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Connective {
     Conjunction,
     Disjunction,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Predicate {
     Comparison(Comparison),
     Connective(Connective, Vec<Predicate>),
@@ -103,10 +323,42 @@ impl Predicate {
     //     }
     // }
 
+    /// Returns the number of nodes on the longest path from the root to a comparison or to an
+    /// empty connective.
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut stack = vec![(self, 1)];
+
+        while let Some((node, node_depth)) = stack.pop() {
+            depth = depth.max(node_depth);
+
+            if let Predicate::Connective(_, operands) = node {
+                for operand in operands {
+                    stack.push((operand, node_depth + 1));
+                }
+            }
+        }
+
+        depth
+    }
+
     pub fn condense(&mut self) {
+        // Condense the operands of a connective before the connective itself, so that operands
+        // that collapse into a connective of the same kind are merged into it.
+        let mut nodes = vec![];
         let mut stack = vec![self as *mut Predicate];
 
         while let Some(node_ptr) = stack.pop() {
+            nodes.push(node_ptr);
+
+            if let Predicate::Connective(_, operands) = unsafe { &mut *node_ptr } {
+                for operand in operands {
+                    stack.push(operand as *mut Predicate);
+                }
+            }
+        }
+
+        for node_ptr in nodes.into_iter().rev() {
             let node = unsafe { &mut *node_ptr };
 
             if let Predicate::Connective(connective, operands) = node {
@@ -121,7 +373,12 @@ impl Predicate {
                                 operands.extend(sub_operands);
                             }
                         }
-                        Predicate::Connective(_, ref sub_operands) if sub_operands.is_empty() => {
+                        Predicate::Connective(sub_connective, ref sub_operands)
+                            if sub_operands.is_empty() =>
+                        {
+                            // A true operand makes a disjunction true and a false one makes a
+                            // conjunction false, so the node becomes the operand.
+                            *connective = sub_connective;
                             operands.clear();
                         }
                         _ => i += 1,
@@ -130,11 +387,6 @@ impl Predicate {
 
                 if operands.len() == 1 {
                     *node = operands.pop().unwrap();
-                    stack.push(node as *mut Predicate);
-                } else {
-                    for operand in operands {
-                        stack.push(operand as *mut Predicate);
-                    }
                 }
             }
         }
@@ -144,19 +396,15 @@ impl Predicate {
         match self {
             Predicate::Comparison(..) => true,
             Predicate::Connective(connective, operands) => match connective {
-                Connective::Conjunction => operands.iter().all(|operand| match operand {
-                    Predicate::Comparison(..) => true,
-                    _ => false,
-                }),
+                Connective::Conjunction => operands
+                    .iter()
+                    .all(|operand| matches!(operand, Predicate::Comparison(..))),
                 Connective::Disjunction => operands.iter().all(|operand| match operand {
                     Predicate::Comparison(..) => true,
                     Predicate::Connective(sub_connective, sub_operands) => match sub_connective {
-                        Connective::Conjunction => {
-                            sub_operands.iter().all(|sub_operand| match sub_operand {
-                                Predicate::Comparison(..) => true,
-                                _ => false,
-                            })
-                        }
+                        Connective::Conjunction => sub_operands
+                            .iter()
+                            .all(|sub_operand| matches!(sub_operand, Predicate::Comparison(..))),
                         Connective::Disjunction => false,
                     },
                 }),
@@ -165,6 +413,10 @@ impl Predicate {
     }
 
     pub fn normalize(&mut self) {
+        // Distributing a conjunction over its disjunctions only finds the disjunctions among its
+        // direct operands, so nested connectives of the same kind are merged first.
+        self.condense();
+
         let mut stack = vec![self as *mut Predicate];
 
         while let Some(node_ptr) = stack.pop() {
@@ -172,9 +424,8 @@ impl Predicate {
 
             if let Predicate::Connective(connective, operands) = node {
                 if *connective == Connective::Conjunction {
-                    let disjunction_position = operands.iter().position(|operand| match operand {
-                        Predicate::Connective(_sub_connective @ Connective::Disjunction, _) => true,
-                        _ => false,
+                    let disjunction_position = operands.iter().position(|operand| {
+                        matches!(operand, Predicate::Connective(Connective::Disjunction, _))
                     });
 
                     if let Some(i) = disjunction_position {
@@ -184,7 +435,15 @@ impl Predicate {
                         if let Predicate::Connective(_, disjunction_operands) = disjunction {
                             for disjunction_operand in disjunction_operands {
                                 let mut conjunction_operands = operands.clone();
-                                conjunction_operands.push(disjunction_operand);
+
+                                match disjunction_operand {
+                                    Predicate::Connective(
+                                        Connective::Conjunction,
+                                        sub_operands,
+                                    ) => conjunction_operands.extend(sub_operands),
+                                    _ => conjunction_operands.push(disjunction_operand),
+                                }
+
                                 new_operands.push(Predicate::conjunction(conjunction_operands));
                             }
                         }
@@ -203,7 +462,7 @@ impl Predicate {
         self.condense();
     }
 
-    pub fn preorder(&self) -> PreorderIter {
+    pub fn preorder(&self) -> PreorderIter<'_> {
         PreorderIter::new(self)
     }
 
@@ -268,7 +527,7 @@ pub struct PreorderIter<'a> {
 }
 
 impl<'a> PreorderIter<'a> {
-    fn new(p: &'a Predicate) -> PreorderIter {
+    fn new(p: &'a Predicate) -> PreorderIter<'a> {
         PreorderIter { stack: vec![p] }
     }
 }
@@ -288,3 +547,220 @@ impl<'a> Iterator for PreorderIter<'a> {
         Some(node)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(mantissa: i128, scale: u32) -> Value {
+        Value::Decimal(Decimal::new(mantissa, scale))
+    }
+
+    #[test]
+    fn decimals_drop_trailing_zeros() {
+        assert_eq!(Decimal::new(1500, 3), Decimal::new(15, 1));
+        assert_eq!(Decimal::new(1500, 3).mantissa(), 15);
+        assert_eq!(Decimal::new(1500, 3).scale(), 1);
+        assert_eq!(Decimal::new(1500, 0).mantissa(), 1500);
+        assert_eq!(Decimal::new(0, 5), Decimal::new(0, 0));
+    }
+
+    #[test]
+    fn decimals_order_by_value_across_scales() {
+        assert!(Decimal::new(15, 1) < Decimal::new(155, 2));
+        assert!(Decimal::new(10, 0) > Decimal::new(999, 2));
+        assert!(Decimal::new(-15, 1) < Decimal::new(-145, 2));
+        assert!(Decimal::new(-1, 3) < Decimal::new(0, 0));
+        assert_eq!(
+            Decimal::new(25, 1).cmp(&Decimal::new(250, 2)),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn decimals_order_by_sign_when_rescaling_overflows() {
+        let large = 10i128.pow(20);
+
+        assert!(Decimal::new(large, 0) > Decimal::new(1, 30));
+        assert!(Decimal::new(1, 30) < Decimal::new(large, 0));
+        assert!(Decimal::new(-large, 0) < Decimal::new(1, 30));
+        assert!(Decimal::new(-large, 0) < Decimal::new(-1, 30));
+    }
+
+    #[test]
+    fn decimals_display_their_digits() {
+        assert_eq!(Decimal::new(15, 1).to_string(), "1.5");
+        assert_eq!(Decimal::new(-5, 2).to_string(), "-0.05");
+        assert_eq!(Decimal::new(1500, 0).to_string(), "1500");
+    }
+
+    #[test]
+    fn decimal_arithmetic_rescales_its_operands() {
+        let sum = Expression::sum(
+            Expression::Constant(decimal(125, 2)),
+            Expression::Constant(decimal(75, 2)),
+        );
+        assert_eq!(sum.evaluate(&[]), Some(decimal(2, 0)));
+
+        let difference = Expression::difference(
+            Expression::Parameter(0),
+            Expression::Constant(decimal(2, 0)),
+        );
+        assert_eq!(
+            difference.evaluate(&[decimal(125, 2)]),
+            Some(decimal(-75, 2))
+        );
+    }
+
+    #[test]
+    fn decimal_arithmetic_fails_on_overflow() {
+        let max = Expression::Constant(decimal(i128::MAX, 0));
+
+        let sum = Expression::sum(max.clone(), Expression::Constant(decimal(1, 0)));
+        assert_eq!(sum.evaluate(&[]), None);
+
+        // Adding a tenth rescales the maximum mantissa, which overflows before the addition does.
+        let sum = Expression::sum(max, Expression::Constant(decimal(1, 1)));
+        assert_eq!(sum.evaluate(&[]), None);
+
+        let difference = Expression::difference(
+            Expression::Constant(decimal(i128::MIN, 0)),
+            Expression::Constant(decimal(1, 0)),
+        );
+        assert_eq!(difference.evaluate(&[]), None);
+    }
+
+    #[test]
+    fn decimals_are_not_mixed_with_integers() {
+        let sum = Expression::sum(
+            Expression::Constant(decimal(15, 1)),
+            Expression::Constant(Value::Integer(1)),
+        );
+        assert_eq!(sum.evaluate(&[]), None);
+
+        assert_ne!(Value::Integer(1), decimal(1, 0));
+        assert!(Value::Integer(usize::MAX) < decimal(-1, 0));
+        assert!(Value::Integer(0) < decimal(-(10i128.pow(30)), 0));
+    }
+
+    #[test]
+    fn expressions_look_up_parameters() {
+        let arguments = [Value::Integer(3), Value::Timestamp(4)];
+
+        assert_eq!(
+            Expression::Parameter(1).evaluate(&arguments),
+            Some(Value::Timestamp(4))
+        );
+        assert_eq!(Expression::Parameter(2).evaluate(&arguments), None);
+
+        // A missing parameter anywhere in the expression fails the whole of it.
+        let sum = Expression::sum(Expression::Parameter(0), Expression::Parameter(2));
+        assert_eq!(sum.evaluate(&arguments), None);
+    }
+
+    #[test]
+    fn integer_arithmetic_saturates() {
+        let sum = Expression::sum(Expression::Parameter(0), Expression::Parameter(1));
+        assert_eq!(
+            sum.evaluate(&[Value::Integer(2), Value::Integer(3)]),
+            Some(Value::Integer(5))
+        );
+        assert_eq!(
+            sum.evaluate(&[Value::Integer(usize::MAX), Value::Integer(1)]),
+            Some(Value::Integer(usize::MAX))
+        );
+
+        let difference = Expression::difference(Expression::Parameter(0), Expression::Parameter(1));
+        assert_eq!(
+            difference.evaluate(&[Value::Integer(2), Value::Integer(3)]),
+            Some(Value::Integer(0))
+        );
+    }
+
+    #[test]
+    fn timestamps_are_offset_by_integers() {
+        let sum = Expression::sum(Expression::Parameter(0), Expression::Parameter(1));
+        let arguments = [Value::Timestamp(-10), Value::Integer(4)];
+        assert_eq!(sum.evaluate(&arguments), Some(Value::Timestamp(-6)));
+
+        let swapped = Expression::sum(Expression::Parameter(1), Expression::Parameter(0));
+        assert_eq!(swapped.evaluate(&arguments), Some(Value::Timestamp(-6)));
+
+        let earlier = Expression::difference(Expression::Parameter(0), Expression::Parameter(1));
+        assert_eq!(earlier.evaluate(&arguments), Some(Value::Timestamp(-14)));
+
+        // Offsets too large for a timestamp saturate.
+        let arguments = [Value::Timestamp(0), Value::Integer(usize::MAX)];
+        assert_eq!(sum.evaluate(&arguments), Some(Value::Timestamp(i64::MAX)));
+        assert_eq!(
+            earlier.evaluate(&arguments),
+            Some(Value::Timestamp(-i64::MAX))
+        );
+    }
+
+    #[test]
+    fn timestamps_do_not_subtract() {
+        let difference = Expression::difference(Expression::Parameter(0), Expression::Parameter(1));
+
+        assert_eq!(
+            difference.evaluate(&[Value::Timestamp(3), Value::Timestamp(10)]),
+            None
+        );
+    }
+
+    #[test]
+    fn unsupported_operands_fail() {
+        let sum = Expression::sum(Expression::Parameter(0), Expression::Parameter(1));
+        let difference = Expression::difference(Expression::Parameter(0), Expression::Parameter(1));
+
+        let timestamps = [Value::Timestamp(1), Value::Timestamp(2)];
+        assert_eq!(sum.evaluate(&timestamps), None);
+
+        let integer_first = [Value::Integer(1), Value::Timestamp(2)];
+        assert_eq!(difference.evaluate(&integer_first), None);
+
+        let strings = [
+            Value::String("a".to_string()),
+            Value::String("b".to_string()),
+        ];
+        assert_eq!(sum.evaluate(&strings), None);
+        assert_eq!(difference.evaluate(&strings), None);
+    }
+
+    #[test]
+    fn expressions_nest() {
+        // (?0 + 2) - (?1 - 1)
+        let expression = Expression::difference(
+            Expression::sum(
+                Expression::Parameter(0),
+                Expression::Constant(Value::Integer(2)),
+            ),
+            Expression::difference(
+                Expression::Parameter(1),
+                Expression::Constant(Value::Integer(1)),
+            ),
+        );
+
+        assert_eq!(
+            expression.evaluate(&[Value::Integer(10), Value::Integer(4)]),
+            Some(Value::Integer(9))
+        );
+        assert_eq!(expression.to_string(), "((param_0 + 2) - (param_1 - 1))");
+    }
+
+    #[test]
+    fn constants_display_as_values() {
+        let expression = Expression::sum(
+            Expression::Constant(Value::String("it's".to_string())),
+            Expression::Constant(Value::Bytes(vec![0, 171])),
+        );
+        assert_eq!(expression.to_string(), "('it's' + 0x00ab)");
+
+        let expression = Expression::difference(
+            Expression::Constant(Value::Timestamp(-5)),
+            Expression::Constant(decimal(-5, 2)),
+        );
+        assert_eq!(expression.to_string(), "(-5µs - -0.05)");
+        assert_eq!(Value::Boolean(true).to_string(), "true");
+    }
+}
@@ -0,0 +1,102 @@
+use crate::predicate::{ComparisonOperator, Connective, Predicate, Value};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Branch {
+    Accept,
+    Reject,
+    Jump(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Instruction {
+    operator: ComparisonOperator,
+    left: usize,
+    right: usize,
+    on_true: Branch,
+    on_false: Branch,
+}
+
+/// A prepared conflict predicate flattened into a branching program. Each instruction evaluates
+/// one comparison and jumps to the next instruction or to a final result, so evaluation needs
+/// neither recursion nor allocation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+    entry: Branch,
+}
+
+impl Program {
+    pub fn compile(conflict: &Predicate) -> Program {
+        let mut instructions = vec![];
+        let entry = compile_node(conflict, Branch::Accept, Branch::Reject, &mut instructions);
+
+        Program {
+            instructions,
+            entry,
+        }
+    }
+
+    pub fn evaluate(&self, p_args: &[Value], q_args: &[Value]) -> bool {
+        use crate::predicate::ComparisonOperator::*;
+
+        let mut branch = self.entry;
+
+        loop {
+            match branch {
+                Branch::Accept => return true,
+                Branch::Reject => return false,
+                Branch::Jump(pc) => {
+                    let instruction = &self.instructions[pc];
+                    let p_value = &p_args[instruction.left];
+                    let q_value = &q_args[instruction.right];
+
+                    let result = match instruction.operator {
+                        Eq => p_value == q_value,
+                        Ne => p_value != q_value,
+                        Lt => p_value < q_value,
+                        Le => p_value <= q_value,
+                        Gt => p_value > q_value,
+                        Ge => p_value >= q_value,
+                    };
+
+                    branch = if result {
+                        instruction.on_true
+                    } else {
+                        instruction.on_false
+                    };
+                }
+            }
+        }
+    }
+}
+
+fn compile_node(
+    node: &Predicate,
+    on_true: Branch,
+    on_false: Branch,
+    instructions: &mut Vec<Instruction>,
+) -> Branch {
+    match node {
+        Predicate::Comparison(comparison) => {
+            instructions.push(Instruction {
+                operator: comparison.operator,
+                left: comparison.left,
+                right: comparison.right,
+                on_true,
+                on_false,
+            });
+
+            Branch::Jump(instructions.len() - 1)
+        }
+        Predicate::Connective(Connective::Conjunction, operands) => {
+            operands.iter().rev().fold(on_true, |next, operand| {
+                compile_node(operand, next, on_false, instructions)
+            })
+        }
+        Predicate::Connective(Connective::Disjunction, operands) => {
+            operands.iter().rev().fold(on_false, |next, operand| {
+                compile_node(operand, on_true, next, instructions)
+            })
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use crate::predicate::Value;
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+
+/// A conflict observed during an acquire: the template of the request being acquired, the
+/// template of the in-flight request it conflicted with, and the arguments of the former. The
+/// in-flight request has no template ID if it was acquired ad hoc.
+#[derive(Clone, Debug)]
+pub struct ConflictSample {
+    pub template_id: Option<usize>,
+    pub other_template_id: Option<usize>,
+    pub arguments: Vec<Value>,
+}
+
+struct Reservoir {
+    num_conflicts: usize,
+    samples: Vec<ConflictSample>,
+}
+
+/// Keeps a uniform sample of the conflicts seen so far, using reservoir sampling so memory stays
+/// bounded however long the run.
+pub(crate) struct ConflictSampler {
+    capacity: usize,
+    reservoir: Mutex<Reservoir>,
+}
+
+impl ConflictSampler {
+    pub(crate) fn new(capacity: usize) -> ConflictSampler {
+        ConflictSampler {
+            capacity,
+            reservoir: Mutex::new(Reservoir {
+                num_conflicts: 0,
+                samples: Vec::with_capacity(capacity),
+            }),
+        }
+    }
+
+    pub(crate) fn record(
+        &self,
+        template_id: Option<usize>,
+        other_template_id: Option<usize>,
+        arguments: &[Value],
+    ) {
+        let mut reservoir = self.reservoir.lock().unwrap();
+        reservoir.num_conflicts += 1;
+
+        let slot = if reservoir.samples.len() < self.capacity {
+            reservoir.samples.len()
+        } else {
+            match rand::thread_rng().gen_range(0, reservoir.num_conflicts) {
+                i if i < self.capacity => i,
+                _ => return,
+            }
+        };
+
+        let sample = ConflictSample {
+            template_id,
+            other_template_id,
+            arguments: arguments.to_vec(),
+        };
+
+        if slot == reservoir.samples.len() {
+            reservoir.samples.push(sample);
+        } else {
+            reservoir.samples[slot] = sample;
+        }
+    }
+
+    pub(crate) fn report(&self, top: usize) -> ConflictReport {
+        let reservoir = self.reservoir.lock().unwrap();
+
+        let mut pairs = BTreeMap::new();
+        let mut values = BTreeMap::new();
+
+        for sample in &reservoir.samples {
+            *pairs
+                .entry((sample.template_id, sample.other_template_id))
+                .or_insert(0) += 1;
+
+            for value in &sample.arguments {
+                *values.entry(value.clone()).or_insert(0) += 1;
+            }
+        }
+
+        ConflictReport {
+            num_conflicts: reservoir.num_conflicts,
+            num_samples: reservoir.samples.len(),
+            top_pairs: most_frequent(pairs, top),
+            hot_values: most_frequent(values, top),
+        }
+    }
+}
+
+fn most_frequent<K>(counts: BTreeMap<K, usize>, top: usize) -> Vec<(K, usize)> {
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+    counts.truncate(top);
+    counts
+}
+
+/// The template of a request being acquired and the template of a request it conflicted with.
+pub type TemplatePair = (Option<usize>, Option<usize>);
+
+/// A summary of the sampled conflicts. Counts are taken over the sample, not over every conflict.
+#[derive(Clone, Debug)]
+pub struct ConflictReport {
+    pub num_conflicts: usize,
+    pub num_samples: usize,
+    pub top_pairs: Vec<(TemplatePair, usize)>,
+    pub hot_values: Vec<(Value, usize)>,
+}
+
+fn fmt_template_id(template_id: Option<usize>) -> String {
+    match template_id {
+        Some(template_id) => template_id.to_string(),
+        None => "ad hoc".to_string(),
+    }
+}
+
+impl fmt::Display for ConflictReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} conflicts, {} sampled",
+            self.num_conflicts, self.num_samples
+        )?;
+
+        writeln!(f, "top conflicting templates:")?;
+        for &((template_id, other_template_id), count) in &self.top_pairs {
+            writeln!(
+                f,
+                "  {} -> {}: {}",
+                fmt_template_id(template_id),
+                fmt_template_id(other_template_id),
+                count
+            )?;
+        }
+
+        writeln!(f, "hottest argument values:")?;
+        for (value, count) in &self.hot_values {
+            writeln!(f, "  {:?}: {}", value, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(sampler: &ConflictSampler, template_id: usize, other_template_id: usize, key: usize) {
+        sampler.record(
+            Some(template_id),
+            Some(other_template_id),
+            &[Value::Integer(key)],
+        );
+    }
+
+    #[test]
+    fn samples_are_bounded_by_capacity() {
+        let sampler = ConflictSampler::new(10);
+
+        for key in 0..1000 {
+            record(&sampler, 0, 1, key);
+        }
+
+        let report = sampler.report(usize::MAX);
+        assert_eq!(report.num_conflicts, 1000);
+        assert_eq!(report.num_samples, 10);
+        assert_eq!(report.top_pairs, vec![((Some(0), Some(1)), 10)]);
+        assert_eq!(
+            report
+                .hot_values
+                .iter()
+                .map(|(_, count)| count)
+                .sum::<usize>(),
+            10
+        );
+    }
+
+    #[test]
+    fn reports_rank_pairs_and_values_by_frequency() {
+        let sampler = ConflictSampler::new(100);
+
+        for _ in 0..3 {
+            record(&sampler, 1, 0, 7);
+        }
+
+        record(&sampler, 0, 1, 8);
+        sampler.record(Some(0), None, &[Value::Integer(7), Value::Integer(9)]);
+
+        let report = sampler.report(2);
+        assert_eq!(report.num_conflicts, 5);
+        assert_eq!(report.num_samples, 5);
+        assert_eq!(report.top_pairs[0], ((Some(1), Some(0)), 3));
+        assert_eq!(report.top_pairs.len(), 2);
+        assert_eq!(report.hot_values[0], (Value::Integer(7), 4));
+        assert_eq!(report.hot_values.len(), 2);
+
+        let report = report.to_string();
+        assert!(report.starts_with("5 conflicts, 5 sampled\n"));
+        assert!(report.contains("  1 -> 0: 3\n"));
+    }
+
+    #[test]
+    fn ad_hoc_requests_are_named_in_reports() {
+        let sampler = ConflictSampler::new(1);
+        sampler.record(None, Some(0), &[]);
+
+        assert!(sampler.report(1).to_string().contains("  ad hoc -> 0: 1\n"));
+    }
+}
@@ -0,0 +1,96 @@
+//! In-flight entries shared by identical reads of read-only transactions.
+//!
+//! Under skew, many transactions read the same hot rows with the same prepared template at once,
+//! and each of their requests solves against the bucket and then sits in it for every later
+//! request to solve against. Once the first of them has been granted, every conflicting request
+//! that arrived before it has completed, and every one that arrived after it waits on it. An
+//! identical read arriving later would wait on exactly the former and be waited on by the latter,
+//! so it can join the granted request's entry instead of adding its own, without solving. The entry
+//! counts the transactions holding it and stays in the bucket until the last of them releases it.
+//!
+//! Only read-only transactions share entries. A transaction that went on to write could conflict
+//! with the entry it shares, and then wait on itself.
+
+use crate::bloom;
+use crate::predicate::Value;
+use crate::sync::{Arc, Ordering};
+use crate::Request;
+use fnv::{FnvHashMap, FnvHasher};
+use std::hash::Hasher;
+
+/// Returns the hash under which a read of `template_id` with `arguments` is shared.
+pub fn share_hash(template_id: usize, arguments: &[Value]) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write_usize(template_id);
+
+    for argument in arguments {
+        bloom::hash_value(argument, &mut hasher);
+    }
+
+    hasher.finish()
+}
+
+#[derive(Default)]
+pub struct SharedReads {
+    /// The slot of the shareable request for each template and hash of arguments.
+    leaders: FnvHashMap<(usize, u64), usize>,
+    /// The number of transactions besides the one that made it that hold the request in each slot.
+    sharers: FnvHashMap<usize, usize>,
+}
+
+impl SharedReads {
+    /// Records the request in `slot` as shareable, unless an identical one already is.
+    pub fn register(&mut self, request: &Request, slot: usize) {
+        if let (Some(template_id), Some(hash)) = (request.template_id(), request.share_hash) {
+            self.leaders.entry((template_id, hash)).or_insert(slot);
+        }
+    }
+
+    /// Joins the entry of a granted request identical to `request`, returning its slot, or returns
+    /// `None` if there is none.
+    pub fn join(
+        &mut self,
+        request: &Request,
+        requests: &[Arc<Request>],
+        positions: &[usize],
+    ) -> Option<usize> {
+        let template_id = request.template_id()?;
+        let slot = *self.leaders.get(&(template_id, request.share_hash?))?;
+        let leader = &requests[positions[slot]];
+
+        if leader.granted.load(Ordering::Acquire)
+            && leader.template_id() == Some(template_id)
+            && leader.arguments == request.arguments
+        {
+            *self.sharers.entry(slot).or_insert(0) += 1;
+            Some(slot)
+        } else {
+            None
+        }
+    }
+
+    /// Releases one holder of the request in `slot`, returning whether others still hold it.
+    pub fn release(&mut self, slot: usize) -> bool {
+        match self.sharers.get_mut(&slot) {
+            Some(sharers) => {
+                *sharers -= 1;
+
+                if *sharers == 0 {
+                    self.sharers.remove(&slot);
+                }
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forgets the request in `slot`, which its last holder released.
+    pub fn remove(&mut self, request: &Request, slot: usize) {
+        if let (Some(template_id), Some(hash)) = (request.template_id(), request.share_hash) {
+            if self.leaders.get(&(template_id, hash)) == Some(&slot) {
+                self.leaders.remove(&(template_id, hash));
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Deterministic execution for reproducing conflict interleavings in tests.
+//!
+//! Once `Dibs::enable_simulation` is called, timeout jitter and the order in which conflicting
+//! requests are awaited are drawn from a seeded RNG, and waiting advances the `ManualClock` that
+//! the `Dibs` measures time against instead of blocking. A request that is still in flight when it
+//! is awaited times out immediately, since nothing else can complete it while a script is being
+//! replayed on a single thread.
+
+use crate::clock::ManualClock;
+use crate::predicate::Value;
+use crate::{AcquireError, Dibs, Request, Transaction};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub(crate) struct Environment {
+    rng: Mutex<StdRng>,
+    clock: Arc<ManualClock>,
+}
+
+impl Environment {
+    pub(crate) fn new(seed: u64, clock: Arc<ManualClock>) -> Environment {
+        Environment {
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            clock,
+        }
+    }
+
+    pub(crate) fn jitter(&self) -> f32 {
+        self.rng.lock().unwrap().gen_range(0.8, 1.2)
+    }
+
+    pub(crate) fn shuffle<T>(&self, values: &mut [T]) {
+        values.shuffle(&mut *self.rng.lock().unwrap());
+    }
+
+    /// Returns how long awaiting `requests` took: nothing if they have all completed, and the
+    /// whole timeout, by which the clock is advanced, otherwise.
+    pub(crate) fn await_completion(&self, requests: &[&Request], timeout: Duration) -> Duration {
+        if requests
+            .iter()
+            .all(|request| request.is_completed.load(Ordering::Acquire))
+        {
+            Duration::default()
+        } else {
+            self.clock.advance(timeout);
+            timeout
+        }
+    }
+}
+
+impl Dibs {
+    /// Runs acquires deterministically on a single thread, as described in the module
+    /// documentation, measuring time against `clock`. This replaces any clock set earlier.
+    pub fn enable_simulation(&mut self, seed: u64, clock: Arc<ManualClock>) {
+        self.clock = Arc::clone(&clock) as _;
+        self.simulation = Some(Environment::new(seed, clock));
+    }
+
+    /// Reseeds the RNG, so that replaying the same script again produces the same outcomes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if simulation is not enabled.
+    pub fn reseed(&self, seed: u64) {
+        let environment = self.simulation.as_ref().expect("simulation is not enabled");
+        *environment.rng.lock().unwrap() = StdRng::seed_from_u64(seed);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Action {
+    Acquire {
+        template_id: usize,
+        arguments: Vec<Value>,
+    },
+    Commit,
+}
+
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub thread: usize,
+    pub action: Action,
+}
+
+/// Merges the scripts of several logical threads into a single sequence of steps. Each script
+/// keeps its own order, and the choice of which thread runs next is drawn from `seed`.
+pub fn interleave(seed: u64, scripts: Vec<Vec<Action>>) -> Vec<Step> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut scripts = scripts
+        .into_iter()
+        .enumerate()
+        .map(|(thread, script)| (thread, script.into_iter()))
+        .collect::<Vec<_>>();
+
+    let mut steps = vec![];
+
+    while !scripts.is_empty() {
+        let i = rng.gen_range(0, scripts.len());
+        let (thread, script) = &mut scripts[i];
+
+        match script.next() {
+            Some(action) => steps.push(Step {
+                thread: *thread,
+                action,
+            }),
+            None => {
+                scripts.swap_remove(i);
+            }
+        }
+    }
+
+    steps
+}
+
+/// Replays `steps` against `dibs`, returning the outcome of each step. Each logical thread runs
+/// one transaction at a time; a commit step ends the thread's current transaction and its next
+/// acquire starts a new one. Transactions that are still open at the end are committed.
+pub fn replay(dibs: &Dibs, steps: &[Step]) -> Vec<Result<(), AcquireError>> {
+    let mut transactions = BTreeMap::new();
+    let mut next_transaction_id = 0;
+    let mut outcomes = vec![];
+
+    for step in steps {
+        match &step.action {
+            Action::Acquire {
+                template_id,
+                arguments,
+            } => {
+                let transaction = transactions.entry(step.thread).or_insert_with(|| {
+                    next_transaction_id += 1;
+                    Transaction::new(next_transaction_id, next_transaction_id)
+                });
+
+                outcomes.push(dibs.acquire(transaction, *template_id, arguments.clone()));
+            }
+
+            Action::Commit => {
+                if let Some(transaction) = transactions.remove(&step.thread) {
+                    transaction.commit();
+                }
+
+                outcomes.push(Ok(()));
+            }
+        }
+    }
+
+    for transaction in transactions.into_values() {
+        transaction.commit();
+    }
+
+    outcomes
+}
@@ -1,13 +1,17 @@
 use crate::predicate::{Comparison, Connective, Predicate, Value};
 use crate::union_find::UnionFind;
 use fnv::FnvHashMap;
-use std::borrow::Cow;
+use std::cell::RefCell;
 use std::{mem, slice};
 
-fn cluster<'a>(
-    p: &'a Predicate,
-    q: &'a Predicate,
-) -> impl Iterator<Item = (Predicate, Predicate)> + 'a {
+thread_local! {
+    // Reused across calls, since clustering runs for every pair of unprepared requests solved.
+    static CLUSTERING: RefCell<(UnionFind, FnvHashMap<usize, usize>)> = RefCell::default();
+}
+
+// Splits the conjuncts of both predicates into clusters that share no columns, so that the
+// predicates conflict only if every cluster's pair of conjunctions does.
+fn cluster(p: &Predicate, q: &Predicate) -> Vec<(Predicate, Predicate)> {
     let p_conjuncts = match p {
         Predicate::Connective(_p_connective @ Connective::Conjunction, p_operands) => p_operands,
         _ => slice::from_ref(p),
@@ -18,26 +22,29 @@ fn cluster<'a>(
         _ => slice::from_ref(q),
     };
 
-    let mut column_map = FnvHashMap::default();
-    let mut union_find = UnionFind::new(p_conjuncts.len() + q_conjuncts.len());
+    CLUSTERING.with(|clustering| {
+        let (union_find, column_map) = &mut *clustering.borrow_mut();
+        union_find.reset(p_conjuncts.len() + q_conjuncts.len());
+        column_map.clear();
 
-    for (i, conjunct) in p_conjuncts.iter().chain(q_conjuncts).enumerate() {
-        for node in conjunct.preorder() {
-            if let Predicate::Comparison(comparison) = node {
-                let j = *column_map.entry(comparison.left).or_insert(i);
+        for (i, conjunct) in p_conjuncts.iter().chain(q_conjuncts).enumerate() {
+            for node in conjunct.preorder() {
+                if let Predicate::Comparison(comparison) = node {
+                    let j = *column_map.entry(comparison.left).or_insert(i);
 
-                if i != j {
-                    union_find.union(i, j);
+                    if i != j {
+                        union_find.union(i, j);
+                    }
                 }
             }
         }
-    }
 
-    union_find.sets().into_iter().map(move |indices| {
-        let mut p_sub = vec![];
-        let mut q_sub = vec![];
+        let (num_sets, labels) = union_find.labels();
+        let mut clusters = vec![(vec![], vec![]); num_sets];
+
+        for (i, &label) in labels.iter().enumerate() {
+            let (p_sub, q_sub) = &mut clusters[label];
 
-        for i in indices {
             if i < p_conjuncts.len() {
                 p_sub.push(p_conjuncts[i].clone());
             } else {
@@ -45,123 +52,77 @@ fn cluster<'a>(
             }
         }
 
-        (Predicate::conjunction(p_sub), Predicate::conjunction(q_sub))
+        clusters
+            .into_iter()
+            .map(|(p_sub, q_sub)| (Predicate::conjunction(p_sub), Predicate::conjunction(q_sub)))
+            .collect()
     })
 }
 
-fn prepare_comparison_comparison(p: &Comparison, q: &Comparison, swap: bool) -> Predicate {
+// Views a normalized predicate as the flat list of conjunctions it is a disjunction of, each a
+// slice of comparisons. A predicate that is true has one empty conjunction and one that is false
+// has none. Solving and preparing loop over these lists instead of recursing into the predicate.
+fn conjunctions(p: &Predicate) -> impl Iterator<Item = &[Predicate]> {
+    let disjuncts = match p {
+        Predicate::Connective(Connective::Disjunction, operands) => operands.as_slice(),
+        _ => slice::from_ref(p),
+    };
+
+    disjuncts.iter().map(|disjunct| match disjunct {
+        Predicate::Connective(Connective::Conjunction, operands) => operands.as_slice(),
+        _ => slice::from_ref(disjunct),
+    })
+}
+
+// Anything other than a comparison can only appear in a conjunction if the predicate isn't
+// normalized, and is skipped as if it were true.
+fn comparisons(conjunction: &[Predicate]) -> impl Iterator<Item = &Comparison> {
+    conjunction.iter().filter_map(|conjunct| match conjunct {
+        Predicate::Comparison(comparison) => Some(comparison),
+        _ => None,
+    })
+}
+
+fn prepare_comparison_comparison(p: &Comparison, q: &Comparison) -> Predicate {
     use crate::predicate::ComparisonOperator::*;
 
     if p.left != q.left {
         return Predicate::boolean(true);
     }
 
-    let mut p_ref = Cow::Borrowed(p);
-    let mut q_ref = Cow::Borrowed(q);
-
-    if swap {
-        mem::swap(p_ref.to_mut(), q_ref.to_mut());
-    }
-
-    match (p_ref.operator, q_ref.operator) {
-        (Eq, Eq) => Predicate::comparison(Eq, p_ref.right, q_ref.right),
-        (Eq, Ne) | (Ne, Eq) => Predicate::comparison(Ne, p_ref.right, q_ref.right),
+    match (p.operator, q.operator) {
+        (Eq, Eq) => Predicate::comparison(Eq, p.right, q.right),
+        (Eq, Ne) | (Ne, Eq) => Predicate::comparison(Ne, p.right, q.right),
         (Eq, Lt) | (Gt, Eq) | (Gt, Lt) | (Ge, Lt) | (Gt, Le) => {
-            Predicate::comparison(Lt, p_ref.right, q_ref.right)
+            Predicate::comparison(Lt, p.right, q.right)
         }
-        (Eq, Le) | (Ge, Eq) | (Ge, Le) => Predicate::comparison(Le, p_ref.right, q_ref.right),
+        (Eq, Le) | (Ge, Eq) | (Ge, Le) => Predicate::comparison(Le, p.right, q.right),
         (Eq, Gt) | (Lt, Eq) | (Lt, Gt) | (Le, Gt) | (Lt, Ge) => {
-            Predicate::comparison(Gt, p_ref.right, q_ref.right)
+            Predicate::comparison(Gt, p.right, q.right)
         }
-        (Eq, Ge) | (Le, Eq) | (Le, Ge) => Predicate::comparison(Ge, p_ref.right, q_ref.right),
+        (Eq, Ge) | (Le, Eq) | (Le, Ge) => Predicate::comparison(Ge, p.right, q.right),
         _ => Predicate::boolean(true),
     }
 }
 
-fn prepare_comparison_conjunction(p: &Comparison, q: &[Predicate], swap: bool) -> Predicate {
-    Predicate::conjunction(
-        q.iter()
-            .filter_map(|q_conjunct| match q_conjunct {
-                Predicate::Comparison(q_comparison) => {
-                    Some(prepare_comparison_comparison(p, q_comparison, swap))
-                }
-                _ => None,
-            })
-            .collect(),
-    )
-}
-
-fn prepare_comparison_disjunction(p: &Comparison, q: &[Predicate], swap: bool) -> Predicate {
-    Predicate::disjunction(
-        q.iter()
-            .filter_map(|q_disjunct| match q_disjunct {
-                Predicate::Comparison(q_comparison) => {
-                    Some(prepare_comparison_comparison(p, q_comparison, swap))
-                }
-                Predicate::Connective(_q_connective @ Connective::Conjunction, q_operands) => {
-                    Some(prepare_comparison_conjunction(p, q_operands, swap))
-                }
-                _ => None,
-            })
-            .collect(),
-    )
-}
-
-fn prepare_conjunction_comparison(p: &[Predicate], q: &Comparison, swap: bool) -> Predicate {
-    prepare_comparison_conjunction(q, p, !swap)
-}
+fn prepare_normalized(p: &Predicate, q: &Predicate) -> Predicate {
+    let mut disjuncts = vec![];
 
-fn prepare_conjunction_conjunction(p: &[Predicate], q: &[Predicate], swap: bool) -> Predicate {
-    Predicate::conjunction(
-        p.iter()
-            .filter_map(|p_conjunct| match p_conjunct {
-                Predicate::Comparison(p_comparison) => {
-                    Some(prepare_comparison_conjunction(p_comparison, q, swap))
-                }
-                _ => None,
-            })
-            .collect(),
-    )
-}
+    for p_conjunction in conjunctions(p) {
+        for q_conjunction in conjunctions(q) {
+            let mut conjuncts = vec![];
 
-fn prepare_conjunction_disjunction(p: &[Predicate], q: &[Predicate], swap: bool) -> Predicate {
-    Predicate::disjunction(
-        q.iter()
-            .filter_map(|q_disjunct| match q_disjunct {
-                Predicate::Comparison(q_comparison) => {
-                    Some(prepare_conjunction_comparison(p, q_comparison, swap))
+            for p_comparison in comparisons(p_conjunction) {
+                for q_comparison in comparisons(q_conjunction) {
+                    conjuncts.push(prepare_comparison_comparison(p_comparison, q_comparison));
                 }
-                Predicate::Connective(_q_connective @ Connective::Conjunction, q_operands) => {
-                    Some(prepare_conjunction_conjunction(p, q_operands, swap))
-                }
-                _ => None,
-            })
-            .collect(),
-    )
-}
-
-fn prepare_disjunction_comparison(p: &[Predicate], q: &Comparison, swap: bool) -> Predicate {
-    prepare_comparison_disjunction(q, p, !swap)
-}
+            }
 
-fn prepare_disjunction_conjunction(p: &[Predicate], q: &[Predicate], swap: bool) -> Predicate {
-    prepare_conjunction_disjunction(q, p, !swap)
-}
+            disjuncts.push(Predicate::conjunction(conjuncts));
+        }
+    }
 
-fn prepare_disjunction_disjunction(p: &[Predicate], q: &[Predicate], swap: bool) -> Predicate {
-    Predicate::disjunction(
-        p.iter()
-            .filter_map(|p_disjunct| match p_disjunct {
-                Predicate::Comparison(p_comparison) => {
-                    Some(prepare_comparison_disjunction(p_comparison, q, swap))
-                }
-                Predicate::Connective(_p_connective @ Connective::Conjunction, p_operands) => {
-                    Some(prepare_conjunction_disjunction(p_operands, q, swap))
-                }
-                _ => None,
-            })
-            .collect(),
-    )
+    Predicate::disjunction(disjuncts)
 }
 
 fn solve_comparison_comparison(
@@ -196,174 +157,61 @@ fn solve_comparison_comparison(
     }
 }
 
-fn solve_comparison_conjunction(
-    p: &Comparison,
-    p_args: &[Value],
-    q: &[Predicate],
-    q_args: &[Value],
-) -> bool {
-    q.iter().all(|q_conjunct| match q_conjunct {
-        Predicate::Comparison(q_comparison) => {
-            solve_comparison_comparison(p, p_args, q_comparison, q_args)
-        }
-        _ => true,
-    })
-}
-
-fn solve_comparison_disjunction(
-    p: &Comparison,
-    p_args: &[Value],
-    q: &[Predicate],
-    q_args: &[Value],
-) -> bool {
-    q.iter().any(|q_disjunct| match q_disjunct {
-        Predicate::Comparison(q_comparison) => {
-            solve_comparison_comparison(p, p_args, q_comparison, q_args)
-        }
-        Predicate::Connective(_q_connective @ Connective::Conjunction, q_operands) => {
-            solve_comparison_conjunction(p, p_args, q_operands, q_args)
-        }
-        _ => true,
-    })
-}
-
-fn solve_conjunction_comparison(
-    p: &[Predicate],
-    p_args: &[Value],
-    q: &Comparison,
-    q_args: &[Value],
-) -> bool {
-    solve_comparison_conjunction(q, q_args, p, p_args)
-}
-
+// Two conjunctions conflict unless some pair of their comparisons rules out every row.
 fn solve_conjunction_conjunction(
     p: &[Predicate],
     p_args: &[Value],
     q: &[Predicate],
     q_args: &[Value],
 ) -> bool {
-    p.iter().all(|p_conjunct| match p_conjunct {
-        Predicate::Comparison(p_comparison) => {
-            solve_comparison_conjunction(p_comparison, p_args, q, q_args)
-        }
-        _ => true,
-    })
-}
-
-fn solve_conjunction_disjunction(
-    p: &[Predicate],
-    p_args: &[Value],
-    q: &[Predicate],
-    q_args: &[Value],
-) -> bool {
-    q.iter().any(|q_disjunct| match q_disjunct {
-        Predicate::Comparison(q_comparison) => {
-            solve_conjunction_comparison(p, p_args, q_comparison, q_args)
-        }
-        Predicate::Connective(_q_connective @ Connective::Conjunction, q_operands) => {
-            solve_conjunction_conjunction(p, p_args, q_operands, q_args)
-        }
-        _ => true,
+    comparisons(p).all(|p_comparison| {
+        comparisons(q).all(|q_comparison| {
+            solve_comparison_comparison(p_comparison, p_args, q_comparison, q_args)
+        })
     })
 }
 
-fn solve_disjunction_comparison(
-    p: &[Predicate],
-    p_args: &[Value],
-    q: &Comparison,
-    q_args: &[Value],
-) -> bool {
-    solve_comparison_disjunction(q, q_args, p, p_args)
-}
+pub fn dnf_blowup(p: &Predicate) -> usize {
+    // Visits the predicate in postorder with an explicit stack, since templates can nest deeply.
+    // A connective is pushed once to visit its operands and again to combine their blowups.
+    let mut stack = vec![(p, false)];
+    let mut blowups = vec![];
+
+    while let Some((node, visited)) = stack.pop() {
+        match node {
+            Predicate::Comparison(_) => blowups.push(1),
+            Predicate::Connective(connective, operands) if visited => {
+                let operand_blowups = blowups.drain(blowups.len() - operands.len()..);
+
+                let blowup = match connective {
+                    Connective::Conjunction => {
+                        operand_blowups.fold(1, |acc: usize, x| acc.saturating_mul(x))
+                    }
+                    Connective::Disjunction => {
+                        operand_blowups.fold(0, |acc: usize, x| acc.saturating_add(x))
+                    }
+                };
 
-fn solve_disjunction_conjunction(
-    p: &[Predicate],
-    p_args: &[Value],
-    q: &[Predicate],
-    q_args: &[Value],
-) -> bool {
-    solve_conjunction_disjunction(q, q_args, p, p_args)
-}
+                blowups.push(blowup);
+            }
+            Predicate::Connective(_, operands) => {
+                stack.push((node, true));
 
-fn solve_disjunction_disjunction(
-    p: &[Predicate],
-    p_args: &[Value],
-    q: &[Predicate],
-    q_args: &[Value],
-) -> bool {
-    p.iter().any(|p_disjunct| match p_disjunct {
-        Predicate::Comparison(p_comparison) => {
-            solve_comparison_disjunction(p_comparison, p_args, q, q_args)
-        }
-        Predicate::Connective(_p_connective @ Connective::Conjunction, p_operands) => {
-            solve_conjunction_disjunction(p_operands, p_args, q, q_args)
+                for operand in operands {
+                    stack.push((operand, false));
+                }
+            }
         }
-        _ => true,
-    })
-}
-
-pub fn dnf_blowup(p: &Predicate) -> usize {
-    match p {
-        Predicate::Comparison(_) => 1,
-        Predicate::Connective(connective, operands) => match connective {
-            Connective::Conjunction => operands.iter().fold(1, |acc, x| acc * dnf_blowup(x)),
-            Connective::Disjunction => operands.iter().fold(0, |acc, x| acc + dnf_blowup(x)),
-        },
     }
+
+    blowups.pop().unwrap()
 }
 
 pub fn prepare(p: &Predicate, q: &Predicate) -> Predicate {
     let mut r = Predicate::conjunction(
-        cluster(p, q)
-            .map(|(mut p_conjunct, mut q_conjunct)| {
-                p_conjunct.normalize();
-                q_conjunct.normalize();
-
-                match (&p_conjunct, &q_conjunct) {
-                    (Predicate::Comparison(p_comparison), Predicate::Comparison(q_comparison)) => {
-                        prepare_comparison_comparison(p_comparison, q_comparison, false)
-                    }
-                    (
-                        Predicate::Comparison(p_comparison),
-                        Predicate::Connective(q_connective, q_operands),
-                    ) => match q_connective {
-                        Connective::Conjunction => {
-                            prepare_comparison_conjunction(p_comparison, q_operands, false)
-                        }
-                        Connective::Disjunction => {
-                            prepare_comparison_disjunction(p_comparison, q_operands, false)
-                        }
-                    },
-                    (
-                        Predicate::Connective(p_connective, p_operands),
-                        Predicate::Comparison(q_comparison),
-                    ) => match p_connective {
-                        Connective::Conjunction => {
-                            prepare_conjunction_comparison(p_operands, q_comparison, false)
-                        }
-                        Connective::Disjunction => {
-                            prepare_disjunction_comparison(p_operands, q_comparison, false)
-                        }
-                    },
-                    (
-                        Predicate::Connective(p_connective, p_operands),
-                        Predicate::Connective(q_connective, q_operands),
-                    ) => match (p_connective, q_connective) {
-                        (Connective::Conjunction, Connective::Conjunction) => {
-                            prepare_conjunction_conjunction(p_operands, q_operands, false)
-                        }
-                        (Connective::Conjunction, Connective::Disjunction) => {
-                            prepare_conjunction_disjunction(p_operands, q_operands, false)
-                        }
-                        (Connective::Disjunction, Connective::Conjunction) => {
-                            prepare_disjunction_conjunction(p_operands, q_operands, false)
-                        }
-                        (Connective::Disjunction, Connective::Disjunction) => {
-                            prepare_disjunction_disjunction(p_operands, q_operands, false)
-                        }
-                    },
-                }
-            })
+        prepare_clusters(p, q)
+            .iter()
+            .map(|(p_conjunct, q_conjunct)| prepare_normalized(p_conjunct, q_conjunct))
             .collect(),
     );
 
@@ -372,86 +220,40 @@ pub fn prepare(p: &Predicate, q: &Predicate) -> Predicate {
     r
 }
 
-pub fn evaluate(conflict: &Predicate, p_args: &[Value], q_args: &[Value]) -> bool {
-    use crate::predicate::ComparisonOperator::*;
-
-    match conflict {
-        Predicate::Comparison(comparison) => match comparison.operator {
-            Eq => p_args[comparison.left] == q_args[comparison.right],
-            Ne => p_args[comparison.left] != q_args[comparison.right],
-            Lt => p_args[comparison.left] < q_args[comparison.right],
-            Le => p_args[comparison.left] <= q_args[comparison.right],
-            Gt => p_args[comparison.left] > q_args[comparison.right],
-            Ge => p_args[comparison.left] >= q_args[comparison.right],
-        },
-        Predicate::Connective(connective, operands) => match connective {
-            Connective::Conjunction => operands
-                .iter()
-                .all(|operand| evaluate(operand, p_args, q_args)),
-            Connective::Disjunction => operands
-                .iter()
-                .any(|operand| evaluate(operand, p_args, q_args)),
-        },
-    }
-}
-
 pub fn solve_dnf(p: &Predicate, p_args: &[Value], q: &Predicate, q_args: &[Value]) -> bool {
     debug_assert!(p.is_normalized());
     debug_assert!(q.is_normalized());
 
-    match (p, q) {
-        (Predicate::Comparison(p_comparison), Predicate::Comparison(q_comparison)) => {
-            solve_comparison_comparison(p_comparison, p_args, q_comparison, q_args)
-        }
-        (Predicate::Comparison(p_comparison), Predicate::Connective(q_connective, q_operands)) => {
-            match q_connective {
-                Connective::Conjunction => {
-                    solve_comparison_conjunction(p_comparison, p_args, q_operands, q_args)
-                }
-                Connective::Disjunction => {
-                    solve_comparison_disjunction(p_comparison, p_args, q_operands, q_args)
-                }
-            }
-        }
-        (Predicate::Connective(p_connective, p_operands), Predicate::Comparison(q_comparison)) => {
-            match p_connective {
-                Connective::Conjunction => {
-                    solve_conjunction_comparison(p_operands, p_args, q_comparison, q_args)
-                }
-                Connective::Disjunction => {
-                    solve_disjunction_comparison(p_operands, p_args, q_comparison, q_args)
-                }
-            }
-        }
-        (
-            Predicate::Connective(p_connective, p_operands),
-            Predicate::Connective(q_connective, q_operands),
-        ) => match (p_connective, q_connective) {
-            (Connective::Conjunction, Connective::Conjunction) => {
-                solve_conjunction_conjunction(p_operands, p_args, q_operands, q_args)
-            }
-            (Connective::Conjunction, Connective::Disjunction) => {
-                solve_conjunction_disjunction(p_operands, p_args, q_operands, q_args)
-            }
-            (Connective::Disjunction, Connective::Conjunction) => {
-                solve_disjunction_conjunction(p_operands, p_args, q_operands, q_args)
-            }
-            (Connective::Disjunction, Connective::Disjunction) => {
-                solve_disjunction_disjunction(p_operands, p_args, q_operands, q_args)
-            }
-        },
+    conjunctions(p).any(|p_conjunction| {
+        conjunctions(q).any(|q_conjunction| {
+            solve_conjunction_conjunction(p_conjunction, p_args, q_conjunction, q_args)
+        })
+    })
+}
+
+/// Clusters two predicates and normalizes each cluster's conjunctions, which is all of
+/// `solve_clustered` that doesn't depend on the arguments.
+pub fn prepare_clusters(p: &Predicate, q: &Predicate) -> Vec<(Predicate, Predicate)> {
+    let mut clusters = cluster(p, q);
+
+    for (p_conjunct, q_conjunct) in &mut clusters {
+        p_conjunct.normalize();
+        q_conjunct.normalize();
     }
+
+    clusters
 }
 
-pub fn solve_clustered(
-    p: &Predicate,
+pub fn solve_clusters(
+    clusters: &[(Predicate, Predicate)],
     p_args: &[Value],
-    q: &Predicate,
     q_args: &[Value],
 ) -> bool {
-    cluster(&p, &q).all(|(mut p_conjunct, mut q_conjunct)| {
-        p_conjunct.normalize();
-        q_conjunct.normalize();
-        solve_dnf(&p_conjunct, p_args, &q_conjunct, q_args)
-    })
+    clusters
+        .iter()
+        .all(|(p_conjunct, q_conjunct)| solve_dnf(p_conjunct, p_args, q_conjunct, q_args))
+}
+
+pub fn solve_clustered(p: &Predicate, p_args: &[Value], q: &Predicate, q_args: &[Value]) -> bool {
+    solve_clusters(&prepare_clusters(p, q), p_args, q_args)
 }
@@ -0,0 +1,484 @@
+//! Renders predicates and request templates as SQL, for diagnostics and for documenting workloads,
+//! and parses predicates back from the same syntax.
+//!
+//! Parameters are written as SQLite's numbered placeholders, so `?1` is the first argument. Columns
+//! are named by a `TableSchema`, or as `column_0`, `column_1` and so on without one.
+//!
+//! With the `sql` feature, templates can also be inferred from whole statements. See
+//! `RequestTemplate::from_sql`.
+
+use crate::predicate::{self, ComparisonOperator, Connective, Expression, Predicate, Value};
+use crate::RequestTemplate;
+use std::fmt::{self, Write};
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+#[cfg(feature = "sql")]
+mod inference;
+
+#[cfg(feature = "sql")]
+pub use inference::InferenceError;
+
+/// The names of a table and its columns, indexed as in request templates.
+#[derive(Clone, Debug)]
+pub struct TableSchema {
+    pub name: String,
+    pub columns: Vec<String>,
+}
+
+impl TableSchema {
+    pub fn new(name: &str, columns: &[&str]) -> TableSchema {
+        TableSchema {
+            name: name.to_string(),
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+        }
+    }
+
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column == name)
+    }
+}
+
+struct ColumnName<'a>(Option<&'a TableSchema>, usize);
+
+impl fmt::Display for ColumnName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.and_then(|schema| schema.columns.get(self.1)) {
+            Some(name) => f.write_str(name),
+            None => write!(f, "column_{}", self.1),
+        }
+    }
+}
+
+/// A predicate written as a SQL condition. See `Predicate::sql`.
+pub struct Sql<'a> {
+    predicate: &'a Predicate,
+    schema: Option<&'a TableSchema>,
+}
+
+impl Sql<'_> {
+    fn fmt_predicate(&self, f: &mut fmt::Formatter, predicate: &Predicate) -> fmt::Result {
+        match predicate {
+            Predicate::Comparison(comparison) => write!(
+                f,
+                "{} {} ?{}",
+                ColumnName(self.schema, comparison.left),
+                match comparison.operator {
+                    ComparisonOperator::Eq => "=",
+                    ComparisonOperator::Ne => "<>",
+                    ComparisonOperator::Lt => "<",
+                    ComparisonOperator::Le => "<=",
+                    ComparisonOperator::Gt => ">",
+                    ComparisonOperator::Ge => ">=",
+                },
+                comparison.right + 1
+            ),
+
+            Predicate::Connective(Connective::Conjunction, operands) if operands.is_empty() => {
+                f.write_str("TRUE")
+            }
+
+            Predicate::Connective(Connective::Disjunction, operands) if operands.is_empty() => {
+                f.write_str("FALSE")
+            }
+
+            Predicate::Connective(connective, operands) => {
+                let separator = match connective {
+                    Connective::Conjunction => " AND ",
+                    Connective::Disjunction => " OR ",
+                };
+
+                for (i, operand) in operands.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(separator)?;
+                    }
+
+                    if let Predicate::Connective(..) = operand {
+                        f.write_char('(')?;
+                        self.fmt_predicate(f, operand)?;
+                        f.write_char(')')?;
+                    } else {
+                        self.fmt_predicate(f, operand)?;
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Sql<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_predicate(f, self.predicate)
+    }
+}
+
+fn fmt_value(f: &mut fmt::Formatter, value: &Value) -> fmt::Result {
+    match value {
+        Value::Boolean(true) => f.write_str("TRUE"),
+        Value::Boolean(false) => f.write_str("FALSE"),
+        Value::Integer(i) => write!(f, "{}", i),
+        Value::String(s) => write!(f, "'{}'", s.replace('\'', "''")),
+        Value::Timestamp(t) => write!(f, "{}", t),
+        Value::Bytes(bytes) => {
+            f.write_str("X'")?;
+
+            for byte in bytes {
+                write!(f, "{:02X}", byte)?;
+            }
+
+            f.write_char('\'')
+        }
+        Value::Decimal(d) => write!(f, "{}", d),
+    }
+}
+
+struct SqlExpression<'a>(&'a Expression);
+
+impl fmt::Display for SqlExpression<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Expression::Parameter(parameter) => write!(f, "?{}", parameter + 1),
+            Expression::Constant(value) => fmt_value(f, value),
+            Expression::Sum(left, right) => {
+                write!(f, "({} + {})", SqlExpression(left), SqlExpression(right))
+            }
+            Expression::Difference(left, right) => {
+                write!(f, "({} - {})", SqlExpression(left), SqlExpression(right))
+            }
+        }
+    }
+}
+
+/// Why a condition could not be parsed, and the byte offset in the text where parsing stopped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.position)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Identifier(String),
+    Parameter(usize),
+    Operator(ComparisonOperator),
+    Open,
+    Close,
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    schema: &'a TableSchema,
+    /// The next token and its offset, or `None` at the end of the text.
+    next: Option<(usize, Token)>,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str, schema: &'a TableSchema) -> Result<Parser<'a>, ParseError> {
+        let mut parser = Parser {
+            text,
+            chars: text.char_indices().peekable(),
+            schema,
+            next: None,
+            depth: 0,
+        };
+
+        parser.advance()?;
+        Ok(parser)
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, ParseError> {
+        Err(ParseError {
+            position: self
+                .next
+                .as_ref()
+                .map_or(self.text.len(), |(position, _)| *position),
+            message,
+        })
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, start: usize, f: F) -> &'a str {
+        let mut end = start;
+
+        while let Some(&(i, c)) = self.chars.peek() {
+            if !f(c) {
+                break;
+            }
+
+            end = i + c.len_utf8();
+            self.chars.next();
+        }
+
+        &self.text[start..end]
+    }
+
+    /// Reads the next token into `next` and returns the previous one.
+    fn advance(&mut self) -> Result<Option<Token>, ParseError> {
+        while let Some(&(_, c)) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+
+            self.chars.next();
+        }
+
+        let token = match self.chars.next() {
+            None => None,
+            Some((start, c)) => {
+                let token = match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    '=' => Token::Operator(ComparisonOperator::Eq),
+                    '!' if self.chars.next_if(|&(_, c)| c == '=').is_some() => {
+                        Token::Operator(ComparisonOperator::Ne)
+                    }
+                    '<' if self.chars.next_if(|&(_, c)| c == '=').is_some() => {
+                        Token::Operator(ComparisonOperator::Le)
+                    }
+                    '<' if self.chars.next_if(|&(_, c)| c == '>').is_some() => {
+                        Token::Operator(ComparisonOperator::Ne)
+                    }
+                    '<' => Token::Operator(ComparisonOperator::Lt),
+                    '>' if self.chars.next_if(|&(_, c)| c == '=').is_some() => {
+                        Token::Operator(ComparisonOperator::Ge)
+                    }
+                    '>' => Token::Operator(ComparisonOperator::Gt),
+                    '?' => {
+                        let digits = self.take_while(start + 1, |c| c.is_ascii_digit());
+
+                        match digits.parse::<usize>() {
+                            Ok(number) if number > 0 => Token::Parameter(number - 1),
+                            _ => {
+                                return Err(ParseError {
+                                    position: start,
+                                    message: "expected a parameter numbered from ?1".to_string(),
+                                })
+                            }
+                        }
+                    }
+                    c if c.is_alphabetic() || c == '_' => Token::Identifier(
+                        self.take_while(start, |c| c.is_alphanumeric() || c == '_')
+                            .to_string(),
+                    ),
+                    c => {
+                        return Err(ParseError {
+                            position: start,
+                            message: format!("unexpected '{}'", c),
+                        })
+                    }
+                };
+
+                Some((start, token))
+            }
+        };
+
+        Ok(std::mem::replace(&mut self.next, token).map(|(_, token)| token))
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.next, Some((_, Token::Identifier(word))) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect_parameter(&mut self) -> Result<usize, ParseError> {
+        match &self.next {
+            Some((_, Token::Parameter(parameter))) => {
+                let parameter = *parameter;
+                self.advance()?;
+                Ok(parameter)
+            }
+            _ => self.error("expected a parameter".to_string()),
+        }
+    }
+
+    fn connective(
+        &mut self,
+        keyword: &str,
+        connective: Connective,
+        operand: fn(&mut Parser<'a>) -> Result<Predicate, ParseError>,
+    ) -> Result<Predicate, ParseError> {
+        let mut operands = vec![];
+
+        loop {
+            // Operands of the same connective, such as the bounds of a BETWEEN, are flattened.
+            match operand(self)? {
+                Predicate::Connective(c, nested) if c == connective => operands.extend(nested),
+                predicate => operands.push(predicate),
+            }
+
+            if !self.at_keyword(keyword) {
+                break;
+            }
+
+            self.advance()?;
+        }
+
+        Ok(if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else {
+            Predicate::Connective(connective, operands)
+        })
+    }
+
+    fn disjunction(&mut self) -> Result<Predicate, ParseError> {
+        self.connective("OR", Connective::Disjunction, Parser::conjunction)
+    }
+
+    fn conjunction(&mut self) -> Result<Predicate, ParseError> {
+        self.connective("AND", Connective::Conjunction, Parser::operand)
+    }
+
+    fn operand(&mut self) -> Result<Predicate, ParseError> {
+        if self.at_keyword("TRUE") {
+            self.advance()?;
+            return Ok(Predicate::conjunction(vec![]));
+        }
+
+        if self.at_keyword("FALSE") {
+            self.advance()?;
+            return Ok(Predicate::disjunction(vec![]));
+        }
+
+        match &self.next {
+            Some((_, Token::Open)) => {
+                // Every parenthesis nests the predicate at least one level deeper.
+                if self.depth == predicate::MAX_DEPTH {
+                    return self.error(format!("nests deeper than {}", predicate::MAX_DEPTH));
+                }
+
+                self.depth += 1;
+                self.advance()?;
+                let predicate = self.disjunction()?;
+
+                if self.next.as_ref().map(|(_, token)| token) != Some(&Token::Close) {
+                    return self.error("expected ')'".to_string());
+                }
+
+                self.depth -= 1;
+                self.advance()?;
+                Ok(predicate)
+            }
+
+            Some((_, Token::Identifier(name))) => {
+                let column = match self.schema.column(name) {
+                    Some(column) => column,
+                    None => return self.error(format!("unknown column {}", name)),
+                };
+
+                self.advance()?;
+
+                if self.at_keyword("BETWEEN") {
+                    self.advance()?;
+                    let lower = self.expect_parameter()?;
+
+                    if !self.at_keyword("AND") {
+                        return self.error("expected AND".to_string());
+                    }
+
+                    self.advance()?;
+                    let upper = self.expect_parameter()?;
+
+                    return Ok(Predicate::conjunction(vec![
+                        Predicate::comparison(ComparisonOperator::Ge, column, lower),
+                        Predicate::comparison(ComparisonOperator::Le, column, upper),
+                    ]));
+                }
+
+                let operator = match &self.next {
+                    Some((_, Token::Operator(operator))) => *operator,
+                    _ => return self.error("expected a comparison operator".to_string()),
+                };
+
+                self.advance()?;
+                let parameter = self.expect_parameter()?;
+                Ok(Predicate::comparison(operator, column, parameter))
+            }
+
+            _ => self.error("expected a comparison".to_string()),
+        }
+    }
+}
+
+impl Predicate {
+    /// Returns the predicate written as a SQL condition, naming columns by `schema` if given.
+    pub fn sql<'a>(&'a self, schema: Option<&'a TableSchema>) -> Sql<'a> {
+        Sql {
+            predicate: self,
+            schema,
+        }
+    }
+
+    /// Parses a condition in the syntax that `sql` writes, with columns named by `schema`. A
+    /// column may also be compared to a range with `BETWEEN ?1 AND ?2`.
+    pub fn parse_sql(text: &str, schema: &TableSchema) -> Result<Predicate, ParseError> {
+        let mut parser = Parser::new(text, schema)?;
+        let predicate = parser.disjunction()?;
+
+        match parser.next {
+            None => Ok(predicate),
+            Some(_) => parser.error("expected AND, OR or the end".to_string()),
+        }
+    }
+}
+
+impl RequestTemplate {
+    /// Returns a statement that covers the same rows and columns as the template. A template that
+    /// writes becomes an `UPDATE` whose new values are named parameters, such as `:balance`, and
+    /// one that only reads becomes a `SELECT`. Derived parameters are numbered after the supplied
+    /// arguments, which a template doesn't know the number of, so they are listed in a comment.
+    pub fn to_sql(&self, schema: &TableSchema) -> String {
+        let mut sql = String::new();
+
+        if !self.derived.is_empty() {
+            sql += "-- derived parameters, in order after the arguments:";
+
+            for expression in &self.derived {
+                write!(sql, " {}", SqlExpression(expression)).unwrap();
+            }
+
+            sql.push('\n');
+        }
+
+        if self.write_columns.is_empty() {
+            let columns = self
+                .read_columns
+                .iter()
+                .map(|column| ColumnName(Some(schema), column).to_string())
+                .collect::<Vec<_>>();
+
+            write!(
+                sql,
+                "SELECT {} FROM {}",
+                if columns.is_empty() {
+                    "1".to_string()
+                } else {
+                    columns.join(", ")
+                },
+                schema.name
+            )
+            .unwrap();
+        } else {
+            let assignments = self
+                .write_columns
+                .iter()
+                .map(|column| {
+                    let name = ColumnName(Some(schema), column);
+                    format!("{} = :{}", name, name)
+                })
+                .collect::<Vec<_>>();
+
+            write!(sql, "UPDATE {} SET {}", schema.name, assignments.join(", ")).unwrap();
+        }
+
+        write!(sql, " WHERE {};", self.predicate.sql(Some(schema))).unwrap();
+        sql
+    }
+}
@@ -0,0 +1,431 @@
+//! Infers request templates from parameterized `SELECT`, `UPDATE`, `DELETE` and `INSERT`
+//! statements on a single table.
+
+use super::TableSchema;
+use crate::predicate::{ComparisonOperator, Connective, Predicate};
+use crate::RequestTemplate;
+use fnv::FnvHashSet;
+use sqlparser::ast::{
+    BinaryOperator, Expr, Ident, ObjectName, Query, SelectItem, SetExpr, Statement, TableAlias,
+    TableFactor, TableWithJoins, Value,
+};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+use std::fmt;
+
+/// Why a template could not be inferred from a statement.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InferenceError {
+    /// The statement is not valid SQL.
+    Syntax(String),
+    /// The statement is valid, but is not one that a template can describe, such as a join.
+    Unsupported(String),
+    UnknownTable(String),
+    UnknownColumn(String),
+}
+
+impl fmt::Display for InferenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InferenceError::Syntax(message) => write!(f, "syntax error: {}", message),
+            InferenceError::Unsupported(message) => write!(f, "unsupported: {}", message),
+            InferenceError::UnknownTable(name) => write!(f, "unknown table {}", name),
+            InferenceError::UnknownColumn(name) => write!(f, "unknown column {}", name),
+        }
+    }
+}
+
+fn unsupported<T>(message: &str) -> Result<T, InferenceError> {
+    Err(InferenceError::Unsupported(message.to_string()))
+}
+
+/// The table a statement accesses, and the names its columns may be qualified by.
+struct Scope<'a> {
+    table: usize,
+    schema: &'a TableSchema,
+    alias: Option<&'a Ident>,
+    /// The number of anonymous `?` placeholders seen so far in the condition.
+    num_anonymous: usize,
+    read_columns: FnvHashSet<usize>,
+}
+
+impl<'a> Scope<'a> {
+    fn new(
+        schemas: &'a [TableSchema],
+        name: &ObjectName,
+        alias: Option<&'a TableAlias>,
+    ) -> Result<Scope<'a>, InferenceError> {
+        let name = name.to_string();
+
+        let table = match schemas
+            .iter()
+            .position(|schema| schema.name.eq_ignore_ascii_case(&name))
+        {
+            Some(table) => table,
+            None => return Err(InferenceError::UnknownTable(name)),
+        };
+
+        Ok(Scope {
+            table,
+            schema: &schemas[table],
+            alias: alias.map(|alias| &alias.name),
+            num_anonymous: 0,
+            read_columns: FnvHashSet::default(),
+        })
+    }
+
+    fn from(
+        schemas: &'a [TableSchema],
+        from: &'a [TableWithJoins],
+    ) -> Result<Scope<'a>, InferenceError> {
+        match from {
+            [TableWithJoins { relation, joins }] if joins.is_empty() => match relation {
+                TableFactor::Table { name, alias, .. } => Scope::new(schemas, name, alias.as_ref()),
+                _ => unsupported("a FROM item other than a table"),
+            },
+            [] => unsupported("a statement without a table"),
+            _ => unsupported("a join"),
+        }
+    }
+
+    fn column(&self, name: &Ident) -> Result<usize, InferenceError> {
+        self.schema
+            .columns
+            .iter()
+            .position(|column| column.eq_ignore_ascii_case(&name.value))
+            .ok_or_else(|| InferenceError::UnknownColumn(name.value.clone()))
+    }
+
+    /// Returns the column that `expr` names, if it names one.
+    fn column_of(&self, expr: &Expr) -> Result<Option<usize>, InferenceError> {
+        match expr {
+            Expr::Identifier(name) => self.column(name).map(Some),
+            Expr::CompoundIdentifier(names) => match names.as_slice() {
+                [qualifier, name]
+                    if qualifier.value.eq_ignore_ascii_case(&self.schema.name)
+                        || matches!(self.alias, Some(alias) if alias.value == qualifier.value) =>
+                {
+                    self.column(name).map(Some)
+                }
+                _ => Err(InferenceError::UnknownColumn(
+                    names
+                        .iter()
+                        .map(|name| name.value.as_str())
+                        .collect::<Vec<_>>()
+                        .join("."),
+                )),
+            },
+            Expr::Nested(expr) => self.column_of(expr),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the argument that `expr` is a placeholder for, if it is one. Anonymous `?`
+    /// placeholders are numbered in the order they are seen.
+    fn parameter_of(&mut self, expr: &Expr) -> Result<Option<usize>, InferenceError> {
+        match expr {
+            Expr::Value(Value::Placeholder(placeholder)) => {
+                if placeholder == "?" {
+                    self.num_anonymous += 1;
+                    return Ok(Some(self.num_anonymous - 1));
+                }
+
+                match placeholder[1..].parse::<usize>() {
+                    Ok(number) if number > 0 && !placeholder.starts_with(':') => {
+                        Ok(Some(number - 1))
+                    }
+                    _ => unsupported(&format!("the placeholder {}", placeholder)),
+                }
+            }
+            Expr::Nested(expr) => self.parameter_of(expr),
+            _ => Ok(None),
+        }
+    }
+
+    /// Adds the columns that `expr` reads. Anything more than a column or a constant is taken to
+    /// read every column.
+    fn read(&mut self, expr: &Expr) -> Result<(), InferenceError> {
+        match self.column_of(expr)? {
+            Some(column) => {
+                self.read_columns.insert(column);
+            }
+            None if matches!(expr, Expr::Value(_)) => {}
+            None => self.read_all(),
+        }
+
+        Ok(())
+    }
+
+    fn read_all(&mut self) {
+        self.read_columns.extend(0..self.schema.columns.len());
+    }
+
+    fn comparison(
+        &mut self,
+        left: &Expr,
+        operator: ComparisonOperator,
+        right: &Expr,
+    ) -> Result<Predicate, InferenceError> {
+        let left_column = self.column_of(left)?;
+        let left_parameter = self.parameter_of(left)?;
+        let right_column = self.column_of(right)?;
+        let right_parameter = self.parameter_of(right)?;
+
+        match (left_column, left_parameter, right_column, right_parameter) {
+            (Some(column), _, _, Some(parameter)) => {
+                self.read_columns.insert(column);
+                Ok(Predicate::comparison(operator, column, parameter))
+            }
+            (_, Some(parameter), Some(column), _) => {
+                self.read_columns.insert(column);
+                Ok(Predicate::comparison(flip(operator), column, parameter))
+            }
+            _ => {
+                // A comparison that isn't between a column and a parameter, such as to a constant,
+                // is widened to true. Conditions have no negation, so this only adds rows.
+                self.read(left)?;
+                self.read(right)?;
+                Ok(Predicate::boolean(true))
+            }
+        }
+    }
+
+    fn condition(&mut self, expr: &Expr) -> Result<Predicate, InferenceError> {
+        match expr {
+            Expr::Nested(expr) => self.condition(expr),
+
+            Expr::BinaryOp { left, op, right } => {
+                let operator = match op {
+                    BinaryOperator::And => {
+                        return Ok(connective(
+                            Connective::Conjunction,
+                            self.condition(left)?,
+                            self.condition(right)?,
+                        ))
+                    }
+                    BinaryOperator::Or => {
+                        return Ok(connective(
+                            Connective::Disjunction,
+                            self.condition(left)?,
+                            self.condition(right)?,
+                        ))
+                    }
+                    BinaryOperator::Eq => ComparisonOperator::Eq,
+                    BinaryOperator::NotEq => ComparisonOperator::Ne,
+                    BinaryOperator::Lt => ComparisonOperator::Lt,
+                    BinaryOperator::LtEq => ComparisonOperator::Le,
+                    BinaryOperator::Gt => ComparisonOperator::Gt,
+                    BinaryOperator::GtEq => ComparisonOperator::Ge,
+                    _ => return unsupported(&format!("the operator {}", op)),
+                };
+
+                self.comparison(left, operator, right)
+            }
+
+            Expr::Between {
+                expr,
+                negated: false,
+                low,
+                high,
+            } => Ok(connective(
+                Connective::Conjunction,
+                self.comparison(expr, ComparisonOperator::Ge, low)?,
+                self.comparison(expr, ComparisonOperator::Le, high)?,
+            )),
+
+            Expr::Value(Value::Boolean(v)) => Ok(Predicate::boolean(*v)),
+
+            _ => unsupported(&format!("the condition {}", expr)),
+        }
+    }
+
+    fn selection(&mut self, selection: Option<&Expr>) -> Result<Predicate, InferenceError> {
+        match selection {
+            Some(selection) => self.condition(selection),
+            None => Ok(Predicate::boolean(true)),
+        }
+    }
+
+    fn template(self, write_columns: FnvHashSet<usize>, predicate: Predicate) -> RequestTemplate {
+        // Written columns conflict with reads and writes alike, so reading them adds nothing.
+        let read_columns = self
+            .read_columns
+            .difference(&write_columns)
+            .cloned()
+            .collect();
+
+        RequestTemplate::new(self.table, read_columns, write_columns, predicate)
+    }
+}
+
+fn flip(operator: ComparisonOperator) -> ComparisonOperator {
+    match operator {
+        ComparisonOperator::Lt => ComparisonOperator::Gt,
+        ComparisonOperator::Le => ComparisonOperator::Ge,
+        ComparisonOperator::Gt => ComparisonOperator::Lt,
+        ComparisonOperator::Ge => ComparisonOperator::Le,
+        operator => operator,
+    }
+}
+
+/// Joins two conditions, flattening nested connectives of the same kind and folding in the true
+/// conditions that unsupported comparisons are widened to.
+fn connective(connective: Connective, left: Predicate, right: Predicate) -> Predicate {
+    let mut operands = vec![];
+
+    for operand in [left, right] {
+        match operand {
+            Predicate::Connective(Connective::Conjunction, nested) if nested.is_empty() => {
+                if connective == Connective::Disjunction {
+                    return Predicate::boolean(true);
+                }
+            }
+            Predicate::Connective(c, nested) if c == connective => operands.extend(nested),
+            operand => operands.push(operand),
+        }
+    }
+
+    if operands.len() == 1 {
+        operands.pop().unwrap()
+    } else {
+        Predicate::Connective(connective, operands)
+    }
+}
+
+fn select(schemas: &[TableSchema], query: &Query) -> Result<RequestTemplate, InferenceError> {
+    let select = match query.body.as_ref() {
+        SetExpr::Select(select) if query.with.is_none() => select,
+        _ => return unsupported("a query other than a single SELECT"),
+    };
+
+    let mut scope = Scope::from(schemas, &select.from)?;
+    let predicate = scope.selection(select.selection.as_ref())?;
+
+    for item in &select.projection {
+        match item {
+            SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+                scope.read(expr)?
+            }
+            SelectItem::QualifiedWildcard(..) | SelectItem::Wildcard(..) => scope.read_all(),
+        }
+    }
+
+    Ok(scope.template(FnvHashSet::default(), predicate))
+}
+
+impl RequestTemplate {
+    /// Infers a template from a statement on one of `schemas`, which is the template's table.
+    ///
+    /// - A `SELECT` reads the columns it projects or compares.
+    /// - An `UPDATE` writes the columns it sets and reads the ones it compares.
+    /// - A `DELETE` writes every column of the rows it matches.
+    /// - An `INSERT` of a single row of parameters writes every column of the row whose columns
+    ///   equal the parameters.
+    ///
+    /// The predicate is the `WHERE` clause. Comparisons of a column to anything but a parameter
+    /// are widened to true, so the template covers at least the rows the statement accesses.
+    /// Parameters are numbered like `?1` or `$1`, or are anonymous `?` placeholders numbered in the
+    /// order they appear in the condition. The values an `UPDATE` sets are not arguments of the
+    /// template, so they don't count.
+    pub fn from_sql(
+        statement: &str,
+        schemas: &[TableSchema],
+    ) -> Result<RequestTemplate, InferenceError> {
+        let mut statements = Parser::parse_sql(&GenericDialect {}, statement)
+            .map_err(|error| InferenceError::Syntax(error.to_string()))?;
+
+        if statements.len() != 1 {
+            return unsupported("more than one statement");
+        }
+
+        match statements.pop().unwrap() {
+            Statement::Query(query) => select(schemas, &query),
+
+            Statement::Update {
+                table,
+                assignments,
+                from: None,
+                selection,
+                ..
+            } => {
+                let mut scope = Scope::from(schemas, std::slice::from_ref(&table))?;
+                let predicate = scope.selection(selection.as_ref())?;
+
+                let write_columns = assignments
+                    .iter()
+                    .map(|assignment| match assignment.id.last() {
+                        Some(name) => scope.column(name),
+                        None => unsupported("an empty assignment"),
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                Ok(scope.template(write_columns, predicate))
+            }
+
+            Statement::Delete {
+                tables,
+                from,
+                using: None,
+                selection,
+                ..
+            } if tables.is_empty() => {
+                let mut scope = Scope::from(schemas, &from)?;
+                let predicate = scope.selection(selection.as_ref())?;
+                let write_columns = (0..scope.schema.columns.len()).collect();
+                Ok(scope.template(write_columns, predicate))
+            }
+
+            Statement::Insert {
+                table_name,
+                columns,
+                source: Some(source),
+                on: None,
+                ..
+            } => {
+                let mut scope = Scope::new(schemas, &table_name, None)?;
+
+                let row = match source.body.as_ref() {
+                    SetExpr::Values(values) if values.rows.len() == 1 => &values.rows[0],
+                    _ => return unsupported("an INSERT other than of a single row"),
+                };
+
+                let columns = if columns.is_empty() {
+                    (0..scope.schema.columns.len()).collect()
+                } else {
+                    columns
+                        .iter()
+                        .map(|column| scope.column(column))
+                        .collect::<Result<Vec<_>, _>>()?
+                };
+
+                if columns.len() != row.len() {
+                    return unsupported("a row with a different number of values than columns");
+                }
+
+                let mut comparisons = vec![];
+
+                for (&column, value) in columns.iter().zip(row) {
+                    match scope.parameter_of(value)? {
+                        Some(parameter) => comparisons.push(Predicate::comparison(
+                            ComparisonOperator::Eq,
+                            column,
+                            parameter,
+                        )),
+                        None => return unsupported("an inserted value other than a parameter"),
+                    }
+                }
+
+                let predicate = if comparisons.len() == 1 {
+                    comparisons.pop().unwrap()
+                } else {
+                    Predicate::conjunction(comparisons)
+                };
+
+                let write_columns = (0..scope.schema.columns.len()).collect();
+                Ok(scope.template(write_columns, predicate))
+            }
+
+            statement => unsupported(&format!("the statement {}", statement)),
+        }
+    }
+}
@@ -6,16 +6,20 @@ struct Node {
     parent: usize,
 }
 
-#[derive(Debug)]
+/// Disjoint sets with union by size and path halving. An instance can be reset and reused, so
+/// that clustering doesn't allocate a fresh one for every pair of requests it solves.
+#[derive(Debug, Default)]
 pub struct UnionFind {
     nodes: Vec<Node>,
+    labels: Vec<usize>,
 }
 
 impl UnionFind {
-    pub fn new(len: usize) -> UnionFind {
-        UnionFind {
-            nodes: (0..len).map(|i| Node { size: 1, parent: i }).collect(),
-        }
+    /// Makes every element in `0..len` a set of its own, keeping the allocated buffers.
+    pub fn reset(&mut self, len: usize) {
+        self.nodes.clear();
+        self.nodes
+            .extend((0..len).map(|i| Node { size: 1, parent: i }));
     }
 
     pub fn union(&mut self, x: usize, y: usize) {
@@ -46,14 +50,27 @@ impl UnionFind {
         xf
     }
 
-    pub fn sets(&mut self) -> Vec<Vec<usize>> {
-        let mut sparse_sets = vec![vec![]; self.nodes.len()];
+    /// Returns the number of sets and the set of each element, with sets numbered in the order of
+    /// their first elements.
+    pub fn labels(&mut self) -> (usize, &[usize]) {
+        let mut num_sets = 0;
+
+        self.labels.clear();
+        self.labels.resize(self.nodes.len(), usize::MAX);
 
         for x in 0..self.nodes.len() {
             let xf = self.find(x);
-            sparse_sets[xf].push(x);
+
+            // A set's label is kept at its root until the root itself is reached, which is never
+            // before the set's first element.
+            if self.labels[xf] == usize::MAX {
+                self.labels[xf] = num_sets;
+                num_sets += 1;
+            }
+
+            self.labels[x] = self.labels[xf];
         }
 
-        sparse_sets.into_iter().filter(|s| !s.is_empty()).collect()
+        (num_sets, &self.labels)
     }
 }
@@ -0,0 +1,100 @@
+//! Checks that a transaction acquiring its requests all at once takes them in table and bucket
+//! order, whatever order they are listed in.
+
+mod common;
+
+use dibs::predicate::Value;
+use dibs::{AcquireError, Dibs, OptimizationLevel, Transaction};
+use std::time::Duration;
+
+const FIRST_WRITE: usize = 0;
+const SECOND_WRITE: usize = 1;
+
+fn dibs() -> Dibs {
+    let write = common::point_templates().remove(common::WRITE);
+    let templates = vec![write.clone(), write.with_table(1)];
+
+    Dibs::new(
+        &[Some(0), Some(0)],
+        &templates,
+        OptimizationLevel::Filtered,
+        None,
+        None,
+        Duration::from_millis(1),
+    )
+}
+
+fn num_requests(dibs: &Dibs, table: usize, bucket: usize) -> usize {
+    dibs.inflight_summary()[table][bucket].num_requests
+}
+
+#[test]
+fn tables_are_acquired_in_order() {
+    let dibs = dibs();
+
+    let mut holder = Transaction::new(0, 0);
+    dibs.acquire(&mut holder, FIRST_WRITE, vec![Value::Integer(3)])
+        .unwrap();
+
+    // The first table's request times out before the second table's is made.
+    let mut transaction = Transaction::new(1, 1);
+    let result = dibs.acquire_all(
+        &mut transaction,
+        &[
+            (SECOND_WRITE, vec![Value::Integer(3)]),
+            (FIRST_WRITE, vec![Value::Integer(3)]),
+        ],
+    );
+
+    assert!(matches!(result, Err(AcquireError::Timeout(_))));
+    assert_eq!(num_requests(&dibs, 1, 3), 0);
+
+    transaction.commit();
+    holder.commit();
+}
+
+#[test]
+fn buckets_are_acquired_in_order() {
+    let dibs = dibs();
+
+    let mut holder = Transaction::new(0, 0);
+    dibs.acquire(&mut holder, FIRST_WRITE, vec![Value::Integer(3)])
+        .unwrap();
+
+    let mut transaction = Transaction::new(1, 1);
+    let result = dibs.acquire_all(
+        &mut transaction,
+        &[
+            (FIRST_WRITE, vec![Value::Integer(5)]),
+            (FIRST_WRITE, vec![Value::Integer(3)]),
+        ],
+    );
+
+    assert!(matches!(result, Err(AcquireError::Timeout(_))));
+    assert_eq!(num_requests(&dibs, 0, 5), 0);
+
+    transaction.commit();
+    holder.commit();
+}
+
+#[test]
+fn unordered_requests_are_all_acquired() {
+    let dibs = dibs();
+
+    let mut transaction = Transaction::new(0, 0);
+    dibs.acquire_all(
+        &mut transaction,
+        &[
+            (SECOND_WRITE, vec![Value::Integer(7)]),
+            (FIRST_WRITE, vec![Value::Integer(5)]),
+            (FIRST_WRITE, vec![Value::Integer(3)]),
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(num_requests(&dibs, 0, 3), 1);
+    assert_eq!(num_requests(&dibs, 0, 5), 1);
+    assert_eq!(num_requests(&dibs, 1, 7), 1);
+
+    transaction.commit();
+}
@@ -0,0 +1,83 @@
+//! Checks that an adaptive table switches levels with requests in flight, and that requests
+//! acquired after a switch still conflict with those from before it.
+
+mod common;
+
+use common::{conflicts, READ, WRITE};
+use dibs::adaptive::AdaptiveController;
+use dibs::predicate::Value;
+use dibs::{AcquireError, Dibs, OptimizationLevel, Transaction};
+use std::time::Duration;
+
+fn dibs() -> Dibs {
+    let mut dibs = common::point_dibs(
+        Some(0),
+        OptimizationLevel::Prepared,
+        Duration::from_millis(1),
+    );
+    assert!(dibs.enable_adaptive(0));
+    dibs
+}
+
+#[test]
+fn switches_keep_earlier_requests() {
+    let dibs = dibs();
+
+    let mut transaction = Transaction::new(0, 0);
+    dibs.acquire(&mut transaction, WRITE, vec![Value::Integer(3)])
+        .unwrap();
+
+    assert!(dibs.set_optimization(0, OptimizationLevel::Filtered));
+    assert!(dibs.optimization(0) == OptimizationLevel::Filtered);
+    assert_eq!(dibs.inflight_summary()[0].len(), 1025);
+
+    assert!(conflicts(&dibs, READ, &[3]));
+    assert!(!conflicts(&dibs, READ, &[4]));
+
+    // The request from before the switch is still in flight.
+    assert!(!dibs.set_optimization(0, OptimizationLevel::Prepared));
+
+    let mut filtered_transaction = Transaction::new(1, 1);
+    dibs.acquire(&mut filtered_transaction, WRITE, vec![Value::Integer(5)])
+        .unwrap();
+
+    transaction.commit();
+    assert!(dibs.set_optimization(0, OptimizationLevel::Prepared));
+    assert!(conflicts(&dibs, READ, &[5]));
+
+    filtered_transaction.commit();
+}
+
+#[test]
+fn costs_count_acquires() {
+    let dibs = dibs();
+
+    for argument in 0..3 {
+        assert!(!conflicts(&dibs, WRITE, &[argument]));
+    }
+
+    assert_eq!(dibs.table_costs(0).unwrap().num_acquires, 3);
+}
+
+#[test]
+fn controller_tries_the_other_level() {
+    let dibs = dibs();
+    let mut controller = AdaptiveController::new(&dibs, 0.0, 1);
+
+    assert!(controller.tick(&dibs).is_empty());
+
+    assert!(!conflicts(&dibs, WRITE, &[3]));
+    assert_eq!(controller.tick(&dibs), vec![0]);
+    assert!(dibs.optimization(0) == OptimizationLevel::Filtered);
+    assert_eq!(controller.num_switches(), 1);
+}
+
+#[test]
+fn optimistic_requests_are_rejected() {
+    let dibs = dibs();
+
+    let mut transaction = Transaction::new(0, 0);
+    let result = dibs.acquire_optimistic(&mut transaction, WRITE, vec![Value::Integer(3)]);
+    assert!(matches!(result, Err(AcquireError::InvalidArguments(_))));
+    transaction.commit();
+}
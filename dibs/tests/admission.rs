@@ -0,0 +1,100 @@
+//! Checks that admission control holds back transactions beyond the limit until one commits.
+
+mod common;
+
+use common::READ;
+use dibs::predicate::Value;
+use dibs::{AcquireError, Dibs, OptimizationLevel, Transaction};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn dibs() -> Dibs {
+    let mut dibs = common::point_dibs(None, OptimizationLevel::Prepared, Duration::from_millis(1));
+    dibs.enable_admission_control(2);
+    dibs
+}
+
+#[test]
+fn admission_waits_for_commit() {
+    let dibs = Arc::new(dibs());
+    let mut holders = vec![];
+
+    for transaction_id in 0..2 {
+        let mut transaction = Transaction::new(transaction_id, transaction_id);
+        dibs.acquire(&mut transaction, READ, vec![Value::Integer(transaction_id)])
+            .unwrap();
+        holders.push(transaction);
+    }
+
+    assert_eq!(dibs.num_active_transactions(), Some(2));
+
+    // Further acquires of an admitted transaction don't need another permit.
+    dibs.acquire(&mut holders[0], READ, vec![Value::Integer(5)])
+        .unwrap();
+
+    let waiter = {
+        let dibs = Arc::clone(&dibs);
+
+        thread::spawn(move || {
+            let mut transaction = Transaction::new(2, 2);
+            dibs.acquire(&mut transaction, READ, vec![Value::Integer(2)])
+                .unwrap();
+            transaction.commit();
+        })
+    };
+
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(dibs.conflict_stats().num_admission_waits, 0);
+    assert_eq!(dibs.num_active_transactions(), Some(2));
+
+    holders.pop().unwrap().commit();
+    waiter.join().unwrap();
+
+    let stats = dibs.conflict_stats();
+    assert_eq!(stats.num_admission_waits, 1);
+    assert!(stats.admission_wait_nanos > 0);
+
+    // A transaction dropped without committing gives up its permit too.
+    drop(holders);
+    assert_eq!(dibs.num_active_transactions(), Some(0));
+}
+
+#[test]
+fn admission_waits_end_at_the_deadline() {
+    let mut dibs = common::point_dibs(None, OptimizationLevel::Prepared, Duration::from_secs(10));
+    dibs.enable_admission_control(1);
+
+    let mut holder = Transaction::new(0, 0);
+    dibs.acquire(&mut holder, READ, vec![Value::Integer(0)])
+        .unwrap();
+
+    let mut transaction = Transaction::new(1, 1);
+    transaction.set_deadline(dibs.clock().now() + Duration::from_millis(20));
+
+    match dibs.acquire(&mut transaction, READ, vec![Value::Integer(1)]) {
+        Err(AcquireError::DeadlineExceeded) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    let mut transaction = Transaction::new(2, 2);
+    transaction.set_wait_budget(Duration::from_millis(20));
+
+    match dibs.acquire(&mut transaction, READ, vec![Value::Integer(2)]) {
+        Err(AcquireError::WaitBudgetExhausted) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    assert_eq!(
+        transaction.remaining_wait_budget(),
+        Some(Duration::default())
+    );
+    assert_eq!(dibs.num_active_transactions(), Some(1));
+
+    holder.commit();
+
+    // With the holder gone, the transaction that gave up is admitted on its next acquire.
+    dibs.acquire(&mut transaction, READ, vec![Value::Integer(2)])
+        .unwrap();
+    transaction.commit();
+}
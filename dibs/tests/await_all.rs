@@ -0,0 +1,120 @@
+//! Checks that a batch of requests is awaited as a whole, and that a waiter panicking mid-wait
+//! doesn't stop the requests it awaited from completing.
+
+use dibs::clock::{Clock, SystemClock};
+use dibs::{Request, RequestVariant, Transaction, WaitStrategy};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn request(transaction_id: usize) -> Arc<Request> {
+    let transaction = Transaction::new(transaction_id, transaction_id);
+    Arc::new(Request::new(
+        &transaction,
+        RequestVariant::Prepared(0),
+        vec![],
+        false,
+    ))
+}
+
+#[test]
+fn await_all_waits_for_every_request() {
+    let requests = [request(0), request(1)];
+    let done = Arc::new(AtomicBool::new(false));
+
+    let waiter = {
+        let requests = requests.clone();
+        let done = Arc::clone(&done);
+
+        thread::spawn(move || {
+            let requests = [&*requests[0], &*requests[1]];
+            let completed = Request::await_all(
+                &requests,
+                Duration::from_secs(10),
+                WaitStrategy::Park,
+                &SystemClock,
+            );
+            done.store(true, Ordering::SeqCst);
+            completed
+        })
+    };
+
+    thread::sleep(Duration::from_millis(10));
+    requests[0].complete();
+    thread::sleep(Duration::from_millis(10));
+    assert!(!done.load(Ordering::SeqCst));
+
+    requests[1].complete();
+    assert!(waiter.join().unwrap());
+}
+
+#[test]
+fn await_all_times_out_while_any_request_is_in_flight() {
+    let strategies = [
+        WaitStrategy::Park,
+        WaitStrategy::SpinThenPark {
+            spins: 10,
+            yields: 10,
+        },
+        WaitStrategy::Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(4),
+        },
+    ];
+
+    let completed = request(0);
+    completed.complete();
+    let in_flight = request(1);
+
+    for &strategy in &strategies {
+        let timeout = Duration::from_millis(10);
+        assert!(!Request::await_all(
+            &[&completed, &in_flight],
+            timeout,
+            strategy,
+            &SystemClock
+        ));
+        assert!(Request::await_all(
+            &[&completed],
+            timeout,
+            strategy,
+            &SystemClock
+        ));
+    }
+}
+
+/// A clock that panics when a waiter parks on it, while the waiter holds its batch's lock.
+struct PanickingClock;
+
+impl Clock for PanickingClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+
+    fn poll_interval(&self, _remaining: Duration) -> Duration {
+        panic!("poll_interval");
+    }
+}
+
+#[test]
+fn requests_complete_after_a_waiter_panics() {
+    let request = request(0);
+
+    let waiter = {
+        let request = Arc::clone(&request);
+
+        thread::spawn(move || {
+            request.await_completion(Duration::from_secs(10), WaitStrategy::Park, &PanickingClock)
+        })
+    };
+
+    assert!(waiter.join().is_err());
+
+    request.complete();
+    assert!(request.await_completion(Duration::default(), WaitStrategy::Park, &SystemClock));
+}
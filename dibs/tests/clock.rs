@@ -0,0 +1,60 @@
+//! Checks that acquires time out against the clock set on `Dibs` rather than the system clock.
+
+mod common;
+
+use common::{READ, WRITE};
+use dibs::clock::ManualClock;
+use dibs::predicate::Value;
+use dibs::{AcquireError, Dibs, Transaction, WaitStrategy};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn dibs(clock: &Arc<ManualClock>, wait_strategy: WaitStrategy) -> Dibs {
+    let mut dibs = common::clocked_dibs(clock);
+    dibs.set_wait_strategy(wait_strategy);
+    dibs
+}
+
+#[test]
+fn acquires_time_out_on_the_set_clock() {
+    let strategies = [
+        WaitStrategy::Park,
+        WaitStrategy::Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(4),
+        },
+    ];
+
+    for &strategy in &strategies {
+        let clock = Arc::new(ManualClock::new());
+        let dibs = Arc::new(dibs(&clock, strategy));
+
+        let mut writer = Transaction::new(0, 0);
+        dibs.acquire(&mut writer, WRITE, vec![Value::Integer(1)])
+            .unwrap();
+
+        let reader = {
+            let dibs = Arc::clone(&dibs);
+
+            thread::spawn(move || {
+                let mut reader = Transaction::new(1, 1);
+                let result = dibs.acquire(&mut reader, READ, vec![Value::Integer(1)]);
+                reader.commit();
+                result
+            })
+        };
+
+        // A timeout of ten seconds, jittered by up to a fifth, elapses on the clock alone.
+        thread::sleep(Duration::from_millis(20));
+        assert!(!reader.is_finished());
+        clock.advance(Duration::from_secs(13));
+
+        match reader.join().unwrap() {
+            Err(AcquireError::Timeout(0)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        writer.commit();
+    }
+}
@@ -0,0 +1,98 @@
+//! Checks that requests conflict only over columns they share, whether solved ad hoc or prepared,
+//! including columns past the first word of a column set.
+
+mod common;
+
+use common::integers;
+use dibs::predicate::{ComparisonOperator, Predicate};
+use dibs::{AcquireError, Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use std::time::Duration;
+
+/// Reads column 1 of the row whose column 0 equals `?0`.
+const READ_LOW: usize = 0;
+/// Reads column 200 of the row whose column 0 equals `?0`.
+const READ_HIGH: usize = 1;
+/// Writes column 200 of the row whose column 0 equals `?0`.
+const WRITE_HIGH: usize = 2;
+
+const OPTIMIZATIONS: [OptimizationLevel; 4] = [
+    OptimizationLevel::Ungrouped,
+    OptimizationLevel::Grouped,
+    OptimizationLevel::Prepared,
+    OptimizationLevel::Filtered,
+];
+
+fn dibs(optimization: OptimizationLevel) -> Dibs {
+    let point = Predicate::comparison(ComparisonOperator::Eq, 0, 0);
+
+    let templates = vec![
+        RequestTemplate::new(
+            0,
+            [1].iter().cloned().collect(),
+            Default::default(),
+            point.clone(),
+        ),
+        RequestTemplate::new(
+            0,
+            [200].iter().cloned().collect(),
+            Default::default(),
+            point.clone(),
+        ),
+        RequestTemplate::new(
+            0,
+            Default::default(),
+            [200].iter().cloned().collect(),
+            point,
+        ),
+    ];
+
+    common::single_table(&templates, Some(0), optimization, Duration::from_millis(1))
+}
+
+/// Like `common::conflicts`, but acquires the request for upgrade.
+fn upgrade_conflicts(dibs: &Dibs, template_id: usize, key: usize) -> bool {
+    let mut transaction = Transaction::new(9, 9);
+    let result = dibs.acquire_for_upgrade(&mut transaction, template_id, integers(&[key]));
+    transaction.commit();
+
+    match result {
+        Ok(()) => false,
+        Err(AcquireError::Timeout(_)) => true,
+        Err(error) => panic!("unexpected error: {:?}", error),
+    }
+}
+
+#[test]
+fn writes_conflict_with_reads_of_the_same_column() {
+    for &optimization in &OPTIMIZATIONS {
+        let dibs = dibs(optimization);
+
+        let mut writer = Transaction::new(0, 0);
+        dibs.acquire(&mut writer, WRITE_HIGH, integers(&[7]))
+            .unwrap();
+
+        assert!(common::conflicts(&dibs, READ_HIGH, &[7]));
+        assert!(common::conflicts(&dibs, WRITE_HIGH, &[7]));
+        assert!(!common::conflicts(&dibs, READ_LOW, &[7]));
+        assert!(!common::conflicts(&dibs, READ_HIGH, &[8]));
+
+        writer.commit();
+    }
+}
+
+#[test]
+fn reads_for_upgrade_conflict_over_shared_columns() {
+    for &optimization in &OPTIMIZATIONS {
+        let dibs = dibs(optimization);
+
+        let mut reader = Transaction::new(0, 0);
+        dibs.acquire_for_upgrade(&mut reader, READ_HIGH, integers(&[7]))
+            .unwrap();
+
+        assert!(upgrade_conflicts(&dibs, READ_HIGH, 7));
+        assert!(!upgrade_conflicts(&dibs, READ_LOW, 7));
+        assert!(!common::conflicts(&dibs, READ_HIGH, &[7]));
+
+        reader.commit();
+    }
+}
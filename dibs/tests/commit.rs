@@ -0,0 +1,64 @@
+//! Checks that committing removes exactly the transaction's own requests from a shared bucket,
+//! whatever order transactions commit in.
+
+mod common;
+
+use common::{integers, WRITE};
+use dibs::{Dibs, OptimizationLevel, Transaction};
+use std::time::Duration;
+
+fn writers(dibs: &Dibs, keys: impl Iterator<Item = usize>) -> Vec<(usize, Transaction)> {
+    keys.map(|key| {
+        let mut writer = Transaction::new(key, key);
+        dibs.acquire(&mut writer, WRITE, integers(&[key])).unwrap();
+        (key, writer)
+    })
+    .collect()
+}
+
+fn num_requests(dibs: &Dibs) -> usize {
+    dibs.inflight_summary()[0][0].num_requests
+}
+
+#[test]
+fn commits_remove_only_their_own_requests() {
+    // Without a filter column, every request shares the table's one bucket.
+    let dibs = common::point_dibs(None, OptimizationLevel::Prepared, Duration::from_millis(1));
+
+    let (odd, even): (Vec<_>, Vec<_>) = writers(&dibs, 0..8)
+        .into_iter()
+        .partition(|(key, _)| key % 2 == 1);
+
+    // Committing from the back and the middle moves the remaining requests around the bucket.
+    for (_, writer) in odd.into_iter().rev() {
+        writer.commit();
+    }
+
+    assert_eq!(num_requests(&dibs), 4);
+
+    for key in 0..8 {
+        assert_eq!(common::conflicts(&dibs, WRITE, &[key]), key % 2 == 0);
+    }
+
+    // New requests take the freed slots, and are removed from them in turn.
+    let (held, released): (Vec<_>, Vec<_>) = writers(&dibs, 10..14)
+        .into_iter()
+        .partition(|(key, _)| key % 2 == 1);
+
+    for (_, writer) in released {
+        writer.commit();
+    }
+
+    assert_eq!(num_requests(&dibs), 6);
+
+    for key in 0..14 {
+        let expected = key < 8 && key % 2 == 0 || key >= 10 && key % 2 == 1;
+        assert_eq!(common::conflicts(&dibs, WRITE, &[key]), expected);
+    }
+
+    for (_, writer) in even.into_iter().chain(held) {
+        writer.commit();
+    }
+
+    assert_eq!(num_requests(&dibs), 0);
+}
@@ -0,0 +1,72 @@
+//! Fixtures shared by the integration tests. Each test crate uses only some of them.
+
+#![allow(dead_code)]
+
+use dibs::clock::ManualClock;
+use dibs::predicate::{ComparisonOperator, Predicate, Value};
+use dibs::{AcquireError, Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The templates of `point_templates`.
+pub const READ: usize = 0;
+pub const WRITE: usize = 1;
+
+/// A read and a write of column 1 of the rows of table 0 whose column 0 equals the argument.
+pub fn point_templates() -> Vec<RequestTemplate> {
+    let point = Predicate::comparison(ComparisonOperator::Eq, 0, 0);
+
+    vec![
+        RequestTemplate::new(
+            0,
+            [1].iter().cloned().collect(),
+            Default::default(),
+            point.clone(),
+        ),
+        RequestTemplate::new(0, Default::default(), [1].iter().cloned().collect(), point),
+    ]
+}
+
+/// Returns a `Dibs` over a single table with the given filter column.
+pub fn single_table(
+    templates: &[RequestTemplate],
+    filter: Option<usize>,
+    optimization: OptimizationLevel,
+    timeout: Duration,
+) -> Dibs {
+    Dibs::new(&[filter], templates, optimization, None, None, timeout)
+}
+
+/// Returns a `Dibs` over `point_templates` on a single table with the given filter column.
+pub fn point_dibs(
+    filter: Option<usize>,
+    optimization: OptimizationLevel,
+    timeout: Duration,
+) -> Dibs {
+    single_table(&point_templates(), filter, optimization, timeout)
+}
+
+/// Returns `point_dibs` without a filter, timing out after ten seconds on the given clock.
+pub fn clocked_dibs(clock: &Arc<ManualClock>) -> Dibs {
+    let mut dibs = point_dibs(None, OptimizationLevel::Prepared, Duration::from_secs(10));
+    dibs.set_clock(Arc::clone(clock) as _);
+    dibs
+}
+
+pub fn integers(values: &[usize]) -> Vec<Value> {
+    values.iter().map(|&value| Value::Integer(value)).collect()
+}
+
+/// Acquires a request in a transaction of its own and returns whether it timed out on a conflict.
+/// The transaction is committed either way.
+pub fn conflicts(dibs: &Dibs, template_id: usize, arguments: &[usize]) -> bool {
+    let mut transaction = Transaction::new(9, 9);
+    let result = dibs.acquire(&mut transaction, template_id, integers(arguments));
+    transaction.commit();
+
+    match result {
+        Ok(()) => false,
+        Err(AcquireError::Timeout(_)) => true,
+        Err(error) => panic!("unexpected error: {:?}", error),
+    }
+}
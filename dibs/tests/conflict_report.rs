@@ -0,0 +1,32 @@
+//! Checks that conflict sampling reports the conflicts acquires run into.
+
+mod common;
+
+use common::{integers, READ, WRITE};
+use dibs::predicate::Value;
+use dibs::{OptimizationLevel, Transaction};
+use std::time::Duration;
+
+#[test]
+fn conflicts_are_reported_by_template_pair_and_value() {
+    let mut dibs = common::point_dibs(None, OptimizationLevel::Prepared, Duration::from_millis(1));
+    assert!(dibs.conflict_report(1).is_none());
+
+    dibs.enable_conflict_sampling(100);
+
+    let mut writer = Transaction::new(0, 0);
+    dibs.acquire(&mut writer, WRITE, integers(&[7])).unwrap();
+
+    for _ in 0..3 {
+        assert!(common::conflicts(&dibs, READ, &[7]));
+    }
+
+    assert!(!common::conflicts(&dibs, READ, &[8]));
+
+    let report = dibs.conflict_report(1).unwrap();
+    assert_eq!(report.num_conflicts, 3);
+    assert_eq!(report.top_pairs, vec![((Some(READ), Some(WRITE)), 3)]);
+    assert_eq!(report.hot_values, vec![(Value::Integer(7), 3)]);
+
+    writer.commit();
+}
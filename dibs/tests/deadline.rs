@@ -0,0 +1,71 @@
+//! Checks that acquires stop waiting at the transaction's deadline.
+
+mod common;
+
+use common::READ;
+use dibs::clock::{Clock, ManualClock};
+use dibs::predicate::Value;
+use dibs::{AcquireError, Transaction};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn acquires_fail_once_the_deadline_has_passed() {
+    let clock = Arc::new(ManualClock::new());
+    let dibs = common::clocked_dibs(&clock);
+
+    let mut transaction = Transaction::new(0, 0);
+    transaction.set_deadline(clock.now() + Duration::from_secs(1));
+    assert_eq!(
+        transaction.remaining_time(clock.now()),
+        Some(Duration::from_secs(1))
+    );
+
+    dibs.acquire(&mut transaction, READ, vec![Value::Integer(1)])
+        .unwrap();
+
+    clock.advance(Duration::from_secs(1));
+
+    match dibs.acquire(&mut transaction, READ, vec![Value::Integer(2)]) {
+        Err(AcquireError::DeadlineExceeded) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    transaction.commit();
+}
+
+#[test]
+fn waits_are_cut_short_at_the_deadline() {
+    use std::thread;
+
+    let clock = Arc::new(ManualClock::new());
+    let dibs = Arc::new(common::clocked_dibs(&clock));
+
+    let mut writer = Transaction::new(0, 0);
+    dibs.acquire(&mut writer, common::WRITE, vec![Value::Integer(1)])
+        .unwrap();
+
+    let reader = {
+        let dibs = Arc::clone(&dibs);
+        let deadline = clock.now() + Duration::from_secs(1);
+
+        thread::spawn(move || {
+            let mut reader = Transaction::new(1, 1);
+            reader.set_deadline(deadline);
+            let result = dibs.acquire(&mut reader, READ, vec![Value::Integer(1)]);
+            reader.commit();
+            result
+        })
+    };
+
+    // The deadline passes well before the timeout of eight seconds or more would.
+    thread::sleep(Duration::from_millis(20));
+    clock.advance(Duration::from_secs(2));
+
+    match reader.join().unwrap() {
+        Err(AcquireError::DeadlineExceeded) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    writer.commit();
+}
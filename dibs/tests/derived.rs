@@ -0,0 +1,102 @@
+//! Checks that requests are solved against the parameters their templates derive from the supplied
+//! arguments.
+
+mod common;
+
+use common::{integers, WRITE};
+use dibs::predicate::{ComparisonOperator, Expression, Predicate, Value};
+use dibs::{AcquireError, Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use std::time::Duration;
+
+/// Reads the rows whose column 0 lies between `?0` and `?0 + ?1`.
+const WINDOW: usize = 2;
+/// Writes the row whose column 0 equals `?0 + 1`.
+const NEXT: usize = 3;
+
+fn dibs() -> Dibs {
+    let window = RequestTemplate::new(
+        0,
+        [1].iter().cloned().collect(),
+        Default::default(),
+        Predicate::conjunction(vec![
+            Predicate::comparison(ComparisonOperator::Ge, 0, 0),
+            Predicate::comparison(ComparisonOperator::Le, 0, 2),
+        ]),
+    )
+    .with_derived(vec![Expression::sum(
+        Expression::Parameter(0),
+        Expression::Parameter(1),
+    )]);
+
+    let next = RequestTemplate::new(
+        0,
+        Default::default(),
+        [1].iter().cloned().collect(),
+        Predicate::comparison(ComparisonOperator::Eq, 0, 1),
+    )
+    .with_derived(vec![Expression::sum(
+        Expression::Parameter(0),
+        Expression::Constant(Value::Integer(1)),
+    )]);
+
+    let mut templates = common::point_templates();
+    templates.extend(vec![window, next]);
+
+    common::single_table(
+        &templates,
+        None,
+        OptimizationLevel::Prepared,
+        Duration::from_millis(1),
+    )
+}
+
+#[test]
+fn requests_conflict_within_derived_bounds() {
+    let dibs = dibs();
+
+    let mut reader = Transaction::new(0, 0);
+    dibs.acquire(&mut reader, WINDOW, integers(&[10, 5]))
+        .unwrap();
+
+    assert!(common::conflicts(&dibs, WRITE, &[10]));
+    assert!(common::conflicts(&dibs, WRITE, &[15]));
+    assert!(!common::conflicts(&dibs, WRITE, &[9]));
+    assert!(!common::conflicts(&dibs, WRITE, &[16]));
+
+    reader.commit();
+}
+
+#[test]
+fn point_requests_conflict_on_derived_keys() {
+    let dibs = dibs();
+
+    let mut writer = Transaction::new(0, 0);
+    dibs.acquire(&mut writer, NEXT, integers(&[7])).unwrap();
+
+    assert!(common::conflicts(&dibs, WRITE, &[8]));
+    assert!(!common::conflicts(&dibs, WRITE, &[7]));
+    assert!(common::conflicts(&dibs, WINDOW, &[0, 8]));
+    assert!(!common::conflicts(&dibs, WINDOW, &[0, 7]));
+
+    writer.commit();
+}
+
+#[test]
+fn unevaluable_expressions_are_invalid_arguments() {
+    let dibs = dibs();
+    let mut transaction = Transaction::new(0, 0);
+
+    let arguments = vec![Value::Integer(10), Value::String("five".to_string())];
+
+    match dibs.acquire(&mut transaction, WINDOW, arguments) {
+        Err(AcquireError::InvalidArguments(_)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    match dibs.acquire(&mut transaction, WINDOW, integers(&[10])) {
+        Err(AcquireError::InvalidArguments(_)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    transaction.commit();
+}
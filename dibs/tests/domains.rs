@@ -0,0 +1,134 @@
+//! Checks that interval pruning rules out conflicts between requests that only overlap outside the
+//! domains their templates declare, like TATP's call forwarding lookups and inserts.
+
+use dibs::predicate::{ComparisonOperator, Domain, Predicate, Value};
+use dibs::{AcquireError, Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use std::time::Duration;
+
+const SF_TYPE: usize = 0;
+const START_TIME: usize = 1;
+const END_TIME: usize = 2;
+
+// sf_type = ?0 AND start_time <= ?1 AND ?2 < end_time
+const GET_DESTINATION: usize = 0;
+// sf_type = ?0 AND start_time = ?1
+const INSERT: usize = 1;
+// sf_type = ?0 AND ?1 <= start_time AND start_time <= ?2
+const DELETE_RANGE: usize = 2;
+
+fn dibs(domains: bool) -> Dibs {
+    let templates = vec![
+        RequestTemplate::new(
+            0,
+            [3].iter().cloned().collect(),
+            Default::default(),
+            Predicate::conjunction(vec![
+                Predicate::comparison(ComparisonOperator::Eq, SF_TYPE, 0),
+                Predicate::comparison(ComparisonOperator::Le, START_TIME, 1),
+                Predicate::comparison(ComparisonOperator::Gt, END_TIME, 2),
+            ]),
+        ),
+        RequestTemplate::new(
+            0,
+            Default::default(),
+            (0..4).collect(),
+            Predicate::conjunction(vec![
+                Predicate::comparison(ComparisonOperator::Eq, SF_TYPE, 0),
+                Predicate::comparison(ComparisonOperator::Eq, START_TIME, 1),
+            ]),
+        ),
+        RequestTemplate::new(
+            0,
+            Default::default(),
+            (0..4).collect(),
+            Predicate::conjunction(vec![
+                Predicate::comparison(ComparisonOperator::Eq, SF_TYPE, 0),
+                Predicate::comparison(ComparisonOperator::Ge, START_TIME, 1),
+                Predicate::comparison(ComparisonOperator::Le, START_TIME, 2),
+            ]),
+        ),
+    ];
+
+    let templates = if domains {
+        templates
+            .into_iter()
+            .map(|template| {
+                template
+                    .with_domain(
+                        START_TIME,
+                        Domain::Values(vec![
+                            Value::Integer(0),
+                            Value::Integer(8),
+                            Value::Integer(16),
+                        ]),
+                    )
+                    .with_domain(
+                        END_TIME,
+                        Domain::Range(Value::Integer(1), Value::Integer(24)),
+                    )
+            })
+            .collect()
+    } else {
+        templates
+    };
+
+    let mut dibs = Dibs::new(
+        &[None],
+        &templates,
+        OptimizationLevel::Prepared,
+        None,
+        None,
+        Duration::from_secs(10),
+    );
+
+    dibs.enable_interval_pruning();
+    dibs
+}
+
+fn integers(values: &[usize]) -> Vec<Value> {
+    values.iter().map(|&value| Value::Integer(value)).collect()
+}
+
+/// Acquires `held` and returns whether `other` would have to wait for it.
+fn blocks(dibs: &Dibs, held: (usize, &[usize]), other: (usize, &[usize])) -> bool {
+    let mut holder = Transaction::new(0, 0);
+    dibs.acquire(&mut holder, held.0, integers(held.1)).unwrap();
+
+    let mut transaction = Transaction::new(1, 1);
+    let result = dibs.try_acquire(&mut transaction, other.0, integers(other.1));
+
+    transaction.commit();
+    holder.commit();
+
+    match result {
+        Ok(()) => false,
+        Err(AcquireError::WouldBlock(_)) => true,
+        Err(error) => panic!("unexpected error: {:?}", error),
+    }
+}
+
+#[test]
+fn lookups_past_the_last_end_time_cover_nothing() {
+    let held = (GET_DESTINATION, &[1, 16, 24][..]);
+    let insert = (INSERT, &[1, 0][..]);
+
+    assert!(blocks(&dibs(false), held, insert));
+
+    let dibs = dibs(true);
+    assert!(!blocks(&dibs, held, insert));
+    assert!(dibs.conflict_stats().num_pruned > 0);
+
+    // Any earlier end time may still be covered.
+    assert!(blocks(&dibs, (GET_DESTINATION, &[1, 16, 23]), insert));
+}
+
+#[test]
+fn ranges_between_start_times_cover_nothing() {
+    let held = (GET_DESTINATION, &[1, 7, 0][..]);
+
+    assert!(blocks(&dibs(false), held, (DELETE_RANGE, &[1, 1, 7])));
+
+    let dibs = dibs(true);
+    assert!(!blocks(&dibs, held, (DELETE_RANGE, &[1, 1, 7])));
+    assert!(blocks(&dibs, held, (DELETE_RANGE, &[1, 0, 7])));
+}
@@ -0,0 +1,183 @@
+//! Drives the lock manager through its C interface the way a storage engine would.
+
+use dibs::ffi::*;
+use dibs::Transaction;
+use std::ptr;
+
+fn integer(value: u64) -> DibsValue {
+    DibsValue {
+        kind: DIBS_VALUE_INTEGER,
+        integer: value,
+        data: ptr::null(),
+        len: 0,
+    }
+}
+
+/// Returns a builder over one table with a point read and a point write of column 1, as
+/// templates 0 and 1.
+unsafe fn point_builder() -> *mut DibsBuilder {
+    let builder = dibs_builder_new(1);
+    let columns = [1];
+    let point = [DibsPredicateNode {
+        kind: DIBS_NODE_COMPARISON,
+        op: DIBS_EQ,
+        left: 0,
+        right: 0,
+        num_operands: 0,
+    }];
+
+    let read = dibs_register_template(
+        builder,
+        0,
+        columns.as_ptr(),
+        1,
+        ptr::null(),
+        0,
+        point.as_ptr(),
+        1,
+    );
+
+    let write = dibs_register_template(
+        builder,
+        0,
+        ptr::null(),
+        0,
+        columns.as_ptr(),
+        1,
+        point.as_ptr(),
+        1,
+    );
+
+    assert_eq!((read, write), (0, 1));
+    builder
+}
+
+#[test]
+fn conflicting_requests_wait_until_commit() {
+    unsafe {
+        let dibs = dibs_new(point_builder(), DIBS_PREPARED, 1000);
+        assert!(!dibs.is_null());
+
+        let arguments = [integer(7)];
+
+        let reader = dibs_transaction_new(0, 0);
+        assert_eq!(
+            dibs_acquire(dibs, reader, 0, arguments.as_ptr(), 1),
+            DIBS_OK
+        );
+
+        let writer = dibs_transaction_new(1, 1);
+        assert_eq!(
+            dibs_try_acquire(dibs, writer, 1, arguments.as_ptr(), 1),
+            DIBS_WOULD_BLOCK
+        );
+        assert_eq!(
+            dibs_acquire(dibs, writer, 1, arguments.as_ptr(), 1),
+            DIBS_TIMEOUT
+        );
+        dibs_commit(writer);
+
+        dibs_commit(reader);
+
+        let writer = dibs_transaction_new(1, 1);
+        assert_eq!(
+            dibs_acquire(dibs, writer, 1, arguments.as_ptr(), 1),
+            DIBS_OK
+        );
+        dibs_commit(writer);
+
+        dibs_free(dibs);
+    }
+}
+
+#[test]
+fn malformed_input_is_rejected() {
+    unsafe {
+        let builder = point_builder();
+        assert!(!dibs_builder_set_filter(builder, 1, 0));
+
+        // A conjunction with more operands than there are predicates before it.
+        let nodes = [DibsPredicateNode {
+            kind: DIBS_NODE_CONJUNCTION,
+            op: 0,
+            left: 0,
+            right: 0,
+            num_operands: 2,
+        }];
+
+        let template = dibs_register_template(
+            builder,
+            0,
+            ptr::null(),
+            0,
+            ptr::null(),
+            0,
+            nodes.as_ptr(),
+            1,
+        );
+        assert_eq!(template, usize::MAX);
+
+        let dibs = dibs_new(builder, DIBS_PREPARED, 1000);
+        let transaction = dibs_transaction_new(0, 0);
+
+        assert_eq!(
+            dibs_acquire(dibs, transaction, 2, ptr::null(), 0),
+            DIBS_INVALID_TEMPLATE
+        );
+
+        let unknown = DibsValue {
+            kind: 99,
+            ..integer(7)
+        };
+        assert_eq!(
+            dibs_acquire(dibs, transaction, 0, &unknown, 1),
+            DIBS_INVALID_ARGUMENTS
+        );
+
+        dibs_commit(transaction);
+        dibs_free(dibs);
+    }
+}
+
+#[test]
+fn unknown_optimization_levels_are_rejected() {
+    unsafe {
+        assert!(dibs_new(point_builder(), 4, 1000).is_null());
+    }
+}
+
+#[test]
+fn offset_template_ids_do_not_overflow() {
+    unsafe {
+        let dibs = dibs_new(point_builder(), DIBS_PREPARED, 1000);
+
+        let mut transaction = Transaction::new(0, 0);
+        transaction.set_template_offset(1);
+        let transaction = Box::into_raw(Box::new(transaction));
+
+        assert_eq!(
+            dibs_acquire(dibs, transaction, usize::MAX, ptr::null(), 0),
+            DIBS_INVALID_TEMPLATE
+        );
+
+        dibs_commit(transaction);
+        dibs_free(dibs);
+    }
+}
+
+#[test]
+fn panics_do_not_unwind_into_c() {
+    // Allocating the builder's tables overflows, which panics.
+    assert!(dibs_builder_new(usize::MAX).is_null());
+}
+
+#[test]
+fn the_header_is_up_to_date() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/dibs.h"));
+
+    assert!(
+        generated == include_str!("../include/dibs.h"),
+        "include/dibs.h differs from {}/dibs.h, which was generated from src/ffi.rs",
+        env!("OUT_DIR")
+    );
+}
@@ -0,0 +1,99 @@
+//! Checks the counts of the routes that each template's requests take among their table's
+//! buckets.
+
+mod common;
+
+use common::READ;
+use dibs::predicate::{ComparisonOperator, Predicate, Value};
+use dibs::{Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use std::time::Duration;
+
+const RESIDUAL_READ: usize = 2;
+
+fn dibs(filter: Option<usize>) -> Dibs {
+    let mut templates = common::point_templates();
+    templates.push(RequestTemplate::new(
+        0,
+        [1].iter().cloned().collect(),
+        Default::default(),
+        Predicate::comparison(ComparisonOperator::Eq, 1, 0),
+    ));
+
+    let mut dibs = common::single_table(
+        &templates,
+        filter,
+        OptimizationLevel::Filtered,
+        Duration::from_millis(1),
+    );
+
+    dibs.enable_filter_stats();
+    dibs
+}
+
+#[test]
+fn report_is_off_by_default() {
+    let dibs = Dibs::new(
+        &[None],
+        &[],
+        OptimizationLevel::Filtered,
+        None,
+        None,
+        Duration::from_millis(1),
+    );
+
+    assert_eq!(dibs.filter_report(), None);
+}
+
+#[test]
+fn counts_partitioned_and_residual_requests() {
+    let dibs = dibs(Some(0));
+
+    // The residual read stays in flight, so the partitioned reads after it check it.
+    let mut residual = Transaction::new(0, 0);
+    dibs.acquire(&mut residual, RESIDUAL_READ, vec![Value::Integer(1)])
+        .unwrap();
+
+    for s_id in 0..3 {
+        let mut transaction = Transaction::new(1, 1);
+        dibs.acquire(&mut transaction, READ, vec![Value::Integer(s_id)])
+            .unwrap();
+        transaction.commit();
+    }
+
+    residual.commit();
+
+    let report = dibs.filter_report().unwrap();
+    assert_eq!(report.templates.len(), 2);
+
+    let read = &report.templates[0];
+    assert_eq!(read.template_id, READ);
+    assert_eq!(read.filter, Some(0));
+    assert_eq!((read.num_partitioned, read.num_residual), (3, 0));
+    assert_eq!(read.num_residual_checked, 3);
+    assert_eq!(read.hit_rate(), Some(1.0));
+
+    let residual_read = &report.templates[1];
+    assert_eq!(residual_read.template_id, RESIDUAL_READ);
+    assert_eq!(residual_read.filter, None);
+    assert_eq!(
+        (residual_read.num_partitioned, residual_read.num_residual),
+        (0, 1)
+    );
+    assert_eq!(residual_read.hit_rate(), Some(0.0));
+}
+
+#[test]
+fn unpartitioned_tables_have_no_hit_rate() {
+    let dibs = dibs(None);
+
+    let mut transaction = Transaction::new(0, 0);
+    dibs.acquire(&mut transaction, READ, vec![Value::Integer(0)])
+        .unwrap();
+    transaction.commit();
+
+    let report = dibs.filter_report().unwrap();
+    assert_eq!(report.templates.len(), 1);
+    assert_eq!(report.templates[0].num_unpartitioned, 1);
+    assert_eq!(report.templates[0].hit_rate(), None);
+    assert!(report.to_string().contains("unpartitioned"));
+}
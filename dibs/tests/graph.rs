@@ -0,0 +1,95 @@
+//! Checks the static conflicts and observed waits of the conflict graph, and its exports.
+
+mod common;
+
+use common::{READ, WRITE};
+use dibs::graph::WaitEdge;
+use dibs::predicate::{ComparisonOperator, Predicate, Value};
+use dibs::{AcquireError, Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use std::time::Duration;
+
+const OTHER_WRITE: usize = 2;
+
+fn dibs() -> Dibs {
+    let mut templates = common::point_templates();
+    templates.push(RequestTemplate::new(
+        0,
+        Default::default(),
+        [2].iter().cloned().collect(),
+        Predicate::comparison(ComparisonOperator::Eq, 0, 0),
+    ));
+
+    let mut dibs = common::single_table(
+        &templates,
+        None,
+        OptimizationLevel::Prepared,
+        Duration::from_millis(1),
+    );
+
+    dibs.enable_wait_graph();
+    dibs
+}
+
+#[test]
+fn static_conflicts_pair_templates_once() {
+    let dibs = dibs();
+    let graph = dibs.conflict_graph();
+
+    // Templates 3 and 4 are the table's point read and write templates.
+    assert_eq!(graph.templates.len(), 5);
+    assert!(graph.templates[4].point && graph.templates[4].writes);
+
+    let conflicts = graph
+        .conflicts
+        .iter()
+        .filter(|&&(_, other_template_id)| other_template_id < dibs.num_templates())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        conflicts,
+        vec![(READ, WRITE), (WRITE, WRITE), (OTHER_WRITE, OTHER_WRITE)]
+    );
+    assert!(graph.waits.is_empty());
+}
+
+#[test]
+fn waits_are_counted_per_template_pair() {
+    let dibs = dibs();
+
+    let mut holder = Transaction::new(0, 0);
+    dibs.acquire(&mut holder, WRITE, vec![Value::Integer(3)])
+        .unwrap();
+
+    for transaction_id in 1..3 {
+        let mut transaction = Transaction::new(transaction_id, transaction_id);
+        let result = dibs.acquire(&mut transaction, READ, vec![Value::Integer(3)]);
+        assert!(matches!(result, Err(AcquireError::Timeout(0))));
+        transaction.commit();
+    }
+
+    holder.commit();
+
+    let graph = dibs.conflict_graph();
+    assert_eq!(graph.waits.len(), 1);
+
+    let WaitEdge {
+        template_id,
+        other_template_id,
+        num_waits,
+        num_timeouts,
+        ..
+    } = graph.waits[0];
+
+    assert_eq!(
+        (template_id, other_template_id, num_waits, num_timeouts),
+        (READ, WRITE, 2, 2)
+    );
+
+    assert!(graph
+        .to_dot()
+        .contains("t0 -> t1 [label=\"2 waits, 2 timeouts\""));
+    assert!(graph.to_json().contains(
+        "{\"template_id\": 0, \"other_template_id\": 1, \"num_waits\": 2, \"num_timeouts\": 2"
+    ));
+}
@@ -0,0 +1,106 @@
+//! Checks that a grouped table keeps templates that can never conflict in separate buckets, and
+//! that its point requests still conflict with every group.
+
+mod common;
+
+use common::conflicts;
+use dibs::predicate::{ComparisonOperator, Predicate, Value};
+use dibs::{AccessMode, AcquireError, Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use std::time::Duration;
+
+const BALANCE_WRITE: usize = 0;
+const NAME_READ: usize = 1;
+const NAME_WRITE: usize = 2;
+
+fn dibs() -> Dibs {
+    let point = Predicate::comparison(ComparisonOperator::Eq, 0, 0);
+
+    let templates = vec![
+        RequestTemplate::new(
+            0,
+            Default::default(),
+            [1].iter().cloned().collect(),
+            point.clone(),
+        ),
+        RequestTemplate::new(
+            0,
+            [2].iter().cloned().collect(),
+            Default::default(),
+            point.clone(),
+        ),
+        RequestTemplate::new(0, Default::default(), [2].iter().cloned().collect(), point),
+    ];
+
+    common::single_table(
+        &templates,
+        None,
+        OptimizationLevel::Grouped,
+        Duration::from_millis(1),
+    )
+}
+
+#[test]
+fn groups_take_separate_buckets() {
+    let dibs = dibs();
+
+    let mut transaction = Transaction::new(0, 0);
+    dibs.acquire(&mut transaction, BALANCE_WRITE, vec![Value::Integer(3)])
+        .unwrap();
+    dibs.acquire(&mut transaction, NAME_WRITE, vec![Value::Integer(3)])
+        .unwrap();
+
+    // Two groups, followed by the residual bucket of the point requests.
+    let summary = dibs.inflight_summary();
+    assert_eq!(summary[0].len(), 3);
+    assert_eq!(summary[0][0].num_requests, 1);
+    assert_eq!(summary[0][1].num_requests, 1);
+    assert_eq!(summary[0][2].num_requests, 0);
+
+    transaction.commit();
+}
+
+#[test]
+fn groups_bypass_each_other() {
+    let dibs = dibs();
+
+    let mut transaction = Transaction::new(0, 0);
+    dibs.acquire(&mut transaction, NAME_WRITE, vec![Value::Integer(3)])
+        .unwrap();
+
+    assert!(!conflicts(&dibs, BALANCE_WRITE, &[3]));
+    assert!(conflicts(&dibs, NAME_READ, &[3]));
+    assert!(!conflicts(&dibs, NAME_READ, &[4]));
+
+    transaction.commit();
+}
+
+#[test]
+fn point_requests_conflict_with_every_group() {
+    let dibs = dibs();
+
+    let mut transaction = Transaction::new(0, 0);
+    dibs.acquire_point(&mut transaction, 0, Value::Integer(3), AccessMode::Write)
+        .unwrap();
+
+    assert!(conflicts(&dibs, BALANCE_WRITE, &[3]));
+    assert!(conflicts(&dibs, NAME_READ, &[3]));
+    assert!(!conflicts(&dibs, NAME_WRITE, &[4]));
+
+    transaction.commit();
+
+    let mut transaction = Transaction::new(1, 1);
+    dibs.acquire(&mut transaction, BALANCE_WRITE, vec![Value::Integer(5)])
+        .unwrap();
+
+    let mut point_transaction = Transaction::new(2, 2);
+    let result = dibs.acquire_point(
+        &mut point_transaction,
+        0,
+        Value::Integer(5),
+        AccessMode::Read,
+    );
+    assert!(matches!(result, Err(AcquireError::Timeout(_))));
+
+    point_transaction.commit();
+    transaction.commit();
+}
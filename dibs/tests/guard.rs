@@ -0,0 +1,75 @@
+//! Checks that guarded requests are held until their guards are dropped. Run with:
+//!
+//! ```text
+//! cargo test -p dibs --features guard --test guard
+//! ```
+
+mod common;
+
+use common::{READ, WRITE};
+use dibs::predicate::Value;
+use dibs::{AcquireError, Dibs, OptimizationLevel, Transaction};
+use std::time::Duration;
+
+fn dibs() -> Dibs {
+    common::point_dibs(
+        Some(0),
+        OptimizationLevel::Filtered,
+        Duration::from_millis(1),
+    )
+}
+
+fn times_out(result: Result<(), AcquireError>) -> bool {
+    match result {
+        Ok(()) => false,
+        Err(AcquireError::Timeout(_)) => true,
+        Err(error) => panic!("unexpected error: {:?}", error),
+    }
+}
+
+#[test]
+fn guards_release_on_drop() {
+    let dibs = dibs();
+    let writer = dibs
+        .acquire_guarded(WRITE, vec![Value::Integer(7)])
+        .unwrap();
+
+    assert!(times_out(
+        dibs.acquire_guarded(READ, vec![Value::Integer(7)])
+            .map(drop)
+    ));
+
+    // Other keys and readers of them are unaffected.
+    let reader = dibs.acquire_guarded(READ, vec![Value::Integer(8)]).unwrap();
+    let other_reader = dibs.acquire_guarded(READ, vec![Value::Integer(8)]).unwrap();
+    assert_ne!(reader.transaction_id(), other_reader.transaction_id());
+
+    // Guards and transactions see each other's requests.
+    let mut transaction = Transaction::new(0, 0);
+    assert!(times_out(dibs.acquire(
+        &mut transaction,
+        WRITE,
+        vec![Value::Integer(7)]
+    )));
+    transaction.commit();
+
+    drop(writer);
+
+    let mut transaction = Transaction::new(0, 0);
+    assert!(!times_out(dibs.acquire(
+        &mut transaction,
+        WRITE,
+        vec![Value::Integer(7)]
+    )));
+
+    assert!(times_out(
+        dibs.acquire_guarded(READ, vec![Value::Integer(7)])
+            .map(drop)
+    ));
+
+    transaction.commit();
+
+    // A failed acquire released its request, so nothing is left on the key.
+    dibs.acquire_guarded(WRITE, vec![Value::Integer(7)])
+        .unwrap();
+}
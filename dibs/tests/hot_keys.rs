@@ -0,0 +1,78 @@
+//! Checks that requests on keys promoted to their own queues still wait on each other.
+
+mod common;
+
+use common::{conflicts, integers, READ, WRITE};
+use dibs::predicate::{ComparisonOperator, Predicate};
+use dibs::{Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use std::time::Duration;
+
+const RANGE: usize = 2;
+
+fn dibs() -> Dibs {
+    let mut templates = common::point_templates();
+    templates.push(RequestTemplate::new(
+        0,
+        [1].iter().cloned().collect(),
+        Default::default(),
+        Predicate::conjunction(vec![
+            Predicate::comparison(ComparisonOperator::Ge, 0, 0),
+            Predicate::comparison(ComparisonOperator::Le, 0, 1),
+        ]),
+    ));
+
+    let mut dibs = common::single_table(
+        &templates,
+        None,
+        OptimizationLevel::Prepared,
+        Duration::from_millis(1),
+    );
+
+    dibs.enable_hot_keys(2);
+    dibs
+}
+
+fn acquire(
+    dibs: &Dibs,
+    transaction_id: usize,
+    template_id: usize,
+    arguments: &[usize],
+) -> Transaction {
+    let mut transaction = Transaction::new(transaction_id, transaction_id);
+    dibs.acquire(&mut transaction, template_id, integers(arguments))
+        .unwrap();
+    transaction
+}
+
+#[test]
+fn hot_keys_find_conflicts() {
+    let dibs = dibs();
+
+    for round in 0..8 {
+        let holder = acquire(&dibs, 0, WRITE, &[7]);
+        let reader = acquire(&dibs, 1, READ, &[8]);
+
+        assert!(conflicts(&dibs, WRITE, &[7]), "round {}", round);
+        assert!(conflicts(&dibs, READ, &[7]), "round {}", round);
+        assert!(!conflicts(&dibs, WRITE, &[9]), "round {}", round);
+
+        // Readers on the same key don't conflict.
+        assert!(!conflicts(&dibs, READ, &[8]), "round {}", round);
+
+        holder.commit();
+        reader.commit();
+    }
+
+    let stats = dibs.conflict_stats();
+    assert!(stats.num_hot_key_promotions > 0);
+    assert!(stats.num_hot_key_solves > 0);
+
+    // A range request isn't keyed, so requests on hot keys must solve against the whole bucket
+    // while it is in flight.
+    let range = acquire(&dibs, 1, RANGE, &[5, 10]);
+
+    assert!(conflicts(&dibs, WRITE, &[7]));
+    assert!(!conflicts(&dibs, READ, &[7]));
+
+    range.commit();
+}
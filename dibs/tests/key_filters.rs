@@ -0,0 +1,79 @@
+//! Checks that a point request skips solving against a bucket whose key filter rules out its key,
+//! and falls back to solving when the filter can't.
+
+mod common;
+
+use common::{READ, WRITE};
+use dibs::predicate::Value;
+use dibs::{Dibs, OptimizationLevel, Transaction};
+use std::time::Duration;
+
+/// The number of readers holding keys, enough that the filter reports some absent keys as
+/// present.
+const NUM_READERS: usize = 400;
+
+fn dibs() -> Dibs {
+    let mut dibs = common::point_dibs(None, OptimizationLevel::Prepared, Duration::from_millis(1));
+    dibs.enable_key_filters(1);
+    dibs
+}
+
+fn readers(dibs: &Dibs) -> Vec<Transaction> {
+    (0..NUM_READERS)
+        .map(|key| {
+            let mut reader = Transaction::new(key, key);
+            dibs.acquire(&mut reader, READ, vec![Value::Integer(key)])
+                .unwrap();
+            reader
+        })
+        .collect()
+}
+
+/// Acquires a write on `key` and returns whether the key filter let it skip solving, and whether
+/// it conflicted.
+fn write(dibs: &Dibs, key: usize) -> (bool, bool) {
+    let skips = dibs.conflict_stats().num_key_filter_skips;
+    let conflicted = common::conflicts(dibs, WRITE, &[key]);
+    (
+        dibs.conflict_stats().num_key_filter_skips > skips,
+        conflicted,
+    )
+}
+
+#[test]
+fn absent_keys_skip_or_fall_back_to_solving() {
+    let dibs = dibs();
+    let readers = readers(&dibs);
+
+    let outcomes = (NUM_READERS..NUM_READERS + 1000)
+        .map(|key| write(&dibs, key))
+        .collect::<Vec<_>>();
+
+    // No absent key conflicts, whether the filter ruled it out or it was solved against every
+    // reader because of a false positive.
+    assert!(outcomes.iter().all(|&(_, conflicted)| !conflicted));
+    assert!(outcomes.iter().any(|&(skipped, _)| skipped));
+    assert!(outcomes.iter().any(|&(skipped, _)| !skipped));
+
+    for reader in readers {
+        reader.commit();
+    }
+}
+
+#[test]
+fn present_keys_are_solved() {
+    let dibs = dibs();
+    let readers = readers(&dibs);
+    let checks = dibs.conflict_stats().num_key_filter_checks;
+
+    assert_eq!(write(&dibs, 7), (false, true));
+    assert_eq!(dibs.conflict_stats().num_key_filter_checks, checks + 1);
+
+    for reader in readers {
+        reader.commit();
+    }
+
+    // With the bucket empty, the write is too small to filter and finds nothing to conflict with.
+    assert_eq!(write(&dibs, 7), (false, false));
+    assert_eq!(dibs.conflict_stats().num_key_filter_checks, checks + 1);
+}
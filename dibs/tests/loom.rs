@@ -0,0 +1,99 @@
+//! Model checks of acquire/commit interleavings. Run with:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test -p dibs --test loom --release
+//! ```
+
+#![cfg(loom)]
+
+mod common;
+
+use common::{READ, WRITE};
+use dibs::predicate::Value;
+use dibs::{Dibs, OptimizationLevel, Transaction};
+use loom::sync::Arc;
+use loom::thread;
+use std::time::Duration;
+
+fn dibs() -> Dibs {
+    common::point_dibs(None, OptimizationLevel::Prepared, Duration::from_secs(3600))
+}
+
+#[test]
+fn commit_wakes_waiter() {
+    loom::model(|| {
+        let dibs = Arc::new(dibs());
+
+        let mut writer = Transaction::new(0, 0);
+        dibs.acquire(&mut writer, WRITE, vec![Value::Integer(1)])
+            .unwrap();
+
+        let reader = {
+            let dibs = Arc::clone(&dibs);
+            thread::spawn(move || {
+                let mut reader = Transaction::new(1, 1);
+                dibs.acquire(&mut reader, READ, vec![Value::Integer(1)])
+                    .unwrap();
+                reader.commit();
+            })
+        };
+
+        writer.commit();
+        reader.join().unwrap();
+    });
+}
+
+#[test]
+fn concurrent_acquires_and_commits() {
+    loom::model(|| {
+        let dibs = Arc::new(dibs());
+
+        let threads = (0..2)
+            .map(|i| {
+                let dibs = Arc::clone(&dibs);
+                thread::spawn(move || {
+                    let mut transaction = Transaction::new(i, i);
+                    dibs.acquire(&mut transaction, WRITE, vec![Value::Integer(1)])
+                        .unwrap();
+                    transaction.commit();
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert!(dibs
+            .inflight_summary()
+            .iter()
+            .flatten()
+            .all(|bucket| bucket.num_requests == 0));
+    });
+}
+
+#[test]
+fn rollback_wakes_waiter() {
+    loom::model(|| {
+        let dibs = Arc::new(dibs());
+
+        let mut writer = Transaction::new(0, 0);
+        let savepoint = writer.savepoint();
+        dibs.acquire(&mut writer, WRITE, vec![Value::Integer(1)])
+            .unwrap();
+
+        let reader = {
+            let dibs = Arc::clone(&dibs);
+            thread::spawn(move || {
+                let mut reader = Transaction::new(1, 1);
+                dibs.acquire(&mut reader, READ, vec![Value::Integer(1)])
+                    .unwrap();
+                reader.commit();
+            })
+        };
+
+        writer.rollback_to(savepoint);
+        reader.join().unwrap();
+        writer.commit();
+    });
+}
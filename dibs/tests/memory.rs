@@ -0,0 +1,60 @@
+//! Checks that the memory held by in-flight requests is accounted for and capped.
+
+mod common;
+
+use common::READ;
+use dibs::predicate::Value;
+use dibs::{AcquireError, Dibs, OptimizationLevel, Transaction};
+use std::time::Duration;
+
+fn dibs(optimization: OptimizationLevel) -> Dibs {
+    common::point_dibs(Some(0), optimization, Duration::from_millis(1))
+}
+
+#[test]
+fn memory_is_released_on_commit() {
+    for &optimization in &[OptimizationLevel::Ungrouped, OptimizationLevel::Filtered] {
+        let mut dibs = dibs(optimization);
+        dibs.enable_memory_accounting(None);
+
+        let mut transaction = Transaction::new(0, 0);
+        dibs.acquire(&mut transaction, READ, vec![Value::Integer(1)])
+            .unwrap();
+
+        let point_bytes = dibs.inflight_bytes().unwrap();
+        assert!(point_bytes > 0);
+
+        let savepoint = transaction.savepoint();
+        dibs.acquire(&mut transaction, READ, vec![Value::Integer(2)])
+            .unwrap();
+        assert!(dibs.inflight_bytes().unwrap() > point_bytes);
+        transaction.rollback_to(savepoint);
+        assert_eq!(dibs.inflight_bytes(), Some(point_bytes));
+
+        transaction.commit();
+        assert_eq!(dibs.inflight_bytes(), Some(0));
+    }
+}
+
+#[test]
+fn requests_past_the_limit_fail() {
+    let mut dibs = dibs(OptimizationLevel::Prepared);
+    dibs.enable_memory_accounting(Some(4096));
+
+    let mut transaction = Transaction::new(0, 0);
+    dibs.acquire(&mut transaction, READ, vec![Value::Integer(1)])
+        .unwrap();
+
+    let held = dibs.inflight_bytes().unwrap();
+
+    match dibs.acquire(&mut transaction, READ, vec![Value::Integer(1); 1000]) {
+        Err(AcquireError::OverCapacity) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    // The refused request isn't charged.
+    assert_eq!(dibs.inflight_bytes(), Some(held));
+
+    transaction.commit();
+    assert_eq!(dibs.inflight_bytes(), Some(0));
+}
@@ -0,0 +1,160 @@
+//! Checks the callbacks an observer sees for a transaction that waits, and one that aborts.
+
+mod common;
+
+use common::{READ, WRITE};
+use dibs::observer::TransactionObserver;
+use dibs::predicate::Value;
+use dibs::{AcquireError, Dibs, OptimizationLevel, Transaction};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Default)]
+struct Log {
+    events: Mutex<Vec<String>>,
+}
+
+impl Log {
+    fn push(&self, event: String) {
+        self.events.lock().unwrap().push(event);
+    }
+
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut self.events.lock().unwrap())
+    }
+}
+
+impl TransactionObserver for Log {
+    fn on_begin(&self, transaction_id: usize) {
+        self.push(format!("begin {}", transaction_id));
+    }
+
+    fn on_acquire(
+        &self,
+        transaction_id: usize,
+        template_id: usize,
+        _duration: Duration,
+        error: Option<&AcquireError>,
+    ) {
+        self.push(format!(
+            "acquire {} {} {}",
+            transaction_id,
+            template_id,
+            if error.is_some() { "failed" } else { "ok" }
+        ));
+    }
+
+    fn on_block(&self, transaction_id: usize, _template_id: usize, other_transaction_id: usize) {
+        self.push(format!(
+            "block {} on {}",
+            transaction_id, other_transaction_id
+        ));
+    }
+
+    fn on_unblock(
+        &self,
+        transaction_id: usize,
+        _template_id: usize,
+        other_transaction_id: usize,
+        _waited: Duration,
+        timed_out: bool,
+    ) {
+        self.push(format!(
+            "unblock {} from {}{}",
+            transaction_id,
+            other_transaction_id,
+            if timed_out { " timed out" } else { "" }
+        ));
+    }
+
+    fn on_commit(&self, transaction_id: usize, _duration: Duration) {
+        self.push(format!("commit {}", transaction_id));
+    }
+
+    fn on_abort(&self, transaction_id: usize, _duration: Duration) {
+        self.push(format!("abort {}", transaction_id));
+    }
+}
+
+fn dibs(timeout: Duration, log: &Arc<Log>) -> Dibs {
+    let mut dibs = common::point_dibs(None, OptimizationLevel::Prepared, timeout);
+    dibs.set_observer(Arc::clone(log) as Arc<dyn TransactionObserver>);
+    dibs
+}
+
+#[test]
+fn observer_sees_waits_and_commits() {
+    let log = Arc::new(Log::default());
+    let dibs = Arc::new(dibs(Duration::from_secs(60), &log));
+
+    let mut writer = Transaction::new(0, 0);
+    dibs.acquire(&mut writer, WRITE, vec![Value::Integer(1)])
+        .unwrap();
+
+    let reader = {
+        let dibs = Arc::clone(&dibs);
+
+        std::thread::spawn(move || {
+            let mut reader = Transaction::new(1, 1);
+            dibs.acquire(&mut reader, READ, vec![Value::Integer(1)])
+                .unwrap();
+            reader
+        })
+    };
+
+    while !log
+        .events
+        .lock()
+        .unwrap()
+        .contains(&"block 1 on 0".to_string())
+    {
+        std::thread::yield_now();
+    }
+
+    writer.commit();
+    reader.join().unwrap().commit();
+
+    assert_eq!(
+        log.take(),
+        vec![
+            "begin 0",
+            "acquire 0 1 ok",
+            "begin 1",
+            "block 1 on 0",
+            "commit 0",
+            "unblock 1 from 0",
+            "acquire 1 0 ok",
+            "commit 1",
+        ]
+    );
+}
+
+#[test]
+fn observer_sees_aborts() {
+    let log = Arc::new(Log::default());
+    let dibs = dibs(Duration::from_millis(1), &log);
+
+    let mut writer = Transaction::new(0, 0);
+    dibs.acquire(&mut writer, WRITE, vec![Value::Integer(1)])
+        .unwrap();
+
+    let mut reader = Transaction::new(1, 1);
+    dibs.acquire(&mut reader, READ, vec![Value::Integer(1)])
+        .unwrap_err();
+    reader.commit();
+    writer.commit();
+
+    assert_eq!(
+        log.take(),
+        vec![
+            "begin 0",
+            "acquire 0 1 ok",
+            "begin 1",
+            "block 1 on 0",
+            "unblock 1 from 0 timed out",
+            "acquire 1 0 failed",
+            "abort 1",
+            "commit 0",
+        ]
+    );
+}
@@ -0,0 +1,81 @@
+//! Checks that optimistic acquires never wait, and that validation fails exactly when a conflicting
+//! request was acquired pessimistically or validated first.
+
+mod common;
+
+use common::{integers, READ, WRITE};
+use dibs::{AcquireError, Dibs, OptimizationLevel, Transaction};
+use std::time::Duration;
+
+fn dibs() -> Dibs {
+    common::point_dibs(None, OptimizationLevel::Prepared, Duration::from_secs(10))
+}
+
+fn optimistic(dibs: &Dibs, transaction_id: usize, template_id: usize, key: usize) -> Transaction {
+    let mut transaction = Transaction::new(transaction_id, transaction_id);
+    dibs.acquire_optimistic(&mut transaction, template_id, integers(&[key]))
+        .unwrap();
+    transaction
+}
+
+fn assert_validation_failed(dibs: &Dibs, transaction: &Transaction, other_transaction_id: usize) {
+    match transaction.validate(dibs) {
+        Err(AcquireError::ValidationFailed(id)) => assert_eq!(id, other_transaction_id),
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn the_first_of_two_conflicting_transactions_to_validate_wins() {
+    let dibs = dibs();
+
+    // Neither acquire waits on the other, despite the ten second timeout.
+    let writer = optimistic(&dibs, 0, WRITE, 1);
+    let reader = optimistic(&dibs, 1, READ, 1);
+
+    writer.validate(&dibs).unwrap();
+    assert_validation_failed(&dibs, &reader, 0);
+
+    reader.commit();
+    writer.commit();
+
+    // Once the winner commits, a retry validates.
+    let reader = optimistic(&dibs, 1, READ, 1);
+    reader.validate(&dibs).unwrap();
+    reader.commit();
+}
+
+#[test]
+fn unvalidated_requests_fail_no_one() {
+    let dibs = dibs();
+
+    let writer = optimistic(&dibs, 0, WRITE, 1);
+    let reader = optimistic(&dibs, 1, READ, 1);
+    let other_writer = optimistic(&dibs, 2, WRITE, 2);
+
+    other_writer.validate(&dibs).unwrap();
+    reader.validate(&dibs).unwrap();
+    assert_validation_failed(&dibs, &writer, 1);
+
+    writer.commit();
+    reader.commit();
+    other_writer.commit();
+}
+
+#[test]
+fn pessimistic_requests_count_as_validated() {
+    let dibs = dibs();
+
+    let mut holder = Transaction::new(0, 0);
+    dibs.acquire(&mut holder, WRITE, integers(&[1])).unwrap();
+
+    let reader = optimistic(&dibs, 1, READ, 1);
+    assert_validation_failed(&dibs, &reader, 0);
+    reader.commit();
+
+    let reader = optimistic(&dibs, 1, READ, 2);
+    reader.validate(&dibs).unwrap();
+    reader.commit();
+
+    holder.commit();
+}
@@ -0,0 +1,139 @@
+//! Checks that point requests conflict with each other by key, and with the table's predicate
+//! requests as a template on the key column would.
+
+use dibs::predicate::{ComparisonOperator, Predicate, Value};
+use dibs::{AccessMode, AcquireError, Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use std::time::Duration;
+
+const READ: usize = 0;
+const RANGE_WRITE: usize = 1;
+
+fn dibs(optimization: OptimizationLevel) -> Dibs {
+    let templates = vec![
+        RequestTemplate::new(
+            0,
+            [1].iter().cloned().collect(),
+            Default::default(),
+            Predicate::comparison(ComparisonOperator::Eq, 0, 0),
+        ),
+        RequestTemplate::new(
+            0,
+            Default::default(),
+            [1].iter().cloned().collect(),
+            Predicate::conjunction(vec![
+                Predicate::comparison(ComparisonOperator::Ge, 0, 0),
+                Predicate::comparison(ComparisonOperator::Le, 0, 1),
+            ]),
+        ),
+        RequestTemplate::new(
+            1,
+            [0].iter().cloned().collect(),
+            Default::default(),
+            Predicate::comparison(ComparisonOperator::Lt, 0, 0),
+        ),
+    ];
+
+    let filters = match optimization {
+        OptimizationLevel::Filtered => [Some(0), None],
+        _ => [None, None],
+    };
+
+    Dibs::new(
+        &filters,
+        &templates,
+        optimization,
+        None,
+        None,
+        Duration::from_millis(1),
+    )
+}
+
+fn point_conflicts(dibs: &Dibs, key: usize, mode: AccessMode) -> bool {
+    let mut transaction = Transaction::new(9, 9);
+    let result = dibs.acquire_point(&mut transaction, 0, Value::Integer(key), mode);
+    transaction.commit();
+
+    match result {
+        Ok(()) => false,
+        Err(AcquireError::Timeout(_)) => true,
+        Err(error) => panic!("unexpected error: {:?}", error),
+    }
+}
+
+#[test]
+fn point_requests_conflict_by_key() {
+    let optimizations = [
+        OptimizationLevel::Ungrouped,
+        OptimizationLevel::Prepared,
+        OptimizationLevel::Filtered,
+    ];
+
+    for &optimization in &optimizations {
+        let dibs = dibs(optimization);
+        assert_eq!(dibs.key_column(0), Some(0));
+        assert_eq!(dibs.key_column(1), None);
+
+        let mut writer = Transaction::new(0, 0);
+        dibs.acquire_point(&mut writer, 0, Value::Integer(7), AccessMode::Write)
+            .unwrap();
+
+        assert!(point_conflicts(&dibs, 7, AccessMode::Read));
+        assert!(!point_conflicts(&dibs, 8, AccessMode::Write));
+        writer.commit();
+
+        let mut reader = Transaction::new(1, 1);
+        dibs.acquire_point(&mut reader, 0, Value::Integer(7), AccessMode::Read)
+            .unwrap();
+
+        assert!(!point_conflicts(&dibs, 7, AccessMode::Read));
+        assert!(point_conflicts(&dibs, 7, AccessMode::Write));
+        reader.commit();
+    }
+}
+
+#[test]
+fn point_requests_conflict_with_predicate_requests() {
+    for &optimization in &[OptimizationLevel::Prepared, OptimizationLevel::Filtered] {
+        let dibs = dibs(optimization);
+
+        let mut writer = Transaction::new(0, 0);
+        dibs.acquire(
+            &mut writer,
+            RANGE_WRITE,
+            vec![Value::Integer(5), Value::Integer(9)],
+        )
+        .unwrap();
+
+        assert!(point_conflicts(&dibs, 7, AccessMode::Read));
+        assert!(!point_conflicts(&dibs, 10, AccessMode::Read));
+        writer.commit();
+
+        let mut writer = Transaction::new(1, 1);
+        dibs.acquire_point(&mut writer, 0, Value::Integer(7), AccessMode::Write)
+            .unwrap();
+
+        let mut reader = Transaction::new(2, 2);
+        match dibs.acquire(&mut reader, READ, vec![Value::Integer(7)]) {
+            Err(AcquireError::Timeout(1)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        reader.commit();
+
+        let mut reader = Transaction::new(3, 3);
+        dibs.acquire(&mut reader, READ, vec![Value::Integer(8)])
+            .unwrap();
+        reader.commit();
+        writer.commit();
+    }
+}
+
+#[test]
+fn tables_without_key_columns_reject_point_requests() {
+    let dibs = dibs(OptimizationLevel::Prepared);
+    let mut transaction = Transaction::new(0, 0);
+
+    match dibs.acquire_point(&mut transaction, 1, Value::Integer(7), AccessMode::Read) {
+        Err(AcquireError::InvalidArguments(_)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
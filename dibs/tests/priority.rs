@@ -0,0 +1,77 @@
+//! Checks that a higher-priority writer preempts the readers it conflicts with but still waits for
+//! them to commit.
+
+mod common;
+
+use common::{READ, WRITE};
+use dibs::predicate::Value;
+use dibs::{AcquireError, OptimizationLevel, Transaction};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn preempted_readers_keep_their_rows_until_they_commit() {
+    let dibs = Arc::new(common::point_dibs(
+        None,
+        OptimizationLevel::Prepared,
+        Duration::from_secs(10),
+    ));
+
+    let mut reader = Transaction::with_priority(0, 0, 0);
+    dibs.acquire(&mut reader, READ, vec![Value::Integer(7)])
+        .unwrap();
+
+    let granted = Arc::new(AtomicBool::new(false));
+
+    let writer = {
+        let dibs = Arc::clone(&dibs);
+        let granted = Arc::clone(&granted);
+
+        thread::spawn(move || {
+            let mut writer = Transaction::with_priority(1, 1, 1);
+            let result = dibs.acquire(&mut writer, WRITE, vec![Value::Integer(7)]);
+            granted.store(true, Ordering::SeqCst);
+            writer.commit();
+            result
+        })
+    };
+
+    while !reader.is_preempted() {
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    // The reader may still be reading the row, so the writer must not have been granted it.
+    thread::sleep(Duration::from_millis(20));
+    assert!(!granted.load(Ordering::SeqCst));
+
+    match dibs.acquire(&mut reader, READ, vec![Value::Integer(8)]) {
+        Err(AcquireError::Preempted) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    reader.commit();
+    writer.join().unwrap().unwrap();
+    assert_eq!(dibs.conflict_stats().num_preemptions, 1);
+}
+
+#[test]
+fn writers_of_lower_priority_are_not_preempted() {
+    let dibs = common::point_dibs(None, OptimizationLevel::Prepared, Duration::from_millis(1));
+
+    let mut writer = Transaction::with_priority(0, 0, 0);
+    dibs.acquire(&mut writer, WRITE, vec![Value::Integer(7)])
+        .unwrap();
+
+    let mut reader = Transaction::with_priority(1, 1, 1);
+
+    match dibs.acquire(&mut reader, READ, vec![Value::Integer(7)]) {
+        Err(AcquireError::Timeout(0)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    reader.commit();
+    assert!(!writer.is_preempted());
+    writer.commit();
+}
@@ -0,0 +1,117 @@
+//! Checks that requests on a filtered table whose templates don't fix the filter column are kept
+//! in the residual bucket, and still conflict with the partitioned requests.
+
+mod common;
+
+use common::{conflicts, READ, WRITE};
+use dibs::predicate::{ComparisonOperator, Predicate, Value};
+use dibs::{Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use std::time::Duration;
+
+const RESIDUAL_READ: usize = 2;
+const RESIDUAL_WRITE: usize = 3;
+
+fn dibs(timeout: Duration) -> Dibs {
+    let secondary = Predicate::comparison(ComparisonOperator::Eq, 1, 0);
+
+    let mut templates = common::point_templates();
+    templates.extend(vec![
+        RequestTemplate::new(
+            0,
+            [1].iter().cloned().collect(),
+            Default::default(),
+            secondary.clone(),
+        ),
+        RequestTemplate::new(
+            0,
+            Default::default(),
+            [1].iter().cloned().collect(),
+            secondary,
+        ),
+    ]);
+
+    common::single_table(&templates, Some(0), OptimizationLevel::Filtered, timeout)
+}
+
+#[test]
+fn residual_requests_take_one_bucket() {
+    let dibs = dibs(Duration::from_millis(1));
+
+    let mut transaction = Transaction::new(0, 0);
+    dibs.acquire(&mut transaction, RESIDUAL_WRITE, vec![Value::Integer(3)])
+        .unwrap();
+
+    let summary = dibs.inflight_summary();
+    assert_eq!(summary[0].len(), 1025);
+    assert_eq!(summary[0][1024].num_requests, 1);
+
+    assert_eq!(
+        summary[0]
+            .iter()
+            .map(|bucket| bucket.num_requests)
+            .sum::<usize>(),
+        1
+    );
+
+    transaction.commit();
+}
+
+#[test]
+fn residual_requests_conflict_with_partitioned_requests() {
+    let dibs = dibs(Duration::from_millis(1));
+
+    // A partitioned request waits on a residual one that arrived first.
+    let mut residual = Transaction::new(0, 0);
+    dibs.acquire(&mut residual, RESIDUAL_WRITE, vec![Value::Integer(3)])
+        .unwrap();
+    assert!(conflicts(&dibs, READ, &[7]));
+    assert!(!conflicts(&dibs, RESIDUAL_WRITE, &[4]));
+    residual.commit();
+    assert!(!conflicts(&dibs, READ, &[7]));
+
+    // A residual request waits on a partitioned one that arrived first.
+    let mut partitioned = Transaction::new(1, 1);
+    dibs.acquire(&mut partitioned, WRITE, vec![Value::Integer(7)])
+        .unwrap();
+    assert!(conflicts(&dibs, RESIDUAL_READ, &[3]));
+    assert!(!conflicts(&dibs, READ, &[8]));
+    partitioned.commit();
+    assert!(!conflicts(&dibs, RESIDUAL_READ, &[3]));
+}
+
+#[test]
+fn residual_and_partitioned_requests_do_not_wait_on_each_other() {
+    use std::sync::Arc;
+    use std::thread;
+
+    let dibs = Arc::new(dibs(Duration::from_secs(10)));
+
+    let threads = (0..4)
+        .map(|i| {
+            let dibs = Arc::clone(&dibs);
+
+            thread::spawn(move || {
+                for j in 0..500 {
+                    let transaction_id = i * 1000 + j;
+                    let mut transaction = Transaction::new(transaction_id, transaction_id);
+
+                    let template_id = if i % 2 == 0 { WRITE } else { RESIDUAL_WRITE };
+                    dibs.acquire(&mut transaction, template_id, vec![Value::Integer(7)])
+                        .unwrap();
+
+                    transaction.commit();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert!(dibs
+        .inflight_summary()
+        .iter()
+        .flatten()
+        .all(|bucket| bucket.num_requests == 0));
+}
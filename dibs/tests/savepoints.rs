@@ -0,0 +1,64 @@
+//! Checks that rolling back to a savepoint releases exactly the requests acquired since it.
+
+mod common;
+
+use common::{integers, WRITE};
+use dibs::{Dibs, OptimizationLevel, Transaction};
+use std::time::Duration;
+
+fn dibs() -> Dibs {
+    common::point_dibs(
+        Some(0),
+        OptimizationLevel::Filtered,
+        Duration::from_millis(1),
+    )
+}
+
+fn held(dibs: &Dibs, keys: &[usize]) -> Vec<bool> {
+    keys.iter()
+        .map(|&key| common::conflicts(dibs, WRITE, &[key]))
+        .collect()
+}
+
+#[test]
+fn rollbacks_release_requests_since_the_savepoint() {
+    let dibs = dibs();
+    let mut transaction = Transaction::new(0, 0);
+
+    dibs.acquire(&mut transaction, WRITE, integers(&[1]))
+        .unwrap();
+    let outer = transaction.savepoint();
+    dibs.acquire(&mut transaction, WRITE, integers(&[2]))
+        .unwrap();
+    let inner = transaction.savepoint();
+    dibs.acquire(&mut transaction, WRITE, integers(&[3]))
+        .unwrap();
+
+    assert_eq!(held(&dibs, &[1, 2, 3]), [true, true, true]);
+
+    transaction.rollback_to(inner);
+    assert_eq!(held(&dibs, &[1, 2, 3]), [true, true, false]);
+
+    // Rolling back to the outer savepoint also releases what was acquired after the inner one.
+    dibs.acquire(&mut transaction, WRITE, integers(&[3]))
+        .unwrap();
+    transaction.rollback_to(outer);
+    assert_eq!(held(&dibs, &[1, 2, 3]), [true, false, false]);
+
+    // The savepoint stays valid after a rollback to it.
+    dibs.acquire(&mut transaction, WRITE, integers(&[4]))
+        .unwrap();
+    transaction.rollback_to(outer);
+    assert_eq!(held(&dibs, &[1, 4]), [true, false]);
+
+    transaction.commit();
+    assert_eq!(held(&dibs, &[1]), [false]);
+}
+
+#[test]
+#[should_panic(expected = "unknown savepoint")]
+fn rollbacks_to_unknown_savepoints_panic() {
+    let mut transaction = Transaction::new(0, 0);
+    transaction.savepoint();
+    transaction.rollback_to(2);
+}
@@ -0,0 +1,66 @@
+//! Checks that a shared read entry is held until every transaction sharing it commits.
+
+mod common;
+
+use common::{conflicts, READ, WRITE};
+use dibs::predicate::Value;
+use dibs::{Dibs, OptimizationLevel, Transaction};
+use std::time::Duration;
+
+fn dibs() -> Dibs {
+    let mut dibs = common::point_dibs(
+        Some(0),
+        OptimizationLevel::Filtered,
+        Duration::from_millis(1),
+    );
+    dibs.enable_shared_reads();
+    dibs
+}
+
+fn read(dibs: &Dibs, transaction_id: usize, read_only: bool) -> Transaction {
+    let mut transaction = Transaction::new(transaction_id, transaction_id);
+
+    if read_only {
+        transaction.set_read_only();
+    }
+
+    dibs.acquire(&mut transaction, READ, vec![Value::Integer(7)])
+        .unwrap();
+    transaction
+}
+
+#[test]
+fn shared_reads_are_held_by_every_sharer() {
+    let dibs = dibs();
+
+    let first = read(&dibs, 0, true);
+    let second = read(&dibs, 1, true);
+    let third = read(&dibs, 2, true);
+    assert_eq!(dibs.conflict_stats().num_shared_reads, 2);
+    assert_eq!(dibs.inflight_summary()[0][7].num_requests, 1);
+
+    first.commit();
+    assert!(conflicts(&dibs, WRITE, &[7]));
+
+    third.commit();
+    assert!(conflicts(&dibs, WRITE, &[7]));
+
+    second.commit();
+    assert!(!conflicts(&dibs, WRITE, &[7]));
+    assert_eq!(dibs.inflight_summary()[0][7].num_requests, 0);
+}
+
+#[test]
+fn only_read_only_transactions_share() {
+    let dibs = dibs();
+
+    let first = read(&dibs, 0, false);
+    let second = read(&dibs, 1, true);
+    let third = read(&dibs, 2, false);
+    assert_eq!(dibs.conflict_stats().num_shared_reads, 0);
+    assert_eq!(dibs.inflight_summary()[0][7].num_requests, 3);
+
+    first.commit();
+    second.commit();
+    third.commit();
+}
@@ -0,0 +1,119 @@
+//! Checks that interleaving scripts and replaying the interleaving produce the same outcomes, and
+//! the same waits on the simulation's clock, every time.
+
+mod common;
+
+use common::{integers, WRITE};
+use dibs::clock::ManualClock;
+use dibs::simulation::{self, Action, Step};
+use dibs::{AcquireError, Dibs, OptimizationLevel};
+use std::sync::Arc;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+fn simulated_dibs(seed: u64) -> (Dibs, Arc<ManualClock>) {
+    let clock = Arc::new(ManualClock::new());
+    let mut dibs = common::point_dibs(None, OptimizationLevel::Prepared, TIMEOUT);
+    dibs.enable_simulation(seed, Arc::clone(&clock));
+    (dibs, clock)
+}
+
+fn write(key: usize) -> Action {
+    Action::Acquire {
+        template_id: WRITE,
+        arguments: integers(&[key]),
+    }
+}
+
+fn scripts() -> Vec<Vec<Action>> {
+    (0..4)
+        .map(|thread| vec![write(thread % 2), write(2), Action::Commit])
+        .collect()
+}
+
+/// Formats outcomes, since `AcquireError` can't be compared directly.
+fn describe(outcomes: &[Result<(), AcquireError>]) -> Vec<String> {
+    outcomes
+        .iter()
+        .map(|outcome| format!("{:?}", outcome))
+        .collect()
+}
+
+#[test]
+fn interleavings_keep_each_scripts_order() {
+    let steps = simulation::interleave(7, scripts());
+    assert_eq!(steps.len(), 12);
+
+    for thread in 0..4 {
+        let actions = steps
+            .iter()
+            .filter(|step| step.thread == thread)
+            .map(|step| format!("{:?}", step.action))
+            .collect::<Vec<_>>();
+
+        let script = scripts()[thread]
+            .iter()
+            .map(|action| format!("{:?}", action))
+            .collect::<Vec<_>>();
+
+        assert_eq!(actions, script);
+    }
+
+    let again = simulation::interleave(7, scripts());
+    assert_eq!(format!("{:?}", steps), format!("{:?}", again));
+}
+
+#[test]
+fn replays_are_deterministic() {
+    let steps = simulation::interleave(7, scripts());
+
+    let (dibs, clock) = simulated_dibs(3);
+    let outcomes = describe(&simulation::replay(&dibs, &steps));
+    let elapsed = clock.elapsed();
+
+    // The scripts conflict over key 2, so some of their acquires wait out a timeout.
+    assert!(elapsed > Duration::default());
+
+    // Replaying on a fresh `Dibs` and replaying again on a reseeded one agree with the first run.
+    let (fresh, fresh_clock) = simulated_dibs(3);
+    assert_eq!(describe(&simulation::replay(&fresh, &steps)), outcomes);
+    assert_eq!(fresh_clock.elapsed(), elapsed);
+
+    dibs.reseed(3);
+    assert_eq!(describe(&simulation::replay(&dibs, &steps)), outcomes);
+    assert_eq!(clock.elapsed(), elapsed * 2);
+}
+
+#[test]
+fn replays_time_out_on_requests_still_in_flight() {
+    let steps = vec![
+        Step {
+            thread: 0,
+            action: write(7),
+        },
+        Step {
+            thread: 1,
+            action: write(7),
+        },
+        Step {
+            thread: 0,
+            action: Action::Commit,
+        },
+        Step {
+            thread: 1,
+            action: write(7),
+        },
+    ];
+
+    let (dibs, clock) = simulated_dibs(0);
+    let outcomes = simulation::replay(&dibs, &steps);
+
+    assert!(matches!(outcomes[0], Ok(())));
+    assert!(matches!(outcomes[1], Err(AcquireError::Timeout(1))));
+    assert!(matches!(outcomes[2], Ok(())));
+    assert!(matches!(outcomes[3], Ok(())));
+
+    // The wait that timed out advanced the clock by the jittered timeout, and no other did.
+    assert!(clock.elapsed() >= TIMEOUT.mul_f32(0.8) && clock.elapsed() <= TIMEOUT.mul_f32(1.2));
+}
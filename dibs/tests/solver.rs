@@ -0,0 +1,418 @@
+//! Randomized checks that the solver never misses a conflict. Run with:
+//!
+//! ```text
+//! cargo test -p dibs --features internals --test solver
+//! ```
+//!
+//! Each case is generated from its own seed, which a failure reports along with the predicates
+//! and arguments so that it can be replayed.
+
+use dibs::internals::testing::{self, PredicateGenerator};
+use dibs::internals::{self, ColumnSet, IntervalTemplate, Key, KeyFilter, Program};
+use dibs::predicate::{ComparisonOperator, Domain, Predicate, Value, MAX_DEPTH};
+use fnv::FnvHashSet;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const NUM_CASES: u64 = 2000;
+const BLOWUP_LIMIT: usize = 64;
+
+struct Case {
+    p: Predicate,
+    p_args: Vec<Value>,
+    q: Predicate,
+    q_args: Vec<Value>,
+    shared_row: Option<Vec<Value>>,
+}
+
+fn cases(generator: &PredicateGenerator) -> impl Iterator<Item = (u64, Case)> + '_ {
+    (0..NUM_CASES).map(move |seed| {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let p = generator.predicate(&mut rng);
+        let p_args = generator.arguments(&mut rng);
+        let q = generator.predicate(&mut rng);
+        let q_args = generator.arguments(&mut rng);
+
+        let shared_row = testing::shared_row(
+            &p,
+            &p_args,
+            &q,
+            &q_args,
+            generator.num_columns(),
+            generator.domain(),
+        );
+
+        let case = Case {
+            p,
+            p_args,
+            q,
+            q_args,
+            shared_row,
+        };
+
+        (seed, case)
+    })
+}
+
+fn check<F>(generator: &PredicateGenerator, solve: F)
+where
+    F: Fn(&Case) -> Option<bool>,
+{
+    for (seed, case) in cases(generator) {
+        if let Some(row) = &case.shared_row {
+            if solve(&case) == Some(false) {
+                panic!(
+                    "missed a conflict (seed {}): {} with {:?} and {} with {:?} share {:?}",
+                    seed, case.p, case.p_args, case.q, case.q_args, row,
+                );
+            }
+        }
+    }
+}
+
+fn generators() -> Vec<PredicateGenerator> {
+    vec![
+        PredicateGenerator::new(1, 2, 4),
+        PredicateGenerator::new(2, 3, 4),
+        PredicateGenerator::new(3, 3, 3).with_max_depth(4),
+    ]
+}
+
+#[test]
+fn prepared_programs_find_conflicts() {
+    for generator in generators() {
+        check(&generator, |case| {
+            let program = Program::compile(&internals::prepare(&case.p, &case.q));
+            Some(program.evaluate(&case.p_args, &case.q_args))
+        });
+    }
+}
+
+#[test]
+fn solve_clustered_finds_conflicts() {
+    for generator in generators() {
+        check(&generator, |case| {
+            Some(internals::solve_clustered(
+                &case.p,
+                &case.p_args,
+                &case.q,
+                &case.q_args,
+            ))
+        });
+    }
+}
+
+#[test]
+fn prepared_clusters_solve_like_clustered() {
+    for generator in generators() {
+        for (seed, case) in cases(&generator) {
+            let clusters = internals::prepare_clusters(&case.p, &case.q);
+
+            assert_eq!(
+                internals::solve_clusters(&clusters, &case.p_args, &case.q_args),
+                internals::solve_clustered(&case.p, &case.p_args, &case.q, &case.q_args),
+                "{} with {:?} and {} with {:?} (seed {})",
+                case.p,
+                case.p_args,
+                case.q,
+                case.q_args,
+                seed,
+            );
+        }
+    }
+}
+
+#[test]
+fn solve_dnf_finds_conflicts() {
+    for generator in generators() {
+        check(&generator, |case| {
+            // The lock manager only solves predicates in DNF below the blowup limit.
+            if internals::dnf_blowup(&case.p) >= BLOWUP_LIMIT
+                || internals::dnf_blowup(&case.q) >= BLOWUP_LIMIT
+            {
+                return None;
+            }
+
+            let mut p = case.p.clone();
+            let mut q = case.q.clone();
+            p.normalize();
+            q.normalize();
+
+            Some(internals::solve_dnf(&p, &case.p_args, &q, &case.q_args))
+        });
+    }
+}
+
+#[test]
+fn interval_pruning_finds_conflicts() {
+    for generator in generators() {
+        check(&generator, |case| {
+            let p_intervals = IntervalTemplate::new(&case.p, &[])?.summarize(&case.p_args);
+            let q_intervals = IntervalTemplate::new(&case.q, &[])?.summarize(&case.q_args);
+
+            Some(!internals::disjoint(
+                &p_intervals,
+                &case.p_args,
+                &q_intervals,
+                &case.q_args,
+            ))
+        });
+    }
+}
+
+#[test]
+fn interval_pruning_covers_unions_of_ranges() {
+    // column 0 in [?0, ?1] or [?2, ?3]
+    let p = Predicate::conjunction(vec![Predicate::disjunction(vec![
+        Predicate::conjunction(vec![
+            Predicate::comparison(ComparisonOperator::Ge, 0, 0),
+            Predicate::comparison(ComparisonOperator::Le, 0, 1),
+        ]),
+        Predicate::conjunction(vec![
+            Predicate::comparison(ComparisonOperator::Ge, 0, 2),
+            Predicate::comparison(ComparisonOperator::Le, 0, 3),
+        ]),
+    ])]);
+
+    let template = IntervalTemplate::new(&p, &[]).unwrap();
+    let intervals = |args: &[Value]| template.summarize(args);
+
+    let p_args = [0, 2, 4, 6]
+        .iter()
+        .map(|&v| Value::Integer(v))
+        .collect::<Vec<_>>();
+    let apart = [7, 8, 9, 10]
+        .iter()
+        .map(|&v| Value::Integer(v))
+        .collect::<Vec<_>>();
+    let between = [3, 3, 3, 3]
+        .iter()
+        .map(|&v| Value::Integer(v))
+        .collect::<Vec<_>>();
+
+    assert!(internals::disjoint(
+        &intervals(&p_args),
+        &p_args,
+        &intervals(&apart),
+        &apart,
+    ));
+
+    // The hull covers the gap between the ranges, so this can't be pruned, although the
+    // predicates don't conflict.
+    assert!(!internals::disjoint(
+        &intervals(&p_args),
+        &p_args,
+        &intervals(&between),
+        &between,
+    ));
+}
+
+#[test]
+fn interval_pruning_with_domains_finds_conflicts() {
+    for generator in generators() {
+        // Even values on the first column and all but the lowest value on the rest, so that both
+        // kinds of domain leave gaps that the arguments can fall in.
+        let even: Vec<_> = (0..=generator.domain())
+            .step_by(2)
+            .map(Value::Integer)
+            .collect();
+
+        let domains = (0..generator.num_columns())
+            .map(|column| match column {
+                0 => (column, Domain::Values(even.clone())),
+                _ => (
+                    column,
+                    Domain::Range(Value::Integer(1), Value::Integer(generator.domain())),
+                ),
+            })
+            .collect::<Vec<_>>();
+
+        for (seed, case) in cases(&generator) {
+            // The oracle's rows are narrowed to the domains, since rows outside them are allowed to
+            // be missed.
+            let shared_row =
+                testing::rows(generator.num_columns(), generator.domain()).find(|row| {
+                    domains
+                        .iter()
+                        .all(|(column, domain)| domain_contains(domain, &row[*column]))
+                        && testing::satisfies(&case.p, row, &case.p_args)
+                        && testing::satisfies(&case.q, row, &case.q_args)
+                });
+
+            let row = match shared_row {
+                Some(row) => row,
+                None => continue,
+            };
+
+            let p_intervals = IntervalTemplate::new(&case.p, &domains).unwrap();
+            let q_intervals = IntervalTemplate::new(&case.q, &domains).unwrap();
+
+            if internals::disjoint(
+                &p_intervals.summarize(&case.p_args),
+                &case.p_args,
+                &q_intervals.summarize(&case.q_args),
+                &case.q_args,
+            ) {
+                panic!(
+                    "missed a conflict (seed {}): {} with {:?} and {} with {:?} share {:?}",
+                    seed, case.p, case.p_args, case.q, case.q_args, row,
+                );
+            }
+        }
+    }
+}
+
+fn domain_contains(domain: &Domain, value: &Value) -> bool {
+    match domain {
+        Domain::Range(min, max) => min <= value && value <= max,
+        Domain::Values(values) => values.contains(value),
+    }
+}
+
+#[test]
+fn interval_pruning_uses_domains() {
+    // column 0 <= ?0, and column 0 in [?0, ?1]
+    let p = Predicate::comparison(ComparisonOperator::Le, 0, 0);
+    let q = Predicate::conjunction(vec![
+        Predicate::comparison(ComparisonOperator::Ge, 0, 0),
+        Predicate::comparison(ComparisonOperator::Le, 0, 1),
+    ]);
+
+    let multiples_of_8 = [(
+        0,
+        Domain::Values(vec![
+            Value::Integer(0),
+            Value::Integer(8),
+            Value::Integer(16),
+        ]),
+    )];
+
+    let p_args = vec![Value::Integer(7)];
+    let apart = vec![Value::Integer(1), Value::Integer(7)];
+    let overlapping = vec![Value::Integer(0), Value::Integer(7)];
+
+    let disjoint = |domains: &[(usize, Domain)], q_args: &[Value]| {
+        internals::disjoint(
+            &IntervalTemplate::new(&p, domains)
+                .unwrap()
+                .summarize(&p_args),
+            &p_args,
+            &IntervalTemplate::new(&q, domains)
+                .unwrap()
+                .summarize(q_args),
+            q_args,
+        )
+    };
+
+    assert!(!disjoint(&[], &apart));
+    assert!(disjoint(&multiples_of_8, &apart));
+    assert!(!disjoint(&multiples_of_8, &overlapping));
+
+    // Integers have no value strictly between 7 and 8.
+    let range = [(0, Domain::Range(Value::Integer(0), Value::Integer(24)))];
+    let p = Predicate::comparison(ComparisonOperator::Gt, 0, 0);
+    let q = Predicate::comparison(ComparisonOperator::Lt, 0, 0);
+    let (p_args, q_args) = (vec![Value::Integer(7)], vec![Value::Integer(8)]);
+
+    assert!(internals::disjoint(
+        &IntervalTemplate::new(&p, &range)
+            .unwrap()
+            .summarize(&p_args),
+        &p_args,
+        &IntervalTemplate::new(&q, &range)
+            .unwrap()
+            .summarize(&q_args),
+        &q_args,
+    ));
+}
+
+#[test]
+fn key_filters_find_present_keys() {
+    for seed in 0..NUM_CASES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut filter = KeyFilter::default();
+        let mut present = vec![];
+
+        // Keys are drawn from a domain small enough to repeat, so some are removed while another
+        // request still holds them.
+        for _ in 0..rng.gen_range(0, 256) {
+            if !present.is_empty() && rng.gen_bool(0.3) {
+                let value = present.swap_remove(rng.gen_range(0, present.len()));
+                filter.remove(Some(Key::new(0, 0, &[Value::Integer(value)])));
+            } else {
+                let value = rng.gen_range(0, 512);
+                filter.insert(Some(Key::new(0, 0, &[Value::Integer(value)])));
+                present.push(value);
+            }
+        }
+
+        for &value in &present {
+            assert!(
+                filter.may_conflict(Key::new(0, 0, &[Value::Integer(value)])),
+                "ruled out present key {} (seed {})",
+                value,
+                seed,
+            );
+        }
+
+        // A request keyed on another column may share a row with any of them.
+        if !present.is_empty() {
+            assert!(filter.may_conflict(Key::new(1, 0, &[Value::Integer(present[0] + 512)])));
+        }
+    }
+}
+
+#[test]
+fn column_sets_intersect_like_hash_sets() {
+    for seed in 0..NUM_CASES {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        // Columns range past the first word so that the spilled words are covered too.
+        let mut columns = || {
+            (0..rng.gen_range(0, 8))
+                .map(|_| rng.gen_range(0, 320))
+                .collect::<FnvHashSet<usize>>()
+        };
+
+        let p = columns();
+        let q = columns();
+
+        assert_eq!(
+            ColumnSet::new(&p).intersects(&ColumnSet::new(&q)),
+            !p.is_disjoint(&q),
+            "{:?} and {:?} (seed {})",
+            p,
+            q,
+            seed,
+        );
+        assert_eq!(ColumnSet::new(&p).is_empty(), p.is_empty());
+
+        let mut sorted = p.iter().cloned().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        assert_eq!(ColumnSet::new(&p).iter().collect::<Vec<_>>(), sorted);
+    }
+}
+
+#[test]
+fn deep_predicates_are_solved() {
+    // Alternates connectives so that condensing can't flatten the nesting away.
+    let mut p = Predicate::comparison(ComparisonOperator::Eq, 0, 0);
+
+    for depth in 1..MAX_DEPTH {
+        let operands = vec![p, Predicate::comparison(ComparisonOperator::Eq, 0, depth)];
+
+        p = if depth % 2 == 0 {
+            Predicate::conjunction(operands)
+        } else {
+            Predicate::disjunction(operands)
+        };
+    }
+
+    assert_eq!(p.depth(), MAX_DEPTH);
+
+    let p_args = (0..MAX_DEPTH).map(Value::Integer).collect::<Vec<_>>();
+    let q_args = p_args.clone();
+
+    assert!(internals::dnf_blowup(&p) > 1);
+    assert!(internals::solve_clustered(&p, &p_args, &p, &q_args));
+}
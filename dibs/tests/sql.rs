@@ -0,0 +1,102 @@
+//! Checks the SQL that predicates and templates are rendered as, and that conditions parse back.
+
+use dibs::predicate::{ComparisonOperator, Expression, Predicate};
+use dibs::sql::TableSchema;
+use dibs::RequestTemplate;
+
+fn schema() -> TableSchema {
+    TableSchema::new("subscriber", &["s_id", "sub_nbr", "bit_1", "vlr_location"])
+}
+
+fn predicate() -> Predicate {
+    Predicate::conjunction(vec![
+        Predicate::comparison(ComparisonOperator::Ge, 0, 0),
+        Predicate::comparison(ComparisonOperator::Le, 0, 1),
+        Predicate::disjunction(vec![
+            Predicate::comparison(ComparisonOperator::Eq, 2, 2),
+            Predicate::comparison(ComparisonOperator::Ne, 1, 3),
+        ]),
+    ])
+}
+
+#[test]
+fn predicates_are_rendered_as_conditions() {
+    let schema = schema();
+
+    assert_eq!(
+        predicate().sql(Some(&schema)).to_string(),
+        "s_id >= ?1 AND s_id <= ?2 AND (bit_1 = ?3 OR sub_nbr <> ?4)"
+    );
+
+    assert_eq!(
+        predicate().sql(None).to_string(),
+        "column_0 >= ?1 AND column_0 <= ?2 AND (column_2 = ?3 OR column_1 <> ?4)"
+    );
+
+    assert_eq!(Predicate::conjunction(vec![]).sql(None).to_string(), "TRUE");
+    assert_eq!(
+        Predicate::disjunction(vec![]).sql(None).to_string(),
+        "FALSE"
+    );
+}
+
+#[test]
+fn templates_are_rendered_as_statements() {
+    let schema = schema();
+
+    let read = RequestTemplate::new(
+        0,
+        [1, 2].iter().cloned().collect(),
+        Default::default(),
+        predicate(),
+    );
+
+    assert_eq!(
+        read.to_sql(&schema),
+        "SELECT sub_nbr, bit_1 FROM subscriber \
+         WHERE s_id >= ?1 AND s_id <= ?2 AND (bit_1 = ?3 OR sub_nbr <> ?4);"
+    );
+
+    let write = RequestTemplate::new(
+        0,
+        Default::default(),
+        [3].iter().cloned().collect(),
+        Predicate::comparison(ComparisonOperator::Eq, 0, 2),
+    )
+    .with_derived(vec![Expression::sum(
+        Expression::Parameter(0),
+        Expression::Parameter(1),
+    )]);
+
+    assert_eq!(
+        write.to_sql(&schema),
+        "-- derived parameters, in order after the arguments: (?1 + ?2)\n\
+         UPDATE subscriber SET vlr_location = :vlr_location WHERE s_id = ?3;"
+    );
+}
+
+#[test]
+fn conditions_are_parsed_back() {
+    let schema = schema();
+    let text = predicate().sql(Some(&schema)).to_string();
+
+    assert_eq!(Predicate::parse_sql(&text, &schema), Ok(predicate()));
+
+    assert_eq!(
+        Predicate::parse_sql(
+            "s_id between ?1 and ?2 and (bit_1 = ?3 or sub_nbr != ?4)",
+            &schema
+        ),
+        Ok(predicate())
+    );
+
+    assert_eq!(
+        Predicate::parse_sql("TRUE", &schema),
+        Ok(Predicate::conjunction(vec![]))
+    );
+
+    assert!(Predicate::parse_sql("s_id = ?0", &schema).is_err());
+    assert!(Predicate::parse_sql("msc_location = ?1", &schema).is_err());
+    assert!(Predicate::parse_sql("s_id = ?1 AND", &schema).is_err());
+    assert!(Predicate::parse_sql("(s_id = ?1", &schema).is_err());
+}
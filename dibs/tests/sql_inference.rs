@@ -0,0 +1,219 @@
+//! Checks that templates inferred from the TATP statements match the hand-written ones.
+
+use dibs::predicate::{ComparisonOperator, Predicate};
+use dibs::sql::{InferenceError, TableSchema};
+use dibs::RequestTemplate;
+
+fn schemas() -> Vec<TableSchema> {
+    let mut subscriber = vec!["s_id".to_string()];
+
+    for prefix in &["bit", "hex", "byte2"] {
+        subscriber.extend((1..=10).map(|i| format!("{}_{}", prefix, i)));
+    }
+
+    subscriber.push("msc_location".to_string());
+    subscriber.push("vlr_location".to_string());
+
+    vec![
+        TableSchema {
+            name: "subscriber".to_string(),
+            columns: subscriber,
+        },
+        TableSchema::new(
+            "access_info",
+            &["s_id", "ai_type", "data1", "data2", "data3", "data4"],
+        ),
+        TableSchema::new(
+            "special_facility",
+            &[
+                "s_id",
+                "sf_type",
+                "is_active",
+                "error_cntrl",
+                "data_a",
+                "data_b",
+            ],
+        ),
+        TableSchema::new(
+            "call_forwarding",
+            &["s_id", "sf_type", "start_time", "end_time", "numberx"],
+        ),
+    ]
+}
+
+fn eq(column: usize, parameter: usize) -> Predicate {
+    Predicate::comparison(ComparisonOperator::Eq, column, parameter)
+}
+
+fn assert_inferred(statement: &str, expected: RequestTemplate) {
+    let schemas = schemas();
+    let schema = &schemas[expected.table()];
+    let inferred = RequestTemplate::from_sql(statement, &schemas).unwrap();
+
+    assert_eq!(inferred.table(), expected.table());
+    assert_eq!(inferred.to_sql(schema), expected.to_sql(schema));
+}
+
+#[test]
+fn tatp_templates_are_inferred() {
+    assert_inferred(
+        "SELECT * FROM subscriber WHERE s_id = ?",
+        RequestTemplate::new(0, (0..33).collect(), Default::default(), eq(0, 0)),
+    );
+
+    // The join of get new destination is split into a statement per table.
+    assert_inferred(
+        "SELECT s_id FROM special_facility AS sf \
+         WHERE sf.s_id = ? AND sf.sf_type = ? AND sf.is_active = 1",
+        RequestTemplate::new(
+            2,
+            (0..3).collect(),
+            Default::default(),
+            Predicate::conjunction(vec![eq(0, 0), eq(1, 1)]),
+        ),
+    );
+
+    assert_inferred(
+        "SELECT numberx FROM call_forwarding \
+         WHERE s_id = ?1 AND sf_type = ?2 AND start_time <= ?3 AND ?4 < end_time",
+        RequestTemplate::new(
+            3,
+            (0..5).collect(),
+            Default::default(),
+            Predicate::conjunction(vec![
+                eq(0, 0),
+                eq(1, 1),
+                Predicate::comparison(ComparisonOperator::Le, 2, 2),
+                Predicate::comparison(ComparisonOperator::Gt, 3, 3),
+            ]),
+        ),
+    );
+
+    assert_inferred(
+        "SELECT data1, data2, data3, data4 FROM access_info WHERE s_id = ? AND ai_type = ?",
+        RequestTemplate::new(
+            1,
+            (0..6).collect(),
+            Default::default(),
+            Predicate::conjunction(vec![eq(0, 0), eq(1, 1)]),
+        ),
+    );
+
+    assert_inferred(
+        "UPDATE subscriber SET bit_1 = ? WHERE s_id = ?",
+        RequestTemplate::new(
+            0,
+            [0].iter().cloned().collect(),
+            [1].iter().cloned().collect(),
+            eq(0, 0),
+        ),
+    );
+
+    assert_inferred(
+        "UPDATE special_facility SET data_a = ? WHERE s_id = ? AND sf_type = ?",
+        RequestTemplate::new(
+            2,
+            (0..2).collect(),
+            [4].iter().cloned().collect(),
+            Predicate::conjunction(vec![eq(0, 0), eq(1, 1)]),
+        ),
+    );
+
+    assert_inferred(
+        "UPDATE subscriber SET vlr_location = $1 WHERE s_id = $2",
+        RequestTemplate::new(
+            0,
+            [0].iter().cloned().collect(),
+            [32].iter().cloned().collect(),
+            eq(0, 1),
+        ),
+    );
+
+    assert_inferred(
+        "SELECT sf_type FROM special_facility WHERE s_id = ?",
+        RequestTemplate::new(2, (0..2).collect(), Default::default(), eq(0, 0)),
+    );
+
+    assert_inferred(
+        "DELETE FROM call_forwarding WHERE s_id = ? AND sf_type = ? AND start_time = ?",
+        RequestTemplate::new(
+            3,
+            Default::default(),
+            (0..5).collect(),
+            Predicate::conjunction(vec![eq(0, 0), eq(1, 1), eq(2, 2)]),
+        ),
+    );
+
+    assert_inferred(
+        "INSERT INTO call_forwarding VALUES (?, ?, ?, ?, ?)",
+        RequestTemplate::new(
+            3,
+            Default::default(),
+            (0..5).collect(),
+            Predicate::conjunction((0..5).map(|i| eq(i, i)).collect()),
+        ),
+    );
+}
+
+#[test]
+fn conditions_are_widened_to_what_templates_can_describe() {
+    // A comparison to a constant can't be described, so it matches every row.
+    assert_inferred(
+        "SELECT s_id FROM special_facility WHERE is_active = 1 OR s_id = ?",
+        RequestTemplate::new(
+            2,
+            [0, 2].iter().cloned().collect(),
+            Default::default(),
+            Predicate::boolean(true),
+        ),
+    );
+
+    assert_inferred(
+        "SELECT s_id FROM call_forwarding WHERE s_id = ? AND start_time BETWEEN ? AND ?",
+        RequestTemplate::new(
+            3,
+            [0, 2].iter().cloned().collect(),
+            Default::default(),
+            Predicate::conjunction(vec![
+                eq(0, 0),
+                Predicate::comparison(ComparisonOperator::Ge, 2, 1),
+                Predicate::comparison(ComparisonOperator::Le, 2, 2),
+            ]),
+        ),
+    );
+}
+
+#[test]
+fn statements_that_templates_cannot_describe_are_rejected() {
+    let schemas = schemas();
+
+    let error = |statement| RequestTemplate::from_sql(statement, &schemas).err();
+
+    assert!(matches!(
+        error("SELECT * FROM subscriber WHERE"),
+        Some(InferenceError::Syntax(_))
+    ));
+
+    assert!(matches!(
+        error(
+            "SELECT numberx FROM special_facility AS sf, call_forwarding AS cf \
+             WHERE sf.s_id = ? AND cf.s_id = sf.s_id"
+        ),
+        Some(InferenceError::Unsupported(_))
+    ));
+
+    assert!(matches!(
+        error("SELECT * FROM subscriber WHERE NOT s_id = ?"),
+        Some(InferenceError::Unsupported(_))
+    ));
+
+    assert_eq!(
+        error("SELECT * FROM caller WHERE s_id = ?"),
+        Some(InferenceError::UnknownTable("caller".to_string()))
+    );
+
+    assert_eq!(
+        error("UPDATE subscriber SET bit_11 = ? WHERE s_id = ?"),
+        Some(InferenceError::UnknownColumn("bit_11".to_string()))
+    );
+}
@@ -0,0 +1,48 @@
+//! Checks that `try_acquire` reports the transactions it would wait on and withdraws its request.
+
+mod common;
+
+use common::{READ, WRITE};
+use dibs::predicate::Value;
+use dibs::{AcquireError, Dibs, OptimizationLevel, Transaction};
+use std::time::Duration;
+
+fn dibs() -> Dibs {
+    common::point_dibs(None, OptimizationLevel::Prepared, Duration::from_secs(10))
+}
+
+#[test]
+fn try_acquire_reports_blocking_transactions() {
+    let dibs = dibs();
+
+    let mut first = Transaction::new(0, 0);
+    dibs.acquire(&mut first, READ, vec![Value::Integer(7)])
+        .unwrap();
+
+    let mut second = Transaction::new(1, 1);
+    dibs.acquire(&mut second, READ, vec![Value::Integer(7)])
+        .unwrap();
+
+    let mut writer = Transaction::new(2, 2);
+    dibs.try_acquire(&mut writer, WRITE, vec![Value::Integer(8)])
+        .unwrap();
+
+    match dibs.try_acquire(&mut writer, WRITE, vec![Value::Integer(7)]) {
+        Err(AcquireError::WouldBlock(transaction_ids)) => assert_eq!(transaction_ids, vec![0, 1]),
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    // The withdrawn request doesn't hold up readers that arrive after it.
+    let mut third = Transaction::new(3, 3);
+    dibs.try_acquire(&mut third, READ, vec![Value::Integer(7)])
+        .unwrap();
+    assert_eq!(dibs.inflight_summary()[0][0].num_requests, 4);
+
+    first.commit();
+    second.commit();
+    third.commit();
+
+    dibs.try_acquire(&mut writer, WRITE, vec![Value::Integer(7)])
+        .unwrap();
+    writer.commit();
+}
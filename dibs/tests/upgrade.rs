@@ -0,0 +1,67 @@
+//! Checks that reads acquired for upgrade exclude each other but not plain reads.
+
+mod common;
+
+use common::{integers, READ, WRITE};
+use dibs::{AcquireError, Dibs, OptimizationLevel, Transaction};
+use std::time::Duration;
+
+const OPTIMIZATIONS: [OptimizationLevel; 4] = [
+    OptimizationLevel::Ungrouped,
+    OptimizationLevel::Grouped,
+    OptimizationLevel::Prepared,
+    OptimizationLevel::Filtered,
+];
+
+fn dibs(optimization: OptimizationLevel) -> Dibs {
+    common::point_dibs(Some(0), optimization, Duration::from_millis(1))
+}
+
+/// Like `common::conflicts`, but acquires the read for upgrade.
+fn upgrade_conflicts(dibs: &Dibs, key: usize) -> bool {
+    let mut transaction = Transaction::new(9, 9);
+    let result = dibs.acquire_for_upgrade(&mut transaction, READ, integers(&[key]));
+    transaction.commit();
+
+    match result {
+        Ok(()) => false,
+        Err(AcquireError::Timeout(_)) => true,
+        Err(error) => panic!("unexpected error: {:?}", error),
+    }
+}
+
+#[test]
+fn reads_for_upgrade_exclude_each_other() {
+    for &optimization in &OPTIMIZATIONS {
+        let dibs = dibs(optimization);
+
+        let mut reader = Transaction::new(0, 0);
+        dibs.acquire_for_upgrade(&mut reader, READ, integers(&[7]))
+            .unwrap();
+
+        assert!(upgrade_conflicts(&dibs, 7));
+        assert!(!upgrade_conflicts(&dibs, 8));
+        assert!(!common::conflicts(&dibs, READ, &[7]));
+        assert!(common::conflicts(&dibs, WRITE, &[7]));
+
+        // The upgrade itself waits on nothing but its own read.
+        dibs.acquire(&mut reader, WRITE, integers(&[7])).unwrap();
+        reader.commit();
+
+        assert!(!upgrade_conflicts(&dibs, 7));
+    }
+}
+
+#[test]
+fn plain_reads_admit_reads_for_upgrade() {
+    for &optimization in &OPTIMIZATIONS {
+        let dibs = dibs(optimization);
+
+        let mut reader = Transaction::new(0, 0);
+        dibs.acquire(&mut reader, READ, integers(&[7])).unwrap();
+
+        assert!(!upgrade_conflicts(&dibs, 7));
+
+        reader.commit();
+    }
+}
@@ -0,0 +1,103 @@
+//! Checks that acquires reject arguments that don't fit their template or the types compared to
+//! a column before, without adding a request.
+
+mod common;
+
+use common::{integers, READ, WRITE};
+use dibs::predicate::{ComparisonOperator, Predicate, Value};
+use dibs::{AcquireError, Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use std::time::Duration;
+
+/// Reads the rows whose column 0 lies between `?0` and `?1`.
+const RANGE: usize = 2;
+
+fn dibs(filter: Option<usize>, optimization: OptimizationLevel) -> Dibs {
+    let range = RequestTemplate::new(
+        0,
+        [1].iter().cloned().collect(),
+        Default::default(),
+        Predicate::conjunction(vec![
+            Predicate::comparison(ComparisonOperator::Ge, 0, 0),
+            Predicate::comparison(ComparisonOperator::Le, 0, 1),
+        ]),
+    );
+
+    let mut templates = common::point_templates();
+    templates.push(range);
+
+    common::single_table(&templates, filter, optimization, Duration::from_millis(1))
+}
+
+fn assert_invalid(dibs: &Dibs, template_id: usize, arguments: Vec<Value>) {
+    let mut transaction = Transaction::new(0, 0);
+
+    match dibs.acquire(&mut transaction, template_id, arguments) {
+        Err(AcquireError::InvalidArguments(_)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    transaction.commit();
+}
+
+#[test]
+fn missing_arguments_are_rejected() {
+    let dibs = dibs(None, OptimizationLevel::Prepared);
+
+    assert_invalid(&dibs, READ, vec![]);
+    assert_invalid(&dibs, RANGE, integers(&[1]));
+
+    // Neither rejected request was added, so nothing conflicts with a write.
+    assert!(!common::conflicts(&dibs, WRITE, &[1]));
+}
+
+#[test]
+fn arguments_compared_to_one_column_share_a_type() {
+    let dibs = dibs(None, OptimizationLevel::Prepared);
+
+    assert_invalid(&dibs, RANGE, vec![Value::Integer(1), Value::Timestamp(5)]);
+
+    // The rejected request fixed no type, so timestamps can still be compared to the column.
+    let mut reader = Transaction::new(0, 0);
+    dibs.acquire(
+        &mut reader,
+        RANGE,
+        vec![Value::Timestamp(1), Value::Timestamp(5)],
+    )
+    .unwrap();
+    reader.commit();
+}
+
+#[test]
+fn requests_compare_one_type_to_each_column() {
+    for &optimization in &[OptimizationLevel::Ungrouped, OptimizationLevel::Prepared] {
+        let dibs = dibs(None, optimization);
+
+        let mut reader = Transaction::new(0, 0);
+        dibs.acquire(&mut reader, RANGE, integers(&[1, 5])).unwrap();
+
+        // Solving these against the reader would compare timestamps to integers.
+        assert_invalid(&dibs, WRITE, vec![Value::Timestamp(3)]);
+        assert_invalid(&dibs, RANGE, vec![Value::Timestamp(1), Value::Timestamp(5)]);
+
+        let mut writer = Transaction::new(1, 1);
+        match dibs.acquire_optimistic(&mut writer, WRITE, vec![Value::Timestamp(3)]) {
+            Err(AcquireError::InvalidArguments(_)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+        writer.commit();
+
+        // Requests of the column's type are still solved against the reader.
+        assert!(common::conflicts(&dibs, WRITE, &[3]));
+        assert!(!common::conflicts(&dibs, WRITE, &[6]));
+
+        reader.commit();
+    }
+}
+
+#[test]
+fn filter_arguments_are_integers() {
+    let dibs = dibs(Some(0), OptimizationLevel::Filtered);
+
+    assert_invalid(&dibs, WRITE, vec![Value::String("7".to_string())]);
+    assert!(!common::conflicts(&dibs, READ, &[7]));
+}
@@ -0,0 +1,62 @@
+//! Checks that a transaction's wait budget bounds the time it blocks across all of its acquires.
+
+mod common;
+
+use common::{integers, READ, WRITE};
+use dibs::{AcquireError, Dibs, OptimizationLevel, Transaction};
+use std::time::Duration;
+
+const BUDGET: Duration = Duration::from_millis(20);
+
+fn dibs() -> Dibs {
+    common::point_dibs(None, OptimizationLevel::Prepared, Duration::from_secs(10))
+}
+
+fn assert_exhausted(dibs: &Dibs, transaction: &mut Transaction, key: usize) {
+    match dibs.acquire(transaction, READ, integers(&[key])) {
+        Err(AcquireError::WaitBudgetExhausted) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn acquires_without_conflicts_leave_the_budget_untouched() {
+    let dibs = dibs();
+
+    let mut transaction = Transaction::new(0, 0);
+    assert_eq!(transaction.remaining_wait_budget(), None);
+
+    transaction.set_wait_budget(BUDGET);
+    dibs.acquire(&mut transaction, READ, integers(&[1]))
+        .unwrap();
+    dibs.acquire(&mut transaction, WRITE, integers(&[2]))
+        .unwrap();
+
+    assert_eq!(transaction.remaining_wait_budget(), Some(BUDGET));
+    transaction.commit();
+}
+
+#[test]
+fn blocked_acquires_stop_once_the_budget_is_spent() {
+    let dibs = dibs();
+
+    let mut writer = Transaction::new(0, 0);
+    dibs.acquire(&mut writer, WRITE, integers(&[2])).unwrap();
+    dibs.acquire(&mut writer, WRITE, integers(&[1])).unwrap();
+
+    let mut reader = Transaction::new(1, 1);
+    reader.set_wait_budget(BUDGET);
+
+    // The first wait uses up the budget well before the timeout.
+    assert_exhausted(&dibs, &mut reader, 1);
+    assert_eq!(reader.remaining_wait_budget(), Some(Duration::default()));
+
+    // Later conflicts fail without waiting, while acquires that don't block still succeed.
+    assert_exhausted(&dibs, &mut reader, 2);
+    dibs.acquire(&mut reader, READ, integers(&[3])).unwrap();
+
+    assert_eq!(dibs.conflict_stats().num_timeouts, 2);
+
+    reader.commit();
+    writer.commit();
+}
@@ -0,0 +1,97 @@
+//! Lets a run's tables switch between the prepared and filtered levels, so that the level given on
+//! the command line is only where each table starts.
+
+use clap::{Arg, ArgMatches};
+use dibs::adaptive::AdaptiveController;
+use dibs::Dibs;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// The share of its acquires' time that a table must spend checking for conflicts before the
+/// controller considers switching it.
+const MIN_CHECK_SHARE: f64 = 0.1;
+const MIN_ACQUIRES: usize = 1000;
+
+/// How often the controller runs, if the tables are adaptive.
+#[derive(Clone, Copy)]
+pub struct Adaptive {
+    interval: Option<Duration>,
+}
+
+impl Adaptive {
+    /// Returns the `--adaptive` flag.
+    pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![Arg::with_name("adaptive")
+            .long("adaptive")
+            .takes_value(true)
+            .help(
+                "Lets tables switch between the prepared and filtered levels, reconsidering \
+                 every this many milliseconds",
+            )]
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> Adaptive {
+        Adaptive {
+            interval: matches
+                .value_of("adaptive")
+                .map(|millis| Duration::from_millis(u64::from_str(millis).unwrap())),
+        }
+    }
+
+    /// Makes every table that can switch levels adaptive.
+    pub fn enable(&self, dibs: &mut Dibs) {
+        if self.interval.is_some() {
+            for table in 0..dibs.num_tables() {
+                dibs.enable_adaptive(table);
+            }
+        }
+    }
+
+    /// Starts the controller on its own thread, if the tables are adaptive.
+    pub fn start(&self, dibs: &Arc<Dibs>) -> Option<AdaptiveRun> {
+        let interval = self.interval?;
+        let dibs = Arc::clone(dibs);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+
+        let handle = thread::spawn(move || {
+            let mut controller = AdaptiveController::new(&dibs, MIN_CHECK_SHARE, MIN_ACQUIRES);
+
+            while !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(interval);
+                controller.tick(&dibs);
+            }
+
+            controller.num_switches()
+        });
+
+        Some(AdaptiveRun { stop, handle })
+    }
+
+    /// Returns a parameter describing the controller for `runner::run_with_parameters`.
+    pub fn parameter(&self) -> (&'static str, String) {
+        let value = match self.interval {
+            Some(interval) => interval.as_millis().to_string(),
+            None => "off".to_string(),
+        };
+
+        ("adaptive", value)
+    }
+}
+
+/// A controller running alongside the workers.
+pub struct AdaptiveRun {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<usize>,
+}
+
+impl AdaptiveRun {
+    /// Stops the controller and returns how many times it switched a table.
+    pub fn stop(self) -> usize {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.join().unwrap()
+    }
+}
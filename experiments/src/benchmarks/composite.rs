@@ -0,0 +1,227 @@
+use crate::{Connection, Generator, Procedure};
+use dibs::predicate::Value;
+use dibs::{AcquireError, Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The tables and templates of one benchmark in a composite workload.
+pub struct Part {
+    pub filters: Vec<Option<usize>>,
+    pub templates: Vec<RequestTemplate>,
+}
+
+impl Part {
+    pub fn new(filters: Vec<Option<usize>>, templates: Vec<RequestTemplate>) -> Part {
+        Part { filters, templates }
+    }
+}
+
+/// Builds one `Dibs` over the tables of every part, with each part's tables and templates
+/// numbered after those of the parts before it. Returns the `Dibs` and the template offset of
+/// each part, which its procedures are run with.
+pub fn dibs(parts: Vec<Part>, optimization: OptimizationLevel) -> (Dibs, Vec<usize>) {
+    let mut filters = vec![];
+    let mut templates = vec![];
+    let mut template_offsets = vec![];
+
+    for part in parts {
+        let table_offset = filters.len();
+
+        template_offsets.push(templates.len());
+        filters.extend(part.filters);
+        templates.extend(part.templates.into_iter().map(|template| {
+            let table = template.table() + table_offset;
+            template.with_table(table)
+        }));
+    }
+
+    let dibs = Dibs::new(
+        &filters,
+        &templates,
+        optimization,
+        None,
+        None,
+        Duration::from_secs(60),
+    );
+
+    (dibs, template_offsets)
+}
+
+/// A procedure of either benchmark in a composite workload.
+pub enum CompositeProcedure<P, Q> {
+    First {
+        procedure: P,
+        template_offset: usize,
+    },
+    Second {
+        procedure: Q,
+        template_offset: usize,
+    },
+}
+
+/// Runs `execute` with the transaction's template IDs shifted by `template_offset`, restoring the
+/// previous offset afterwards so that composite workloads can be nested.
+fn with_template_offset<F>(
+    transaction: &mut Transaction,
+    template_offset: usize,
+    execute: F,
+) -> Result<(), AcquireError>
+where
+    F: FnOnce(&mut Transaction) -> Result<(), AcquireError>,
+{
+    let previous = transaction.template_offset();
+    transaction.set_template_offset(previous + template_offset);
+    let result = execute(transaction);
+    transaction.set_template_offset(previous);
+    result
+}
+
+impl<P, Q, C, D> Procedure<CompositeConnection<C, D>> for CompositeProcedure<P, Q>
+where
+    P: Procedure<C>,
+    Q: Procedure<D>,
+{
+    fn name(&self) -> &'static str {
+        match self {
+            CompositeProcedure::First { procedure, .. } => procedure.name(),
+            CompositeProcedure::Second { procedure, .. } => procedure.name(),
+        }
+    }
+
+    fn is_read_only(&self) -> bool {
+        match self {
+            CompositeProcedure::First { procedure, .. } => procedure.is_read_only(),
+            CompositeProcedure::Second { procedure, .. } => procedure.is_read_only(),
+        }
+    }
+
+    fn execute(
+        &self,
+        dibs: &Option<Arc<Dibs>>,
+        transaction: &mut Transaction,
+        connection: &mut CompositeConnection<C, D>,
+    ) -> Result<(), AcquireError> {
+        match self {
+            CompositeProcedure::First {
+                procedure,
+                template_offset,
+            } => with_template_offset(transaction, *template_offset, |transaction| {
+                procedure.execute(dibs, transaction, &mut connection.first)
+            }),
+            CompositeProcedure::Second {
+                procedure,
+                template_offset,
+            } => with_template_offset(transaction, *template_offset, |transaction| {
+                procedure.execute(dibs, transaction, &mut connection.second)
+            }),
+        }
+    }
+
+    fn declared_requests(&self) -> Vec<(usize, Vec<Value>)> {
+        let (requests, template_offset) = match self {
+            CompositeProcedure::First {
+                procedure,
+                template_offset,
+            } => (procedure.declared_requests(), template_offset),
+            CompositeProcedure::Second {
+                procedure,
+                template_offset,
+            } => (procedure.declared_requests(), template_offset),
+        };
+
+        requests
+            .into_iter()
+            .map(|(template_id, arguments)| (template_id + template_offset, arguments))
+            .collect()
+    }
+}
+
+/// Draws procedures from two benchmarks, taking a fraction `first_ratio` of them from the first.
+pub struct CompositeGenerator<G, H> {
+    first: G,
+    second: H,
+    first_ratio: f64,
+    template_offsets: [usize; 2],
+}
+
+impl<G, H> CompositeGenerator<G, H> {
+    /// Creates a generator whose parts run with the template offsets returned by `dibs`.
+    pub fn new(
+        first: G,
+        second: H,
+        first_ratio: f64,
+        template_offsets: [usize; 2],
+    ) -> CompositeGenerator<G, H> {
+        assert!((0.0..=1.0).contains(&first_ratio));
+
+        CompositeGenerator {
+            first,
+            second,
+            first_ratio,
+            template_offsets,
+        }
+    }
+}
+
+impl<G, H> Generator for CompositeGenerator<G, H>
+where
+    G: Generator,
+    H: Generator,
+{
+    type Item = CompositeProcedure<G::Item, H::Item>;
+
+    fn next(&self, rng: &mut StdRng) -> Self::Item {
+        if rng.gen_bool(self.first_ratio) {
+            CompositeProcedure::First {
+                procedure: self.first.next(rng),
+                template_offset: self.template_offsets[0],
+            }
+        } else {
+            CompositeProcedure::Second {
+                procedure: self.second.next(rng),
+                template_offset: self.template_offsets[1],
+            }
+        }
+    }
+}
+
+/// A connection to the databases of both benchmarks. Transactions span both, so a procedure
+/// of either benchmark is committed or rolled back together with the rest of its group.
+pub struct CompositeConnection<C, D> {
+    pub first: C,
+    pub second: D,
+}
+
+impl<C, D> CompositeConnection<C, D> {
+    pub fn new(first: C, second: D) -> CompositeConnection<C, D> {
+        CompositeConnection { first, second }
+    }
+}
+
+impl<C, D> Connection for CompositeConnection<C, D>
+where
+    C: Connection,
+    D: Connection,
+{
+    fn begin(&mut self) {
+        self.first.begin();
+        self.second.begin();
+    }
+
+    fn commit(&mut self) {
+        self.first.commit();
+        self.second.commit();
+    }
+
+    fn rollback(&mut self) {
+        self.first.rollback();
+        self.second.rollback();
+    }
+
+    fn savepoint(&mut self) {
+        self.first.savepoint();
+        self.second.savepoint();
+    }
+}
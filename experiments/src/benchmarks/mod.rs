@@ -1,3 +1,5 @@
+pub mod composite;
 pub mod scan;
+pub mod synthetic;
 pub mod tatp;
 pub mod ycsb;
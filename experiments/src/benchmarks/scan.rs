@@ -2,8 +2,8 @@ use crate::{Generator, Procedure};
 use dibs::predicate::{ComparisonOperator, Predicate, Value};
 use dibs::{AcquireError, Dibs, OptimizationLevel, RequestTemplate, Transaction};
 use fnv::FnvHashSet;
-use rand::rngs::ThreadRng;
-use rand::{thread_rng, Rng};
+use rand::rngs::StdRng;
+use rand::Rng;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -24,7 +24,7 @@ pub trait ScanConnection {
     ///     AND ((byte2_10 BETWEEN ? AND ?) OR (byte2_10 BETWEEN ? AND ?))
     /// ```
     fn get_subscriber_data_scan(
-        &self,
+        &mut self,
         byte2: [(u8, u8, u8, u8); 10],
     ) -> Vec<([bool; 10], [u8; 10], [u8; 10], u32, u32)>;
 
@@ -43,7 +43,7 @@ pub trait ScanConnection {
     ///     AND ((byte2_9 BETWEEN ? AND ?) OR (byte2_9 BETWEEN ? AND ?))
     ///     AND ((byte2_10 BETWEEN ? AND ?) OR (byte2_10 BETWEEN ? AND ?))
     /// ```
-    fn update_subscriber_location_scan(&self, vlr_location: u32, byte2: [(u8, u8, u8, u8); 10]);
+    fn update_subscriber_location_scan(&mut self, vlr_location: u32, byte2: [(u8, u8, u8, u8); 10]);
 }
 
 pub enum ScanProcedure {
@@ -69,6 +69,13 @@ fn byte2_to_arguments(byte2: &[(u8, u8, u8, u8); 10]) -> Vec<Value> {
 }
 
 impl<C: ScanConnection> Procedure<C> for ScanProcedure {
+    fn name(&self) -> &'static str {
+        match self {
+            ScanProcedure::GetSubscriberDataScan { .. } => "get_subscriber_data_scan",
+            ScanProcedure::UpdateSubscriberLocationScan { .. } => "update_subscriber_location_scan",
+        }
+    }
+
     fn is_read_only(&self) -> bool {
         match self {
             ScanProcedure::GetSubscriberDataScan { .. } => true,
@@ -106,24 +113,82 @@ impl<C: ScanConnection> Procedure<C> for ScanProcedure {
     }
 }
 
+/// The number of `byte2` columns that scans may restrict.
+pub const NUM_BYTE2_COLUMNS: usize = 10;
+
+#[derive(Clone, Debug)]
+pub struct ScanConfig {
+    /// The fraction of scans that only read.
+    pub select_mix: f64,
+    /// The bounds on the width of each `BETWEEN` range, drawn uniformly for every range.
+    pub min_range: u8,
+    pub max_range: u8,
+    /// The number of `byte2` columns that scans restrict, which should match the number of
+    /// conjuncts in the templates so that requests cover exactly the rows that are scanned. The
+    /// other columns are given ranges that match every value.
+    pub num_conjuncts: usize,
+}
+
+impl ScanConfig {
+    pub fn new(select_mix: f64, range: u8) -> ScanConfig {
+        ScanConfig {
+            select_mix,
+            min_range: range,
+            max_range: range,
+            num_conjuncts: NUM_BYTE2_COLUMNS,
+        }
+    }
+
+    /// Estimates the fraction of rows that a scan matches, treating the two ranges on a column
+    /// as independent and taking the width halfway between its bounds.
+    pub fn selectivity(&self) -> f64 {
+        let width = (self.min_range as f64 + self.max_range as f64) / 2.0 + 1.0;
+        let column = 1.0 - (1.0 - width / 256.0).powi(2);
+        column.powi(self.num_conjuncts as i32)
+    }
+}
+
 pub struct ScanGenerator {
     select_mix: f64,
-    range: u8,
+    min_range: u8,
+    max_range: u8,
+    num_conjuncts: usize,
 }
 
 impl ScanGenerator {
     pub fn new(select_mix: f64, range: u8) -> ScanGenerator {
-        ScanGenerator { select_mix, range }
+        ScanGenerator::with_config(&ScanConfig::new(select_mix, range))
+    }
+
+    pub fn with_config(config: &ScanConfig) -> ScanGenerator {
+        assert!(config.min_range <= config.max_range && config.max_range < u8::MAX);
+        assert!(config.num_conjuncts <= NUM_BYTE2_COLUMNS);
+
+        ScanGenerator {
+            select_mix: config.select_mix,
+            min_range: config.min_range,
+            max_range: config.max_range,
+            num_conjuncts: config.num_conjuncts,
+        }
+    }
+
+    fn gen_range(&self, rng: &mut StdRng) -> (u8, u8) {
+        let range = rng.gen_range(self.min_range, self.max_range + 1);
+        let start = rng.gen_range(0, u8::MAX - range);
+        (start, start + range)
     }
 
-    fn gen_byte2(&self, rng: &mut ThreadRng) -> [(u8, u8, u8, u8); 10] {
-        let mut arguments = [(0, 0, 0, 0); 10];
+    fn gen_byte2(&self, rng: &mut StdRng) -> [(u8, u8, u8, u8); NUM_BYTE2_COLUMNS] {
+        let mut arguments = [(0, u8::MAX, 0, u8::MAX); NUM_BYTE2_COLUMNS];
+
+        for argument in &mut arguments[..self.num_conjuncts] {
+            let (start, end) = self.gen_range(rng);
+            argument.0 = start;
+            argument.1 = end;
 
-        for argument in &mut arguments {
-            argument.0 = rng.gen_range(0, u8::max_value() - self.range);
-            argument.1 = argument.0 + self.range;
-            argument.2 = rng.gen_range(0, u8::max_value() - self.range);
-            argument.3 = argument.2 + self.range;
+            let (start, end) = self.gen_range(rng);
+            argument.2 = start;
+            argument.3 = end;
         }
 
         arguments
@@ -133,18 +198,16 @@ impl ScanGenerator {
 impl Generator for ScanGenerator {
     type Item = ScanProcedure;
 
-    fn next(&self) -> ScanProcedure {
-        let mut rng = thread_rng();
-
+    fn next(&self, rng: &mut StdRng) -> ScanProcedure {
         let transaction_type = rng.gen::<f64>();
 
         if transaction_type < self.select_mix {
-            let byte2 = self.gen_byte2(&mut rng);
+            let byte2 = self.gen_byte2(rng);
 
             ScanProcedure::GetSubscriberDataScan { byte2 }
         } else {
             let vlr_location = rng.gen();
-            let byte2 = self.gen_byte2(&mut rng);
+            let byte2 = self.gen_byte2(rng);
 
             ScanProcedure::UpdateSubscriberLocationScan {
                 vlr_location,
@@ -154,7 +217,9 @@ impl Generator for ScanGenerator {
     }
 }
 
-pub fn dibs(num_conjuncts: usize, optimization: OptimizationLevel, blowup_limit: usize) -> Dibs {
+/// Returns the request templates of the scan procedures, which restrict the first `num_conjuncts`
+/// `byte2` columns of the subscriber table.
+pub fn templates(num_conjuncts: usize) -> Vec<RequestTemplate> {
     let scan_predicate = Predicate::conjunction(
         (0..num_conjuncts)
             .map(|i| {
@@ -172,7 +237,7 @@ pub fn dibs(num_conjuncts: usize, optimization: OptimizationLevel, blowup_limit:
             .collect(),
     );
 
-    let templates = vec![
+    vec![
         // (0) Get subscriber data scan.
         RequestTemplate::new(
             0,
@@ -187,13 +252,16 @@ pub fn dibs(num_conjuncts: usize, optimization: OptimizationLevel, blowup_limit:
             [32].iter().cloned().collect(),
             scan_predicate,
         ),
-    ];
+    ]
+}
 
+pub fn dibs(num_conjuncts: usize, optimization: OptimizationLevel, blowup_limit: usize) -> Dibs {
     Dibs::new(
         &[None],
-        &templates,
+        &templates(num_conjuncts),
         optimization,
-        blowup_limit,
+        None,
+        Some(blowup_limit),
         Duration::from_secs(60),
     )
 }
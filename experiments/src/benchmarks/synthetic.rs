@@ -0,0 +1,196 @@
+//! A synthetic benchmark over a single table, for sweeping the conflict rate and the number of
+//! templates without the semantics of TATP or YCSB getting in the way.
+//!
+//! Each of the `num_templates` kinds of statement has a read template and an update template with
+//! the same predicate shape, over a field of its own, so templates of different kinds conflict only
+//! when they share a field. The key skew and the width of the predicates set how often requests
+//! overlap. The benchmark is built as a `Workload`, so it runs without a database.
+
+use crate::workload::{
+    ArgumentSpec, StatementSpec, TableSpec, TemplateSpec, TransactionSpec, Workload,
+};
+use std::str::FromStr;
+
+/// The columns that the `multi_column` shape restricts besides the key.
+pub const NUM_ATTRIBUTES: usize = 4;
+
+/// The shape of every template's predicate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PredicateShape {
+    /// `key = ?1`
+    Point,
+    /// `key BETWEEN ?1 AND ?2`
+    Range,
+    /// `key = ?1 OR key = ?2 OR ...`, with `width` keys.
+    Disjunction,
+    /// `key BETWEEN ?1 AND ?2 AND attribute_i = ?3`, where `i` depends on the template.
+    MultiColumn,
+}
+
+impl FromStr for PredicateShape {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "point" => Ok(PredicateShape::Point),
+            "range" => Ok(PredicateShape::Range),
+            "disjunction" => Ok(PredicateShape::Disjunction),
+            "multi_column" => Ok(PredicateShape::MultiColumn),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SyntheticConfig {
+    pub num_rows: u32,
+    pub num_templates: usize,
+    pub shape: PredicateShape,
+    /// The number of keys a range covers, or the number of keys a disjunction lists.
+    pub width: usize,
+    /// The number of distinct fields that templates read and write. Kinds of statement share a
+    /// field when there are fewer fields than kinds.
+    pub num_fields: usize,
+    /// The fraction of statements that update rather than read.
+    pub write_mix: f64,
+    pub num_statements_per_transaction: usize,
+    /// One of the names that `KeyDistribution::from_name` accepts, and its skew.
+    pub distribution: String,
+    pub skew: f64,
+    /// Whether Dibs partitions requests on the key.
+    pub filter: bool,
+}
+
+impl SyntheticConfig {
+    pub fn new(num_rows: u32, num_templates: usize, shape: PredicateShape) -> SyntheticConfig {
+        SyntheticConfig {
+            num_rows,
+            num_templates,
+            shape,
+            width: 1,
+            num_fields: num_templates,
+            write_mix: 0.5,
+            num_statements_per_transaction: 1,
+            distribution: "uniform".to_string(),
+            skew: 0.0,
+            filter: false,
+        }
+    }
+
+    fn predicate(&self, kind: usize) -> String {
+        match self.shape {
+            PredicateShape::Point => "key = ?1".to_string(),
+            PredicateShape::Range => "key BETWEEN ?1 AND ?2".to_string(),
+            PredicateShape::Disjunction => (1..=self.width)
+                .map(|i| format!("key = ?{}", i))
+                .collect::<Vec<_>>()
+                .join(" OR "),
+            PredicateShape::MultiColumn => format!(
+                "key BETWEEN ?1 AND ?2 AND attribute_{} = ?3",
+                kind % NUM_ATTRIBUTES
+            ),
+        }
+    }
+
+    fn arguments(&self) -> Vec<ArgumentSpec> {
+        let key = || ArgumentSpec::Key {
+            distribution: self.distribution.clone(),
+            max: None,
+            skew: self.skew,
+        };
+
+        let range = || ArgumentSpec::Offset {
+            offset: 0,
+            by: self.width.saturating_sub(1),
+        };
+
+        match self.shape {
+            PredicateShape::Point => vec![key()],
+            PredicateShape::Range => vec![key(), range()],
+            PredicateShape::Disjunction => (0..self.width).map(|_| key()).collect(),
+            PredicateShape::MultiColumn => vec![
+                key(),
+                range(),
+                ArgumentSpec::Key {
+                    distribution: "uniform".to_string(),
+                    max: Some(self.num_rows.min(256)),
+                    skew: 0.0,
+                },
+            ],
+        }
+    }
+}
+
+/// Returns the workload for the configuration. Its phases, workers and optimization level are left
+/// at their defaults for the caller to set.
+pub fn workload(config: &SyntheticConfig) -> Workload {
+    assert!(config.num_templates > 0 && config.num_fields > 0 && config.width > 0);
+    assert!((0.0..=1.0).contains(&config.write_mix));
+
+    let columns = std::iter::once("key".to_string())
+        .chain((0..NUM_ATTRIBUTES).map(|i| format!("attribute_{}", i)))
+        .chain((0..config.num_fields).map(|i| format!("field_{}", i)))
+        .collect();
+
+    let mut templates = Vec::with_capacity(config.num_templates * 2);
+    let mut transactions = Vec::with_capacity(config.num_templates * 2);
+
+    for kind in 0..config.num_templates {
+        let field = vec![format!("field_{}", kind % config.num_fields)];
+
+        for &write in &[false, true] {
+            let name = format!("{}_{}", if write { "update" } else { "read" }, kind);
+
+            templates.push(TemplateSpec {
+                name: name.clone(),
+                table: "synthetic".to_string(),
+                reads: if write { vec![] } else { field.clone() },
+                writes: if write { field.clone() } else { vec![] },
+                predicate: config.predicate(kind),
+            });
+
+            let mix = if write {
+                config.write_mix
+            } else {
+                1.0 - config.write_mix
+            };
+
+            // A transaction repeats one kind of statement, so that the mix of transactions is
+            // also the mix of statements.
+            if mix > 0.0 {
+                transactions.push(TransactionSpec {
+                    name: name.clone(),
+                    weight: mix / config.num_templates as f64,
+                    statements: (0..config.num_statements_per_transaction)
+                        .map(|_| StatementSpec {
+                            template: name.clone(),
+                            arguments: config.arguments(),
+                        })
+                        .collect(),
+                });
+            }
+        }
+    }
+
+    Workload {
+        workers: 1,
+        warmup: None,
+        measurement: None,
+        cooldown: None,
+        optimization: "prepared".to_string(),
+        blowup_limit: None,
+        statement_time: 0.0,
+        tables: vec![TableSpec {
+            name: "synthetic".to_string(),
+            columns,
+            rows: config.num_rows,
+            filter: if config.filter {
+                Some("key".to_string())
+            } else {
+                None
+            },
+        }],
+        templates,
+        transactions,
+    }
+}
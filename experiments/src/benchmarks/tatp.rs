@@ -1,12 +1,18 @@
+use crate::codec;
+use crate::codec::Decoder;
+use crate::server::Request;
 use crate::{Generator, Procedure};
-use dibs::predicate::{ComparisonOperator, Predicate, Value};
-use dibs::{AcquireError, Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use dibs::predicate::{ComparisonOperator, Domain, Predicate, Value};
+use dibs::{AccessMode, AcquireError, Dibs, OptimizationLevel, RequestTemplate, Transaction};
 use fnv::FnvHashSet;
-use rand::rngs::ThreadRng;
-use rand::{thread_rng, Rng};
+use rand::rngs::StdRng;
+use rand::Rng;
 use std::sync::Arc;
 use std::time::Duration;
 
+/// The statements of the TATP transactions. Systems implement the single statements, and may
+/// override the compositions of them that a transaction makes, such as `update_subscriber_data`,
+/// to run each as one stored procedure.
 pub trait TATPConnection {
     /// Get subscriber data by ID.
     /// ```sql
@@ -58,8 +64,16 @@ pub trait TATPConnection {
     /// UPDATE special_facility
     /// SET data_a = ?
     /// WHERE s_id = ? AND sf_type = ?;
+    /// ```
     fn update_special_facility_data(&mut self, data_a: u8, s_id: u32, sf_type: u8);
 
+    /// Update subscriber data, which updates the subscriber bit and then the special facility
+    /// data.
+    fn update_subscriber_data(&mut self, bit_1: bool, s_id: u32, data_a: u8, sf_type: u8) {
+        self.update_subscriber_bit(bit_1, s_id);
+        self.update_special_facility_data(data_a, s_id, sf_type);
+    }
+
     /// Update subscriber location.
     /// ```sql
     /// UPDATE subscriber
@@ -137,6 +151,18 @@ pub enum TATPProcedure {
 }
 
 impl<C: TATPConnection> Procedure<C> for TATPProcedure {
+    fn name(&self) -> &'static str {
+        match self {
+            TATPProcedure::GetSubscriberData { .. } => "get_subscriber_data",
+            TATPProcedure::GetNewDestination { .. } => "get_new_destination",
+            TATPProcedure::GetAccessData { .. } => "get_access_data",
+            TATPProcedure::UpdateSubscriberData { .. } => "update_subscriber_data",
+            TATPProcedure::UpdateLocation { .. } => "update_location",
+            TATPProcedure::InsertCallForwarding { .. } => "insert_call_forwarding",
+            TATPProcedure::DeleteCallForwarding { .. } => "delete_call_forwarding",
+        }
+    }
+
     fn is_read_only(&self) -> bool {
         match self {
             TATPProcedure::GetSubscriberData { .. }
@@ -157,8 +183,15 @@ impl<C: TATPConnection> Procedure<C> for TATPProcedure {
     ) -> Result<(), AcquireError> {
         match self {
             TATPProcedure::GetSubscriberData { s_id } => {
+                // The subscriber table is table 0 in the composite workloads too, where TATP's
+                // tables come first.
                 if let Some(d) = dibs {
-                    d.acquire(transaction, 0, vec![Value::Integer(*s_id as usize)])?;
+                    d.acquire_point(
+                        transaction,
+                        0,
+                        Value::Integer(*s_id as usize),
+                        AccessMode::Read,
+                    )?;
                 }
 
                 connection.get_subscriber_data(*s_id);
@@ -171,19 +204,19 @@ impl<C: TATPConnection> Procedure<C> for TATPProcedure {
                 end_time,
             } => {
                 if let Some(d) = dibs {
-                    d.acquire(
+                    d.acquire_borrowed(
                         transaction,
                         1,
-                        vec![
+                        &[
                             Value::Integer(*s_id as usize),
                             Value::Integer(*sf_type as usize),
                         ],
                     )?;
 
-                    d.acquire(
+                    d.acquire_borrowed(
                         transaction,
                         2,
-                        vec![
+                        &[
                             Value::Integer(*s_id as usize),
                             Value::Integer(*sf_type as usize),
                             Value::Integer(*start_time as usize),
@@ -197,10 +230,10 @@ impl<C: TATPConnection> Procedure<C> for TATPProcedure {
 
             TATPProcedure::GetAccessData { s_id, ai_type } => {
                 if let Some(d) = dibs {
-                    d.acquire(
+                    d.acquire_borrowed(
                         transaction,
                         3,
-                        vec![
+                        &[
                             Value::Integer(*s_id as usize),
                             Value::Integer(*ai_type as usize),
                         ],
@@ -216,25 +249,12 @@ impl<C: TATPConnection> Procedure<C> for TATPProcedure {
                 data_a,
                 sf_type,
             } => {
-                if let Some(d) = dibs {
-                    d.acquire(transaction, 4, vec![Value::Integer(*s_id as usize)])?;
-
-                    d.acquire(
-                        transaction,
-                        5,
-                        vec![
-                            Value::Integer(*s_id as usize),
-                            Value::Integer(*sf_type as usize),
-                        ],
-                    )?;
-                }
-
-                connection.update_subscriber_bit(*bit_1, *s_id);
-                connection.update_special_facility_data(*data_a, *s_id, *sf_type);
+                // The requests are declared, and were acquired in table order before executing.
+                connection.update_subscriber_data(*bit_1, *s_id, *data_a, *sf_type);
             }
             TATPProcedure::UpdateLocation { vlr_location, s_id } => {
                 if let Some(d) = dibs {
-                    d.acquire(transaction, 6, vec![Value::Integer(*s_id as usize)])?;
+                    d.acquire_borrowed(transaction, 6, &[Value::Integer(*s_id as usize)])?;
                 }
 
                 connection.update_subscriber_location(*vlr_location, *s_id);
@@ -247,12 +267,12 @@ impl<C: TATPConnection> Procedure<C> for TATPProcedure {
                 numberx,
             } => {
                 if let Some(d) = dibs {
-                    d.acquire(transaction, 7, vec![Value::Integer(*s_id as usize)])?;
+                    d.acquire_borrowed(transaction, 7, &[Value::Integer(*s_id as usize)])?;
 
-                    d.acquire(
+                    d.acquire_borrowed(
                         transaction,
                         8,
-                        vec![
+                        &[
                             Value::Integer(*s_id as usize),
                             Value::Integer(*sf_type as usize),
                             Value::Integer(*start_time as usize),
@@ -269,10 +289,10 @@ impl<C: TATPConnection> Procedure<C> for TATPProcedure {
                 start_time,
             } => {
                 if let Some(d) = dibs {
-                    d.acquire(
+                    d.acquire_borrowed(
                         transaction,
                         8,
-                        vec![
+                        &[
                             Value::Integer(*s_id as usize),
                             Value::Integer(*sf_type as usize),
                             Value::Integer(*start_time as usize),
@@ -286,15 +306,196 @@ impl<C: TATPConnection> Procedure<C> for TATPProcedure {
 
         Ok(())
     }
+
+    fn declared_requests(&self) -> Vec<(usize, Vec<Value>)> {
+        match self {
+            // The subscriber and special facility rows follow from the inputs alone.
+            TATPProcedure::UpdateSubscriberData { s_id, sf_type, .. } => vec![
+                (4, vec![Value::Integer(*s_id as usize)]),
+                (
+                    5,
+                    vec![
+                        Value::Integer(*s_id as usize),
+                        Value::Integer(*sf_type as usize),
+                    ],
+                ),
+            ],
+            _ => vec![],
+        }
+    }
+}
+
+impl Request for TATPProcedure {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            TATPProcedure::GetSubscriberData { s_id } => {
+                codec::put_u8(buf, 0);
+                codec::put_u32(buf, *s_id);
+            }
+            TATPProcedure::GetNewDestination {
+                s_id,
+                sf_type,
+                start_time,
+                end_time,
+            } => {
+                codec::put_u8(buf, 1);
+                codec::put_u32(buf, *s_id);
+                codec::put_u8(buf, *sf_type);
+                codec::put_u8(buf, *start_time);
+                codec::put_u8(buf, *end_time);
+            }
+            TATPProcedure::GetAccessData { s_id, ai_type } => {
+                codec::put_u8(buf, 2);
+                codec::put_u32(buf, *s_id);
+                codec::put_u8(buf, *ai_type);
+            }
+            TATPProcedure::UpdateSubscriberData {
+                bit_1,
+                s_id,
+                data_a,
+                sf_type,
+            } => {
+                codec::put_u8(buf, 3);
+                codec::put_bool(buf, *bit_1);
+                codec::put_u32(buf, *s_id);
+                codec::put_u8(buf, *data_a);
+                codec::put_u8(buf, *sf_type);
+            }
+            TATPProcedure::UpdateLocation { vlr_location, s_id } => {
+                codec::put_u8(buf, 4);
+                codec::put_u32(buf, *vlr_location);
+                codec::put_u32(buf, *s_id);
+            }
+            TATPProcedure::InsertCallForwarding {
+                s_id,
+                sf_type,
+                start_time,
+                end_time,
+                numberx,
+            } => {
+                codec::put_u8(buf, 5);
+                codec::put_u32(buf, *s_id);
+                codec::put_u8(buf, *sf_type);
+                codec::put_u8(buf, *start_time);
+                codec::put_u8(buf, *end_time);
+                codec::put_bytes(buf, numberx.as_bytes());
+            }
+            TATPProcedure::DeleteCallForwarding {
+                s_id,
+                sf_type,
+                start_time,
+            } => {
+                codec::put_u8(buf, 6);
+                codec::put_u32(buf, *s_id);
+                codec::put_u8(buf, *sf_type);
+                codec::put_u8(buf, *start_time);
+            }
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> Option<TATPProcedure> {
+        let procedure = match decoder.u8()? {
+            0 => TATPProcedure::GetSubscriberData {
+                s_id: decoder.u32()?,
+            },
+            1 => TATPProcedure::GetNewDestination {
+                s_id: decoder.u32()?,
+                sf_type: decoder.u8()?,
+                start_time: decoder.u8()?,
+                end_time: decoder.u8()?,
+            },
+            2 => TATPProcedure::GetAccessData {
+                s_id: decoder.u32()?,
+                ai_type: decoder.u8()?,
+            },
+            3 => TATPProcedure::UpdateSubscriberData {
+                bit_1: decoder.bool()?,
+                s_id: decoder.u32()?,
+                data_a: decoder.u8()?,
+                sf_type: decoder.u8()?,
+            },
+            4 => TATPProcedure::UpdateLocation {
+                vlr_location: decoder.u32()?,
+                s_id: decoder.u32()?,
+            },
+            5 => TATPProcedure::InsertCallForwarding {
+                s_id: decoder.u32()?,
+                sf_type: decoder.u8()?,
+                start_time: decoder.u8()?,
+                end_time: decoder.u8()?,
+                numberx: decoder.string()?,
+            },
+            6 => TATPProcedure::DeleteCallForwarding {
+                s_id: decoder.u32()?,
+                sf_type: decoder.u8()?,
+                start_time: decoder.u8()?,
+            },
+            _ => return None,
+        };
+
+        Some(procedure)
+    }
+}
+
+/// The relative weights of the TATP transaction types in the standard mix, in the order of the
+/// specification: get subscriber data, get new destination, get access data, update subscriber
+/// data, update location, insert call forwarding, delete call forwarding.
+pub const STANDARD_MIX: [f64; 7] = [35.0, 10.0, 35.0, 2.0, 14.0, 2.0, 2.0];
+
+#[derive(Clone, Debug)]
+pub struct TATPConfig {
+    /// The number of subscribers that transactions access, which may be less than the number
+    /// loaded.
+    pub population: u32,
+    /// Whether subscriber IDs follow the non-uniform distribution of the specification rather
+    /// than a uniform one.
+    pub non_uniform: bool,
+    /// The relative weights of the transaction types, in the order of `STANDARD_MIX`.
+    pub mix: [f64; 7],
+}
+
+impl TATPConfig {
+    pub fn new(population: u32) -> TATPConfig {
+        TATPConfig {
+            population,
+            non_uniform: true,
+            mix: STANDARD_MIX,
+        }
+    }
+
+    /// Parses seven comma-separated weights, such as `35,10,35,2,14,2,2`.
+    pub fn parse_mix(s: &str) -> Option<[f64; 7]> {
+        let weights = s
+            .split(',')
+            .map(|weight| weight.trim().parse::<f64>().ok().filter(|&w| w >= 0.0))
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut mix = [0.0; 7];
+
+        if weights.len() != mix.len() || weights.iter().sum::<f64>() <= 0.0 {
+            return None;
+        }
+
+        mix.copy_from_slice(&weights);
+        Some(mix)
+    }
 }
 
 pub struct TATPGenerator {
     num_rows: u32,
     a_val: u32,
+    non_uniform: bool,
+    thresholds: [f64; 7],
 }
 
 impl TATPGenerator {
     pub fn new(num_rows: u32) -> TATPGenerator {
+        TATPGenerator::with_config(&TATPConfig::new(num_rows))
+    }
+
+    pub fn with_config(config: &TATPConfig) -> TATPGenerator {
+        let num_rows = config.population;
+
         let a_val = if num_rows <= 1000000 {
             65535
         } else if num_rows <= 10000000 {
@@ -303,14 +504,33 @@ impl TATPGenerator {
             2097151
         };
 
-        TATPGenerator { num_rows, a_val }
+        let total = config.mix.iter().sum::<f64>();
+        let mut thresholds = [0.0; 7];
+        let mut cumulative = 0.0;
+
+        for (threshold, weight) in thresholds.iter_mut().zip(&config.mix) {
+            cumulative += weight / total;
+            *threshold = cumulative;
+        }
+
+        TATPGenerator {
+            num_rows,
+            a_val,
+            non_uniform: config.non_uniform,
+            thresholds,
+        }
     }
 
-    fn gen_s_id(&self, rng: &mut ThreadRng) -> u32 {
-        (rng.gen_range(0, self.a_val + 1) | rng.gen_range(1, self.num_rows + 1)) % self.num_rows + 1
+    fn gen_s_id(&self, rng: &mut StdRng) -> u32 {
+        if self.non_uniform {
+            (rng.gen_range(0, self.a_val + 1) | rng.gen_range(1, self.num_rows + 1)) % self.num_rows
+                + 1
+        } else {
+            rng.gen_range(1, self.num_rows + 1)
+        }
     }
 
-    fn gen_numberx(&self, rng: &mut ThreadRng) -> String {
+    fn gen_numberx(&self, rng: &mut StdRng) -> String {
         let mut numberx = vec![0; 15];
         let s = rng.gen_range(1, self.num_rows + 1).to_string();
         numberx[(15 - s.len())..].copy_from_slice(s.as_bytes());
@@ -321,15 +541,13 @@ impl TATPGenerator {
 impl Generator for TATPGenerator {
     type Item = TATPProcedure;
 
-    fn next(&self) -> TATPProcedure {
-        let mut rng = thread_rng();
-
+    fn next(&self, rng: &mut StdRng) -> TATPProcedure {
         let transaction_type = rng.gen::<f64>();
-        let s_id = self.gen_s_id(&mut rng);
+        let s_id = self.gen_s_id(rng);
 
-        if transaction_type < 0.35 {
+        if transaction_type < self.thresholds[0] {
             TATPProcedure::GetSubscriberData { s_id }
-        } else if transaction_type < 0.45 {
+        } else if transaction_type < self.thresholds[1] {
             let sf_type = rng.gen_range(1, 5);
             let start_time = rng.gen_range(0, 3) * 8;
             let end_time = rng.gen_range(1, 25);
@@ -340,11 +558,11 @@ impl Generator for TATPGenerator {
                 start_time,
                 end_time,
             }
-        } else if transaction_type < 0.80 {
+        } else if transaction_type < self.thresholds[2] {
             let ai_type = rng.gen_range(1, 5);
 
             TATPProcedure::GetAccessData { s_id, ai_type }
-        } else if transaction_type < 0.82 {
+        } else if transaction_type < self.thresholds[3] {
             let bit_1 = rng.gen();
             let data_a = rng.gen();
             let sf_type = rng.gen_range(1, 5);
@@ -355,15 +573,15 @@ impl Generator for TATPGenerator {
                 data_a,
                 sf_type,
             }
-        } else if transaction_type < 0.96 {
+        } else if transaction_type < self.thresholds[4] {
             let vlr_location = rng.gen();
 
             TATPProcedure::UpdateLocation { vlr_location, s_id }
-        } else if transaction_type < 0.98 {
+        } else if transaction_type < self.thresholds[5] {
             let sf_type = rng.gen_range(1, 5);
             let start_time = rng.gen_range(0, 3) * 8;
             let end_time = rng.gen_range(1, 25);
-            let numberx = self.gen_numberx(&mut rng);
+            let numberx = self.gen_numberx(rng);
 
             TATPProcedure::InsertCallForwarding {
                 s_id,
@@ -385,14 +603,19 @@ impl Generator for TATPGenerator {
     }
 }
 
-pub fn dibs(optimization: OptimizationLevel) -> Dibs {
-    let filters = match optimization {
-        OptimizationLevel::Filtered => &[Some(0), Some(0), Some(0), Some(0)],
-        _ => &[None, None, None, None],
-    };
+/// Returns the filter column of each TATP table.
+pub fn filters(optimization: OptimizationLevel) -> Vec<Option<usize>> {
+    match optimization {
+        OptimizationLevel::Filtered => vec![Some(0), Some(0), Some(0), Some(0)],
+        _ => vec![None, None, None, None],
+    }
+}
 
-    let templates = vec![
-        // (0) Get subscriber data.
+/// Returns the request templates of the TATP procedures. With the `sql` feature of `dibs`, they
+/// can be checked against the statements of `TATPConnection` with `RequestTemplate::from_sql`.
+pub fn templates() -> Vec<RequestTemplate> {
+    vec![
+        // (0) Get subscriber data, which the procedure acquires as a point read instead.
         RequestTemplate::new(
             0,
             (0..33).collect(),
@@ -410,7 +633,7 @@ pub fn dibs(optimization: OptimizationLevel) -> Dibs {
             ]),
         ),
         // (2) Get new destination (call forwarding).
-        RequestTemplate::new(
+        with_call_forwarding_domains(RequestTemplate::new(
             3,
             (0..5).collect(),
             FnvHashSet::default(),
@@ -420,7 +643,7 @@ pub fn dibs(optimization: OptimizationLevel) -> Dibs {
                 Predicate::comparison(ComparisonOperator::Le, 2, 2),
                 Predicate::comparison(ComparisonOperator::Gt, 3, 3),
             ]),
-        ),
+        )),
         // (3) Get access data.
         RequestTemplate::new(
             1,
@@ -435,7 +658,7 @@ pub fn dibs(optimization: OptimizationLevel) -> Dibs {
         RequestTemplate::new(
             0,
             [0].iter().cloned().collect(),
-            [2].iter().cloned().collect(),
+            [1].iter().cloned().collect(),
             Predicate::comparison(ComparisonOperator::Eq, 0, 0),
         ),
         // (5) Update special facility data.
@@ -463,7 +686,7 @@ pub fn dibs(optimization: OptimizationLevel) -> Dibs {
             Predicate::comparison(ComparisonOperator::Eq, 0, 0),
         ),
         // (8) Insert/delete call forwarding.
-        RequestTemplate::new(
+        with_call_forwarding_domains(RequestTemplate::new(
             3,
             FnvHashSet::default(),
             (0..5).collect(),
@@ -472,19 +695,36 @@ pub fn dibs(optimization: OptimizationLevel) -> Dibs {
                 Predicate::comparison(ComparisonOperator::Eq, 1, 1),
                 Predicate::comparison(ComparisonOperator::Eq, 2, 2),
             ]),
-        ),
-    ];
+        )),
+    ]
+}
 
+/// Declares the values that the sf_type, start_time and end_time columns of call forwarding rows
+/// take, so that interval pruning can rule out lookups whose times fall outside them.
+fn with_call_forwarding_domains(template: RequestTemplate) -> RequestTemplate {
+    let start_times = [0, 8, 16]
+        .iter()
+        .map(|&time| Value::Integer(time))
+        .collect();
+
+    template
+        .with_domain(1, Domain::Range(Value::Integer(1), Value::Integer(4)))
+        .with_domain(2, Domain::Values(start_times))
+        .with_domain(3, Domain::Range(Value::Integer(1), Value::Integer(24)))
+}
+
+pub fn dibs(optimization: OptimizationLevel) -> Dibs {
     Dibs::new(
-        filters,
-        &templates,
+        &filters(optimization),
+        &templates(),
         optimization,
-        usize::max_value(),
+        None,
+        None,
         Duration::from_secs(60),
     )
 }
 
-pub fn uppercase_alphabetic_string(len: usize, rng: &mut ThreadRng) -> String {
+pub fn uppercase_alphabetic_string<R: Rng>(len: usize, rng: &mut R) -> String {
     const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
     (0..len)
         .map(|_| CHARSET[rng.gen_range(0, CHARSET.len())] as char)
@@ -1,13 +1,25 @@
+use crate::codec;
+use crate::codec::Decoder;
+use crate::server::Request;
 use crate::{Generator, OptimizationLevel, Procedure};
 use dibs::predicate::{ComparisonOperator, Predicate, Value};
 use dibs::{AcquireError, Dibs, RequestTemplate, Transaction};
 use fnv::FnvHashSet;
 use rand::distributions::Alphanumeric;
-use rand::{distributions, thread_rng, Rng};
+use rand::rngs::StdRng;
+use rand::{distributions, Rng};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 pub const NUM_FIELDS: usize = 10;
+pub const MAX_SCAN_LENGTH: u32 = 100;
+
+const READ_MODIFY_WRITE_TEMPLATE: usize = 2 * NUM_FIELDS;
+const INSERT_TEMPLATE: usize = 3 * NUM_FIELDS;
+const SCAN_TEMPLATE: usize = 3 * NUM_FIELDS + 1;
+const DELETE_TEMPLATE: usize = 4 * NUM_FIELDS + 1;
 
 pub trait YCSBConnection {
     /// Get user.
@@ -25,6 +37,28 @@ pub trait YCSBConnection {
     /// WHERE id = ?;
     /// ```
     fn update_user(&mut self, field: usize, data: &str, user_id: u32);
+
+    /// Insert user.
+    /// ```sql
+    /// INSERT INTO users
+    /// VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);
+    /// ```
+    fn insert_user(&mut self, user_id: u32, fields: &[String]);
+
+    /// Delete user.
+    /// ```sql
+    /// DELETE FROM users
+    /// WHERE id = ?;
+    /// ```
+    fn delete_user(&mut self, user_id: u32);
+
+    /// Scan users.
+    /// ```sql
+    /// SELECT field
+    /// FROM users
+    /// WHERE id >= ? AND id < ?;
+    /// ```
+    fn scan_users(&mut self, field: usize, start_user_id: u32, end_user_id: u32) -> Vec<String>;
 }
 
 pub enum YCSBStatement {
@@ -37,6 +71,23 @@ pub enum YCSBStatement {
         data: String,
         user_id: u32,
     },
+    ReadModifyWriteUser {
+        field: usize,
+        data: String,
+        user_id: u32,
+    },
+    InsertUser {
+        user_id: u32,
+        fields: Vec<String>,
+    },
+    DeleteUser {
+        user_id: u32,
+    },
+    ScanUsers {
+        field: usize,
+        start_user_id: u32,
+        end_user_id: u32,
+    },
 }
 
 pub struct YCSBProcedure {
@@ -50,10 +101,30 @@ impl YCSBProcedure {
 }
 
 impl<C: YCSBConnection> Procedure<C> for YCSBProcedure {
+    /// Names the procedure after its most expensive kind of statement, since a transaction may mix
+    /// several.
+    fn name(&self) -> &'static str {
+        self.statements
+            .iter()
+            .map(|statement| match statement {
+                YCSBStatement::SelectUser { .. } => (0, "read"),
+                YCSBStatement::ScanUsers { .. } => (1, "scan"),
+                YCSBStatement::UpdateUser { .. } => (2, "update"),
+                YCSBStatement::ReadModifyWriteUser { .. } => (3, "read_modify_write"),
+                YCSBStatement::InsertUser { .. } => (4, "insert"),
+                YCSBStatement::DeleteUser { .. } => (5, "delete"),
+            })
+            .max()
+            .map_or("read", |(_, name)| name)
+    }
+
     fn is_read_only(&self) -> bool {
         self.statements.iter().all(|statement| match statement {
-            YCSBStatement::SelectUser { .. } => true,
-            YCSBStatement::UpdateUser { .. } => false,
+            YCSBStatement::SelectUser { .. } | YCSBStatement::ScanUsers { .. } => true,
+            YCSBStatement::UpdateUser { .. }
+            | YCSBStatement::ReadModifyWriteUser { .. }
+            | YCSBStatement::InsertUser { .. }
+            | YCSBStatement::DeleteUser { .. } => false,
         })
     }
 
@@ -87,6 +158,62 @@ impl<C: YCSBConnection> Procedure<C> for YCSBProcedure {
 
                     connection.update_user(*field, data, *user_id);
                 }
+                YCSBStatement::ReadModifyWriteUser {
+                    field,
+                    data,
+                    user_id,
+                } => {
+                    if let Some(d) = dibs {
+                        d.acquire(
+                            transaction,
+                            READ_MODIFY_WRITE_TEMPLATE + *field,
+                            vec![Value::Integer(*user_id as usize)],
+                        )?;
+                    }
+
+                    connection.select_user(*field, *user_id);
+                    connection.update_user(*field, data, *user_id);
+                }
+                YCSBStatement::InsertUser { user_id, fields } => {
+                    if let Some(d) = dibs {
+                        d.acquire(
+                            transaction,
+                            INSERT_TEMPLATE,
+                            vec![Value::Integer(*user_id as usize)],
+                        )?;
+                    }
+
+                    connection.insert_user(*user_id, fields);
+                }
+                YCSBStatement::DeleteUser { user_id } => {
+                    if let Some(d) = dibs {
+                        d.acquire(
+                            transaction,
+                            DELETE_TEMPLATE,
+                            vec![Value::Integer(*user_id as usize)],
+                        )?;
+                    }
+
+                    connection.delete_user(*user_id);
+                }
+                YCSBStatement::ScanUsers {
+                    field,
+                    start_user_id,
+                    end_user_id,
+                } => {
+                    if let Some(d) = dibs {
+                        d.acquire(
+                            transaction,
+                            SCAN_TEMPLATE + *field,
+                            vec![
+                                Value::Integer(*start_user_id as usize),
+                                Value::Integer(*end_user_id as usize),
+                            ],
+                        )?;
+                    }
+
+                    connection.scan_users(*field, *start_user_id, *end_user_id);
+                }
             }
         }
 
@@ -94,15 +221,203 @@ impl<C: YCSBConnection> Procedure<C> for YCSBProcedure {
     }
 }
 
+impl Request for YCSBProcedure {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        codec::put_u32(buf, self.statements.len() as u32);
+
+        for statement in &self.statements {
+            match statement {
+                YCSBStatement::SelectUser { field, user_id } => {
+                    codec::put_u8(buf, 0);
+                    codec::put_u8(buf, *field as u8);
+                    codec::put_u32(buf, *user_id);
+                }
+                YCSBStatement::UpdateUser {
+                    field,
+                    data,
+                    user_id,
+                } => {
+                    codec::put_u8(buf, 1);
+                    codec::put_u8(buf, *field as u8);
+                    codec::put_bytes(buf, data.as_bytes());
+                    codec::put_u32(buf, *user_id);
+                }
+                YCSBStatement::ReadModifyWriteUser {
+                    field,
+                    data,
+                    user_id,
+                } => {
+                    codec::put_u8(buf, 2);
+                    codec::put_u8(buf, *field as u8);
+                    codec::put_bytes(buf, data.as_bytes());
+                    codec::put_u32(buf, *user_id);
+                }
+                YCSBStatement::InsertUser { user_id, fields } => {
+                    codec::put_u8(buf, 3);
+                    codec::put_u32(buf, *user_id);
+                    codec::put_u8(buf, fields.len() as u8);
+
+                    for field in fields {
+                        codec::put_bytes(buf, field.as_bytes());
+                    }
+                }
+                YCSBStatement::ScanUsers {
+                    field,
+                    start_user_id,
+                    end_user_id,
+                } => {
+                    codec::put_u8(buf, 4);
+                    codec::put_u8(buf, *field as u8);
+                    codec::put_u32(buf, *start_user_id);
+                    codec::put_u32(buf, *end_user_id);
+                }
+                YCSBStatement::DeleteUser { user_id } => {
+                    codec::put_u8(buf, 5);
+                    codec::put_u32(buf, *user_id);
+                }
+            }
+        }
+    }
+
+    /// Rejects statements on fields past `NUM_FIELDS`, since their templates don't exist.
+    fn decode(decoder: &mut Decoder) -> Option<YCSBProcedure> {
+        let field = |decoder: &mut Decoder| match decoder.u8()? as usize {
+            field if field < NUM_FIELDS => Some(field),
+            _ => None,
+        };
+
+        let num_statements = decoder.u32()?;
+        let mut statements = vec![];
+
+        for _ in 0..num_statements {
+            let statement = match decoder.u8()? {
+                0 => YCSBStatement::SelectUser {
+                    field: field(decoder)?,
+                    user_id: decoder.u32()?,
+                },
+                1 => YCSBStatement::UpdateUser {
+                    field: field(decoder)?,
+                    data: decoder.string()?,
+                    user_id: decoder.u32()?,
+                },
+                2 => YCSBStatement::ReadModifyWriteUser {
+                    field: field(decoder)?,
+                    data: decoder.string()?,
+                    user_id: decoder.u32()?,
+                },
+                3 => {
+                    let user_id = decoder.u32()?;
+                    let num_fields = decoder.u8()?;
+
+                    YCSBStatement::InsertUser {
+                        user_id,
+                        fields: (0..num_fields)
+                            .map(|_| decoder.string())
+                            .collect::<Option<_>>()?,
+                    }
+                }
+                4 => YCSBStatement::ScanUsers {
+                    field: field(decoder)?,
+                    start_user_id: decoder.u32()?,
+                    end_user_id: decoder.u32()?,
+                },
+                5 => YCSBStatement::DeleteUser {
+                    user_id: decoder.u32()?,
+                },
+                _ => return None,
+            };
+
+            statements.push(statement);
+        }
+
+        Some(YCSBProcedure::new(statements))
+    }
+}
+
+/// The proportions of each kind of statement a generator produces. They should sum to one.
+#[derive(Clone, Copy, Debug)]
+pub struct YCSBMix {
+    pub read: f64,
+    pub update: f64,
+    pub insert: f64,
+    pub delete: f64,
+    pub scan: f64,
+    pub read_modify_write: f64,
+}
+
+impl YCSBMix {
+    pub fn read_update(read: f64) -> YCSBMix {
+        YCSBMix {
+            read,
+            update: 1.0 - read,
+            insert: 0.0,
+            delete: 0.0,
+            scan: 0.0,
+            read_modify_write: 0.0,
+        }
+    }
+
+    /// Turns `delete` of the read share into deletes of the users the key distribution picks, so
+    /// that a mix with inserts need not only grow the table.
+    pub fn with_deletes(self, delete: f64) -> YCSBMix {
+        assert!(
+            delete <= self.read,
+            "not enough reads to replace with deletes"
+        );
+
+        YCSBMix {
+            read: self.read - delete,
+            delete,
+            ..self
+        }
+    }
+}
+
+impl FromStr for YCSBMix {
+    type Err = ();
+
+    /// Parses the name of a standard YCSB workload, `a` through `f`.
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let none = YCSBMix::read_update(0.0);
+
+        match s {
+            "a" => Ok(YCSBMix::read_update(0.5)),
+            "b" => Ok(YCSBMix::read_update(0.95)),
+            "c" => Ok(YCSBMix::read_update(1.0)),
+            "d" => Ok(YCSBMix {
+                read: 0.95,
+                insert: 0.05,
+                update: 0.0,
+                ..none
+            }),
+            "e" => Ok(YCSBMix {
+                scan: 0.95,
+                insert: 0.05,
+                update: 0.0,
+                ..none
+            }),
+            "f" => Ok(YCSBMix {
+                read: 0.5,
+                read_modify_write: 0.5,
+                update: 0.0,
+                ..none
+            }),
+            _ => Err(()),
+        }
+    }
+}
+
 pub struct YCSBGenerator<D> {
     field_size: usize,
-    select_mix: f64,
+    mix: YCSBMix,
     num_statements_per_transaction: usize,
     distribution: D,
+    next_user_id: Arc<AtomicU32>,
 }
 
 impl<D> YCSBGenerator<D> {
     fn new(
+        num_rows: u32,
         field_size: usize,
         select_mix: f64,
         num_statements_per_transaction: usize,
@@ -110,43 +425,119 @@ impl<D> YCSBGenerator<D> {
     ) -> YCSBGenerator<D> {
         YCSBGenerator {
             field_size,
-            select_mix,
+            mix: YCSBMix::read_update(select_mix),
             num_statements_per_transaction,
             distribution,
+            next_user_id: Arc::new(AtomicU32::new(num_rows)),
         }
     }
+
+    /// Replaces the read/update mix. Inserted users take IDs from `next_user_id`, which should
+    /// start at the number of loaded rows and be shared by every generator in the run.
+    pub fn with_mix(mut self, mix: YCSBMix, next_user_id: Arc<AtomicU32>) -> YCSBGenerator<D> {
+        self.mix = mix;
+        self.next_user_id = next_user_id;
+        self
+    }
+
+    fn random_field(&self, rng: &mut StdRng) -> String {
+        rng.sample_iter(&Alphanumeric)
+            .take(self.field_size)
+            .collect()
+    }
 }
 
-pub type YCSBUniformGenerator = YCSBGenerator<distributions::Uniform<usize>>;
-pub type YCSBZipfGenerator = YCSBGenerator<zipf::ZipfDistribution>;
+/// How the generator picks the users that statements access. Samples are one-based.
+#[derive(Clone, Debug)]
+pub enum KeyDistribution {
+    Uniform(distributions::Uniform<usize>),
+    Zipfian(zipf::ZipfDistribution),
+    /// Zipfian over recency, so the most recently inserted users are the most popular. A user may
+    /// be picked before its insert has executed, in which case connections read it as empty.
+    Latest {
+        zipf: zipf::ZipfDistribution,
+        next_user_id: Arc<AtomicU32>,
+    },
+    /// A `hot_op_fraction` of accesses go to the first `hot_set_fraction` of users and the rest
+    /// go to the remainder, uniformly within each set.
+    Hotspot {
+        num_rows: usize,
+        hot_set_fraction: f64,
+        hot_op_fraction: f64,
+    },
+}
 
-pub fn uniform_generator(
-    num_rows: u32,
-    field_size: usize,
-    select_mix: f64,
-    num_statements_per_transaction: usize,
-) -> YCSBUniformGenerator {
-    YCSBGenerator::new(
-        field_size,
-        select_mix,
-        num_statements_per_transaction,
-        distributions::Uniform::new(1, num_rows as usize + 1),
-    )
+impl KeyDistribution {
+    /// Builds a distribution from the name used on the command line. `skew` is the Zipfian
+    /// exponent for `zipfian` and `latest`, and the fraction of accesses that go to the hottest
+    /// 20% of users for `hotspot`. `next_user_id` must be the counter shared with the generators'
+    /// inserts.
+    pub fn from_name(
+        name: &str,
+        num_rows: u32,
+        skew: f64,
+        next_user_id: &Arc<AtomicU32>,
+    ) -> Option<KeyDistribution> {
+        match name {
+            "uniform" => Some(KeyDistribution::Uniform(distributions::Uniform::new(
+                1,
+                num_rows as usize + 1,
+            ))),
+            "zipfian" => Some(KeyDistribution::Zipfian(
+                zipf::ZipfDistribution::new(num_rows as usize, skew).ok()?,
+            )),
+            "latest" => Some(KeyDistribution::Latest {
+                zipf: zipf::ZipfDistribution::new(num_rows as usize, skew).ok()?,
+                next_user_id: Arc::clone(next_user_id),
+            }),
+            "hotspot" if (0.0..=1.0).contains(&skew) => Some(KeyDistribution::Hotspot {
+                num_rows: num_rows as usize,
+                hot_set_fraction: 0.2,
+                hot_op_fraction: skew,
+            }),
+            _ => None,
+        }
+    }
 }
 
-pub fn zipf_generator(
+impl distributions::Distribution<usize> for KeyDistribution {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        match self {
+            KeyDistribution::Uniform(uniform) => uniform.sample(rng),
+            KeyDistribution::Zipfian(zipf) => zipf.sample(rng),
+            KeyDistribution::Latest { zipf, next_user_id } => {
+                next_user_id.load(Ordering::Relaxed) as usize + 1 - zipf.sample(rng)
+            }
+            KeyDistribution::Hotspot {
+                num_rows,
+                hot_set_fraction,
+                hot_op_fraction,
+            } => {
+                let hot_set_size = ((*num_rows as f64 * hot_set_fraction) as usize).max(1);
+
+                if hot_set_size == *num_rows || rng.gen::<f64>() < *hot_op_fraction {
+                    rng.gen_range(1, hot_set_size + 1)
+                } else {
+                    rng.gen_range(hot_set_size + 1, num_rows + 1)
+                }
+            }
+        }
+    }
+}
+
+pub fn generator(
     num_rows: u32,
     field_size: usize,
     select_mix: f64,
     num_statements_per_transaction: usize,
-    skew: f64,
-) -> YCSBZipfGenerator {
-    assert!(skew > 0.0);
+    distribution: KeyDistribution,
+) -> YCSBGenerator<KeyDistribution> {
     YCSBGenerator::new(
+        num_rows,
         field_size,
         select_mix,
         num_statements_per_transaction,
-        zipf::ZipfDistribution::new(num_rows as usize, skew).unwrap(),
+        distribution,
     )
 }
 
@@ -156,28 +547,54 @@ where
 {
     type Item = YCSBProcedure;
 
-    fn next(&self) -> YCSBProcedure {
-        let mut rng = thread_rng();
-
+    fn next(&self, rng: &mut StdRng) -> YCSBProcedure {
         YCSBProcedure::new(
             (0..self.num_statements_per_transaction)
                 .map(|_| {
-                    let transaction_type = rng.gen::<f64>();
+                    let statement_type = rng.gen::<f64>();
                     let field = rng.gen_range(0, NUM_FIELDS);
-                    let user_id = (self.distribution.sample(&mut rng) - 1) as u32;
-
-                    if transaction_type < self.select_mix {
-                        YCSBStatement::SelectUser { field, user_id }
-                    } else {
-                        let data = rng
-                            .sample_iter(&Alphanumeric)
-                            .take(self.field_size)
-                            .collect();
-                        YCSBStatement::UpdateUser {
+                    let user_id = (self.distribution.sample(rng) - 1) as u32;
+
+                    let mut threshold = self.mix.read;
+                    if statement_type < threshold {
+                        return YCSBStatement::SelectUser { field, user_id };
+                    }
+
+                    threshold += self.mix.scan;
+                    if statement_type < threshold {
+                        return YCSBStatement::ScanUsers {
                             field,
-                            data,
+                            start_user_id: user_id,
+                            end_user_id: user_id + rng.gen_range(1, MAX_SCAN_LENGTH + 1),
+                        };
+                    }
+
+                    threshold += self.mix.insert;
+                    if statement_type < threshold {
+                        return YCSBStatement::InsertUser {
+                            user_id: self.next_user_id.fetch_add(1, Ordering::Relaxed),
+                            fields: (0..NUM_FIELDS).map(|_| self.random_field(rng)).collect(),
+                        };
+                    }
+
+                    threshold += self.mix.delete;
+                    if statement_type < threshold {
+                        return YCSBStatement::DeleteUser { user_id };
+                    }
+
+                    threshold += self.mix.read_modify_write;
+                    if statement_type < threshold {
+                        return YCSBStatement::ReadModifyWriteUser {
+                            field,
+                            data: self.random_field(rng),
                             user_id,
-                        }
+                        };
+                    }
+
+                    YCSBStatement::UpdateUser {
+                        field,
+                        data: self.random_field(rng),
+                        user_id,
                     }
                 })
                 .collect(),
@@ -185,13 +602,17 @@ where
     }
 }
 
-pub fn dibs(optimization: OptimizationLevel) -> Dibs {
-    let filters = match optimization {
-        OptimizationLevel::Filtered => &[Some(0)],
-        _ => &[None],
-    };
+/// Returns the filter column of the YCSB table.
+pub fn filters(optimization: OptimizationLevel) -> Vec<Option<usize>> {
+    match optimization {
+        OptimizationLevel::Filtered => vec![Some(0)],
+        _ => vec![None],
+    }
+}
 
-    let templates = (0..NUM_FIELDS)
+/// Returns the request templates of the YCSB statements.
+pub fn templates() -> Vec<RequestTemplate> {
+    (0..NUM_FIELDS)
         .map(|field| {
             // (0..num_fields) Get user.
             RequestTemplate::new(
@@ -210,13 +631,55 @@ pub fn dibs(optimization: OptimizationLevel) -> Dibs {
                 Predicate::comparison(ComparisonOperator::Eq, 0, 0),
             )
         }))
-        .collect::<Vec<_>>();
+        .chain((0..NUM_FIELDS).map(|field| {
+            // (2*num_fields..3*num_fields) Read-modify-write user.
+            RequestTemplate::new(
+                0,
+                [field].iter().cloned().collect(),
+                [field].iter().cloned().collect(),
+                Predicate::comparison(ComparisonOperator::Eq, 0, 0),
+            )
+        }))
+        .chain(std::iter::once(
+            // (3*num_fields) Insert user.
+            RequestTemplate::new(
+                0,
+                FnvHashSet::default(),
+                (0..NUM_FIELDS).collect(),
+                Predicate::comparison(ComparisonOperator::Eq, 0, 0),
+            ),
+        ))
+        .chain((0..NUM_FIELDS).map(|field| {
+            // (3*num_fields+1..4*num_fields+1) Scan users.
+            RequestTemplate::new(
+                0,
+                [field].iter().cloned().collect(),
+                FnvHashSet::default(),
+                Predicate::conjunction(vec![
+                    Predicate::comparison(ComparisonOperator::Ge, 0, 0),
+                    Predicate::comparison(ComparisonOperator::Lt, 0, 1),
+                ]),
+            )
+        }))
+        .chain(std::iter::once(
+            // (4*num_fields+1) Delete user.
+            RequestTemplate::new(
+                0,
+                FnvHashSet::default(),
+                (0..NUM_FIELDS).collect(),
+                Predicate::comparison(ComparisonOperator::Eq, 0, 0),
+            ),
+        ))
+        .collect()
+}
 
+pub fn dibs(optimization: OptimizationLevel) -> Dibs {
     Dibs::new(
-        filters,
-        &templates,
+        &filters(optimization),
+        &templates(),
         optimization,
-        usize::max_value(),
+        None,
+        None,
         Duration::from_secs(60),
     )
 }
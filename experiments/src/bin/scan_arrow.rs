@@ -1,10 +1,15 @@
 use clap::{App, Arg};
 use dibs::OptimizationLevel;
 use dibs_experiments::benchmarks::scan;
-use dibs_experiments::benchmarks::scan::ScanGenerator;
+use dibs_experiments::benchmarks::scan::{ScanConfig, ScanGenerator};
+use dibs_experiments::breakdown::LatencyBreakdown;
+use dibs_experiments::graph::GraphExport;
+use dibs_experiments::placement::Placement;
 use dibs_experiments::runner;
+use dibs_experiments::runner::Phases;
+use dibs_experiments::seed::Seed;
 use dibs_experiments::systems::arrow::{ArrowScanConnection, ArrowScanDatabase};
-use dibs_experiments::worker::{StandardWorker, Worker};
+use dibs_experiments::worker::{Arrivals, RetryPolicy, StandardWorker, Worker};
 use std::str::FromStr;
 use std::sync::Arc;
 
@@ -22,31 +27,144 @@ fn main() {
         )
         .arg(Arg::with_name("blowup_limit").required(true))
         .arg(Arg::with_name("num_workers").required(true))
+        .arg(
+            Arg::with_name("max_range")
+                .long("max-range")
+                .takes_value(true)
+                .help("Draws the width of each range between range and this, defaults to range"),
+        )
+        .arg(
+            Arg::with_name("sample_conflicts")
+                .long("sample-conflicts")
+                .takes_value(true)
+                .help("Samples this many conflicts and prints a contention report to stderr"),
+        )
+        .arg(
+            Arg::with_name("filter_report")
+                .long("filter-report")
+                .help("Prints how often each template's requests were partitioned to stderr"),
+        )
+        .arg(
+            Arg::with_name("interval_pruning")
+                .long("interval-pruning")
+                .help("Skips solving for requests that bound a column to disjoint ranges"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .takes_value(true)
+                .help("Writes detailed results as JSON, or as CSV if the path ends in .csv"),
+        )
+        .args(&Phases::args())
+        .args(&Placement::args())
+        .args(&RetryPolicy::args())
+        .args(&Seed::args())
+        .args(&GraphExport::args())
+        .args(&LatencyBreakdown::args())
+        .args(&Arrivals::args())
         .get_matches();
 
     let num_rows = u32::from_str(matches.value_of("num_rows").unwrap()).unwrap();
     let select_mix = f64::from_str(matches.value_of("select_mix").unwrap()).unwrap();
     let range = u8::from_str(matches.value_of("range").unwrap()).unwrap();
     let num_conjuncts = usize::from_str(matches.value_of("num_conjuncts").unwrap()).unwrap();
+    let max_range = matches
+        .value_of("max_range")
+        .map_or(range, |max_range| u8::from_str(max_range).unwrap());
     let optimization =
         OptimizationLevel::from_str(matches.value_of("optimization").unwrap()).unwrap();
     let blowup_limit = usize::from_str(matches.value_of("blowup_limit").unwrap()).unwrap();
     let num_workers = usize::from_str(matches.value_of("num_workers").unwrap()).unwrap();
+    let phases = Phases::from_matches(&matches);
+    let placement = Placement::from_matches(&matches);
+    let seed = Seed::from_matches(&matches);
+    let graph_export = GraphExport::from_matches(&matches);
+    let latency_breakdown = LatencyBreakdown::from_matches(&matches);
+    let retry_policy = RetryPolicy::from_matches(&matches);
+    let arrivals = Arrivals::from_matches(&matches, num_workers);
+    let sample_conflicts = matches
+        .value_of("sample_conflicts")
+        .map(|capacity| usize::from_str(capacity).unwrap());
+
+    let config = ScanConfig {
+        max_range,
+        num_conjuncts,
+        ..ScanConfig::new(select_mix, range)
+    };
+
+    let mut dibs = scan::dibs(num_conjuncts, optimization, blowup_limit);
+    dibs.enable_seeded_jitter(seed.dibs());
+
+    if let Some(capacity) = sample_conflicts {
+        dibs.enable_conflict_sampling(capacity);
+    }
+
+    if matches.is_present("filter_report") {
+        dibs.enable_filter_stats();
+    }
 
-    let dibs = Arc::new(scan::dibs(num_conjuncts, optimization, blowup_limit));
+    graph_export.enable(&mut dibs);
+    latency_breakdown.enable(&mut dibs);
 
-    let db = Arc::new(ArrowScanDatabase::new(num_rows));
+    if matches.is_present("interval_pruning") {
+        dibs.enable_interval_pruning();
+    }
+
+    let dibs = Arc::new(dibs);
+
+    let db = Arc::new(placement.load(move || ArrowScanDatabase::new(num_rows, &mut seed.loader())));
 
     let mut workers: Vec<Box<dyn Worker + Send>> = vec![];
 
     for worker_id in 0..num_workers {
-        workers.push(Box::new(StandardWorker::new(
-            worker_id,
-            Some(Arc::clone(&dibs)),
-            ScanGenerator::new(select_mix, range),
-            ArrowScanConnection::new(Arc::clone(&db)),
-        )))
+        workers.push(Box::new(
+            StandardWorker::new(
+                worker_id,
+                Some(Arc::clone(&dibs)),
+                ScanGenerator::with_config(&config),
+                ArrowScanConnection::new(Arc::clone(&db)),
+            )
+            .with_retry_policy(retry_policy)
+            .with_rng(seed.worker(worker_id))
+            .with_arrivals(arrivals),
+        ))
     }
 
-    runner::run(workers);
+    let results = runner::run_with_parameters(
+        workers,
+        phases,
+        &placement,
+        &[
+            placement.parameter(),
+            latency_breakdown.parameter(),
+            seed.parameter(),
+            arrivals.parameter(),
+            (
+                "interval_pruning",
+                matches.is_present("interval_pruning").to_string(),
+            ),
+            ("select_mix", config.select_mix.to_string()),
+            (
+                "range",
+                format!("{}-{}", config.min_range, config.max_range),
+            ),
+            ("num_conjuncts", config.num_conjuncts.to_string()),
+            ("selectivity", format!("{:.6}", config.selectivity())),
+        ],
+        Some(&dibs),
+    );
+
+    if let Some(output) = matches.value_of("output") {
+        results.write(output).unwrap();
+    }
+
+    graph_export.write(&dibs).unwrap();
+
+    if let Some(report) = dibs.conflict_report(10) {
+        eprint!("{}", report);
+    }
+
+    if let Some(report) = dibs.filter_report() {
+        eprint!("{}", report);
+    }
 }
@@ -0,0 +1,248 @@
+use clap::{App, Arg};
+use dibs_experiments::benchmarks::synthetic;
+use dibs_experiments::benchmarks::synthetic::{PredicateShape, SyntheticConfig};
+use dibs_experiments::breakdown::LatencyBreakdown;
+use dibs_experiments::graph::GraphExport;
+use dibs_experiments::placement::Placement;
+use dibs_experiments::runner;
+use dibs_experiments::runner::Phases;
+use dibs_experiments::seed::Seed;
+use dibs_experiments::worker::{Arrivals, RetryPolicy, StandardWorker, Worker};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+fn main() {
+    let matches = App::new("Synthetic")
+        .arg(Arg::with_name("num_rows").required(true))
+        .arg(Arg::with_name("num_templates").required(true))
+        .arg(
+            Arg::with_name("shape")
+                .possible_values(&["point", "range", "disjunction", "multi_column"])
+                .required(true),
+        )
+        .arg(Arg::with_name("write_mix").required(true))
+        .arg(Arg::with_name("skew").required(true))
+        .arg(
+            Arg::with_name("optimization")
+                .possible_values(&["ungrouped", "grouped", "prepared", "filtered"])
+                .required(true),
+        )
+        .arg(Arg::with_name("num_workers").required(true))
+        .arg(
+            Arg::with_name("width")
+                .long("width")
+                .takes_value(true)
+                .help("The number of keys a range covers or a disjunction lists; defaults to 1"),
+        )
+        .arg(
+            Arg::with_name("num_fields")
+                .long("num-fields")
+                .takes_value(true)
+                .help("The number of fields the templates share; defaults to one per template"),
+        )
+        .arg(
+            Arg::with_name("num_statements_per_transaction")
+                .long("num-statements-per-transaction")
+                .takes_value(true)
+                .help("Defaults to 1"),
+        )
+        .arg(
+            Arg::with_name("distribution")
+                .long("distribution")
+                .possible_values(&["uniform", "zipfian", "latest", "hotspot"])
+                .takes_value(true)
+                .help("Defaults to uniform if skew is 0 and zipfian otherwise"),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .long("filter")
+                .help("Partitions requests on the key"),
+        )
+        .arg(
+            Arg::with_name("statement_time")
+                .long("statement-time")
+                .takes_value(true)
+                .help("Microseconds that each statement holds its requests for; defaults to 0"),
+        )
+        .arg(
+            Arg::with_name("shared_reads")
+                .long("shared-reads")
+                .help("Lets identical reads of read-only transactions share an in-flight entry"),
+        )
+        .arg(
+            Arg::with_name("max_active_transactions")
+                .long("max-active-transactions")
+                .takes_value(true)
+                .help("Holds back transactions beyond this many until one of them commits"),
+        )
+        .arg(
+            Arg::with_name("sample_conflicts")
+                .long("sample-conflicts")
+                .takes_value(true)
+                .help("Samples this many conflicts and prints a contention report to stderr"),
+        )
+        .arg(
+            Arg::with_name("filter_report")
+                .long("filter-report")
+                .help("Prints how often each template's requests were partitioned to stderr"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .takes_value(true)
+                .help("Writes detailed results as JSON, or as CSV if the path ends in .csv"),
+        )
+        .args(&Phases::args())
+        .args(&Placement::args())
+        .args(&RetryPolicy::args())
+        .args(&Seed::args())
+        .args(&GraphExport::args())
+        .args(&LatencyBreakdown::args())
+        .args(&Arrivals::args())
+        .get_matches();
+
+    let num_rows = u32::from_str(matches.value_of("num_rows").unwrap()).unwrap();
+    let num_templates = usize::from_str(matches.value_of("num_templates").unwrap()).unwrap();
+    let shape = PredicateShape::from_str(matches.value_of("shape").unwrap()).unwrap();
+    let write_mix = f64::from_str(matches.value_of("write_mix").unwrap()).unwrap();
+    let skew = f64::from_str(matches.value_of("skew").unwrap()).unwrap();
+    let optimization = matches.value_of("optimization").unwrap();
+    let num_workers = usize::from_str(matches.value_of("num_workers").unwrap()).unwrap();
+    let phases = Phases::from_matches(&matches);
+    let placement = Placement::from_matches(&matches);
+    let seed = Seed::from_matches(&matches);
+    let graph_export = GraphExport::from_matches(&matches);
+    let latency_breakdown = LatencyBreakdown::from_matches(&matches);
+    let retry_policy = RetryPolicy::from_matches(&matches);
+    let arrivals = Arrivals::from_matches(&matches, num_workers);
+    let sample_conflicts = matches
+        .value_of("sample_conflicts")
+        .map(|capacity| usize::from_str(capacity).unwrap());
+    let max_active_transactions = matches
+        .value_of("max_active_transactions")
+        .map(|max_active| usize::from_str(max_active).unwrap());
+
+    let mut config = SyntheticConfig::new(num_rows, num_templates, shape);
+    config.write_mix = write_mix;
+    config.skew = skew;
+    config.filter = matches.is_present("filter");
+    config.distribution = matches
+        .value_of("distribution")
+        .unwrap_or(if skew == 0.0 { "uniform" } else { "zipfian" })
+        .to_string();
+
+    if let Some(width) = matches.value_of("width") {
+        config.width = usize::from_str(width).unwrap();
+    }
+
+    if let Some(num_fields) = matches.value_of("num_fields") {
+        config.num_fields = usize::from_str(num_fields).unwrap();
+    }
+
+    if let Some(n) = matches.value_of("num_statements_per_transaction") {
+        config.num_statements_per_transaction = usize::from_str(n).unwrap();
+    }
+
+    let mut workload = synthetic::workload(&config);
+    workload.workers = num_workers;
+    workload.optimization = optimization.to_string();
+
+    if let Some(statement_time) = matches.value_of("statement_time") {
+        workload.statement_time = f64::from_str(statement_time).unwrap();
+    }
+
+    // Preparing the templates is most of the cost of building Dibs, and it grows with the number
+    // of templates, so report it alongside the throughput.
+    let prepare_start = Instant::now();
+    let mut dibs = workload.dibs().unwrap();
+    let prepare_time = prepare_start.elapsed();
+
+    dibs.enable_seeded_jitter(seed.dibs());
+
+    let generator = workload.generator().unwrap();
+
+    if let Some(capacity) = sample_conflicts {
+        dibs.enable_conflict_sampling(capacity);
+    }
+
+    if matches.is_present("filter_report") {
+        dibs.enable_filter_stats();
+    }
+
+    graph_export.enable(&mut dibs);
+    latency_breakdown.enable(&mut dibs);
+
+    if matches.is_present("shared_reads") {
+        dibs.enable_shared_reads();
+    }
+
+    if let Some(max_active) = max_active_transactions {
+        dibs.enable_admission_control(max_active);
+    }
+
+    let dibs = Arc::new(dibs);
+
+    let mut workers: Vec<Box<dyn Worker + Send>> = vec![];
+
+    for worker_id in 0..num_workers {
+        workers.push(Box::new(
+            StandardWorker::new(
+                worker_id,
+                Some(Arc::clone(&dibs)),
+                generator.clone(),
+                workload.connection(),
+            )
+            .with_retry_policy(retry_policy)
+            .with_rng(seed.worker(worker_id))
+            .with_arrivals(arrivals),
+        ))
+    }
+
+    let results = runner::run_with_parameters(
+        workers,
+        phases,
+        &placement,
+        &[
+            placement.parameter(),
+            latency_breakdown.parameter(),
+            seed.parameter(),
+            arrivals.parameter(),
+            ("shape", matches.value_of("shape").unwrap().to_string()),
+            ("num_templates", num_templates.to_string()),
+            ("width", config.width.to_string()),
+            ("num_fields", config.num_fields.to_string()),
+            ("write_mix", write_mix.to_string()),
+            ("distribution", config.distribution.clone()),
+            ("skew", skew.to_string()),
+            ("filter", config.filter.to_string()),
+            ("optimization", optimization.to_string()),
+            (
+                "shared_reads",
+                matches.is_present("shared_reads").to_string(),
+            ),
+            (
+                "max_active_transactions",
+                max_active_transactions
+                    .map_or("off".to_string(), |max_active| max_active.to_string()),
+            ),
+            ("statement_time", workload.statement_time.to_string()),
+            ("prepare_time", prepare_time.as_secs_f64().to_string()),
+        ],
+        Some(&dibs),
+    );
+
+    if let Some(output) = matches.value_of("output") {
+        results.write(output).unwrap();
+    }
+
+    graph_export.write(&dibs).unwrap();
+
+    if let Some(report) = dibs.conflict_report(10) {
+        eprint!("{}", report);
+    }
+
+    if let Some(report) = dibs.filter_report() {
+        eprint!("{}", report);
+    }
+}
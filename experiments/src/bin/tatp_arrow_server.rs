@@ -0,0 +1,84 @@
+use clap::{App, Arg};
+use dibs::OptimizationLevel;
+use dibs_experiments::benchmarks::tatp;
+use dibs_experiments::benchmarks::tatp::TATPProcedure;
+use dibs_experiments::placement::Placement;
+use dibs_experiments::seed::Seed;
+use dibs_experiments::server::Server;
+use dibs_experiments::systems::arrow::wal::Wal;
+use dibs_experiments::systems::arrow::{ArrowTATPConnection, ArrowTATPDatabase};
+use dibs_experiments::worker::RetryPolicy;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+fn main() {
+    let matches = App::new("TATP on Arrow, served over TCP")
+        .arg(Arg::with_name("num_rows").required(true))
+        .arg(
+            Arg::with_name("optimization")
+                .possible_values(&["ungrouped", "grouped", "prepared", "filtered"])
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("listen")
+                .long("listen")
+                .takes_value(true)
+                .help("Address to accept clients on, defaults to 0.0.0.0:7878"),
+        )
+        .arg(
+            Arg::with_name("snapshot")
+                .long("snapshot")
+                .takes_value(true)
+                .help("Loads the database from this directory, or saves it there if it is missing"),
+        )
+        .arg(
+            Arg::with_name("wal")
+                .long("wal")
+                .takes_value(true)
+                .help("Logs changes to this file, syncing it as transactions end"),
+        )
+        .args(&Placement::args())
+        .args(&RetryPolicy::args())
+        .args(&Seed::args())
+        .get_matches();
+
+    let num_rows = u32::from_str(matches.value_of("num_rows").unwrap()).unwrap();
+    let optimization =
+        OptimizationLevel::from_str(matches.value_of("optimization").unwrap()).unwrap();
+    let listen = matches.value_of("listen").unwrap_or("0.0.0.0:7878");
+    let placement = Placement::from_matches(&matches);
+    let seed = Seed::from_matches(&matches);
+    let retry_policy = RetryPolicy::from_matches(&matches);
+
+    let mut dibs = tatp::dibs(optimization);
+    dibs.enable_seeded_jitter(seed.dibs());
+    let dibs = Arc::new(dibs);
+
+    let snapshot = matches.value_of("snapshot").map(PathBuf::from);
+
+    let db = Arc::new(placement.load(move || {
+        let mut rng = seed.loader();
+
+        match snapshot {
+            Some(path) => ArrowTATPDatabase::load_or_create(&path, num_rows, &mut rng).unwrap(),
+            None => ArrowTATPDatabase::new(num_rows, &mut rng),
+        }
+    }));
+
+    let wal = matches
+        .value_of("wal")
+        .map(|path| Arc::new(Wal::create(Path::new(path)).unwrap()));
+
+    let listener = TcpListener::bind(listen).unwrap();
+    eprintln!("Listening on {}", listener.local_addr().unwrap());
+
+    Server::<TATPProcedure, _, _>::new(Some(dibs), move || {
+        ArrowTATPConnection::new(Arc::clone(&db)).with_wal(wal.clone())
+    })
+    .with_retry_policy(retry_policy)
+    .with_seed(seed)
+    .serve(listener)
+    .unwrap();
+}
@@ -0,0 +1,178 @@
+use clap::{App, Arg};
+use dibs::OptimizationLevel;
+use dibs_experiments::benchmarks::tatp;
+use dibs_experiments::benchmarks::tatp::{TATPConfig, TATPGenerator};
+use dibs_experiments::consistency::Consistency;
+use dibs_experiments::placement::Placement;
+use dibs_experiments::runner::Phases;
+use dibs_experiments::seed::Seed;
+use dibs_experiments::systems::pool::{Pool, PooledConnection};
+use dibs_experiments::systems::postgres::{IsolationMechanism, PostgresTATPConnection};
+use dibs_experiments::systems::IsolationLevel;
+use dibs_experiments::trace::Trace;
+use dibs_experiments::worker::{Arrivals, RetryPolicy, StandardWorker, Worker};
+use dibs_experiments::{runner, systems};
+use std::str::FromStr;
+use std::sync::Arc;
+
+fn main() {
+    let matches = App::new("TATP on Postgres")
+        .arg(Arg::with_name("num_rows").required(true))
+        .arg(
+            Arg::with_name("optimization")
+                .possible_values(&["ungrouped", "grouped", "prepared", "filtered"])
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("isolation")
+                .possible_values(&["PostgresReadCommitted", "DibsSerializable"])
+                .required(true),
+        )
+        .arg(Arg::with_name("num_workers").required(true))
+        .arg(
+            Arg::with_name("population")
+                .long("population")
+                .takes_value(true)
+                .help("Number of subscribers accessed, defaults to num_rows"),
+        )
+        .arg(
+            Arg::with_name("uniform")
+                .long("uniform")
+                .help("Picks subscribers uniformly instead of non-uniformly"),
+        )
+        .arg(
+            Arg::with_name("mix")
+                .long("mix")
+                .takes_value(true)
+                .help("Seven comma-separated transaction weights, defaults to 35,10,35,2,14,2,2"),
+        )
+        .arg(
+            Arg::with_name("postgres")
+                .long("postgres")
+                .takes_value(true)
+                .help("Connection string, defaults to host=localhost user=dibs dbname=tatp"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .takes_value(true)
+                .help("Writes detailed results as JSON, or as CSV if the path ends in .csv"),
+        )
+        .args(&Phases::args())
+        .args(&Placement::args())
+        .args(&RetryPolicy::args())
+        .args(&Seed::args())
+        .args(&Consistency::args())
+        .args(&Trace::args())
+        .args(&Arrivals::args())
+        .args(&systems::pool::args())
+        .args(&IsolationLevel::args())
+        .get_matches();
+
+    let num_rows = u32::from_str(matches.value_of("num_rows").unwrap()).unwrap();
+    let optimization =
+        OptimizationLevel::from_str(matches.value_of("optimization").unwrap()).unwrap();
+    let isolation = IsolationMechanism::from_str(matches.value_of("isolation").unwrap()).unwrap();
+    let num_workers = usize::from_str(matches.value_of("num_workers").unwrap()).unwrap();
+    let params = matches
+        .value_of("postgres")
+        .unwrap_or("host=localhost user=dibs dbname=tatp")
+        .to_string();
+    let phases = Phases::from_matches(&matches);
+    let placement = Placement::from_matches(&matches);
+    let seed = Seed::from_matches(&matches);
+    let retry_policy = RetryPolicy::from_matches(&matches);
+    let trace = Trace::from_matches(&matches);
+    let arrivals = Arrivals::from_matches(&matches, num_workers);
+    let pool_size = systems::pool::size_from_matches(&matches, num_workers);
+    let isolation_level = IsolationLevel::from_matches(&matches, IsolationLevel::ReadCommitted);
+
+    let mut config = TATPConfig::new(num_rows);
+
+    if let Some(population) = matches.value_of("population") {
+        config.population = u32::from_str(population).unwrap();
+        assert!(config.population > 0 && config.population <= num_rows);
+    }
+
+    config.non_uniform = !matches.is_present("uniform");
+
+    if let Some(mix) = matches.value_of("mix") {
+        config.mix = TATPConfig::parse_mix(mix).expect("invalid transaction mix");
+    }
+
+    let mut dibs = tatp::dibs(optimization);
+    dibs.enable_seeded_jitter(seed.dibs());
+    let dibs = Arc::new(dibs);
+
+    placement.load({
+        let params = params.clone();
+        move || systems::postgres::load_tatp(&params, num_rows, seed.loader())
+    });
+
+    let pool = Arc::new(Pool::new(pool_size, {
+        let params = params.clone();
+        move || PostgresTATPConnection::new(&params).with_isolation_level(isolation_level)
+    }));
+
+    let mut workers: Vec<Box<dyn Worker + Send>> = vec![];
+
+    for worker_id in 0..num_workers {
+        let dibs = match isolation {
+            IsolationMechanism::DibsSerializable => Some(Arc::clone(&dibs)),
+            IsolationMechanism::PostgresReadCommitted => None,
+        };
+
+        workers.push(Box::new(
+            StandardWorker::new(
+                worker_id,
+                dibs,
+                trace.wrap(worker_id, TATPGenerator::with_config(&config)),
+                PooledConnection::new(Arc::clone(&pool)),
+            )
+            .with_retry_policy(retry_policy)
+            .with_rng(seed.worker(worker_id))
+            .with_arrivals(arrivals),
+        ));
+    }
+
+    let mut results = runner::run_with_parameters(
+        workers,
+        phases,
+        &placement,
+        &[
+            placement.parameter(),
+            seed.parameter(),
+            trace.parameter(),
+            arrivals.parameter(),
+            isolation_level.parameter(),
+            ("pool_size", pool_size.to_string()),
+            ("population", config.population.to_string()),
+            ("non_uniform", config.non_uniform.to_string()),
+            (
+                "mix",
+                config
+                    .mix
+                    .iter()
+                    .map(|weight| weight.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+        ],
+        match isolation {
+            IsolationMechanism::DibsSerializable => Some(&*dibs),
+            IsolationMechanism::PostgresReadCommitted => None,
+        },
+    );
+
+    trace.finish();
+
+    if Consistency::is_enabled(&matches) {
+        let consistency = PostgresTATPConnection::new(&params).check_consistency();
+        eprint!("{}", consistency);
+        results.consistency = Some(consistency);
+    }
+
+    if let Some(output) = matches.value_of("output") {
+        results.write(output).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+use clap::{App, Arg};
+use dibs_experiments::benchmarks::tatp::{TATPConfig, TATPGenerator};
+use dibs_experiments::coordinator::Coordination;
+use dibs_experiments::placement::Placement;
+use dibs_experiments::runner;
+use dibs_experiments::runner::Phases;
+use dibs_experiments::seed::Seed;
+use dibs_experiments::server::{Client, RemoteWorker};
+use dibs_experiments::systems::arrow::ArrowTATPConnection;
+use dibs_experiments::worker::Worker;
+use std::str::FromStr;
+
+fn main() {
+    let matches = App::new("TATP against a remote server")
+        .arg(
+            Arg::with_name("address")
+                .required(true)
+                .help("Address of a server started by tatp_arrow_server"),
+        )
+        .arg(Arg::with_name("num_rows").required(true))
+        .arg(Arg::with_name("num_workers").required(true))
+        .arg(
+            Arg::with_name("population")
+                .long("population")
+                .takes_value(true)
+                .help("Number of subscribers accessed, defaults to num_rows"),
+        )
+        .arg(
+            Arg::with_name("uniform")
+                .long("uniform")
+                .help("Picks subscribers uniformly instead of non-uniformly"),
+        )
+        .arg(
+            Arg::with_name("mix")
+                .long("mix")
+                .takes_value(true)
+                .help("Seven comma-separated transaction weights, defaults to 35,10,35,2,14,2,2"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .takes_value(true)
+                .help("Writes detailed results as JSON, or as CSV if the path ends in .csv"),
+        )
+        .args(&Phases::args())
+        .args(&Placement::args())
+        .args(&Seed::args())
+        .args(&Coordination::args())
+        .get_matches();
+
+    let address = matches.value_of("address").unwrap();
+    let num_rows = u32::from_str(matches.value_of("num_rows").unwrap()).unwrap();
+    let num_workers = usize::from_str(matches.value_of("num_workers").unwrap()).unwrap();
+    let phases = Phases::from_matches(&matches);
+    let placement = Placement::from_matches(&matches);
+    let seed = Seed::from_matches(&matches);
+    let coordination = Coordination::from_matches(&matches);
+
+    let mut config = TATPConfig::new(num_rows);
+
+    if let Some(population) = matches.value_of("population") {
+        config.population = u32::from_str(population).unwrap();
+        assert!(config.population > 0 && config.population <= num_rows);
+    }
+
+    config.non_uniform = !matches.is_present("uniform");
+
+    if let Some(mix) = matches.value_of("mix") {
+        config.mix = TATPConfig::parse_mix(mix).expect("invalid transaction mix");
+    }
+
+    let session = coordination.start(num_workers, phases, seed).unwrap();
+    let seed = session.seed;
+
+    let mut workers: Vec<Box<dyn Worker + Send>> = vec![];
+
+    for worker_id in 0..num_workers {
+        workers.push(Box::new(
+            RemoteWorker::<_, ArrowTATPConnection>::new(
+                TATPGenerator::with_config(&config),
+                Client::connect(address).unwrap(),
+            )
+            .with_rng(seed.worker(session.first_worker_id + worker_id)),
+        ));
+    }
+
+    let results = runner::run_with_parameters(
+        workers,
+        session.phases,
+        &placement,
+        &[
+            placement.parameter(),
+            seed.parameter(),
+            session.parameter(),
+            ("address", address.to_string()),
+            ("population", config.population.to_string()),
+            ("non_uniform", config.non_uniform.to_string()),
+            (
+                "mix",
+                config
+                    .mix
+                    .iter()
+                    .map(|weight| weight.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+        ],
+        None,
+    );
+
+    let results = match session.finish(results).unwrap() {
+        Some(results) => results,
+        None => return,
+    };
+
+    if let Some(output) = matches.value_of("output") {
+        results.write(output).unwrap();
+    }
+}
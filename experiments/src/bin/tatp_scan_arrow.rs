@@ -0,0 +1,204 @@
+use clap::{App, Arg};
+use dibs::{Dibs, OptimizationLevel};
+use dibs_experiments::benchmarks::composite::{CompositeConnection, CompositeGenerator};
+use dibs_experiments::benchmarks::scan::{ScanConfig, ScanGenerator};
+use dibs_experiments::benchmarks::tatp::{TATPConfig, TATPGenerator};
+use dibs_experiments::benchmarks::{scan, tatp};
+use dibs_experiments::placement::Placement;
+use dibs_experiments::runner;
+use dibs_experiments::runner::Phases;
+use dibs_experiments::seed::Seed;
+use dibs_experiments::systems::arrow::{ArrowTATPConnection, ArrowTATPDatabase};
+use dibs_experiments::worker::{Arrivals, RetryPolicy, StandardWorker, Worker};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn main() {
+    let matches = App::new("TATP with concurrent subscriber scans on Arrow")
+        .arg(Arg::with_name("num_rows").required(true))
+        .arg(
+            Arg::with_name("optimization")
+                .possible_values(&["ungrouped", "grouped", "prepared", "filtered"])
+                .required(true),
+        )
+        .arg(Arg::with_name("num_workers").required(true))
+        .arg(
+            Arg::with_name("num_scan_workers")
+                .long("scan-workers")
+                .takes_value(true)
+                .help("Workers that run scans instead of TATP transactions, defaults to 1"),
+        )
+        .arg(
+            Arg::with_name("scan_rate")
+                .long("scan-rate")
+                .takes_value(true)
+                .help("Scans per second across the scan workers, defaults to back to back"),
+        )
+        .arg(
+            Arg::with_name("scan_select_mix")
+                .long("scan-select-mix")
+                .takes_value(true)
+                .help("Fraction of scans that only read, the rest update locations, defaults to 1"),
+        )
+        .arg(
+            Arg::with_name("scan_range")
+                .long("scan-range")
+                .takes_value(true)
+                .help("Width of each byte2 range that scans restrict, defaults to 0"),
+        )
+        .arg(
+            Arg::with_name("scan_conjuncts")
+                .long("scan-conjuncts")
+                .takes_value(true)
+                .help("Byte2 columns that scans restrict, defaults to 0 for full scans"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .takes_value(true)
+                .help("Writes detailed results as JSON, or as CSV if the path ends in .csv"),
+        )
+        .args(&Phases::args())
+        .args(&Placement::args())
+        .args(&RetryPolicy::args())
+        .args(&Seed::args())
+        .get_matches();
+
+    let num_rows = u32::from_str(matches.value_of("num_rows").unwrap()).unwrap();
+    let optimization =
+        OptimizationLevel::from_str(matches.value_of("optimization").unwrap()).unwrap();
+    let num_workers = usize::from_str(matches.value_of("num_workers").unwrap()).unwrap();
+    let num_scan_workers = matches
+        .value_of("num_scan_workers")
+        .map_or(1, |num_scan_workers| {
+            usize::from_str(num_scan_workers).unwrap()
+        });
+    let phases = Phases::from_matches(&matches);
+    let placement = Placement::from_matches(&matches);
+    let seed = Seed::from_matches(&matches);
+    let retry_policy = RetryPolicy::from_matches(&matches);
+
+    let scan_arrivals = match matches.value_of("scan_rate") {
+        Some(rate) => Arrivals::Fixed {
+            rate: f64::from_str(rate).unwrap() / num_scan_workers as f64,
+            queue_capacity: 1,
+        },
+        None => Arrivals::ClosedLoop,
+    };
+
+    let scan_config = ScanConfig {
+        num_conjuncts: matches
+            .value_of("scan_conjuncts")
+            .map_or(0, |num_conjuncts| usize::from_str(num_conjuncts).unwrap()),
+        ..ScanConfig::new(
+            matches
+                .value_of("scan_select_mix")
+                .map_or(1.0, |select_mix| f64::from_str(select_mix).unwrap()),
+            matches
+                .value_of("scan_range")
+                .map_or(0, |range| u8::from_str(range).unwrap()),
+        )
+    };
+
+    let tatp_config = TATPConfig::new(num_rows);
+
+    // Scans lock the same subscriber table as TATP, so their templates follow TATP's on the same
+    // tables rather than on tables of their own as in a composite workload. Filtering partitions
+    // the subscriber table by s_id, which scans don't restrict.
+    let tatp_templates = tatp::templates();
+    let template_offsets = [0, tatp_templates.len()];
+
+    let mut dibs = Dibs::new(
+        &tatp::filters(optimization),
+        &tatp_templates
+            .into_iter()
+            .chain(scan::templates(scan_config.num_conjuncts))
+            .collect::<Vec<_>>(),
+        optimization,
+        None,
+        None,
+        Duration::from_secs(60),
+    );
+    dibs.enable_seeded_jitter(seed.dibs());
+    let dibs = Arc::new(dibs);
+
+    let db = Arc::new(placement.load(move || ArrowTATPDatabase::new(num_rows, &mut seed.loader())));
+
+    let mut workers: Vec<Box<dyn Worker + Send>> = vec![];
+
+    // Point workers draw every procedure from TATP and scan workers every procedure from the
+    // scans, each running it on its own view of the shared database.
+    for worker_id in 0..num_workers + num_scan_workers {
+        let is_scan_worker = worker_id >= num_workers;
+
+        workers.push(Box::new(
+            StandardWorker::new(
+                worker_id,
+                Some(Arc::clone(&dibs)),
+                CompositeGenerator::new(
+                    TATPGenerator::with_config(&tatp_config),
+                    ScanGenerator::with_config(&scan_config),
+                    if is_scan_worker { 0.0 } else { 1.0 },
+                    template_offsets,
+                ),
+                CompositeConnection::new(
+                    ArrowTATPConnection::new(Arc::clone(&db)),
+                    ArrowTATPConnection::new(Arc::clone(&db)),
+                ),
+            )
+            .with_retry_policy(retry_policy)
+            .with_rng(seed.worker(worker_id))
+            .with_arrivals(if is_scan_worker {
+                scan_arrivals
+            } else {
+                Arrivals::ClosedLoop
+            }),
+        ));
+    }
+
+    let results = runner::run_with_parameters(
+        workers,
+        phases,
+        &placement,
+        &[
+            placement.parameter(),
+            seed.parameter(),
+            ("num_scan_workers", num_scan_workers.to_string()),
+            ("scan_arrivals", scan_arrivals.parameter().1),
+            ("scan_select_mix", scan_config.select_mix.to_string()),
+            ("scan_range", scan_config.min_range.to_string()),
+            ("scan_conjuncts", scan_config.num_conjuncts.to_string()),
+        ],
+        Some(&dibs),
+    );
+
+    let is_scan = |name: &str| name.ends_with("_scan");
+
+    let point_commits = results
+        .latencies
+        .iter()
+        .filter(|(name, _)| !is_scan(name))
+        .map(|(_, histogram)| histogram.count())
+        .sum::<u64>();
+
+    println!(
+        "point_throughput={}",
+        (point_commits as f64 / results.duration.as_secs_f64()) as u64
+    );
+
+    for (name, histogram) in results.latencies.iter().filter(|(name, _)| is_scan(name)) {
+        println!(
+            "{} count={} p50_us={} p99_us={} max_us={}",
+            name,
+            histogram.count(),
+            histogram.percentile(0.5) / 1000,
+            histogram.percentile(0.99) / 1000,
+            histogram.max() / 1000
+        );
+    }
+
+    if let Some(output) = matches.value_of("output") {
+        results.write(output).unwrap();
+    }
+}
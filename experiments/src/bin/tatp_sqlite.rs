@@ -1,10 +1,17 @@
 use clap::{App, Arg};
 use dibs::OptimizationLevel;
 use dibs_experiments::benchmarks::tatp;
-use dibs_experiments::benchmarks::tatp::TATPGenerator;
-use dibs_experiments::systems::sqlite::SQLiteTATPConnection;
+use dibs_experiments::benchmarks::tatp::{TATPConfig, TATPGenerator};
+use dibs_experiments::consistency::Consistency;
+use dibs_experiments::placement::Placement;
+use dibs_experiments::runner::Phases;
+use dibs_experiments::seed::Seed;
+use dibs_experiments::systems::pool::{Pool, PooledConnection};
+use dibs_experiments::systems::sqlite::{IsolationMechanism, SQLiteTATPConnection};
+use dibs_experiments::systems::IsolationLevel;
+use dibs_experiments::trace::Trace;
 use dibs_experiments::worker::{
-    GroupCommitWorker, ReadOnlyGenerator, ReceivingGenerator, StandardWorker, Worker,
+    GroupCommitWorker, ReadOnlyGenerator, ReceivingGenerator, RetryPolicy, StandardWorker, Worker,
 };
 use dibs_experiments::{runner, systems};
 use std::str::FromStr;
@@ -20,6 +27,44 @@ fn main() {
                 .required(true),
         )
         .arg(Arg::with_name("num_workers").required(true))
+        .arg(
+            Arg::with_name("population")
+                .long("population")
+                .takes_value(true)
+                .help("Number of subscribers accessed, defaults to num_rows"),
+        )
+        .arg(
+            Arg::with_name("uniform")
+                .long("uniform")
+                .help("Picks subscribers uniformly instead of non-uniformly"),
+        )
+        .arg(
+            Arg::with_name("mix")
+                .long("mix")
+                .takes_value(true)
+                .help("Seven comma-separated transaction weights, defaults to 35,10,35,2,14,2,2"),
+        )
+        .arg(
+            Arg::with_name("isolation")
+                .long("isolation")
+                .possible_values(&["SQLiteSnapshot", "DibsSerializable"])
+                .takes_value(true)
+                .help("Whether readers acquire requests from dibs, defaults to SQLiteSnapshot"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .takes_value(true)
+                .help("Writes detailed results as JSON, or as CSV if the path ends in .csv"),
+        )
+        .args(&Phases::args())
+        .args(&Placement::args())
+        .args(&RetryPolicy::args())
+        .args(&Seed::args())
+        .args(&Consistency::args())
+        .args(&Trace::args())
+        .args(&systems::pool::args())
+        .args(&IsolationLevel::args())
         .get_matches();
 
     let num_rows = u32::from_str(matches.value_of("num_rows").unwrap()).unwrap();
@@ -28,32 +73,122 @@ fn main() {
     let optimization =
         OptimizationLevel::from_str(matches.value_of("optimization").unwrap()).unwrap();
     let num_workers = usize::from_str(matches.value_of("num_workers").unwrap()).unwrap();
+    let phases = Phases::from_matches(&matches);
+    let placement = Placement::from_matches(&matches);
+    let seed = Seed::from_matches(&matches);
+    let retry_policy = RetryPolicy::from_matches(&matches);
+    let trace = Trace::from_matches(&matches);
+    let pool_size = systems::pool::size_from_matches(&matches, num_workers);
+    let isolation = matches
+        .value_of("isolation")
+        .map_or(IsolationMechanism::SQLiteSnapshot, |isolation| {
+            IsolationMechanism::from_str(isolation).unwrap()
+        });
+    let isolation_level = IsolationLevel::from_matches(&matches, isolation.isolation_level());
 
-    let dibs = Arc::new(tatp::dibs(optimization));
+    let mut config = TATPConfig::new(num_rows);
 
-    systems::sqlite::load_tatp("tatp.sqlite", num_rows);
+    if let Some(population) = matches.value_of("population") {
+        config.population = u32::from_str(population).unwrap();
+        assert!(config.population > 0 && config.population <= num_rows);
+    }
+
+    config.non_uniform = !matches.is_present("uniform");
+
+    if let Some(mix) = matches.value_of("mix") {
+        config.mix = TATPConfig::parse_mix(mix).expect("invalid transaction mix");
+    }
+
+    let mut dibs = tatp::dibs(optimization);
+    dibs.enable_seeded_jitter(seed.dibs());
+    let dibs = Arc::new(dibs);
+
+    placement.load(move || systems::sqlite::load_tatp("tatp.sqlite", num_rows, seed.loader()));
+
+    let pool = Arc::new(Pool::new(pool_size, move || {
+        SQLiteTATPConnection::new("tatp.sqlite").with_isolation_level(isolation_level)
+    }));
 
     let (sender, receiver) = mpsc::sync_channel(0);
 
-    let mut workers: Vec<Box<dyn Worker + Send>> = vec![Box::new(GroupCommitWorker::new(
-        0,
-        Some(dibs),
-        ReceivingGenerator::new(TATPGenerator::new(num_rows), receiver),
-        SQLiteTATPConnection::new("tatp.sqlite"),
-        num_transactions_per_group,
-    ))];
+    let mut workers: Vec<Box<dyn Worker + Send>> = vec![Box::new(
+        GroupCommitWorker::new(
+            0,
+            Some(Arc::clone(&dibs)),
+            ReceivingGenerator::new(trace.wrap(0, TATPGenerator::with_config(&config)), receiver),
+            PooledConnection::new(Arc::clone(&pool)),
+            num_transactions_per_group,
+        )
+        .with_retry_policy(retry_policy)
+        .with_rng(seed.worker(0)),
+    )];
 
     for worker_id in 1..num_workers {
-        let generator: ReadOnlyGenerator<TATPGenerator, SQLiteTATPConnection> =
-            ReadOnlyGenerator::new(TATPGenerator::new(num_rows), sender.clone());
-
-        workers.push(Box::new(StandardWorker::new(
-            worker_id,
-            None,
-            generator,
-            SQLiteTATPConnection::new("tatp.sqlite"),
-        )))
+        let generator: ReadOnlyGenerator<_, PooledConnection<SQLiteTATPConnection>> =
+            ReadOnlyGenerator::new(
+                trace.wrap(worker_id, TATPGenerator::with_config(&config)),
+                sender.clone(),
+            );
+
+        let dibs = match isolation {
+            IsolationMechanism::DibsSerializable => Some(Arc::clone(&dibs)),
+            IsolationMechanism::SQLiteSnapshot => None,
+        };
+
+        workers.push(Box::new(
+            StandardWorker::new(
+                worker_id,
+                dibs,
+                generator,
+                PooledConnection::new(Arc::clone(&pool)),
+            )
+            .with_retry_policy(retry_policy)
+            .with_rng(seed.worker(worker_id)),
+        ))
+    }
+
+    let mut results = runner::run_with_parameters(
+        workers,
+        phases,
+        &placement,
+        &[
+            placement.parameter(),
+            seed.parameter(),
+            trace.parameter(),
+            (
+                "isolation",
+                match isolation {
+                    IsolationMechanism::SQLiteSnapshot => "SQLiteSnapshot",
+                    IsolationMechanism::DibsSerializable => "DibsSerializable",
+                }
+                .to_string(),
+            ),
+            isolation_level.parameter(),
+            ("pool_size", pool_size.to_string()),
+            ("population", config.population.to_string()),
+            ("non_uniform", config.non_uniform.to_string()),
+            (
+                "mix",
+                config
+                    .mix
+                    .iter()
+                    .map(|weight| weight.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+        ],
+        Some(&dibs),
+    );
+
+    trace.finish();
+
+    if Consistency::is_enabled(&matches) {
+        let consistency = SQLiteTATPConnection::new("tatp.sqlite").check_consistency();
+        eprint!("{}", consistency);
+        results.consistency = Some(consistency);
     }
 
-    runner::run(workers);
+    if let Some(output) = matches.value_of("output") {
+        results.write(output).unwrap();
+    }
 }
@@ -0,0 +1,136 @@
+use clap::{App, Arg};
+use dibs::OptimizationLevel;
+use dibs_experiments::benchmarks::composite::{CompositeConnection, CompositeGenerator, Part};
+use dibs_experiments::benchmarks::tatp::{TATPConfig, TATPGenerator};
+use dibs_experiments::benchmarks::ycsb::KeyDistribution;
+use dibs_experiments::benchmarks::{composite, tatp, ycsb};
+use dibs_experiments::placement::Placement;
+use dibs_experiments::runner;
+use dibs_experiments::runner::Phases;
+use dibs_experiments::seed::Seed;
+use dibs_experiments::systems::arrow::{
+    ArrowTATPConnection, ArrowTATPDatabase, ArrowYCSBConnection, ArrowYCSBDatabase,
+};
+use dibs_experiments::worker::{RetryPolicy, StandardWorker, Worker};
+use std::str::FromStr;
+use std::sync::atomic::AtomicU32;
+use std::sync::Arc;
+
+fn main() {
+    let matches = App::new("TATP and YCSB on Arrow")
+        .arg(Arg::with_name("tatp_num_rows").required(true))
+        .arg(Arg::with_name("ycsb_num_rows").required(true))
+        .arg(Arg::with_name("field_size").required(true))
+        .arg(Arg::with_name("select_mix").required(true))
+        .arg(Arg::with_name("num_statements_per_transaction").required(true))
+        .arg(Arg::with_name("skew").required(true))
+        .arg(
+            Arg::with_name("tatp_ratio")
+                .required(true)
+                .help("Fraction of transactions drawn from TATP, the rest are drawn from YCSB"),
+        )
+        .arg(
+            Arg::with_name("optimization")
+                .possible_values(&["ungrouped", "grouped", "prepared", "filtered"])
+                .required(true),
+        )
+        .arg(Arg::with_name("num_workers").required(true))
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .takes_value(true)
+                .help("Writes detailed results as JSON, or as CSV if the path ends in .csv"),
+        )
+        .args(&Phases::args())
+        .args(&Placement::args())
+        .args(&RetryPolicy::args())
+        .args(&Seed::args())
+        .get_matches();
+
+    let tatp_num_rows = u32::from_str(matches.value_of("tatp_num_rows").unwrap()).unwrap();
+    let ycsb_num_rows = u32::from_str(matches.value_of("ycsb_num_rows").unwrap()).unwrap();
+    let field_size = usize::from_str(matches.value_of("field_size").unwrap()).unwrap();
+    let select_mix = f64::from_str(matches.value_of("select_mix").unwrap()).unwrap();
+    let num_statements_per_transaction =
+        usize::from_str(matches.value_of("num_statements_per_transaction").unwrap()).unwrap();
+    let skew = f64::from_str(matches.value_of("skew").unwrap()).unwrap();
+    let tatp_ratio = f64::from_str(matches.value_of("tatp_ratio").unwrap()).unwrap();
+    let optimization =
+        OptimizationLevel::from_str(matches.value_of("optimization").unwrap()).unwrap();
+    let num_workers = usize::from_str(matches.value_of("num_workers").unwrap()).unwrap();
+    let phases = Phases::from_matches(&matches);
+    let placement = Placement::from_matches(&matches);
+    let seed = Seed::from_matches(&matches);
+    let retry_policy = RetryPolicy::from_matches(&matches);
+
+    let config = TATPConfig::new(tatp_num_rows);
+    let next_user_id = Arc::new(AtomicU32::new(ycsb_num_rows));
+    let distribution_name = if skew == 0.0 { "uniform" } else { "zipfian" };
+    let distribution =
+        KeyDistribution::from_name(distribution_name, ycsb_num_rows, skew, &next_user_id).unwrap();
+
+    let (mut dibs, template_offsets) = composite::dibs(
+        vec![
+            Part::new(tatp::filters(optimization), tatp::templates()),
+            Part::new(ycsb::filters(optimization), ycsb::templates()),
+        ],
+        optimization,
+    );
+    dibs.enable_seeded_jitter(seed.dibs());
+
+    let dibs = Arc::new(dibs);
+
+    let (tatp_db, ycsb_db) = placement.load(move || {
+        let mut rng = seed.loader();
+
+        (
+            Arc::new(ArrowTATPDatabase::new(tatp_num_rows, &mut rng)),
+            Arc::new(ArrowYCSBDatabase::new(ycsb_num_rows, field_size, &mut rng)),
+        )
+    });
+
+    let mut workers: Vec<Box<dyn Worker + Send>> = vec![];
+
+    for worker_id in 0..num_workers {
+        workers.push(Box::new(
+            StandardWorker::new(
+                worker_id,
+                Some(Arc::clone(&dibs)),
+                CompositeGenerator::new(
+                    TATPGenerator::with_config(&config),
+                    ycsb::generator(
+                        ycsb_num_rows,
+                        field_size,
+                        select_mix,
+                        num_statements_per_transaction,
+                        distribution.clone(),
+                    ),
+                    tatp_ratio,
+                    [template_offsets[0], template_offsets[1]],
+                ),
+                CompositeConnection::new(
+                    ArrowTATPConnection::new(Arc::clone(&tatp_db)),
+                    ArrowYCSBConnection::new(Arc::clone(&ycsb_db)),
+                ),
+            )
+            .with_retry_policy(retry_policy)
+            .with_rng(seed.worker(worker_id)),
+        ));
+    }
+
+    let results = runner::run_with_parameters(
+        workers,
+        phases,
+        &placement,
+        &[
+            placement.parameter(),
+            seed.parameter(),
+            ("tatp_ratio", tatp_ratio.to_string()),
+        ],
+        Some(&dibs),
+    );
+
+    if let Some(output) = matches.value_of("output") {
+        results.write(output).unwrap();
+    }
+}
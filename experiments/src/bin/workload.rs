@@ -0,0 +1,130 @@
+use clap::{App, Arg};
+use dibs_experiments::breakdown::LatencyBreakdown;
+use dibs_experiments::graph::GraphExport;
+use dibs_experiments::placement::Placement;
+use dibs_experiments::runner;
+use dibs_experiments::seed::Seed;
+use dibs_experiments::worker::{Arrivals, RetryPolicy, StandardWorker, Worker};
+use dibs_experiments::workload::Workload;
+use std::str::FromStr;
+use std::sync::Arc;
+
+fn main() {
+    let matches = App::new("Workload from a file")
+        .arg(
+            Arg::with_name("workload")
+                .required(true)
+                .help("A TOML file describing the tables, templates and transaction mix"),
+        )
+        .arg(
+            Arg::with_name("sample_conflicts")
+                .long("sample-conflicts")
+                .takes_value(true)
+                .help("Samples this many conflicts and prints a contention report to stderr"),
+        )
+        .arg(
+            Arg::with_name("filter_report")
+                .long("filter-report")
+                .help("Prints how often each template's requests were partitioned to stderr"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .takes_value(true)
+                .help("Writes detailed results as JSON, or as CSV if the path ends in .csv"),
+        )
+        .args(&Placement::args())
+        .args(&RetryPolicy::args())
+        .args(&Seed::args())
+        .args(&GraphExport::args())
+        .args(&LatencyBreakdown::args())
+        .args(&Arrivals::args())
+        .get_matches();
+
+    let path = matches.value_of("workload").unwrap();
+
+    let workload = Workload::from_file(path).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        std::process::exit(1);
+    });
+
+    let phases = workload.phases();
+    let placement = Placement::from_matches(&matches);
+    let seed = Seed::from_matches(&matches);
+    let graph_export = GraphExport::from_matches(&matches);
+    let latency_breakdown = LatencyBreakdown::from_matches(&matches);
+    let retry_policy = RetryPolicy::from_matches(&matches);
+    let arrivals = Arrivals::from_matches(&matches, workload.workers);
+    let sample_conflicts = matches
+        .value_of("sample_conflicts")
+        .map(|capacity| usize::from_str(capacity).unwrap());
+
+    let (mut dibs, generator) = match (workload.dibs(), workload.generator()) {
+        (Ok(dibs), Ok(generator)) => (dibs, generator),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+
+    dibs.enable_seeded_jitter(seed.dibs());
+
+    if let Some(capacity) = sample_conflicts {
+        dibs.enable_conflict_sampling(capacity);
+    }
+
+    if matches.is_present("filter_report") {
+        dibs.enable_filter_stats();
+    }
+
+    graph_export.enable(&mut dibs);
+    latency_breakdown.enable(&mut dibs);
+
+    let dibs = Arc::new(dibs);
+
+    let mut workers: Vec<Box<dyn Worker + Send>> = vec![];
+
+    for worker_id in 0..workload.workers {
+        workers.push(Box::new(
+            StandardWorker::new(
+                worker_id,
+                Some(Arc::clone(&dibs)),
+                generator.clone(),
+                workload.connection(),
+            )
+            .with_retry_policy(retry_policy)
+            .with_rng(seed.worker(worker_id))
+            .with_arrivals(arrivals),
+        ))
+    }
+
+    let results = runner::run_with_parameters(
+        workers,
+        phases,
+        &placement,
+        &[
+            placement.parameter(),
+            latency_breakdown.parameter(),
+            seed.parameter(),
+            arrivals.parameter(),
+            ("workload", path.to_string()),
+            ("optimization", workload.optimization.clone()),
+            ("statement_time", workload.statement_time.to_string()),
+        ],
+        Some(&dibs),
+    );
+
+    if let Some(output) = matches.value_of("output") {
+        results.write(output).unwrap();
+    }
+
+    graph_export.write(&dibs).unwrap();
+
+    if let Some(report) = dibs.conflict_report(10) {
+        eprint!("{}", report);
+    }
+
+    if let Some(report) = dibs.filter_report() {
+        eprint!("{}", report);
+    }
+}
@@ -1,10 +1,23 @@
 use clap::{App, Arg};
 use dibs::OptimizationLevel;
+use dibs_experiments::adaptive::Adaptive;
 use dibs_experiments::benchmarks::ycsb;
+use dibs_experiments::benchmarks::ycsb::{KeyDistribution, YCSBMix};
+use dibs_experiments::breakdown::LatencyBreakdown;
+use dibs_experiments::committer::{Committer, PipelinedWorker};
+use dibs_experiments::consistency::Consistency;
+use dibs_experiments::graph::GraphExport;
+use dibs_experiments::placement::Placement;
 use dibs_experiments::runner;
+use dibs_experiments::runner::Phases;
+use dibs_experiments::seed::Seed;
+use dibs_experiments::systems::arrow::checkpoint::Verification;
 use dibs_experiments::systems::arrow::{ArrowYCSBConnection, ArrowYCSBDatabase};
-use dibs_experiments::worker::{StandardWorker, Worker};
+use dibs_experiments::trace::Trace;
+use dibs_experiments::worker::{Arrivals, RetryPolicy, StandardWorker, Worker};
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::atomic::AtomicU32;
 use std::sync::Arc;
 
 fn main() {
@@ -20,6 +33,101 @@ fn main() {
                 .required(true),
         )
         .arg(Arg::with_name("num_workers").required(true))
+        .arg(
+            Arg::with_name("distribution")
+                .long("distribution")
+                .possible_values(&["uniform", "zipfian", "latest", "hotspot"])
+                .takes_value(true)
+                .help("Defaults to uniform if skew is 0 and zipfian otherwise"),
+        )
+        .arg(
+            Arg::with_name("workload")
+                .long("workload")
+                .possible_values(&["a", "b", "c", "d", "e", "f"])
+                .takes_value(true)
+                .help("Runs a standard YCSB workload mix instead of select_mix"),
+        )
+        .arg(
+            Arg::with_name("delete_mix")
+                .long("delete-mix")
+                .takes_value(true)
+                .help("Turns this share of the mix's reads into deletes, defaults to 0"),
+        )
+        .arg(
+            Arg::with_name("sample_conflicts")
+                .long("sample-conflicts")
+                .takes_value(true)
+                .help("Samples this many conflicts and prints a contention report to stderr"),
+        )
+        .arg(
+            Arg::with_name("filter_report")
+                .long("filter-report")
+                .help("Prints how often each template's requests were partitioned to stderr"),
+        )
+        .arg(
+            Arg::with_name("interval_pruning")
+                .long("interval-pruning")
+                .help("Skips solving for requests that bound a column to disjoint ranges"),
+        )
+        .arg(
+            Arg::with_name("key_filters")
+                .long("key-filters")
+                .takes_value(true)
+                .help("Skips solving for point requests whose key is absent from buckets of at least this many requests"),
+        )
+        .arg(
+            Arg::with_name("shared_reads")
+                .long("shared-reads")
+                .help("Lets identical reads of read-only transactions share an in-flight entry"),
+        )
+        .arg(
+            Arg::with_name("max_active_transactions")
+                .long("max-active-transactions")
+                .takes_value(true)
+                .help("Holds back transactions beyond this many until one of them commits"),
+        )
+        .arg(
+            Arg::with_name("hot_keys")
+                .long("hot-keys")
+                .takes_value(true)
+                .help("Queues requests on keys that have conflicted this many times apart from the rest"),
+        )
+        .arg(
+            Arg::with_name("snapshot")
+                .long("snapshot")
+                .takes_value(true)
+                .help("Loads the database from this file, or saves it there if it is missing"),
+        )
+        .arg(
+            Arg::with_name("wal")
+                .long("wal")
+                .takes_value(true)
+                .help("Logs changes to this file, syncing it as transactions end"),
+        )
+        .arg(
+            Arg::with_name("committer")
+                .long("committer")
+                .requires("wal")
+                .conflicts_with("arrival_rate")
+                .help("Hands transactions to a thread that syncs the log for batches of them"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .takes_value(true)
+                .help("Writes detailed results as JSON, or as CSV if the path ends in .csv"),
+        )
+        .args(&Phases::args())
+        .args(&Placement::args())
+        .args(&RetryPolicy::args())
+        .args(&Seed::args())
+        .args(&GraphExport::args())
+        .args(&LatencyBreakdown::args())
+        .args(&Verification::args())
+        .args(&Adaptive::args())
+        .args(&Consistency::args())
+        .args(&Trace::args())
+        .args(&Arrivals::args())
         .get_matches();
 
     let num_rows = u32::from_str(matches.value_of("num_rows").unwrap()).unwrap();
@@ -31,41 +139,211 @@ fn main() {
     let optimization =
         OptimizationLevel::from_str(matches.value_of("optimization").unwrap()).unwrap();
     let num_workers = usize::from_str(matches.value_of("num_workers").unwrap()).unwrap();
+    let phases = Phases::from_matches(&matches);
+    let placement = Placement::from_matches(&matches);
+    let seed = Seed::from_matches(&matches);
+    let graph_export = GraphExport::from_matches(&matches);
+    let latency_breakdown = LatencyBreakdown::from_matches(&matches);
+    let adaptive = Adaptive::from_matches(&matches);
+    let retry_policy = RetryPolicy::from_matches(&matches);
+    let trace = Trace::from_matches(&matches);
+    let arrivals = Arrivals::from_matches(&matches, num_workers);
+    let mix = match matches.value_of("workload") {
+        Some(workload) => YCSBMix::from_str(workload).unwrap(),
+        None => YCSBMix::read_update(select_mix),
+    };
+    let delete_mix = matches
+        .value_of("delete_mix")
+        .map_or(0.0, |delete| f64::from_str(delete).unwrap());
+    let mix = mix.with_deletes(delete_mix);
+    let next_user_id = Arc::new(AtomicU32::new(num_rows));
+    let distribution_name =
+        matches
+            .value_of("distribution")
+            .unwrap_or(if skew == 0.0 { "uniform" } else { "zipfian" });
+    let distribution =
+        KeyDistribution::from_name(distribution_name, num_rows, skew, &next_user_id).unwrap();
+    let sample_conflicts = matches
+        .value_of("sample_conflicts")
+        .map(|capacity| usize::from_str(capacity).unwrap());
+    let key_filters = matches
+        .value_of("key_filters")
+        .map(|min_requests| usize::from_str(min_requests).unwrap());
+    let hot_keys = matches
+        .value_of("hot_keys")
+        .map(|threshold| usize::from_str(threshold).unwrap());
+    let max_active_transactions = matches
+        .value_of("max_active_transactions")
+        .map(|max_active| usize::from_str(max_active).unwrap());
+
+    let mut dibs = ycsb::dibs(optimization);
+    dibs.enable_seeded_jitter(seed.dibs());
+
+    if let Some(capacity) = sample_conflicts {
+        dibs.enable_conflict_sampling(capacity);
+    }
+
+    if matches.is_present("filter_report") {
+        dibs.enable_filter_stats();
+    }
 
-    let dibs = Arc::new(ycsb::dibs(optimization));
+    graph_export.enable(&mut dibs);
+    latency_breakdown.enable(&mut dibs);
+    adaptive.enable(&mut dibs);
 
-    let db = Arc::new(ArrowYCSBDatabase::new(num_rows, field_size));
+    if matches.is_present("interval_pruning") {
+        dibs.enable_interval_pruning();
+    }
+
+    if let Some(min_requests) = key_filters {
+        dibs.enable_key_filters(min_requests);
+    }
+
+    if let Some(threshold) = hot_keys {
+        dibs.enable_hot_keys(threshold);
+    }
+
+    if matches.is_present("shared_reads") {
+        dibs.enable_shared_reads();
+    }
+
+    if let Some(max_active) = max_active_transactions {
+        dibs.enable_admission_control(max_active);
+    }
+
+    let dibs = Arc::new(dibs);
+
+    let snapshot = matches.value_of("snapshot").map(PathBuf::from);
+
+    let db = Arc::new(placement.load(move || {
+        let mut rng = seed.loader();
+
+        match snapshot {
+            Some(path) => {
+                ArrowYCSBDatabase::load_or_create(&path, num_rows, field_size, &mut rng).unwrap()
+            }
+            None => ArrowYCSBDatabase::new(num_rows, field_size, &mut rng),
+        }
+    }));
+
+    let verification = Verification::from_matches(&matches);
+    let wal = verification.open_wal(&matches).map(Arc::new);
+
+    let committer = match &wal {
+        Some(wal) if matches.is_present("committer") => Some(Committer::start(Arc::clone(wal))),
+        _ => None,
+    };
 
     let mut workers: Vec<Box<dyn Worker + Send>> = vec![];
 
     for worker_id in 0..num_workers {
-        if skew == 0.0 {
-            workers.push(Box::new(StandardWorker::new(
-                worker_id,
-                Some(Arc::clone(&dibs)),
-                ycsb::uniform_generator(
-                    num_rows,
-                    field_size,
-                    select_mix,
-                    num_statements_per_transaction,
-                ),
-                ArrowYCSBConnection::new(Arc::clone(&db)),
-            )));
-        } else {
-            workers.push(Box::new(StandardWorker::new(
-                worker_id,
-                Some(Arc::clone(&dibs)),
-                ycsb::zipf_generator(
-                    num_rows,
-                    field_size,
-                    select_mix,
-                    num_statements_per_transaction,
-                    skew,
-                ),
-                ArrowYCSBConnection::new(Arc::clone(&db)),
-            )));
-        }
+        let generator = trace.wrap(
+            worker_id,
+            ycsb::generator(
+                num_rows,
+                field_size,
+                select_mix,
+                num_statements_per_transaction,
+                distribution.clone(),
+            )
+            .with_mix(mix, Arc::clone(&next_user_id)),
+        );
+        let connection = ArrowYCSBConnection::new(Arc::clone(&db)).with_wal(wal.clone());
+
+        workers.push(match &committer {
+            Some(committer) => Box::new(
+                PipelinedWorker::new(
+                    worker_id,
+                    Arc::clone(&dibs),
+                    generator,
+                    connection,
+                    committer,
+                )
+                .with_retry_policy(retry_policy)
+                .with_rng(seed.worker(worker_id)),
+            ),
+            None => Box::new(
+                StandardWorker::new(worker_id, Some(Arc::clone(&dibs)), generator, connection)
+                    .with_retry_policy(retry_policy)
+                    .with_rng(seed.worker(worker_id))
+                    .with_arrivals(arrivals),
+            ),
+        });
     }
 
-    runner::run(workers);
+    verification.start(&*db).unwrap();
+
+    let adaptive_run = adaptive.start(&dibs);
+
+    let mut results = runner::run_with_parameters(
+        workers,
+        phases,
+        &placement,
+        &[
+            placement.parameter(),
+            latency_breakdown.parameter(),
+            seed.parameter(),
+            trace.parameter(),
+            arrivals.parameter(),
+            adaptive.parameter(),
+            (
+                "interval_pruning",
+                matches.is_present("interval_pruning").to_string(),
+            ),
+            (
+                "key_filters",
+                key_filters.map_or("off".to_string(), |min_requests| min_requests.to_string()),
+            ),
+            (
+                "hot_keys",
+                hot_keys.map_or("off".to_string(), |threshold| threshold.to_string()),
+            ),
+            (
+                "shared_reads",
+                matches.is_present("shared_reads").to_string(),
+            ),
+            (
+                "max_active_transactions",
+                max_active_transactions
+                    .map_or("off".to_string(), |max_active| max_active.to_string()),
+            ),
+            ("committer", committer.is_some().to_string()),
+            ("distribution", distribution_name.to_string()),
+            ("skew", skew.to_string()),
+            ("delete_mix", delete_mix.to_string()),
+        ],
+        Some(&dibs),
+    );
+
+    if let Some(adaptive_run) = adaptive_run {
+        eprintln!("Switched optimization levels {} times", adaptive_run.stop());
+    }
+
+    trace.finish();
+
+    if Consistency::is_enabled(&matches) {
+        let consistency = db.check_consistency(field_size);
+        eprint!("{}", consistency);
+        results.consistency = Some(consistency);
+    }
+
+    if let Some(verified) = verification.finish(&*db).unwrap() {
+        eprint!("{}", verified);
+        let consistency = results.consistency.get_or_insert_with(Consistency::default);
+        consistency.checks.extend(verified.checks);
+    }
+
+    if let Some(output) = matches.value_of("output") {
+        results.write(output).unwrap();
+    }
+
+    graph_export.write(&dibs).unwrap();
+
+    if let Some(report) = dibs.conflict_report(10) {
+        eprint!("{}", report);
+    }
+
+    if let Some(report) = dibs.filter_report() {
+        eprint!("{}", report);
+    }
 }
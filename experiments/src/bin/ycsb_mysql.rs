@@ -1,10 +1,18 @@
 use clap::{App, Arg};
 use dibs::OptimizationLevel;
 use dibs_experiments::benchmarks::ycsb;
+use dibs_experiments::benchmarks::ycsb::{KeyDistribution, YCSBMix};
+use dibs_experiments::consistency::Consistency;
+use dibs_experiments::placement::Placement;
+use dibs_experiments::runner::Phases;
+use dibs_experiments::seed::Seed;
 use dibs_experiments::systems::mysql::{IsolationMechanism, MySQLYCSBConnection};
-use dibs_experiments::worker::{StandardWorker, Worker};
+use dibs_experiments::systems::IsolationLevel;
+use dibs_experiments::trace::Trace;
+use dibs_experiments::worker::{Arrivals, RetryPolicy, StandardWorker, Worker};
 use dibs_experiments::{runner, systems};
 use std::str::FromStr;
+use std::sync::atomic::AtomicU32;
 use std::sync::Arc;
 
 fn main() {
@@ -21,6 +29,34 @@ fn main() {
                 .required(true),
         )
         .arg(Arg::with_name("num_workers").required(true))
+        .arg(
+            Arg::with_name("distribution")
+                .long("distribution")
+                .possible_values(&["uniform", "zipfian", "latest", "hotspot"])
+                .takes_value(true)
+                .help("Defaults to uniform if skew is 0 and zipfian otherwise"),
+        )
+        .arg(
+            Arg::with_name("workload")
+                .long("workload")
+                .possible_values(&["a", "b", "c", "d", "e", "f"])
+                .takes_value(true)
+                .help("Runs a standard YCSB workload mix instead of select_mix"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .takes_value(true)
+                .help("Writes detailed results as JSON, or as CSV if the path ends in .csv"),
+        )
+        .args(&Phases::args())
+        .args(&Placement::args())
+        .args(&RetryPolicy::args())
+        .args(&Seed::args())
+        .args(&Consistency::args())
+        .args(&Trace::args())
+        .args(&Arrivals::args())
+        .args(&IsolationLevel::args())
         .get_matches();
 
     let num_rows = u32::from_str(matches.value_of("num_rows").unwrap()).unwrap();
@@ -33,10 +69,30 @@ fn main() {
     let optimization =
         OptimizationLevel::from_str(matches.value_of("optimization").unwrap()).unwrap();
     let num_workers = usize::from_str(matches.value_of("num_workers").unwrap()).unwrap();
+    let phases = Phases::from_matches(&matches);
+    let placement = Placement::from_matches(&matches);
+    let seed = Seed::from_matches(&matches);
+    let retry_policy = RetryPolicy::from_matches(&matches);
+    let trace = Trace::from_matches(&matches);
+    let arrivals = Arrivals::from_matches(&matches, num_workers);
+    let isolation_level = IsolationLevel::from_matches(&matches, isolation.isolation_level());
+    let mix = match matches.value_of("workload") {
+        Some(workload) => YCSBMix::from_str(workload).unwrap(),
+        None => YCSBMix::read_update(select_mix),
+    };
+    let next_user_id = Arc::new(AtomicU32::new(num_rows));
+    let distribution_name =
+        matches
+            .value_of("distribution")
+            .unwrap_or(if skew == 0.0 { "uniform" } else { "zipfian" });
+    let distribution =
+        KeyDistribution::from_name(distribution_name, num_rows, skew, &next_user_id).unwrap();
 
-    let dibs = Arc::new(ycsb::dibs(optimization));
+    let mut dibs = ycsb::dibs(optimization);
+    dibs.enable_seeded_jitter(seed.dibs());
+    let dibs = Arc::new(dibs);
 
-    systems::mysql::load_ycsb(num_rows, field_size);
+    placement.load(move || systems::mysql::load_ycsb(num_rows, field_size, seed.loader()));
 
     let mut workers: Vec<Box<dyn Worker + Send>> = vec![];
 
@@ -48,33 +104,59 @@ fn main() {
             }
         };
 
-        workers.push(if skew == 0.0 {
-            Box::new(StandardWorker::new(
-                worker_id,
-                dibs,
-                ycsb::uniform_generator(
-                    num_rows,
-                    field_size,
-                    select_mix,
-                    num_statements_per_transaction,
-                ),
-                MySQLYCSBConnection::new(isolation),
-            ))
-        } else {
-            Box::new(StandardWorker::new(
+        workers.push(Box::new(
+            StandardWorker::new(
                 worker_id,
                 dibs,
-                ycsb::zipf_generator(
-                    num_rows,
-                    field_size,
-                    select_mix,
-                    num_statements_per_transaction,
-                    skew,
+                trace.wrap(
+                    worker_id,
+                    ycsb::generator(
+                        num_rows,
+                        field_size,
+                        select_mix,
+                        num_statements_per_transaction,
+                        distribution.clone(),
+                    )
+                    .with_mix(mix, Arc::clone(&next_user_id)),
                 ),
-                MySQLYCSBConnection::new(isolation),
-            ))
-        });
+                MySQLYCSBConnection::new(isolation).with_isolation_level(isolation_level),
+            )
+            .with_retry_policy(retry_policy)
+            .with_rng(seed.worker(worker_id))
+            .with_arrivals(arrivals),
+        ));
     }
 
-    runner::run(workers);
+    let mut results = runner::run_with_parameters(
+        workers,
+        phases,
+        &placement,
+        &[
+            placement.parameter(),
+            seed.parameter(),
+            trace.parameter(),
+            arrivals.parameter(),
+            isolation_level.parameter(),
+            ("distribution", distribution_name.to_string()),
+            ("skew", skew.to_string()),
+        ],
+        match isolation {
+            IsolationMechanism::DibsSerializable => Some(&*dibs),
+            IsolationMechanism::MySQLSerializable | IsolationMechanism::MySQLReadUncommitted => {
+                None
+            }
+        },
+    );
+
+    trace.finish();
+
+    if Consistency::is_enabled(&matches) {
+        let consistency = MySQLYCSBConnection::new(isolation).check_consistency(field_size);
+        eprint!("{}", consistency);
+        results.consistency = Some(consistency);
+    }
+
+    if let Some(output) = matches.value_of("output") {
+        results.write(output).unwrap();
+    }
 }
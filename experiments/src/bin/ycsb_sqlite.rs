@@ -1,46 +1,74 @@
 use clap::{App, Arg};
 use dibs::{Dibs, OptimizationLevel};
 use dibs_experiments::benchmarks::ycsb;
-use dibs_experiments::benchmarks::ycsb::YCSBGenerator;
-use dibs_experiments::systems::sqlite::SQLiteYCSBConnection;
+use dibs_experiments::benchmarks::ycsb::{KeyDistribution, YCSBGenerator, YCSBMix};
+use dibs_experiments::consistency::Consistency;
+use dibs_experiments::placement::Placement;
+use dibs_experiments::runner::Phases;
+use dibs_experiments::seed::Seed;
+use dibs_experiments::systems::pool::{Pool, PooledConnection};
+use dibs_experiments::systems::sqlite::{IsolationMechanism, SQLiteYCSBConnection};
+use dibs_experiments::systems::IsolationLevel;
+use dibs_experiments::trace::{Trace, TracedGenerator};
 use dibs_experiments::worker::{
-    GroupCommitWorker, ReadOnlyGenerator, ReceivingGenerator, StandardWorker, Worker,
+    GroupCommitWorker, ReadOnlyGenerator, ReceivingGenerator, RetryPolicy, StandardWorker, Worker,
 };
 use dibs_experiments::{runner, systems};
 use rand::distributions::Distribution;
 use std::str::FromStr;
+use std::sync::atomic::AtomicU32;
 use std::sync::{mpsc, Arc};
 
+#[allow(clippy::too_many_arguments)]
 fn make_workers<F, D>(
     num_transactions_per_group: usize,
     num_workers: usize,
-    dibs: Arc<Dibs>,
+    dibs: &Arc<Dibs>,
+    isolation: IsolationMechanism,
+    pool: &Arc<Pool<SQLiteYCSBConnection>>,
+    retry_policy: RetryPolicy,
+    seed: Seed,
     make_generator: F,
 ) -> Vec<Box<dyn Worker + Send>>
 where
-    F: Fn() -> YCSBGenerator<D>,
+    F: Fn(usize) -> TracedGenerator<YCSBGenerator<D>>,
     D: 'static + Distribution<usize> + Send,
 {
     let (sender, receiver) = mpsc::sync_channel(0);
 
-    let mut workers: Vec<Box<dyn Worker + Send>> = vec![Box::new(GroupCommitWorker::new(
-        0,
-        Some(dibs),
-        ReceivingGenerator::new(make_generator(), receiver),
-        SQLiteYCSBConnection::new("ycsb.sqlite"),
-        num_transactions_per_group,
-    ))];
+    let mut workers: Vec<Box<dyn Worker + Send>> = vec![Box::new(
+        GroupCommitWorker::new(
+            0,
+            Some(Arc::clone(dibs)),
+            ReceivingGenerator::new(make_generator(0), receiver),
+            PooledConnection::new(Arc::clone(pool)),
+            num_transactions_per_group,
+        )
+        .with_retry_policy(retry_policy)
+        .with_rng(seed.worker(0)),
+    )];
 
     for worker_id in 1..num_workers {
-        let generator: ReadOnlyGenerator<YCSBGenerator<D>, SQLiteYCSBConnection> =
-            ReadOnlyGenerator::new(make_generator(), sender.clone());
-
-        workers.push(Box::new(StandardWorker::new(
-            worker_id,
-            None,
-            generator,
-            SQLiteYCSBConnection::new("ycsb.sqlite"),
-        )));
+        let generator: ReadOnlyGenerator<
+            TracedGenerator<YCSBGenerator<D>>,
+            PooledConnection<SQLiteYCSBConnection>,
+        > = ReadOnlyGenerator::new(make_generator(worker_id), sender.clone());
+
+        let dibs = match isolation {
+            IsolationMechanism::DibsSerializable => Some(Arc::clone(dibs)),
+            IsolationMechanism::SQLiteSnapshot => None,
+        };
+
+        workers.push(Box::new(
+            StandardWorker::new(
+                worker_id,
+                dibs,
+                generator,
+                PooledConnection::new(Arc::clone(pool)),
+            )
+            .with_retry_policy(retry_policy)
+            .with_rng(seed.worker(worker_id)),
+        ));
     }
 
     workers
@@ -60,6 +88,41 @@ fn main() {
                 .required(true),
         )
         .arg(Arg::with_name("num_workers").required(true))
+        .arg(
+            Arg::with_name("distribution")
+                .long("distribution")
+                .possible_values(&["uniform", "zipfian", "latest", "hotspot"])
+                .takes_value(true)
+                .help("Defaults to uniform if skew is 0 and zipfian otherwise"),
+        )
+        .arg(
+            Arg::with_name("workload")
+                .long("workload")
+                .possible_values(&["a", "b", "c", "d", "e", "f"])
+                .takes_value(true)
+                .help("Runs a standard YCSB workload mix instead of select_mix"),
+        )
+        .arg(
+            Arg::with_name("isolation")
+                .long("isolation")
+                .possible_values(&["SQLiteSnapshot", "DibsSerializable"])
+                .takes_value(true)
+                .help("Whether readers acquire requests from dibs, defaults to SQLiteSnapshot"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .takes_value(true)
+                .help("Writes detailed results as JSON, or as CSV if the path ends in .csv"),
+        )
+        .args(&Phases::args())
+        .args(&Placement::args())
+        .args(&RetryPolicy::args())
+        .args(&Seed::args())
+        .args(&Consistency::args())
+        .args(&Trace::args())
+        .args(&systems::pool::args())
+        .args(&IsolationLevel::args())
         .get_matches();
 
     let num_rows = u32::from_str(matches.value_of("num_rows").unwrap()).unwrap();
@@ -73,31 +136,98 @@ fn main() {
     let optimization =
         OptimizationLevel::from_str(matches.value_of("optimization").unwrap()).unwrap();
     let num_workers = usize::from_str(matches.value_of("num_workers").unwrap()).unwrap();
+    let phases = Phases::from_matches(&matches);
+    let placement = Placement::from_matches(&matches);
+    let seed = Seed::from_matches(&matches);
+    let retry_policy = RetryPolicy::from_matches(&matches);
+    let trace = Trace::from_matches(&matches);
+    let pool_size = systems::pool::size_from_matches(&matches, num_workers);
+    let isolation = matches
+        .value_of("isolation")
+        .map_or(IsolationMechanism::SQLiteSnapshot, |isolation| {
+            IsolationMechanism::from_str(isolation).unwrap()
+        });
+    let isolation_level = IsolationLevel::from_matches(&matches, isolation.isolation_level());
+    let mix = match matches.value_of("workload") {
+        Some(workload) => YCSBMix::from_str(workload).unwrap(),
+        None => YCSBMix::read_update(select_mix),
+    };
+    let next_user_id = Arc::new(AtomicU32::new(num_rows));
+    let distribution_name =
+        matches
+            .value_of("distribution")
+            .unwrap_or(if skew == 0.0 { "uniform" } else { "zipfian" });
+    let distribution =
+        KeyDistribution::from_name(distribution_name, num_rows, skew, &next_user_id).unwrap();
 
-    let dibs = Arc::new(ycsb::dibs(optimization));
+    let mut dibs = ycsb::dibs(optimization);
+    dibs.enable_seeded_jitter(seed.dibs());
+    let dibs = Arc::new(dibs);
 
-    systems::sqlite::load_ycsb("ycsb.sqlite", num_rows, field_size);
+    placement.load(move || {
+        systems::sqlite::load_ycsb("ycsb.sqlite", num_rows, field_size, seed.loader())
+    });
 
-    let workers = if skew == 0.0 {
-        make_workers(num_transactions_per_group, num_workers, dibs, || {
-            ycsb::uniform_generator(
-                num_rows,
-                field_size,
-                select_mix,
-                num_statements_per_transaction,
-            )
-        })
-    } else {
-        make_workers(num_transactions_per_group, num_workers, dibs, || {
-            ycsb::zipf_generator(
-                num_rows,
-                field_size,
-                select_mix,
-                num_statements_per_transaction,
-                skew,
+    let pool = Arc::new(Pool::new(pool_size, move || {
+        SQLiteYCSBConnection::new("ycsb.sqlite").with_isolation_level(isolation_level)
+    }));
+
+    let workers = make_workers(
+        num_transactions_per_group,
+        num_workers,
+        &dibs,
+        isolation,
+        &pool,
+        retry_policy,
+        seed,
+        |worker_id| {
+            trace.wrap(
+                worker_id,
+                ycsb::generator(
+                    num_rows,
+                    field_size,
+                    select_mix,
+                    num_statements_per_transaction,
+                    distribution.clone(),
+                )
+                .with_mix(mix, Arc::clone(&next_user_id)),
             )
-        })
-    };
+        },
+    );
 
-    runner::run(workers);
+    let mut results = runner::run_with_parameters(
+        workers,
+        phases,
+        &placement,
+        &[
+            placement.parameter(),
+            seed.parameter(),
+            trace.parameter(),
+            (
+                "isolation",
+                match isolation {
+                    IsolationMechanism::SQLiteSnapshot => "SQLiteSnapshot",
+                    IsolationMechanism::DibsSerializable => "DibsSerializable",
+                }
+                .to_string(),
+            ),
+            isolation_level.parameter(),
+            ("pool_size", pool_size.to_string()),
+            ("distribution", distribution_name.to_string()),
+            ("skew", skew.to_string()),
+        ],
+        Some(&dibs),
+    );
+
+    trace.finish();
+
+    if Consistency::is_enabled(&matches) {
+        let consistency = SQLiteYCSBConnection::new("ycsb.sqlite").check_consistency(field_size);
+        eprint!("{}", consistency);
+        results.consistency = Some(consistency);
+    }
+
+    if let Some(output) = matches.value_of("output") {
+        results.write(output).unwrap();
+    }
 }
@@ -0,0 +1,107 @@
+//! Splits the latency of each committed transaction into the time it spent acquiring requests
+//! from dibs, blocking included, and the time it spent executing otherwise, so that the overhead
+//! of dibs shows up in the results next to the work it protects.
+//!
+//! The acquires are timed by an observer of dibs. Its callbacks run on the thread of the
+//! transaction that acquires, so it adds the times up per thread, and the worker running the
+//! transaction takes the sum once the transaction ends. Standard and pipelined workers record
+//! breakdowns, while the others only record latencies.
+
+use crate::results::Recorder;
+use clap::{Arg, ArgMatches};
+use dibs::observer::TransactionObserver;
+use dibs::{AcquireError, Dibs};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Whether any `Dibs` in the process times its acquires.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// The time that the calling thread's transaction has spent acquiring so far.
+    static ACQUIRE_TIME: Cell<Duration> = const { Cell::new(Duration::from_secs(0)) };
+}
+
+struct AcquireTimer;
+
+impl TransactionObserver for AcquireTimer {
+    fn on_acquire(
+        &self,
+        _transaction_id: usize,
+        _template_id: usize,
+        duration: Duration,
+        _error: Option<&AcquireError>,
+    ) {
+        ACQUIRE_TIME.with(|time| time.set(time.get() + duration));
+    }
+}
+
+/// Whether to break down the latencies of a run.
+pub struct LatencyBreakdown {
+    enabled: bool,
+}
+
+impl LatencyBreakdown {
+    /// Returns the `--latency-breakdown` flag.
+    pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![Arg::with_name("latency_breakdown")
+            .long("latency-breakdown")
+            .help(
+                "Reports the time each procedure spent acquiring from dibs, blocking included, \
+                 apart from the rest of its execution",
+            )]
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> LatencyBreakdown {
+        LatencyBreakdown {
+            enabled: matches.is_present("latency_breakdown"),
+        }
+    }
+
+    /// Times the acquires of `dibs` if the latencies are to be broken down. This takes the place
+    /// of any other observer of `dibs`.
+    pub fn enable(&self, dibs: &mut Dibs) {
+        if self.enabled {
+            dibs.set_observer(Arc::new(AcquireTimer));
+            ENABLED.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns a parameter describing whether latencies are broken down for
+    /// `runner::run_with_parameters`.
+    pub fn parameter(&self) -> (&'static str, String) {
+        ("latency_breakdown", self.enabled.to_string())
+    }
+}
+
+/// Times the attempts of a transaction run on the calling thread.
+pub(crate) struct TransactionTimer {
+    running: Duration,
+}
+
+impl TransactionTimer {
+    pub(crate) fn start() -> TransactionTimer {
+        ACQUIRE_TIME.with(|time| time.set(Duration::from_secs(0)));
+
+        TransactionTimer {
+            running: Duration::from_secs(0),
+        }
+    }
+
+    /// Adds the time that an attempt spent running the procedure, acquires included.
+    pub(crate) fn add(&mut self, attempt: Duration) {
+        self.running += attempt;
+    }
+
+    /// Records the breakdown of the transaction, which committed, if latencies are broken down.
+    pub(crate) fn record(self, recorder: &Recorder, procedure: &'static str) {
+        if !ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let acquire = ACQUIRE_TIME.with(Cell::get);
+        recorder.breakdown(procedure, acquire, self.running.saturating_sub(acquire));
+    }
+}
@@ -0,0 +1,137 @@
+//! A compact binary encoding shared by the write-ahead log and the network front-end. Values are
+//! little-endian, and byte strings are prefixed with their length.
+
+pub fn put_u8(buf: &mut Vec<u8>, value: u8) {
+    buf.push(value);
+}
+
+pub fn put_bool(buf: &mut Vec<u8>, value: bool) {
+    buf.push(value as u8);
+}
+
+pub fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+pub fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Appends `value` prefixed with its length.
+pub fn put_bytes(buf: &mut Vec<u8>, value: &[u8]) {
+    put_u32(buf, value.len() as u32);
+    buf.extend_from_slice(value);
+}
+
+/// Reads the values written by the `put_*` functions back, in the same order.
+pub struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(buf: &'a [u8]) -> Decoder<'a> {
+        Decoder { buf }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Takes the next `len` bytes as they are.
+    pub fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.buf.len() {
+            return None;
+        }
+
+        let (value, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Some(value)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|value| value[0])
+    }
+
+    pub fn bool(&mut self) -> Option<bool> {
+        self.u8().map(|value| value != 0)
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        let mut value = [0; 4];
+        value.copy_from_slice(self.take(4)?);
+        Some(u32::from_le_bytes(value))
+    }
+
+    pub fn u64(&mut self) -> Option<u64> {
+        let mut value = [0; 8];
+        value.copy_from_slice(self.take(8)?);
+        Some(u64::from_le_bytes(value))
+    }
+
+    pub fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    pub fn string(&mut self) -> Option<String> {
+        String::from_utf8(self.bytes()?.to_vec()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode() -> Vec<u8> {
+        let mut buf = vec![];
+        put_u8(&mut buf, 7);
+        put_bool(&mut buf, true);
+        put_u32(&mut buf, 0xdead_beef);
+        put_u64(&mut buf, u64::MAX - 1);
+        put_bytes(&mut buf, b"\x00\xff");
+        put_bytes(&mut buf, "caf\u{e9}".as_bytes());
+        buf
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let buf = encode();
+        let mut decoder = Decoder::new(&buf);
+
+        assert_eq!(decoder.u8(), Some(7));
+        assert_eq!(decoder.bool(), Some(true));
+        assert_eq!(decoder.u32(), Some(0xdead_beef));
+        assert_eq!(decoder.u64(), Some(u64::MAX - 1));
+        assert_eq!(decoder.bytes(), Some(&b"\x00\xff"[..]));
+        assert_eq!(decoder.string(), Some("caf\u{e9}".to_string()));
+        assert!(decoder.is_empty());
+        assert_eq!(decoder.u8(), None);
+    }
+
+    #[test]
+    fn truncated_values_do_not_decode() {
+        let buf = encode();
+
+        // The string is the last 9 bytes: its length and its 5 bytes.
+        let mut decoder = Decoder::new(&buf[..buf.len() - 1]);
+        decoder.take(buf.len() - 9).unwrap();
+        assert_eq!(decoder.string(), None);
+
+        for &len in &[0, 3] {
+            assert_eq!(Decoder::new(&buf[2..2 + len]).u32(), None);
+        }
+
+        // A length prefix longer than what follows doesn't read past it.
+        let mut buf = vec![];
+        put_u32(&mut buf, u32::MAX);
+        buf.push(0);
+        assert_eq!(Decoder::new(&buf).bytes(), None);
+    }
+
+    #[test]
+    fn strings_must_be_utf8() {
+        let mut buf = vec![];
+        put_bytes(&mut buf, b"\xff");
+        assert_eq!(Decoder::new(&buf).string(), None);
+    }
+}
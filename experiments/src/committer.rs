@@ -0,0 +1,239 @@
+//! A committer thread that makes the transactions of many workers durable together.
+//!
+//! A `PipelinedWorker` doesn't wait for its transactions to become durable. When a procedure
+//! succeeds, the worker hands its log records and its dibs transaction, with the requests still
+//! held, to the committer and moves on to its next procedure. The committer takes every
+//! transaction handed to it while it was busy, writes their records to the log as one frame with
+//! one sync, and only then commits their dibs transactions. No other worker can see a change
+//! before it is durable, yet the log is synced once per batch rather than once per transaction.
+//!
+//! A worker runs all of its transactions in one group, so that a transaction that conflicts with
+//! one of the worker's own still waiting for the committer fails at once instead of waiting on it.
+//! The worker then waits for the committer to catch up and retries, as the `GroupCommitWorker`
+//! does when its group conflicts.
+
+use crate::breakdown::TransactionTimer;
+use crate::results::Recorder;
+use crate::systems::arrow::wal::Wal;
+use crate::worker::{RetryPolicy, State, Worker};
+use crate::{Connection, Generator, Procedure};
+use dibs::{AcquireError, Dibs, Transaction};
+use rand::rngs::StdRng;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+
+/// A connection whose changes a committer can log in its place.
+pub trait Logged: Connection {
+    /// Moves the log records of the changes made since the last call to the end of `buf`.
+    fn take_records(&mut self, buf: &mut Vec<u8>);
+}
+
+/// The number of a worker's transactions that the committer hasn't committed yet.
+struct Outstanding {
+    count: Mutex<usize>,
+    done: Condvar,
+}
+
+impl Outstanding {
+    fn add(&self) {
+        *self.count.lock().unwrap() += 1;
+    }
+
+    fn complete(&self) {
+        *self.count.lock().unwrap() -= 1;
+        self.done.notify_all();
+    }
+
+    fn wait(&self) {
+        let mut count = self.count.lock().unwrap();
+
+        while *count > 0 {
+            count = self.done.wait(count).unwrap();
+        }
+    }
+}
+
+/// A transaction handed to the committer.
+struct Entry {
+    transaction: Transaction,
+    records: Vec<u8>,
+    name: &'static str,
+    start: Instant,
+    retried: bool,
+    recorder: Arc<Recorder>,
+    outstanding: Arc<Outstanding>,
+}
+
+pub struct Committer {
+    sender: Sender<Entry>,
+}
+
+impl Committer {
+    /// Starts a committer thread that logs to `wal`. The thread exits once the committer and
+    /// every worker using it have been dropped and it has committed what they handed it.
+    pub fn start(wal: Arc<Wal>) -> Committer {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || Committer::run(&wal, receiver));
+        Committer { sender }
+    }
+
+    fn run(wal: &Wal, receiver: Receiver<Entry>) {
+        let mut records = vec![];
+
+        while let Ok(entry) = receiver.recv() {
+            let mut batch = vec![entry];
+            batch.extend(receiver.try_iter());
+
+            records.clear();
+
+            for entry in &batch {
+                records.extend_from_slice(&entry.records);
+            }
+
+            if !records.is_empty() {
+                wal.commit(&records).unwrap();
+            }
+
+            for entry in batch {
+                entry.transaction.commit();
+
+                entry
+                    .recorder
+                    .commit(entry.name, entry.start.elapsed(), entry.retried);
+
+                entry.outstanding.complete();
+            }
+        }
+    }
+}
+
+/// A worker that hands its transactions to a committer instead of waiting for them to become
+/// durable. Latencies are recorded when the committer commits a transaction, so they include the
+/// time spent waiting for it.
+pub struct PipelinedWorker<G, C> {
+    state: State,
+    generator: G,
+    connection: C,
+    sender: Sender<Entry>,
+    outstanding: Arc<Outstanding>,
+    retry_policy: RetryPolicy,
+}
+
+impl<G, C> PipelinedWorker<G, C> {
+    pub fn new(
+        worker_id: usize,
+        dibs: Arc<Dibs>,
+        generator: G,
+        connection: C,
+        committer: &Committer,
+    ) -> PipelinedWorker<G, C> {
+        PipelinedWorker {
+            state: State::new(worker_id, Some(dibs)),
+            generator,
+            connection,
+            sender: committer.sender.clone(),
+            outstanding: Arc::new(Outstanding {
+                count: Mutex::new(0),
+                done: Condvar::new(),
+            }),
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> PipelinedWorker<G, C> {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_rng(mut self, rng: StdRng) -> PipelinedWorker<G, C> {
+        self.state.rng = rng;
+        self
+    }
+}
+
+impl<G, C> Worker for PipelinedWorker<G, C>
+where
+    G: Generator,
+    G::Item: Procedure<C>,
+    C: Logged,
+{
+    fn run(&mut self, recorder: Arc<Recorder>, terminate: Arc<AtomicBool>) {
+        let group_id = self.state.group_id();
+
+        while !terminate.load(Ordering::Relaxed) {
+            let procedure = self.generator.next(&mut self.state.rng);
+            let start = Instant::now();
+            let mut timer = TransactionTimer::start();
+            let mut retries = 0;
+
+            self.connection.begin();
+
+            let committed = loop {
+                let mut transaction = Transaction::new(group_id, self.state.transaction_id());
+                self.retry_policy.set_deadline(&mut transaction, start);
+
+                let attempt = self.state.now();
+                let result =
+                    procedure.run(&self.state.dibs, &mut transaction, &mut self.connection);
+                timer.add(self.state.elapsed(attempt));
+
+                if result.is_ok() {
+                    break Some(transaction);
+                }
+
+                let remaining = self.state.remaining(&transaction);
+                transaction.commit();
+                recorder.abort();
+
+                if let Err(AcquireError::GroupConflict) = result {
+                    self.outstanding.wait();
+                }
+
+                if !self.retry_policy.allows(retries, remaining)
+                    || terminate.load(Ordering::Relaxed)
+                {
+                    break None;
+                }
+
+                retries += 1;
+                self.retry_policy.back_off(retries, &mut self.state.rng);
+            };
+
+            match committed {
+                Some(transaction) => {
+                    // The wait for the log is part of the latency but neither of its parts.
+                    timer.record(&recorder, procedure.name());
+
+                    let mut records = vec![];
+                    self.connection.take_records(&mut records);
+                    self.connection.commit();
+                    self.outstanding.add();
+
+                    self.sender
+                        .send(Entry {
+                            transaction,
+                            records,
+                            name: procedure.name(),
+                            start,
+                            retried: retries > 0,
+                            recorder: Arc::clone(&recorder),
+                            outstanding: Arc::clone(&self.outstanding),
+                        })
+                        .unwrap();
+                }
+                None => {
+                    // The changes of the failed attempts are logged by the connection itself,
+                    // after those of the transactions ahead of them.
+                    self.outstanding.wait();
+                    self.connection.commit();
+                    recorder.give_up();
+                }
+            }
+        }
+
+        self.outstanding.wait();
+    }
+}
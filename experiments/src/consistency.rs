@@ -0,0 +1,141 @@
+use crate::benchmarks::ycsb;
+use clap::{Arg, ArgMatches};
+use std::fmt;
+
+/// An invariant of a benchmark's database and the number of rows that violate it.
+#[derive(Clone, Debug)]
+pub struct Check {
+    pub name: String,
+    pub violations: usize,
+}
+
+/// The invariants checked on a database after a run. Isolation bugs rarely show up in throughput,
+/// but lost updates and inserts that race with deletes leave rows that the workload never writes.
+#[derive(Clone, Debug, Default)]
+pub struct Consistency {
+    pub checks: Vec<Check>,
+}
+
+impl Consistency {
+    /// Returns the `--check-consistency` flag.
+    pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![Arg::with_name("check_consistency")
+            .long("check-consistency")
+            .help("Checks the database's invariants after the run and reports the violations")]
+    }
+
+    pub fn is_enabled(matches: &ArgMatches) -> bool {
+        matches.is_present("check_consistency")
+    }
+
+    /// Runs each of `checks`, which are queries that count the rows violating an invariant.
+    pub fn query<F>(checks: &[(String, String)], mut count: F) -> Consistency
+    where
+        F: FnMut(&str) -> usize,
+    {
+        Consistency {
+            checks: checks
+                .iter()
+                .map(|(name, query)| Check {
+                    name: name.clone(),
+                    violations: count(query),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn push(&mut self, name: &str, violations: usize) {
+        self.checks.push(Check {
+            name: name.to_string(),
+            violations,
+        });
+    }
+
+    pub fn violations(&self) -> usize {
+        self.checks.iter().map(|check| check.violations).sum()
+    }
+}
+
+impl fmt::Display for Consistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "{}: {} violations", check.name, check.violations)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn check(name: &str, query: String) -> (String, String) {
+    (name.to_string(), query)
+}
+
+/// Returns the TATP invariants as queries that count violating rows, in SQL that SQLite, MySQL and
+/// Postgres all accept. Booleans are left out, since their types differ between the systems.
+pub fn tatp_checks() -> Vec<(String, String)> {
+    let hex = (1..=10)
+        .map(|i| format!("hex_{} NOT BETWEEN 0 AND 15", i))
+        .chain((1..=10).map(|i| format!("byte2_{} NOT BETWEEN 0 AND 255", i)))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+
+    vec![
+        check(
+            "access_info references subscriber",
+            "SELECT COUNT(*) FROM access_info AS ai WHERE NOT EXISTS \
+             (SELECT 1 FROM subscriber AS s WHERE s.s_id = ai.s_id)"
+                .to_string(),
+        ),
+        check(
+            "special_facility references subscriber",
+            "SELECT COUNT(*) FROM special_facility AS sf WHERE NOT EXISTS \
+             (SELECT 1 FROM subscriber AS s WHERE s.s_id = sf.s_id)"
+                .to_string(),
+        ),
+        check(
+            "call_forwarding references special_facility",
+            "SELECT COUNT(*) FROM call_forwarding AS cf WHERE NOT EXISTS \
+             (SELECT 1 FROM special_facility AS sf \
+             WHERE sf.s_id = cf.s_id AND sf.sf_type = cf.sf_type)"
+                .to_string(),
+        ),
+        check(
+            "subscriber ranges",
+            format!("SELECT COUNT(*) FROM subscriber WHERE {}", hex),
+        ),
+        check(
+            "access_info ranges",
+            "SELECT COUNT(*) FROM access_info WHERE ai_type NOT BETWEEN 1 AND 4 \
+             OR data1 NOT BETWEEN 0 AND 255 OR data2 NOT BETWEEN 0 AND 255 \
+             OR LENGTH(data3) <> 3 OR LENGTH(data4) <> 5"
+                .to_string(),
+        ),
+        check(
+            "special_facility ranges",
+            "SELECT COUNT(*) FROM special_facility WHERE sf_type NOT BETWEEN 1 AND 4 \
+             OR error_cntrl NOT BETWEEN 0 AND 255 OR data_a NOT BETWEEN 0 AND 255 \
+             OR LENGTH(data_b) <> 5"
+                .to_string(),
+        ),
+        check(
+            "call_forwarding ranges",
+            "SELECT COUNT(*) FROM call_forwarding WHERE start_time NOT IN (0, 8, 16) \
+             OR end_time NOT BETWEEN 1 AND 24 OR LENGTH(numberx) <> 15"
+                .to_string(),
+        ),
+    ]
+}
+
+/// Returns the YCSB invariants, which are that every field is `field_size` characters long.
+pub fn ycsb_checks(field_size: usize) -> Vec<(String, String)> {
+    vec![check(
+        "field sizes",
+        format!(
+            "SELECT COUNT(*) FROM users WHERE {}",
+            (0..ycsb::NUM_FIELDS)
+                .map(|field| format!("LENGTH(field_{}) <> {}", field, field_size))
+                .collect::<Vec<_>>()
+                .join(" OR ")
+        ),
+    )]
+}
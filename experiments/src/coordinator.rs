@@ -0,0 +1,245 @@
+//! Runs several client processes, possibly on other machines, as one experiment against a server,
+//! so that generating the load doesn't take cores from the database.
+//!
+//! One client process coordinates: it waits for the others to join, then tells each to start
+//! along with the phases to run, the seed, and the first of the worker IDs the process owns, so
+//! that every process measures the same window and its workers draw from their own streams. Once
+//! the run ends, each joined process sends its results to the coordinator, which merges them into
+//! its own. Messages are framed as the server's are.
+
+use crate::codec;
+use crate::codec::Decoder;
+use crate::results::Results;
+use crate::runner::Phases;
+use crate::seed::Seed;
+use crate::server::{read_frame, write_frame};
+use clap::{Arg, ArgMatches};
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::str::FromStr;
+use std::time::Duration;
+
+enum Role {
+    Alone,
+    Coordinate { address: String, num_joining: usize },
+    Join { address: String },
+}
+
+/// The part this process plays among the client processes of an experiment.
+pub struct Coordination {
+    role: Role,
+}
+
+impl Coordination {
+    /// Returns the `--coordinate`, `--processes` and `--join` flags.
+    pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![
+            Arg::with_name("coordinate")
+                .long("coordinate")
+                .takes_value(true)
+                .help(
+                    "Waits on this address for other client processes to join, then runs with \
+                     them and merges their results",
+                ),
+            Arg::with_name("processes")
+                .long("processes")
+                .takes_value(true)
+                .requires("coordinate")
+                .help("Number of other client processes to wait for, defaults to 1"),
+            Arg::with_name("join")
+                .long("join")
+                .takes_value(true)
+                .conflicts_with("coordinate")
+                .help(
+                    "Runs as a client process of the coordinator at this address, which sets the \
+                     phases and the seed",
+                ),
+        ]
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> Coordination {
+        let role = match (matches.value_of("coordinate"), matches.value_of("join")) {
+            (Some(address), _) => Role::Coordinate {
+                address: address.to_string(),
+                num_joining: matches
+                    .value_of("processes")
+                    .map_or(1, |processes| usize::from_str(processes).unwrap()),
+            },
+            (None, Some(address)) => Role::Join {
+                address: address.to_string(),
+            },
+            (None, None) => Role::Alone,
+        };
+
+        Coordination { role }
+    }
+
+    /// Waits until every client process is ready to run `num_workers` workers. `phases` and
+    /// `seed` are the coordinator's, and are replaced by the coordinator's in a joined process.
+    pub fn start(&self, num_workers: usize, phases: Phases, seed: Seed) -> io::Result<Session> {
+        match &self.role {
+            Role::Alone => Ok(Session {
+                phases,
+                seed,
+                first_worker_id: 0,
+                num_processes: 1,
+                peers: Peers::Alone,
+            }),
+            Role::Coordinate {
+                address,
+                num_joining,
+            } => {
+                let listener = TcpListener::bind(address)?;
+                eprintln!(
+                    "Waiting for {} client processes on {}",
+                    num_joining,
+                    listener.local_addr()?
+                );
+
+                let mut buf = vec![];
+                let mut joined = vec![];
+
+                for _ in 0..*num_joining {
+                    let (mut stream, _) = listener.accept()?;
+                    let num_workers = if read_frame(&mut stream, &mut buf)? {
+                        Decoder::new(&buf).u32()
+                    } else {
+                        None
+                    };
+
+                    joined.push((stream, num_workers.ok_or_else(invalid_message)? as usize));
+                }
+
+                let num_processes = joined.len() + 1;
+                let mut first_worker_id = num_workers;
+                let mut peers = vec![];
+
+                for (mut stream, num_workers) in joined {
+                    buf.clear();
+                    encode_start(&mut buf, phases, seed, first_worker_id, num_processes);
+                    write_frame(&mut stream, &buf)?;
+
+                    first_worker_id += num_workers;
+                    peers.push(stream);
+                }
+
+                Ok(Session {
+                    phases,
+                    seed,
+                    first_worker_id: 0,
+                    num_processes,
+                    peers: Peers::Joined(peers),
+                })
+            }
+            Role::Join { address } => {
+                let mut stream = TcpStream::connect(address.as_str())?;
+                let mut buf = vec![];
+
+                codec::put_u32(&mut buf, num_workers as u32);
+                write_frame(&mut stream, &buf)?;
+
+                if !read_frame(&mut stream, &mut buf)? {
+                    return Err(invalid_message());
+                }
+
+                let mut decoder = Decoder::new(&buf);
+                let mut phase = || decoder.u64().map(Duration::from_nanos);
+
+                let phases = Phases {
+                    warmup: phase().ok_or_else(invalid_message)?,
+                    measurement: phase().ok_or_else(invalid_message)?,
+                    cooldown: phase().ok_or_else(invalid_message)?,
+                };
+
+                Ok(Session {
+                    phases,
+                    seed: decoder.u64().map(Seed::new).ok_or_else(invalid_message)?,
+                    first_worker_id: decoder.u32().ok_or_else(invalid_message)? as usize,
+                    num_processes: decoder.u32().ok_or_else(invalid_message)? as usize,
+                    peers: Peers::Coordinator(stream),
+                })
+            }
+        }
+    }
+}
+
+fn encode_start(
+    buf: &mut Vec<u8>,
+    phases: Phases,
+    seed: Seed,
+    first_worker_id: usize,
+    num_processes: usize,
+) {
+    codec::put_u64(buf, phases.warmup.as_nanos() as u64);
+    codec::put_u64(buf, phases.measurement.as_nanos() as u64);
+    codec::put_u64(buf, phases.cooldown.as_nanos() as u64);
+    codec::put_u64(buf, seed.get());
+    codec::put_u32(buf, first_worker_id as u32);
+    codec::put_u32(buf, num_processes as u32);
+}
+
+fn invalid_message() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid coordination message")
+}
+
+enum Peers {
+    Alone,
+    Joined(Vec<TcpStream>),
+    Coordinator(TcpStream),
+}
+
+/// A run that every client process has started.
+pub struct Session {
+    pub phases: Phases,
+    pub seed: Seed,
+    /// The ID of this process's first worker. Its workers take the IDs that follow.
+    pub first_worker_id: usize,
+    pub num_processes: usize,
+    peers: Peers,
+}
+
+impl Session {
+    /// Returns a parameter describing the number of client processes for
+    /// `runner::run_with_parameters`.
+    pub fn parameter(&self) -> (&'static str, String) {
+        ("client_processes", self.num_processes.to_string())
+    }
+
+    /// Hands the results of this process's run to the coordinator. Returns the results of every
+    /// process merged in the coordinator, or `None` in a joined process.
+    pub fn finish(self, mut results: Results) -> io::Result<Option<Results>> {
+        match self.peers {
+            Peers::Alone => Ok(Some(results)),
+            Peers::Joined(peers) => {
+                let mut buf = vec![];
+
+                for mut stream in peers {
+                    if !read_frame(&mut stream, &mut buf)? {
+                        return Err(invalid_message());
+                    }
+
+                    let mut decoder = Decoder::new(&buf);
+
+                    match Results::decode(&mut decoder) {
+                        Some(other) if decoder.is_empty() => results.merge(&other),
+                        _ => return Err(invalid_message()),
+                    }
+                }
+
+                eprintln!(
+                    "{} client processes committed {} transactions per second",
+                    self.num_processes,
+                    results.throughput() as usize
+                );
+
+                Ok(Some(results))
+            }
+            Peers::Coordinator(mut stream) => {
+                let mut buf = vec![];
+                results.encode(&mut buf);
+                write_frame(&mut stream, &buf)?;
+                Ok(None)
+            }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! Writes the conflict graph of a run's `Dibs`, to see which templates the blocking chains run
+//! through.
+
+use clap::{Arg, ArgMatches};
+use dibs::Dibs;
+use std::fs;
+use std::io;
+
+/// Where to write the conflict graph after a run, if anywhere.
+pub struct GraphExport {
+    path: Option<String>,
+}
+
+impl GraphExport {
+    /// Returns the `--conflict-graph` flag.
+    pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![Arg::with_name("conflict_graph")
+            .long("conflict-graph")
+            .takes_value(true)
+            .help(
+                "Writes the templates that may conflict and the waits between them as GraphViz \
+                 DOT, or as JSON if the path ends in .json",
+            )]
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> GraphExport {
+        GraphExport {
+            path: matches.value_of("conflict_graph").map(str::to_string),
+        }
+    }
+
+    /// Counts the waits between templates if the graph will be written.
+    pub fn enable(&self, dibs: &mut Dibs) {
+        if self.path.is_some() {
+            dibs.enable_wait_graph();
+        }
+    }
+
+    pub fn write(&self, dibs: &Dibs) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let graph = dibs.conflict_graph();
+
+        if path.ends_with(".json") {
+            fs::write(path, graph.to_json())
+        } else {
+            fs::write(path, graph.to_dot())
+        }
+    }
+}
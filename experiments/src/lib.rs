@@ -1,12 +1,30 @@
+use dibs::predicate::Value;
 use dibs::{AcquireError, Dibs, OptimizationLevel, Transaction};
+use rand::rngs::StdRng;
 use std::sync::Arc;
 
+pub mod adaptive;
 pub mod benchmarks;
+pub mod breakdown;
+pub mod codec;
+#[cfg(feature = "arrow")]
+pub mod committer;
+pub mod consistency;
+pub mod coordinator;
+pub mod graph;
+pub mod placement;
+pub mod results;
 pub mod runner;
+pub mod seed;
+pub mod server;
 pub mod systems;
+pub mod trace;
 pub mod worker;
+pub mod workload;
 
 pub trait Procedure<C> {
+    /// The name under which the procedure's latencies are reported.
+    fn name(&self) -> &'static str;
     fn is_read_only(&self) -> bool;
     fn execute(
         &self,
@@ -14,11 +32,38 @@ pub trait Procedure<C> {
         transaction: &mut Transaction,
         connection: &mut C,
     ) -> Result<(), AcquireError>;
+
+    /// The requests that the procedure makes whatever it reads, as template IDs and arguments.
+    /// `run` acquires these with `Dibs::acquire_all` before executing the procedure, which then
+    /// acquires only the rest.
+    fn declared_requests(&self) -> Vec<(usize, Vec<Value>)> {
+        vec![]
+    }
+
+    /// Acquires the procedure's declared requests, then executes it.
+    fn run(
+        &self,
+        dibs: &Option<Arc<Dibs>>,
+        transaction: &mut Transaction,
+        connection: &mut C,
+    ) -> Result<(), AcquireError> {
+        if let Some(d) = dibs {
+            let requests = self.declared_requests();
+
+            if !requests.is_empty() {
+                d.acquire_all(transaction, &requests)?;
+            }
+        }
+
+        self.execute(dibs, transaction, connection)
+    }
 }
 
 pub trait Generator {
     type Item;
-    fn next(&self) -> Self::Item;
+
+    /// Generates the next procedure, drawing from `rng`, which is the calling worker's.
+    fn next(&self, rng: &mut StdRng) -> Self::Item;
 }
 
 pub trait Statement {
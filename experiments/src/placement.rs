@@ -0,0 +1,232 @@
+use clap::{Arg, ArgMatches};
+use core_affinity::CoreId;
+use std::fs;
+use std::str::FromStr;
+use std::thread;
+
+/// How worker threads are assigned to cores.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pinning {
+    /// Leaves scheduling to the operating system.
+    Unpinned,
+
+    /// Pins workers to cores in the order the operating system lists them.
+    Sequential,
+
+    /// Pins workers to cores taken from each socket in turn, spreading them across sockets.
+    Interleaved,
+
+    /// Pins workers to the given core IDs in order.
+    Cores(Vec<usize>),
+}
+
+/// Where the runner places worker threads and where the database is loaded.
+#[derive(Clone, Debug)]
+pub struct Placement {
+    pub pinning: Pinning,
+
+    /// The number of cores, taken from the front of the pinning order, that run the loading
+    /// thread and no workers. Ignored if the workers are unpinned.
+    pub reserved_cores: usize,
+
+    /// The socket whose cores load the database. Since memory is placed on the socket that first
+    /// touches it, this puts tables built by the loader, such as the Arrow columns, in that
+    /// socket's memory.
+    pub load_socket: Option<usize>,
+}
+
+impl Default for Placement {
+    fn default() -> Placement {
+        Placement {
+            pinning: Pinning::Sequential,
+            reserved_cores: 0,
+            load_socket: None,
+        }
+    }
+}
+
+impl Placement {
+    /// Returns the `--pinning`, `--cores`, `--reserve-cores` and `--load-socket` flags.
+    pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![
+            Arg::with_name("pinning")
+                .long("pinning")
+                .possible_values(&["none", "sequential", "interleaved"])
+                .takes_value(true)
+                .conflicts_with("cores")
+                .help("How workers are pinned to cores, defaults to sequential"),
+            Arg::with_name("cores")
+                .long("cores")
+                .takes_value(true)
+                .help("Comma-separated core IDs to pin workers to, in order"),
+            Arg::with_name("reserve_cores")
+                .long("reserve-cores")
+                .takes_value(true)
+                .help("Cores to reserve for loading the database, defaults to 0"),
+            Arg::with_name("load_socket")
+                .long("load-socket")
+                .takes_value(true)
+                .help("Loads the database on this socket so that its memory is allocated there"),
+        ]
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> Placement {
+        let pinning = match matches.value_of("cores") {
+            Some(cores) => Pinning::Cores(
+                cores
+                    .split(',')
+                    .map(|core| usize::from_str(core.trim()).unwrap())
+                    .collect(),
+            ),
+            None => match matches.value_of("pinning").unwrap_or("sequential") {
+                "none" => Pinning::Unpinned,
+                "sequential" => Pinning::Sequential,
+                "interleaved" => Pinning::Interleaved,
+                _ => unreachable!(),
+            },
+        };
+
+        Placement {
+            pinning,
+            reserved_cores: matches
+                .value_of("reserve_cores")
+                .map_or(0, |reserved_cores| usize::from_str(reserved_cores).unwrap()),
+            load_socket: matches
+                .value_of("load_socket")
+                .map(|socket| usize::from_str(socket).unwrap()),
+        }
+    }
+
+    /// Returns the cores in pinning order, including the reserved ones.
+    fn ordered_cores(&self) -> Vec<CoreId> {
+        match &self.pinning {
+            Pinning::Unpinned => vec![],
+            Pinning::Sequential => core_affinity::get_core_ids().unwrap(),
+            Pinning::Interleaved => interleave(core_affinity::get_core_ids().unwrap()),
+            Pinning::Cores(ids) => ids.iter().map(|&id| CoreId { id }).collect(),
+        }
+    }
+
+    /// Returns the cores to pin workers to, which the runner cycles through, or `None` if the
+    /// workers are unpinned.
+    pub fn worker_cores(&self) -> Option<Vec<CoreId>> {
+        if self.pinning == Pinning::Unpinned {
+            return None;
+        }
+
+        let cores = self.ordered_cores();
+
+        assert!(
+            self.reserved_cores < cores.len(),
+            "cannot reserve {} of {} cores",
+            self.reserved_cores,
+            cores.len()
+        );
+
+        Some(cores[self.reserved_cores..].to_vec())
+    }
+
+    /// Returns the core that the loading thread is pinned to, if any. This is the first reserved
+    /// core on the load socket, or if no cores are reserved, the first core on the load socket.
+    fn loader_core(&self) -> Option<CoreId> {
+        let reserved = self
+            .ordered_cores()
+            .into_iter()
+            .take(self.reserved_cores)
+            .collect::<Vec<_>>();
+
+        match self.load_socket {
+            Some(socket) => {
+                let candidates = if reserved.is_empty() {
+                    core_affinity::get_core_ids().unwrap()
+                } else {
+                    reserved
+                };
+
+                let core = candidates
+                    .into_iter()
+                    .find(|core| socket_of(*core) == socket)
+                    .unwrap_or_else(|| panic!("no core to load on socket {}", socket));
+
+                Some(core)
+            }
+            None => reserved.into_iter().next(),
+        }
+    }
+
+    /// Runs `load` on a thread pinned to the loader core, or on the current thread if there is
+    /// none.
+    pub fn load<T, F>(&self, load: F) -> T
+    where
+        T: 'static + Send,
+        F: 'static + FnOnce() -> T + Send,
+    {
+        match self.loader_core() {
+            Some(core) => thread::spawn(move || {
+                core_affinity::set_for_current(core);
+                load()
+            })
+            .join()
+            .unwrap(),
+            None => load(),
+        }
+    }
+
+    /// Returns a parameter describing the placement for `runner::run_with_parameters`.
+    pub fn parameter(&self) -> (&'static str, String) {
+        let pinning = match &self.pinning {
+            Pinning::Unpinned => "none".to_string(),
+            Pinning::Sequential => "sequential".to_string(),
+            Pinning::Interleaved => "interleaved".to_string(),
+            Pinning::Cores(ids) => ids
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(":"),
+        };
+
+        ("pinning", pinning)
+    }
+}
+
+/// Returns the socket of a core, or 0 if the topology is unavailable.
+fn socket_of(core: CoreId) -> usize {
+    fs::read_to_string(format!(
+        "/sys/devices/system/cpu/cpu{}/topology/physical_package_id",
+        core.id
+    ))
+    .ok()
+    .and_then(|socket| usize::from_str(socket.trim()).ok())
+    .unwrap_or(0)
+}
+
+/// Orders cores by taking one from each socket in turn.
+fn interleave(cores: Vec<CoreId>) -> Vec<CoreId> {
+    let mut sockets: Vec<(usize, Vec<CoreId>)> = vec![];
+
+    for core in cores {
+        let socket = socket_of(core);
+
+        match sockets.iter_mut().find(|(id, _)| *id == socket) {
+            Some((_, socket_cores)) => socket_cores.push(core),
+            None => sockets.push((socket, vec![core])),
+        }
+    }
+
+    let num_cores = sockets.iter().map(|(_, cores)| cores.len()).sum();
+    let mut interleaved = Vec::with_capacity(num_cores);
+
+    for i in 0.. {
+        if interleaved.len() == num_cores {
+            break;
+        }
+
+        for (_, socket_cores) in &sockets {
+            if let Some(&core) = socket_cores.get(i) {
+                interleaved.push(core);
+            }
+        }
+    }
+
+    interleaved
+}
@@ -0,0 +1,985 @@
+use crate::codec;
+use crate::codec::Decoder;
+use crate::consistency::Consistency;
+use dibs::ConflictStats;
+use fnv::FnvHashMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::{fs, io, mem};
+
+const SUB_BUCKET_BITS: u32 = 5;
+const NUM_SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+const NUM_BUCKETS: usize = (64 - SUB_BUCKET_BITS as usize + 1) * NUM_SUB_BUCKETS;
+
+/// A log-linear histogram of nanosecond latencies. Each power of two is split into 32 buckets, so
+/// percentiles are accurate to within about 3% while memory stays constant regardless of the
+/// number of transactions.
+#[derive(Clone)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    max: u64,
+}
+
+impl Histogram {
+    pub fn new() -> Histogram {
+        Histogram {
+            counts: vec![0; NUM_BUCKETS],
+            count: 0,
+            max: 0,
+        }
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        let nanos = latency.as_nanos().min(u64::MAX as u128) as u64;
+        self.counts[Histogram::index(nanos)] += 1;
+        self.count += 1;
+        self.max = self.max.max(nanos);
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other_count) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other_count;
+        }
+
+        self.count += other.count;
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// Returns the upper bound of the bucket containing the `quantile`th latency, in nanoseconds.
+    pub fn percentile(&self, quantile: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+
+        let rank = ((quantile * self.count as f64).ceil() as u64).max(1);
+        let mut cumulative = 0;
+
+        for (index, count) in self.counts.iter().enumerate() {
+            cumulative += count;
+
+            if cumulative >= rank {
+                return Histogram::upper_bound(index).min(self.max);
+            }
+        }
+
+        self.max
+    }
+
+    /// Appends the histogram's nonempty buckets.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let buckets = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .collect::<Vec<_>>();
+
+        codec::put_u64(buf, self.max);
+        codec::put_u32(buf, buckets.len() as u32);
+
+        for (index, &count) in buckets {
+            codec::put_u32(buf, index as u32);
+            codec::put_u64(buf, count);
+        }
+    }
+
+    pub fn decode(decoder: &mut Decoder) -> Option<Histogram> {
+        let mut histogram = Histogram::new();
+        histogram.max = decoder.u64()?;
+
+        for _ in 0..decoder.u32()? {
+            let index = decoder.u32()? as usize;
+            let count = decoder.u64()?;
+
+            *histogram.counts.get_mut(index)? += count;
+            histogram.count += count;
+        }
+
+        Some(histogram)
+    }
+
+    fn index(nanos: u64) -> usize {
+        if nanos < NUM_SUB_BUCKETS as u64 {
+            return nanos as usize;
+        }
+
+        let shift = 63 - nanos.leading_zeros() - SUB_BUCKET_BITS;
+        let sub_bucket = (nanos >> shift) as usize & (NUM_SUB_BUCKETS - 1);
+
+        (shift as usize + 1) * NUM_SUB_BUCKETS + sub_bucket
+    }
+
+    fn upper_bound(index: usize) -> u64 {
+        if index < NUM_SUB_BUCKETS {
+            return index as u64;
+        }
+
+        let shift = (index / NUM_SUB_BUCKETS - 1) as u32;
+        let sub_bucket = (index % NUM_SUB_BUCKETS) as u128;
+        let bound = ((NUM_SUB_BUCKETS as u128 + sub_bucket + 1) << shift) - 1;
+
+        bound.min(u64::MAX as u128) as u64
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Histogram {
+        Histogram::new()
+    }
+}
+
+/// How the latencies of a procedure's committed transactions split between acquiring requests
+/// from dibs, including blocking, and the rest of executing the procedure, which is mostly the
+/// connection's data operations. Both span every attempt of a transaction but not its backoffs.
+#[derive(Clone, Default)]
+pub struct Breakdown {
+    pub acquire: Histogram,
+    pub execution: Histogram,
+}
+
+impl Breakdown {
+    pub fn merge(&mut self, other: &Breakdown) {
+        self.acquire.merge(&other.acquire);
+        self.execution.merge(&other.execution);
+    }
+}
+
+/// Collects the outcomes of one worker's transactions. The counters cover the whole run so that the
+/// runner can sample them over time, but latencies are only recorded while the shared `measuring`
+/// flag is set, so transactions during warmup and cooldown are ignored.
+pub struct Recorder {
+    measuring: Arc<AtomicBool>,
+    commits: AtomicUsize,
+    aborts: AtomicUsize,
+    retried_commits: AtomicUsize,
+    give_ups: AtomicUsize,
+    dropped: AtomicUsize,
+    latencies: Mutex<FnvHashMap<&'static str, Histogram>>,
+    breakdowns: Mutex<FnvHashMap<&'static str, Breakdown>>,
+    queueing: Mutex<Histogram>,
+}
+
+impl Recorder {
+    pub fn new(measuring: Arc<AtomicBool>) -> Recorder {
+        Recorder {
+            measuring,
+            commits: AtomicUsize::new(0),
+            aborts: AtomicUsize::new(0),
+            retried_commits: AtomicUsize::new(0),
+            give_ups: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+            latencies: Mutex::new(FnvHashMap::default()),
+            breakdowns: Mutex::new(FnvHashMap::default()),
+            queueing: Mutex::new(Histogram::new()),
+        }
+    }
+
+    fn is_measuring(&self) -> bool {
+        self.measuring.load(Ordering::Relaxed)
+    }
+
+    /// Records a committed transaction. Its latency spans every attempt, including backoff, and
+    /// `retried` is whether it was aborted at least once first.
+    pub fn commit(&self, procedure: &'static str, latency: Duration, retried: bool) {
+        self.commits.fetch_add(1, Ordering::Relaxed);
+
+        if retried {
+            self.retried_commits.fetch_add(1, Ordering::Relaxed);
+        }
+
+        if !self.is_measuring() {
+            return;
+        }
+
+        self.latencies
+            .lock()
+            .unwrap()
+            .entry(procedure)
+            .or_default()
+            .record(latency);
+    }
+
+    /// Records how the time of a committed transaction split between acquiring and executing.
+    pub fn breakdown(&self, procedure: &'static str, acquire: Duration, execution: Duration) {
+        if !self.is_measuring() {
+            return;
+        }
+
+        let mut breakdowns = self.breakdowns.lock().unwrap();
+        let breakdown = breakdowns.entry(procedure).or_default();
+        breakdown.acquire.record(acquire);
+        breakdown.execution.record(execution);
+    }
+
+    pub fn abort(&self) {
+        self.aborts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a transaction that was abandoned after exhausting its retries.
+    pub fn give_up(&self) {
+        self.give_ups.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records how long an open-loop transaction waited between its arrival and its start.
+    pub fn queued(&self, delay: Duration) {
+        if !self.is_measuring() {
+            return;
+        }
+
+        self.queueing.lock().unwrap().record(delay);
+    }
+
+    /// Records an open-loop arrival that was discarded because the queue was full.
+    pub fn drop_arrival(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn commits(&self) -> usize {
+        self.commits.load(Ordering::Relaxed)
+    }
+
+    pub fn aborts(&self) -> usize {
+        self.aborts.load(Ordering::Relaxed)
+    }
+
+    pub fn retried_commits(&self) -> usize {
+        self.retried_commits.load(Ordering::Relaxed)
+    }
+
+    pub fn give_ups(&self) -> usize {
+        self.give_ups.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn take_latencies(&self) -> FnvHashMap<&'static str, Histogram> {
+        mem::take(&mut *self.latencies.lock().unwrap())
+    }
+
+    pub fn take_breakdowns(&self) -> FnvHashMap<&'static str, Breakdown> {
+        mem::take(&mut *self.breakdowns.lock().unwrap())
+    }
+
+    pub fn take_queueing(&self) -> Histogram {
+        mem::take(&mut *self.queueing.lock().unwrap())
+    }
+}
+
+/// The transactions completed during one interval of a run.
+#[derive(Clone, Debug)]
+pub struct Sample {
+    /// The end of the interval, relative to the start of the run.
+    pub time: Duration,
+    pub duration: Duration,
+    pub phase: &'static str,
+    pub commits: usize,
+    pub aborts: usize,
+    /// The number of workers that committed or aborted a transaction during the interval.
+    pub active_workers: usize,
+}
+
+/// The measurements of one run, taken over the measurement window only except for the timeseries,
+/// which covers every phase.
+pub struct Results {
+    pub parameters: Vec<(String, String)>,
+    pub duration: Duration,
+    pub commits: usize,
+    /// Failed attempts, including those that were later retried.
+    pub aborts: usize,
+    /// Commits that needed at least one retry.
+    pub retried_commits: usize,
+    /// Transactions abandoned after exhausting their retries.
+    pub give_ups: usize,
+    /// Open-loop arrivals discarded because a worker's queue was full.
+    pub dropped: usize,
+    /// Service latencies by procedure name, sorted by name. These exclude queueing delay.
+    pub latencies: Vec<(&'static str, Histogram)>,
+    /// The breakdowns of the latencies by procedure name, sorted by name. Empty unless the run
+    /// measured them.
+    pub breakdowns: Vec<(&'static str, Breakdown)>,
+    /// The delay between each open-loop transaction's arrival and its start. Empty in closed-loop
+    /// runs.
+    pub queueing: Histogram,
+    pub conflicts: Option<ConflictStats>,
+    /// The invariants checked on the database once the workers stopped, if they were checked.
+    pub consistency: Option<Consistency>,
+    pub timeseries: Vec<Sample>,
+}
+
+impl Results {
+    pub fn throughput(&self) -> f64 {
+        self.commits as f64 / self.duration.as_secs_f64()
+    }
+
+    /// Returns the fraction of attempted transactions that aborted.
+    pub fn abort_rate(&self) -> f64 {
+        match self.commits + self.aborts {
+            0 => 0.0,
+            attempts => self.aborts as f64 / attempts as f64,
+        }
+    }
+
+    /// Returns the fraction of aborted transactions that eventually committed.
+    pub fn retried_success_rate(&self) -> f64 {
+        match self.retried_commits + self.give_ups {
+            0 => 0.0,
+            retried => self.retried_commits as f64 / retried as f64,
+        }
+    }
+
+    /// Adds the transactions of `other`, a run over the same phases by other workers, such as
+    /// those of another client process. Conflicts and consistency are left as they are, since
+    /// they describe the database rather than the workers.
+    pub fn merge(&mut self, other: &Results) {
+        self.commits += other.commits;
+        self.aborts += other.aborts;
+        self.retried_commits += other.retried_commits;
+        self.give_ups += other.give_ups;
+        self.dropped += other.dropped;
+        self.queueing.merge(&other.queueing);
+
+        for (name, histogram) in &other.latencies {
+            match self
+                .latencies
+                .iter_mut()
+                .find(|(other_name, _)| other_name == name)
+            {
+                Some((_, merged)) => merged.merge(histogram),
+                None => self.latencies.push((name, histogram.clone())),
+            }
+        }
+
+        self.latencies.sort_by_key(|&(name, _)| name);
+
+        for (name, breakdown) in &other.breakdowns {
+            match self
+                .breakdowns
+                .iter_mut()
+                .find(|(other_name, _)| other_name == name)
+            {
+                Some((_, merged)) => merged.merge(breakdown),
+                None => self.breakdowns.push((name, breakdown.clone())),
+            }
+        }
+
+        self.breakdowns.sort_by_key(|&(name, _)| name);
+
+        // The runs started together, so their samples line up.
+        for (i, sample) in other.timeseries.iter().enumerate() {
+            match self.timeseries.get_mut(i) {
+                Some(merged) => {
+                    merged.commits += sample.commits;
+                    merged.aborts += sample.aborts;
+                    merged.active_workers += sample.active_workers;
+                }
+                None => self.timeseries.push(sample.clone()),
+            }
+        }
+    }
+
+    /// Appends what `merge` needs of the results.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        codec::put_u64(buf, self.duration.as_nanos() as u64);
+
+        for &count in &[
+            self.commits,
+            self.aborts,
+            self.retried_commits,
+            self.give_ups,
+            self.dropped,
+        ] {
+            codec::put_u64(buf, count as u64);
+        }
+
+        codec::put_u32(buf, self.latencies.len() as u32);
+
+        for (name, histogram) in &self.latencies {
+            codec::put_bytes(buf, name.as_bytes());
+            histogram.encode(buf);
+        }
+
+        codec::put_u32(buf, self.breakdowns.len() as u32);
+
+        for (name, breakdown) in &self.breakdowns {
+            codec::put_bytes(buf, name.as_bytes());
+            breakdown.acquire.encode(buf);
+            breakdown.execution.encode(buf);
+        }
+
+        self.queueing.encode(buf);
+        codec::put_u32(buf, self.timeseries.len() as u32);
+
+        for sample in &self.timeseries {
+            codec::put_u64(buf, sample.time.as_nanos() as u64);
+            codec::put_u64(buf, sample.duration.as_nanos() as u64);
+            codec::put_bytes(buf, sample.phase.as_bytes());
+            codec::put_u64(buf, sample.commits as u64);
+            codec::put_u64(buf, sample.aborts as u64);
+            codec::put_u64(buf, sample.active_workers as u64);
+        }
+    }
+
+    /// Decodes results written by `encode`, without parameters, conflicts or consistency.
+    pub fn decode(decoder: &mut Decoder) -> Option<Results> {
+        let duration = Duration::from_nanos(decoder.u64()?);
+        let commits = decoder.u64()? as usize;
+        let aborts = decoder.u64()? as usize;
+        let retried_commits = decoder.u64()? as usize;
+        let give_ups = decoder.u64()? as usize;
+        let dropped = decoder.u64()? as usize;
+
+        let mut latencies = vec![];
+
+        for _ in 0..decoder.u32()? {
+            latencies.push((leak_name(decoder)?, Histogram::decode(decoder)?));
+        }
+
+        let mut breakdowns = vec![];
+
+        for _ in 0..decoder.u32()? {
+            let name = leak_name(decoder)?;
+
+            breakdowns.push((
+                name,
+                Breakdown {
+                    acquire: Histogram::decode(decoder)?,
+                    execution: Histogram::decode(decoder)?,
+                },
+            ));
+        }
+
+        let queueing = Histogram::decode(decoder)?;
+        let mut timeseries = vec![];
+
+        for _ in 0..decoder.u32()? {
+            let time = Duration::from_nanos(decoder.u64()?);
+            let duration = Duration::from_nanos(decoder.u64()?);
+            let phase = match decoder.bytes()? {
+                b"warmup" => "warmup",
+                b"measurement" => "measurement",
+                b"cooldown" => "cooldown",
+                _ => return None,
+            };
+
+            timeseries.push(Sample {
+                time,
+                duration,
+                phase,
+                commits: decoder.u64()? as usize,
+                aborts: decoder.u64()? as usize,
+                active_workers: decoder.u64()? as usize,
+            });
+        }
+
+        Some(Results {
+            parameters: vec![],
+            duration,
+            commits,
+            aborts,
+            retried_commits,
+            give_ups,
+            dropped,
+            latencies,
+            breakdowns,
+            queueing,
+            conflicts: None,
+            consistency: None,
+            timeseries,
+        })
+    }
+
+    fn overall_latency(&self) -> Histogram {
+        let mut overall = Histogram::new();
+
+        for (_, histogram) in &self.latencies {
+            overall.merge(histogram);
+        }
+
+        overall
+    }
+
+    /// Returns the breakdowns by procedure followed by their merged `all` breakdown, or nothing if
+    /// the run didn't measure them.
+    fn procedure_breakdowns(&self) -> Vec<(&'static str, Breakdown)> {
+        if self.breakdowns.is_empty() {
+            return vec![];
+        }
+
+        let mut overall = Breakdown::default();
+
+        for (_, breakdown) in &self.breakdowns {
+            overall.merge(breakdown);
+        }
+
+        let mut breakdowns = self.breakdowns.clone();
+        breakdowns.push(("all", overall));
+        breakdowns
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+
+        json.push_str("{\n  \"parameters\": {");
+        for (i, (name, value)) in self.parameters.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(json, "{}\n    {}: {}", separator, quote(name), quote(value)).unwrap();
+        }
+        if !self.parameters.is_empty() {
+            json.push_str("\n  ");
+        }
+        json.push_str("},\n");
+
+        writeln!(
+            json,
+            "  \"duration_secs\": {},",
+            self.duration.as_secs_f64()
+        )
+        .unwrap();
+        writeln!(json, "  \"commits\": {},", self.commits).unwrap();
+        writeln!(json, "  \"aborts\": {},", self.aborts).unwrap();
+        writeln!(json, "  \"retried_commits\": {},", self.retried_commits).unwrap();
+        writeln!(json, "  \"give_ups\": {},", self.give_ups).unwrap();
+        writeln!(json, "  \"dropped\": {},", self.dropped).unwrap();
+        writeln!(json, "  \"throughput\": {},", self.throughput()).unwrap();
+        writeln!(json, "  \"abort_rate\": {},", self.abort_rate()).unwrap();
+        writeln!(
+            json,
+            "  \"retried_success_rate\": {},",
+            self.retried_success_rate()
+        )
+        .unwrap();
+
+        json.push_str("  \"latency_ns\": {");
+        let overall = self.overall_latency();
+        let procedures = self
+            .latencies
+            .iter()
+            .map(|(name, histogram)| (*name, histogram))
+            .chain(Some(("all", &overall)));
+        for (i, (name, histogram)) in procedures.enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(
+                json,
+                "{}\n    {}: {{\"count\": {}, \"p50\": {}, \"p95\": {}, \"p99\": {}, \"max\": {}}}",
+                separator,
+                quote(name),
+                histogram.count(),
+                histogram.percentile(0.50),
+                histogram.percentile(0.95),
+                histogram.percentile(0.99),
+                histogram.max()
+            )
+            .unwrap();
+        }
+        json.push_str("\n  },\n");
+
+        json.push_str("  \"breakdown_ns\": {");
+        let breakdowns = self.procedure_breakdowns();
+        for (i, (name, breakdown)) in breakdowns.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(
+                json,
+                "{}\n    {}: {{\"acquire\": {}, \"execution\": {}}}",
+                separator,
+                quote(name),
+                histogram_json(&breakdown.acquire),
+                histogram_json(&breakdown.execution)
+            )
+            .unwrap();
+        }
+        if !breakdowns.is_empty() {
+            json.push_str("\n  ");
+        }
+        json.push_str("},\n");
+
+        writeln!(
+            json,
+            "  \"queueing_ns\": {{\"count\": {}, \"p50\": {}, \"p95\": {}, \"p99\": {}, \
+             \"max\": {}}},",
+            self.queueing.count(),
+            self.queueing.percentile(0.50),
+            self.queueing.percentile(0.95),
+            self.queueing.percentile(0.99),
+            self.queueing.max()
+        )
+        .unwrap();
+
+        match &self.conflicts {
+            Some(conflicts) => writeln!(
+                json,
+                "  \"conflicts\": {{\"waits\": {}, \"timeouts\": {}, \"group_conflicts\": {}, \
+                 \"preemptions\": {}, \"pruned\": {}, \"key_filter_checks\": {}, \
+                 \"key_filter_skips\": {}, \"hot_key_promotions\": {}, \"hot_key_solves\": {}, \
+                 \"admission_waits\": {}, \"admission_wait_ns\": {}, \"shared_reads\": {}}},",
+                conflicts.num_waits,
+                conflicts.num_timeouts,
+                conflicts.num_group_conflicts,
+                conflicts.num_preemptions,
+                conflicts.num_pruned,
+                conflicts.num_key_filter_checks,
+                conflicts.num_key_filter_skips,
+                conflicts.num_hot_key_promotions,
+                conflicts.num_hot_key_solves,
+                conflicts.num_admission_waits,
+                conflicts.admission_wait_nanos,
+                conflicts.num_shared_reads
+            )
+            .unwrap(),
+            None => json.push_str("  \"conflicts\": null,\n"),
+        }
+
+        match &self.consistency {
+            Some(consistency) => {
+                write!(
+                    json,
+                    "  \"consistency\": {{\"violations\": {}, \"checks\": {{",
+                    consistency.violations()
+                )
+                .unwrap();
+                for (i, check) in consistency.checks.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { ", " };
+                    write!(
+                        json,
+                        "{}{}: {}",
+                        separator,
+                        quote(&check.name),
+                        check.violations
+                    )
+                    .unwrap();
+                }
+                json.push_str("}},\n");
+            }
+            None => json.push_str("  \"consistency\": null,\n"),
+        }
+
+        json.push_str("  \"timeseries\": [");
+        for (i, sample) in self.timeseries.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(
+                json,
+                "{}\n    {{\"time\": {}, \"duration\": {}, \"phase\": {}, \"commits\": {}, \
+                 \"aborts\": {}, \"active_workers\": {}}}",
+                separator,
+                sample.time.as_secs_f64(),
+                sample.duration.as_secs_f64(),
+                quote(sample.phase),
+                sample.commits,
+                sample.aborts,
+                sample.active_workers
+            )
+            .unwrap();
+        }
+        if !self.timeseries.is_empty() {
+            json.push_str("\n  ");
+        }
+        json.push_str("]\n");
+
+        json.push_str("}\n");
+        json
+    }
+
+    /// Formats the results as CSV with one row per procedure, plus an `all` row. Every row repeats
+    /// the parameters and run-wide measurements so that files from several runs can be
+    /// concatenated.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+
+        let mut header = self
+            .parameters
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        header.extend(&[
+            "throughput",
+            "abort_rate",
+            "retried_success_rate",
+            "give_ups",
+            "dropped",
+            "queueing_p50_ns",
+            "queueing_p95_ns",
+            "queueing_p99_ns",
+            "queueing_max_ns",
+            "conflict_waits",
+            "conflict_timeouts",
+            "group_conflicts",
+            "preemptions",
+            "pruned",
+            "key_filter_checks",
+            "key_filter_skips",
+            "hot_key_promotions",
+            "hot_key_solves",
+            "admission_waits",
+            "admission_wait_ns",
+            "shared_reads",
+            "consistency_violations",
+            "procedure",
+            "count",
+            "p50_ns",
+            "p95_ns",
+            "p99_ns",
+            "max_ns",
+            "acquire_p50_ns",
+            "acquire_p99_ns",
+            "execution_p50_ns",
+            "execution_p99_ns",
+        ]);
+        writeln!(csv, "{}", header.join(",")).unwrap();
+
+        let mut run_columns = self
+            .parameters
+            .iter()
+            .map(|(_, value)| escape_csv(value))
+            .collect::<Vec<_>>();
+        run_columns.push(self.throughput().to_string());
+        run_columns.push(self.abort_rate().to_string());
+        run_columns.push(self.retried_success_rate().to_string());
+        run_columns.push(self.give_ups.to_string());
+        run_columns.push(self.dropped.to_string());
+        run_columns.extend(
+            [
+                self.queueing.percentile(0.50),
+                self.queueing.percentile(0.95),
+                self.queueing.percentile(0.99),
+                self.queueing.max(),
+            ]
+            .iter()
+            .map(|nanos| nanos.to_string()),
+        );
+        match &self.conflicts {
+            Some(conflicts) => run_columns.extend(
+                [
+                    conflicts.num_waits,
+                    conflicts.num_timeouts,
+                    conflicts.num_group_conflicts,
+                    conflicts.num_preemptions,
+                    conflicts.num_pruned,
+                    conflicts.num_key_filter_checks,
+                    conflicts.num_key_filter_skips,
+                    conflicts.num_hot_key_promotions,
+                    conflicts.num_hot_key_solves,
+                    conflicts.num_admission_waits,
+                    conflicts.admission_wait_nanos,
+                    conflicts.num_shared_reads,
+                ]
+                .iter()
+                .map(|count| count.to_string()),
+            ),
+            None => run_columns.extend(vec![String::new(); 12]),
+        }
+        run_columns.push(
+            self.consistency
+                .as_ref()
+                .map_or(String::new(), |consistency| {
+                    consistency.violations().to_string()
+                }),
+        );
+        let run_columns = run_columns.join(",");
+
+        let overall = self.overall_latency();
+        let procedures = self
+            .latencies
+            .iter()
+            .map(|(name, histogram)| (*name, histogram))
+            .chain(Some(("all", &overall)));
+        let breakdowns = self.procedure_breakdowns();
+        for (name, histogram) in procedures {
+            let breakdown = match breakdowns
+                .iter()
+                .find(|(other_name, _)| *other_name == name)
+            {
+                Some((_, breakdown)) => [
+                    breakdown.acquire.percentile(0.50),
+                    breakdown.acquire.percentile(0.99),
+                    breakdown.execution.percentile(0.50),
+                    breakdown.execution.percentile(0.99),
+                ]
+                .iter()
+                .map(|nanos| nanos.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+                None => ",,,".to_string(),
+            };
+
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{}",
+                run_columns,
+                escape_csv(name),
+                histogram.count(),
+                histogram.percentile(0.50),
+                histogram.percentile(0.95),
+                histogram.percentile(0.99),
+                histogram.max(),
+                breakdown
+            )
+            .unwrap();
+        }
+
+        csv
+    }
+
+    /// Formats the timeseries as CSV with one row per sample.
+    pub fn timeseries_csv(&self) -> String {
+        let mut csv = String::from("time,duration,phase,commits,aborts,active_workers\n");
+
+        for sample in &self.timeseries {
+            writeln!(
+                csv,
+                "{},{},{},{},{},{}",
+                sample.time.as_secs_f64(),
+                sample.duration.as_secs_f64(),
+                sample.phase,
+                sample.commits,
+                sample.aborts,
+                sample.active_workers
+            )
+            .unwrap();
+        }
+
+        csv
+    }
+
+    /// Writes the results to `path`, as CSV if it ends in `.csv` and as JSON otherwise. CSV output
+    /// writes the timeseries to a second file, with `.timeseries.csv` in place of `.csv`.
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        if let Some(stem) = path.strip_suffix(".csv") {
+            fs::write(path, self.to_csv())?;
+            fs::write(format!("{}.timeseries.csv", stem), self.timeseries_csv())
+        } else {
+            fs::write(path, self.to_json())
+        }
+    }
+}
+
+/// Decodes a procedure name. Names are static in the process that recorded them, and there are a
+/// handful per benchmark, so leaking them is cheaper than making every name owned.
+fn leak_name(decoder: &mut Decoder) -> Option<&'static str> {
+    Some(Box::leak(decoder.string()?.into_boxed_str()))
+}
+
+fn histogram_json(histogram: &Histogram) -> String {
+    format!(
+        "{{\"count\": {}, \"p50\": {}, \"p95\": {}, \"p99\": {}, \"max\": {}}}",
+        histogram.count(),
+        histogram.percentile(0.50),
+        histogram.percentile(0.95),
+        histogram.percentile(0.99),
+        histogram.max()
+    )
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+fn escape_csv(s: &str) -> String {
+    if s.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn histogram(nanos: impl IntoIterator<Item = u64>) -> Histogram {
+        let mut histogram = Histogram::new();
+
+        for nanos in nanos {
+            histogram.record(Duration::from_nanos(nanos));
+        }
+
+        histogram
+    }
+
+    /// Checks that `percentile` is within the bucket error of `expected`, which it never undercuts.
+    fn assert_close(percentile: u64, expected: u64) {
+        assert!(
+            percentile >= expected && percentile as f64 <= expected as f64 * 1.04,
+            "{} is not close to {}",
+            percentile,
+            expected
+        );
+    }
+
+    #[test]
+    fn empty_histograms_report_zero() {
+        let histogram = Histogram::new();
+
+        assert_eq!(histogram.count(), 0);
+        assert_eq!(histogram.max(), 0);
+
+        for &quantile in &[0.0, 0.5, 0.99, 1.0] {
+            assert_eq!(histogram.percentile(quantile), 0);
+        }
+    }
+
+    #[test]
+    fn small_latencies_are_exact() {
+        let histogram = histogram(1..=31);
+
+        assert_eq!(histogram.percentile(0.0), 1);
+        assert_eq!(histogram.percentile(0.5), 16);
+        assert_eq!(histogram.percentile(0.99), 31);
+        assert_eq!(histogram.percentile(1.0), 31);
+    }
+
+    #[test]
+    fn large_latencies_are_within_a_bucket() {
+        let histogram = histogram((1..=1000).map(|micros| micros * 1000));
+
+        assert_eq!(histogram.count(), 1000);
+        assert_close(histogram.percentile(0.0), 1000);
+        assert_close(histogram.percentile(0.5), 500_000);
+        assert_close(histogram.percentile(0.99), 990_000);
+
+        // The highest bucket is capped at the largest latency recorded.
+        assert_eq!(histogram.percentile(1.0), 1_000_000);
+        assert_eq!(histogram.max(), 1_000_000);
+    }
+
+    #[test]
+    fn merged_histograms_match_one_recording_everything() {
+        let mut merged = histogram((1..=500).map(|micros| micros * 1000));
+        merged.merge(&histogram((501..=1000).map(|micros| micros * 1000)));
+        merged.merge(&Histogram::new());
+
+        let whole = histogram((1..=1000).map(|micros| micros * 1000));
+
+        assert_eq!(merged.count(), whole.count());
+        assert_eq!(merged.max(), whole.max());
+
+        for &quantile in &[0.0, 0.5, 0.99, 1.0] {
+            assert_eq!(merged.percentile(quantile), whole.percentile(quantile));
+        }
+    }
+}
@@ -1,48 +1,147 @@
+use crate::placement::Placement;
+use crate::results::{Breakdown, Histogram, Recorder, Results, Sample};
 use crate::worker::Worker;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use clap::{Arg, ArgMatches};
+use dibs::{ConflictStats, Dibs};
+use fnv::FnvHashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-pub fn run(workers: Vec<Box<dyn Worker + Send>>) {
-    let warmup_duration = Duration::from_secs(10);
-    let measurement_duration = Duration::from_secs(60);
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
 
-    let commit_counters = (0..workers.len())
-        .map(|_| Arc::new(AtomicUsize::new(0)))
+/// How long the workers run in each phase of an experiment. Only the measurement phase is recorded.
+#[derive(Clone, Copy, Debug)]
+pub struct Phases {
+    /// Lets caches and the database reach a steady state after loading.
+    pub warmup: Duration,
+    pub measurement: Duration,
+    /// Keeps the workers running after the measurement so that transactions in flight at its end
+    /// complete under the same load, then stops them.
+    pub cooldown: Duration,
+}
+
+impl Default for Phases {
+    fn default() -> Phases {
+        Phases {
+            warmup: Duration::from_secs(10),
+            measurement: Duration::from_secs(60),
+            cooldown: Duration::from_secs(1),
+        }
+    }
+}
+
+impl Phases {
+    /// Returns the `--warmup`, `--measurement` and `--cooldown` flags, which take seconds.
+    pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![
+            Arg::with_name("warmup")
+                .long("warmup")
+                .takes_value(true)
+                .help("Seconds to run before measuring, defaults to 10"),
+            Arg::with_name("measurement")
+                .long("measurement")
+                .takes_value(true)
+                .help("Seconds to measure for, defaults to 60"),
+            Arg::with_name("cooldown")
+                .long("cooldown")
+                .takes_value(true)
+                .help("Seconds to run after measuring before stopping, defaults to 1"),
+        ]
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> Phases {
+        let seconds = |name, default: Duration| {
+            matches.value_of(name).map_or(default, |value| {
+                Duration::from_secs_f64(f64::from_str(value).unwrap())
+            })
+        };
+
+        let default = Phases::default();
+
+        let phases = Phases {
+            warmup: seconds("warmup", default.warmup),
+            measurement: seconds("measurement", default.measurement),
+            cooldown: seconds("cooldown", default.cooldown),
+        };
+
+        assert!(phases.measurement > Duration::from_secs(0));
+
+        phases
+    }
+}
+
+pub fn run(workers: Vec<Box<dyn Worker + Send>>) -> Results {
+    run_with_parameters(workers, Phases::default(), &Placement::default(), &[], None)
+}
+
+/// Runs the workers like `run`, first printing the given experiment parameters as `name=value`
+/// pairs on their own line so that results can be matched to the configuration that produced them.
+/// If `dibs` is given, the results include the conflicts it encountered during the measurement.
+pub fn run_with_parameters(
+    workers: Vec<Box<dyn Worker + Send>>,
+    phases: Phases,
+    placement: &Placement,
+    parameters: &[(&str, String)],
+    dibs: Option<&Dibs>,
+) -> Results {
+    if !parameters.is_empty() {
+        println!(
+            "{}",
+            parameters
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+    }
+
+    let measuring = Arc::new(AtomicBool::new(false));
+    let recorders = (0..workers.len())
+        .map(|_| Arc::new(Recorder::new(Arc::clone(&measuring))))
         .collect::<Vec<_>>();
     let terminate = Arc::new(AtomicBool::new(false));
 
-    let handles = core_affinity::get_core_ids()
-        .unwrap()
+    let core_ids = placement.worker_cores();
+
+    let handles = workers
         .into_iter()
-        .cycle()
-        .zip(workers)
-        .zip(&commit_counters)
-        .map(|((core_id, mut worker), commits)| {
-            let commits = Arc::clone(&commits);
+        .zip(&recorders)
+        .enumerate()
+        .map(|(i, (mut worker, recorder))| {
+            let core_id = core_ids
+                .as_ref()
+                .map(|core_ids| core_ids[i % core_ids.len()]);
+            let recorder = Arc::clone(recorder);
             let terminate = Arc::clone(&terminate);
 
             thread::spawn(move || {
-                core_affinity::set_for_current(core_id);
-                worker.run(commits, terminate);
+                if let Some(core_id) = core_id {
+                    core_affinity::set_for_current(core_id);
+                }
+
+                worker.run(recorder, terminate);
             })
         })
         .collect::<Vec<_>>();
 
-    thread::sleep(warmup_duration);
+    let mut sampler = Sampler::new(&recorders);
 
-    let start = commit_counters
-        .iter()
-        .map(|commits| commits.load(Ordering::Relaxed))
-        .sum::<usize>();
+    sampler.run_phase("warmup", phases.warmup);
 
-    thread::sleep(measurement_duration);
+    let start_totals = totals(&recorders);
+    let start_conflicts = dibs.map(Dibs::conflict_stats);
+    measuring.store(true, Ordering::Relaxed);
 
-    let stop = commit_counters
-        .iter()
-        .map(|commits| commits.load(Ordering::Relaxed))
-        .sum::<usize>();
+    sampler.run_phase("measurement", phases.measurement);
+
+    measuring.store(false, Ordering::Relaxed);
+    let stop_conflicts = dibs.map(Dibs::conflict_stats);
+    let stop_totals = totals(&recorders);
+
+    sampler.run_phase("cooldown", phases.cooldown);
 
     terminate.store(true, Ordering::Relaxed);
 
@@ -50,8 +149,161 @@ pub fn run(workers: Vec<Box<dyn Worker + Send>>) {
         handle.join().unwrap();
     }
 
+    let commits = stop_totals.commits - start_totals.commits;
+    let mut queueing = Histogram::new();
+    let mut latencies = FnvHashMap::<&'static str, Histogram>::default();
+    let mut breakdowns = FnvHashMap::<&'static str, Breakdown>::default();
+
+    for recorder in &recorders {
+        queueing.merge(&recorder.take_queueing());
+
+        for (name, histogram) in recorder.take_latencies() {
+            latencies.entry(name).or_default().merge(&histogram);
+        }
+
+        for (name, breakdown) in recorder.take_breakdowns() {
+            breakdowns.entry(name).or_default().merge(&breakdown);
+        }
+    }
+
     println!(
         "{}",
-        (stop - start) / measurement_duration.as_secs() as usize
+        (commits as f64 / phases.measurement.as_secs_f64()) as usize
     );
+
+    let mut latencies = latencies.into_iter().collect::<Vec<_>>();
+    latencies.sort_by_key(|&(name, _)| name);
+
+    let mut breakdowns = breakdowns.into_iter().collect::<Vec<_>>();
+    breakdowns.sort_by_key(|&(name, _)| name);
+
+    Results {
+        parameters: parameters
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect(),
+        duration: phases.measurement,
+        commits,
+        aborts: stop_totals.aborts - start_totals.aborts,
+        retried_commits: stop_totals.retried_commits - start_totals.retried_commits,
+        give_ups: stop_totals.give_ups - start_totals.give_ups,
+        dropped: stop_totals.dropped - start_totals.dropped,
+        latencies,
+        breakdowns,
+        queueing,
+        conflicts: start_conflicts
+            .zip(stop_conflicts)
+            .map(|(start, stop)| ConflictStats {
+                num_waits: stop.num_waits - start.num_waits,
+                num_timeouts: stop.num_timeouts - start.num_timeouts,
+                num_group_conflicts: stop.num_group_conflicts - start.num_group_conflicts,
+                num_preemptions: stop.num_preemptions - start.num_preemptions,
+                num_pruned: stop.num_pruned - start.num_pruned,
+                num_key_filter_checks: stop.num_key_filter_checks - start.num_key_filter_checks,
+                num_key_filter_skips: stop.num_key_filter_skips - start.num_key_filter_skips,
+                num_hot_key_promotions: stop.num_hot_key_promotions - start.num_hot_key_promotions,
+                num_hot_key_solves: stop.num_hot_key_solves - start.num_hot_key_solves,
+                num_admission_waits: stop.num_admission_waits - start.num_admission_waits,
+                admission_wait_nanos: stop.admission_wait_nanos - start.admission_wait_nanos,
+                num_shared_reads: stop.num_shared_reads - start.num_shared_reads,
+            }),
+        consistency: None,
+        timeseries: sampler.samples,
+    }
+}
+
+/// The counters of all workers so far.
+#[derive(Clone, Copy, Default)]
+struct Totals {
+    commits: usize,
+    aborts: usize,
+    retried_commits: usize,
+    give_ups: usize,
+    dropped: usize,
+}
+
+fn totals(recorders: &[Arc<Recorder>]) -> Totals {
+    recorders
+        .iter()
+        .fold(Totals::default(), |totals, recorder| Totals {
+            commits: totals.commits + recorder.commits(),
+            aborts: totals.aborts + recorder.aborts(),
+            retried_commits: totals.retried_commits + recorder.retried_commits(),
+            give_ups: totals.give_ups + recorder.give_ups(),
+            dropped: totals.dropped + recorder.dropped(),
+        })
+}
+
+/// Samples the workers' counters every `SAMPLE_INTERVAL` and at the end of each phase.
+struct Sampler<'a> {
+    recorders: &'a [Arc<Recorder>],
+    start: Instant,
+    last_sample: Instant,
+    last_counts: Vec<(usize, usize)>,
+    samples: Vec<Sample>,
+}
+
+impl<'a> Sampler<'a> {
+    fn new(recorders: &'a [Arc<Recorder>]) -> Sampler<'a> {
+        let start = Instant::now();
+
+        Sampler {
+            recorders,
+            start,
+            last_sample: start,
+            last_counts: vec![(0, 0); recorders.len()],
+            samples: vec![],
+        }
+    }
+
+    fn run_phase(&mut self, phase: &'static str, duration: Duration) {
+        if duration == Duration::from_secs(0) {
+            return;
+        }
+
+        let end = Instant::now() + duration;
+
+        loop {
+            let next_sample = (self.last_sample + SAMPLE_INTERVAL).min(end);
+            let now = Instant::now();
+
+            if next_sample > now {
+                thread::sleep(next_sample - now);
+            }
+
+            self.sample(phase);
+
+            if next_sample >= end {
+                break;
+            }
+        }
+    }
+
+    fn sample(&mut self, phase: &'static str) {
+        let now = Instant::now();
+        let mut sample = Sample {
+            time: now - self.start,
+            duration: now - self.last_sample,
+            phase,
+            commits: 0,
+            aborts: 0,
+            active_workers: 0,
+        };
+
+        for (recorder, last_counts) in self.recorders.iter().zip(&mut self.last_counts) {
+            let counts = (recorder.commits(), recorder.aborts());
+
+            sample.commits += counts.0 - last_counts.0;
+            sample.aborts += counts.1 - last_counts.1;
+
+            if counts != *last_counts {
+                sample.active_workers += 1;
+            }
+
+            *last_counts = counts;
+        }
+
+        self.last_sample = now;
+        self.samples.push(sample);
+    }
 }
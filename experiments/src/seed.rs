@@ -0,0 +1,71 @@
+//! Derives every random stream of a run from one master seed, so that a run can be repeated with
+//! the same database, the same procedures on each worker, and the same timeouts and backoffs.
+//!
+//! Each worker draws from its own stream, so what a worker generates doesn't depend on how the
+//! threads are scheduled. What the workers interleave into still does.
+
+use clap::{Arg, ArgMatches};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::str::FromStr;
+
+const LOADER_STREAM: u64 = 0;
+const DIBS_STREAM: u64 = 1;
+const FIRST_WORKER_STREAM: u64 = 2;
+
+/// The master seed of a run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Seed(u64);
+
+impl Seed {
+    pub fn new(seed: u64) -> Seed {
+        Seed(seed)
+    }
+
+    /// Returns the `--seed` flag.
+    pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![Arg::with_name("seed").long("seed").takes_value(true).help(
+            "Seeds the loader, the generators and the timeout jitter, defaults to a random seed",
+        )]
+    }
+
+    /// Parses the flag, drawing a seed if there is none. Either way it is reported as a
+    /// parameter, so that the run can be repeated.
+    pub fn from_matches(matches: &ArgMatches) -> Seed {
+        Seed(
+            matches
+                .value_of("seed")
+                .map_or_else(rand::random, |seed| u64::from_str(seed).unwrap()),
+        )
+    }
+
+    /// Returns the master seed, which `Seed::new` takes back.
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+
+    fn stream(&self, stream: u64) -> u64 {
+        self.0
+            .wrapping_add(stream.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+    }
+
+    /// Returns the RNG that the database is loaded with.
+    pub fn loader(&self) -> StdRng {
+        StdRng::seed_from_u64(self.stream(LOADER_STREAM))
+    }
+
+    /// Returns the RNG of the worker with id `worker_id`.
+    pub fn worker(&self, worker_id: usize) -> StdRng {
+        StdRng::seed_from_u64(self.stream(FIRST_WORKER_STREAM + worker_id as u64))
+    }
+
+    /// Returns the seed for `Dibs::enable_seeded_jitter`.
+    pub fn dibs(&self) -> u64 {
+        self.stream(DIBS_STREAM)
+    }
+
+    /// Returns a parameter describing the seed for `runner::run_with_parameters`.
+    pub fn parameter(&self) -> (&'static str, String) {
+        ("seed", self.0.to_string())
+    }
+}
@@ -0,0 +1,511 @@
+//! A network front-end that lets clients on other machines, or written in other languages, drive
+//! the benchmark procedures against a database served by this process.
+//!
+//! Clients send each procedure as a frame: its length as a little-endian `u32`, followed by the
+//! procedure encoded with the functions in `codec`, as done by each procedure's `Request`
+//! implementation. The server runs the procedure as one transaction under dibs, retrying it as a
+//! worker would, and answers with a fixed-size response: a byte that is 1 if the procedure
+//! committed and 0 if it gave up, followed by the number of retries as a `u32`. A client may send
+//! its next procedure as soon as it reads the response. The server closes the connection if it
+//! receives a frame that doesn't decode. Procedures are trusted to refer to rows and field sizes
+//! that the database holds, as the generators' procedures do.
+
+use crate::codec::Decoder;
+use crate::results::Recorder;
+use crate::seed::Seed;
+use crate::worker::{RetryPolicy, State, Worker};
+use crate::{Connection, Generator, Procedure};
+use dibs::{Dibs, Transaction};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+/// The largest frame the server accepts.
+const MAX_FRAME_LEN: u32 = 1 << 24;
+
+/// A procedure that can be sent to the server.
+pub trait Request: Sized {
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Decodes a procedure, returning `None` if `decoder` doesn't hold a whole one.
+    fn decode(decoder: &mut Decoder) -> Option<Self>;
+}
+
+/// The server's answer to a procedure.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Response {
+    pub committed: bool,
+    pub retries: u32,
+}
+
+impl Response {
+    const LEN: usize = 5;
+
+    fn encode(&self) -> [u8; Response::LEN] {
+        let mut buf = [0; Response::LEN];
+        buf[0] = self.committed as u8;
+        buf[1..].copy_from_slice(&self.retries.to_le_bytes());
+        buf
+    }
+
+    fn decode(buf: &[u8; Response::LEN]) -> Response {
+        let mut retries = [0; 4];
+        retries.copy_from_slice(&buf[1..]);
+
+        Response {
+            committed: buf[0] != 0,
+            retries: u32::from_le_bytes(retries),
+        }
+    }
+}
+
+/// Reads a frame into `buf`, returning `false` if the peer closed the connection between frames.
+/// A frame cut off anywhere after its first byte is an error.
+pub(crate) fn read_frame<R: Read>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<bool> {
+    let mut len = [0; 4];
+
+    loop {
+        match reader.read(&mut len[..1]) {
+            Ok(0) => return Ok(false),
+            Ok(_) => break,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    reader.read_exact(&mut len[1..])?;
+
+    let len = u32::from_le_bytes(len);
+
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too long"));
+    }
+
+    buf.resize(len as usize, 0);
+    reader.read_exact(buf)?;
+    Ok(true)
+}
+
+pub(crate) fn write_frame<W: Write>(writer: &mut W, payload: &[u8]) -> io::Result<()> {
+    writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+/// Serves the procedures of one benchmark, running each on a connection created by `connect`.
+pub struct Server<P, C, F> {
+    dibs: Option<Arc<Dibs>>,
+    connect: F,
+    retry_policy: RetryPolicy,
+    seed: Option<Seed>,
+    _phantom: PhantomData<fn(P) -> C>,
+}
+
+impl<P, C, F> Server<P, C, F>
+where
+    P: Request + Procedure<C>,
+    C: Connection,
+    F: Fn() -> C + Send + Sync + 'static,
+{
+    pub fn new(dibs: Option<Arc<Dibs>>, connect: F) -> Server<P, C, F> {
+        Server {
+            dibs,
+            connect,
+            retry_policy: RetryPolicy::default(),
+            seed: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Server<P, C, F> {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Draws each client's backoffs from the stream of `seed` for the client's worker ID.
+    pub fn with_seed(mut self, seed: Seed) -> Server<P, C, F> {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Accepts clients on `listener` until it fails, serving each on its own thread with its own
+    /// connection. At most 1024 clients may be connected at once, since each takes a worker ID.
+    pub fn serve(self, listener: TcpListener) -> io::Result<()>
+    where
+        P: 'static,
+        C: 'static,
+    {
+        let server = Arc::new(self);
+        let worker_ids = Arc::new(Mutex::new((0..1024).rev().collect::<Vec<usize>>()));
+
+        for stream in listener.incoming() {
+            let stream = stream?;
+            stream.set_nodelay(true)?;
+
+            let worker_id = match worker_ids.lock().unwrap().pop() {
+                Some(worker_id) => worker_id,
+                None => {
+                    eprintln!("Refusing {:?}: too many clients", stream.peer_addr());
+                    continue;
+                }
+            };
+
+            let server = Arc::clone(&server);
+            let worker_ids = Arc::clone(&worker_ids);
+
+            thread::spawn(move || {
+                let peer = stream.peer_addr();
+
+                if let Err(e) = server.serve_client(worker_id, stream) {
+                    eprintln!("Closing {:?}: {}", peer, e);
+                }
+
+                worker_ids.lock().unwrap().push(worker_id);
+            });
+        }
+
+        Ok(())
+    }
+
+    fn serve_client(&self, worker_id: usize, stream: TcpStream) -> io::Result<()> {
+        let mut state = State::new(worker_id, self.dibs.clone());
+
+        if let Some(seed) = &self.seed {
+            state.rng = seed.worker(worker_id);
+        }
+
+        let mut connection = (self.connect)();
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+        let mut buf = vec![];
+
+        while read_frame(&mut reader, &mut buf)? {
+            let mut decoder = Decoder::new(&buf);
+
+            let procedure = match P::decode(&mut decoder) {
+                Some(procedure) if decoder.is_empty() => procedure,
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "invalid procedure",
+                    ))
+                }
+            };
+
+            let response = self.execute(&mut state, &procedure, &mut connection);
+            writer.write_all(&response.encode())?;
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Runs `procedure` the way `StandardWorker` does, retrying it in a new transaction each time
+    /// it fails to acquire its requests.
+    fn execute(&self, state: &mut State, procedure: &P, connection: &mut C) -> Response {
+        let start = state.now();
+        let mut retries = 0;
+
+        connection.begin();
+
+        let committed = loop {
+            let mut transaction = Transaction::new(state.group_id(), state.transaction_id());
+
+            if procedure.is_read_only() {
+                transaction.set_read_only();
+            }
+
+            self.retry_policy.set_deadline(&mut transaction, start);
+
+            let result = procedure.execute(&state.dibs, &mut transaction, connection);
+
+            let remaining = state.remaining(&transaction);
+            transaction.commit();
+
+            if result.is_ok() {
+                break true;
+            }
+
+            if !self.retry_policy.allows(retries, remaining) {
+                break false;
+            }
+
+            retries += 1;
+            self.retry_policy.back_off(retries, &mut state.rng);
+        };
+
+        connection.commit();
+
+        Response {
+            committed,
+            retries: retries as u32,
+        }
+    }
+}
+
+/// A connection to a server, which runs one procedure at a time.
+pub struct Client {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    buf: Vec<u8>,
+}
+
+impl Client {
+    pub fn connect<A: ToSocketAddrs>(address: A) -> io::Result<Client> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Client {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+            buf: vec![],
+        })
+    }
+
+    /// Sends `procedure` to the server and waits for it to finish.
+    pub fn call<P: Request>(&mut self, procedure: &P) -> io::Result<Response> {
+        self.buf.clear();
+        procedure.encode(&mut self.buf);
+        write_frame(&mut self.writer, &self.buf)?;
+
+        let mut response = [0; Response::LEN];
+        self.reader.read_exact(&mut response)?;
+        Ok(Response::decode(&response))
+    }
+}
+
+/// A worker that sends the procedures of a generator to a server instead of running them itself,
+/// so that load can be generated from several machines. Latencies include the round trip.
+pub struct RemoteWorker<G, C> {
+    generator: G,
+    client: Client,
+    rng: StdRng,
+    _phantom: PhantomData<fn() -> C>,
+}
+
+impl<G, C> RemoteWorker<G, C> {
+    pub fn new(generator: G, client: Client) -> RemoteWorker<G, C> {
+        RemoteWorker {
+            generator,
+            client,
+            rng: StdRng::from_entropy(),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn with_rng(mut self, rng: StdRng) -> RemoteWorker<G, C> {
+        self.rng = rng;
+        self
+    }
+}
+
+impl<G, C> Worker for RemoteWorker<G, C>
+where
+    G: Generator,
+    G::Item: Request + Procedure<C>,
+{
+    fn run(&mut self, recorder: Arc<Recorder>, terminate: Arc<AtomicBool>) {
+        while !terminate.load(Ordering::Relaxed) {
+            let procedure = self.generator.next(&mut self.rng);
+            let start = Instant::now();
+
+            let response = self
+                .client
+                .call(&procedure)
+                .expect("lost connection to server");
+
+            for _ in 0..response.retries {
+                recorder.abort();
+            }
+
+            if response.committed {
+                recorder.commit(procedure.name(), start.elapsed(), response.retries > 0);
+            } else {
+                recorder.abort();
+                recorder.give_up();
+            }
+        }
+    }
+}
+
+unsafe impl<G, C> Send for RemoteWorker<G, C> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::benchmarks::tatp::{TATPGenerator, TATPProcedure};
+    use crate::benchmarks::ycsb::{self, KeyDistribution, YCSBMix, YCSBProcedure};
+    use crate::codec;
+    use dibs::AcquireError;
+    use std::io::Cursor;
+    use std::net::Shutdown;
+    use std::sync::atomic::AtomicU32;
+
+    struct TestConnection;
+
+    impl Connection for TestConnection {
+        fn begin(&mut self) {}
+        fn commit(&mut self) {}
+        fn rollback(&mut self) {}
+        fn savepoint(&mut self) {}
+    }
+
+    /// A procedure that does nothing, sent as a single `u32`.
+    struct Noop(u32);
+
+    impl Request for Noop {
+        fn encode(&self, buf: &mut Vec<u8>) {
+            codec::put_u32(buf, self.0);
+        }
+
+        fn decode(decoder: &mut Decoder) -> Option<Noop> {
+            decoder.u32().map(Noop)
+        }
+    }
+
+    impl Procedure<TestConnection> for Noop {
+        fn name(&self) -> &'static str {
+            "noop"
+        }
+
+        fn is_read_only(&self) -> bool {
+            true
+        }
+
+        fn execute(
+            &self,
+            _dibs: &Option<Arc<Dibs>>,
+            _transaction: &mut Transaction,
+            _connection: &mut TestConnection,
+        ) -> Result<(), AcquireError> {
+            Ok(())
+        }
+    }
+
+    /// Checks that each procedure decodes to one that encodes the same way, and that no strict
+    /// prefix of its encoding decodes.
+    fn assert_round_trips<P: Request>(procedures: impl Iterator<Item = P>) {
+        for procedure in procedures {
+            let mut buf = vec![];
+            procedure.encode(&mut buf);
+
+            let mut decoder = Decoder::new(&buf);
+            let decoded = P::decode(&mut decoder).unwrap();
+            assert!(decoder.is_empty());
+
+            let mut decoded_buf = vec![];
+            decoded.encode(&mut decoded_buf);
+            assert_eq!(decoded_buf, buf);
+
+            for len in 0..buf.len() {
+                assert!(P::decode(&mut Decoder::new(&buf[..len])).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn tatp_procedures_round_trip() {
+        let generator = TATPGenerator::new(1000);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert_round_trips::<TATPProcedure>((0..1000).map(|_| generator.next(&mut rng)));
+    }
+
+    #[test]
+    fn ycsb_procedures_round_trip() {
+        let mix = YCSBMix {
+            read: 0.2,
+            update: 0.2,
+            insert: 0.1,
+            delete: 0.1,
+            scan: 0.2,
+            read_modify_write: 0.2,
+        };
+
+        let distribution = KeyDistribution::Uniform(rand::distributions::Uniform::new(1, 101));
+        let generator = ycsb::generator(100, 8, 0.5, 4, distribution)
+            .with_mix(mix, Arc::new(AtomicU32::new(100)));
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert_round_trips::<YCSBProcedure>((0..1000).map(|_| generator.next(&mut rng)));
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let mut stream = vec![];
+        write_frame(&mut stream, b"first").unwrap();
+        write_frame(&mut stream, b"").unwrap();
+
+        let mut reader = Cursor::new(stream);
+        let mut buf = vec![];
+
+        assert!(read_frame(&mut reader, &mut buf).unwrap());
+        assert_eq!(buf, b"first");
+        assert!(read_frame(&mut reader, &mut buf).unwrap());
+        assert!(buf.is_empty());
+        assert!(!read_frame(&mut reader, &mut buf).unwrap());
+    }
+
+    #[test]
+    fn truncated_and_oversized_frames_are_rejected() {
+        let mut stream = vec![];
+        write_frame(&mut stream, b"payload").unwrap();
+        let mut buf = vec![];
+
+        // A frame cut off in its length or its payload is an error, unlike one that never began.
+        for len in 1..stream.len() {
+            let error = read_frame(&mut Cursor::new(&stream[..len]), &mut buf).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        }
+
+        let oversized = (MAX_FRAME_LEN + 1).to_le_bytes();
+        let error = read_frame(&mut Cursor::new(&oversized[..]), &mut buf).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn servers_close_connections_that_send_bad_frames() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = Server::<Noop, _, _>::new(None, || TestConnection);
+        thread::spawn(move || server.serve(listener));
+
+        let call = |procedure| Client::connect(address).unwrap().call(&procedure).unwrap();
+        let expected = Response {
+            committed: true,
+            retries: 0,
+        };
+
+        assert_eq!(call(Noop(1)), expected);
+
+        let mut oversized = (MAX_FRAME_LEN + 1).to_le_bytes().to_vec();
+        oversized.extend_from_slice(&[0; 8]);
+
+        let mut undecodable = vec![];
+        write_frame(&mut undecodable, &[0; 5]).unwrap();
+
+        let mut truncated = vec![];
+        write_frame(&mut truncated, &[0; 4]).unwrap();
+        truncated.pop();
+
+        for frame in &[oversized, undecodable, truncated] {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(frame).unwrap();
+            stream.shutdown(Shutdown::Write).unwrap();
+
+            let mut response = vec![];
+            stream.read_to_end(&mut response).unwrap();
+            assert!(response.is_empty());
+        }
+
+        // The server still serves clients after closing the others.
+        assert_eq!(call(Noop(2)), expected);
+    }
+}
@@ -0,0 +1,350 @@
+//! Checkpoints of the Arrow databases taken before and after a run, and checks that the run's log
+//! accounts for every difference between them.
+//!
+//! A checkpoint is a directory holding one snapshot per table. Rows are matched across
+//! checkpoints by their key columns, so the diff doesn't depend on where a table put each row.
+//! Two checks are made of a run: replaying its log over the first checkpoint must give the second,
+//! and every row or column that differs must be written by a logged record, per the workload's
+//! rules for what each record writes.
+
+use super::snapshot;
+use super::wal::{Record, Wal};
+use crate::consistency::Consistency;
+use arrow::array::{
+    ArrayRef, BooleanArray, FixedSizeBinaryArray, PrimitiveArrayOps, StringArray, UInt32Array,
+    UInt8Array,
+};
+use arrow::datatypes::DataType;
+use arrow::error::{ArrowError, Result};
+use clap::{Arg, ArgMatches};
+use fnv::FnvHashSet;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A table of a checkpoint, saved to `<name>.arrow`.
+pub struct CheckpointTable {
+    pub name: &'static str,
+    /// The columns that identify a row, which must hold unsigned integers.
+    pub key: &'static [&'static str],
+}
+
+/// The rows of a checkpoint's table that a logged record writes.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Write {
+    pub table: &'static str,
+    /// The leading key columns of the rows written.
+    pub key: Vec<u64>,
+    /// The column written, or `None` if the rows are inserted or deleted.
+    pub column: Option<String>,
+}
+
+impl Write {
+    pub fn column(table: &'static str, key: Vec<u64>, column: &str) -> Write {
+        Write {
+            table,
+            key,
+            column: Some(column.to_string()),
+        }
+    }
+
+    pub fn rows(table: &'static str, key: Vec<u64>) -> Write {
+        Write {
+            table,
+            key,
+            column: None,
+        }
+    }
+}
+
+/// A database that can be checkpointed and whose log says what each record writes.
+pub trait Checkpointed: Sized {
+    type Record: Record;
+
+    const TABLES: &'static [CheckpointTable];
+
+    /// Saves each table to its file in the directory at `path`.
+    fn save_checkpoint(&self, path: &Path) -> Result<()>;
+
+    /// Loads the checkpoint at `path` and replays the log at `wal` over it.
+    fn recover_checkpoint(path: &Path, wal: &Path) -> Result<Self>;
+
+    /// Returns what `record` writes. A record that writes a row's column may also change it to
+    /// the value it already had, so a write needn't show up in the diff.
+    fn writes(record: &Self::Record) -> Vec<Write>;
+}
+
+/// The value of a cell, compared across checkpoints.
+#[derive(Clone, Debug, PartialEq)]
+enum Cell {
+    UInt(u64),
+    Bool(bool),
+    Bytes(Vec<u8>),
+}
+
+fn downcast<A: 'static>(array: &ArrayRef) -> &A {
+    array.as_any().downcast_ref::<A>().unwrap()
+}
+
+fn cell(array: &ArrayRef, row: usize) -> Result<Cell> {
+    let cell = match array.data_type() {
+        DataType::UInt8 => Cell::UInt(downcast::<UInt8Array>(array).value(row) as u64),
+        DataType::UInt32 => Cell::UInt(downcast::<UInt32Array>(array).value(row) as u64),
+        DataType::Boolean => Cell::Bool(downcast::<BooleanArray>(array).value(row)),
+        DataType::FixedSizeBinary(_) => {
+            Cell::Bytes(downcast::<FixedSizeBinaryArray>(array).value(row).to_vec())
+        }
+        DataType::Utf8 => Cell::Bytes(downcast::<StringArray>(array).value(row).into()),
+        data_type => {
+            return Err(ArrowError::InvalidArgumentError(format!(
+                "unexpected column type {:?}",
+                data_type
+            )))
+        }
+    };
+
+    Ok(cell)
+}
+
+/// The rows of a table in a checkpoint, by key.
+struct Rows {
+    columns: Vec<String>,
+    rows: BTreeMap<Vec<u64>, Vec<Cell>>,
+}
+
+impl Rows {
+    fn load(path: &Path, table: &CheckpointTable) -> Result<Rows> {
+        let batch = snapshot::load(&path.join(format!("{}.arrow", table.name)))?;
+        let schema = batch.schema();
+
+        let key_columns = table
+            .key
+            .iter()
+            .map(|name| snapshot::column(&batch, name))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut rows = BTreeMap::new();
+
+        for row in 0..batch.num_rows() {
+            let key = key_columns
+                .iter()
+                .map(|column| match cell(column, row)? {
+                    Cell::UInt(value) => Ok(value),
+                    _ => Err(ArrowError::InvalidArgumentError(format!(
+                        "key of {} is not an unsigned integer",
+                        table.name
+                    ))),
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let cells = batch
+                .columns()
+                .iter()
+                .map(|column| cell(column, row))
+                .collect::<Result<Vec<_>>>()?;
+
+            rows.insert(key, cells);
+        }
+
+        Ok(Rows {
+            columns: schema.fields().iter().map(|f| f.name().clone()).collect(),
+            rows,
+        })
+    }
+}
+
+/// The rows of a table that differ between two checkpoints.
+#[derive(Debug, Default)]
+pub struct TableDiff {
+    pub inserted: Vec<Vec<u64>>,
+    pub deleted: Vec<Vec<u64>>,
+    /// The keys of the rows in both checkpoints whose values differ, with the differing columns.
+    pub updated: Vec<(Vec<u64>, Vec<String>)>,
+}
+
+impl TableDiff {
+    /// The number of rows that differ.
+    pub fn len(&self) -> usize {
+        self.inserted.len() + self.deleted.len() + self.updated.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Compares each of `tables` in the checkpoints at `before` and `after`.
+pub fn diff(
+    tables: &[CheckpointTable],
+    before: &Path,
+    after: &Path,
+) -> Result<Vec<(&'static str, TableDiff)>> {
+    tables
+        .iter()
+        .map(|table| {
+            let before = Rows::load(before, table)?;
+            let after = Rows::load(after, table)?;
+
+            if before.columns != after.columns {
+                return Err(ArrowError::InvalidArgumentError(format!(
+                    "the checkpoints of {} have different columns",
+                    table.name
+                )));
+            }
+
+            let mut diff = TableDiff::default();
+
+            for (key, cells) in &before.rows {
+                match after.rows.get(key) {
+                    Some(after_cells) => {
+                        let columns = before
+                            .columns
+                            .iter()
+                            .zip(cells.iter().zip(after_cells))
+                            .filter(|(_, (cell, after_cell))| cell != after_cell)
+                            .map(|(column, _)| column.clone())
+                            .collect::<Vec<_>>();
+
+                        if !columns.is_empty() {
+                            diff.updated.push((key.clone(), columns));
+                        }
+                    }
+                    None => diff.deleted.push(key.clone()),
+                }
+            }
+
+            for key in after.rows.keys() {
+                if !before.rows.contains_key(key) {
+                    diff.inserted.push(key.clone());
+                }
+            }
+
+            Ok((table.name, diff))
+        })
+        .collect()
+}
+
+/// Checks the run between the checkpoints saved by `save_checkpoint` to `before` and `after`,
+/// whose changes were logged to `wal`. The log is replayed into a third checkpoint at `replayed`.
+/// Returns the number of rows in each table that replaying the log gets wrong, and the number of
+/// rows whose changes no logged record accounts for.
+pub fn verify<D: Checkpointed>(
+    before: &Path,
+    after: &Path,
+    replayed: &Path,
+    wal: &Path,
+) -> Result<Consistency> {
+    D::recover_checkpoint(before, wal)?.save_checkpoint(replayed)?;
+
+    let mut writes = FnvHashSet::default();
+    Wal::replay(wal, |record: D::Record| writes.extend(D::writes(&record)))?;
+
+    // A row write covers every row whose key starts with its key.
+    let written = |table: &'static str, key: &[u64], column: Option<&String>| {
+        (0..=key.len()).any(|len| writes.contains(&Write::rows(table, key[..len].to_vec())))
+            || match column {
+                Some(column) => writes.contains(&Write::column(table, key.to_vec(), column)),
+                None => false,
+            }
+    };
+
+    let mut consistency = Consistency::default();
+
+    for (table, diff) in diff(D::TABLES, after, replayed)? {
+        consistency.push(&format!("{} matches replayed log", table), diff.len());
+    }
+
+    for (table, diff) in diff(D::TABLES, before, after)? {
+        let unexplained = diff
+            .inserted
+            .iter()
+            .chain(&diff.deleted)
+            .filter(|key| !written(table, key, None))
+            .count()
+            + diff
+                .updated
+                .iter()
+                .filter(|(key, columns)| {
+                    columns
+                        .iter()
+                        .any(|column| !written(table, key, Some(column)))
+                })
+                .count();
+
+        consistency.push(&format!("{} changes are logged", table), unexplained);
+    }
+
+    Ok(consistency)
+}
+
+/// Where to checkpoint the database before and after a run to verify it, if anywhere.
+pub struct Verification {
+    dir: Option<PathBuf>,
+    wal: Option<PathBuf>,
+}
+
+impl Verification {
+    /// Returns the `--verify-checkpoints` flag.
+    pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![Arg::with_name("verify_checkpoints")
+            .long("verify-checkpoints")
+            .takes_value(true)
+            .help(
+                "Checkpoints the database to this directory before and after the run and checks \
+                 that the committed transactions account for the changes between them",
+            )]
+    }
+
+    /// Verifies against the log at `--wal` if the run has one, and otherwise against a log kept
+    /// in the checkpoint directory.
+    pub fn from_matches(matches: &ArgMatches) -> Verification {
+        let dir = matches.value_of("verify_checkpoints").map(PathBuf::from);
+
+        let wal = match matches.value_of("wal") {
+            Some(wal) => Some(PathBuf::from(wal)),
+            None => dir.as_ref().map(|dir| dir.join("committed.wal")),
+        };
+
+        Verification { dir, wal }
+    }
+
+    /// Opens the log that the run's connections should write, if it is to be verified.
+    pub fn open_wal(&self, matches: &ArgMatches) -> Option<Wal> {
+        if let Some(dir) = &self.dir {
+            fs::create_dir_all(dir).unwrap();
+        }
+
+        match (&self.wal, matches.is_present("wal")) {
+            (Some(wal), true) => Some(Wal::create(wal).unwrap()),
+            (Some(wal), false) => Some(Wal::create_unsynced(wal).unwrap()),
+            (None, _) => None,
+        }
+    }
+
+    /// Checkpoints the database before the run.
+    pub fn start<D: Checkpointed>(&self, db: &D) -> Result<()> {
+        match &self.dir {
+            Some(dir) => db.save_checkpoint(&dir.join("before")),
+            None => Ok(()),
+        }
+    }
+
+    /// Checkpoints the database after the run and verifies the run, once every transaction has
+    /// ended.
+    pub fn finish<D: Checkpointed>(&self, db: &D) -> Result<Option<Consistency>> {
+        let (dir, wal) = match (&self.dir, &self.wal) {
+            (Some(dir), Some(wal)) => (dir, wal),
+            _ => return Ok(None),
+        };
+
+        db.save_checkpoint(&dir.join("after"))?;
+
+        verify::<D>(
+            &dir.join("before"),
+            &dir.join("after"),
+            &dir.join("replayed"),
+            wal,
+        )
+        .map(Some)
+    }
+}
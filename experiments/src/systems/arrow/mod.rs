@@ -0,0 +1,2424 @@
+use crate::benchmarks::scan::ScanConnection;
+use crate::benchmarks::tatp::TATPConnection;
+use crate::benchmarks::ycsb::YCSBConnection;
+use crate::benchmarks::{tatp, ycsb};
+use crate::codec;
+use crate::codec::Decoder;
+use crate::committer::Logged;
+use crate::consistency::Consistency;
+use crate::Connection;
+use arrow::array::{
+    BooleanBuilder, FixedSizeBinaryBuilder, StringBuilder, UInt32Builder, UInt8Builder,
+};
+use arrow::error::{ArrowError, Result};
+use checkpoint::{CheckpointTable, Checkpointed, Write};
+use rand::distributions::Alphanumeric;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use snapshot::Column;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use table::{
+    Block, BooleanArrayMut, FixedSizeBinaryArrayMut, MultiHashIndex, StringArrayMut, Table,
+    UInt32ArrayMut, UInt8ArrayMut,
+};
+use wal::{Record, Wal};
+
+pub mod checkpoint;
+pub mod snapshot;
+pub mod table;
+pub mod wal;
+
+/// The number of rows a table grows by when it is full.
+const BLOCK_LEN: usize = 1 << 12;
+
+/// Returns a path under the temporary directory for a test to write `name` to, removing anything
+/// a previous run left there.
+#[cfg(test)]
+fn scratch_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("dibs-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&path);
+    let _ = fs::remove_file(&path);
+    path
+}
+
+/// The columns of a subscriber: bit, hex, byte2, msc_location, and vlr_location.
+pub type SubscriberRow = ([bool; 10], [u8; 10], [u8; 10], u32, u32);
+
+struct SubscriberBlock {
+    col_s_id: UInt32ArrayMut,
+    col_bit: Vec<BooleanArrayMut>,
+    col_hex: Vec<UInt8ArrayMut>,
+    col_byte2: Vec<UInt8ArrayMut>,
+    col_msc_location: UInt32ArrayMut,
+    col_vlr_location: UInt32ArrayMut,
+}
+
+impl SubscriberBlock {
+    fn get_row_data(&self, row: usize) -> SubscriberRow {
+        let mut bit = [false; 10];
+        for (dst, src) in bit.iter_mut().zip(&self.col_bit) {
+            *dst = src.value(row);
+        }
+
+        let mut hex = [0; 10];
+        for (dst, src) in hex.iter_mut().zip(&self.col_hex) {
+            *dst = src.value(row);
+        }
+
+        let mut byte2 = [0; 10];
+        for (dst, src) in byte2.iter_mut().zip(&self.col_byte2) {
+            *dst = src.value(row);
+        }
+
+        (
+            bit,
+            hex,
+            byte2,
+            self.col_msc_location.value(row),
+            self.col_vlr_location.value(row),
+        )
+    }
+
+    fn set_row_data(&self, row: usize, s_id: u32, data: SubscriberRow) {
+        let (bit, hex, byte2, msc_location, vlr_location) = data;
+
+        self.col_s_id.set(row, s_id);
+
+        for (dst, &src) in self.col_bit.iter().zip(&bit) {
+            dst.set(row, src);
+        }
+
+        for (dst, &src) in self.col_hex.iter().zip(&hex) {
+            dst.set(row, src);
+        }
+
+        for (dst, &src) in self.col_byte2.iter().zip(&byte2) {
+            dst.set(row, src);
+        }
+
+        self.col_msc_location.set(row, msc_location);
+        self.col_vlr_location.set(row, vlr_location);
+    }
+}
+
+impl Block for SubscriberBlock {
+    fn empty(&self, len: usize) -> SubscriberBlock {
+        SubscriberBlock {
+            col_s_id: UInt32ArrayMut::new(UInt32Builder::new(len), len),
+            col_bit: (0..10)
+                .map(|_| BooleanArrayMut::new(BooleanBuilder::new(len), len))
+                .collect(),
+            col_hex: (0..10)
+                .map(|_| UInt8ArrayMut::new(UInt8Builder::new(len), len))
+                .collect(),
+            col_byte2: (0..10)
+                .map(|_| UInt8ArrayMut::new(UInt8Builder::new(len), len))
+                .collect(),
+            col_msc_location: UInt32ArrayMut::new(UInt32Builder::new(len), len),
+            col_vlr_location: UInt32ArrayMut::new(UInt32Builder::new(len), len),
+        }
+    }
+}
+
+struct Subscriber {
+    table: Table<u32, SubscriberBlock>,
+}
+
+impl Subscriber {
+    fn new(num_rows: u32, rng: &mut StdRng) -> Subscriber {
+        let mut s_ids = (1..=num_rows).collect::<Vec<_>>();
+        s_ids.shuffle(rng);
+
+        let capacity = num_rows as usize;
+
+        let mut s_id_builder = UInt32Builder::new(capacity);
+        let mut bit_builders = (0..10)
+            .map(|_| BooleanBuilder::new(capacity))
+            .collect::<Vec<_>>();
+        let mut hex_builders = (0..10)
+            .map(|_| UInt8Builder::new(capacity))
+            .collect::<Vec<_>>();
+        let mut byte2_builders = (0..10)
+            .map(|_| UInt8Builder::new(capacity))
+            .collect::<Vec<_>>();
+        let mut msc_location_builder = UInt32Builder::new(capacity);
+        let mut vlr_location_builder = UInt32Builder::new(capacity);
+
+        for s_id in &s_ids {
+            s_id_builder.append_value(*s_id).unwrap();
+
+            for bit_builder in &mut bit_builders {
+                bit_builder.append_value(rng.gen()).unwrap();
+            }
+
+            for hex_builder in &mut hex_builders {
+                hex_builder.append_value(rng.gen_range(0, 16)).unwrap();
+            }
+
+            for byte2_builder in &mut byte2_builders {
+                byte2_builder.append_value(rng.gen()).unwrap();
+            }
+
+            msc_location_builder
+                .append_value(rng.gen_range(1, u32::max_value()))
+                .unwrap();
+
+            vlr_location_builder
+                .append_value(rng.gen_range(1, u32::max_value()))
+                .unwrap();
+        }
+
+        let first = SubscriberBlock {
+            col_s_id: UInt32ArrayMut::new(s_id_builder, capacity),
+            col_bit: bit_builders
+                .into_iter()
+                .map(|b| BooleanArrayMut::new(b, capacity))
+                .collect(),
+            col_hex: hex_builders
+                .into_iter()
+                .map(|b| UInt8ArrayMut::new(b, capacity))
+                .collect(),
+            col_byte2: byte2_builders
+                .into_iter()
+                .map(|b| UInt8ArrayMut::new(b, capacity))
+                .collect(),
+            col_msc_location: UInt32ArrayMut::new(msc_location_builder, capacity),
+            col_vlr_location: UInt32ArrayMut::new(vlr_location_builder, capacity),
+        };
+
+        Subscriber {
+            table: Table::new(s_ids, first, BLOCK_LEN),
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let rows = self.table.rows_by_block();
+        let mut columns = vec![(
+            "s_id".to_string(),
+            snapshot::gather(&rows, |block: &SubscriberBlock| &block.col_s_id),
+        )];
+
+        for i in 0..10 {
+            columns.push((
+                format!("bit_{}", i + 1),
+                snapshot::gather(&rows, |block: &SubscriberBlock| &block.col_bit[i]),
+            ));
+        }
+
+        for i in 0..10 {
+            columns.push((
+                format!("hex_{}", i + 1),
+                snapshot::gather(&rows, |block: &SubscriberBlock| &block.col_hex[i]),
+            ));
+        }
+
+        for i in 0..10 {
+            columns.push((
+                format!("byte2_{}", i + 1),
+                snapshot::gather(&rows, |block: &SubscriberBlock| &block.col_byte2[i]),
+            ));
+        }
+
+        columns.push((
+            "msc_location".to_string(),
+            snapshot::gather(&rows, |block: &SubscriberBlock| &block.col_msc_location),
+        ));
+        columns.push((
+            "vlr_location".to_string(),
+            snapshot::gather(&rows, |block: &SubscriberBlock| &block.col_vlr_location),
+        ));
+
+        snapshot::save(path, columns)
+    }
+
+    fn load(path: &Path) -> Result<Subscriber> {
+        let batch = snapshot::load(path)?;
+        let column = |name: &str| snapshot::column(&batch, name);
+
+        let first = SubscriberBlock {
+            col_s_id: Column::load(column("s_id")?)?,
+            col_bit: (1..=10)
+                .map(|i| Column::load(column(&format!("bit_{}", i))?))
+                .collect::<Result<_>>()?,
+            col_hex: (1..=10)
+                .map(|i| Column::load(column(&format!("hex_{}", i))?))
+                .collect::<Result<_>>()?,
+            col_byte2: (1..=10)
+                .map(|i| Column::load(column(&format!("byte2_{}", i))?))
+                .collect::<Result<_>>()?,
+            col_msc_location: Column::load(column("msc_location")?)?,
+            col_vlr_location: Column::load(column("vlr_location")?)?,
+        };
+
+        let s_ids = (0..batch.num_rows())
+            .map(|row| first.col_s_id.value(row))
+            .collect::<Vec<_>>();
+
+        Ok(Subscriber {
+            table: Table::new(s_ids, first, BLOCK_LEN),
+        })
+    }
+
+    /// Calls `f` with the block and index of every subscriber, in row order.
+    fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(&SubscriberBlock, usize),
+    {
+        for (start, block, len) in self.table.blocks() {
+            for index in 0..len {
+                if self.table.get(&block.col_s_id.value(index)) == Some(start + index) {
+                    f(&block, index);
+                }
+            }
+        }
+    }
+
+    /// Calls `f` with the block and index of every subscriber whose byte2 columns each fall in
+    /// one of two ranges.
+    fn scan<F>(&self, byte2: [(u8, u8, u8, u8); 10], mut f: F)
+    where
+        F: FnMut(&SubscriberBlock, usize),
+    {
+        for (start, block, len) in self.table.blocks() {
+            for index in 0..len {
+                let matches =
+                    block
+                        .col_byte2
+                        .iter()
+                        .zip(&byte2)
+                        .all(|(col_byte2, &(a, b, c, d))| {
+                            let value = col_byte2.value(index);
+                            (value >= a && value <= b) || (value >= c && value <= d)
+                        });
+
+                // Rows that are not in use are checked last, since few rows match.
+                if matches && self.table.get(&block.col_s_id.value(index)) == Some(start + index) {
+                    f(&block, index);
+                }
+            }
+        }
+    }
+}
+
+struct AccessInfoBlock {
+    col_s_id: UInt32ArrayMut,
+    col_ai_type: UInt8ArrayMut,
+    col_data1: UInt8ArrayMut,
+    col_data2: UInt8ArrayMut,
+    col_data3: FixedSizeBinaryArrayMut,
+    col_data4: FixedSizeBinaryArrayMut,
+}
+
+impl Block for AccessInfoBlock {
+    fn empty(&self, len: usize) -> AccessInfoBlock {
+        AccessInfoBlock {
+            col_s_id: UInt32ArrayMut::new(UInt32Builder::new(len), len),
+            col_ai_type: UInt8ArrayMut::new(UInt8Builder::new(len), len),
+            col_data1: UInt8ArrayMut::new(UInt8Builder::new(len), len),
+            col_data2: UInt8ArrayMut::new(UInt8Builder::new(len), len),
+            col_data3: FixedSizeBinaryArrayMut::new(FixedSizeBinaryBuilder::new(len, 3), 3, len),
+            col_data4: FixedSizeBinaryArrayMut::new(FixedSizeBinaryBuilder::new(len, 5), 5, len),
+        }
+    }
+}
+
+struct AccessInfo {
+    table: Table<(u32, u8), AccessInfoBlock>,
+}
+
+impl AccessInfo {
+    fn new(subscriber: &Subscriber, rng: &mut StdRng) -> AccessInfo {
+        let capacity = subscriber.table.capacity() * 4;
+
+        let mut s_id_builder = UInt32Builder::new(capacity);
+        let mut ai_type_builder = UInt8Builder::new(capacity);
+        let mut data1_builder = UInt8Builder::new(capacity);
+        let mut data2_builder = UInt8Builder::new(capacity);
+        let mut data3_builder = FixedSizeBinaryBuilder::new(capacity, 3);
+        let mut data4_builder = FixedSizeBinaryBuilder::new(capacity, 5);
+        let mut keys = vec![];
+
+        subscriber.for_each(|block, index| {
+            let s_id = block.col_s_id.value(index);
+            let num_ai_types = rng.gen_range(1, 5);
+            for ai_type in [1, 2, 3, 4].choose_multiple(rng, num_ai_types) {
+                s_id_builder.append_value(s_id).unwrap();
+                ai_type_builder.append_value(*ai_type).unwrap();
+                data1_builder.append_value(rng.gen()).unwrap();
+                data2_builder.append_value(rng.gen()).unwrap();
+                data3_builder
+                    .append_value(tatp::uppercase_alphabetic_string(3, rng).as_bytes())
+                    .unwrap();
+                data4_builder
+                    .append_value(tatp::uppercase_alphabetic_string(5, rng).as_bytes())
+                    .unwrap();
+                keys.push((s_id, *ai_type));
+            }
+        });
+
+        let capacity = keys.len();
+
+        let first = AccessInfoBlock {
+            col_s_id: UInt32ArrayMut::new(s_id_builder, capacity),
+            col_ai_type: UInt8ArrayMut::new(ai_type_builder, capacity),
+            col_data1: UInt8ArrayMut::new(data1_builder, capacity),
+            col_data2: UInt8ArrayMut::new(data2_builder, capacity),
+            col_data3: FixedSizeBinaryArrayMut::new(data3_builder, 3, capacity),
+            col_data4: FixedSizeBinaryArrayMut::new(data4_builder, 5, capacity),
+        };
+
+        AccessInfo {
+            table: Table::new(keys, first, BLOCK_LEN),
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let rows = self.table.rows_by_block();
+
+        snapshot::save(
+            path,
+            vec![
+                (
+                    "s_id".to_string(),
+                    snapshot::gather(&rows, |block: &AccessInfoBlock| &block.col_s_id),
+                ),
+                (
+                    "ai_type".to_string(),
+                    snapshot::gather(&rows, |block: &AccessInfoBlock| &block.col_ai_type),
+                ),
+                (
+                    "data1".to_string(),
+                    snapshot::gather(&rows, |block: &AccessInfoBlock| &block.col_data1),
+                ),
+                (
+                    "data2".to_string(),
+                    snapshot::gather(&rows, |block: &AccessInfoBlock| &block.col_data2),
+                ),
+                (
+                    "data3".to_string(),
+                    snapshot::gather(&rows, |block: &AccessInfoBlock| &block.col_data3),
+                ),
+                (
+                    "data4".to_string(),
+                    snapshot::gather(&rows, |block: &AccessInfoBlock| &block.col_data4),
+                ),
+            ],
+        )
+    }
+
+    fn load(path: &Path) -> Result<AccessInfo> {
+        let batch = snapshot::load(path)?;
+        let column = |name: &str| snapshot::column(&batch, name);
+
+        let first = AccessInfoBlock {
+            col_s_id: Column::load(column("s_id")?)?,
+            col_ai_type: Column::load(column("ai_type")?)?,
+            col_data1: Column::load(column("data1")?)?,
+            col_data2: Column::load(column("data2")?)?,
+            col_data3: Column::load(column("data3")?)?,
+            col_data4: Column::load(column("data4")?)?,
+        };
+
+        let keys = (0..batch.num_rows())
+            .map(|row| (first.col_s_id.value(row), first.col_ai_type.value(row)))
+            .collect::<Vec<_>>();
+
+        Ok(AccessInfo {
+            table: Table::new(keys, first, BLOCK_LEN),
+        })
+    }
+}
+
+struct SpecialFacilityBlock {
+    col_s_id: UInt32ArrayMut,
+    col_sf_type: UInt8ArrayMut,
+    col_is_active: BooleanArrayMut,
+    col_error_cntrl: UInt8ArrayMut,
+    col_data_a: UInt8ArrayMut,
+    col_data_b: FixedSizeBinaryArrayMut,
+}
+
+impl Block for SpecialFacilityBlock {
+    fn empty(&self, len: usize) -> SpecialFacilityBlock {
+        SpecialFacilityBlock {
+            col_s_id: UInt32ArrayMut::new(UInt32Builder::new(len), len),
+            col_sf_type: UInt8ArrayMut::new(UInt8Builder::new(len), len),
+            col_is_active: BooleanArrayMut::new(BooleanBuilder::new(len), len),
+            col_error_cntrl: UInt8ArrayMut::new(UInt8Builder::new(len), len),
+            col_data_a: UInt8ArrayMut::new(UInt8Builder::new(len), len),
+            col_data_b: FixedSizeBinaryArrayMut::new(FixedSizeBinaryBuilder::new(len, 5), 5, len),
+        }
+    }
+}
+
+struct SpecialFacility {
+    table: Table<(u32, u8), SpecialFacilityBlock>,
+    by_s_id: MultiHashIndex<u32>,
+}
+
+impl SpecialFacility {
+    fn new(subscriber: &Subscriber, rng: &mut StdRng) -> SpecialFacility {
+        let capacity = subscriber.table.capacity() * 4;
+
+        let mut s_id_builder = UInt32Builder::new(capacity);
+        let mut sf_type_builder = UInt8Builder::new(capacity);
+        let mut is_active_builder = BooleanBuilder::new(capacity);
+        let mut error_cntrl_builder = UInt8Builder::new(capacity);
+        let mut data_a_builder = UInt8Builder::new(capacity);
+        let mut data_b_builder = FixedSizeBinaryBuilder::new(capacity, 5);
+        let mut keys = vec![];
+        let by_s_id = MultiHashIndex::new();
+
+        subscriber.for_each(|block, index| {
+            let s_id = block.col_s_id.value(index);
+            let num_sf_types = rng.gen_range(1, 5);
+            for sf_type in [1, 2, 3, 4].choose_multiple(rng, num_sf_types) {
+                s_id_builder.append_value(s_id).unwrap();
+                sf_type_builder.append_value(*sf_type).unwrap();
+                is_active_builder.append_value(rng.gen_bool(0.85)).unwrap();
+                error_cntrl_builder.append_value(rng.gen()).unwrap();
+                data_a_builder.append_value(rng.gen()).unwrap();
+                data_b_builder
+                    .append_value(&(0..5).map(|_| rng.gen()).collect::<Vec<_>>())
+                    .unwrap();
+                by_s_id.insert(s_id, keys.len());
+                keys.push((s_id, *sf_type));
+            }
+        });
+
+        let capacity = keys.len();
+
+        let first = SpecialFacilityBlock {
+            col_s_id: UInt32ArrayMut::new(s_id_builder, capacity),
+            col_sf_type: UInt8ArrayMut::new(sf_type_builder, capacity),
+            col_is_active: BooleanArrayMut::new(is_active_builder, capacity),
+            col_error_cntrl: UInt8ArrayMut::new(error_cntrl_builder, capacity),
+            col_data_a: UInt8ArrayMut::new(data_a_builder, capacity),
+            col_data_b: FixedSizeBinaryArrayMut::new(data_b_builder, 5, capacity),
+        };
+
+        SpecialFacility {
+            table: Table::new(keys, first, BLOCK_LEN),
+            by_s_id,
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let rows = self.table.rows_by_block();
+
+        snapshot::save(
+            path,
+            vec![
+                (
+                    "s_id".to_string(),
+                    snapshot::gather(&rows, |block: &SpecialFacilityBlock| &block.col_s_id),
+                ),
+                (
+                    "sf_type".to_string(),
+                    snapshot::gather(&rows, |block: &SpecialFacilityBlock| &block.col_sf_type),
+                ),
+                (
+                    "is_active".to_string(),
+                    snapshot::gather(&rows, |block: &SpecialFacilityBlock| &block.col_is_active),
+                ),
+                (
+                    "error_cntrl".to_string(),
+                    snapshot::gather(&rows, |block: &SpecialFacilityBlock| &block.col_error_cntrl),
+                ),
+                (
+                    "data_a".to_string(),
+                    snapshot::gather(&rows, |block: &SpecialFacilityBlock| &block.col_data_a),
+                ),
+                (
+                    "data_b".to_string(),
+                    snapshot::gather(&rows, |block: &SpecialFacilityBlock| &block.col_data_b),
+                ),
+            ],
+        )
+    }
+
+    fn load(path: &Path) -> Result<SpecialFacility> {
+        let batch = snapshot::load(path)?;
+        let column = |name: &str| snapshot::column(&batch, name);
+
+        let first = SpecialFacilityBlock {
+            col_s_id: Column::load(column("s_id")?)?,
+            col_sf_type: Column::load(column("sf_type")?)?,
+            col_is_active: Column::load(column("is_active")?)?,
+            col_error_cntrl: Column::load(column("error_cntrl")?)?,
+            col_data_a: Column::load(column("data_a")?)?,
+            col_data_b: Column::load(column("data_b")?)?,
+        };
+
+        let mut keys = vec![];
+        let by_s_id = MultiHashIndex::new();
+
+        for row in 0..batch.num_rows() {
+            let s_id = first.col_s_id.value(row);
+            by_s_id.insert(s_id, row);
+            keys.push((s_id, first.col_sf_type.value(row)));
+        }
+
+        Ok(SpecialFacility {
+            table: Table::new(keys, first, BLOCK_LEN),
+            by_s_id,
+        })
+    }
+}
+
+struct CallForwardingBlock {
+    col_s_id: UInt32ArrayMut,
+    col_sf_type: UInt8ArrayMut,
+    col_start_time: UInt8ArrayMut,
+    col_end_time: UInt8ArrayMut,
+    col_numberx: StringArrayMut,
+}
+
+impl Block for CallForwardingBlock {
+    fn empty(&self, len: usize) -> CallForwardingBlock {
+        CallForwardingBlock {
+            col_s_id: UInt32ArrayMut::new(UInt32Builder::new(len), len),
+            col_sf_type: UInt8ArrayMut::new(UInt8Builder::new(len), len),
+            col_start_time: UInt8ArrayMut::new(UInt8Builder::new(len), len),
+            col_end_time: UInt8ArrayMut::new(UInt8Builder::new(len), len),
+            col_numberx: StringArrayMut::new(StringBuilder::with_capacity(len, 0), len),
+        }
+    }
+}
+
+struct CallForwarding {
+    table: Table<(u32, u8, u8), CallForwardingBlock>,
+    by_special_facility: MultiHashIndex<(u32, u8)>,
+}
+
+impl CallForwarding {
+    fn new(special_facility: &SpecialFacility, rng: &mut StdRng) -> CallForwarding {
+        // Each special facility has up to three call forwardings.
+        let capacity = special_facility.table.capacity() * 3;
+
+        let mut s_id_builder = UInt32Builder::new(capacity);
+        let mut sf_type_builder = UInt8Builder::new(capacity);
+        let mut start_time_builder = UInt8Builder::new(capacity);
+        let mut end_time_builder = UInt8Builder::new(capacity);
+        let mut numberx_builder = StringBuilder::with_capacity(capacity, capacity * 15);
+        let mut keys = vec![];
+        let by_special_facility = MultiHashIndex::new();
+
+        for (_, block, len) in special_facility.table.blocks() {
+            for index in 0..len {
+                let s_id = block.col_s_id.value(index);
+                let sf_type = block.col_sf_type.value(index);
+                let num_start_times = rng.gen_range(0, 4);
+                for start_time in [0, 8, 16].choose_multiple(rng, num_start_times) {
+                    s_id_builder.append_value(s_id).unwrap();
+                    sf_type_builder.append_value(sf_type).unwrap();
+                    start_time_builder.append_value(*start_time).unwrap();
+                    end_time_builder
+                        .append_value(start_time + rng.gen_range(1, 9))
+                        .unwrap();
+                    numberx_builder
+                        .append_value(&tatp::uppercase_alphabetic_string(15, rng))
+                        .unwrap();
+                    by_special_facility.insert((s_id, sf_type), keys.len());
+                    keys.push((s_id, sf_type, *start_time));
+                }
+            }
+        }
+
+        let capacity = keys.len();
+
+        let first = CallForwardingBlock {
+            col_s_id: UInt32ArrayMut::new(s_id_builder, capacity),
+            col_sf_type: UInt8ArrayMut::new(sf_type_builder, capacity),
+            col_start_time: UInt8ArrayMut::new(start_time_builder, capacity),
+            col_end_time: UInt8ArrayMut::new(end_time_builder, capacity),
+            col_numberx: StringArrayMut::new(numberx_builder, capacity),
+        };
+
+        CallForwarding {
+            table: Table::new(keys, first, BLOCK_LEN),
+            by_special_facility,
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let rows = self.table.rows_by_block();
+
+        snapshot::save(
+            path,
+            vec![
+                (
+                    "s_id".to_string(),
+                    snapshot::gather(&rows, |block: &CallForwardingBlock| &block.col_s_id),
+                ),
+                (
+                    "sf_type".to_string(),
+                    snapshot::gather(&rows, |block: &CallForwardingBlock| &block.col_sf_type),
+                ),
+                (
+                    "start_time".to_string(),
+                    snapshot::gather(&rows, |block: &CallForwardingBlock| &block.col_start_time),
+                ),
+                (
+                    "end_time".to_string(),
+                    snapshot::gather(&rows, |block: &CallForwardingBlock| &block.col_end_time),
+                ),
+                (
+                    "numberx".to_string(),
+                    snapshot::gather(&rows, |block: &CallForwardingBlock| &block.col_numberx),
+                ),
+            ],
+        )
+    }
+
+    fn load(path: &Path) -> Result<CallForwarding> {
+        let batch = snapshot::load(path)?;
+        let column = |name: &str| snapshot::column(&batch, name);
+
+        let first = CallForwardingBlock {
+            col_s_id: Column::load(column("s_id")?)?,
+            col_sf_type: Column::load(column("sf_type")?)?,
+            col_start_time: Column::load(column("start_time")?)?,
+            col_end_time: Column::load(column("end_time")?)?,
+            col_numberx: Column::load(column("numberx")?)?,
+        };
+
+        let mut keys = vec![];
+        let by_special_facility = MultiHashIndex::new();
+
+        for row in 0..batch.num_rows() {
+            let s_id = first.col_s_id.value(row);
+            let sf_type = first.col_sf_type.value(row);
+            by_special_facility.insert((s_id, sf_type), row);
+            keys.push((s_id, sf_type, first.col_start_time.value(row)));
+        }
+
+        Ok(CallForwarding {
+            table: Table::new(keys, first, BLOCK_LEN),
+            by_special_facility,
+        })
+    }
+}
+
+pub struct ArrowTATPDatabase {
+    subscriber: Subscriber,
+    access_info: AccessInfo,
+    special_facility: SpecialFacility,
+    call_forwarding: CallForwarding,
+}
+
+impl ArrowTATPDatabase {
+    pub fn new(num_rows: u32, rng: &mut StdRng) -> ArrowTATPDatabase {
+        let subscriber = Subscriber::new(num_rows, rng);
+        let access_info = AccessInfo::new(&subscriber, rng);
+        let special_facility = SpecialFacility::new(&subscriber, rng);
+        let call_forwarding = CallForwarding::new(&special_facility, rng);
+
+        ArrowTATPDatabase {
+            subscriber,
+            access_info,
+            special_facility,
+            call_forwarding,
+        }
+    }
+
+    /// Saves the database to the directory at `path`, with one Arrow IPC file per table.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::create_dir_all(path)?;
+        self.subscriber.save(&path.join("subscriber.arrow"))?;
+        self.access_info.save(&path.join("access_info.arrow"))?;
+        self.special_facility
+            .save(&path.join("special_facility.arrow"))?;
+        self.call_forwarding
+            .save(&path.join("call_forwarding.arrow"))
+    }
+
+    /// Loads a database saved by `save` from the directory at `path`.
+    pub fn load(path: &Path) -> Result<ArrowTATPDatabase> {
+        Ok(ArrowTATPDatabase {
+            subscriber: Subscriber::load(&path.join("subscriber.arrow"))?,
+            access_info: AccessInfo::load(&path.join("access_info.arrow"))?,
+            special_facility: SpecialFacility::load(&path.join("special_facility.arrow"))?,
+            call_forwarding: CallForwarding::load(&path.join("call_forwarding.arrow"))?,
+        })
+    }
+
+    /// Loads the database from the directory at `path` if there is one, and otherwise creates
+    /// it with `num_rows` subscribers drawn from `rng` and saves it there.
+    pub fn load_or_create(
+        path: &Path,
+        num_rows: u32,
+        rng: &mut StdRng,
+    ) -> Result<ArrowTATPDatabase> {
+        if path.exists() {
+            let db = ArrowTATPDatabase::load(path)?;
+
+            if db.num_subscribers() != num_rows as usize {
+                return Err(ArrowError::InvalidArgumentError(format!(
+                    "{} holds {} subscribers, not {}",
+                    path.display(),
+                    db.num_subscribers(),
+                    num_rows
+                )));
+            }
+
+            Ok(db)
+        } else {
+            let db = ArrowTATPDatabase::new(num_rows, rng);
+            db.save(path)?;
+            Ok(db)
+        }
+    }
+
+    pub fn num_subscribers(&self) -> usize {
+        self.subscriber
+            .table
+            .rows_by_block()
+            .iter()
+            .map(|(_, indexes)| indexes.len())
+            .sum()
+    }
+
+    /// Checks the invariants of `consistency::tatp_checks` that the column types don't already
+    /// guarantee, under the same names. It should only be called once the workers have stopped.
+    pub fn check_consistency(&self) -> Consistency {
+        fn count<B, F>(rows: Vec<(Arc<B>, Vec<usize>)>, violates: F) -> usize
+        where
+            F: Fn(&B, usize) -> bool,
+        {
+            rows.iter()
+                .map(|(block, indexes)| {
+                    indexes
+                        .iter()
+                        .filter(|&&index| violates(block, index))
+                        .count()
+                })
+                .sum()
+        }
+
+        let subscriber = &self.subscriber.table;
+        let access_info = &self.access_info.table;
+        let special_facility = &self.special_facility.table;
+        let call_forwarding = &self.call_forwarding.table;
+        let mut consistency = Consistency::default();
+
+        consistency.push(
+            "access_info references subscriber",
+            count(access_info.rows_by_block(), |block, index| {
+                subscriber.get(&block.col_s_id.value(index)).is_none()
+            }),
+        );
+
+        consistency.push(
+            "special_facility references subscriber",
+            count(special_facility.rows_by_block(), |block, index| {
+                subscriber.get(&block.col_s_id.value(index)).is_none()
+            }),
+        );
+
+        consistency.push(
+            "call_forwarding references special_facility",
+            count(call_forwarding.rows_by_block(), |block, index| {
+                let key = (block.col_s_id.value(index), block.col_sf_type.value(index));
+                special_facility.get(&key).is_none()
+            }),
+        );
+
+        consistency.push(
+            "subscriber ranges",
+            count(subscriber.rows_by_block(), |block, index| {
+                block.col_hex.iter().any(|hex| hex.value(index) > 15)
+            }),
+        );
+
+        consistency.push(
+            "access_info ranges",
+            count(access_info.rows_by_block(), |block, index| {
+                !(1..=4).contains(&block.col_ai_type.value(index))
+            }),
+        );
+
+        consistency.push(
+            "special_facility ranges",
+            count(special_facility.rows_by_block(), |block, index| {
+                !(1..=4).contains(&block.col_sf_type.value(index))
+            }),
+        );
+
+        consistency.push(
+            "call_forwarding ranges",
+            count(call_forwarding.rows_by_block(), |block, index| {
+                ![0, 8, 16].contains(&block.col_start_time.value(index))
+                    || !(1..=24).contains(&block.col_end_time.value(index))
+                    || block.col_numberx.value(index).len() != 15
+            }),
+        );
+
+        consistency
+    }
+}
+
+/// A change to the TATP database, as written to the write-ahead log.
+pub enum TATPRecord {
+    UpdateSubscriberBit {
+        s_id: u32,
+        bit_1: bool,
+    },
+    UpdateSubscriberLocation {
+        s_id: u32,
+        vlr_location: u32,
+    },
+    UpdateSpecialFacilityData {
+        s_id: u32,
+        sf_type: u8,
+        data_a: u8,
+    },
+    InsertCallForwarding {
+        s_id: u32,
+        sf_type: u8,
+        start_time: u8,
+        end_time: u8,
+        numberx: String,
+    },
+    DeleteCallForwarding {
+        s_id: u32,
+        sf_type: u8,
+        start_time: u8,
+    },
+    InsertSubscriber {
+        s_id: u32,
+        data: SubscriberRow,
+    },
+    DeleteSubscriber {
+        s_id: u32,
+    },
+    InsertAccessInfo {
+        s_id: u32,
+        ai_type: u8,
+        data1: u8,
+        data2: u8,
+        data3: String,
+        data4: String,
+    },
+    DeleteAccessInfo {
+        s_id: u32,
+        ai_type: u8,
+    },
+    InsertSpecialFacility {
+        s_id: u32,
+        sf_type: u8,
+        is_active: bool,
+        error_cntrl: u8,
+        data_a: u8,
+        data_b: Vec<u8>,
+    },
+    DeleteSpecialFacility {
+        s_id: u32,
+        sf_type: u8,
+    },
+}
+
+impl Record for TATPRecord {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            TATPRecord::UpdateSubscriberBit { s_id, bit_1 } => {
+                codec::put_u8(buf, 0);
+                codec::put_u32(buf, *s_id);
+                codec::put_bool(buf, *bit_1);
+            }
+            TATPRecord::UpdateSubscriberLocation { s_id, vlr_location } => {
+                codec::put_u8(buf, 1);
+                codec::put_u32(buf, *s_id);
+                codec::put_u32(buf, *vlr_location);
+            }
+            TATPRecord::UpdateSpecialFacilityData {
+                s_id,
+                sf_type,
+                data_a,
+            } => {
+                codec::put_u8(buf, 2);
+                codec::put_u32(buf, *s_id);
+                codec::put_u8(buf, *sf_type);
+                codec::put_u8(buf, *data_a);
+            }
+            TATPRecord::InsertCallForwarding {
+                s_id,
+                sf_type,
+                start_time,
+                end_time,
+                numberx,
+            } => {
+                codec::put_u8(buf, 3);
+                codec::put_u32(buf, *s_id);
+                codec::put_u8(buf, *sf_type);
+                codec::put_u8(buf, *start_time);
+                codec::put_u8(buf, *end_time);
+                codec::put_bytes(buf, numberx.as_bytes());
+            }
+            TATPRecord::DeleteCallForwarding {
+                s_id,
+                sf_type,
+                start_time,
+            } => {
+                codec::put_u8(buf, 4);
+                codec::put_u32(buf, *s_id);
+                codec::put_u8(buf, *sf_type);
+                codec::put_u8(buf, *start_time);
+            }
+            TATPRecord::InsertSubscriber { s_id, data } => {
+                let (bit, hex, byte2, msc_location, vlr_location) = data;
+                codec::put_u8(buf, 5);
+                codec::put_u32(buf, *s_id);
+                bit.iter().for_each(|&value| codec::put_bool(buf, value));
+                hex.iter().for_each(|&value| codec::put_u8(buf, value));
+                byte2.iter().for_each(|&value| codec::put_u8(buf, value));
+                codec::put_u32(buf, *msc_location);
+                codec::put_u32(buf, *vlr_location);
+            }
+            TATPRecord::DeleteSubscriber { s_id } => {
+                codec::put_u8(buf, 6);
+                codec::put_u32(buf, *s_id);
+            }
+            TATPRecord::InsertAccessInfo {
+                s_id,
+                ai_type,
+                data1,
+                data2,
+                data3,
+                data4,
+            } => {
+                codec::put_u8(buf, 7);
+                codec::put_u32(buf, *s_id);
+                codec::put_u8(buf, *ai_type);
+                codec::put_u8(buf, *data1);
+                codec::put_u8(buf, *data2);
+                codec::put_bytes(buf, data3.as_bytes());
+                codec::put_bytes(buf, data4.as_bytes());
+            }
+            TATPRecord::DeleteAccessInfo { s_id, ai_type } => {
+                codec::put_u8(buf, 8);
+                codec::put_u32(buf, *s_id);
+                codec::put_u8(buf, *ai_type);
+            }
+            TATPRecord::InsertSpecialFacility {
+                s_id,
+                sf_type,
+                is_active,
+                error_cntrl,
+                data_a,
+                data_b,
+            } => {
+                codec::put_u8(buf, 9);
+                codec::put_u32(buf, *s_id);
+                codec::put_u8(buf, *sf_type);
+                codec::put_bool(buf, *is_active);
+                codec::put_u8(buf, *error_cntrl);
+                codec::put_u8(buf, *data_a);
+                codec::put_bytes(buf, data_b);
+            }
+            TATPRecord::DeleteSpecialFacility { s_id, sf_type } => {
+                codec::put_u8(buf, 10);
+                codec::put_u32(buf, *s_id);
+                codec::put_u8(buf, *sf_type);
+            }
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> Option<TATPRecord> {
+        let record = match decoder.u8()? {
+            0 => TATPRecord::UpdateSubscriberBit {
+                s_id: decoder.u32()?,
+                bit_1: decoder.bool()?,
+            },
+            1 => TATPRecord::UpdateSubscriberLocation {
+                s_id: decoder.u32()?,
+                vlr_location: decoder.u32()?,
+            },
+            2 => TATPRecord::UpdateSpecialFacilityData {
+                s_id: decoder.u32()?,
+                sf_type: decoder.u8()?,
+                data_a: decoder.u8()?,
+            },
+            3 => TATPRecord::InsertCallForwarding {
+                s_id: decoder.u32()?,
+                sf_type: decoder.u8()?,
+                start_time: decoder.u8()?,
+                end_time: decoder.u8()?,
+                numberx: decoder.string()?,
+            },
+            4 => TATPRecord::DeleteCallForwarding {
+                s_id: decoder.u32()?,
+                sf_type: decoder.u8()?,
+                start_time: decoder.u8()?,
+            },
+            5 => {
+                let s_id = decoder.u32()?;
+                let mut bit = [false; 10];
+                for value in &mut bit {
+                    *value = decoder.bool()?;
+                }
+
+                let mut hex = [0; 10];
+                for value in &mut hex {
+                    *value = decoder.u8()?;
+                }
+
+                let mut byte2 = [0; 10];
+                for value in &mut byte2 {
+                    *value = decoder.u8()?;
+                }
+
+                TATPRecord::InsertSubscriber {
+                    s_id,
+                    data: (bit, hex, byte2, decoder.u32()?, decoder.u32()?),
+                }
+            }
+            6 => TATPRecord::DeleteSubscriber {
+                s_id: decoder.u32()?,
+            },
+            7 => TATPRecord::InsertAccessInfo {
+                s_id: decoder.u32()?,
+                ai_type: decoder.u8()?,
+                data1: decoder.u8()?,
+                data2: decoder.u8()?,
+                data3: decoder.string()?,
+                data4: decoder.string()?,
+            },
+            8 => TATPRecord::DeleteAccessInfo {
+                s_id: decoder.u32()?,
+                ai_type: decoder.u8()?,
+            },
+            9 => TATPRecord::InsertSpecialFacility {
+                s_id: decoder.u32()?,
+                sf_type: decoder.u8()?,
+                is_active: decoder.bool()?,
+                error_cntrl: decoder.u8()?,
+                data_a: decoder.u8()?,
+                data_b: decoder.bytes()?.to_vec(),
+            },
+            10 => TATPRecord::DeleteSpecialFacility {
+                s_id: decoder.u32()?,
+                sf_type: decoder.u8()?,
+            },
+            _ => return None,
+        };
+
+        Some(record)
+    }
+}
+
+impl ArrowTATPDatabase {
+    /// Loads the snapshot saved by `save` at `snapshot` and replays the log at `wal` over it.
+    pub fn recover(snapshot: &Path, wal: &Path) -> Result<ArrowTATPDatabase> {
+        let db = ArrowTATPDatabase::load(snapshot)?;
+
+        Wal::replay(wal, |record| {
+            db.apply(&record);
+        })?;
+
+        Ok(db)
+    }
+
+    /// Makes the change described by `record`, returning whether it changed anything.
+    fn apply(&self, record: &TATPRecord) -> bool {
+        match record {
+            TATPRecord::UpdateSubscriberBit { s_id, bit_1 } => self
+                .subscriber
+                .table
+                .update(s_id, |block, index| block.col_bit[0].set(index, *bit_1)),
+            TATPRecord::UpdateSubscriberLocation { s_id, vlr_location } => {
+                self.subscriber.table.update(s_id, |block, index| {
+                    block.col_vlr_location.set(index, *vlr_location)
+                })
+            }
+            TATPRecord::UpdateSpecialFacilityData {
+                s_id,
+                sf_type,
+                data_a,
+            } => self
+                .special_facility
+                .table
+                .update(&(*s_id, *sf_type), |block, index| {
+                    block.col_data_a.set(index, *data_a)
+                }),
+            TATPRecord::InsertCallForwarding {
+                s_id,
+                sf_type,
+                start_time,
+                end_time,
+                numberx,
+            } => {
+                let call_forwarding = &self.call_forwarding;
+
+                call_forwarding
+                    .table
+                    .insert((*s_id, *sf_type, *start_time), |row| {
+                        let (block, index) = call_forwarding.table.block(row);
+                        block.col_s_id.set(index, *s_id);
+                        block.col_sf_type.set(index, *sf_type);
+                        block.col_start_time.set(index, *start_time);
+                        block.col_end_time.set(index, *end_time);
+                        block.col_numberx.set(index, numberx);
+                        call_forwarding
+                            .by_special_facility
+                            .insert((*s_id, *sf_type), row);
+                    })
+                    .is_some()
+            }
+            TATPRecord::DeleteCallForwarding {
+                s_id,
+                sf_type,
+                start_time,
+            } => {
+                let call_forwarding = &self.call_forwarding;
+
+                call_forwarding
+                    .table
+                    .delete(&(*s_id, *sf_type, *start_time), |row| {
+                        call_forwarding
+                            .by_special_facility
+                            .remove(&(*s_id, *sf_type), row)
+                    })
+                    .is_some()
+            }
+            TATPRecord::InsertSubscriber { s_id, data } => {
+                let subscriber = &self.subscriber;
+
+                subscriber
+                    .table
+                    .insert(*s_id, |row| {
+                        let (block, index) = subscriber.table.block(row);
+                        block.set_row_data(index, *s_id, *data);
+                    })
+                    .is_some()
+            }
+            TATPRecord::DeleteSubscriber { s_id } => {
+                // A subscriber's access info, special facilities, and call forwardings go with
+                // it.
+                for ai_type in 1..=4 {
+                    self.apply(&TATPRecord::DeleteAccessInfo {
+                        s_id: *s_id,
+                        ai_type,
+                    });
+                }
+
+                for sf_type in self.get_special_facility_types(*s_id) {
+                    self.apply(&TATPRecord::DeleteSpecialFacility {
+                        s_id: *s_id,
+                        sf_type,
+                    });
+                }
+
+                self.subscriber.table.delete(s_id, |_| {}).is_some()
+            }
+            TATPRecord::InsertAccessInfo {
+                s_id,
+                ai_type,
+                data1,
+                data2,
+                data3,
+                data4,
+            } => {
+                let access_info = &self.access_info;
+
+                access_info
+                    .table
+                    .insert((*s_id, *ai_type), |row| {
+                        let (block, index) = access_info.table.block(row);
+                        block.col_s_id.set(index, *s_id);
+                        block.col_ai_type.set(index, *ai_type);
+                        block.col_data1.set(index, *data1);
+                        block.col_data2.set(index, *data2);
+                        block.col_data3.set(index, data3.as_bytes());
+                        block.col_data4.set(index, data4.as_bytes());
+                    })
+                    .is_some()
+            }
+            TATPRecord::DeleteAccessInfo { s_id, ai_type } => self
+                .access_info
+                .table
+                .delete(&(*s_id, *ai_type), |_| {})
+                .is_some(),
+            TATPRecord::InsertSpecialFacility {
+                s_id,
+                sf_type,
+                is_active,
+                error_cntrl,
+                data_a,
+                data_b,
+            } => {
+                let special_facility = &self.special_facility;
+
+                special_facility
+                    .table
+                    .insert((*s_id, *sf_type), |row| {
+                        let (block, index) = special_facility.table.block(row);
+                        block.col_s_id.set(index, *s_id);
+                        block.col_sf_type.set(index, *sf_type);
+                        block.col_is_active.set(index, *is_active);
+                        block.col_error_cntrl.set(index, *error_cntrl);
+                        block.col_data_a.set(index, *data_a);
+                        block.col_data_b.set(index, data_b);
+                        special_facility.by_s_id.insert(*s_id, row);
+                    })
+                    .is_some()
+            }
+            TATPRecord::DeleteSpecialFacility { s_id, sf_type } => {
+                // A special facility's call forwardings go with it.
+                let call_forwarding = &self.call_forwarding;
+
+                for cf_row in call_forwarding.by_special_facility.get(&(*s_id, *sf_type)) {
+                    let (block, index) = call_forwarding.table.block(cf_row);
+
+                    self.apply(&TATPRecord::DeleteCallForwarding {
+                        s_id: *s_id,
+                        sf_type: *sf_type,
+                        start_time: block.col_start_time.value(index),
+                    });
+                }
+
+                let special_facility = &self.special_facility;
+
+                special_facility
+                    .table
+                    .delete(&(*s_id, *sf_type), |row| {
+                        special_facility.by_s_id.remove(s_id, row)
+                    })
+                    .is_some()
+            }
+        }
+    }
+
+    fn get_special_facility_types(&self, s_id: u32) -> Vec<u8> {
+        let special_facility = &self.special_facility;
+
+        special_facility
+            .by_s_id
+            .get(&s_id)
+            .into_iter()
+            .map(|row| {
+                let (block, index) = special_facility.table.block(row);
+                block.col_sf_type.value(index)
+            })
+            .collect()
+    }
+}
+
+impl Checkpointed for ArrowTATPDatabase {
+    type Record = TATPRecord;
+
+    const TABLES: &'static [CheckpointTable] = &[
+        CheckpointTable {
+            name: "subscriber",
+            key: &["s_id"],
+        },
+        CheckpointTable {
+            name: "access_info",
+            key: &["s_id", "ai_type"],
+        },
+        CheckpointTable {
+            name: "special_facility",
+            key: &["s_id", "sf_type"],
+        },
+        CheckpointTable {
+            name: "call_forwarding",
+            key: &["s_id", "sf_type", "start_time"],
+        },
+    ];
+
+    fn save_checkpoint(&self, path: &Path) -> Result<()> {
+        self.save(path)
+    }
+
+    fn recover_checkpoint(path: &Path, wal: &Path) -> Result<ArrowTATPDatabase> {
+        ArrowTATPDatabase::recover(path, wal)
+    }
+
+    /// Deleting a subscriber or special facility deletes the rows that reference it too.
+    fn writes(record: &TATPRecord) -> Vec<Write> {
+        match *record {
+            TATPRecord::UpdateSubscriberBit { s_id, .. } => {
+                vec![Write::column("subscriber", vec![s_id as u64], "bit_1")]
+            }
+            TATPRecord::UpdateSubscriberLocation { s_id, .. } => {
+                vec![Write::column(
+                    "subscriber",
+                    vec![s_id as u64],
+                    "vlr_location",
+                )]
+            }
+            TATPRecord::UpdateSpecialFacilityData { s_id, sf_type, .. } => vec![Write::column(
+                "special_facility",
+                vec![s_id as u64, sf_type as u64],
+                "data_a",
+            )],
+            TATPRecord::InsertCallForwarding {
+                s_id,
+                sf_type,
+                start_time,
+                ..
+            }
+            | TATPRecord::DeleteCallForwarding {
+                s_id,
+                sf_type,
+                start_time,
+            } => vec![Write::rows(
+                "call_forwarding",
+                vec![s_id as u64, sf_type as u64, start_time as u64],
+            )],
+            TATPRecord::InsertSubscriber { s_id, .. } => {
+                vec![Write::rows("subscriber", vec![s_id as u64])]
+            }
+            TATPRecord::DeleteSubscriber { s_id } => [
+                "subscriber",
+                "access_info",
+                "special_facility",
+                "call_forwarding",
+            ]
+            .iter()
+            .map(|table| Write::rows(table, vec![s_id as u64]))
+            .collect(),
+            TATPRecord::InsertAccessInfo { s_id, ai_type, .. }
+            | TATPRecord::DeleteAccessInfo { s_id, ai_type } => vec![Write::rows(
+                "access_info",
+                vec![s_id as u64, ai_type as u64],
+            )],
+            TATPRecord::InsertSpecialFacility { s_id, sf_type, .. } => vec![Write::rows(
+                "special_facility",
+                vec![s_id as u64, sf_type as u64],
+            )],
+            TATPRecord::DeleteSpecialFacility { s_id, sf_type } => {
+                ["special_facility", "call_forwarding"]
+                    .iter()
+                    .map(|table| Write::rows(table, vec![s_id as u64, sf_type as u64]))
+                    .collect()
+            }
+        }
+    }
+}
+
+pub struct ArrowTATPConnection {
+    db: Arc<ArrowTATPDatabase>,
+    wal: Option<Arc<Wal>>,
+    records: Vec<u8>,
+}
+
+impl ArrowTATPConnection {
+    pub fn new(db: Arc<ArrowTATPDatabase>) -> ArrowTATPConnection {
+        ArrowTATPConnection {
+            db,
+            wal: None,
+            records: vec![],
+        }
+    }
+
+    /// Logs every change to `wal` if there is one. The log is synced whenever a transaction
+    /// that made changes ends.
+    pub fn with_wal(self, wal: Option<Arc<Wal>>) -> ArrowTATPConnection {
+        ArrowTATPConnection { wal, ..self }
+    }
+
+    /// Makes the change described by `record` and logs it if it changed anything, returning
+    /// whether it did.
+    fn execute(&mut self, record: TATPRecord) -> bool {
+        let changed = self.db.apply(&record);
+
+        if changed && self.wal.is_some() {
+            record.encode(&mut self.records);
+        }
+
+        changed
+    }
+
+    /// Inserts a subscriber, returning `false` if one with `s_id` exists.
+    pub fn insert_subscriber(&mut self, s_id: u32, data: SubscriberRow) -> bool {
+        self.execute(TATPRecord::InsertSubscriber { s_id, data })
+    }
+
+    /// Deletes a subscriber along with its access info, special facilities, and call
+    /// forwardings, returning `false` if there is no subscriber with `s_id`.
+    pub fn delete_subscriber(&mut self, s_id: u32) -> bool {
+        self.execute(TATPRecord::DeleteSubscriber { s_id })
+    }
+
+    /// Inserts access info, returning `false` if there is already some for `s_id` and `ai_type`.
+    pub fn insert_access_info(
+        &mut self,
+        s_id: u32,
+        ai_type: u8,
+        data1: u8,
+        data2: u8,
+        data3: &str,
+        data4: &str,
+    ) -> bool {
+        self.execute(TATPRecord::InsertAccessInfo {
+            s_id,
+            ai_type,
+            data1,
+            data2,
+            data3: data3.to_string(),
+            data4: data4.to_string(),
+        })
+    }
+
+    /// Deletes access info, returning `false` if there is none for `s_id` and `ai_type`.
+    pub fn delete_access_info(&mut self, s_id: u32, ai_type: u8) -> bool {
+        self.execute(TATPRecord::DeleteAccessInfo { s_id, ai_type })
+    }
+
+    /// Inserts a special facility, returning `false` if one with `s_id` and `sf_type` exists.
+    pub fn insert_special_facility(
+        &mut self,
+        s_id: u32,
+        sf_type: u8,
+        is_active: bool,
+        error_cntrl: u8,
+        data_a: u8,
+        data_b: &[u8],
+    ) -> bool {
+        self.execute(TATPRecord::InsertSpecialFacility {
+            s_id,
+            sf_type,
+            is_active,
+            error_cntrl,
+            data_a,
+            data_b: data_b.to_vec(),
+        })
+    }
+
+    /// Deletes a special facility along with its call forwardings, returning `false` if there is
+    /// none with `s_id` and `sf_type`.
+    pub fn delete_special_facility(&mut self, s_id: u32, sf_type: u8) -> bool {
+        self.execute(TATPRecord::DeleteSpecialFacility { s_id, sf_type })
+    }
+
+    /// Hands the records of the transaction that just ended to the log, waiting until they are
+    /// durable. The Arrow engines can't undo changes, so this happens whether the transaction
+    /// committed or rolled back.
+    fn flush(&mut self) {
+        if let Some(wal) = &self.wal {
+            if !self.records.is_empty() {
+                wal.commit(&self.records).unwrap();
+                self.records.clear();
+            }
+        }
+    }
+}
+
+impl Connection for ArrowTATPConnection {
+    fn begin(&mut self) {}
+
+    fn commit(&mut self) {
+        self.flush();
+    }
+
+    fn rollback(&mut self) {
+        self.flush();
+    }
+
+    fn savepoint(&mut self) {}
+}
+
+impl Logged for ArrowTATPConnection {
+    fn take_records(&mut self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.records);
+        self.records.clear();
+    }
+}
+
+impl TATPConnection for ArrowTATPConnection {
+    fn get_subscriber_data(&mut self, s_id: u32) -> SubscriberRow {
+        self.db
+            .subscriber
+            .table
+            .read(&s_id, |block, index| block.get_row_data(index))
+            .unwrap()
+    }
+
+    fn get_new_destination(
+        &mut self,
+        s_id: u32,
+        sf_type: u8,
+        start_time: u8,
+        end_time: u8,
+    ) -> Vec<String> {
+        let special_facility = &self.db.special_facility;
+        let call_forwarding = &self.db.call_forwarding;
+
+        let is_active = special_facility
+            .table
+            .read(&(s_id, sf_type), |block, index| {
+                block.col_is_active.value(index)
+            });
+
+        match is_active {
+            Some(true) => call_forwarding
+                .by_special_facility
+                .get(&(s_id, sf_type))
+                .into_iter()
+                .filter_map(|cf_row| {
+                    let (block, index) = call_forwarding.table.block(cf_row);
+
+                    if block.col_start_time.value(index) <= start_time
+                        && end_time < block.col_end_time.value(index)
+                    {
+                        Some(block.col_numberx.value(index))
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    fn get_access_data(&mut self, s_id: u32, ai_type: u8) -> Option<(u8, u8, String, String)> {
+        self.db
+            .access_info
+            .table
+            .read(&(s_id, ai_type), |block, index| {
+                (
+                    block.col_data1.value(index),
+                    block.col_data2.value(index),
+                    String::from_utf8(block.col_data3.value(index).to_vec()).unwrap(),
+                    String::from_utf8(block.col_data4.value(index).to_vec()).unwrap(),
+                )
+            })
+    }
+
+    fn update_subscriber_bit(&mut self, bit_1: bool, s_id: u32) {
+        self.execute(TATPRecord::UpdateSubscriberBit { s_id, bit_1 });
+    }
+
+    fn update_special_facility_data(&mut self, data_a: u8, s_id: u32, sf_type: u8) {
+        self.execute(TATPRecord::UpdateSpecialFacilityData {
+            s_id,
+            sf_type,
+            data_a,
+        });
+    }
+
+    fn update_subscriber_location(&mut self, vlr_location: u32, s_id: u32) {
+        self.execute(TATPRecord::UpdateSubscriberLocation { s_id, vlr_location });
+    }
+
+    fn get_special_facility_types(&mut self, s_id: u32) -> Vec<u8> {
+        self.db.get_special_facility_types(s_id)
+    }
+
+    fn insert_call_forwarding(
+        &mut self,
+        s_id: u32,
+        sf_type: u8,
+        start_time: u8,
+        end_time: u8,
+        numberx: &str,
+    ) {
+        self.execute(TATPRecord::InsertCallForwarding {
+            s_id,
+            sf_type,
+            start_time,
+            end_time,
+            numberx: numberx.to_string(),
+        });
+    }
+
+    fn delete_call_forwarding(&mut self, s_id: u32, sf_type: u8, start_time: u8) {
+        self.execute(TATPRecord::DeleteCallForwarding {
+            s_id,
+            sf_type,
+            start_time,
+        });
+    }
+}
+
+/// Scans the subscribers of a TATP database, so that scans can run alongside TATP transactions.
+impl ScanConnection for ArrowTATPConnection {
+    fn get_subscriber_data_scan(&mut self, byte2: [(u8, u8, u8, u8); 10]) -> Vec<SubscriberRow> {
+        let mut rows = vec![];
+
+        self.db
+            .subscriber
+            .scan(byte2, |block, index| rows.push(block.get_row_data(index)));
+
+        rows
+    }
+
+    /// Updates the matching subscribers one at a time, so that each update is logged.
+    fn update_subscriber_location_scan(
+        &mut self,
+        vlr_location: u32,
+        byte2: [(u8, u8, u8, u8); 10],
+    ) {
+        let mut s_ids = vec![];
+
+        self.db.subscriber.scan(byte2, |block, index| {
+            s_ids.push(block.col_s_id.value(index))
+        });
+
+        for s_id in s_ids {
+            self.execute(TATPRecord::UpdateSubscriberLocation { s_id, vlr_location });
+        }
+    }
+}
+
+pub struct ArrowScanDatabase {
+    subscriber: Subscriber,
+}
+
+impl ArrowScanDatabase {
+    pub fn new(num_rows: u32, rng: &mut StdRng) -> ArrowScanDatabase {
+        ArrowScanDatabase {
+            subscriber: Subscriber::new(num_rows, rng),
+        }
+    }
+}
+
+pub struct ArrowScanConnection {
+    db: Arc<ArrowScanDatabase>,
+}
+
+impl ArrowScanConnection {
+    pub fn new(db: Arc<ArrowScanDatabase>) -> ArrowScanConnection {
+        ArrowScanConnection { db }
+    }
+}
+
+impl Connection for ArrowScanConnection {
+    fn begin(&mut self) {}
+    fn commit(&mut self) {}
+    fn rollback(&mut self) {}
+    fn savepoint(&mut self) {}
+}
+
+impl ScanConnection for ArrowScanConnection {
+    fn get_subscriber_data_scan(&mut self, byte2: [(u8, u8, u8, u8); 10]) -> Vec<SubscriberRow> {
+        let mut rows = vec![];
+
+        self.db
+            .subscriber
+            .scan(byte2, |block, index| rows.push(block.get_row_data(index)));
+
+        rows
+    }
+
+    fn update_subscriber_location_scan(
+        &mut self,
+        vlr_location: u32,
+        byte2: [(u8, u8, u8, u8); 10],
+    ) {
+        self.db.subscriber.scan(byte2, |block, index| {
+            block.col_vlr_location.set(index, vlr_location)
+        });
+    }
+}
+
+struct UserBlock {
+    col_user_id: UInt32ArrayMut,
+    col_fields: Vec<StringArrayMut>,
+}
+
+impl Block for UserBlock {
+    fn empty(&self, len: usize) -> UserBlock {
+        UserBlock {
+            col_user_id: UInt32ArrayMut::new(UInt32Builder::new(len), len),
+            col_fields: (0..ycsb::NUM_FIELDS)
+                .map(|_| StringArrayMut::new(StringBuilder::with_capacity(len, 0), len))
+                .collect(),
+        }
+    }
+}
+
+pub struct ArrowYCSBDatabase {
+    table: Table<u32, UserBlock>,
+    /// The row of each user, ordered by user ID for scans.
+    by_user_id: RwLock<BTreeMap<u32, usize>>,
+}
+
+impl ArrowYCSBDatabase {
+    pub fn new(num_rows: u32, field_size: usize, rng: &mut StdRng) -> ArrowYCSBDatabase {
+        assert!(field_size > 0 && field_size <= i32::max_value() as usize);
+
+        let mut user_ids = (0..num_rows).collect::<Vec<_>>();
+        user_ids.shuffle(rng);
+
+        let capacity = user_ids.len();
+
+        let mut user_id_builder = UInt32Builder::new(capacity);
+        let mut field_builders = (0..ycsb::NUM_FIELDS)
+            .map(|_| StringBuilder::with_capacity(capacity, capacity * field_size))
+            .collect::<Vec<_>>();
+
+        for &user_id in &user_ids {
+            user_id_builder.append_value(user_id).unwrap();
+
+            for field_builder in &mut field_builders {
+                field_builder
+                    .append_value(
+                        &(0..field_size)
+                            .map(|_| rng.sample(Alphanumeric))
+                            .collect::<String>(),
+                    )
+                    .unwrap();
+            }
+        }
+
+        let first = UserBlock {
+            col_user_id: UInt32ArrayMut::new(user_id_builder, capacity),
+            col_fields: field_builders
+                .into_iter()
+                .map(|b| StringArrayMut::new(b, capacity))
+                .collect(),
+        };
+
+        ArrowYCSBDatabase::with_first(user_ids, first)
+    }
+
+    /// Creates a database whose users are already loaded into `first` with `user_ids` in order.
+    fn with_first(user_ids: Vec<u32>, first: UserBlock) -> ArrowYCSBDatabase {
+        let by_user_id = user_ids
+            .iter()
+            .enumerate()
+            .map(|(row, &user_id)| (user_id, row))
+            .collect();
+
+        ArrowYCSBDatabase {
+            table: Table::new(user_ids, first, BLOCK_LEN),
+            by_user_id: RwLock::new(by_user_id),
+        }
+    }
+
+    /// Saves the users to the Arrow IPC file at `path`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let rows = self.table.rows_by_block();
+        let mut columns = vec![(
+            "user_id".to_string(),
+            snapshot::gather(&rows, |block: &UserBlock| &block.col_user_id),
+        )];
+
+        for i in 0..ycsb::NUM_FIELDS {
+            columns.push((
+                format!("field_{}", i),
+                snapshot::gather(&rows, |block: &UserBlock| &block.col_fields[i]),
+            ));
+        }
+
+        snapshot::save(path, columns)
+    }
+
+    /// Loads a database saved by `save` from the file at `path`.
+    pub fn load(path: &Path) -> Result<ArrowYCSBDatabase> {
+        let batch = snapshot::load(path)?;
+        let column = |name: &str| snapshot::column(&batch, name);
+
+        let first = UserBlock {
+            col_user_id: Column::load(column("user_id")?)?,
+            col_fields: (0..ycsb::NUM_FIELDS)
+                .map(|i| Column::load(column(&format!("field_{}", i))?))
+                .collect::<Result<_>>()?,
+        };
+
+        let user_ids = (0..batch.num_rows())
+            .map(|row| first.col_user_id.value(row))
+            .collect();
+
+        Ok(ArrowYCSBDatabase::with_first(user_ids, first))
+    }
+
+    /// Loads the database from the file at `path` if there is one, and otherwise creates it
+    /// with `num_rows` users whose fields are `field_size` bytes long, drawn from `rng`, and
+    /// saves it there.
+    pub fn load_or_create(
+        path: &Path,
+        num_rows: u32,
+        field_size: usize,
+        rng: &mut StdRng,
+    ) -> Result<ArrowYCSBDatabase> {
+        if path.exists() {
+            let db = ArrowYCSBDatabase::load(path)?;
+            let num_users = db.num_users();
+            let db_field_size = db.field_size();
+
+            if num_users != num_rows as usize || db_field_size != field_size {
+                return Err(ArrowError::InvalidArgumentError(format!(
+                    "{} holds {} users with {}-byte fields, not {} with {}-byte fields",
+                    path.display(),
+                    num_users,
+                    db_field_size,
+                    num_rows,
+                    field_size
+                )));
+            }
+
+            Ok(db)
+        } else {
+            let db = ArrowYCSBDatabase::new(num_rows, field_size, rng);
+            db.save(path)?;
+            Ok(db)
+        }
+    }
+
+    pub fn num_users(&self) -> usize {
+        self.by_user_id.read().unwrap().len()
+    }
+
+    /// The length of the first field of the user with the lowest ID, which a freshly loaded
+    /// database gives every field.
+    fn field_size(&self) -> usize {
+        let by_user_id = self.by_user_id.read().unwrap();
+
+        by_user_id.values().next().map_or(0, |&row| {
+            let (block, index) = self.table.block(row);
+            block.col_fields[0].value(index).len()
+        })
+    }
+
+    /// Checks the invariants of `consistency::ycsb_checks` under the same names. It should only
+    /// be called once the workers have stopped.
+    pub fn check_consistency(&self, field_size: usize) -> Consistency {
+        let violations = self
+            .table
+            .rows_by_block()
+            .iter()
+            .map(|(block, indexes)| {
+                indexes
+                    .iter()
+                    .filter(|&&index| {
+                        block
+                            .col_fields
+                            .iter()
+                            .any(|col_field| col_field.value(index).len() != field_size)
+                    })
+                    .count()
+            })
+            .sum();
+
+        let mut consistency = Consistency::default();
+        consistency.push("field sizes", violations);
+        consistency
+    }
+}
+
+/// A change to the YCSB database, as written to the write-ahead log.
+pub enum YCSBRecord {
+    UpdateUser {
+        user_id: u32,
+        field: usize,
+        data: String,
+    },
+    InsertUser {
+        user_id: u32,
+        fields: Vec<String>,
+    },
+    DeleteUser {
+        user_id: u32,
+    },
+}
+
+impl Record for YCSBRecord {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            YCSBRecord::UpdateUser {
+                user_id,
+                field,
+                data,
+            } => {
+                codec::put_u8(buf, 0);
+                codec::put_u32(buf, *user_id);
+                codec::put_u8(buf, *field as u8);
+                codec::put_bytes(buf, data.as_bytes());
+            }
+            YCSBRecord::InsertUser { user_id, fields } => {
+                codec::put_u8(buf, 1);
+                codec::put_u32(buf, *user_id);
+                codec::put_u8(buf, fields.len() as u8);
+                for field in fields {
+                    codec::put_bytes(buf, field.as_bytes());
+                }
+            }
+            YCSBRecord::DeleteUser { user_id } => {
+                codec::put_u8(buf, 2);
+                codec::put_u32(buf, *user_id);
+            }
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> Option<YCSBRecord> {
+        let record = match decoder.u8()? {
+            0 => YCSBRecord::UpdateUser {
+                user_id: decoder.u32()?,
+                field: decoder.u8()? as usize,
+                data: decoder.string()?,
+            },
+            1 => {
+                let user_id = decoder.u32()?;
+                let num_fields = decoder.u8()?;
+
+                YCSBRecord::InsertUser {
+                    user_id,
+                    fields: (0..num_fields)
+                        .map(|_| decoder.string())
+                        .collect::<Option<_>>()?,
+                }
+            }
+            2 => YCSBRecord::DeleteUser {
+                user_id: decoder.u32()?,
+            },
+            _ => return None,
+        };
+
+        Some(record)
+    }
+}
+
+impl ArrowYCSBDatabase {
+    /// Loads the snapshot saved by `save` at `snapshot` and replays the log at `wal` over it.
+    pub fn recover(snapshot: &Path, wal: &Path) -> Result<ArrowYCSBDatabase> {
+        let db = ArrowYCSBDatabase::load(snapshot)?;
+        Wal::replay(wal, |record| db.apply(&record))?;
+        Ok(db)
+    }
+
+    /// Makes the change described by `record`. Updating or deleting a user that doesn't exist
+    /// does nothing.
+    fn apply(&self, record: &YCSBRecord) {
+        match record {
+            YCSBRecord::UpdateUser {
+                user_id,
+                field,
+                data,
+            } => {
+                self.table.update(user_id, |block, index| {
+                    block.col_fields[*field].set(index, data)
+                });
+            }
+            YCSBRecord::InsertUser { user_id, fields } => {
+                let row = self.table.insert(*user_id, |row| {
+                    let (block, index) = self.table.block(row);
+                    block.col_user_id.set(index, *user_id);
+
+                    for (col_field, field) in block.col_fields.iter().zip(fields) {
+                        col_field.set(index, field);
+                    }
+
+                    self.by_user_id.write().unwrap().insert(*user_id, row);
+                });
+
+                assert!(row.is_some(), "duplicate user {}", user_id);
+            }
+            YCSBRecord::DeleteUser { user_id } => {
+                self.table.delete(user_id, |_| {
+                    self.by_user_id.write().unwrap().remove(user_id);
+                });
+            }
+        }
+    }
+}
+
+impl Checkpointed for ArrowYCSBDatabase {
+    type Record = YCSBRecord;
+
+    const TABLES: &'static [CheckpointTable] = &[CheckpointTable {
+        name: "users",
+        key: &["user_id"],
+    }];
+
+    fn save_checkpoint(&self, path: &Path) -> Result<()> {
+        fs::create_dir_all(path)?;
+        self.save(&path.join("users.arrow"))
+    }
+
+    fn recover_checkpoint(path: &Path, wal: &Path) -> Result<ArrowYCSBDatabase> {
+        ArrowYCSBDatabase::recover(&path.join("users.arrow"), wal)
+    }
+
+    fn writes(record: &YCSBRecord) -> Vec<Write> {
+        match *record {
+            YCSBRecord::UpdateUser { user_id, field, .. } => vec![Write::column(
+                "users",
+                vec![user_id as u64],
+                &format!("field_{}", field),
+            )],
+            YCSBRecord::InsertUser { user_id, .. } | YCSBRecord::DeleteUser { user_id } => {
+                vec![Write::rows("users", vec![user_id as u64])]
+            }
+        }
+    }
+}
+
+pub struct ArrowYCSBConnection {
+    db: Arc<ArrowYCSBDatabase>,
+    wal: Option<Arc<Wal>>,
+    records: Vec<u8>,
+}
+
+impl ArrowYCSBConnection {
+    pub fn new(db: Arc<ArrowYCSBDatabase>) -> ArrowYCSBConnection {
+        ArrowYCSBConnection {
+            db,
+            wal: None,
+            records: vec![],
+        }
+    }
+
+    /// Logs every change to `wal` if there is one. The log is synced whenever a transaction
+    /// that made changes ends.
+    pub fn with_wal(self, wal: Option<Arc<Wal>>) -> ArrowYCSBConnection {
+        ArrowYCSBConnection { wal, ..self }
+    }
+
+    fn execute(&mut self, record: YCSBRecord) {
+        self.db.apply(&record);
+
+        if self.wal.is_some() {
+            record.encode(&mut self.records);
+        }
+    }
+
+    /// Hands the records of the transaction that just ended to the log, as
+    /// `ArrowTATPConnection` does.
+    fn flush(&mut self) {
+        if let Some(wal) = &self.wal {
+            if !self.records.is_empty() {
+                wal.commit(&self.records).unwrap();
+                self.records.clear();
+            }
+        }
+    }
+}
+
+impl Connection for ArrowYCSBConnection {
+    fn begin(&mut self) {}
+
+    fn commit(&mut self) {
+        self.flush();
+    }
+
+    fn rollback(&mut self) {
+        self.flush();
+    }
+
+    fn savepoint(&mut self) {}
+}
+
+impl Logged for ArrowYCSBConnection {
+    fn take_records(&mut self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.records);
+        self.records.clear();
+    }
+}
+
+impl YCSBConnection for ArrowYCSBConnection {
+    fn select_user(&mut self, field: usize, user_id: u32) -> String {
+        self.db
+            .table
+            .read(&user_id, |block, index| {
+                block.col_fields[field].value(index)
+            })
+            .unwrap_or_default()
+    }
+
+    fn update_user(&mut self, field: usize, data: &str, user_id: u32) {
+        self.execute(YCSBRecord::UpdateUser {
+            user_id,
+            field,
+            data: data.to_string(),
+        });
+    }
+
+    fn insert_user(&mut self, user_id: u32, fields: &[String]) {
+        self.execute(YCSBRecord::InsertUser {
+            user_id,
+            fields: fields.to_vec(),
+        });
+    }
+
+    fn delete_user(&mut self, user_id: u32) {
+        self.execute(YCSBRecord::DeleteUser { user_id });
+    }
+
+    fn scan_users(&mut self, field: usize, start_user_id: u32, end_user_id: u32) -> Vec<String> {
+        if start_user_id >= end_user_id {
+            return vec![];
+        }
+
+        let rows = self
+            .db
+            .by_user_id
+            .read()
+            .unwrap()
+            .range(start_user_id..end_user_id)
+            .map(|(_, &row)| row)
+            .collect::<Vec<_>>();
+
+        rows.into_iter()
+            .map(|row| {
+                let (block, index) = self.db.table.block(row);
+                block.col_fields[field].value(index)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use std::fmt::Debug;
+    use std::hash::Hash;
+
+    /// Describes each row of `table` in key order, looking the row up by the key that `key` reads
+    /// from it so that the table's index is checked too.
+    fn describe<K, B, F, G>(table: &Table<K, B>, key: F, row: G) -> Vec<String>
+    where
+        K: Hash + Ord + Debug,
+        B: Block,
+        F: Fn(&B, usize) -> K,
+        G: Fn(&B, usize) -> String,
+    {
+        let mut keys = vec![];
+
+        for (block, indexes) in table.rows_by_block() {
+            keys.extend(indexes.into_iter().map(|index| key(&block, index)));
+        }
+
+        keys.sort_unstable();
+
+        keys.into_iter()
+            .map(|key| {
+                let row = table.read(&key, |block, index| row(block, index)).unwrap();
+                format!("{:?}: {}", key, row)
+            })
+            .collect()
+    }
+
+    fn tatp_rows(db: &ArrowTATPDatabase) -> Vec<String> {
+        let call_forwarding = &db.call_forwarding;
+
+        let mut rows = describe(
+            &db.subscriber.table,
+            |block, index| block.col_s_id.value(index),
+            |block, index| {
+                let mut sf_types = db.get_special_facility_types(block.col_s_id.value(index));
+                sf_types.sort_unstable();
+                format!("{:?} {:?}", block.get_row_data(index), sf_types)
+            },
+        );
+
+        rows.extend(describe(
+            &db.access_info.table,
+            |block, index| (block.col_s_id.value(index), block.col_ai_type.value(index)),
+            |block, index| {
+                format!(
+                    "{} {} {:?} {:?}",
+                    block.col_data1.value(index),
+                    block.col_data2.value(index),
+                    block.col_data3.value(index),
+                    block.col_data4.value(index)
+                )
+            },
+        ));
+
+        rows.extend(describe(
+            &db.special_facility.table,
+            |block, index| (block.col_s_id.value(index), block.col_sf_type.value(index)),
+            |block, index| {
+                let key = (block.col_s_id.value(index), block.col_sf_type.value(index));
+
+                format!(
+                    "{} {} {} {:?} {}",
+                    block.col_is_active.value(index),
+                    block.col_error_cntrl.value(index),
+                    block.col_data_a.value(index),
+                    block.col_data_b.value(index),
+                    call_forwarding.by_special_facility.get(&key).len()
+                )
+            },
+        ));
+
+        rows.extend(describe(
+            &call_forwarding.table,
+            |block, index| {
+                (
+                    block.col_s_id.value(index),
+                    block.col_sf_type.value(index),
+                    block.col_start_time.value(index),
+                )
+            },
+            |block, index| {
+                format!(
+                    "{} {}",
+                    block.col_end_time.value(index),
+                    block.col_numberx.value(index)
+                )
+            },
+        ));
+
+        rows
+    }
+
+    fn ycsb_rows(db: &ArrowYCSBDatabase) -> Vec<String> {
+        let rows = describe(
+            &db.table,
+            |block, index| block.col_user_id.value(index),
+            |block, index| {
+                let fields = block.col_fields.iter().map(|field| field.value(index));
+                format!("{:?}", fields.collect::<Vec<_>>())
+            },
+        );
+
+        // The scan index covers exactly the users in the table.
+        let by_user_id = db.by_user_id.read().unwrap();
+        assert_eq!(by_user_id.len(), rows.len());
+
+        for (&user_id, &row) in by_user_id.iter() {
+            let (block, index) = db.table.block(row);
+            assert_eq!(block.col_user_id.value(index), user_id);
+        }
+
+        rows
+    }
+
+    /// Deletes some of the subscribers of a TATP database, freeing rows in every table, inserts a
+    /// subscriber with a row in every table, which reuses them, and updates another subscriber.
+    /// The changes are logged to `wal` if there is one.
+    fn change_tatp(db: &Arc<ArrowTATPDatabase>, wal: Option<Arc<Wal>>) {
+        let mut connection = ArrowTATPConnection::new(Arc::clone(db)).with_wal(wal);
+
+        for s_id in (1..=100).step_by(9) {
+            assert!(connection.delete_subscriber(s_id));
+        }
+
+        let data = ([true; 10], [15; 10], [255; 10], 7, 8);
+        assert!(connection.insert_subscriber(1000, data));
+        assert!(connection.insert_access_info(1000, 2, 3, 4, "abc", "defgh"));
+        assert!(connection.insert_special_facility(1000, 3, true, 5, 6, b"ijklm"));
+        connection.insert_call_forwarding(1000, 3, 8, 12, "NOPQRSTUVWXYZAB");
+        connection.update_subscriber_location(9, 1000);
+        connection.update_subscriber_location(9, 2);
+        connection.commit();
+    }
+
+    #[test]
+    fn tatp_snapshots_round_trip() {
+        let path = scratch_path("tatp-snapshot");
+        let mut rng = StdRng::seed_from_u64(0);
+        let db = Arc::new(ArrowTATPDatabase::new(100, &mut rng));
+        change_tatp(&db, None);
+
+        db.save(&path).unwrap();
+        let loaded = ArrowTATPDatabase::load(&path).unwrap();
+
+        assert_eq!(loaded.num_subscribers(), 100 - 12 + 1);
+        assert_eq!(tatp_rows(&loaded), tatp_rows(&db));
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn ycsb_snapshots_round_trip() {
+        let path = scratch_path("ycsb-snapshot");
+        let mut rng = StdRng::seed_from_u64(0);
+        let db = Arc::new(ArrowYCSBDatabase::new(50, 10, &mut rng));
+        let mut connection = ArrowYCSBConnection::new(Arc::clone(&db));
+
+        for user_id in (0..50).step_by(7) {
+            connection.delete_user(user_id);
+        }
+
+        // Shorter values are written in place, and longer ones are appended to the free area.
+        connection.update_user(0, "short", 1);
+        connection.update_user(1, &"long".repeat(10), 2);
+        connection.insert_user(100, &vec!["inserted".to_string(); ycsb::NUM_FIELDS]);
+        connection.commit();
+
+        db.save(&path).unwrap();
+        let loaded = ArrowYCSBDatabase::load(&path).unwrap();
+
+        assert_eq!(loaded.num_users(), 50 - 8 + 1);
+        assert_eq!(ycsb_rows(&loaded), ycsb_rows(&db));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn tatp_checkpoints_and_their_log_restore_the_run() {
+        let dir = scratch_path("tatp-checkpoints");
+        let (before, after, wal_path) = (dir.join("before"), dir.join("after"), dir.join("wal"));
+        let mut rng = StdRng::seed_from_u64(0);
+        let db = Arc::new(ArrowTATPDatabase::new(100, &mut rng));
+
+        db.save_checkpoint(&before).unwrap();
+        change_tatp(&db, Some(Arc::new(Wal::create(&wal_path).unwrap())));
+        db.save_checkpoint(&after).unwrap();
+
+        let recovered = ArrowTATPDatabase::recover_checkpoint(&before, &wal_path).unwrap();
+        assert_eq!(tatp_rows(&recovered), tatp_rows(&db));
+
+        let diff = checkpoint::diff(ArrowTATPDatabase::TABLES, &before, &after).unwrap();
+        let subscriber = &diff[0].1;
+        assert_eq!(subscriber.deleted.len(), 12);
+        assert_eq!(subscriber.inserted, vec![vec![1000]]);
+        assert_eq!(
+            subscriber.updated,
+            vec![(vec![2], vec!["vlr_location".to_string()])]
+        );
+
+        let consistency = checkpoint::verify::<ArrowTATPDatabase>(
+            &before,
+            &after,
+            &dir.join("replayed"),
+            &wal_path,
+        )
+        .unwrap();
+        assert_eq!(consistency.violations(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ycsb_checkpoints_catch_unlogged_changes() {
+        let dir = scratch_path("ycsb-checkpoints");
+        let (before, after, wal_path) = (dir.join("before"), dir.join("after"), dir.join("wal"));
+        let replayed = dir.join("replayed");
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let db = Arc::new(ArrowYCSBDatabase::new(50, 10, &mut rng));
+        let wal = Arc::new(Wal::create(&wal_path).unwrap());
+        let mut connection = ArrowYCSBConnection::new(Arc::clone(&db)).with_wal(Some(wal));
+
+        db.save_checkpoint(&before).unwrap();
+        connection.update_user(0, &"long".repeat(10), 1);
+        connection.delete_user(7);
+        connection.insert_user(100, &vec!["inserted".to_string(); ycsb::NUM_FIELDS]);
+        connection.commit();
+        db.save_checkpoint(&after).unwrap();
+
+        let recovered = ArrowYCSBDatabase::recover_checkpoint(&before, &wal_path).unwrap();
+        assert_eq!(ycsb_rows(&recovered), ycsb_rows(&db));
+
+        let consistency =
+            checkpoint::verify::<ArrowYCSBDatabase>(&before, &after, &replayed, &wal_path).unwrap();
+        assert_eq!(consistency.violations(), 0);
+
+        // A change that bypasses the log shows up both as a row the log doesn't restore and as a
+        // change that no record accounts for.
+        db.apply(&YCSBRecord::UpdateUser {
+            user_id: 2,
+            field: 3,
+            data: "unlogged".to_string(),
+        });
+        db.save_checkpoint(&after).unwrap();
+
+        let consistency =
+            checkpoint::verify::<ArrowYCSBDatabase>(&before, &after, &replayed, &wal_path).unwrap();
+        assert_eq!(consistency.violations(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,189 @@
+//! Snapshots of the Arrow databases, so that a database can be generated once and loaded from
+//! disk on every run after that. Each table is saved as an Arrow IPC file holding one record
+//! batch with the table's rows in use.
+
+use super::table::{BooleanArrayMut, FixedSizeBinaryArrayMut, PrimitiveArrayMut, StringArrayMut};
+use arrow::array::{
+    Array, ArrayBuilder, ArrayRef, BooleanArray, BooleanBuilder, FixedSizeBinaryArray,
+    FixedSizeBinaryBuilder, PrimitiveArray, PrimitiveArrayOps, PrimitiveBuilder, StringArray,
+    StringBuilder,
+};
+use arrow::datatypes::{ArrowNumericType, Field, Schema};
+use arrow::error::{ArrowError, Result};
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A column that can be saved to a snapshot and loaded from one.
+pub trait Column: Sized {
+    type Builder: ArrayBuilder;
+
+    /// Creates a builder for `capacity` values of this column.
+    fn builder(&self, capacity: usize) -> Self::Builder;
+
+    /// Appends the value at `row` to `builder`.
+    fn append(&self, row: usize, builder: &mut Self::Builder);
+
+    /// Creates a column holding the values of `array`.
+    fn load(array: &ArrayRef) -> Result<Self>;
+}
+
+fn downcast<A: 'static>(array: &ArrayRef) -> Result<&A> {
+    array
+        .as_any()
+        .downcast_ref::<A>()
+        .ok_or_else(|| ArrowError::InvalidArgumentError("unexpected column type".to_string()))
+}
+
+impl<T: ArrowNumericType> Column for PrimitiveArrayMut<T> {
+    type Builder = PrimitiveBuilder<T>;
+
+    fn builder(&self, capacity: usize) -> PrimitiveBuilder<T> {
+        PrimitiveBuilder::new(capacity)
+    }
+
+    fn append(&self, row: usize, builder: &mut PrimitiveBuilder<T>) {
+        builder.append_value(self.value(row)).unwrap();
+    }
+
+    fn load(array: &ArrayRef) -> Result<PrimitiveArrayMut<T>> {
+        let array = downcast::<PrimitiveArray<T>>(array)?;
+        let mut builder = PrimitiveBuilder::new(array.len());
+        builder.append_slice(array.value_slice(0, array.len()))?;
+        Ok(PrimitiveArrayMut::new(builder, array.len()))
+    }
+}
+
+impl Column for BooleanArrayMut {
+    type Builder = BooleanBuilder;
+
+    fn builder(&self, capacity: usize) -> BooleanBuilder {
+        BooleanBuilder::new(capacity)
+    }
+
+    fn append(&self, row: usize, builder: &mut BooleanBuilder) {
+        builder.append_value(self.value(row)).unwrap();
+    }
+
+    fn load(array: &ArrayRef) -> Result<BooleanArrayMut> {
+        let array = downcast::<BooleanArray>(array)?;
+        let mut builder = BooleanBuilder::new(array.len());
+
+        for row in 0..array.len() {
+            builder.append_value(array.value(row))?;
+        }
+
+        Ok(BooleanArrayMut::new(builder, array.len()))
+    }
+}
+
+impl Column for FixedSizeBinaryArrayMut {
+    type Builder = FixedSizeBinaryBuilder;
+
+    fn builder(&self, capacity: usize) -> FixedSizeBinaryBuilder {
+        FixedSizeBinaryBuilder::new(capacity, self.byte_width() as i32)
+    }
+
+    fn append(&self, row: usize, builder: &mut FixedSizeBinaryBuilder) {
+        builder.append_value(self.value(row)).unwrap();
+    }
+
+    fn load(array: &ArrayRef) -> Result<FixedSizeBinaryArrayMut> {
+        let array = downcast::<FixedSizeBinaryArray>(array)?;
+        let byte_width = array.value_length();
+        let mut builder = FixedSizeBinaryBuilder::new(array.len(), byte_width);
+
+        for row in 0..array.len() {
+            builder.append_value(array.value(row))?;
+        }
+
+        Ok(FixedSizeBinaryArrayMut::new(
+            builder,
+            byte_width as usize,
+            array.len(),
+        ))
+    }
+}
+
+impl Column for StringArrayMut {
+    type Builder = StringBuilder;
+
+    fn builder(&self, capacity: usize) -> StringBuilder {
+        StringBuilder::new(capacity)
+    }
+
+    fn append(&self, row: usize, builder: &mut StringBuilder) {
+        builder.append_value(&self.value(row)).unwrap();
+    }
+
+    fn load(array: &ArrayRef) -> Result<StringArrayMut> {
+        let array = downcast::<StringArray>(array)?;
+        let mut builder = StringBuilder::with_capacity(array.len(), array.value_data().len());
+
+        for row in 0..array.len() {
+            builder.append_value(array.value(row))?;
+        }
+
+        Ok(StringArrayMut::new(builder, array.len()))
+    }
+}
+
+/// Copies the column selected by `column` out of the rows returned by `Table::rows_by_block`.
+pub fn gather<B, C, F>(rows: &[(Arc<B>, Vec<usize>)], column: F) -> ArrayRef
+where
+    C: Column,
+    F: Fn(&B) -> &C,
+{
+    let len = rows.iter().map(|(_, indexes)| indexes.len()).sum();
+    let mut builder = column(&rows[0].0).builder(len);
+
+    for (block, indexes) in rows {
+        let column = column(block);
+
+        for &index in indexes {
+            column.append(index, &mut builder);
+        }
+    }
+
+    builder.finish()
+}
+
+/// Writes `columns` to the file at `path`, replacing it if it exists.
+pub fn save(path: &Path, columns: Vec<(String, ArrayRef)>) -> Result<()> {
+    let schema = Arc::new(Schema::new(
+        columns
+            .iter()
+            .map(|(name, array)| Field::new(name, array.data_type().clone(), false))
+            .collect(),
+    ));
+
+    let batch = RecordBatch::try_new(
+        Arc::clone(&schema),
+        columns.into_iter().map(|(_, array)| array).collect(),
+    )?;
+
+    let mut writer = FileWriter::try_new(File::create(path)?, &schema)?;
+    writer.write(&batch)?;
+    writer.finish()
+}
+
+/// Reads the columns written by `save` to the file at `path`.
+pub fn load(path: &Path) -> Result<RecordBatch> {
+    let mut reader = FileReader::try_new(File::open(path)?)?;
+
+    reader.next().unwrap_or_else(|| {
+        Err(ArrowError::IoError(format!(
+            "{} holds no record batch",
+            path.display()
+        )))
+    })
+}
+
+/// Returns the column of `batch` named `name`.
+pub fn column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a ArrayRef> {
+    let index = batch.schema().index_of(name)?;
+    Ok(batch.column(index))
+}
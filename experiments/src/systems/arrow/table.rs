@@ -0,0 +1,965 @@
+//! Mutable tables over Arrow arrays.
+//!
+//! Arrow arrays are immutable once built, so the columns here write to their buffers in place.
+//! Nothing stops two writes, or a write and a read, of the same row from racing; the benchmarks
+//! rely on dibs to rule that out. Each column's `set` checks that the row is in bounds, while
+//! `set_unchecked` leaves that to the caller and only checks it in debug builds.
+
+use arrow::array::{
+    Array, ArrayBuilder, BooleanArray, BooleanBuilder, FixedSizeBinaryArray,
+    FixedSizeBinaryBuilder, PrimitiveArray, PrimitiveArrayOps, PrimitiveBuilder, StringBuilder,
+    UInt32Builder,
+};
+use arrow::buffer::{Buffer, MutableBuffer};
+use arrow::datatypes::{ArrowNumericType, UInt32Type, UInt8Type};
+use arrow::util::bit_util;
+use fnv::{FnvHashMap, FnvHasher};
+use std::collections::hash_map::Entry;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+const NUM_PARTITIONS: usize = 100;
+
+/// A numeric column whose values can be updated in place.
+pub struct PrimitiveArrayMut<T: ArrowNumericType> {
+    array: PrimitiveArray<T>,
+}
+
+pub type UInt8ArrayMut = PrimitiveArrayMut<UInt8Type>;
+pub type UInt32ArrayMut = PrimitiveArrayMut<UInt32Type>;
+
+impl<T: ArrowNumericType> PrimitiveArrayMut<T> {
+    /// Finishes `builder`, padded with default values to `capacity` rows.
+    pub fn new(mut builder: PrimitiveBuilder<T>, capacity: usize) -> PrimitiveArrayMut<T> {
+        while builder.len() < capacity {
+            builder.append_value(T::Native::default()).unwrap();
+        }
+
+        PrimitiveArrayMut {
+            array: builder.finish(),
+        }
+    }
+
+    pub fn value(&self, row: usize) -> T::Native {
+        self.array.value(row)
+    }
+
+    /// Writes `value` to `row`, panicking if `row` is out of bounds.
+    pub fn set(&self, row: usize, value: T::Native) {
+        assert!(row < self.array.len(), "row {} out of bounds", row);
+        unsafe { self.set_unchecked(row, value) }
+    }
+
+    /// Writes `value` to `row` without checking that `row` is in bounds.
+    ///
+    /// # Safety
+    ///
+    /// `row` must be less than the number of rows in the column.
+    pub unsafe fn set_unchecked(&self, row: usize, value: T::Native) {
+        debug_assert!(row < self.array.len());
+        *(self.array.raw_values().add(row) as *mut T::Native) = value;
+    }
+}
+
+/// Sets or clears bit `i` of `buffer`.
+///
+/// # Safety
+///
+/// `i` must be less than the number of bits in `buffer`.
+unsafe fn write_bit(buffer: &Buffer, i: usize, value: bool) {
+    debug_assert!(i < buffer.len() * 8);
+    let data = buffer.raw_data() as *mut u8;
+
+    if value {
+        bit_util::set_bit_raw(data, i);
+    } else {
+        bit_util::unset_bit_raw(data, i);
+    }
+}
+
+/// A boolean column whose values can be updated in place.
+pub struct BooleanArrayMut {
+    array: BooleanArray,
+}
+
+impl BooleanArrayMut {
+    /// Finishes `builder`, padded with `false` to `capacity` rows.
+    pub fn new(mut builder: BooleanBuilder, capacity: usize) -> BooleanArrayMut {
+        while builder.len() < capacity {
+            builder.append_value(false).unwrap();
+        }
+
+        BooleanArrayMut {
+            array: builder.finish(),
+        }
+    }
+
+    pub fn value(&self, row: usize) -> bool {
+        self.array.value(row)
+    }
+
+    /// Writes `value` to `row`, panicking if `row` is out of bounds.
+    pub fn set(&self, row: usize, value: bool) {
+        assert!(row < self.array.len(), "row {} out of bounds", row);
+        unsafe { self.set_unchecked(row, value) }
+    }
+
+    /// Writes `value` to `row` without checking that `row` is in bounds.
+    ///
+    /// # Safety
+    ///
+    /// `row` must be less than the number of rows in the column.
+    pub unsafe fn set_unchecked(&self, row: usize, value: bool) {
+        debug_assert!(row < self.array.len());
+        write_bit(&self.array.values(), self.array.offset() + row, value);
+    }
+}
+
+/// A fixed-width binary column whose values can be updated in place.
+pub struct FixedSizeBinaryArrayMut {
+    array: FixedSizeBinaryArray,
+}
+
+impl FixedSizeBinaryArrayMut {
+    /// Finishes `builder`, whose values are `byte_width` bytes long, padded with zeroed values to
+    /// `capacity` rows.
+    pub fn new(
+        mut builder: FixedSizeBinaryBuilder,
+        byte_width: usize,
+        capacity: usize,
+    ) -> FixedSizeBinaryArrayMut {
+        let zeroes = vec![0; byte_width];
+
+        while builder.len() < capacity {
+            builder.append_value(&zeroes).unwrap();
+        }
+
+        let array = builder.finish();
+        assert_eq!(array.value_length() as usize, byte_width);
+
+        FixedSizeBinaryArrayMut { array }
+    }
+
+    /// The length of every value in the column.
+    pub fn byte_width(&self) -> usize {
+        self.array.value_length() as usize
+    }
+
+    pub fn value(&self, row: usize) -> &[u8] {
+        self.array.value(row)
+    }
+
+    /// Writes `value` to `row`, panicking if `row` is out of bounds or `value` is not as long as
+    /// the column's values.
+    pub fn set(&self, row: usize, value: &[u8]) {
+        assert!(row < self.array.len(), "row {} out of bounds", row);
+        assert_eq!(value.len(), self.array.value_length() as usize);
+        unsafe { self.set_unchecked(row, value) }
+    }
+
+    /// Writes `value` to `row` without checking that `row` is in bounds or that `value` has the
+    /// right length.
+    ///
+    /// # Safety
+    ///
+    /// `row` must be less than the number of rows in the column, and `value` must be as long as
+    /// the column's values.
+    pub unsafe fn set_unchecked(&self, row: usize, value: &[u8]) {
+        debug_assert!(row < self.array.len());
+        debug_assert_eq!(value.len(), self.array.value_length() as usize);
+        let offset = self.array.value_offset(row) as usize;
+        let dst = self.array.value_data().raw_data().add(offset) as *mut u8;
+        dst.copy_from_nonoverlapping(value.as_ptr(), value.len());
+    }
+}
+
+/// A string column whose values can be replaced by values of any length.
+///
+/// The values sit back to back in a data buffer, and each row's value is found through its start
+/// and end offsets. A value no longer than the one it replaces is written over it, and a longer one
+/// is appended to the free area at the end of the buffer; either way, the bytes the new value
+/// doesn't reuse become a tombstone. When the free area runs out, the values are copied into a new
+/// buffer with as much room again, leaving the tombstones behind. Only that copy holds the column's
+/// lock exclusively; reads and writes share it.
+pub struct StringArrayMut {
+    len: usize,
+    starts: UInt32ArrayMut,
+    ends: UInt32ArrayMut,
+    data: RwLock<StringData>,
+}
+
+struct StringData {
+    buffer: Buffer,
+    /// The end of the last appended value, where the free area starts.
+    tail: AtomicUsize,
+    /// The number of bytes in tombstones.
+    dead: AtomicUsize,
+}
+
+impl StringData {
+    /// Creates a buffer holding `values`, followed by `free` bytes of free area.
+    fn new(values: &[u8], free: usize) -> StringData {
+        let capacity = values.len() + free;
+        assert!(capacity <= u32::MAX as usize, "string column too large");
+
+        let mut buffer = MutableBuffer::new(capacity).with_bitset(capacity, false);
+        buffer.data_mut()[..values.len()].copy_from_slice(values);
+
+        StringData {
+            buffer: buffer.freeze(),
+            tail: AtomicUsize::new(values.len()),
+            dead: AtomicUsize::new(0),
+        }
+    }
+
+    fn get(&self, start: usize, end: usize) -> &[u8] {
+        &self.buffer.data()[start..end]
+    }
+
+    /// Copies `value` to `start` in the buffer.
+    ///
+    /// # Safety
+    ///
+    /// `value` must fit in the buffer at `start`, and nothing may read or write those bytes at the
+    /// same time.
+    unsafe fn write(&self, start: usize, value: &[u8]) {
+        debug_assert!(start + value.len() <= self.buffer.len());
+        let dst = self.buffer.raw_data().add(start) as *mut u8;
+        dst.copy_from_nonoverlapping(value.as_ptr(), value.len());
+    }
+}
+
+impl StringArrayMut {
+    /// Finishes `builder`, padded with empty values to `capacity` rows, leaving a free area as
+    /// large as the values.
+    pub fn new(mut builder: StringBuilder, capacity: usize) -> StringArrayMut {
+        while builder.len() < capacity {
+            builder.append_value("").unwrap();
+        }
+
+        let array = builder.finish();
+        let mut starts = UInt32Builder::new(capacity);
+        let mut ends = UInt32Builder::new(capacity);
+
+        for row in 0..array.len() {
+            let start = array.value_offset(row) as u32;
+            starts.append_value(start).unwrap();
+            ends.append_value(start + array.value_length(row) as u32)
+                .unwrap();
+        }
+
+        let values = array.value_data();
+        let values = &values.data()[..array.value_offset(array.len()) as usize];
+
+        StringArrayMut {
+            len: array.len(),
+            starts: UInt32ArrayMut::new(starts, capacity),
+            ends: UInt32ArrayMut::new(ends, capacity),
+            data: RwLock::new(StringData::new(values, values.len())),
+        }
+    }
+
+    /// Returns a copy of the value at `row`, since a later write may move it.
+    pub fn value(&self, row: usize) -> String {
+        let data = self.data.read().unwrap();
+        let start = self.starts.value(row) as usize;
+        let end = self.ends.value(row) as usize;
+        String::from_utf8(data.get(start, end).to_vec()).unwrap()
+    }
+
+    /// The number of bytes in tombstones, which the next copy of the values will reclaim.
+    pub fn dead_bytes(&self) -> usize {
+        self.data.read().unwrap().dead.load(Ordering::Relaxed)
+    }
+
+    /// Writes `value` to `row`, panicking if `row` is out of bounds.
+    pub fn set(&self, row: usize, value: &str) {
+        assert!(row < self.len, "row {} out of bounds", row);
+        let value = value.as_bytes();
+
+        loop {
+            let data = self.data.read().unwrap();
+            let start = self.starts.value(row) as usize;
+            let old_len = self.ends.value(row) as usize - start;
+
+            if value.len() <= old_len {
+                unsafe { data.write(start, value) }
+                self.ends.set(row, (start + value.len()) as u32);
+                data.dead
+                    .fetch_add(old_len - value.len(), Ordering::Relaxed);
+                return;
+            }
+
+            let new_start = data.tail.fetch_add(value.len(), Ordering::Relaxed);
+
+            if new_start + value.len() <= data.buffer.len() {
+                unsafe { data.write(new_start, value) }
+                self.starts.set(row, new_start as u32);
+                self.ends.set(row, (new_start + value.len()) as u32);
+                data.dead.fetch_add(old_len, Ordering::Relaxed);
+                return;
+            }
+
+            drop(data);
+            self.compact(value.len());
+        }
+    }
+
+    /// Copies the values into a new buffer whose free area is as large as the values and at least
+    /// `needed` bytes, unless another write already made room for `needed` bytes.
+    fn compact(&self, needed: usize) {
+        let mut data = self.data.write().unwrap();
+
+        if *data.tail.get_mut() + needed <= data.buffer.len() {
+            return;
+        }
+
+        let mut values = Vec::with_capacity(data.buffer.len() - *data.dead.get_mut());
+
+        for row in 0..self.len {
+            let start = self.starts.value(row) as usize;
+            let end = self.ends.value(row) as usize;
+            self.starts.set(row, values.len() as u32);
+            values.extend_from_slice(data.get(start, end));
+            self.ends.set(row, values.len() as u32);
+        }
+
+        *data = StringData::new(&values, values.len().max(needed));
+    }
+}
+
+/// Hands out the rows of a table, reusing the rows of deleted ones and adding more rows when
+/// every row is in use.
+pub struct RowAllocator {
+    state: Mutex<AllocatorState>,
+}
+
+struct AllocatorState {
+    free: Vec<usize>,
+    capacity: usize,
+}
+
+impl RowAllocator {
+    /// Creates an allocator for `capacity` rows, of which the first `num_rows` are in use.
+    pub fn new(num_rows: usize, capacity: usize) -> RowAllocator {
+        RowAllocator {
+            state: Mutex::new(AllocatorState {
+                free: (num_rows..capacity).rev().collect(),
+                capacity,
+            }),
+        }
+    }
+
+    /// The number of rows, whether in use or not.
+    pub fn capacity(&self) -> usize {
+        self.state.lock().unwrap().capacity
+    }
+
+    /// Returns a free row. If every row is in use, first calls `grow` with the current number of
+    /// rows to make room for more, which returns how many rows it added. Only one call to `grow`
+    /// runs at a time.
+    pub fn allocate<G>(&self, grow: G) -> usize
+    where
+        G: FnOnce(usize) -> usize,
+    {
+        let mut state = self.state.lock().unwrap();
+
+        if state.free.is_empty() {
+            let capacity = state.capacity;
+            let num_rows = grow(capacity);
+            assert!(num_rows > 0, "table did not grow");
+
+            state.free.extend((capacity..capacity + num_rows).rev());
+            state.capacity += num_rows;
+        }
+
+        state.free.pop().unwrap()
+    }
+
+    pub fn free(&self, row: usize) {
+        self.state.lock().unwrap().free.push(row);
+    }
+}
+
+/// The columns of a table, one block of rows at a time.
+pub trait Block {
+    /// Creates a block of `len` empty rows with the same columns as this one.
+    fn empty(&self, len: usize) -> Self;
+}
+
+/// The blocks of a table, kept separately so that adding rows never moves the ones already
+/// there. The first block holds the loaded rows and every later block holds the same number of
+/// rows.
+struct Blocks<B> {
+    blocks: RwLock<Vec<Arc<B>>>,
+    first_len: usize,
+    block_len: usize,
+}
+
+impl<B: Block> Blocks<B> {
+    fn new(first: B, first_len: usize, block_len: usize) -> Blocks<B> {
+        assert!(block_len > 0);
+
+        Blocks {
+            blocks: RwLock::new(vec![Arc::new(first)]),
+            first_len,
+            block_len,
+        }
+    }
+
+    fn get(&self, row: usize) -> (Arc<B>, usize) {
+        let (block, index) = if row < self.first_len {
+            (0, row)
+        } else {
+            let row = row - self.first_len;
+            (1 + row / self.block_len, row % self.block_len)
+        };
+
+        (Arc::clone(&self.blocks.read().unwrap()[block]), index)
+    }
+
+    fn all(&self) -> Vec<(usize, Arc<B>, usize)> {
+        let blocks = self.blocks.read().unwrap();
+        let mut start = 0;
+
+        blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| {
+                let len = if i == 0 {
+                    self.first_len
+                } else {
+                    self.block_len
+                };
+
+                start += len;
+                (start - len, Arc::clone(block), len)
+            })
+            .collect()
+    }
+
+    /// Appends an empty block, returning the number of rows it adds.
+    fn grow(&self) -> usize {
+        let block = self.blocks.read().unwrap()[0].empty(self.block_len);
+        self.blocks.write().unwrap().push(Arc::new(block));
+        self.block_len
+    }
+}
+
+/// A hash map split into partitions that are locked separately, so that concurrent operations
+/// on different keys rarely contend.
+struct Partitions<K, V> {
+    partitions: Vec<RwLock<FnvHashMap<K, V>>>,
+}
+
+impl<K: Hash + Eq, V> Partitions<K, V> {
+    fn new() -> Partitions<K, V> {
+        Partitions {
+            partitions: (0..NUM_PARTITIONS)
+                .map(|_| RwLock::new(FnvHashMap::default()))
+                .collect(),
+        }
+    }
+
+    fn get(&self, key: &K) -> &RwLock<FnvHashMap<K, V>> {
+        let mut hasher = FnvHasher::default();
+        key.hash(&mut hasher);
+        &self.partitions[hasher.finish() as usize % self.partitions.len()]
+    }
+}
+
+/// An index from unique keys to rows.
+pub struct HashIndex<K> {
+    partitions: Partitions<K, usize>,
+}
+
+impl<K: Hash + Eq> HashIndex<K> {
+    pub fn new() -> HashIndex<K> {
+        HashIndex {
+            partitions: Partitions::new(),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<usize> {
+        self.partitions.get(key).read().unwrap().get(key).cloned()
+    }
+
+    /// Maps `key` to `row`, returning `false` without changing the index if `key` is present.
+    pub fn insert(&self, key: K, row: usize) -> bool {
+        match self.partitions.get(&key).write().unwrap().entry(key) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(row);
+                true
+            }
+        }
+    }
+
+    pub fn remove(&self, key: &K) -> Option<usize> {
+        self.partitions.get(key).write().unwrap().remove(key)
+    }
+}
+
+impl<K: Hash + Eq> Default for HashIndex<K> {
+    fn default() -> HashIndex<K> {
+        HashIndex::new()
+    }
+}
+
+/// An index from keys to the rows that share them.
+pub struct MultiHashIndex<K> {
+    partitions: Partitions<K, Vec<usize>>,
+}
+
+impl<K: Hash + Eq> MultiHashIndex<K> {
+    pub fn new() -> MultiHashIndex<K> {
+        MultiHashIndex {
+            partitions: Partitions::new(),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Vec<usize> {
+        self.partitions
+            .get(key)
+            .read()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn insert(&self, key: K, row: usize) {
+        self.partitions
+            .get(&key)
+            .write()
+            .unwrap()
+            .entry(key)
+            .or_default()
+            .push(row);
+    }
+
+    pub fn remove(&self, key: &K, row: usize) {
+        let mut partition = self.partitions.get(key).write().unwrap();
+
+        if let Some(rows) = partition.get_mut(key) {
+            rows.retain(|&other| other != row);
+
+            if rows.is_empty() {
+                partition.remove(key);
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq> Default for MultiHashIndex<K> {
+    fn default() -> MultiHashIndex<K> {
+        MultiHashIndex::new()
+    }
+}
+
+/// A table, made up of its primary key index and blocks of rows. A row is identified by its
+/// position across all blocks, which is what the table's indexes map keys to. The table's own
+/// type writes the columns through the callbacks of `insert` and `delete`, so that a row is
+/// complete before it becomes visible and is unlinked from any secondary index before it is
+/// reused. When every row is in use, inserting adds a block of `block_len` empty rows.
+pub struct Table<K, B> {
+    primary: HashIndex<K>,
+    allocator: RowAllocator,
+    blocks: Blocks<B>,
+}
+
+impl<K: Hash + Eq, B: Block> Table<K, B> {
+    /// Creates a table whose first rows are already loaded into `first` with `keys` in order, and
+    /// which grows by `block_len` rows at a time.
+    pub fn new<I>(keys: I, first: B, block_len: usize) -> Table<K, B>
+    where
+        I: IntoIterator<Item = K>,
+    {
+        let primary = HashIndex::new();
+        let mut num_rows = 0;
+
+        for (row, key) in keys.into_iter().enumerate() {
+            assert!(primary.insert(key, row), "duplicate primary key");
+            num_rows += 1;
+        }
+
+        Table {
+            primary,
+            allocator: RowAllocator::new(num_rows, num_rows),
+            blocks: Blocks::new(first, num_rows, block_len),
+        }
+    }
+
+    /// The number of rows, whether in use or not.
+    pub fn capacity(&self) -> usize {
+        self.allocator.capacity()
+    }
+
+    /// Returns the row with primary key `key`.
+    pub fn get(&self, key: &K) -> Option<usize> {
+        self.primary.get(key)
+    }
+
+    /// Returns the block that holds `row`, along with the row's index within that block.
+    pub fn block(&self, row: usize) -> (Arc<B>, usize) {
+        self.blocks.get(row)
+    }
+
+    /// Returns every block, along with its first row and its number of rows. Rows that are not
+    /// in use hold stale or empty values.
+    pub fn blocks(&self) -> Vec<(usize, Arc<B>, usize)> {
+        self.blocks.all()
+    }
+
+    /// Returns every block along with the indexes of its rows that are in use, in order.
+    pub fn rows_by_block(&self) -> Vec<(Arc<B>, Vec<usize>)> {
+        let mut rows = self
+            .primary
+            .partitions
+            .partitions
+            .iter()
+            .flat_map(|partition| {
+                partition
+                    .read()
+                    .unwrap()
+                    .values()
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        rows.sort_unstable();
+
+        let mut rows = rows.into_iter().peekable();
+
+        self.blocks()
+            .into_iter()
+            .map(|(start, block, len)| {
+                let mut indexes = vec![];
+
+                while let Some(&row) = rows.peek() {
+                    if row >= start + len {
+                        break;
+                    }
+
+                    indexes.push(row - start);
+                    rows.next();
+                }
+
+                (block, indexes)
+            })
+            .collect()
+    }
+
+    /// Calls `read` with the block and index of the row with primary key `key`, returning its
+    /// result, or `None` if there is no such row.
+    pub fn read<F, R>(&self, key: &K, read: F) -> Option<R>
+    where
+        F: FnOnce(&B, usize) -> R,
+    {
+        let (block, index) = self.block(self.get(key)?);
+        Some(read(&block, index))
+    }
+
+    /// Calls `update` with the block and index of the row with primary key `key`, returning
+    /// whether there is one.
+    pub fn update<F>(&self, key: &K, update: F) -> bool
+    where
+        F: FnOnce(&B, usize),
+    {
+        self.read(key, update).is_some()
+    }
+
+    /// Inserts a row with primary key `key`, calling `write` with the row to fill in its columns.
+    /// Returns the row, or `None` if a row with that key exists.
+    pub fn insert<F>(&self, key: K, write: F) -> Option<usize>
+    where
+        F: FnOnce(usize),
+    {
+        let mut partition = self.primary.partitions.get(&key).write().unwrap();
+
+        match partition.entry(key) {
+            Entry::Occupied(_) => None,
+            Entry::Vacant(entry) => {
+                let row = self.allocator.allocate(|_| self.blocks.grow());
+                write(row);
+                entry.insert(row);
+                Some(row)
+            }
+        }
+    }
+
+    /// Deletes the row with primary key `key`, calling `unlink` with it before it is freed.
+    /// Returns the row, or `None` if there is none with that key.
+    pub fn delete<F>(&self, key: &K, unlink: F) -> Option<usize>
+    where
+        F: FnOnce(usize),
+    {
+        let row = self.primary.remove(key)?;
+        unlink(row);
+        self.allocator.free(row);
+        Some(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A block without columns, which only knows how many rows it holds.
+    struct TestBlock {
+        len: usize,
+    }
+
+    impl Block for TestBlock {
+        fn empty(&self, len: usize) -> TestBlock {
+            TestBlock { len }
+        }
+    }
+
+    /// Returns a table whose first block holds keys `0..num_rows` and which grows by `block_len`
+    /// rows at a time.
+    fn table(num_rows: usize, block_len: usize) -> Table<usize, TestBlock> {
+        Table::new(0..num_rows, TestBlock { len: num_rows }, block_len)
+    }
+
+    fn layout(table: &Table<usize, TestBlock>) -> Vec<(usize, Vec<usize>)> {
+        table
+            .rows_by_block()
+            .into_iter()
+            .map(|(block, indexes)| (block.len, indexes))
+            .collect()
+    }
+
+    fn strings(values: &[&str]) -> StringArrayMut {
+        let mut builder = StringBuilder::new(values.len());
+
+        for value in values {
+            builder.append_value(value).unwrap();
+        }
+
+        StringArrayMut::new(builder, values.len())
+    }
+
+    /// Returns the length of the column's buffer and where its free area starts.
+    fn buffer(strings: &StringArrayMut) -> (usize, usize) {
+        let data = strings.data.read().unwrap();
+        (data.buffer.len(), data.tail.load(Ordering::Relaxed))
+    }
+
+    fn values(strings: &StringArrayMut) -> Vec<String> {
+        (0..strings.len).map(|row| strings.value(row)).collect()
+    }
+
+    #[test]
+    fn inserts_reuse_deleted_rows() {
+        let table = table(4, 2);
+        let mut unlinked = vec![];
+
+        assert_eq!(table.delete(&1, |row| unlinked.push(row)), Some(1));
+        assert_eq!(table.delete(&1, |row| unlinked.push(row)), None);
+        assert_eq!(unlinked, vec![1]);
+
+        let mut written = vec![];
+        assert_eq!(table.insert(10, |row| written.push(row)), Some(1));
+        assert_eq!(written, vec![1]);
+
+        assert_eq!(table.get(&10), Some(1));
+        assert_eq!(table.get(&1), None);
+        assert_eq!(table.capacity(), 4);
+    }
+
+    #[test]
+    fn duplicate_keys_are_not_inserted() {
+        let table = table(2, 2);
+
+        assert_eq!(table.insert(1, |_| panic!("wrote a duplicate row")), None);
+        assert_eq!(table.get(&1), Some(1));
+        assert_eq!(table.capacity(), 2);
+    }
+
+    #[test]
+    fn rows_by_block_lists_rows_in_use_in_order() {
+        let table = table(3, 2);
+        table.delete(&1, |_| {}).unwrap();
+
+        // The first insert takes the deleted row, and the next two fill a new block.
+        for key in 10..13 {
+            table.insert(key, |_| {}).unwrap();
+        }
+
+        assert_eq!(layout(&table), vec![(3, vec![0, 1, 2]), (2, vec![0, 1])]);
+
+        table.delete(&11, |_| {}).unwrap();
+        table.delete(&2, |_| {}).unwrap();
+
+        assert_eq!(layout(&table), vec![(3, vec![0, 1]), (2, vec![1])]);
+    }
+
+    #[test]
+    fn shorter_strings_are_written_in_place() {
+        let strings = strings(&["abc", "defg"]);
+        assert_eq!(buffer(&strings), (14, 7));
+
+        strings.set(0, "x");
+
+        assert_eq!(values(&strings), vec!["x", "defg"]);
+        assert_eq!(strings.dead_bytes(), 2);
+        assert_eq!(buffer(&strings), (14, 7));
+    }
+
+    #[test]
+    fn longer_strings_are_appended_to_the_free_area() {
+        let strings = strings(&["abc", "defg"]);
+
+        strings.set(0, "abcdef");
+
+        assert_eq!(values(&strings), vec!["abcdef", "defg"]);
+        assert_eq!(strings.dead_bytes(), 3);
+        assert_eq!(buffer(&strings), (14, 13));
+    }
+
+    #[test]
+    fn compaction_keeps_every_string_and_drops_tombstones() {
+        let strings = strings(&["aa", "bb", "cc"]);
+        assert_eq!(buffer(&strings), (12, 6));
+
+        strings.set(0, "aaaa");
+        assert_eq!(strings.dead_bytes(), 2);
+        assert_eq!(buffer(&strings), (12, 10));
+
+        // The free area is too small, so the 8 bytes of values are copied into a 16 byte buffer
+        // before the new value is appended. Only the bytes it replaces are dead afterwards.
+        strings.set(1, "bbbb");
+
+        assert_eq!(values(&strings), vec!["aaaa", "bbbb", "cc"]);
+        assert_eq!(strings.dead_bytes(), 2);
+        assert_eq!(buffer(&strings), (16, 12));
+    }
+
+    #[test]
+    fn strings_larger_than_the_buffer_fit_after_compaction() {
+        let strings = strings(&["a", "b"]);
+        let long = "z".repeat(100);
+
+        strings.set(0, &long);
+
+        assert_eq!(values(&strings), vec![long.as_str(), "b"]);
+        assert_eq!(strings.dead_bytes(), 1);
+        assert_eq!(buffer(&strings), (102, 102));
+    }
+
+    #[test]
+    fn allocators_reuse_freed_rows() {
+        let allocator = RowAllocator::new(2, 4);
+        let no_growth = |_| panic!("grew with free rows");
+
+        assert_eq!(allocator.allocate(no_growth), 2);
+        assert_eq!(allocator.allocate(no_growth), 3);
+
+        allocator.free(0);
+        assert_eq!(allocator.allocate(no_growth), 0);
+        assert_eq!(allocator.capacity(), 4);
+    }
+
+    #[test]
+    fn allocators_grow_when_every_row_is_in_use() {
+        let allocator = RowAllocator::new(2, 2);
+
+        let row = allocator.allocate(|num_rows| {
+            assert_eq!(num_rows, 2);
+            3
+        });
+
+        assert_eq!(row, 2);
+        assert_eq!(allocator.allocate(|_| panic!("grew with free rows")), 3);
+        assert_eq!(allocator.allocate(|_| panic!("grew with free rows")), 4);
+        assert_eq!(allocator.capacity(), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "table did not grow")]
+    fn allocators_require_growth_when_full() {
+        RowAllocator::new(1, 1).allocate(|_| 0);
+    }
+
+    #[test]
+    fn blocks_map_rows_across_block_boundaries() {
+        let blocks = Blocks::new(TestBlock { len: 3 }, 3, 2);
+        assert_eq!(blocks.grow(), 2);
+        assert_eq!(blocks.grow(), 2);
+
+        let all = blocks.all();
+        let starts = all.iter().map(|(start, _, len)| (*start, *len));
+        assert_eq!(starts.collect::<Vec<_>>(), vec![(0, 3), (3, 2), (5, 2)]);
+
+        for (row, block, index) in [(2, 0, 2), (3, 1, 0), (4, 1, 1), (5, 2, 0), (6, 2, 1)] {
+            let (found, found_index) = blocks.get(row);
+            assert!(Arc::ptr_eq(&found, &all[block].1), "row {}", row);
+            assert_eq!(found_index, index, "row {}", row);
+        }
+    }
+
+    #[test]
+    fn blocks_map_rows_past_an_empty_first_block() {
+        let blocks = Blocks::new(TestBlock { len: 0 }, 0, 2);
+        blocks.grow();
+
+        let (block, index) = blocks.get(0);
+        assert!(Arc::ptr_eq(&block, &blocks.all()[1].1));
+        assert_eq!(index, 0);
+    }
+
+    #[test]
+    fn tables_grow_past_the_first_block() {
+        let table = table(2, 2);
+
+        for key in 10..13 {
+            table.insert(key, |_| {}).unwrap();
+        }
+
+        assert_eq!(table.get(&12), Some(4));
+        assert_eq!(table.capacity(), 6);
+
+        let (block, index) = table.block(4);
+        assert_eq!(block.len, 2);
+        assert_eq!(index, 0);
+
+        assert_eq!(
+            layout(&table),
+            vec![(2, vec![0, 1]), (2, vec![0, 1]), (2, vec![0])]
+        );
+    }
+
+    #[test]
+    fn hash_indexes_keep_the_first_row_of_a_key() {
+        let index = HashIndex::new();
+
+        assert!(index.insert("a", 0));
+        assert!(!index.insert("a", 1));
+        assert_eq!(index.get(&"a"), Some(0));
+
+        assert_eq!(index.remove(&"a"), Some(0));
+        assert_eq!(index.remove(&"a"), None);
+        assert_eq!(index.get(&"a"), None);
+    }
+
+    #[test]
+    fn multi_hash_indexes_keep_every_row_of_a_key() {
+        let index = MultiHashIndex::new();
+
+        index.insert("a", 2);
+        index.insert("a", 0);
+        index.insert("b", 1);
+        assert_eq!(index.get(&"a"), vec![2, 0]);
+
+        index.remove(&"a", 2);
+        assert_eq!(index.get(&"a"), vec![0]);
+
+        index.remove(&"a", 0);
+        index.remove(&"a", 0);
+        assert!(index.get(&"a").is_empty());
+        assert_eq!(index.get(&"b"), vec![1]);
+    }
+}
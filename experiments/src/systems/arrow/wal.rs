@@ -0,0 +1,249 @@
+//! A write-ahead log for the Arrow engines.
+//!
+//! Connections encode the changes a transaction makes as records and hand them to the log when
+//! the transaction ends. Each transaction's records are written as one frame, prefixed with its
+//! length and a checksum so that a frame torn by a crash is detected and ignored on recovery.
+//! Transactions that end while the log is being synced are written and synced together by the
+//! next one to sync it.
+
+use crate::codec::{put_u32, put_u64, Decoder};
+use fnv::FnvHasher;
+use std::fs::{File, OpenOptions};
+use std::hash::Hasher;
+use std::io;
+use std::io::{Read, Write};
+use std::mem;
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+
+/// A change that can be written to the log and replayed from it.
+pub trait Record: Sized {
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Decodes a record, returning `None` if `decoder` doesn't hold a whole one.
+    fn decode(decoder: &mut Decoder) -> Option<Self>;
+}
+
+fn checksum(payload: &[u8]) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write(payload);
+    hasher.finish()
+}
+
+struct State {
+    pending: Vec<u8>,
+    num_appended: u64,
+    num_durable: u64,
+    syncing: bool,
+}
+
+/// A log file shared by every connection to a database.
+pub struct Wal {
+    file: File,
+    sync: bool,
+    state: Mutex<State>,
+    synced: Condvar,
+}
+
+impl Wal {
+    /// Creates a log at `path`, replacing any log there.
+    pub fn create(path: &Path) -> io::Result<Wal> {
+        Wal::open(path, true)
+    }
+
+    /// Creates a log at `path` that is written but never synced, for logs that only need to
+    /// outlive the run and not a crash.
+    pub fn create_unsynced(path: &Path) -> io::Result<Wal> {
+        Wal::open(path, false)
+    }
+
+    fn open(path: &Path, sync: bool) -> io::Result<Wal> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        Ok(Wal {
+            file,
+            sync,
+            state: Mutex::new(State {
+                pending: vec![],
+                num_appended: 0,
+                num_durable: 0,
+                syncing: false,
+            }),
+            synced: Condvar::new(),
+        })
+    }
+
+    /// Appends the encoded `records` of one transaction and waits until they are durable.
+    pub fn commit(&self, records: &[u8]) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        put_u32(&mut state.pending, records.len() as u32);
+        put_u64(&mut state.pending, checksum(records));
+        state.pending.extend_from_slice(records);
+        state.num_appended += 1;
+
+        let lsn = state.num_appended;
+
+        while state.num_durable < lsn {
+            if state.syncing {
+                state = self.synced.wait(state).unwrap();
+                continue;
+            }
+
+            // Sync everything appended so far on behalf of every waiting transaction.
+            state.syncing = true;
+            let pending = mem::take(&mut state.pending);
+            let num_appended = state.num_appended;
+            drop(state);
+
+            let result = (&self.file).write_all(&pending).and_then(|_| {
+                if self.sync {
+                    self.file.sync_data()
+                } else {
+                    Ok(())
+                }
+            });
+
+            state = self.state.lock().unwrap();
+            state.syncing = false;
+
+            if result.is_ok() {
+                state.num_durable = num_appended;
+            }
+
+            self.synced.notify_all();
+            result?;
+        }
+
+        Ok(())
+    }
+
+    /// Decodes the records in the log at `path`, calling `apply` with each in order. Stops at
+    /// the first frame that was not completely written. Returns the number of records applied.
+    pub fn replay<R, F>(path: &Path, mut apply: F) -> io::Result<usize>
+    where
+        R: Record,
+        F: FnMut(R),
+    {
+        let mut buf = vec![];
+        File::open(path)?.read_to_end(&mut buf)?;
+
+        let mut frames = Decoder::new(&buf);
+        let mut num_records = 0;
+
+        while let Some(len) = frames.u32() {
+            let expected = match frames.u64() {
+                Some(expected) => expected,
+                None => break,
+            };
+
+            let payload = match frames.take(len as usize) {
+                Some(payload) if checksum(payload) == expected => payload,
+                _ => break,
+            };
+
+            let mut records = Decoder::new(payload);
+
+            while !records.is_empty() {
+                let record = R::decode(&mut records).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "invalid log record")
+                })?;
+
+                apply(record);
+                num_records += 1;
+            }
+        }
+
+        Ok(num_records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec;
+    use crate::systems::arrow::scratch_path;
+    use std::fs;
+
+    struct TestRecord(u32);
+
+    impl Record for TestRecord {
+        fn encode(&self, buf: &mut Vec<u8>) {
+            codec::put_u32(buf, self.0);
+        }
+
+        fn decode(decoder: &mut Decoder) -> Option<TestRecord> {
+            decoder.u32().map(TestRecord)
+        }
+    }
+
+    fn replay(path: &Path) -> Vec<u32> {
+        let mut records = vec![];
+        let num_records = Wal::replay(path, |TestRecord(value)| records.push(value)).unwrap();
+        assert_eq!(num_records, records.len());
+        records
+    }
+
+    #[test]
+    fn replay_recovers_the_transactions_written_before_a_tear() {
+        let path = scratch_path("wal");
+        let transactions = vec![vec![1, 2], vec![3], vec![4, 5, 6]];
+        let mut ends = vec![];
+
+        {
+            let wal = Wal::create(&path).unwrap();
+
+            for records in &transactions {
+                let mut buf = vec![];
+                for &value in records {
+                    TestRecord(value).encode(&mut buf);
+                }
+
+                wal.commit(&buf).unwrap();
+                ends.push(fs::metadata(&path).unwrap().len());
+            }
+        }
+
+        assert_eq!(replay(&path), vec![1, 2, 3, 4, 5, 6]);
+
+        // Tearing the log anywhere, including inside a frame's header, loses only the frames that
+        // were not completely written.
+        for len in (0..ends[2]).rev() {
+            fs::OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_len(len)
+                .unwrap();
+
+            let num_whole = ends.iter().filter(|&&end| end <= len).count();
+            let expected = transactions[..num_whole].concat();
+            assert_eq!(replay(&path), expected, "log torn at byte {}", len);
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replay_stops_at_a_frame_that_fails_its_checksum() {
+        let path = scratch_path("wal-checksum");
+
+        {
+            let wal = Wal::create(&path).unwrap();
+            wal.commit(&1u32.to_le_bytes()).unwrap();
+            wal.commit(&2u32.to_le_bytes()).unwrap();
+        }
+
+        // Flip a bit of the second frame's payload, which is the last byte of the log.
+        let mut buf = fs::read(&path).unwrap();
+        *buf.last_mut().unwrap() ^= 1;
+        fs::write(&path, &buf).unwrap();
+
+        assert_eq!(replay(&path), vec![1]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,3 +1,69 @@
+use clap::{Arg, ArgMatches};
+
+#[cfg(feature = "arrow")]
 pub mod arrow;
 pub mod mysql;
+pub mod pool;
+pub mod postgres;
 pub mod sqlite;
+
+/// The isolation level that a SQL system runs its own transactions at, beneath any isolation that
+/// dibs provides. Each system maps the levels onto the closest ones it supports, and rejects those
+/// it has no counterpart for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IsolationLevel {
+    ReadUncommitted,
+    ReadCommitted,
+    Snapshot,
+    Serializable,
+}
+
+impl IsolationLevel {
+    /// Returns the `--isolation-level` flag.
+    pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![Arg::with_name("isolation_level")
+            .long("isolation-level")
+            .possible_values(&[
+                "read-uncommitted",
+                "read-committed",
+                "snapshot",
+                "serializable",
+            ])
+            .takes_value(true)
+            .help("Isolation level of the system's transactions, defaults to the system's own")]
+    }
+
+    /// Parses the flag from `matches`, returning `default` if it is absent.
+    pub fn from_matches(matches: &ArgMatches, default: IsolationLevel) -> IsolationLevel {
+        match matches.value_of("isolation_level") {
+            Some("read-uncommitted") => IsolationLevel::ReadUncommitted,
+            Some("read-committed") => IsolationLevel::ReadCommitted,
+            Some("snapshot") => IsolationLevel::Snapshot,
+            Some("serializable") => IsolationLevel::Serializable,
+            Some(_) => unreachable!(),
+            None => default,
+        }
+    }
+
+    pub fn parameter(&self) -> (&'static str, String) {
+        let value = match self {
+            IsolationLevel::ReadUncommitted => "read-uncommitted",
+            IsolationLevel::ReadCommitted => "read-committed",
+            IsolationLevel::Snapshot => "snapshot",
+            IsolationLevel::Serializable => "serializable",
+        };
+
+        ("isolation_level", value.to_string())
+    }
+
+    /// Returns the level as named by `SET TRANSACTION ISOLATION LEVEL` in Postgres and MySQL, where
+    /// repeatable read runs transactions on a snapshot.
+    fn sql(&self) -> &'static str {
+        match self {
+            IsolationLevel::ReadUncommitted => "READ UNCOMMITTED",
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::Snapshot => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        }
+    }
+}
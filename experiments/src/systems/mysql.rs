@@ -1,10 +1,14 @@
-use crate::benchmarks::ycsb;
+use crate::benchmarks::tatp::TATPConnection;
 use crate::benchmarks::ycsb::YCSBConnection;
+use crate::benchmarks::{tatp, ycsb};
+use crate::consistency::{self, Consistency};
+use crate::systems::IsolationLevel;
 use crate::Connection;
 use itertools::Itertools;
 use mysql::prelude::Queryable;
-use mysql::{params, Conn, OptsBuilder, Statement, TxOpts};
+use mysql::{params, Conn, OptsBuilder, Row, Statement, TxOpts, Value};
 use rand::distributions::Alphanumeric;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
 use rand::Rng;
 use std::str::FromStr;
@@ -29,17 +33,460 @@ impl FromStr for IsolationMechanism {
     }
 }
 
-pub fn load_ycsb(num_rows: u32, field_size: usize) {
+impl IsolationMechanism {
+    /// Returns the isolation level that MySQL runs transactions at under this mechanism.
+    pub fn isolation_level(&self) -> IsolationLevel {
+        match self {
+            IsolationMechanism::MySQLSerializable => IsolationLevel::Serializable,
+            IsolationMechanism::MySQLReadUncommitted | IsolationMechanism::DibsSerializable => {
+                IsolationLevel::ReadUncommitted
+            }
+        }
+    }
+}
+
+/// Sets the isolation level of the transactions on `conn`. Repeatable read is MySQL's snapshot
+/// isolation.
+fn set_isolation_level(conn: &mut Conn, level: IsolationLevel) {
+    conn.query_drop(format!(
+        "SET SESSION TRANSACTION ISOLATION LEVEL {};",
+        level.sql()
+    ))
+    .unwrap();
+}
+
+/// Connects to `db` and sets the isolation level of its transactions.
+fn connect(db: &str, isolation: IsolationMechanism) -> Conn {
+    let mut conn = Conn::new(OptsBuilder::new().user(Some("dibs")).db_name(Some(db))).unwrap();
+    set_isolation_level(&mut conn, isolation.isolation_level());
+    conn
+}
+
+fn check(conn: &mut Conn, checks: &[(String, String)]) -> Consistency {
+    Consistency::query(checks, |query| {
+        conn.query_first::<u64, _>(query).unwrap().unwrap() as usize
+    })
+}
+
+pub fn load_tatp(num_rows: u32, mut rng: StdRng) {
+    let mut conn = Conn::new(OptsBuilder::new().user(Some("dibs")).db_name(Some("tatp"))).unwrap();
+
+    conn.query_drop("DROP TABLE IF EXISTS tatp.call_forwarding;")
+        .unwrap();
+    conn.query_drop("DROP TABLE IF EXISTS tatp.special_facility;")
+        .unwrap();
+    conn.query_drop("DROP TABLE IF EXISTS tatp.access_info;")
+        .unwrap();
+    conn.query_drop("DROP TABLE IF EXISTS tatp.subscriber;")
+        .unwrap();
+
+    conn.query_drop(
+        "CREATE TABLE tatp.subscriber (s_id INTEGER UNSIGNED PRIMARY KEY,
+                bit_1 BOOLEAN, bit_2 BOOLEAN, bit_3 BOOLEAN, bit_4 BOOLEAN,
+                bit_5 BOOLEAN, bit_6 BOOLEAN, bit_7 BOOLEAN, bit_8 BOOLEAN,
+                bit_9 BOOLEAN, bit_10 BOOLEAN,
+                hex_1 TINYINT UNSIGNED, hex_2 TINYINT UNSIGNED, hex_3 TINYINT UNSIGNED,
+                hex_4 TINYINT UNSIGNED, hex_5 TINYINT UNSIGNED, hex_6 TINYINT UNSIGNED,
+                hex_7 TINYINT UNSIGNED, hex_8 TINYINT UNSIGNED, hex_9 TINYINT UNSIGNED,
+                hex_10 TINYINT UNSIGNED,
+                byte2_1 TINYINT UNSIGNED, byte2_2 TINYINT UNSIGNED, byte2_3 TINYINT UNSIGNED,
+                byte2_4 TINYINT UNSIGNED, byte2_5 TINYINT UNSIGNED, byte2_6 TINYINT UNSIGNED,
+                byte2_7 TINYINT UNSIGNED, byte2_8 TINYINT UNSIGNED, byte2_9 TINYINT UNSIGNED,
+                byte2_10 TINYINT UNSIGNED,
+                msc_location INTEGER UNSIGNED, vlr_location INTEGER UNSIGNED);",
+    )
+    .unwrap();
+
+    conn.query_drop(
+        "CREATE TABLE tatp.access_info (s_id INTEGER UNSIGNED NOT NULL,
+                ai_type TINYINT UNSIGNED NOT NULL,
+                data1 TINYINT UNSIGNED, data2 TINYINT UNSIGNED, data3 CHAR(3), data4 CHAR(5),
+                PRIMARY KEY (s_id, ai_type),
+                FOREIGN KEY (s_id) REFERENCES tatp.subscriber (s_id));",
+    )
+    .unwrap();
+
+    conn.query_drop(
+        "CREATE TABLE tatp.special_facility (s_id INTEGER UNSIGNED NOT NULL,
+                sf_type TINYINT UNSIGNED NOT NULL,
+                is_active BOOLEAN, error_cntrl TINYINT UNSIGNED,
+                data_a TINYINT UNSIGNED, data_b CHAR(5),
+                PRIMARY KEY (s_id, sf_type),
+                FOREIGN KEY (s_id) REFERENCES tatp.subscriber (s_id));",
+    )
+    .unwrap();
+
+    conn.query_drop(
+        "CREATE TABLE tatp.call_forwarding (s_id INTEGER UNSIGNED NOT NULL,
+                sf_type TINYINT UNSIGNED NOT NULL,
+                start_time TINYINT UNSIGNED, end_time TINYINT UNSIGNED, numberx CHAR(15),
+                PRIMARY KEY (s_id, sf_type, start_time),
+                FOREIGN KEY (s_id, sf_type)
+                REFERENCES tatp.special_facility (s_id, sf_type));",
+    )
+    .unwrap();
+
+    let mut s_ids = (1..=num_rows).collect::<Vec<_>>();
+    s_ids.shuffle(&mut rng);
+
+    let mut transaction = conn.start_transaction(TxOpts::default()).unwrap();
+
+    // Subscribers are inserted in batches, each followed by the rows that reference them.
+    for s_ids in s_ids.chunks(1000) {
+        transaction
+            .query_drop(format!(
+                "INSERT INTO tatp.subscriber VALUES {};",
+                s_ids
+                    .iter()
+                    .map(|&s_id| format!(
+                        "({},{},{},{},{},{})",
+                        s_id,
+                        (0..10).map(|_| rng.gen_range(0, 2)).join(","),
+                        (0..10).map(|_| rng.gen_range(0, 16)).join(","),
+                        (0..10).map(|_| rng.gen_range(0, 256)).join(","),
+                        rng.gen::<u32>(),
+                        rng.gen::<u32>(),
+                    ))
+                    .join(",")
+            ))
+            .unwrap();
+
+        transaction
+            .query_drop(format!(
+                "INSERT INTO tatp.access_info VALUES {};",
+                s_ids
+                    .iter()
+                    .flat_map(|&s_id| {
+                        let num_ai_types = rng.gen_range(1, 5);
+                        [1, 2, 3, 4]
+                            .choose_multiple(&mut rng, num_ai_types)
+                            .map(|&ai_type| {
+                                format!(
+                                    "({},{},{},{},'{}','{}')",
+                                    s_id,
+                                    ai_type,
+                                    rng.gen::<u8>(),
+                                    rng.gen::<u8>(),
+                                    tatp::uppercase_alphabetic_string(3, &mut rng),
+                                    tatp::uppercase_alphabetic_string(5, &mut rng)
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .join(",")
+            ))
+            .unwrap();
+
+        let sf_types = s_ids
+            .iter()
+            .flat_map(|&s_id| {
+                let num_sf_types = rng.gen_range(1, 5);
+                [1, 2, 3, 4]
+                    .choose_multiple(&mut rng, num_sf_types)
+                    .map(move |&sf_type| (s_id, sf_type))
+            })
+            .collect::<Vec<_>>();
+
+        transaction
+            .query_drop(format!(
+                "INSERT INTO tatp.special_facility VALUES {};",
+                sf_types
+                    .iter()
+                    .map(|&(s_id, sf_type)| {
+                        format!(
+                            "({},{},{},{},{},'{}')",
+                            s_id,
+                            sf_type,
+                            if rng.gen_bool(0.85) { 1 } else { 0 },
+                            rng.gen::<u8>(),
+                            rng.gen::<u8>(),
+                            tatp::uppercase_alphabetic_string(5, &mut rng),
+                        )
+                    })
+                    .join(",")
+            ))
+            .unwrap();
+
+        let call_forwarding = sf_types
+            .iter()
+            .flat_map(|&(s_id, sf_type)| {
+                let num_start_times = rng.gen_range(0, 4);
+                [0, 8, 16]
+                    .choose_multiple(&mut rng, num_start_times)
+                    .map(|&start_time| {
+                        format!(
+                            "({},{},{},{},'{}')",
+                            s_id,
+                            sf_type,
+                            start_time,
+                            start_time + rng.gen_range(1, 9),
+                            tatp::uppercase_alphabetic_string(15, &mut rng)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        if !call_forwarding.is_empty() {
+            transaction
+                .query_drop(format!(
+                    "INSERT INTO tatp.call_forwarding VALUES {};",
+                    call_forwarding.join(",")
+                ))
+                .unwrap();
+        }
+    }
+
+    transaction.commit().unwrap();
+}
+
+pub struct MySQLTATPConnection {
+    conn: Conn,
+    get_subscriber_data_stmt: Statement,
+    get_new_destination_stmt: Statement,
+    get_access_data_stmt: Statement,
+    update_subscriber_bit_stmt: Statement,
+    update_special_facility_data_stmt: Statement,
+    update_subscriber_location_stmt: Statement,
+    get_special_facility_types_stmt: Statement,
+    insert_call_forwarding_stmt: Statement,
+    delete_call_forwarding_stmt: Statement,
+}
+
+impl MySQLTATPConnection {
+    pub fn new(isolation: IsolationMechanism) -> MySQLTATPConnection {
+        let mut conn = connect("tatp", isolation);
+
+        let get_subscriber_data_stmt = conn
+            .prep(
+                "SELECT *
+                FROM tatp.subscriber
+                WHERE s_id = ?;",
+            )
+            .unwrap();
+
+        let get_new_destination_stmt = conn
+            .prep(
+                "SELECT cf.numberx
+                FROM tatp.special_facility AS sf, tatp.call_forwarding AS cf
+                WHERE
+                    (sf.s_id = ?
+                        AND sf.sf_type = ?
+                        AND sf.is_active = 1)
+                    AND (cf.s_id = sf.s_id
+                        AND cf.sf_type = sf.sf_type)
+                    AND (cf.start_time <= ?
+                        AND ? < cf.end_time);",
+            )
+            .unwrap();
+
+        let get_access_data_stmt = conn
+            .prep(
+                "SELECT data1, data2, data3, data4
+                FROM tatp.access_info
+                WHERE s_id = ? AND ai_type = ?;",
+            )
+            .unwrap();
+
+        let update_subscriber_bit_stmt = conn
+            .prep(
+                "UPDATE tatp.subscriber
+                SET bit_1 = ?
+                WHERE s_id = ?;",
+            )
+            .unwrap();
+
+        let update_special_facility_data_stmt = conn
+            .prep(
+                "UPDATE tatp.special_facility
+                SET data_a = ?
+                WHERE s_id = ? AND sf_type = ?;",
+            )
+            .unwrap();
+
+        let update_subscriber_location_stmt = conn
+            .prep(
+                "UPDATE tatp.subscriber
+                SET vlr_location = ?
+                WHERE s_id = ?;",
+            )
+            .unwrap();
+
+        let get_special_facility_types_stmt = conn
+            .prep(
+                "SELECT sf_type
+                FROM tatp.special_facility
+                WHERE s_id = ?;",
+            )
+            .unwrap();
+
+        // Duplicate keys and missing special facilities are expected, so they are ignored.
+        let insert_call_forwarding_stmt = conn
+            .prep(
+                "INSERT IGNORE INTO tatp.call_forwarding
+                VALUES (?, ?, ?, ?, ?);",
+            )
+            .unwrap();
+
+        let delete_call_forwarding_stmt = conn
+            .prep(
+                "DELETE FROM tatp.call_forwarding
+                WHERE s_id = ? AND sf_type = ? AND start_time = ?;",
+            )
+            .unwrap();
+
+        MySQLTATPConnection {
+            conn,
+            get_subscriber_data_stmt,
+            get_new_destination_stmt,
+            get_access_data_stmt,
+            update_subscriber_bit_stmt,
+            update_special_facility_data_stmt,
+            update_subscriber_location_stmt,
+            get_special_facility_types_stmt,
+            insert_call_forwarding_stmt,
+            delete_call_forwarding_stmt,
+        }
+    }
+
+    /// Runs transactions at `level` instead of the level of the isolation mechanism.
+    pub fn with_isolation_level(mut self, level: IsolationLevel) -> MySQLTATPConnection {
+        set_isolation_level(&mut self.conn, level);
+        self
+    }
+
+    pub fn check_consistency(&mut self) -> Consistency {
+        check(&mut self.conn, &consistency::tatp_checks())
+    }
+}
+
+impl Connection for MySQLTATPConnection {
+    fn begin(&mut self) {
+        self.conn.query_drop("START TRANSACTION").unwrap();
+    }
+
+    fn commit(&mut self) {
+        self.conn.query_drop("COMMIT").unwrap();
+    }
+
+    fn rollback(&mut self) {
+        self.conn.query_drop("ROLLBACK TO SAVEPOINT x").unwrap();
+    }
+
+    fn savepoint(&mut self) {
+        self.conn.query_drop("SAVEPOINT x").unwrap();
+    }
+}
+
+impl TATPConnection for MySQLTATPConnection {
+    fn get_subscriber_data(&mut self, s_id: u32) -> ([bool; 10], [u8; 10], [u8; 10], u32, u32) {
+        let row: Row = self
+            .conn
+            .exec_first(&self.get_subscriber_data_stmt, (s_id,))
+            .unwrap()
+            .unwrap();
+
+        let mut bit = [false; 10];
+        for (i, flag) in bit.iter_mut().enumerate() {
+            *flag = row.get(i + 1).unwrap();
+        }
+
+        let mut hex = [0; 10];
+        for (i, digit) in hex.iter_mut().enumerate() {
+            *digit = row.get(i + 11).unwrap();
+        }
+
+        let mut byte2 = [0; 10];
+        for (i, byte) in byte2.iter_mut().enumerate() {
+            *byte = row.get(i + 21).unwrap();
+        }
+
+        (bit, hex, byte2, row.get(31).unwrap(), row.get(32).unwrap())
+    }
+
+    fn get_new_destination(
+        &mut self,
+        s_id: u32,
+        sf_type: u8,
+        start_time: u8,
+        end_time: u8,
+    ) -> Vec<String> {
+        self.conn
+            .exec(
+                &self.get_new_destination_stmt,
+                (s_id, sf_type, start_time, end_time),
+            )
+            .unwrap()
+    }
+
+    fn get_access_data(&mut self, s_id: u32, ai_type: u8) -> Option<(u8, u8, String, String)> {
+        self.conn
+            .exec_first(&self.get_access_data_stmt, (s_id, ai_type))
+            .unwrap()
+    }
+
+    fn update_subscriber_bit(&mut self, bit_1: bool, s_id: u32) {
+        self.conn
+            .exec_drop(&self.update_subscriber_bit_stmt, (bit_1, s_id))
+            .unwrap();
+    }
+
+    fn update_special_facility_data(&mut self, data_a: u8, s_id: u32, sf_type: u8) {
+        self.conn
+            .exec_drop(
+                &self.update_special_facility_data_stmt,
+                (data_a, s_id, sf_type),
+            )
+            .unwrap();
+    }
+
+    fn update_subscriber_location(&mut self, vlr_location: u32, s_id: u32) {
+        self.conn
+            .exec_drop(&self.update_subscriber_location_stmt, (vlr_location, s_id))
+            .unwrap();
+    }
+
+    fn get_special_facility_types(&mut self, s_id: u32) -> Vec<u8> {
+        self.conn
+            .exec(&self.get_special_facility_types_stmt, (s_id,))
+            .unwrap()
+    }
+
+    fn insert_call_forwarding(
+        &mut self,
+        s_id: u32,
+        sf_type: u8,
+        start_time: u8,
+        end_time: u8,
+        numberx: &str,
+    ) {
+        self.conn
+            .exec_drop(
+                &self.insert_call_forwarding_stmt,
+                (s_id, sf_type, start_time, end_time, numberx),
+            )
+            .unwrap();
+    }
+
+    fn delete_call_forwarding(&mut self, s_id: u32, sf_type: u8, start_time: u8) {
+        self.conn
+            .exec_drop(
+                &self.delete_call_forwarding_stmt,
+                (s_id, sf_type, start_time),
+            )
+            .unwrap();
+    }
+}
+
+pub fn load_ycsb(num_rows: u32, field_size: usize, mut rng: StdRng) {
     assert!(num_rows > 0);
     assert_eq!(num_rows % 1000, 0);
 
-    let mut rng = rand::thread_rng();
-
     let mut conn = Conn::new(OptsBuilder::new().user(Some("dibs")).db_name(Some("ycsb"))).unwrap();
 
     conn.query_drop("DROP TABLE IF EXISTS ycsb.users;").unwrap();
 
-    conn.query_drop(&format!(
+    conn.query_drop(format!(
         "CREATE TABLE ycsb.users (id INTEGER PRIMARY KEY, {});",
         (0..ycsb::NUM_FIELDS)
             .map(|field| format!("field_{} CHAR({})", field, field_size))
@@ -54,7 +501,7 @@ pub fn load_ycsb(num_rows: u32, field_size: usize) {
 
     for i in 0..num_rows as usize / 1000 {
         transaction
-            .query_drop(&format!(
+            .query_drop(format!(
                 "INSERT INTO ycsb.users VALUES {};",
                 ids.iter()
                     .skip(i * 1000)
@@ -65,7 +512,8 @@ pub fn load_ycsb(num_rows: u32, field_size: usize) {
                         (0..ycsb::NUM_FIELDS)
                             .map(|_| format!(
                                 "'{}'",
-                                rng.sample_iter(&Alphanumeric)
+                                (&mut rng)
+                                    .sample_iter(&Alphanumeric)
                                     .take(field_size)
                                     .collect::<String>()
                             ))
@@ -83,27 +531,18 @@ pub struct MySQLYCSBConnection {
     conn: Conn,
     select_user_stmts: Vec<Statement>,
     update_user_stmts: Vec<Statement>,
+    insert_user_stmt: Statement,
+    delete_user_stmt: Statement,
+    scan_users_stmts: Vec<Statement>,
 }
 
 impl MySQLYCSBConnection {
     pub fn new(isolation: IsolationMechanism) -> MySQLYCSBConnection {
-        let mut conn =
-            Conn::new(OptsBuilder::new().user(Some("dibs")).db_name(Some("ycsb"))).unwrap();
-
-        conn.query_drop(format!(
-            "SET SESSION TRANSACTION ISOLATION LEVEL {};",
-            match isolation {
-                IsolationMechanism::MySQLSerializable => "SERIALIZABLE",
-                IsolationMechanism::MySQLReadUncommitted | IsolationMechanism::DibsSerializable => {
-                    "READ UNCOMMITTED"
-                }
-            }
-        ))
-        .unwrap();
+        let mut conn = connect("ycsb", isolation);
 
         let select_user_stmts = (0..ycsb::NUM_FIELDS)
             .map(|field| {
-                conn.prep(&format!(
+                conn.prep(format!(
                     "SELECT field_{} FROM ycsb.users WHERE id = ?;",
                     field
                 ))
@@ -113,7 +552,7 @@ impl MySQLYCSBConnection {
 
         let update_user_stmts = (0..ycsb::NUM_FIELDS)
             .map(|field| {
-                conn.prep(&format!(
+                conn.prep(format!(
                     "UPDATE ycsb.users SET field_{} = :field WHERE id = :id;",
                     field
                 ))
@@ -121,12 +560,44 @@ impl MySQLYCSBConnection {
             })
             .collect();
 
+        let insert_user_stmt = conn
+            .prep(format!(
+                "INSERT INTO ycsb.users VALUES (?{});",
+                ",?".repeat(ycsb::NUM_FIELDS)
+            ))
+            .unwrap();
+
+        let delete_user_stmt = conn.prep("DELETE FROM ycsb.users WHERE id = ?;").unwrap();
+
+        let scan_users_stmts = (0..ycsb::NUM_FIELDS)
+            .map(|field| {
+                conn.prep(format!(
+                    "SELECT field_{} FROM ycsb.users WHERE id >= ? AND id < ?;",
+                    field
+                ))
+                .unwrap()
+            })
+            .collect();
+
         MySQLYCSBConnection {
             conn,
             select_user_stmts,
             update_user_stmts,
+            insert_user_stmt,
+            delete_user_stmt,
+            scan_users_stmts,
         }
     }
+
+    /// Runs transactions at `level` instead of the level of the isolation mechanism.
+    pub fn with_isolation_level(mut self, level: IsolationLevel) -> MySQLYCSBConnection {
+        set_isolation_level(&mut self.conn, level);
+        self
+    }
+
+    pub fn check_consistency(&mut self, field_size: usize) -> Consistency {
+        check(&mut self.conn, &consistency::ycsb_checks(field_size))
+    }
 }
 
 impl Connection for MySQLYCSBConnection {
@@ -152,7 +623,7 @@ impl YCSBConnection for MySQLYCSBConnection {
         self.conn
             .exec_first(&self.select_user_stmts[field], (user_id,))
             .unwrap()
-            .unwrap()
+            .unwrap_or_default()
     }
 
     fn update_user(&mut self, field: usize, data: &str, user_id: u32) {
@@ -166,4 +637,27 @@ impl YCSBConnection for MySQLYCSBConnection {
             )
             .unwrap();
     }
+
+    fn insert_user(&mut self, user_id: u32, fields: &[String]) {
+        self.conn
+            .exec_drop(
+                &self.insert_user_stmt,
+                std::iter::once(Value::from(user_id))
+                    .chain(fields.iter().map(Value::from))
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap();
+    }
+
+    fn delete_user(&mut self, user_id: u32) {
+        self.conn
+            .exec_drop(&self.delete_user_stmt, (user_id,))
+            .unwrap();
+    }
+
+    fn scan_users(&mut self, field: usize, start_user_id: u32, end_user_id: u32) -> Vec<String> {
+        self.conn
+            .exec(&self.scan_users_stmts[field], (start_user_id, end_user_id))
+            .unwrap()
+    }
 }
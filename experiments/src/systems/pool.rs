@@ -0,0 +1,352 @@
+//! A pool of connections to a SQL database, shared by the workers of a run.
+//!
+//! Without a pool, each worker opens a connection of its own and holds it for the whole run. A
+//! `PooledConnection` instead checks a connection out of the pool when its transaction begins and
+//! checks it back in when the transaction commits, so that many workers can share fewer
+//! connections than there are workers. Connections are opened lazily, up to the pool's maximum
+//! size, and are kept open while idle, along with the statements each one has prepared.
+
+use crate::benchmarks::tatp::TATPConnection;
+use crate::benchmarks::ycsb::YCSBConnection;
+use crate::Connection;
+use clap::{Arg, ArgMatches};
+use std::mem;
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Returns the `--pool-size` flag.
+pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![Arg::with_name("pool_size")
+        .long("pool-size")
+        .takes_value(true)
+        .help("Shares this many connections among the workers, defaults to one per worker")]
+}
+
+/// Parses the `--pool-size` flag from `matches`, returning `num_workers` if it is absent.
+pub fn size_from_matches(matches: &ArgMatches, num_workers: usize) -> usize {
+    matches
+        .value_of("pool_size")
+        .map_or(num_workers, |size| usize::from_str(size).unwrap())
+}
+
+struct PoolState<C> {
+    idle: Vec<C>,
+    size: usize,
+}
+
+pub struct Pool<C> {
+    state: Mutex<PoolState<C>>,
+    available: Condvar,
+    max_size: usize,
+    connect: Box<dyn Fn() -> C + Send + Sync>,
+}
+
+impl<C> Pool<C> {
+    /// Creates a pool of at most `max_size` connections, each opened by `connect`.
+    pub fn new<F>(max_size: usize, connect: F) -> Pool<C>
+    where
+        F: Fn() -> C + Send + Sync + 'static,
+    {
+        assert!(max_size > 0, "pool size must be positive");
+
+        Pool {
+            state: Mutex::new(PoolState {
+                idle: vec![],
+                size: 0,
+            }),
+            available: Condvar::new(),
+            max_size,
+            connect: Box::new(connect),
+        }
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Returns an idle connection, opening a new one if there is none and the pool isn't full, or
+    /// otherwise waiting for one to be checked in.
+    pub fn checkout(&self) -> C {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some(connection) = state.idle.pop() {
+                return connection;
+            }
+
+            if state.size < self.max_size {
+                state.size += 1;
+                break;
+            }
+
+            state = self.available.wait(state).unwrap();
+        }
+
+        // Connect without holding the lock, since opening a connection can take a while. If
+        // connecting panics, the reservation gives its slot back.
+        drop(state);
+        let reservation = Reservation { pool: self };
+        let connection = (self.connect)();
+        mem::forget(reservation);
+        connection
+    }
+
+    /// Returns a connection to the pool, waking a worker waiting for one.
+    pub fn checkin(&self, connection: C) {
+        self.state.lock().unwrap().idle.push(connection);
+        self.available.notify_one();
+    }
+}
+
+/// A slot in a pool taken for a connection that is still being opened.
+struct Reservation<'a, C> {
+    pool: &'a Pool<C>,
+}
+
+impl<C> Drop for Reservation<'_, C> {
+    fn drop(&mut self) {
+        self.pool.state.lock().unwrap().size -= 1;
+        self.pool.available.notify_one();
+    }
+}
+
+/// A worker's handle on a pool, which holds a connection from the pool for the duration of each
+/// transaction.
+pub struct PooledConnection<C> {
+    pool: Arc<Pool<C>>,
+    connection: Option<C>,
+}
+
+impl<C> PooledConnection<C> {
+    pub fn new(pool: Arc<Pool<C>>) -> PooledConnection<C> {
+        PooledConnection {
+            pool,
+            connection: None,
+        }
+    }
+
+    fn get(&mut self) -> &mut C {
+        self.connection
+            .as_mut()
+            .expect("statement outside of a transaction")
+    }
+}
+
+impl<C> Drop for PooledConnection<C> {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.pool.checkin(connection);
+        }
+    }
+}
+
+impl<C: Connection> Connection for PooledConnection<C> {
+    fn begin(&mut self) {
+        assert!(self.connection.is_none(), "transaction already begun");
+        self.connection = Some(self.pool.checkout());
+        self.get().begin();
+    }
+
+    fn commit(&mut self) {
+        self.get().commit();
+        self.pool.checkin(self.connection.take().unwrap());
+    }
+
+    fn rollback(&mut self) {
+        self.get().rollback();
+    }
+
+    fn savepoint(&mut self) {
+        self.get().savepoint();
+    }
+}
+
+impl<C: TATPConnection> TATPConnection for PooledConnection<C> {
+    fn get_subscriber_data(&mut self, s_id: u32) -> ([bool; 10], [u8; 10], [u8; 10], u32, u32) {
+        self.get().get_subscriber_data(s_id)
+    }
+
+    fn get_new_destination(
+        &mut self,
+        s_id: u32,
+        sf_type: u8,
+        start_time: u8,
+        end_time: u8,
+    ) -> Vec<String> {
+        self.get()
+            .get_new_destination(s_id, sf_type, start_time, end_time)
+    }
+
+    fn get_access_data(&mut self, s_id: u32, ai_type: u8) -> Option<(u8, u8, String, String)> {
+        self.get().get_access_data(s_id, ai_type)
+    }
+
+    fn update_subscriber_bit(&mut self, bit_1: bool, s_id: u32) {
+        self.get().update_subscriber_bit(bit_1, s_id)
+    }
+
+    fn update_special_facility_data(&mut self, data_a: u8, s_id: u32, sf_type: u8) {
+        self.get()
+            .update_special_facility_data(data_a, s_id, sf_type)
+    }
+
+    fn update_subscriber_data(&mut self, bit_1: bool, s_id: u32, data_a: u8, sf_type: u8) {
+        self.get()
+            .update_subscriber_data(bit_1, s_id, data_a, sf_type)
+    }
+
+    fn update_subscriber_location(&mut self, vlr_location: u32, s_id: u32) {
+        self.get().update_subscriber_location(vlr_location, s_id)
+    }
+
+    fn get_special_facility_types(&mut self, s_id: u32) -> Vec<u8> {
+        self.get().get_special_facility_types(s_id)
+    }
+
+    fn insert_call_forwarding(
+        &mut self,
+        s_id: u32,
+        sf_type: u8,
+        start_time: u8,
+        end_time: u8,
+        numberx: &str,
+    ) {
+        self.get()
+            .insert_call_forwarding(s_id, sf_type, start_time, end_time, numberx)
+    }
+
+    fn delete_call_forwarding(&mut self, s_id: u32, sf_type: u8, start_time: u8) {
+        self.get().delete_call_forwarding(s_id, sf_type, start_time)
+    }
+}
+
+impl<C: YCSBConnection> YCSBConnection for PooledConnection<C> {
+    fn select_user(&mut self, field: usize, user_id: u32) -> String {
+        self.get().select_user(field, user_id)
+    }
+
+    fn update_user(&mut self, field: usize, data: &str, user_id: u32) {
+        self.get().update_user(field, data, user_id)
+    }
+
+    fn insert_user(&mut self, user_id: u32, fields: &[String]) {
+        self.get().insert_user(user_id, fields)
+    }
+
+    fn delete_user(&mut self, user_id: u32) {
+        self.get().delete_user(user_id)
+    }
+
+    fn scan_users(&mut self, field: usize, start_user_id: u32, end_user_id: u32) -> Vec<String> {
+        self.get().scan_users(field, start_user_id, end_user_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    /// A connection that only records how many transactions it has committed.
+    struct TestConnection {
+        id: usize,
+        num_commits: usize,
+    }
+
+    impl Connection for TestConnection {
+        fn begin(&mut self) {}
+
+        fn commit(&mut self) {
+            self.num_commits += 1;
+        }
+
+        fn rollback(&mut self) {}
+
+        fn savepoint(&mut self) {}
+    }
+
+    /// Returns a pool whose connections are numbered in the order they are opened.
+    fn pool(max_size: usize) -> Arc<Pool<TestConnection>> {
+        let num_connects = AtomicUsize::new(0);
+
+        Arc::new(Pool::new(max_size, move || TestConnection {
+            id: num_connects.fetch_add(1, Ordering::Relaxed),
+            num_commits: 0,
+        }))
+    }
+
+    #[test]
+    fn idle_connections_are_reused() {
+        let pool = pool(2);
+
+        let connection = pool.checkout();
+        pool.checkin(connection);
+        let connection = pool.checkout();
+        let other_connection = pool.checkout();
+
+        assert_eq!(connection.id, 0);
+        assert_eq!(other_connection.id, 1);
+    }
+
+    #[test]
+    fn checkouts_wait_for_a_checkin_when_full() {
+        let pool = pool(1);
+        let connection = pool.checkout();
+
+        let waiter = {
+            let pool = Arc::clone(&pool);
+            thread::spawn(move || pool.checkout())
+        };
+
+        thread::sleep(Duration::from_millis(20));
+        pool.checkin(connection);
+
+        assert_eq!(waiter.join().unwrap().id, 0);
+    }
+
+    #[test]
+    fn failed_connects_give_their_slot_back() {
+        let num_connects = AtomicUsize::new(0);
+
+        let pool = Pool::new(1, move || {
+            if num_connects.fetch_add(1, Ordering::Relaxed) == 0 {
+                panic!("connection refused");
+            }
+
+            TestConnection {
+                id: 1,
+                num_commits: 0,
+            }
+        });
+
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| pool.checkout())).is_err());
+        assert_eq!(pool.checkout().id, 1);
+    }
+
+    #[test]
+    fn transactions_hold_a_connection_until_they_commit() {
+        let pool = pool(1);
+        let mut pooled = PooledConnection::new(Arc::clone(&pool));
+
+        pooled.begin();
+        pooled.commit();
+        pooled.begin();
+        pooled.commit();
+
+        let connection = pool.checkout();
+        assert_eq!(connection.id, 0);
+        assert_eq!(connection.num_commits, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "transaction already begun")]
+    fn transactions_begin_once() {
+        let mut pooled = PooledConnection::new(pool(2));
+
+        pooled.begin();
+        pooled.begin();
+    }
+}
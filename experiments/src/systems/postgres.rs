@@ -0,0 +1,738 @@
+use crate::benchmarks::tatp::TATPConnection;
+use crate::benchmarks::ycsb::YCSBConnection;
+use crate::benchmarks::{tatp, ycsb};
+use crate::consistency::{self, Consistency};
+use crate::systems::IsolationLevel;
+use crate::Connection;
+use itertools::Itertools;
+use postgres::types::ToSql;
+use postgres::{Client, NoTls, Statement};
+use rand::distributions::Alphanumeric;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::str::FromStr;
+
+/// How transactions on Postgres are isolated. There is no Postgres serializable mode because a
+/// serialization failure aborts the transaction, which the workers have no way to retry.
+#[derive(PartialEq, Clone, Copy)]
+pub enum IsolationMechanism {
+    PostgresReadCommitted,
+    DibsSerializable,
+}
+
+impl FromStr for IsolationMechanism {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "PostgresReadCommitted" => Ok(IsolationMechanism::PostgresReadCommitted),
+            "DibsSerializable" => Ok(IsolationMechanism::DibsSerializable),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Connects to Postgres with a connection string such as `host=localhost user=dibs dbname=tatp`.
+fn connect(params: &str) -> Client {
+    let mut client = Client::connect(params, NoTls).unwrap();
+
+    client
+        .batch_execute("SET SESSION CHARACTERISTICS AS TRANSACTION ISOLATION LEVEL READ COMMITTED;")
+        .unwrap();
+
+    client
+}
+
+struct PostgresBase {
+    client: Client,
+}
+
+impl PostgresBase {
+    /// Sets the isolation level of the connection's transactions. Repeatable read is snapshot
+    /// isolation in Postgres, and read uncommitted behaves as read committed. A transaction that
+    /// fails to serialize panics, since workers only retry transactions that dibs aborts.
+    fn set_isolation_level(&mut self, level: IsolationLevel) {
+        self.client
+            .batch_execute(&format!(
+                "SET SESSION CHARACTERISTICS AS TRANSACTION ISOLATION LEVEL {};",
+                level.sql()
+            ))
+            .unwrap();
+    }
+
+    fn check(&mut self, checks: &[(String, String)]) -> Consistency {
+        Consistency::query(checks, |query| {
+            self.client.query_one(query, &[]).unwrap().get::<_, i64>(0) as usize
+        })
+    }
+}
+
+impl Connection for PostgresBase {
+    fn begin(&mut self) {
+        self.client.batch_execute("BEGIN;").unwrap();
+    }
+
+    fn commit(&mut self) {
+        self.client.batch_execute("COMMIT;").unwrap();
+    }
+
+    fn rollback(&mut self) {
+        self.client
+            .batch_execute("ROLLBACK TO SAVEPOINT x;")
+            .unwrap();
+    }
+
+    fn savepoint(&mut self) {
+        self.client.batch_execute("SAVEPOINT x;").unwrap();
+    }
+}
+
+pub fn load_tatp(params: &str, num_rows: u32, mut rng: StdRng) {
+    assert!(num_rows <= i32::MAX as u32);
+
+    let mut client = Client::connect(params, NoTls).unwrap();
+
+    client
+        .batch_execute(
+            "DROP TABLE IF EXISTS call_forwarding;
+            DROP TABLE IF EXISTS special_facility;
+            DROP TABLE IF EXISTS access_info;
+            DROP TABLE IF EXISTS subscriber;",
+        )
+        .unwrap();
+
+    client
+        .batch_execute(
+            "CREATE TABLE subscriber (s_id INTEGER PRIMARY KEY,
+                    bit_1 BOOLEAN, bit_2 BOOLEAN, bit_3 BOOLEAN, bit_4 BOOLEAN,
+                    bit_5 BOOLEAN, bit_6 BOOLEAN, bit_7 BOOLEAN, bit_8 BOOLEAN,
+                    bit_9 BOOLEAN, bit_10 BOOLEAN,
+                    hex_1 SMALLINT, hex_2 SMALLINT, hex_3 SMALLINT, hex_4 SMALLINT,
+                    hex_5 SMALLINT, hex_6 SMALLINT, hex_7 SMALLINT, hex_8 SMALLINT,
+                    hex_9 SMALLINT, hex_10 SMALLINT,
+                    byte2_1 SMALLINT, byte2_2 SMALLINT, byte2_3 SMALLINT, byte2_4 SMALLINT,
+                    byte2_5 SMALLINT, byte2_6 SMALLINT, byte2_7 SMALLINT, byte2_8 SMALLINT,
+                    byte2_9 SMALLINT, byte2_10 SMALLINT,
+                    msc_location BIGINT, vlr_location BIGINT);
+
+            CREATE TABLE access_info (s_id INTEGER NOT NULL,
+                ai_type SMALLINT NOT NULL,
+                data1 SMALLINT, data2 SMALLINT, data3 TEXT, data4 TEXT,
+                PRIMARY KEY (s_id, ai_type),
+                FOREIGN KEY (s_id) REFERENCES subscriber (s_id));
+
+            CREATE TABLE special_facility (s_id INTEGER NOT NULL,
+                sf_type SMALLINT NOT NULL,
+                is_active BOOLEAN, error_cntrl SMALLINT,
+                data_a SMALLINT, data_b TEXT,
+                PRIMARY KEY (s_id, sf_type),
+                FOREIGN KEY (s_id) REFERENCES subscriber (s_id));
+
+            CREATE TABLE call_forwarding (s_id INTEGER NOT NULL,
+                sf_type SMALLINT NOT NULL,
+                start_time SMALLINT, end_time SMALLINT, numberx TEXT,
+                PRIMARY KEY (s_id, sf_type, start_time),
+                FOREIGN KEY (s_id, sf_type)
+                REFERENCES special_facility (s_id, sf_type));",
+        )
+        .unwrap();
+
+    let mut s_ids = (1..=num_rows).collect::<Vec<_>>();
+    s_ids.shuffle(&mut rng);
+
+    let mut transaction = client.transaction().unwrap();
+
+    // Subscribers are inserted in batches, each followed by the rows that reference them.
+    for s_ids in s_ids.chunks(1000) {
+        transaction
+            .batch_execute(&format!(
+                "INSERT INTO subscriber VALUES {};",
+                s_ids
+                    .iter()
+                    .map(|&s_id| format!(
+                        "({},{},{},{},{},{})",
+                        s_id,
+                        (0..10).map(|_| rng.gen_bool(0.5)).join(","),
+                        (0..10).map(|_| rng.gen_range(0, 16)).join(","),
+                        (0..10).map(|_| rng.gen_range(0, 256)).join(","),
+                        rng.gen::<u32>(),
+                        rng.gen::<u32>(),
+                    ))
+                    .join(",")
+            ))
+            .unwrap();
+
+        transaction
+            .batch_execute(&format!(
+                "INSERT INTO access_info VALUES {};",
+                s_ids
+                    .iter()
+                    .flat_map(|&s_id| {
+                        let num_ai_types = rng.gen_range(1, 5);
+                        [1, 2, 3, 4]
+                            .choose_multiple(&mut rng, num_ai_types)
+                            .map(|&ai_type| {
+                                format!(
+                                    "({},{},{},{},'{}','{}')",
+                                    s_id,
+                                    ai_type,
+                                    rng.gen::<u8>(),
+                                    rng.gen::<u8>(),
+                                    tatp::uppercase_alphabetic_string(3, &mut rng),
+                                    tatp::uppercase_alphabetic_string(5, &mut rng)
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .join(",")
+            ))
+            .unwrap();
+
+        let sf_types = s_ids
+            .iter()
+            .flat_map(|&s_id| {
+                let num_sf_types = rng.gen_range(1, 5);
+                [1, 2, 3, 4]
+                    .choose_multiple(&mut rng, num_sf_types)
+                    .map(move |&sf_type| (s_id, sf_type))
+            })
+            .collect::<Vec<_>>();
+
+        transaction
+            .batch_execute(&format!(
+                "INSERT INTO special_facility VALUES {};",
+                sf_types
+                    .iter()
+                    .map(|&(s_id, sf_type)| {
+                        format!(
+                            "({},{},{},{},{},'{}')",
+                            s_id,
+                            sf_type,
+                            rng.gen_bool(0.85),
+                            rng.gen::<u8>(),
+                            rng.gen::<u8>(),
+                            tatp::uppercase_alphabetic_string(5, &mut rng),
+                        )
+                    })
+                    .join(",")
+            ))
+            .unwrap();
+
+        let call_forwarding = sf_types
+            .iter()
+            .flat_map(|&(s_id, sf_type)| {
+                let num_start_times = rng.gen_range(0, 4);
+                [0, 8, 16]
+                    .choose_multiple(&mut rng, num_start_times)
+                    .map(|&start_time| {
+                        format!(
+                            "({},{},{},{},'{}')",
+                            s_id,
+                            sf_type,
+                            start_time,
+                            start_time + rng.gen_range(1, 9),
+                            tatp::uppercase_alphabetic_string(15, &mut rng)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        if !call_forwarding.is_empty() {
+            transaction
+                .batch_execute(&format!(
+                    "INSERT INTO call_forwarding VALUES {};",
+                    call_forwarding.join(",")
+                ))
+                .unwrap();
+        }
+    }
+
+    transaction.commit().unwrap();
+}
+
+pub struct PostgresTATPConnection {
+    base: PostgresBase,
+    get_subscriber_data_stmt: Statement,
+    get_new_destination_stmt: Statement,
+    get_access_data_stmt: Statement,
+    update_subscriber_bit_stmt: Statement,
+    update_special_facility_data_stmt: Statement,
+    update_subscriber_location_stmt: Statement,
+    get_special_facility_types_stmt: Statement,
+    insert_call_forwarding_stmt: Statement,
+    delete_call_forwarding_stmt: Statement,
+}
+
+impl PostgresTATPConnection {
+    pub fn new(params: &str) -> PostgresTATPConnection {
+        let mut client = connect(params);
+
+        let get_subscriber_data_stmt = client
+            .prepare(
+                "SELECT *
+                FROM subscriber
+                WHERE s_id = $1;",
+            )
+            .unwrap();
+
+        let get_new_destination_stmt = client
+            .prepare(
+                "SELECT cf.numberx
+                FROM special_facility AS sf, call_forwarding AS cf
+                WHERE
+                    (sf.s_id = $1
+                        AND sf.sf_type = $2
+                        AND sf.is_active)
+                    AND (cf.s_id = sf.s_id
+                        AND cf.sf_type = sf.sf_type)
+                    AND (cf.start_time <= $3
+                        AND $4 < cf.end_time);",
+            )
+            .unwrap();
+
+        let get_access_data_stmt = client
+            .prepare(
+                "SELECT data1, data2, data3, data4
+                        FROM access_info
+                        WHERE s_id = $1 AND ai_type = $2;",
+            )
+            .unwrap();
+
+        let update_subscriber_bit_stmt = client
+            .prepare(
+                "UPDATE subscriber
+                        SET bit_1 = $1
+                        WHERE s_id = $2;",
+            )
+            .unwrap();
+
+        let update_special_facility_data_stmt = client
+            .prepare(
+                "UPDATE special_facility
+                        SET data_a = $1
+                        WHERE s_id = $2 AND sf_type = $3;",
+            )
+            .unwrap();
+
+        let update_subscriber_location_stmt = client
+            .prepare(
+                "UPDATE subscriber
+                        SET vlr_location = $1
+                        WHERE s_id = $2;",
+            )
+            .unwrap();
+
+        let get_special_facility_types_stmt = client
+            .prepare(
+                "SELECT sf_type
+                        FROM special_facility
+                        WHERE s_id = $1;",
+            )
+            .unwrap();
+
+        // An error aborts the whole Postgres transaction, so rows that would violate a constraint
+        // are skipped rather than rejected.
+        let insert_call_forwarding_stmt = client
+            .prepare(
+                "INSERT INTO call_forwarding
+                        SELECT $1::INTEGER, $2::SMALLINT, $3::SMALLINT, $4::SMALLINT, $5::TEXT
+                        WHERE EXISTS (
+                            SELECT *
+                            FROM special_facility
+                            WHERE s_id = $1 AND sf_type = $2)
+                        ON CONFLICT DO NOTHING;",
+            )
+            .unwrap();
+
+        let delete_call_forwarding_stmt = client
+            .prepare(
+                "DELETE FROM call_forwarding
+                        WHERE s_id = $1 AND sf_type = $2 AND start_time = $3;",
+            )
+            .unwrap();
+
+        PostgresTATPConnection {
+            base: PostgresBase { client },
+            get_subscriber_data_stmt,
+            get_new_destination_stmt,
+            get_access_data_stmt,
+            update_subscriber_bit_stmt,
+            update_special_facility_data_stmt,
+            update_subscriber_location_stmt,
+            get_special_facility_types_stmt,
+            insert_call_forwarding_stmt,
+            delete_call_forwarding_stmt,
+        }
+    }
+
+    /// Runs transactions at `level` instead of read committed.
+    pub fn with_isolation_level(mut self, level: IsolationLevel) -> PostgresTATPConnection {
+        self.base.set_isolation_level(level);
+        self
+    }
+
+    pub fn check_consistency(&mut self) -> Consistency {
+        self.base.check(&consistency::tatp_checks())
+    }
+}
+
+impl Connection for PostgresTATPConnection {
+    fn begin(&mut self) {
+        self.base.begin();
+    }
+
+    fn commit(&mut self) {
+        self.base.commit();
+    }
+
+    fn rollback(&mut self) {
+        self.base.rollback();
+    }
+
+    fn savepoint(&mut self) {
+        self.base.savepoint();
+    }
+}
+
+impl TATPConnection for PostgresTATPConnection {
+    fn get_subscriber_data(&mut self, s_id: u32) -> ([bool; 10], [u8; 10], [u8; 10], u32, u32) {
+        let row = self
+            .base
+            .client
+            .query_one(&self.get_subscriber_data_stmt, &[&(s_id as i32)])
+            .unwrap();
+
+        let mut bit = [false; 10];
+        for (i, flag) in bit.iter_mut().enumerate() {
+            *flag = row.get(i + 1);
+        }
+
+        let mut hex = [0; 10];
+        for (i, digit) in hex.iter_mut().enumerate() {
+            *digit = row.get::<_, i16>(i + 11) as u8;
+        }
+
+        let mut byte2 = [0; 10];
+        for (i, byte) in byte2.iter_mut().enumerate() {
+            *byte = row.get::<_, i16>(i + 21) as u8;
+        }
+
+        (
+            bit,
+            hex,
+            byte2,
+            row.get::<_, i64>(31) as u32,
+            row.get::<_, i64>(32) as u32,
+        )
+    }
+
+    fn get_new_destination(
+        &mut self,
+        s_id: u32,
+        sf_type: u8,
+        start_time: u8,
+        end_time: u8,
+    ) -> Vec<String> {
+        self.base
+            .client
+            .query(
+                &self.get_new_destination_stmt,
+                &[
+                    &(s_id as i32),
+                    &(sf_type as i16),
+                    &(start_time as i16),
+                    &(end_time as i16),
+                ],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect()
+    }
+
+    fn get_access_data(&mut self, s_id: u32, ai_type: u8) -> Option<(u8, u8, String, String)> {
+        self.base
+            .client
+            .query_opt(
+                &self.get_access_data_stmt,
+                &[&(s_id as i32), &(ai_type as i16)],
+            )
+            .unwrap()
+            .map(|row| {
+                (
+                    row.get::<_, i16>(0) as u8,
+                    row.get::<_, i16>(1) as u8,
+                    row.get(2),
+                    row.get(3),
+                )
+            })
+    }
+
+    fn update_subscriber_bit(&mut self, bit_1: bool, s_id: u32) {
+        self.base
+            .client
+            .execute(&self.update_subscriber_bit_stmt, &[&bit_1, &(s_id as i32)])
+            .unwrap();
+    }
+
+    fn update_special_facility_data(&mut self, data_a: u8, s_id: u32, sf_type: u8) {
+        self.base
+            .client
+            .execute(
+                &self.update_special_facility_data_stmt,
+                &[&(data_a as i16), &(s_id as i32), &(sf_type as i16)],
+            )
+            .unwrap();
+    }
+
+    fn update_subscriber_location(&mut self, vlr_location: u32, s_id: u32) {
+        self.base
+            .client
+            .execute(
+                &self.update_subscriber_location_stmt,
+                &[&(vlr_location as i64), &(s_id as i32)],
+            )
+            .unwrap();
+    }
+
+    fn get_special_facility_types(&mut self, s_id: u32) -> Vec<u8> {
+        self.base
+            .client
+            .query(&self.get_special_facility_types_stmt, &[&(s_id as i32)])
+            .unwrap()
+            .iter()
+            .map(|row| row.get::<_, i16>(0) as u8)
+            .collect()
+    }
+
+    fn insert_call_forwarding(
+        &mut self,
+        s_id: u32,
+        sf_type: u8,
+        start_time: u8,
+        end_time: u8,
+        numberx: &str,
+    ) {
+        self.base
+            .client
+            .execute(
+                &self.insert_call_forwarding_stmt,
+                &[
+                    &(s_id as i32),
+                    &(sf_type as i16),
+                    &(start_time as i16),
+                    &(end_time as i16),
+                    &numberx,
+                ],
+            )
+            .unwrap();
+    }
+
+    fn delete_call_forwarding(&mut self, s_id: u32, sf_type: u8, start_time: u8) {
+        self.base
+            .client
+            .execute(
+                &self.delete_call_forwarding_stmt,
+                &[&(s_id as i32), &(sf_type as i16), &(start_time as i16)],
+            )
+            .unwrap();
+    }
+}
+
+pub fn load_ycsb(params: &str, num_rows: u32, field_size: usize, mut rng: StdRng) {
+    assert!(num_rows > 0);
+    assert_eq!(num_rows % 1000, 0);
+    assert!(num_rows <= i32::MAX as u32);
+
+    let mut client = Client::connect(params, NoTls).unwrap();
+
+    client.batch_execute("DROP TABLE IF EXISTS users;").unwrap();
+
+    client
+        .batch_execute(&format!(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, {});",
+            (0..ycsb::NUM_FIELDS)
+                .map(|field| format!("field_{} TEXT", field))
+                .join(",")
+        ))
+        .unwrap();
+
+    let mut ids = (0..num_rows).collect::<Vec<_>>();
+    ids.shuffle(&mut rng);
+
+    let mut transaction = client.transaction().unwrap();
+
+    for i in 0..num_rows as usize / 1000 {
+        transaction
+            .batch_execute(&format!(
+                "INSERT INTO users VALUES {};",
+                ids.iter()
+                    .skip(i * 1000)
+                    .take(1000)
+                    .map(|&id| format!(
+                        "({},{})",
+                        id,
+                        (0..ycsb::NUM_FIELDS)
+                            .map(|_| format!(
+                                "'{}'",
+                                (&mut rng)
+                                    .sample_iter(&Alphanumeric)
+                                    .take(field_size)
+                                    .collect::<String>()
+                            ))
+                            .join(",")
+                    ))
+                    .join(",")
+            ))
+            .unwrap();
+    }
+
+    transaction.commit().unwrap();
+}
+
+pub struct PostgresYCSBConnection {
+    base: PostgresBase,
+    select_user_stmts: Vec<Statement>,
+    update_user_stmts: Vec<Statement>,
+    insert_user_stmt: Statement,
+    delete_user_stmt: Statement,
+    scan_users_stmts: Vec<Statement>,
+}
+
+impl PostgresYCSBConnection {
+    pub fn new(params: &str) -> PostgresYCSBConnection {
+        let mut client = connect(params);
+
+        let select_user_stmts = (0..ycsb::NUM_FIELDS)
+            .map(|field| {
+                client
+                    .prepare(&format!("SELECT field_{} FROM users WHERE id = $1;", field))
+                    .unwrap()
+            })
+            .collect();
+
+        let update_user_stmts = (0..ycsb::NUM_FIELDS)
+            .map(|field| {
+                client
+                    .prepare(&format!(
+                        "UPDATE users SET field_{} = $1 WHERE id = $2;",
+                        field
+                    ))
+                    .unwrap()
+            })
+            .collect();
+
+        let insert_user_stmt = client
+            .prepare(&format!(
+                "INSERT INTO users VALUES ({});",
+                (1..=ycsb::NUM_FIELDS + 1)
+                    .map(|i| format!("${}", i))
+                    .join(",")
+            ))
+            .unwrap();
+
+        let delete_user_stmt = client.prepare("DELETE FROM users WHERE id = $1;").unwrap();
+
+        let scan_users_stmts = (0..ycsb::NUM_FIELDS)
+            .map(|field| {
+                client
+                    .prepare(&format!(
+                        "SELECT field_{} FROM users WHERE id >= $1 AND id < $2;",
+                        field
+                    ))
+                    .unwrap()
+            })
+            .collect();
+
+        PostgresYCSBConnection {
+            base: PostgresBase { client },
+            select_user_stmts,
+            update_user_stmts,
+            insert_user_stmt,
+            delete_user_stmt,
+            scan_users_stmts,
+        }
+    }
+
+    /// Runs transactions at `level` instead of read committed.
+    pub fn with_isolation_level(mut self, level: IsolationLevel) -> PostgresYCSBConnection {
+        self.base.set_isolation_level(level);
+        self
+    }
+
+    pub fn check_consistency(&mut self, field_size: usize) -> Consistency {
+        self.base.check(&consistency::ycsb_checks(field_size))
+    }
+}
+
+impl Connection for PostgresYCSBConnection {
+    fn begin(&mut self) {
+        self.base.begin();
+    }
+
+    fn commit(&mut self) {
+        self.base.commit();
+    }
+
+    fn rollback(&mut self) {
+        self.base.rollback();
+    }
+
+    fn savepoint(&mut self) {
+        self.base.savepoint();
+    }
+}
+
+impl YCSBConnection for PostgresYCSBConnection {
+    fn select_user(&mut self, field: usize, user_id: u32) -> String {
+        self.base
+            .client
+            .query_opt(&self.select_user_stmts[field], &[&(user_id as i32)])
+            .unwrap()
+            .map(|row| row.get(0))
+            .unwrap_or_default()
+    }
+
+    fn update_user(&mut self, field: usize, data: &str, user_id: u32) {
+        self.base
+            .client
+            .execute(&self.update_user_stmts[field], &[&data, &(user_id as i32)])
+            .unwrap();
+    }
+
+    fn insert_user(&mut self, user_id: u32, fields: &[String]) {
+        let user_id = user_id as i32;
+
+        self.base
+            .client
+            .execute(
+                &self.insert_user_stmt,
+                &std::iter::once(&user_id as &(dyn ToSql + Sync))
+                    .chain(fields.iter().map(|field| field as &(dyn ToSql + Sync)))
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap();
+    }
+
+    fn delete_user(&mut self, user_id: u32) {
+        self.base
+            .client
+            .execute(&self.delete_user_stmt, &[&(user_id as i32)])
+            .unwrap();
+    }
+
+    fn scan_users(&mut self, field: usize, start_user_id: u32, end_user_id: u32) -> Vec<String> {
+        self.base
+            .client
+            .query(
+                &self.scan_users_stmts[field],
+                &[&(start_user_id as i32), &(end_user_id as i32)],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect()
+    }
+}
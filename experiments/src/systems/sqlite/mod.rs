@@ -1,74 +1,153 @@
 use crate::benchmarks::tatp::TATPConnection;
 use crate::benchmarks::ycsb::YCSBConnection;
 use crate::benchmarks::{tatp, ycsb};
+use crate::consistency::{self, Consistency};
+use crate::systems::IsolationLevel;
 use crate::Connection;
 use itertools::Itertools;
 use rand::distributions::Alphanumeric;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
 use rand::Rng;
-use rusqlite::{params, ErrorCode, Statement};
-use std::path::Path;
+use rusqlite::{params, CachedStatement, ErrorCode, OpenFlags, ToSql};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::Duration;
 
-struct SQLiteBaseStatements<'a> {
-    begin_stmt: Statement<'a>,
-    commit_stmt: Statement<'a>,
-    rollback_stmt: Statement<'a>,
-    savepoint_stmt: Statement<'a>,
+/// Statements beyond the benchmarks' own, with room to spare.
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+/// How transactions on SQLite are isolated. In both, a single worker runs every update, grouping
+/// them under dibs, since SQLite allows only one writer at a time.
+#[derive(PartialEq, Clone, Copy)]
+pub enum IsolationMechanism {
+    /// Readers run without dibs, reading from SQLite's snapshots.
+    SQLiteSnapshot,
+
+    /// Readers acquire their requests from dibs too, and SQLite runs read uncommitted, so that
+    /// dibs alone isolates readers from the writer.
+    DibsSerializable,
 }
 
-impl<'a> SQLiteBaseStatements<'a> {
-    fn new(conn: *mut rusqlite::Connection) -> SQLiteBaseStatements<'a> {
-        let begin_stmt = unsafe { conn.as_ref() }.unwrap().prepare("BEGIN;").unwrap();
+impl IsolationMechanism {
+    /// Returns the isolation level that SQLite runs transactions at under this mechanism.
+    pub fn isolation_level(&self) -> IsolationLevel {
+        match self {
+            IsolationMechanism::SQLiteSnapshot => IsolationLevel::Snapshot,
+            IsolationMechanism::DibsSerializable => IsolationLevel::ReadUncommitted,
+        }
+    }
+}
 
-        let commit_stmt = unsafe { conn.as_ref() }
-            .unwrap()
-            .prepare("COMMIT;")
-            .unwrap();
+impl FromStr for IsolationMechanism {
+    type Err = ();
 
-        let rollback_stmt = unsafe { conn.as_ref() }
-            .unwrap()
-            .prepare("ROLLBACK TO 'X';")
-            .unwrap();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "SQLiteSnapshot" => Ok(IsolationMechanism::SQLiteSnapshot),
+            "DibsSerializable" => Ok(IsolationMechanism::DibsSerializable),
+            _ => Err(()),
+        }
+    }
+}
 
-        let savepoint_stmt = unsafe { conn.as_ref() }
-            .unwrap()
-            .prepare("SAVEPOINT 'X';")
-            .unwrap();
+fn open(path: &Path, flags: OpenFlags) -> rusqlite::Connection {
+    let conn = rusqlite::Connection::open_with_flags(path, flags).unwrap();
+
+    conn.busy_timeout(Duration::from_secs(10)).unwrap();
+    conn.pragma_update(None, "cache_size", &"-8388608").unwrap();
+    conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+
+    conn
+}
+
+/// A connection to SQLite that prepares each statement the first time it runs and caches it by its
+/// SQL, so that prepared statements live inside the connection rather than borrowing it.
+struct SQLiteBase {
+    conn: rusqlite::Connection,
+    path: PathBuf,
+    begin_sql: &'static str,
+}
 
-        SQLiteBaseStatements {
-            begin_stmt,
-            commit_stmt,
-            rollback_stmt,
-            savepoint_stmt,
+impl SQLiteBase {
+    fn new<P>(path: P) -> SQLiteBase
+    where
+        P: AsRef<Path>,
+    {
+        SQLiteBase {
+            conn: open(path.as_ref(), OpenFlags::default()),
+            path: path.as_ref().to_path_buf(),
+            begin_sql: "BEGIN;",
         }
     }
+
+    /// Sets how transactions begin. SQLite serializes its writers, so the levels differ in when a
+    /// transaction takes the write lock and what its reads wait for. Snapshot transactions read
+    /// from a snapshot of the write-ahead log and take the lock on their first write, while
+    /// serializable transactions take it as they begin, so that even readers run one at a time.
+    /// Read uncommitted reopens the connection in shared-cache mode, where readers take no locks
+    /// and see the writer's changes as it makes them. SQLite has no read committed level.
+    fn set_isolation_level(&mut self, level: IsolationLevel) {
+        self.begin_sql = match level {
+            IsolationLevel::ReadUncommitted => {
+                self.conn = open(
+                    &self.path,
+                    OpenFlags::default() | OpenFlags::SQLITE_OPEN_SHARED_CACHE,
+                );
+
+                self.conn
+                    .pragma_update(None, "read_uncommitted", &true)
+                    .unwrap();
+                "BEGIN DEFERRED;"
+            }
+            IsolationLevel::ReadCommitted => {
+                panic!("SQLite doesn't support {:?} isolation", level)
+            }
+            IsolationLevel::Snapshot => {
+                self.conn
+                    .pragma_update(None, "journal_mode", &"WAL")
+                    .unwrap();
+                "BEGIN DEFERRED;"
+            }
+            IsolationLevel::Serializable => "BEGIN IMMEDIATE;",
+        };
+    }
+
+    fn prepare(&self, sql: &str) -> CachedStatement<'_> {
+        self.conn.prepare_cached(sql).unwrap()
+    }
+
+    fn check(&self, checks: &[(String, String)]) -> Consistency {
+        Consistency::query(checks, |query| {
+            self.conn
+                .query_row(query, params![], |row| row.get::<_, i64>(0))
+                .unwrap() as usize
+        })
+    }
 }
 
-impl Connection for SQLiteBaseStatements<'_> {
+impl Connection for SQLiteBase {
     fn begin(&mut self) {
-        self.begin_stmt.execute(params![]).unwrap();
+        self.prepare(self.begin_sql).execute(params![]).unwrap();
     }
 
     fn commit(&mut self) {
-        self.commit_stmt.execute(params![]).unwrap();
+        self.prepare("COMMIT;").execute(params![]).unwrap();
     }
 
     fn rollback(&mut self) {
-        self.rollback_stmt.execute(params![]).unwrap();
+        self.prepare("ROLLBACK TO 'X';").execute(params![]).unwrap();
     }
 
     fn savepoint(&mut self) {
-        self.savepoint_stmt.execute(params![]).unwrap();
+        self.prepare("SAVEPOINT 'X';").execute(params![]).unwrap();
     }
 }
 
-pub fn load_tatp<P>(path: P, num_rows: u32)
+pub fn load_tatp<P>(path: P, num_rows: u32, mut rng: StdRng)
 where
     P: AsRef<Path>,
 {
-    let mut rng = rand::thread_rng();
-
     let conn = rusqlite::Connection::open(path).unwrap();
 
     conn.pragma_update(None, "journal_mode", &"WAL").unwrap();
@@ -163,7 +242,7 @@ where
                     let num_ai_types = rng.gen_range(1, 5);
                     [1, 2, 3, 4]
                         .choose_multiple(&mut rng, num_ai_types)
-                        .map(move |&ai_type| {
+                        .map(|&ai_type| {
                             format!(
                                 "({},{},{},{},'{}','{}')",
                                 s_id,
@@ -174,6 +253,7 @@ where
                                 tatp::uppercase_alphabetic_string(5, &mut rng)
                             )
                         })
+                        .collect::<Vec<_>>()
                 })
                 .join(",")
         ),
@@ -222,7 +302,7 @@ where
                     let num_start_times = rng.gen_range(0, 4);
                     [0, 8, 16]
                         .choose_multiple(&mut rng, num_start_times)
-                        .map(move |&start_time| {
+                        .map(|&start_time| {
                             format!(
                                 "({},{},{},{},'{}')",
                                 s_id,
@@ -232,6 +312,7 @@ where
                                 tatp::uppercase_alphabetic_string(15, &mut rng)
                             )
                         })
+                        .collect::<Vec<_>>()
                 })
                 .join(",")
         ),
@@ -240,142 +321,32 @@ where
     .unwrap();
 }
 
-pub struct SQLiteTATPConnection<'a> {
-    base: SQLiteBaseStatements<'a>,
-    get_subscriber_data_stmt: Statement<'a>,
-    get_new_destination_stmt: Statement<'a>,
-    get_access_data_stmt: Statement<'a>,
-    update_subscriber_bit_stmt: Statement<'a>,
-    update_special_facility_data_stmt: Statement<'a>,
-    update_subscriber_location_stmt: Statement<'a>,
-    get_special_facility_types_stmt: Statement<'a>,
-    insert_call_forwarding_stmt: Statement<'a>,
-    delete_call_forwarding_stmt: Statement<'a>,
-    _conn: Box<rusqlite::Connection>,
+pub struct SQLiteTATPConnection {
+    base: SQLiteBase,
 }
 
-impl<'a> SQLiteTATPConnection<'a> {
-    pub fn new<P>(path: P) -> SQLiteTATPConnection<'a>
+impl SQLiteTATPConnection {
+    pub fn new<P>(path: P) -> SQLiteTATPConnection
     where
         P: AsRef<Path>,
     {
-        let conn = Box::into_raw(Box::new(rusqlite::Connection::open(path).unwrap()));
-
-        unsafe { conn.as_ref() }
-            .unwrap()
-            .busy_timeout(Duration::from_secs(10))
-            .unwrap();
-
-        unsafe { conn.as_ref() }
-            .unwrap()
-            .pragma_update(None, "cache_size", &"-8388608")
-            .unwrap();
-
-        let base = SQLiteBaseStatements::new(conn);
-
-        let get_subscriber_data_stmt = unsafe { conn.as_ref() }
-            .unwrap()
-            .prepare(
-                "SELECT *
-                FROM subscriber
-                WHERE s_id = ?;",
-            )
-            .unwrap();
-
-        let get_new_destination_stmt = unsafe { conn.as_ref() }
-            .unwrap()
-            .prepare(
-                "SELECT cf.numberx
-                FROM special_facility AS sf, call_forwarding AS cf
-                WHERE
-                    (sf.s_id = ?
-                        AND sf.sf_type = ?
-                        AND sf.is_active = 1)
-                    AND (cf.s_id = sf.s_id
-                        AND cf.sf_type = sf.sf_type)
-                    AND (cf.start_time <= ?
-                        AND ? < cf.end_time);",
-            )
-            .unwrap();
-
-        let get_access_data_stmt = unsafe { conn.as_ref() }
-            .unwrap()
-            .prepare(
-                "SELECT data1, data2, data3, data4
-                        FROM access_info
-                        WHERE s_id = ? AND ai_type = ?;",
-            )
-            .unwrap();
-
-        let update_subscriber_bit_stmt = unsafe { conn.as_ref() }
-            .unwrap()
-            .prepare(
-                "UPDATE subscriber
-                        SET bit_1 = ?
-                        WHERE s_id = ?;",
-            )
-            .unwrap();
-
-        let update_special_facility_data_stmt = unsafe { conn.as_ref() }
-            .unwrap()
-            .prepare(
-                "UPDATE special_facility
-                        SET data_a = ?
-                        WHERE s_id = ? AND sf_type = ?;",
-            )
-            .unwrap();
-
-        let update_subscriber_location_stmt = unsafe { conn.as_ref() }
-            .unwrap()
-            .prepare(
-                "UPDATE subscriber
-                        SET vlr_location = ?
-                        WHERE s_id = ?;",
-            )
-            .unwrap();
-
-        let get_special_facility_types_stmt = unsafe { conn.as_ref() }
-            .unwrap()
-            .prepare(
-                "SELECT sf_type
-                        FROM special_facility
-                        WHERE s_id = ?;",
-            )
-            .unwrap();
-
-        let insert_call_forwarding_stmt = unsafe { conn.as_ref() }
-            .unwrap()
-            .prepare(
-                "INSERT INTO call_forwarding
-                        VALUES (?, ?, ?, ?, ?);",
-            )
-            .unwrap();
-
-        let delete_call_forwarding_stmt = unsafe { conn.as_ref() }
-            .unwrap()
-            .prepare(
-                "DELETE FROM call_forwarding
-                        WHERE s_id = ? AND sf_type = ? AND start_time = ?;",
-            )
-            .unwrap();
-
         SQLiteTATPConnection {
-            base,
-            get_subscriber_data_stmt,
-            get_new_destination_stmt,
-            get_access_data_stmt,
-            update_subscriber_bit_stmt,
-            update_special_facility_data_stmt,
-            update_subscriber_location_stmt,
-            get_special_facility_types_stmt,
-            insert_call_forwarding_stmt,
-            delete_call_forwarding_stmt,
-            _conn: unsafe { Box::from_raw(conn) },
+            base: SQLiteBase::new(path),
         }
     }
+
+    /// Runs transactions at `level` instead of snapshot isolation.
+    pub fn with_isolation_level(mut self, level: IsolationLevel) -> SQLiteTATPConnection {
+        self.base.set_isolation_level(level);
+        self
+    }
+
+    pub fn check_consistency(&mut self) -> Consistency {
+        self.base.check(&consistency::tatp_checks())
+    }
 }
 
-impl Connection for SQLiteTATPConnection<'_> {
+impl Connection for SQLiteTATPConnection {
     fn begin(&mut self) {
         self.base.begin();
     }
@@ -393,9 +364,15 @@ impl Connection for SQLiteTATPConnection<'_> {
     }
 }
 
-impl TATPConnection for SQLiteTATPConnection<'_> {
+impl TATPConnection for SQLiteTATPConnection {
     fn get_subscriber_data(&mut self, s_id: u32) -> ([bool; 10], [u8; 10], [u8; 10], u32, u32) {
-        let mut rows = self.get_subscriber_data_stmt.query(&[s_id]).unwrap();
+        let mut stmt = self.base.prepare(
+            "SELECT *
+            FROM subscriber
+            WHERE s_id = ?;",
+        );
+
+        let mut rows = stmt.query([s_id]).unwrap();
         let row = rows.next().unwrap().unwrap();
 
         let mut bit = [false; 10];
@@ -425,8 +402,20 @@ impl TATPConnection for SQLiteTATPConnection<'_> {
     ) -> Vec<String> {
         let mut numberx = vec![];
 
-        let mut rows = self
-            .get_new_destination_stmt
+        let mut stmt = self.base.prepare(
+            "SELECT cf.numberx
+            FROM special_facility AS sf, call_forwarding AS cf
+            WHERE
+                (sf.s_id = ?
+                    AND sf.sf_type = ?
+                    AND sf.is_active = 1)
+                AND (cf.s_id = sf.s_id
+                    AND cf.sf_type = sf.sf_type)
+                AND (cf.start_time <= ?
+                    AND ? < cf.end_time);",
+        );
+
+        let mut rows = stmt
             .query(params![s_id, sf_type, start_time, end_time])
             .unwrap();
 
@@ -438,10 +427,13 @@ impl TATPConnection for SQLiteTATPConnection<'_> {
     }
 
     fn get_access_data(&mut self, s_id: u32, ai_type: u8) -> Option<(u8, u8, String, String)> {
-        let mut rows = self
-            .get_access_data_stmt
-            .query(params![s_id, ai_type])
-            .unwrap();
+        let mut stmt = self.base.prepare(
+            "SELECT data1, data2, data3, data4
+            FROM access_info
+            WHERE s_id = ? AND ai_type = ?;",
+        );
+
+        let mut rows = stmt.query(params![s_id, ai_type]).unwrap();
 
         rows.next().unwrap().map(|row| {
             (
@@ -454,19 +446,34 @@ impl TATPConnection for SQLiteTATPConnection<'_> {
     }
 
     fn update_subscriber_bit(&mut self, bit_1: bool, s_id: u32) {
-        self.update_subscriber_bit_stmt
+        self.base
+            .prepare(
+                "UPDATE subscriber
+                SET bit_1 = ?
+                WHERE s_id = ?;",
+            )
             .execute(params![bit_1, s_id])
             .unwrap();
     }
 
     fn update_special_facility_data(&mut self, data_a: u8, s_id: u32, sf_type: u8) {
-        self.update_special_facility_data_stmt
+        self.base
+            .prepare(
+                "UPDATE special_facility
+                SET data_a = ?
+                WHERE s_id = ? AND sf_type = ?;",
+            )
             .execute(params![data_a, s_id, sf_type])
             .unwrap();
     }
 
     fn update_subscriber_location(&mut self, vlr_location: u32, s_id: u32) {
-        self.update_subscriber_location_stmt
+        self.base
+            .prepare(
+                "UPDATE subscriber
+                SET vlr_location = ?
+                WHERE s_id = ?;",
+            )
             .execute(params![vlr_location, s_id])
             .unwrap();
     }
@@ -474,7 +481,13 @@ impl TATPConnection for SQLiteTATPConnection<'_> {
     fn get_special_facility_types(&mut self, s_id: u32) -> Vec<u8> {
         let mut sf_type = vec![];
 
-        let mut rows = self.get_special_facility_types_stmt.query(&[s_id]).unwrap();
+        let mut stmt = self.base.prepare(
+            "SELECT sf_type
+            FROM special_facility
+            WHERE s_id = ?;",
+        );
+
+        let mut rows = stmt.query([s_id]).unwrap();
 
         while let Some(row) = rows.next().unwrap() {
             sf_type.push(row.get(0).unwrap());
@@ -492,7 +505,11 @@ impl TATPConnection for SQLiteTATPConnection<'_> {
         numberx: &str,
     ) {
         if let Err(error) = self
-            .insert_call_forwarding_stmt
+            .base
+            .prepare(
+                "INSERT INTO call_forwarding
+                VALUES (?, ?, ?, ?, ?);",
+            )
             .execute(params![s_id, sf_type, start_time, end_time, numberx])
         {
             match &error {
@@ -507,15 +524,17 @@ impl TATPConnection for SQLiteTATPConnection<'_> {
     }
 
     fn delete_call_forwarding(&mut self, s_id: u32, sf_type: u8, start_time: u8) {
-        self.delete_call_forwarding_stmt
+        self.base
+            .prepare(
+                "DELETE FROM call_forwarding
+                WHERE s_id = ? AND sf_type = ? AND start_time = ?;",
+            )
             .execute(params![s_id, sf_type, start_time])
             .unwrap();
     }
 }
 
-unsafe impl Send for SQLiteTATPConnection<'_> {}
-
-pub fn load_ycsb<P>(path: P, num_rows: u32, field_size: usize)
+pub fn load_ycsb<P>(path: P, num_rows: u32, field_size: usize, mut rng: StdRng)
 where
     P: AsRef<Path>,
 {
@@ -523,8 +542,6 @@ where
     assert_eq!(num_rows % 1000, 0);
     assert!(field_size > 0 && field_size <= i32::max_value() as usize);
 
-    let mut rng = rand::thread_rng();
-
     let conn = rusqlite::Connection::open(path).unwrap();
 
     conn.pragma_update(None, "journal_mode", &"WAL").unwrap();
@@ -560,7 +577,8 @@ where
                         (0..ycsb::NUM_FIELDS)
                             .map(|_| format!(
                                 "'{}'",
-                                rng.sample_iter(&Alphanumeric)
+                                (&mut rng)
+                                    .sample_iter(&Alphanumeric)
                                     .take(field_size)
                                     .collect::<String>()
                             ))
@@ -574,63 +592,56 @@ where
     }
 }
 
-pub struct SQLiteYCSBConnection<'a> {
-    base: SQLiteBaseStatements<'a>,
-    select_user_stmts: Vec<Statement<'a>>,
-    update_user_stmts: Vec<Statement<'a>>,
-    _conn: Box<rusqlite::Connection>,
+pub struct SQLiteYCSBConnection {
+    base: SQLiteBase,
+    select_user_sql: Vec<String>,
+    update_user_sql: Vec<String>,
+    insert_user_sql: String,
+    delete_user_sql: String,
+    scan_users_sql: Vec<String>,
 }
 
-impl<'a> SQLiteYCSBConnection<'a> {
-    pub fn new<P>(path: P) -> SQLiteYCSBConnection<'a>
+impl SQLiteYCSBConnection {
+    pub fn new<P>(path: P) -> SQLiteYCSBConnection
     where
         P: AsRef<Path>,
     {
-        let conn = Box::into_raw(Box::new(rusqlite::Connection::open(path).unwrap()));
-
-        unsafe { conn.as_ref() }
-            .unwrap()
-            .busy_timeout(Duration::from_secs(10))
-            .unwrap();
-
-        unsafe { conn.as_ref() }
-            .unwrap()
-            .pragma_update(None, "cache_size", &"-8388608")
-            .unwrap();
-
-        let base = SQLiteBaseStatements::new(conn);
-
-        let select_user_stmts = (0..ycsb::NUM_FIELDS)
-            .map(|field| {
-                unsafe { conn.as_ref() }
-                    .unwrap()
-                    .prepare(&format!("SELECT field_{} FROM users WHERE id = ?;", field))
-                    .unwrap()
-            })
-            .collect();
-
-        let update_user_stmts = (0..ycsb::NUM_FIELDS)
-            .map(|field| {
-                unsafe { conn.as_ref() }
-                    .unwrap()
-                    .prepare(&format!(
-                        "UPDATE users SET field_{} = ? WHERE id = ?;",
-                        field
-                    ))
-                    .unwrap()
-            })
-            .collect();
-
         SQLiteYCSBConnection {
-            base,
-            select_user_stmts,
-            update_user_stmts,
-            _conn: unsafe { Box::from_raw(conn) },
+            base: SQLiteBase::new(path),
+            select_user_sql: (0..ycsb::NUM_FIELDS)
+                .map(|field| format!("SELECT field_{} FROM users WHERE id = ?;", field))
+                .collect(),
+            update_user_sql: (0..ycsb::NUM_FIELDS)
+                .map(|field| format!("UPDATE users SET field_{} = ? WHERE id = ?;", field))
+                .collect(),
+            insert_user_sql: format!(
+                "INSERT INTO users VALUES (?{});",
+                ",?".repeat(ycsb::NUM_FIELDS)
+            ),
+            delete_user_sql: "DELETE FROM users WHERE id = ?;".to_string(),
+            scan_users_sql: (0..ycsb::NUM_FIELDS)
+                .map(|field| {
+                    format!(
+                        "SELECT field_{} FROM users WHERE id >= ? AND id < ?;",
+                        field
+                    )
+                })
+                .collect(),
         }
     }
+
+    /// Runs transactions at `level` instead of snapshot isolation.
+    pub fn with_isolation_level(mut self, level: IsolationLevel) -> SQLiteYCSBConnection {
+        self.base.set_isolation_level(level);
+        self
+    }
+
+    pub fn check_consistency(&mut self, field_size: usize) -> Consistency {
+        self.base.check(&consistency::ycsb_checks(field_size))
+    }
 }
 
-impl Connection for SQLiteYCSBConnection<'_> {
+impl Connection for SQLiteYCSBConnection {
     fn begin(&mut self) {
         self.base.begin();
     }
@@ -648,23 +659,48 @@ impl Connection for SQLiteYCSBConnection<'_> {
     }
 }
 
-impl YCSBConnection for SQLiteYCSBConnection<'_> {
+impl YCSBConnection for SQLiteYCSBConnection {
     fn select_user(&mut self, field: usize, user_id: u32) -> String {
-        self.select_user_stmts[field]
+        self.base
+            .prepare(&self.select_user_sql[field])
             .query(&[user_id])
             .unwrap()
             .next()
             .unwrap()
-            .unwrap()
-            .get(0)
-            .unwrap()
+            .map(|row| row.get(0).unwrap())
+            .unwrap_or_default()
     }
 
     fn update_user(&mut self, field: usize, data: &str, user_id: u32) {
-        self.update_user_stmts[field]
+        self.base
+            .prepare(&self.update_user_sql[field])
             .execute(params![data, user_id])
             .unwrap();
     }
-}
 
-unsafe impl Send for SQLiteYCSBConnection<'_> {}
+    fn insert_user(&mut self, user_id: u32, fields: &[String]) {
+        self.base
+            .prepare(&self.insert_user_sql)
+            .execute(
+                std::iter::once(&user_id as &dyn ToSql)
+                    .chain(fields.iter().map(|field| field as &dyn ToSql)),
+            )
+            .unwrap();
+    }
+
+    fn delete_user(&mut self, user_id: u32) {
+        self.base
+            .prepare(&self.delete_user_sql)
+            .execute(params![user_id])
+            .unwrap();
+    }
+
+    fn scan_users(&mut self, field: usize, start_user_id: u32, end_user_id: u32) -> Vec<String> {
+        self.base
+            .prepare(&self.scan_users_sql[field])
+            .query_map(params![start_user_id, end_user_id], |row| row.get(0))
+            .unwrap()
+            .map(|value| value.unwrap())
+            .collect()
+    }
+}
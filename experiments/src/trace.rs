@@ -0,0 +1,202 @@
+//! Records the procedures that each worker's generator produces, so that a later run can replay the
+//! exact same inputs, e.g. to compare optimization levels without the noise of fresh random draws.
+//!
+//! A trace is a sequence of frames, each holding a worker's id and one procedure in the encoding of
+//! `server::Request`. Replaying gives each worker the procedures recorded for it, in order.
+
+use crate::codec::{self, Decoder};
+use crate::server::Request;
+use crate::Generator;
+use clap::{Arg, ArgMatches};
+use rand::rngs::StdRng;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Appends frames to a trace file. Workers share one writer, so recording serializes generation.
+pub struct Recorder {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> std::io::Result<Recorder> {
+        Ok(Recorder {
+            writer: Mutex::new(BufWriter::new(File::create(path)?)),
+        })
+    }
+
+    fn record<R: Request>(&self, worker_id: usize, procedure: &R) {
+        let mut encoded = vec![];
+        procedure.encode(&mut encoded);
+
+        let mut frame = Vec::with_capacity(encoded.len() + 8);
+        codec::put_u32(&mut frame, worker_id as u32);
+        codec::put_bytes(&mut frame, &encoded);
+
+        self.writer.lock().unwrap().write_all(&frame).unwrap();
+    }
+
+    pub fn flush(&self) -> std::io::Result<()> {
+        self.writer.lock().unwrap().flush()
+    }
+}
+
+/// A trace loaded into memory, with each worker's procedures still encoded.
+pub struct Replay {
+    procedures: Vec<Vec<Vec<u8>>>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> std::io::Result<Replay> {
+        let buf = std::fs::read(path)?;
+        let mut decoder = Decoder::new(&buf);
+        let mut procedures: Vec<Vec<Vec<u8>>> = vec![];
+
+        while !decoder.is_empty() {
+            let (worker_id, procedure) = decoder
+                .u32()
+                .and_then(|worker_id| Some((worker_id as usize, decoder.bytes()?)))
+                .ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, "truncated trace")
+                })?;
+
+            if worker_id >= procedures.len() {
+                procedures.resize_with(worker_id + 1, Vec::new);
+            }
+
+            procedures[worker_id].push(procedure.to_vec());
+        }
+
+        // Workers that never started a procedure leave no frames, and replay skips over them.
+        procedures.retain(|procedures| !procedures.is_empty());
+
+        if procedures.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "empty trace",
+            ));
+        }
+
+        Ok(Replay { procedures })
+    }
+
+    /// Returns the procedures recorded for `worker_id`. A run with more workers than the recorded
+    /// one reuses the recorded workers' procedures round-robin.
+    fn worker(&self, worker_id: usize) -> &[Vec<u8>] {
+        &self.procedures[worker_id % self.procedures.len()]
+    }
+}
+
+/// Whether a run records its procedures, replays recorded ones, or just generates them.
+#[derive(Clone)]
+pub enum Trace {
+    Live,
+    Record(Arc<Recorder>),
+    Replay(Arc<Replay>),
+}
+
+impl Trace {
+    /// Returns the `--record-trace` and `--replay-trace` flags.
+    pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![
+            Arg::with_name("record_trace")
+                .long("record-trace")
+                .takes_value(true)
+                .help("Records each worker's procedures to this file"),
+            Arg::with_name("replay_trace")
+                .long("replay-trace")
+                .takes_value(true)
+                .conflicts_with("record_trace")
+                .help("Replays the procedures recorded in this file instead of generating them"),
+        ]
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> Trace {
+        if let Some(path) = matches.value_of("record_trace") {
+            Trace::Record(Arc::new(Recorder::create(Path::new(path)).unwrap()))
+        } else if let Some(path) = matches.value_of("replay_trace") {
+            Trace::Replay(Arc::new(Replay::load(Path::new(path)).unwrap()))
+        } else {
+            Trace::Live
+        }
+    }
+
+    /// Wraps the generator of the worker with id `worker_id`.
+    pub fn wrap<G>(&self, worker_id: usize, inner: G) -> TracedGenerator<G>
+    where
+        G: Generator,
+        G::Item: Request,
+    {
+        TracedGenerator {
+            inner,
+            worker_id,
+            trace: self.clone(),
+            next_procedure: AtomicUsize::new(0),
+            wrapped: AtomicBool::new(false),
+        }
+    }
+
+    /// Flushes the trace being recorded, if any.
+    pub fn finish(&self) {
+        if let Trace::Record(recorder) = self {
+            recorder.flush().unwrap();
+        }
+    }
+
+    /// Returns a parameter describing the trace for `runner::run_with_parameters`.
+    pub fn parameter(&self) -> (&'static str, String) {
+        let value = match self {
+            Trace::Live => "live",
+            Trace::Record(_) => "record",
+            Trace::Replay(_) => "replay",
+        };
+
+        ("trace", value.to_string())
+    }
+}
+
+/// A generator that records the procedures of `inner`, or replays recorded ones in their place.
+pub struct TracedGenerator<G> {
+    inner: G,
+    worker_id: usize,
+    trace: Trace,
+    next_procedure: AtomicUsize,
+    wrapped: AtomicBool,
+}
+
+impl<G> Generator for TracedGenerator<G>
+where
+    G: Generator,
+    G::Item: Request,
+{
+    type Item = G::Item;
+
+    fn next(&self, rng: &mut StdRng) -> G::Item {
+        match &self.trace {
+            Trace::Live => self.inner.next(rng),
+            Trace::Record(recorder) => {
+                let procedure = self.inner.next(rng);
+                recorder.record(self.worker_id, &procedure);
+                procedure
+            }
+            Trace::Replay(replay) => {
+                let procedures = replay.worker(self.worker_id);
+                let i = self.next_procedure.fetch_add(1, Ordering::Relaxed);
+
+                // Running longer than the recording starts the trace over, which repeats inputs.
+                if i >= procedures.len() && !self.wrapped.swap(true, Ordering::Relaxed) {
+                    eprintln!(
+                        "Worker {} exhausted its trace of {} procedures and is replaying it again",
+                        self.worker_id,
+                        procedures.len()
+                    );
+                }
+
+                G::Item::decode(&mut Decoder::new(&procedures[i % procedures.len()]))
+                    .expect("corrupt trace")
+            }
+        }
+    }
+}
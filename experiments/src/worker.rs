@@ -1,33 +1,68 @@
+use crate::breakdown::TransactionTimer;
+use crate::results::Recorder;
 use crate::{Connection, Generator, Procedure};
+use clap::{Arg, ArgMatches};
+use dibs::clock::{Clock, SystemClock};
 use dibs::{Dibs, Transaction};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
 use std::marker::PhantomData;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, SyncSender};
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-struct State {
+pub(crate) struct State {
     group_counter: usize,
     transaction_counter: usize,
-    dibs: Option<Arc<Dibs>>,
+    pub(crate) dibs: Option<Arc<Dibs>>,
+    /// The clock that latencies are measured against, which is Dibs' if there is one.
+    clock: Arc<dyn Clock>,
+    /// The RNG that procedures, arrivals and backoffs are drawn from, which is seeded from entropy
+    /// unless the worker is given one.
+    pub(crate) rng: StdRng,
 }
 
 impl State {
-    fn new(worker_id: usize, dibs: Option<Arc<Dibs>>) -> State {
+    pub(crate) fn new(worker_id: usize, dibs: Option<Arc<Dibs>>) -> State {
         assert!(worker_id < 1024);
         let counter = worker_id * (usize::max_value() / 1024);
 
+        let clock = match &dibs {
+            Some(dibs) => Arc::clone(dibs.clock()),
+            None => Arc::new(SystemClock),
+        };
+
         State {
             group_counter: counter,
             transaction_counter: counter,
             dibs,
+            clock,
+            rng: StdRng::from_entropy(),
         }
     }
 
-    fn group_id(&mut self) -> usize {
+    pub(crate) fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    pub(crate) fn elapsed(&self, start: Instant) -> Duration {
+        self.clock.now().saturating_duration_since(start)
+    }
+
+    /// Returns the time left before the transaction's deadline, or `None` if it has none.
+    pub(crate) fn remaining(&self, transaction: &Transaction) -> Option<Duration> {
+        transaction.remaining_time(self.clock.now())
+    }
+
+    pub(crate) fn group_id(&mut self) -> usize {
         State::fetch_inc(&mut self.group_counter)
     }
 
-    fn transaction_id(&mut self) -> usize {
+    pub(crate) fn transaction_id(&mut self) -> usize {
         State::fetch_inc(&mut self.transaction_counter)
     }
 
@@ -67,9 +102,9 @@ where
 {
     type Item = G::Item;
 
-    fn next(&self) -> G::Item {
+    fn next(&self, rng: &mut StdRng) -> G::Item {
         loop {
-            let procedure = self.inner.next();
+            let procedure = self.inner.next(rng);
 
             if procedure.is_read_only() {
                 break procedure;
@@ -103,22 +138,269 @@ where
 {
     type Item = G::Item;
 
-    fn next(&self) -> G::Item {
+    fn next(&self, rng: &mut StdRng) -> G::Item {
         match self.receiver.try_recv() {
             Ok(procedure) => procedure,
-            Err(_) => self.inner.next(),
+            Err(_) => self.inner.next(rng),
+        }
+    }
+}
+
+/// When a worker issues its transactions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arrivals {
+    /// Issues each transaction as soon as the previous one commits.
+    ClosedLoop,
+
+    /// Transactions arrive as a Poisson process with `rate` arrivals per second and wait in a queue
+    /// of up to `queue_capacity` transactions, beyond which the oldest arrivals are dropped.
+    Poisson { rate: f64, queue_capacity: usize },
+
+    /// Like `Poisson`, but transactions arrive at a fixed interval of `1 / rate` seconds.
+    Fixed { rate: f64, queue_capacity: usize },
+}
+
+impl Arrivals {
+    /// Returns the `--arrival-rate`, `--arrival-process` and `--queue-capacity` flags.
+    pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![
+            Arg::with_name("arrival_rate")
+                .long("arrival-rate")
+                .takes_value(true)
+                .help("Runs open-loop with this many arrivals per second across all workers"),
+            Arg::with_name("arrival_process")
+                .long("arrival-process")
+                .possible_values(&["poisson", "fixed"])
+                .takes_value(true)
+                .requires("arrival_rate")
+                .help("Defaults to poisson"),
+            Arg::with_name("queue_capacity")
+                .long("queue-capacity")
+                .takes_value(true)
+                .requires("arrival_rate")
+                .help("Arrivals each worker queues before dropping the oldest, defaults to 1000"),
+        ]
+    }
+
+    /// Parses the flags from `args`, dividing the arrival rate evenly among `num_workers`.
+    pub fn from_matches(matches: &ArgMatches, num_workers: usize) -> Arrivals {
+        let rate = match matches.value_of("arrival_rate") {
+            Some(rate) => f64::from_str(rate).unwrap() / num_workers as f64,
+            None => return Arrivals::ClosedLoop,
+        };
+
+        assert!(rate > 0.0, "arrival rate must be positive");
+
+        let queue_capacity = matches
+            .value_of("queue_capacity")
+            .map_or(1000, |capacity| usize::from_str(capacity).unwrap());
+
+        match matches.value_of("arrival_process").unwrap_or("poisson") {
+            "poisson" => Arrivals::Poisson {
+                rate,
+                queue_capacity,
+            },
+            "fixed" => Arrivals::Fixed {
+                rate,
+                queue_capacity,
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns a parameter describing each worker's arrivals for `runner::run_with_parameters`.
+    pub fn parameter(&self) -> (&'static str, String) {
+        let value = match self {
+            Arrivals::ClosedLoop => "closed".to_string(),
+            Arrivals::Poisson { rate, .. } => format!("poisson:{}", rate),
+            Arrivals::Fixed { rate, .. } => format!("fixed:{}", rate),
+        };
+
+        ("arrivals_per_worker", value)
+    }
+}
+
+/// The arrivals that an open-loop worker has not yet started.
+struct ArrivalQueue {
+    arrivals: Arrivals,
+    capacity: usize,
+    next_arrival: Instant,
+    pending: VecDeque<Instant>,
+}
+
+impl ArrivalQueue {
+    fn new(arrivals: Arrivals) -> Option<ArrivalQueue> {
+        let capacity = match arrivals {
+            Arrivals::ClosedLoop => return None,
+            Arrivals::Poisson { queue_capacity, .. } | Arrivals::Fixed { queue_capacity, .. } => {
+                queue_capacity
+            }
+        };
+
+        Some(ArrivalQueue {
+            arrivals,
+            capacity: capacity.max(1),
+            next_arrival: Instant::now(),
+            pending: VecDeque::new(),
+        })
+    }
+
+    fn interval(&self, rng: &mut StdRng) -> Duration {
+        match self.arrivals {
+            Arrivals::ClosedLoop => unreachable!(),
+            Arrivals::Poisson { rate, .. } => {
+                let u = rng.gen::<f64>();
+                Duration::from_secs_f64(-(1.0 - u).ln() / rate)
+            }
+            Arrivals::Fixed { rate, .. } => Duration::from_secs_f64(1.0 / rate),
+        }
+    }
+
+    /// Waits for the next arrival and returns its arrival time, dropping the oldest arrivals if
+    /// more than the queue's capacity are pending.
+    fn next(&mut self, recorder: &Recorder, rng: &mut StdRng) -> Instant {
+        let now = Instant::now();
+
+        while self.next_arrival <= now {
+            self.pending.push_back(self.next_arrival);
+            self.next_arrival += self.interval(rng);
+
+            if self.pending.len() > self.capacity {
+                self.pending.pop_front();
+                recorder.drop_arrival();
+            }
+        }
+
+        match self.pending.pop_front() {
+            Some(arrival) => arrival,
+            None => {
+                thread::sleep(self.next_arrival - now);
+
+                let arrival = self.next_arrival;
+                self.next_arrival += self.interval(rng);
+                arrival
+            }
+        }
+    }
+}
+
+/// How a worker handles a transaction that fails to acquire its requests.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// The retries before giving up on the transaction, or `None` to retry until it commits.
+    pub max_retries: Option<usize>,
+
+    /// The backoff before the first retry, which doubles with each further retry up to
+    /// `max_backoff`. Each backoff is drawn uniformly between zero and its bound.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+
+    /// How long after it starts a transaction must commit by, or `None` for no limit. Its acquires
+    /// stop waiting at the deadline, and it isn't retried past it.
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    /// Retries immediately until the transaction commits.
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: None,
+            initial_backoff: Duration::from_secs(0),
+            max_backoff: Duration::from_secs(0),
+            deadline: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the `--max-retries`, `--backoff`, `--max-backoff` and `--deadline` flags.
+    pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+        vec![
+            Arg::with_name("max_retries")
+                .long("max-retries")
+                .takes_value(true)
+                .help("Retries before giving up on an aborted transaction, defaults to unlimited"),
+            Arg::with_name("backoff")
+                .long("backoff")
+                .takes_value(true)
+                .help("Microseconds to back off before the first retry, defaults to 0"),
+            Arg::with_name("max_backoff")
+                .long("max-backoff")
+                .takes_value(true)
+                .help("Microseconds that the doubling backoff is capped at, defaults to 100000"),
+            Arg::with_name("deadline")
+                .long("deadline")
+                .takes_value(true)
+                .help("Microseconds after its start that a transaction must commit by, defaults to none"),
+        ]
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> RetryPolicy {
+        let micros = |name| {
+            matches
+                .value_of(name)
+                .map(|value| Duration::from_micros(u64::from_str(value).unwrap()))
+        };
+
+        let initial_backoff = micros("backoff").unwrap_or_default();
+
+        RetryPolicy {
+            max_retries: matches
+                .value_of("max_retries")
+                .map(|max_retries| usize::from_str(max_retries).unwrap()),
+            initial_backoff,
+            max_backoff: micros("max_backoff")
+                .unwrap_or_else(|| Duration::from_millis(100))
+                .max(initial_backoff),
+            deadline: micros("deadline"),
         }
     }
+
+    /// Returns whether a transaction that has been retried `retries` times, with `remaining` left
+    /// before its deadline, may be retried again.
+    pub(crate) fn allows(&self, retries: usize, remaining: Option<Duration>) -> bool {
+        if remaining == Some(Duration::default()) {
+            return false;
+        }
+
+        match self.max_retries {
+            Some(max_retries) => retries < max_retries,
+            None => true,
+        }
+    }
+
+    /// Sets the deadline of a transaction that started at `start`, if the policy has one.
+    pub(crate) fn set_deadline(&self, transaction: &mut Transaction, start: Instant) {
+        if let Some(deadline) = self.deadline {
+            transaction.set_deadline(start + deadline);
+        }
+    }
+
+    /// Sleeps before the `retry`th retry, counting from one.
+    pub(crate) fn back_off(&self, retry: usize, rng: &mut StdRng) {
+        if self.initial_backoff == Duration::from_secs(0) {
+            return;
+        }
+
+        let bound = self
+            .initial_backoff
+            .checked_mul(1 << (retry - 1).min(31) as u32)
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff));
+
+        thread::sleep(bound.mul_f64(rng.gen::<f64>()));
+    }
 }
 
 pub trait Worker {
-    fn run(&mut self, commits: Arc<AtomicUsize>, terminate: Arc<AtomicBool>);
+    fn run(&mut self, recorder: Arc<Recorder>, terminate: Arc<AtomicBool>);
 }
 
 pub struct StandardWorker<G, C> {
     state: State,
     generator: G,
     connection: C,
+    arrivals: Arrivals,
+    retry_policy: RetryPolicy,
 }
 
 impl<G, C> StandardWorker<G, C> {
@@ -132,8 +414,25 @@ impl<G, C> StandardWorker<G, C> {
             state: State::new(worker_id, dibs),
             generator,
             connection,
+            arrivals: Arrivals::ClosedLoop,
+            retry_policy: RetryPolicy::default(),
         }
     }
+
+    pub fn with_arrivals(mut self, arrivals: Arrivals) -> StandardWorker<G, C> {
+        self.arrivals = arrivals;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> StandardWorker<G, C> {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_rng(mut self, rng: StdRng) -> StandardWorker<G, C> {
+        self.state.rng = rng;
+        self
+    }
 }
 
 impl<G, C> Worker for StandardWorker<G, C>
@@ -142,29 +441,67 @@ where
     G::Item: Procedure<C>,
     C: Connection,
 {
-    fn run(&mut self, commits: Arc<AtomicUsize>, terminate: Arc<AtomicBool>) {
+    fn run(&mut self, recorder: Arc<Recorder>, terminate: Arc<AtomicBool>) {
+        let mut queue = ArrivalQueue::new(self.arrivals);
+
         while !terminate.load(Ordering::Relaxed) {
-            let mut transaction =
-                Transaction::new(self.state.group_id(), self.state.transaction_id());
+            if let Some(queue) = &mut queue {
+                let arrival = queue.next(&recorder, &mut self.state.rng);
+                recorder.queued(arrival.elapsed());
+            }
 
-            let procedure = self.generator.next();
+            let procedure = self.generator.next(&mut self.state.rng);
+            let start = self.state.now();
+            let mut timer = TransactionTimer::start();
+            let mut retries = 0;
 
             self.connection.begin();
 
-            loop {
+            // An aborted attempt releases its requests and retries in a new transaction. The
+            // connection's transaction stays open across attempts, since not every connection can
+            // roll back.
+            let committed = loop {
+                let mut transaction =
+                    Transaction::new(self.state.group_id(), self.state.transaction_id());
+
+                if procedure.is_read_only() {
+                    transaction.set_read_only();
+                }
+
+                self.retry_policy.set_deadline(&mut transaction, start);
+
+                let attempt = self.state.now();
                 let result =
-                    procedure.execute(&self.state.dibs, &mut transaction, &mut self.connection);
+                    procedure.run(&self.state.dibs, &mut transaction, &mut self.connection);
+                timer.add(self.state.elapsed(attempt));
+
+                let remaining = self.state.remaining(&transaction);
+                transaction.commit();
 
                 if result.is_ok() {
-                    break;
+                    break true;
                 }
-            }
 
-            self.connection.commit();
+                recorder.abort();
+
+                if !self.retry_policy.allows(retries, remaining)
+                    || terminate.load(Ordering::Relaxed)
+                {
+                    break false;
+                }
 
-            transaction.commit();
+                retries += 1;
+                self.retry_policy.back_off(retries, &mut self.state.rng);
+            };
 
-            commits.fetch_add(1, Ordering::Relaxed);
+            self.connection.commit();
+
+            if committed {
+                recorder.commit(procedure.name(), self.state.elapsed(start), retries > 0);
+                timer.record(&recorder, procedure.name());
+            } else {
+                recorder.give_up();
+            }
         }
     }
 }
@@ -176,6 +513,7 @@ pub struct GroupCommitWorker<G, C> {
     generator: G,
     connection: C,
     num_transactions_per_group: usize,
+    retry_policy: RetryPolicy,
 }
 
 impl<G, C> GroupCommitWorker<G, C> {
@@ -191,8 +529,19 @@ impl<G, C> GroupCommitWorker<G, C> {
             generator,
             connection,
             num_transactions_per_group,
+            retry_policy: RetryPolicy::default(),
         }
     }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> GroupCommitWorker<G, C> {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_rng(mut self, rng: StdRng) -> GroupCommitWorker<G, C> {
+        self.state.rng = rng;
+        self
+    }
 }
 
 impl<G, C> Worker for GroupCommitWorker<G, C>
@@ -201,9 +550,13 @@ where
     G::Item: Procedure<C>,
     C: Connection,
 {
-    fn run(&mut self, commits: Arc<AtomicUsize>, terminate: Arc<AtomicBool>) {
+    fn run(&mut self, recorder: Arc<Recorder>, terminate: Arc<AtomicBool>) {
+        // An aborted procedure ends its group and is retried first in the next one.
+        let mut retry = None;
+
         while !terminate.load(Ordering::Relaxed) {
             let mut transactions = vec![];
+            let mut started = vec![];
 
             let group_id = self.state.group_id();
             let mut i = 0;
@@ -211,21 +564,32 @@ where
             self.connection.begin();
 
             while i < self.num_transactions_per_group {
-                transactions.push(Transaction::new(group_id, self.state.transaction_id()));
+                let mut transaction = Transaction::new(group_id, self.state.transaction_id());
+
+                let (procedure, start, retries) = retry.take().unwrap_or_else(|| {
+                    (
+                        self.generator.next(&mut self.state.rng),
+                        self.state.now(),
+                        0,
+                    )
+                });
 
-                let procedure = self.generator.next();
+                self.retry_policy.set_deadline(&mut transaction, start);
+                transactions.push(transaction);
 
                 self.connection.savepoint();
 
-                match procedure.execute(
+                match procedure.run(
                     &self.state.dibs,
                     transactions.last_mut().unwrap(),
                     &mut self.connection,
                 ) {
                     Ok(_) => {
+                        started.push((procedure.name(), start, retries > 0));
                         i += 1;
                     }
                     Err(_) => {
+                        let remaining = self.state.remaining(transactions.last().unwrap());
                         self.connection.rollback();
                         self.connection.commit();
 
@@ -233,7 +597,21 @@ where
                             transaction.commit();
                         }
 
-                        commits.fetch_add(i, Ordering::Relaxed);
+                        recorder.abort();
+
+                        for (name, start, retried) in started.drain(..) {
+                            recorder.commit(name, self.state.elapsed(start), retried);
+                        }
+
+                        if self.retry_policy.allows(retries, remaining)
+                            && !terminate.load(Ordering::Relaxed)
+                        {
+                            self.retry_policy.back_off(retries + 1, &mut self.state.rng);
+                            retry = Some((procedure, start, retries + 1));
+                        } else {
+                            recorder.give_up();
+                        }
+
                         i = 0;
 
                         self.connection.begin();
@@ -248,7 +626,9 @@ where
                 transaction.commit();
             }
 
-            commits.fetch_add(i, Ordering::Relaxed);
+            for (name, start, retried) in started.drain(..) {
+                recorder.commit(name, self.state.elapsed(start), retried);
+            }
         }
     }
 }
@@ -0,0 +1,555 @@
+//! Experiments described by a TOML file rather than by a benchmark module, for trying out request
+//! shapes without writing a generator and a database for them.
+//!
+//! A workload names its tables and their columns, gives each request template as a SQL condition
+//! over one table, and mixes transactions that run a sequence of templates with arguments drawn
+//! from key distributions. There is no database behind it: every statement holds its requests for
+//! `statement_time` and does nothing else, so the results measure Dibs alone.
+//!
+//! ```toml
+//! workers = 8
+//! measurement = 30
+//! optimization = "prepared"
+//! statement_time = 10 # microseconds
+//!
+//! [[tables]]
+//! name = "accounts"
+//! columns = ["id", "branch", "balance"]
+//! rows = 100000
+//! filter = "id"
+//!
+//! [[templates]]
+//! name = "get_balance"
+//! table = "accounts"
+//! reads = ["balance"]
+//! predicate = "id = ?1"
+//!
+//! [[templates]]
+//! name = "audit_branch"
+//! table = "accounts"
+//! reads = ["id", "balance"]
+//! predicate = "branch = ?1 AND id BETWEEN ?2 AND ?3"
+//!
+//! [[transactions]]
+//! name = "lookup"
+//! weight = 0.9
+//! statements = [
+//!     { template = "get_balance", arguments = [{ distribution = "zipfian", skew = 0.8 }] },
+//! ]
+//!
+//! [[transactions]]
+//! name = "audit"
+//! weight = 0.1
+//! statements = [
+//!     { template = "audit_branch", arguments = [
+//!         { distribution = "uniform", max = 10 },
+//!         { distribution = "uniform" },
+//!         { offset = 1, by = 100 },
+//!     ] },
+//! ]
+//! ```
+
+use crate::benchmarks::ycsb::KeyDistribution;
+use crate::runner::Phases;
+use crate::{Connection, Generator, Procedure};
+use dibs::predicate::{Predicate, Value};
+use dibs::sql::TableSchema;
+use dibs::{AcquireError, Dibs, OptimizationLevel, RequestTemplate, Transaction};
+use rand::distributions::Distribution;
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde::Deserialize;
+use std::str::FromStr;
+use std::sync::atomic::AtomicU32;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{error, fmt, fs, io};
+
+#[derive(Debug)]
+pub enum WorkloadError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    /// The file parsed but describes something that can't be run, such as a template over an
+    /// unknown table.
+    Invalid(String),
+}
+
+impl fmt::Display for WorkloadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WorkloadError::Io(e) => write!(f, "{}", e),
+            WorkloadError::Toml(e) => write!(f, "{}", e),
+            WorkloadError::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+impl error::Error for WorkloadError {}
+
+impl From<io::Error> for WorkloadError {
+    fn from(e: io::Error) -> WorkloadError {
+        WorkloadError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for WorkloadError {
+    fn from(e: toml::de::Error) -> WorkloadError {
+        WorkloadError::Toml(e)
+    }
+}
+
+fn invalid<T>(message: String) -> Result<T, WorkloadError> {
+    Err(WorkloadError::Invalid(message))
+}
+
+fn default_workers() -> usize {
+    1
+}
+
+fn default_optimization() -> String {
+    "prepared".to_string()
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Workload {
+    #[serde(default = "default_workers")]
+    pub workers: usize,
+    /// The phases in seconds, which default to those of `Phases::default`.
+    pub warmup: Option<f64>,
+    pub measurement: Option<f64>,
+    pub cooldown: Option<f64>,
+    /// One of the names that `OptimizationLevel::from_str` accepts.
+    #[serde(default = "default_optimization")]
+    pub optimization: String,
+    pub blowup_limit: Option<usize>,
+    /// Microseconds that each statement holds its requests for before the next one acquires.
+    #[serde(default)]
+    pub statement_time: f64,
+    pub tables: Vec<TableSpec>,
+    pub templates: Vec<TemplateSpec>,
+    pub transactions: Vec<TransactionSpec>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TableSpec {
+    pub name: String,
+    pub columns: Vec<String>,
+    /// The number of keys, which key arguments of statements on the table draw from by default.
+    pub rows: u32,
+    /// The column that Dibs partitions the table's requests on, if any.
+    pub filter: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateSpec {
+    pub name: String,
+    pub table: String,
+    #[serde(default)]
+    pub reads: Vec<String>,
+    #[serde(default)]
+    pub writes: Vec<String>,
+    /// A condition in the syntax of `Predicate::parse_sql`.
+    pub predicate: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransactionSpec {
+    pub name: String,
+    /// The transaction's share of the mix, relative to the other transactions' weights.
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    pub statements: Vec<StatementSpec>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatementSpec {
+    pub template: String,
+    #[serde(default)]
+    pub arguments: Vec<ArgumentSpec>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum ArgumentSpec {
+    /// A key drawn from `0..max` by a distribution that `KeyDistribution::from_name` accepts.
+    /// `max` defaults to the number of rows in the statement's table.
+    Key {
+        distribution: String,
+        max: Option<u32>,
+        #[serde(default)]
+        skew: f64,
+    },
+    Constant {
+        constant: usize,
+    },
+    /// An earlier argument of the same statement plus `by`, such as the upper end of a range.
+    Offset {
+        offset: usize,
+        by: usize,
+    },
+}
+
+impl Workload {
+    pub fn from_file(path: &str) -> Result<Workload, WorkloadError> {
+        Workload::from_str(&fs::read_to_string(path)?)
+    }
+
+    pub fn phases(&self) -> Phases {
+        let default = Phases::default();
+        let seconds = |value: Option<f64>, default| value.map_or(default, Duration::from_secs_f64);
+
+        Phases {
+            warmup: seconds(self.warmup, default.warmup),
+            measurement: seconds(self.measurement, default.measurement),
+            cooldown: seconds(self.cooldown, default.cooldown),
+        }
+    }
+
+    pub fn optimization(&self) -> Result<OptimizationLevel, WorkloadError> {
+        OptimizationLevel::from_str(&self.optimization)
+            .or_else(|()| invalid(format!("unknown optimization level {}", self.optimization)))
+    }
+
+    pub fn schemas(&self) -> Vec<TableSchema> {
+        self.tables
+            .iter()
+            .map(|table| TableSchema {
+                name: table.name.clone(),
+                columns: table.columns.clone(),
+            })
+            .collect()
+    }
+
+    fn table(&self, name: &str) -> Result<usize, WorkloadError> {
+        match self.tables.iter().position(|table| table.name == name) {
+            Some(table) => Ok(table),
+            None => invalid(format!("unknown table {}", name)),
+        }
+    }
+
+    fn template(&self, name: &str) -> Result<usize, WorkloadError> {
+        match self
+            .templates
+            .iter()
+            .position(|template| template.name == name)
+        {
+            Some(template) => Ok(template),
+            None => invalid(format!("unknown template {}", name)),
+        }
+    }
+
+    fn predicate(&self, template: &TemplateSpec) -> Result<Predicate, WorkloadError> {
+        let schema = &self.schemas()[self.table(&template.table)?];
+
+        Predicate::parse_sql(&template.predicate, schema)
+            .or_else(|e| invalid(format!("template {}: {}", template.name, e)))
+    }
+
+    /// Returns the request templates in the order they are listed, which is also their ids.
+    pub fn request_templates(&self) -> Result<Vec<RequestTemplate>, WorkloadError> {
+        let schemas = self.schemas();
+
+        self.templates
+            .iter()
+            .map(|template| {
+                let table = self.table(&template.table)?;
+                let schema = &schemas[table];
+
+                let columns = |names: &[String]| {
+                    names
+                        .iter()
+                        .map(|name| match schema.column(name) {
+                            Some(column) => Ok(column),
+                            None => invalid(format!(
+                                "template {} names unknown column {}",
+                                template.name, name
+                            )),
+                        })
+                        .collect::<Result<_, _>>()
+                };
+
+                Ok(RequestTemplate::new(
+                    table,
+                    columns(&template.reads)?,
+                    columns(&template.writes)?,
+                    self.predicate(template)?,
+                ))
+            })
+            .collect()
+    }
+
+    pub fn dibs(&self) -> Result<Dibs, WorkloadError> {
+        let schemas = self.schemas();
+
+        let filters = self
+            .tables
+            .iter()
+            .zip(&schemas)
+            .map(|(table, schema)| match &table.filter {
+                None => Ok(None),
+                Some(name) => match schema.column(name) {
+                    Some(column) => Ok(Some(column)),
+                    None => invalid(format!(
+                        "table {} filters on unknown column {}",
+                        table.name, name
+                    )),
+                },
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Dibs::new(
+            &filters,
+            &self.request_templates()?,
+            self.optimization()?,
+            None,
+            self.blowup_limit,
+            Duration::from_secs(60),
+        ))
+    }
+
+    /// Returns a generator of the transaction mix. Each worker should have its own.
+    pub fn generator(&self) -> Result<WorkloadGenerator, WorkloadError> {
+        let total_weight: f64 = self.transactions.iter().map(|t| t.weight).sum();
+
+        if self.transactions.is_empty() || total_weight <= 0.0 {
+            return invalid("the transaction mix is empty".to_string());
+        }
+
+        let mut cumulative_weight = 0.0;
+        let mut transactions = Vec::with_capacity(self.transactions.len());
+
+        for transaction in &self.transactions {
+            let mut read_only = true;
+            let mut statements = Vec::with_capacity(transaction.statements.len());
+
+            for statement in &transaction.statements {
+                let template_id = self.template(&statement.template)?;
+                let template = &self.templates[template_id];
+                let rows = self.tables[self.table(&template.table)?].rows;
+
+                read_only &= template.writes.is_empty();
+
+                let num_parameters = self
+                    .predicate(template)?
+                    .preorder()
+                    .filter_map(|predicate| match predicate {
+                        Predicate::Comparison(comparison) => Some(comparison.right + 1),
+                        Predicate::Connective(..) => None,
+                    })
+                    .max()
+                    .unwrap_or(0);
+
+                if statement.arguments.len() < num_parameters {
+                    return invalid(format!(
+                        "transaction {} gives template {} {} arguments, but it takes {}",
+                        transaction.name,
+                        statement.template,
+                        statement.arguments.len(),
+                        num_parameters
+                    ));
+                }
+
+                let arguments = statement
+                    .arguments
+                    .iter()
+                    .enumerate()
+                    .map(|(i, argument)| match argument {
+                        ArgumentSpec::Key {
+                            distribution,
+                            max,
+                            skew,
+                        } => {
+                            let max = max.unwrap_or(rows);
+
+                            // `latest` ranks keys by recency from the newest, which is `max` since
+                            // a workload never inserts.
+                            let newest = Arc::new(AtomicU32::new(max));
+
+                            match KeyDistribution::from_name(distribution, max, *skew, &newest) {
+                                Some(distribution) => Ok(Argument::Key(distribution)),
+                                None => invalid(format!(
+                                    "transaction {} has an invalid {} distribution",
+                                    transaction.name, distribution
+                                )),
+                            }
+                        }
+                        ArgumentSpec::Constant { constant } => Ok(Argument::Constant(*constant)),
+                        ArgumentSpec::Offset { offset, by } if *offset < i => {
+                            Ok(Argument::Offset(*offset, *by))
+                        }
+                        ArgumentSpec::Offset { .. } => invalid(format!(
+                            "transaction {} offsets an argument by a later one",
+                            transaction.name
+                        )),
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                statements.push((template_id, arguments));
+            }
+
+            cumulative_weight += transaction.weight / total_weight;
+
+            transactions.push(TransactionMix {
+                // Procedures report under static names, and a workload is loaded once per run.
+                name: Box::leak(transaction.name.clone().into_boxed_str()),
+                read_only,
+                threshold: cumulative_weight,
+                statements,
+            });
+        }
+
+        Ok(WorkloadGenerator {
+            transactions: Arc::new(transactions),
+        })
+    }
+
+    pub fn connection(&self) -> WorkloadConnection {
+        WorkloadConnection {
+            statement_time: Duration::from_secs_f64(self.statement_time / 1_000_000.0),
+        }
+    }
+}
+
+impl FromStr for Workload {
+    type Err = WorkloadError;
+
+    fn from_str(s: &str) -> Result<Workload, WorkloadError> {
+        let workload: Workload = toml::from_str(s)?;
+
+        if workload.workers == 0 {
+            return invalid("a workload needs at least one worker".to_string());
+        }
+
+        Ok(workload)
+    }
+}
+
+enum Argument {
+    Key(KeyDistribution),
+    Constant(usize),
+    Offset(usize, usize),
+}
+
+struct TransactionMix {
+    name: &'static str,
+    read_only: bool,
+    /// The cumulative weight up to and including this transaction, as a fraction of the total.
+    threshold: f64,
+    statements: Vec<(usize, Vec<Argument>)>,
+}
+
+#[derive(Clone)]
+pub struct WorkloadGenerator {
+    transactions: Arc<Vec<TransactionMix>>,
+}
+
+impl Generator for WorkloadGenerator {
+    type Item = WorkloadProcedure;
+
+    fn next(&self, rng: &mut StdRng) -> WorkloadProcedure {
+        let transaction_type = rng.gen::<f64>();
+        let transaction = self
+            .transactions
+            .iter()
+            .find(|transaction| transaction_type < transaction.threshold)
+            .unwrap_or_else(|| self.transactions.last().unwrap());
+
+        let statements = transaction
+            .statements
+            .iter()
+            .map(|(template_id, arguments)| {
+                let mut values: Vec<usize> = Vec::with_capacity(arguments.len());
+
+                for argument in arguments {
+                    let value = match argument {
+                        Argument::Key(distribution) => distribution.sample(rng) - 1,
+                        Argument::Constant(constant) => *constant,
+                        Argument::Offset(offset, by) => values[*offset] + by,
+                    };
+
+                    values.push(value);
+                }
+
+                (
+                    *template_id,
+                    values.into_iter().map(Value::Integer).collect(),
+                )
+            })
+            .collect();
+
+        WorkloadProcedure {
+            name: transaction.name,
+            read_only: transaction.read_only,
+            statements,
+        }
+    }
+}
+
+pub struct WorkloadProcedure {
+    name: &'static str,
+    read_only: bool,
+    statements: Vec<(usize, Vec<Value>)>,
+}
+
+impl Procedure<WorkloadConnection> for WorkloadProcedure {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn execute(
+        &self,
+        dibs: &Option<Arc<Dibs>>,
+        transaction: &mut Transaction,
+        connection: &mut WorkloadConnection,
+    ) -> Result<(), AcquireError> {
+        for (template_id, arguments) in &self.statements {
+            if let Some(d) = dibs {
+                d.acquire(transaction, *template_id, arguments.clone())?;
+            }
+
+            connection.execute();
+        }
+
+        Ok(())
+    }
+}
+
+/// Stands in for a database by spinning for the statement time, so that requests are held for
+/// about as long as a real statement would hold them.
+#[derive(Clone, Copy)]
+pub struct WorkloadConnection {
+    statement_time: Duration,
+}
+
+impl WorkloadConnection {
+    fn execute(&self) {
+        let start = Instant::now();
+
+        while start.elapsed() < self.statement_time {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+impl Connection for WorkloadConnection {
+    fn begin(&mut self) {}
+
+    fn commit(&mut self) {}
+
+    fn rollback(&mut self) {}
+
+    fn savepoint(&mut self) {}
+}